@@ -0,0 +1,87 @@
+/// Headlamp dashboard addon, reachable through `oxide dashboard-ui` only
+use anyhow::Result;
+use std::path::Path;
+use tracing::info;
+
+use crate::bundle::Bundle;
+use crate::config::{DashboardConfig, HelmMode};
+use crate::utils::helm;
+
+pub const NAMESPACE: &str = "headlamp";
+
+/// Service of the dashboard, as `kubectl port-forward` takes it
+pub const SERVICE: &str = "svc/headlamp";
+
+pub const SERVICE_PORT: u16 = 80;
+
+/// ServiceAccount whose tokens sign in to the dashboard
+pub const SERVICE_ACCOUNT: &str = "dashboard-viewer";
+
+/// ClusterRole of [`SERVICE_ACCOUNT`]; `view` excludes Secrets
+pub const ROLE: &str = "view";
+
+/// Install Headlamp via Helm and wait for it to roll out
+pub async fn install(
+    kubeconfig_path: &Path,
+    config: &DashboardConfig,
+    helm_mode: HelmMode,
+    rollout_timeout_secs: u64,
+    bundle: Option<&Bundle>,
+) -> Result<()> {
+    info!("Installing the Headlamp dashboard {}...", config.version);
+
+    let mut chart = chart(config);
+    if let Some(bundle) = bundle {
+        chart = bundle.local_chart(chart)?;
+    }
+    helm::add_chart_repo(kubeconfig_path, &chart).await?;
+    helm::upgrade_install(kubeconfig_path, helm_mode, &chart).await?;
+    helm::wait_for_release(
+        kubeconfig_path,
+        helm_mode,
+        "headlamp",
+        NAMESPACE,
+        rollout_timeout_secs,
+    )
+    .await?;
+
+    info!("Headlamp installed successfully, open it with oxide dashboard-ui");
+    Ok(())
+}
+
+/// The Headlamp chart
+pub fn chart(config: &DashboardConfig) -> helm::Chart {
+    helm::Chart {
+        release: "headlamp".to_string(),
+        repo_name: "headlamp".to_string(),
+        repo_url: "https://kubernetes-sigs.github.io/headlamp/".to_string(),
+        name: "headlamp".to_string(),
+        version: config.version.clone(),
+        namespace: NAMESPACE.to_string(),
+        values: vec![
+            "service.type=ClusterIP".to_string(),
+            format!("service.port={}", SERVICE_PORT),
+            "ingress.enabled=false".to_string(),
+            "clusterRoleBinding.create=false".to_string(),
+        ],
+        privileged: false,
+        archive: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chart() {
+        let config = DashboardConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        let values = helm::values_document(&chart(&config).values).unwrap();
+        assert_eq!(values["service"]["type"], "ClusterIP");
+        assert_eq!(values["ingress"]["enabled"], false);
+        assert_eq!(values["clusterRoleBinding"]["create"], false);
+    }
+}
@@ -0,0 +1,58 @@
+/// Descheduler addon, evicting pods the scheduler would now place elsewhere
+use anyhow::Result;
+use std::path::Path;
+use tracing::info;
+
+use crate::bundle::Bundle;
+use crate::config::{DeschedulerConfig, HelmMode};
+use crate::utils::helm;
+
+const NAMESPACE: &str = "kube-system";
+
+/// Install the descheduler via Helm and wait for it to roll out
+pub async fn install(
+    kubeconfig_path: &Path,
+    config: &DeschedulerConfig,
+    helm_mode: HelmMode,
+    rollout_timeout_secs: u64,
+    bundle: Option<&Bundle>,
+) -> Result<()> {
+    info!("Installing the descheduler {}...", config.version);
+
+    let mut chart = chart(config);
+    if let Some(bundle) = bundle {
+        chart = bundle.local_chart(chart)?;
+    }
+    helm::add_chart_repo(kubeconfig_path, &chart).await?;
+    helm::upgrade_install(kubeconfig_path, helm_mode, &chart).await?;
+    helm::wait_for_release(
+        kubeconfig_path,
+        helm_mode,
+        "descheduler",
+        NAMESPACE,
+        rollout_timeout_secs,
+    )
+    .await?;
+
+    info!("Descheduler installed successfully");
+    Ok(())
+}
+
+/// The descheduler chart, as a Deployment rather than the default CronJob,
+/// so its rollout can be waited for
+pub fn chart(config: &DeschedulerConfig) -> helm::Chart {
+    helm::Chart {
+        release: "descheduler".to_string(),
+        repo_name: "descheduler".to_string(),
+        repo_url: "https://kubernetes-sigs.github.io/descheduler/".to_string(),
+        name: "descheduler".to_string(),
+        version: config.version.clone(),
+        namespace: NAMESPACE.to_string(),
+        values: vec![
+            "kind=Deployment".to_string(),
+            format!("deschedulingInterval={}", config.interval),
+        ],
+        privileged: false,
+        archive: None,
+    }
+}
@@ -0,0 +1,278 @@
+/// ingress-nginx addon, for Ingress annotations the Cilium Gateway lacks
+use anyhow::{Context, Result};
+use serde_json::json;
+use std::path::Path;
+use tracing::info;
+
+use crate::bundle::Bundle;
+use crate::config::{HelmMode, IngressNginxConfig, IngressService};
+use crate::exit::Failure;
+use crate::k8s::ResourceManager;
+use crate::utils::helm;
+
+const NAMESPACE: &str = "ingress-nginx";
+
+const CCM_NAMESPACE: &str = "kube-system";
+
+/// Secret the cloud-controller-manager reads its token and settings from
+const CCM_SECRET: &str = "hcloud";
+
+const CCM_VERSION: &str = "1.24.0";
+
+/// Machine config patch leaving node initialization to the
+/// cloud-controller-manager
+///
+/// Kubelets run with `--cloud-provider=external`, so the CCM sets the
+/// provider IDs by which it finds the servers behind the load balancer.
+pub const MACHINE_CONFIG_PATCH: &str = r#"cluster:
+  externalCloudProvider:
+    enabled: true
+"#;
+
+/// Where the cloud-controller-manager creates the load balancer
+#[derive(Debug, Clone)]
+pub struct CloudLoadBalancer {
+    /// Name of the load balancer, see [`load_balancer_name`]
+    pub name: String,
+    pub location: String,
+    /// Private network the load balancer forwards over
+    pub network: String,
+}
+
+/// Name of the load balancer of the controller Service
+pub fn load_balancer_name(cluster_name: &str) -> String {
+    format!("{}-ingress-nginx", cluster_name)
+}
+
+/// Install the cloud-controller-manager with `service: load_balancer`
+///
+/// The CCM initializes the nodes tainted by the external cloud provider, so
+/// it is installed ahead of the other addons.
+pub async fn install_ccm(
+    kubeconfig_path: &Path,
+    load_balancer: &CloudLoadBalancer,
+    hcloud_token: Option<&str>,
+    helm_mode: HelmMode,
+    rollout_timeout_secs: u64,
+    bundle: Option<&Bundle>,
+) -> Result<()> {
+    info!(
+        "Installing the hcloud cloud-controller-manager {}...",
+        CCM_VERSION
+    );
+
+    let token = hcloud_token.context(Failure::config(
+        "The hcloud cloud-controller-manager needs the Hetzner Cloud API token",
+    ))?;
+    let secret = json!({
+        "apiVersion": "v1",
+        "kind": "Secret",
+        "metadata": {
+            "name": CCM_SECRET,
+            "namespace": CCM_NAMESPACE,
+            "labels": { "app.kubernetes.io/managed-by": "oxide" },
+        },
+        "stringData": ccm_secret_data(token, load_balancer),
+    });
+    ResourceManager::apply_stdin(kubeconfig_path, &serde_yaml::to_string(&secret)?).await?;
+
+    let mut chart = ccm_chart(load_balancer);
+    if let Some(bundle) = bundle {
+        chart = bundle.local_chart(chart)?;
+    }
+    helm::add_chart_repo(kubeconfig_path, &chart).await?;
+    helm::upgrade_install(kubeconfig_path, helm_mode, &chart).await?;
+    helm::wait_for_release(
+        kubeconfig_path,
+        helm_mode,
+        "hccm",
+        CCM_NAMESPACE,
+        rollout_timeout_secs,
+    )
+    .await?;
+
+    info!("hcloud cloud-controller-manager installed successfully");
+    Ok(())
+}
+
+/// Install ingress-nginx via Helm and wait for the controller to roll out
+pub async fn install(
+    kubeconfig_path: &Path,
+    config: &IngressNginxConfig,
+    load_balancer: Option<&CloudLoadBalancer>,
+    helm_mode: HelmMode,
+    rollout_timeout_secs: u64,
+    bundle: Option<&Bundle>,
+) -> Result<()> {
+    info!("Installing ingress-nginx {}...", config.version);
+
+    let mut chart = chart(config, load_balancer);
+    if let Some(bundle) = bundle {
+        chart = bundle.local_chart(chart)?;
+    }
+    helm::add_chart_repo(kubeconfig_path, &chart).await?;
+    helm::upgrade_install(kubeconfig_path, helm_mode, &chart).await?;
+    helm::wait_for_release(
+        kubeconfig_path,
+        helm_mode,
+        "ingress-nginx",
+        NAMESPACE,
+        rollout_timeout_secs,
+    )
+    .await?;
+
+    info!("ingress-nginx installed successfully");
+    Ok(())
+}
+
+/// Keys of the cloud-controller-manager Secret
+///
+/// Helm `--set` turns `true` and `false` into booleans, which environment
+/// variables cannot hold, so the flags come from the Secret as well.
+fn ccm_secret_data(token: &str, load_balancer: &CloudLoadBalancer) -> serde_json::Value {
+    json!({
+        "token": token,
+        "network": load_balancer.network,
+        "load-balancers-use-private-ip": "true",
+        "network-routes-enabled": "false",
+    })
+}
+
+/// The hcloud cloud-controller-manager chart
+///
+/// Cilium routes the pod traffic, so the CCM only manages load balancers
+/// and node metadata, not the routes of the network.
+pub fn ccm_chart(load_balancer: &CloudLoadBalancer) -> helm::Chart {
+    let mut values = vec![format!(
+        "env.HCLOUD_LOAD_BALANCERS_LOCATION.value={}",
+        load_balancer.location
+    )];
+    for (variable, key) in [
+        ("HCLOUD_NETWORK", "network"),
+        (
+            "HCLOUD_LOAD_BALANCERS_USE_PRIVATE_IP",
+            "load-balancers-use-private-ip",
+        ),
+        ("HCLOUD_NETWORK_ROUTES_ENABLED", "network-routes-enabled"),
+    ] {
+        values.push(format!(
+            "env.{}.valueFrom.secretKeyRef.name={}",
+            variable, CCM_SECRET
+        ));
+        values.push(format!(
+            "env.{}.valueFrom.secretKeyRef.key={}",
+            variable, key
+        ));
+    }
+    helm::Chart {
+        release: "hccm".to_string(),
+        repo_name: "hcloud".to_string(),
+        repo_url: "https://charts.hetzner.cloud".to_string(),
+        name: "hcloud-cloud-controller-manager".to_string(),
+        version: CCM_VERSION.to_string(),
+        namespace: CCM_NAMESPACE.to_string(),
+        values,
+        privileged: false,
+        archive: None,
+    }
+}
+
+/// The ingress-nginx chart
+///
+/// With node IPAM the Service asks for the `io.cilium/node` class
+/// explicitly, so it keeps the node IPs even if the cloud-controller-manager
+/// takes over Services without a class.
+pub fn chart(
+    config: &IngressNginxConfig,
+    load_balancer: Option<&CloudLoadBalancer>,
+) -> helm::Chart {
+    let mut values = vec![
+        format!("controller.replicaCount={}", config.replicas),
+        format!(
+            "controller.ingressClassResource.default={}",
+            config.default_class
+        ),
+    ];
+    match (config.service, load_balancer) {
+        (IngressService::LoadBalancer, Some(load_balancer)) => values.extend([
+            format!(
+                "controller.service.annotations.load-balancer\\.hetzner\\.cloud/name={}",
+                load_balancer.name
+            ),
+            format!(
+                "controller.service.annotations.load-balancer\\.hetzner\\.cloud/type={}",
+                config.load_balancer_type
+            ),
+        ]),
+        _ => values.extend([
+            "controller.service.loadBalancerClass=io.cilium/node".to_string(),
+            format!("controller.service.ports.http={}", config.http_port),
+            format!("controller.service.ports.https={}", config.https_port),
+        ]),
+    }
+    helm::Chart {
+        release: "ingress-nginx".to_string(),
+        repo_name: "ingress-nginx".to_string(),
+        repo_url: "https://kubernetes.github.io/ingress-nginx".to_string(),
+        name: "ingress-nginx".to_string(),
+        version: config.version.clone(),
+        namespace: NAMESPACE.to_string(),
+        values,
+        privileged: false,
+        archive: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_balancer() -> CloudLoadBalancer {
+        CloudLoadBalancer {
+            name: load_balancer_name("prod"),
+            location: "fsn1".to_string(),
+            network: "prod-network".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_chart() {
+        let mut config = IngressNginxConfig {
+            enabled: true,
+            http_port: 8080,
+            https_port: 8443,
+            ..Default::default()
+        };
+        let values = helm::values_document(&chart(&config, None).values).unwrap();
+        let service = &values["controller"]["service"];
+        assert_eq!(service["loadBalancerClass"], "io.cilium/node");
+        assert_eq!(service["ports"]["https"], 8443);
+
+        config.service = IngressService::LoadBalancer;
+        let values = helm::values_document(&chart(&config, Some(&load_balancer())).values).unwrap();
+        let annotations = &values["controller"]["service"]["annotations"];
+        assert_eq!(
+            annotations["load-balancer.hetzner.cloud/name"],
+            "prod-ingress-nginx"
+        );
+        assert_eq!(annotations["load-balancer.hetzner.cloud/type"], "lb11");
+        assert!(values["controller"]["service"]["loadBalancerClass"].is_null());
+    }
+
+    #[test]
+    fn test_ccm_chart() {
+        let values = helm::values_document(&ccm_chart(&load_balancer()).values).unwrap();
+        let env = &values["env"];
+        assert_eq!(env["HCLOUD_LOAD_BALANCERS_LOCATION"]["value"], "fsn1");
+
+        // Every flag comes from a Secret key the Secret has
+        let data = ccm_secret_data("token", &load_balancer());
+        for variable in ["HCLOUD_NETWORK", "HCLOUD_NETWORK_ROUTES_ENABLED"] {
+            let key = env[variable]["valueFrom"]["secretKeyRef"]["key"]
+                .as_str()
+                .unwrap();
+            assert!(data[key].is_string(), "{}", key);
+        }
+        assert_eq!(data["network"], "prod-network");
+    }
+}
@@ -0,0 +1,165 @@
+/// Istio service mesh addon, in ambient mode
+use anyhow::Result;
+use serde_json::json;
+use std::path::Path;
+use tracing::info;
+
+use crate::bundle::Bundle;
+use crate::config::{HelmMode, IstioConfig};
+use crate::k8s::ResourceManager;
+use crate::utils::helm;
+
+const NAMESPACE: &str = "istio-system";
+
+/// Namespace label enrolling the pods of a namespace in the ambient mesh
+const DATAPLANE_MODE_LABEL: &str = "istio.io/dataplane-mode";
+
+/// Link-local address ztunnel SNATs the kubelet health probes to
+///
+/// Probes of meshed pods arrive from this address instead of the node, so
+/// the Cilium default-deny policy has to admit it.
+const PROBE_SOURCE_CIDR: &str = "169.254.7.127/32";
+
+/// Install the Istio charts in dependency order and enroll the ambient
+/// namespaces
+pub async fn install(
+    kubeconfig_path: &Path,
+    config: &IstioConfig,
+    helm_mode: HelmMode,
+    rollout_timeout_secs: u64,
+    bundle: Option<&Bundle>,
+) -> Result<()> {
+    info!("Installing Istio {} (ambient)...", config.version);
+
+    // The CNI node agent and ztunnel need host access
+    ResourceManager::ensure_privileged_namespace(kubeconfig_path, NAMESPACE).await?;
+
+    // The CRDs from base before istiod, and istiod before the node
+    // components, which fetch their certificates from it
+    for mut chart in charts(config) {
+        if let Some(bundle) = bundle {
+            chart = bundle.local_chart(chart)?;
+        }
+        helm::add_chart_repo(kubeconfig_path, &chart).await?;
+        helm::upgrade_install(kubeconfig_path, helm_mode, &chart).await?;
+        helm::wait_for_release(
+            kubeconfig_path,
+            helm_mode,
+            &chart.release,
+            NAMESPACE,
+            rollout_timeout_secs,
+        )
+        .await?;
+    }
+
+    // Server-side apply only adds the label, leaving the other labels of
+    // namespaces that already exist alone
+    ResourceManager::apply_server_side(kubeconfig_path, NAMESPACE, &manifest(config)).await?;
+
+    info!("Istio installed successfully");
+    Ok(())
+}
+
+/// The Istio charts, in install order
+pub fn charts(config: &IstioConfig) -> Vec<helm::Chart> {
+    let chart = |release: &str, name: &str, values: Vec<String>| helm::Chart {
+        release: release.to_string(),
+        repo_name: "istio".to_string(),
+        repo_url: "https://istio-release.storage.googleapis.com/charts".to_string(),
+        name: name.to_string(),
+        version: config.version.clone(),
+        namespace: NAMESPACE.to_string(),
+        values,
+        privileged: true,
+        archive: None,
+    };
+    let ambient = || vec!["profile=ambient".to_string()];
+    vec![
+        chart("istio-base", "base", Vec::new()),
+        chart("istiod", "istiod", ambient()),
+        chart("istio-cni", "cni", ambient()),
+        chart("ztunnel", "ztunnel", ambient()),
+    ]
+}
+
+/// Policy admitting the SNAT-ed kubelet probes, and the ambient namespaces
+/// labeled for the mesh
+///
+/// The policy does not enable default deny for the pods it selects, so it
+/// only matters when `cilium.default_deny` is on.
+pub fn manifest(config: &IstioConfig) -> String {
+    let mut objects = vec![json!({
+        "apiVersion": "cilium.io/v2",
+        "kind": "CiliumClusterwideNetworkPolicy",
+        "metadata": {
+            "name": "oxide-allow-ambient-probes",
+            "labels": { "app.kubernetes.io/managed-by": "oxide" },
+        },
+        "spec": {
+            "description": "Allow kubelet health probes SNAT-ed by ztunnel into ambient pods",
+            "enableDefaultDeny": { "ingress": false, "egress": false },
+            "endpointSelector": {},
+            "ingress": [{ "fromCIDR": [PROBE_SOURCE_CIDR] }],
+        },
+    })];
+    for namespace in &config.ambient_namespaces {
+        objects.push(json!({
+            "apiVersion": "v1",
+            "kind": "Namespace",
+            "metadata": {
+                "name": namespace,
+                "labels": { DATAPLANE_MODE_LABEL: "ambient" },
+            },
+        }));
+    }
+    objects
+        .iter()
+        .map(|object| serde_yaml::to_string(object).expect("Istio object serializes"))
+        .collect::<Vec<_>>()
+        .join("---\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_charts() {
+        let config = IstioConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        let charts = charts(&config);
+        let releases: Vec<&str> = charts.iter().map(|c| c.release.as_str()).collect();
+        assert_eq!(releases, ["istio-base", "istiod", "istio-cni", "ztunnel"]);
+        for chart in &charts[1..] {
+            let values = helm::values_document(&chart.values).unwrap();
+            assert_eq!(values["profile"], "ambient", "{}", chart.release);
+            assert_eq!(chart.version, config.version);
+        }
+    }
+
+    #[test]
+    fn test_manifest() {
+        let config = IstioConfig {
+            enabled: true,
+            ambient_namespaces: vec!["shop".to_string()],
+            ..Default::default()
+        };
+        let documents: Vec<serde_yaml::Value> = manifest(&config)
+            .split("---\n")
+            .map(|doc| serde_yaml::from_str(doc).unwrap())
+            .collect();
+        assert_eq!(documents.len(), 2);
+        assert_eq!(
+            documents[0]["spec"]["ingress"][0]["fromCIDR"][0],
+            PROBE_SOURCE_CIDR
+        );
+        assert_eq!(documents[0]["spec"]["enableDefaultDeny"]["ingress"], false);
+        assert_eq!(documents[1]["metadata"]["name"], "shop");
+        assert_eq!(
+            documents[1]["metadata"]["labels"][DATAPLANE_MODE_LABEL],
+            "ambient"
+        );
+    }
+}
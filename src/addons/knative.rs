@@ -0,0 +1,209 @@
+/// Knative Serving addon, routed through the Cilium Gateway
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::path::Path;
+use tracing::info;
+
+use crate::config::{DnsConfig, KnativeConfig};
+use crate::k8s::ResourceManager;
+
+/// Name of the manifest holding the Gateways and Knative settings
+pub const MANIFEST_NAME: &str = "knative";
+
+pub const NAMESPACE: &str = "knative-serving";
+
+/// Ingress class of the net-gateway-api networking layer
+const INGRESS_CLASS: &str = "gateway-api.ingress.networking.knative.dev";
+
+const EXTERNAL_GATEWAY: &str = "knative-external";
+
+/// Gateway of cluster-local routes, on a port the cluster firewall keeps
+/// closed
+const LOCAL_GATEWAY: &str = "knative-local";
+
+const LOCAL_GATEWAY_PORT: u16 = 8081;
+
+/// Deployments of Serving and the networking layer
+const DEPLOYMENTS: &[&str] = &[
+    "activator",
+    "autoscaler",
+    "controller",
+    "webhook",
+    "net-gateway-api-controller",
+    "net-gateway-api-webhook",
+];
+
+/// Release manifests of Serving and the networking layer, in apply order
+pub fn release_manifests(config: &KnativeConfig) -> [String; 3] {
+    let serving = format!(
+        "https://github.com/knative/serving/releases/download/knative-v{}",
+        config.version
+    );
+    [
+        format!("{}/serving-crds.yaml", serving),
+        format!("{}/serving-core.yaml", serving),
+        format!(
+            "https://github.com/knative-extensions/net-gateway-api/releases/download/knative-v{}/net-gateway-api.yaml",
+            config.version
+        ),
+    ]
+}
+
+/// Install Knative Serving and wait for it and its Gateways to be ready
+pub async fn install(
+    kubeconfig_path: &Path,
+    config: &KnativeConfig,
+    dns: Option<&DnsConfig>,
+    rollout_timeout_secs: u64,
+) -> Result<()> {
+    info!("Installing Knative Serving {}...", config.version);
+
+    for url in release_manifests(config) {
+        ResourceManager::apply_remote(kubeconfig_path, &url).await?;
+    }
+    for deployment in DEPLOYMENTS {
+        ResourceManager::wait_for_rollout(
+            kubeconfig_path,
+            NAMESPACE,
+            &format!("deployment/{}", deployment),
+            rollout_timeout_secs,
+        )
+        .await?;
+    }
+
+    // Not a managed manifest: pruning would delete ConfigMaps of Serving
+    let manifest = manifest(dns);
+    ResourceManager::apply_server_side(kubeconfig_path, NAMESPACE, &manifest).await?;
+    ResourceManager::wait_for_manifest(kubeconfig_path, NAMESPACE, &manifest, rollout_timeout_secs)
+        .await?;
+
+    info!("Knative Serving installed successfully");
+    Ok(())
+}
+
+/// The Gateways, and the settings pointing Knative at them and the domain
+///
+/// The ConfigMaps come with Serving; server-side apply only sets the keys
+/// listed here.
+pub fn manifest(dns: Option<&DnsConfig>) -> String {
+    let mut external_listener = json!({
+        "name": "http",
+        "protocol": "HTTP",
+        "port": 80,
+        "allowedRoutes": { "namespaces": { "from": "All" } },
+    });
+    if let Some(dns) = dns {
+        external_listener["hostname"] = format!("*.{}", dns.domain).into();
+    }
+    let local_listener = json!({
+        "name": "http",
+        "protocol": "HTTP",
+        "port": LOCAL_GATEWAY_PORT,
+        "allowedRoutes": { "namespaces": { "from": "All" } },
+    });
+
+    let mut objects = vec![
+        gateway(EXTERNAL_GATEWAY, external_listener),
+        gateway(LOCAL_GATEWAY, local_listener),
+        config_map("config-network", json!({ "ingress-class": INGRESS_CLASS })),
+        config_map(
+            "config-gateway",
+            json!({
+                "external-gateways": gateway_setting(EXTERNAL_GATEWAY),
+                "local-gateways": gateway_setting(LOCAL_GATEWAY),
+            }),
+        ),
+    ];
+    if let Some(dns) = dns {
+        objects.push(config_map("config-domain", json!({ &dns.domain: "" })));
+    }
+    objects
+        .iter()
+        .map(|object| serde_yaml::to_string(object).expect("Knative object serializes"))
+        .collect::<Vec<_>>()
+        .join("---\n")
+}
+
+fn gateway(name: &str, listener: Value) -> Value {
+    json!({
+        "apiVersion": "gateway.networking.k8s.io/v1",
+        "kind": "Gateway",
+        "metadata": { "name": name, "namespace": NAMESPACE },
+        "spec": {
+            "gatewayClassName": "cilium",
+            "listeners": [listener],
+        },
+    })
+}
+
+/// Entry of `config-gateway`; Cilium names the Service of a Gateway
+/// `cilium-gateway-<name>`
+fn gateway_setting(name: &str) -> String {
+    format!(
+        "- class: cilium\n  gateway: {namespace}/{name}\n  service: {namespace}/cilium-gateway-{name}\n",
+        namespace = NAMESPACE,
+        name = name
+    )
+}
+
+fn config_map(name: &str, data: Value) -> Value {
+    json!({
+        "apiVersion": "v1",
+        "kind": "ConfigMap",
+        "metadata": { "name": name, "namespace": NAMESPACE },
+        "data": data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(manifest: &str) -> Vec<serde_yaml::Value> {
+        manifest
+            .split("---\n")
+            .map(|doc| serde_yaml::from_str(doc).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_manifest() {
+        let dns = DnsConfig {
+            domain: "apps.example.com".to_string(),
+        };
+        let documents = parse(&manifest(Some(&dns)));
+        assert_eq!(documents.len(), 5);
+
+        let listener = &documents[0]["spec"]["listeners"][0];
+        assert_eq!(listener["hostname"], "*.apps.example.com");
+        assert_eq!(listener["port"], 80);
+        assert_eq!(
+            documents[1]["spec"]["listeners"][0]["port"],
+            LOCAL_GATEWAY_PORT
+        );
+        assert_eq!(documents[2]["data"]["ingress-class"], INGRESS_CLASS);
+
+        let external: serde_yaml::Value =
+            serde_yaml::from_str(documents[3]["data"]["external-gateways"].as_str().unwrap())
+                .unwrap();
+        assert_eq!(
+            external[0]["service"],
+            "knative-serving/cilium-gateway-knative-external"
+        );
+        assert_eq!(documents[4]["data"]["apps.example.com"], "");
+
+        // Without a domain, Knative keeps its default
+        let documents = parse(&manifest(None));
+        assert_eq!(documents.len(), 4);
+        assert!(documents[0]["spec"]["listeners"][0]["hostname"].is_null());
+    }
+
+    #[test]
+    fn test_release_manifests() {
+        let config = KnativeConfig::default();
+        let [crds, core, networking] = release_manifests(&config);
+        assert!(crds.ends_with("/knative-v1.17.0/serving-crds.yaml"));
+        assert!(core.ends_with("/serving-core.yaml"));
+        assert!(networking.contains("/net-gateway-api/"));
+    }
+}
@@ -0,0 +1,219 @@
+/// KubeVirt addon, for virtual machines next to the containers
+use anyhow::{Context, Result};
+use serde_json::json;
+use std::path::Path;
+use tracing::info;
+
+use crate::config::KubevirtConfig;
+use crate::exit::Failure;
+use crate::k8s::ResourceManager;
+use crate::utils::command::CommandBuilder;
+use crate::utils::polling::PollingConfig;
+
+/// Name of the managed manifest holding the `KubeVirt` and `CDI` resources
+pub const MANIFEST_NAME: &str = "kubevirt";
+
+pub const NAMESPACE: &str = "kubevirt";
+
+pub const CDI_NAMESPACE: &str = "cdi";
+
+/// Node resource virt-handler advertises on nodes with `/dev/kvm`
+const KVM_RESOURCE: &str = "devices.kubevirt.io/kvm";
+
+/// Machine config patch loading the vfio modules
+///
+/// KVM is built into the Talos kernel; the vfio modules let virt-handler
+/// pass host PCI devices through to VMs.
+pub const MACHINE_CONFIG_PATCH: &str = r#"machine:
+  kernel:
+    modules:
+      - name: vfio_pci
+      - name: vfio_iommu_type1
+"#;
+
+/// Operator manifests of the configured KubeVirt and CDI releases
+pub fn operator_manifests(config: &KubevirtConfig) -> [String; 2] {
+    [
+        format!(
+            "https://github.com/kubevirt/kubevirt/releases/download/{}/kubevirt-operator.yaml",
+            config.version
+        ),
+        format!(
+            "https://github.com/kubevirt/containerized-data-importer/releases/download/{}/cdi-operator.yaml",
+            config.cdi_version
+        ),
+    ]
+}
+
+/// Install the operators, then KubeVirt and CDI, and check that VMs have
+/// somewhere to run
+pub async fn install(
+    kubeconfig_path: &Path,
+    config: &KubevirtConfig,
+    rollout_timeout_secs: u64,
+) -> Result<()> {
+    info!(
+        "Installing KubeVirt {} and CDI {}...",
+        config.version, config.cdi_version
+    );
+
+    // virt-handler needs host access on every node
+    ResourceManager::ensure_privileged_namespace(kubeconfig_path, NAMESPACE).await?;
+
+    for url in operator_manifests(config) {
+        ResourceManager::apply_remote(kubeconfig_path, &url).await?;
+    }
+    ResourceManager::wait_for_rollout(
+        kubeconfig_path,
+        NAMESPACE,
+        "deployment/virt-operator",
+        rollout_timeout_secs,
+    )
+    .await?;
+    ResourceManager::wait_for_rollout(
+        kubeconfig_path,
+        CDI_NAMESPACE,
+        "deployment/cdi-operator",
+        rollout_timeout_secs,
+    )
+    .await?;
+
+    ResourceManager::apply_managed(kubeconfig_path, NAMESPACE, MANIFEST_NAME, &manifest(config))
+        .await?;
+    for (resource, namespace) in [
+        ("kubevirt.kubevirt.io/kubevirt", Some(NAMESPACE)),
+        ("cdi.cdi.kubevirt.io/cdi", None),
+    ] {
+        wait_available(kubeconfig_path, resource, namespace, rollout_timeout_secs).await?;
+    }
+
+    if !config.software_emulation {
+        wait_for_kvm_nodes(kubeconfig_path, rollout_timeout_secs).await?;
+    }
+
+    info!("KubeVirt installed successfully");
+    Ok(())
+}
+
+/// The `KubeVirt` and `CDI` resources
+pub fn manifest(config: &KubevirtConfig) -> String {
+    let kubevirt = json!({
+        "apiVersion": "kubevirt.io/v1",
+        "kind": "KubeVirt",
+        "metadata": { "name": "kubevirt", "namespace": NAMESPACE },
+        "spec": {
+            "configuration": {
+                "developerConfiguration": {
+                    "useEmulation": config.software_emulation,
+                },
+            },
+        },
+    });
+    // Importers wait for the consumer of a volume, so a WaitForFirstConsumer
+    // class like Longhorn's binds it on the node the VM lands on
+    let cdi = json!({
+        "apiVersion": "cdi.kubevirt.io/v1beta1",
+        "kind": "CDI",
+        "metadata": { "name": "cdi" },
+        "spec": {
+            "config": { "featureGates": ["HonorWaitForFirstConsumer"] },
+        },
+    });
+    [kubevirt, cdi]
+        .iter()
+        .map(|object| serde_yaml::to_string(object).expect("KubeVirt object serializes"))
+        .collect::<Vec<_>>()
+        .join("---\n")
+}
+
+async fn wait_available(
+    kubeconfig_path: &Path,
+    resource: &str,
+    namespace: Option<&str>,
+    timeout_secs: u64,
+) -> Result<()> {
+    info!("Waiting for {} to become available...", resource);
+    let timeout = format!("--timeout={}s", timeout_secs);
+    let mut args = vec!["wait", resource, "--for=condition=Available", &timeout];
+    if let Some(namespace) = namespace {
+        args.extend(["-n", namespace]);
+    }
+    CommandBuilder::new("kubectl")
+        .args(args)
+        .kubeconfig(kubeconfig_path)
+        .context(format!("Failed to wait for {}", resource))
+        .run_silent()
+        .await
+        .context(Failure::timeout(format!(
+            "{} did not become available",
+            resource
+        )))?;
+    info!("✓ {} available", resource);
+    Ok(())
+}
+
+/// Wait until virt-handler reports `/dev/kvm` on at least one node
+async fn wait_for_kvm_nodes(kubeconfig_path: &Path, timeout_secs: u64) -> Result<()> {
+    let jsonpath = format!(
+        "jsonpath={{.items[*].status.allocatable.{}}}",
+        KVM_RESOURCE.replace('.', "\\.")
+    );
+    PollingConfig::new(timeout_secs, 5, "Waiting for nodes with /dev/kvm")
+        .poll_until(|| async {
+            let output = CommandBuilder::new("kubectl")
+                .args(["get", "nodes", "-o", &jsonpath])
+                .kubeconfig(kubeconfig_path)
+                .context("Failed to list nodes")
+                .run()
+                .await?;
+            Ok(kvm_nodes(&output) > 0)
+        })
+        .await
+        .context(Failure::config(
+            "No node exposes /dev/kvm, so VMs cannot start; add Robot servers with \
+             virtualization enabled, or set addons.kubevirt.software_emulation",
+        ))
+}
+
+/// Number of nodes with allocatable KVM devices (a quantity like `1k`), as
+/// listed by `kubectl get nodes -o jsonpath`
+fn kvm_nodes(output: &str) -> usize {
+    output
+        .split_whitespace()
+        .filter(|quantity| *quantity != "0")
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest() {
+        let mut config = KubevirtConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        let [kubevirt, cdi] = operator_manifests(&config);
+        assert!(kubevirt.contains(&format!("/{}/kubevirt-operator.yaml", config.version)));
+        assert!(cdi.contains(&format!("/{}/cdi-operator.yaml", config.cdi_version)));
+
+        config.software_emulation = true;
+        let documents: Vec<serde_yaml::Value> = manifest(&config)
+            .split("---\n")
+            .map(|doc| serde_yaml::from_str(doc).unwrap())
+            .collect();
+        assert_eq!(
+            documents[0]["spec"]["configuration"]["developerConfiguration"]["useEmulation"],
+            true
+        );
+        assert_eq!(documents[1]["kind"], "CDI");
+    }
+
+    #[test]
+    fn test_kvm_nodes() {
+        assert_eq!(kvm_nodes(""), 0);
+        assert_eq!(kvm_nodes("0 0"), 0);
+        assert_eq!(kvm_nodes("1k 0 110"), 2);
+    }
+}
@@ -0,0 +1,187 @@
+/// Longhorn distributed block storage addon
+use anyhow::{Context, Result};
+use std::path::Path;
+use tracing::info;
+
+use crate::bundle::Bundle;
+use crate::config::{HelmMode, LonghornConfig};
+use crate::exit::Failure;
+use crate::hcloud::server::{ServerInfo, ServerManager};
+use crate::k8s::ResourceManager;
+use crate::talos::extensions::NodeExtensions;
+use crate::talos::TalosClient;
+use crate::utils::helm;
+
+const NAMESPACE: &str = "longhorn-system";
+
+/// Talos system extensions Longhorn needs on every storage node
+///
+/// `iscsi-tools` provides iscsid for volume attachment and `util-linux-tools`
+/// provides `fstrim`/`nsenter` used by longhorn-manager.
+pub const REQUIRED_EXTENSIONS: &[&str] = &["siderolabs/iscsi-tools", "siderolabs/util-linux-tools"];
+
+/// Machine config patch exposing /var/lib/longhorn to the kubelet
+///
+/// Talos runs the kubelet in a container, so the data path has to be
+/// bind-mounted with rshared propagation or Longhorn volumes never become
+/// visible to pods.
+pub const MACHINE_CONFIG_PATCH: &str = r#"machine:
+  kubelet:
+    extraMounts:
+      - destination: /var/lib/longhorn
+        type: bind
+        source: /var/lib/longhorn
+        options:
+          - bind
+          - rshared
+          - rw
+"#;
+
+/// Fail unless every node runs the extensions Longhorn needs
+///
+/// Extensions come with the image a node booted from, the snapshot of
+/// `talos.hcloud_snapshot_id`; the install image oxide pins only takes effect
+/// on the next upgrade. Without them Longhorn installs fine but its volumes
+/// never attach.
+pub async fn check_extensions(talos: &TalosClient, nodes: &[ServerInfo]) -> Result<()> {
+    let mut problems = Vec::new();
+    for node in nodes {
+        let ip = ServerManager::talos_ip(&node.server)
+            .context(format!("Server {} has no IP", node.server.name))?;
+        let missing = missing_extensions(&talos.extensions(&ip).await?);
+        if !missing.is_empty() {
+            problems.push(format!("{} ({})", node.server.name, missing.join(", ")));
+        }
+    }
+    if problems.is_empty() {
+        return Ok(());
+    }
+    anyhow::bail!(Failure::config(format!(
+        "Longhorn needs the Talos extensions {} on every node, but these nodes lack them: {}. \
+         Build the talos.hcloud_snapshot_id snapshot from the Image Factory disk image logged \
+         above, which includes them, and recreate the cluster",
+        REQUIRED_EXTENSIONS.join(", "),
+        problems.join(", ")
+    )))
+}
+
+/// Required extensions a node does not run, by official name
+fn missing_extensions(node: &NodeExtensions) -> Vec<&'static str> {
+    REQUIRED_EXTENSIONS
+        .iter()
+        .copied()
+        .filter(|required| {
+            let short = required.rsplit('/').next().unwrap_or(required);
+            !node
+                .extensions
+                .iter()
+                .any(|installed| installed.name == short)
+        })
+        .collect()
+}
+
+/// Install Longhorn via Helm and wait for the manager to roll out
+pub async fn install(
+    kubeconfig_path: &Path,
+    config: &LonghornConfig,
+    helm_mode: HelmMode,
+    schedulable_nodes: u32,
+    rollout_timeout_secs: u64,
+    bundle: Option<&Bundle>,
+) -> Result<()> {
+    info!("Installing Longhorn {}...", config.version);
+
+    // Talos enforces the baseline Pod Security Standard cluster-wide, but the
+    // Longhorn manager needs privileged host access
+    ResourceManager::ensure_privileged_namespace(kubeconfig_path, NAMESPACE).await?;
+
+    let mut chart = chart(config, schedulable_nodes);
+    if let Some(bundle) = bundle {
+        chart = bundle.local_chart(chart)?;
+    }
+    helm::add_chart_repo(kubeconfig_path, &chart).await?;
+
+    let replicas = effective_replica_count(config.replica_count, schedulable_nodes);
+    if replicas != config.replica_count {
+        info!(
+            "Reducing Longhorn replica count from {} to {} to match schedulable nodes",
+            config.replica_count, replicas
+        );
+    }
+
+    helm::upgrade_install(kubeconfig_path, helm_mode, &chart).await?;
+
+    helm::wait_for_release(
+        kubeconfig_path,
+        helm_mode,
+        "longhorn",
+        NAMESPACE,
+        rollout_timeout_secs,
+    )
+    .await?;
+
+    info!("Longhorn installed successfully");
+    Ok(())
+}
+
+/// The Longhorn chart with the replica count clamped to the schedulable nodes
+pub fn chart(config: &LonghornConfig, schedulable_nodes: u32) -> helm::Chart {
+    let replicas = effective_replica_count(config.replica_count, schedulable_nodes);
+    helm::Chart {
+        release: "longhorn".to_string(),
+        repo_name: "longhorn".to_string(),
+        repo_url: "https://charts.longhorn.io".to_string(),
+        name: "longhorn".to_string(),
+        version: config.version.clone(),
+        namespace: NAMESPACE.to_string(),
+        values: vec![
+            format!("persistence.defaultClassReplicaCount={}", replicas),
+            format!("defaultSettings.defaultReplicaCount={}", replicas),
+        ],
+        privileged: true,
+        archive: None,
+    }
+}
+
+/// Clamp the replica count so volumes can actually be scheduled
+fn effective_replica_count(requested: u32, schedulable_nodes: u32) -> u32 {
+    requested.min(schedulable_nodes.max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::talos::extensions::InstalledExtension;
+
+    #[test]
+    fn test_effective_replica_count() {
+        assert_eq!(effective_replica_count(3, 5), 3);
+        assert_eq!(effective_replica_count(3, 2), 2);
+        assert_eq!(effective_replica_count(3, 0), 1);
+    }
+
+    #[test]
+    fn test_machine_config_patch_is_valid_yaml() {
+        let patch: serde_yaml::Value = serde_yaml::from_str(MACHINE_CONFIG_PATCH).unwrap();
+        let mount = &patch["machine"]["kubelet"]["extraMounts"][0];
+        assert_eq!(mount["destination"].as_str(), Some("/var/lib/longhorn"));
+    }
+
+    #[test]
+    fn test_missing_extensions() {
+        let mut node = NodeExtensions::default();
+        assert_eq!(missing_extensions(&node), REQUIRED_EXTENSIONS);
+
+        node.extensions.push(InstalledExtension {
+            name: "iscsi-tools".to_string(),
+            version: "v0.1.6".to_string(),
+        });
+        assert_eq!(missing_extensions(&node), ["siderolabs/util-linux-tools"]);
+
+        node.extensions.push(InstalledExtension {
+            name: "util-linux-tools".to_string(),
+            version: "2.40.4".to_string(),
+        });
+        assert!(missing_extensions(&node).is_empty());
+    }
+}
@@ -0,0 +1,60 @@
+/// metrics-server addon, serving the resource metrics API
+use anyhow::Result;
+use std::path::Path;
+use tracing::info;
+
+use crate::bundle::Bundle;
+use crate::config::{HelmMode, MetricsServerConfig};
+use crate::utils::helm;
+
+const NAMESPACE: &str = "kube-system";
+
+/// Install metrics-server via Helm and wait for it to roll out
+pub async fn install(
+    kubeconfig_path: &Path,
+    config: &MetricsServerConfig,
+    helm_mode: HelmMode,
+    rollout_timeout_secs: u64,
+    bundle: Option<&Bundle>,
+) -> Result<()> {
+    info!("Installing metrics-server {}...", config.version);
+
+    let mut chart = chart(config);
+    if let Some(bundle) = bundle {
+        chart = bundle.local_chart(chart)?;
+    }
+    helm::add_chart_repo(kubeconfig_path, &chart).await?;
+    helm::upgrade_install(kubeconfig_path, helm_mode, &chart).await?;
+    helm::wait_for_release(
+        kubeconfig_path,
+        helm_mode,
+        "metrics-server",
+        NAMESPACE,
+        rollout_timeout_secs,
+    )
+    .await?;
+
+    info!("metrics-server installed successfully");
+    Ok(())
+}
+
+/// The metrics-server chart
+///
+/// Talos kubelets serve a self-signed certificate, so metrics-server skips
+/// its verification and scrapes them on their private addresses.
+pub fn chart(config: &MetricsServerConfig) -> helm::Chart {
+    helm::Chart {
+        release: "metrics-server".to_string(),
+        repo_name: "metrics-server".to_string(),
+        repo_url: "https://kubernetes-sigs.github.io/metrics-server/".to_string(),
+        name: "metrics-server".to_string(),
+        version: config.version.clone(),
+        namespace: NAMESPACE.to_string(),
+        values: vec![
+            "args={--kubelet-insecure-tls,--kubelet-preferred-address-types=InternalIP}"
+                .to_string(),
+        ],
+        privileged: false,
+        archive: None,
+    }
+}
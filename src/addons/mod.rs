@@ -0,0 +1,588 @@
+/// Optional cluster addons and their Talos prerequisites
+pub mod dashboard;
+pub mod descheduler;
+pub mod ingress_nginx;
+pub mod istio;
+pub mod knative;
+pub mod kubevirt;
+pub mod longhorn;
+pub mod metrics_server;
+pub mod node_problem_detector;
+pub mod nvidia;
+pub mod policy;
+pub mod prepull;
+pub mod spegel;
+pub mod status;
+pub mod vpa;
+
+use anyhow::Result;
+
+use crate::bundle::Bundle;
+use crate::cilium::CiliumManager;
+use crate::config::{AddonsConfig, ClusterConfig, DnsConfig, HelmMode, IngressService, NodeConfig};
+use crate::hcloud::network::cluster_network_names;
+use crate::utils::helm::Chart;
+
+/// Edges of the addon dependency graph, by config key: the first addon needs
+/// the second, which is installed along with it even if not enabled
+const DEPENDENCIES: &[(&str, &str)] = &[("vpa", "metrics_server")];
+
+/// Enabled flag of an addon in [`DEPENDENCIES`], `None` for other names
+fn enabled_flag<'a>(config: &'a mut AddonsConfig, addon: &str) -> Option<&'a mut bool> {
+    match addon {
+        "metrics_server" => Some(&mut config.metrics_server.enabled),
+        "vpa" => Some(&mut config.vpa.enabled),
+        _ => None,
+    }
+}
+
+/// `config` with the dependencies of every enabled addon enabled as well
+pub fn resolve_dependencies(config: &AddonsConfig) -> AddonsConfig {
+    let mut resolved = config.clone();
+    // Repeat until nothing changes, so dependencies of dependencies count
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (addon, dependency) in DEPENDENCIES {
+            if !enabled_flag(&mut resolved, addon).is_some_and(|enabled| *enabled) {
+                continue;
+            }
+            if let Some(enabled) = enabled_flag(&mut resolved, dependency) {
+                if !*enabled {
+                    *enabled = true;
+                    changed = true;
+                }
+            }
+        }
+    }
+    resolved
+}
+
+/// Addon deployment manager
+pub struct AddonManager {
+    config: AddonsConfig,
+    kubeconfig_path: std::path::PathBuf,
+    helm_mode: HelmMode,
+    schedulable_nodes: u32,
+    has_gpu_pools: bool,
+    /// Whether `talos.registry_mirror` is configured
+    has_registry_mirror: bool,
+    rollout_timeout: u64,
+    /// Chart whose images the pre-pull addon pulls
+    cilium_chart: Chart,
+    /// Load balancer of ingress-nginx with `service: load_balancer`
+    ingress_load_balancer: Option<ingress_nginx::CloudLoadBalancer>,
+    hcloud_token: Option<String>,
+    /// Domain Knative names its routes under
+    dns: Option<DnsConfig>,
+    bundle: Option<Bundle>,
+}
+
+impl AddonManager {
+    /// Create a new addon manager for a cluster
+    pub fn new(cluster: &ClusterConfig, kubeconfig_path: std::path::PathBuf) -> Self {
+        // Workloads only land on workers unless there are none
+        let worker_count: u32 = cluster.workers.iter().map(|w| w.count).sum();
+        let schedulable_nodes = if worker_count > 0 {
+            worker_count
+        } else {
+            cluster.control_planes.iter().map(|cp| cp.count).sum()
+        };
+
+        let control_plane_count = cluster.control_planes.iter().map(|cp| cp.count).sum();
+        let cilium_chart = CiliumManager::new(
+            cluster.cilium.clone(),
+            kubeconfig_path.clone(),
+            control_plane_count,
+        )
+        .with_kubespan(cluster.multi_region())
+        .with_cloud_load_balancers(cluster.cloud_load_balancers())
+        .with_istio(cluster.addons.istio.enabled)
+        .chart();
+
+        Self {
+            config: resolve_dependencies(&cluster.addons),
+            kubeconfig_path,
+            helm_mode: cluster.helm.mode,
+            schedulable_nodes,
+            has_gpu_pools: cluster.workers.iter().any(|w| w.gpu),
+            has_registry_mirror: cluster.talos.registry_mirror.is_some(),
+            rollout_timeout: cluster.timeouts.addon_rollout,
+            cilium_chart,
+            ingress_load_balancer: cluster.cloud_load_balancers().then(|| {
+                ingress_nginx::CloudLoadBalancer {
+                    name: ingress_nginx::load_balancer_name(&cluster.cluster_name),
+                    location: cluster.hcloud.location.clone(),
+                    network: cluster_network_names(&cluster.cluster_name, &cluster.hcloud)
+                        .remove(0),
+                }
+            }),
+            hcloud_token: None,
+            dns: cluster.dns.clone(),
+            bundle: None,
+        }
+    }
+
+    /// Hetzner Cloud API token for the cloud-controller-manager
+    pub fn with_hcloud_token(mut self, token: String) -> Self {
+        self.hcloud_token = Some(token);
+        self
+    }
+
+    /// Install the charts from an offline bundle
+    pub fn with_bundle(mut self, bundle: Option<Bundle>) -> Self {
+        self.bundle = bundle;
+        self
+    }
+
+    /// Machine config patches required by the enabled addons
+    ///
+    /// These must be applied when the Talos configs are generated, before any
+    /// server boots, since addons like Longhorn cannot work without them.
+    pub fn machine_config_patches(config: &AddonsConfig, talos_version: &str) -> Vec<String> {
+        let mut patches = Vec::new();
+
+        if config.longhorn.enabled {
+            patches.push(longhorn::MACHINE_CONFIG_PATCH.to_string());
+        }
+        if config.spegel.enabled {
+            patches.push(spegel::machine_config_patch(talos_version));
+        }
+        if config.ingress_nginx.enabled
+            && config.ingress_nginx.service == IngressService::LoadBalancer
+        {
+            patches.push(ingress_nginx::MACHINE_CONFIG_PATCH.to_string());
+        }
+        if config.kubevirt.enabled {
+            patches.push(kubevirt::MACHINE_CONFIG_PATCH.to_string());
+        }
+
+        patches
+    }
+
+    /// Talos system extensions required by the enabled addons
+    pub fn required_extensions(config: &AddonsConfig) -> Vec<String> {
+        let mut extensions = Vec::new();
+
+        if config.longhorn.enabled {
+            extensions.extend(longhorn::REQUIRED_EXTENSIONS.iter().map(|e| e.to_string()));
+        }
+
+        extensions
+    }
+
+    /// Additional machine config patches for a dedicated node pool
+    ///
+    /// Returns an empty list for pools that can share the base worker config.
+    pub fn pool_machine_config_patches(pool: &NodeConfig) -> Vec<String> {
+        let mut patches = Vec::new();
+
+        if pool.gpu {
+            patches.push(nvidia::MACHINE_CONFIG_PATCH.to_string());
+        }
+
+        patches
+    }
+
+    /// Full extension set for a dedicated node pool
+    ///
+    /// Includes the cluster-wide addon extensions, since the pool's installer
+    /// image replaces the base one rather than adding to it.
+    pub fn pool_required_extensions(config: &AddonsConfig, pool: &NodeConfig) -> Vec<String> {
+        let mut extensions = Self::required_extensions(config);
+
+        if pool.gpu {
+            extensions.extend(nvidia::REQUIRED_EXTENSIONS.iter().map(|e| e.to_string()));
+        }
+
+        extensions
+    }
+
+    /// Charts of the enabled addons, with the values they are installed with
+    pub fn charts(&self) -> Vec<Chart> {
+        let mut charts = Vec::new();
+        if let Some(load_balancer) = &self.ingress_load_balancer {
+            charts.push(ingress_nginx::ccm_chart(load_balancer));
+        }
+        if self.config.spegel.enabled {
+            charts.push(spegel::chart(&self.config.spegel, self.has_registry_mirror));
+        }
+        if self.config.longhorn.enabled {
+            charts.push(longhorn::chart(
+                &self.config.longhorn,
+                self.schedulable_nodes,
+            ));
+        }
+        if self.has_gpu_pools {
+            charts.push(nvidia::chart(&self.config.nvidia_device_plugin));
+        }
+        if self.config.metrics_server.enabled {
+            charts.push(metrics_server::chart(&self.config.metrics_server));
+        }
+        if self.config.vpa.enabled {
+            charts.push(vpa::chart(&self.config.vpa));
+        }
+        if self.config.node_problem_detector.enabled {
+            charts.push(node_problem_detector::chart(
+                &self.config.node_problem_detector,
+            ));
+        }
+        if self.config.descheduler.enabled {
+            charts.push(descheduler::chart(&self.config.descheduler));
+        }
+        if self.config.dashboard.enabled {
+            charts.push(dashboard::chart(&self.config.dashboard));
+        }
+        if self.config.istio.enabled {
+            charts.extend(istio::charts(&self.config.istio));
+        }
+        if self.config.ingress_nginx.enabled {
+            charts.push(ingress_nginx::chart(
+                &self.config.ingress_nginx,
+                self.ingress_load_balancer.as_ref(),
+            ));
+        }
+        if self.config.policy_engine.enabled {
+            charts.push(policy::chart(&self.config.policy_engine));
+        }
+        charts
+    }
+
+    /// Manifests the enabled addons apply besides their charts, by name
+    pub fn manifests(&self) -> Vec<(&'static str, String)> {
+        let mut manifests = Vec::new();
+        if self.has_gpu_pools {
+            manifests.push((
+                "nvidia-runtime-class",
+                nvidia::RUNTIME_CLASS_MANIFEST.to_string(),
+            ));
+        }
+        if self.config.kubevirt.enabled {
+            // The operators come from their release manifests, not a chart
+            let [kubevirt_operator, cdi_operator] =
+                kubevirt::operator_manifests(&self.config.kubevirt);
+            manifests.push((
+                kubevirt::MANIFEST_NAME,
+                format!(
+                    "# Apply after the operators of {} and {}\n{}",
+                    kubevirt_operator,
+                    cdi_operator,
+                    kubevirt::manifest(&self.config.kubevirt)
+                ),
+            ));
+        }
+        if self.config.knative.enabled {
+            let [crds, core, networking] = knative::release_manifests(&self.config.knative);
+            manifests.push((
+                knative::MANIFEST_NAME,
+                format!(
+                    "# Apply after {}, {} and {}\n{}",
+                    crds,
+                    core,
+                    networking,
+                    knative::manifest(self.dns.as_ref())
+                ),
+            ));
+        }
+        if self.config.istio.enabled {
+            manifests.push(("istio-ambient", istio::manifest(&self.config.istio)));
+        }
+        if self.config.policy_engine.enabled {
+            manifests.push((
+                policy::MANIFEST_NAME,
+                policy::render_manifest(&self.config.policy_engine, &self.policy_exempt()),
+            ));
+        }
+        manifests
+    }
+
+    /// Namespaces of the other enabled addons, which the policy pack leaves
+    /// alone
+    fn policy_exempt(&self) -> Vec<String> {
+        let engine = policy::chart(&self.config.policy_engine).release;
+        let mut namespaces: Vec<String> = self
+            .charts()
+            .into_iter()
+            .filter(|chart| chart.release != engine)
+            .map(|chart| chart.namespace)
+            .collect();
+        if self.config.kubevirt.enabled {
+            namespaces.extend([kubevirt::NAMESPACE, kubevirt::CDI_NAMESPACE].map(String::from));
+        }
+        if self.config.knative.enabled {
+            namespaces.push(knative::NAMESPACE.to_string());
+        }
+        namespaces
+    }
+
+    /// Pull the images of the pre-pull addon onto every node, if enabled
+    pub async fn prepull_images(&self) -> Result<()> {
+        if !self.config.image_prepull.enabled {
+            return Ok(());
+        }
+        let cilium = match &self.bundle {
+            Some(bundle) => bundle.local_chart(self.cilium_chart.clone())?,
+            None => self.cilium_chart.clone(),
+        };
+        prepull::install(
+            &self.kubeconfig_path,
+            &self.config.image_prepull,
+            &cilium,
+            self.rollout_timeout,
+        )
+        .await
+    }
+
+    /// Install all enabled addons
+    pub async fn install(&self) -> Result<()> {
+        // Nodes stay tainted as uninitialized until the CCM runs
+        if let Some(load_balancer) = &self.ingress_load_balancer {
+            ingress_nginx::install_ccm(
+                &self.kubeconfig_path,
+                load_balancer,
+                self.hcloud_token.as_deref(),
+                self.helm_mode,
+                self.rollout_timeout,
+                self.bundle.as_ref(),
+            )
+            .await?;
+        }
+
+        // Spegel first, so the images of the other addons are shared too
+        if self.config.spegel.enabled {
+            spegel::install(
+                &self.kubeconfig_path,
+                &self.config.spegel,
+                self.helm_mode,
+                self.has_registry_mirror,
+                self.rollout_timeout,
+                self.bundle.as_ref(),
+            )
+            .await?;
+        }
+
+        self.prepull_images().await?;
+
+        if self.config.longhorn.enabled {
+            longhorn::install(
+                &self.kubeconfig_path,
+                &self.config.longhorn,
+                self.helm_mode,
+                self.schedulable_nodes,
+                self.rollout_timeout,
+                self.bundle.as_ref(),
+            )
+            .await?;
+        }
+
+        if self.has_gpu_pools {
+            nvidia::install(
+                &self.kubeconfig_path,
+                &self.config.nvidia_device_plugin,
+                self.helm_mode,
+                self.rollout_timeout,
+                self.bundle.as_ref(),
+            )
+            .await?;
+        }
+
+        // Dependencies first: the VPA recommender reads the metrics API
+        if self.config.metrics_server.enabled {
+            metrics_server::install(
+                &self.kubeconfig_path,
+                &self.config.metrics_server,
+                self.helm_mode,
+                self.rollout_timeout,
+                self.bundle.as_ref(),
+            )
+            .await?;
+        }
+
+        if self.config.vpa.enabled {
+            vpa::install(
+                &self.kubeconfig_path,
+                &self.config.vpa,
+                self.helm_mode,
+                self.rollout_timeout,
+                self.bundle.as_ref(),
+            )
+            .await?;
+        }
+
+        if self.config.node_problem_detector.enabled {
+            node_problem_detector::install(
+                &self.kubeconfig_path,
+                &self.config.node_problem_detector,
+                self.helm_mode,
+                self.rollout_timeout,
+                self.bundle.as_ref(),
+            )
+            .await?;
+        }
+
+        if self.config.descheduler.enabled {
+            descheduler::install(
+                &self.kubeconfig_path,
+                &self.config.descheduler,
+                self.helm_mode,
+                self.rollout_timeout,
+                self.bundle.as_ref(),
+            )
+            .await?;
+        }
+
+        if self.config.dashboard.enabled {
+            dashboard::install(
+                &self.kubeconfig_path,
+                &self.config.dashboard,
+                self.helm_mode,
+                self.rollout_timeout,
+                self.bundle.as_ref(),
+            )
+            .await?;
+        }
+
+        if self.config.kubevirt.enabled {
+            kubevirt::install(
+                &self.kubeconfig_path,
+                &self.config.kubevirt,
+                self.rollout_timeout,
+            )
+            .await?;
+        }
+
+        // Cilium is ready by now, so the Istio CNI plugin has a config to
+        // chain onto
+        if self.config.istio.enabled {
+            istio::install(
+                &self.kubeconfig_path,
+                &self.config.istio,
+                self.helm_mode,
+                self.rollout_timeout,
+                self.bundle.as_ref(),
+            )
+            .await?;
+        }
+
+        if self.config.knative.enabled {
+            knative::install(
+                &self.kubeconfig_path,
+                &self.config.knative,
+                self.dns.as_ref(),
+                self.rollout_timeout,
+            )
+            .await?;
+        }
+
+        if self.config.ingress_nginx.enabled {
+            ingress_nginx::install(
+                &self.kubeconfig_path,
+                &self.config.ingress_nginx,
+                self.ingress_load_balancer.as_ref(),
+                self.helm_mode,
+                self.rollout_timeout,
+                self.bundle.as_ref(),
+            )
+            .await?;
+        }
+
+        // Last, so the pack cannot block the pods of the other addons
+        if self.config.policy_engine.enabled {
+            policy::install(
+                &self.kubeconfig_path,
+                &self.config.policy_engine,
+                self.helm_mode,
+                &self.policy_exempt(),
+                self.rollout_timeout,
+                self.bundle.as_ref(),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prerequisites_follow_enabled_addons() {
+        let mut config = AddonsConfig::default();
+        assert!(AddonManager::machine_config_patches(&config, "v1.11.2").is_empty());
+        assert!(AddonManager::required_extensions(&config).is_empty());
+
+        config.longhorn.enabled = true;
+        assert_eq!(
+            AddonManager::machine_config_patches(&config, "v1.11.2").len(),
+            1
+        );
+        assert!(AddonManager::required_extensions(&config)
+            .contains(&"siderolabs/iscsi-tools".to_string()));
+    }
+
+    #[test]
+    fn test_spegel_prerequisites() {
+        let mut config = AddonsConfig::default();
+        config.spegel.enabled = true;
+        assert_eq!(
+            AddonManager::machine_config_patches(&config, "v1.11.2"),
+            [spegel::machine_config_patch("v1.11.2")]
+        );
+        assert!(AddonManager::required_extensions(&config).is_empty());
+    }
+
+    #[test]
+    fn test_dependencies() {
+        let mut config = AddonsConfig::default();
+        assert!(!resolve_dependencies(&config).metrics_server.enabled);
+
+        config.vpa.enabled = true;
+        let resolved = resolve_dependencies(&config);
+        assert!(resolved.metrics_server.enabled);
+        assert!(resolved.vpa.enabled);
+
+        let cluster = ClusterConfig {
+            addons: config,
+            ..ClusterConfig::example()
+        };
+        let releases: Vec<String> = AddonManager::new(&cluster, "kubeconfig".into())
+            .charts()
+            .into_iter()
+            .map(|chart| chart.release)
+            .collect();
+        let metrics_server = releases.iter().position(|r| r == "metrics-server");
+        let vpa = releases.iter().position(|r| r == "vpa");
+        assert!(metrics_server.unwrap() < vpa.unwrap());
+    }
+
+    #[test]
+    fn test_dependency_edges_are_known() {
+        let mut config = AddonsConfig::default();
+        for (addon, dependency) in DEPENDENCIES {
+            assert!(enabled_flag(&mut config, addon).is_some(), "{}", addon);
+            assert!(
+                enabled_flag(&mut config, dependency).is_some(),
+                "{}",
+                dependency
+            );
+        }
+        assert!(enabled_flag(&mut config, "unknown").is_none());
+    }
+
+    #[test]
+    fn test_gpu_pool_prerequisites() {
+        let mut cluster = ClusterConfig::example();
+        cluster.addons.longhorn.enabled = true;
+        let mut pool = cluster.workers[0].clone();
+        assert!(AddonManager::pool_machine_config_patches(&pool).is_empty());
+
+        pool.gpu = true;
+        assert_eq!(AddonManager::pool_machine_config_patches(&pool).len(), 1);
+
+        let extensions = AddonManager::pool_required_extensions(&cluster.addons, &pool);
+        assert!(extensions.contains(&"siderolabs/iscsi-tools".to_string()));
+        assert!(extensions.contains(&"siderolabs/nvidia-container-toolkit-production".to_string()));
+    }
+}
@@ -0,0 +1,71 @@
+/// node-problem-detector addon, reporting node problems as node conditions
+use anyhow::Result;
+use std::path::Path;
+use tracing::info;
+
+use crate::bundle::Bundle;
+use crate::config::{HelmMode, NodeProblemDetectorConfig};
+use crate::utils::helm;
+
+/// Talos exempts kube-system from the baseline Pod Security Standard, which
+/// the host access of the DaemonSet would violate
+const NAMESPACE: &str = "kube-system";
+
+/// Install node-problem-detector via Helm and wait for the DaemonSet to roll
+/// out
+pub async fn install(
+    kubeconfig_path: &Path,
+    config: &NodeProblemDetectorConfig,
+    helm_mode: HelmMode,
+    rollout_timeout_secs: u64,
+    bundle: Option<&Bundle>,
+) -> Result<()> {
+    info!("Installing node-problem-detector {}...", config.version);
+
+    let mut chart = chart(config);
+    if let Some(bundle) = bundle {
+        chart = bundle.local_chart(chart)?;
+    }
+    helm::add_chart_repo(kubeconfig_path, &chart).await?;
+    helm::upgrade_install(kubeconfig_path, helm_mode, &chart).await?;
+    helm::wait_for_release(
+        kubeconfig_path,
+        helm_mode,
+        "node-problem-detector",
+        NAMESPACE,
+        rollout_timeout_secs,
+    )
+    .await?;
+
+    info!("node-problem-detector installed successfully");
+    Ok(())
+}
+
+/// The node-problem-detector chart with the kernel monitor only
+pub fn chart(config: &NodeProblemDetectorConfig) -> helm::Chart {
+    helm::Chart {
+        release: "node-problem-detector".to_string(),
+        repo_name: "deliveryhero".to_string(),
+        repo_url: "https://charts.deliveryhero.io/".to_string(),
+        name: "node-problem-detector".to_string(),
+        version: config.version.clone(),
+        namespace: NAMESPACE.to_string(),
+        values: vec!["settings.log_monitors={/config/kernel-monitor.json}".to_string()],
+        privileged: false,
+        archive: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chart() {
+        let values =
+            helm::values_document(&chart(&NodeProblemDetectorConfig::default()).values).unwrap();
+        let monitors = values["settings"]["log_monitors"].as_sequence().unwrap();
+        assert_eq!(monitors.len(), 1);
+        assert_eq!(monitors[0], "/config/kernel-monitor.json");
+    }
+}
@@ -0,0 +1,114 @@
+/// NVIDIA GPU support: Talos prerequisites and the device plugin addon
+use anyhow::Result;
+use std::path::Path;
+use tracing::info;
+
+use crate::bundle::Bundle;
+use crate::config::{HelmMode, NvidiaDevicePluginConfig};
+use crate::k8s::ResourceManager;
+use crate::utils::helm;
+
+const NAMESPACE: &str = "nvidia-device-plugin";
+
+/// Talos system extensions providing the NVIDIA driver and container runtime hook
+pub const REQUIRED_EXTENSIONS: &[&str] = &[
+    "siderolabs/nonfree-kmod-nvidia-production",
+    "siderolabs/nvidia-container-toolkit-production",
+];
+
+/// Machine config patch for GPU pool nodes
+///
+/// Loads the driver modules at boot, hardens the BPF JIT as required by the
+/// NVIDIA container toolkit on Talos, and labels/taints the node so that only
+/// GPU workloads (which tolerate `nvidia.com/gpu`) are scheduled there.
+pub const MACHINE_CONFIG_PATCH: &str = r#"machine:
+  kernel:
+    modules:
+      - name: nvidia
+      - name: nvidia_uvm
+      - name: nvidia_drm
+      - name: nvidia_modeset
+  sysctls:
+    net.core.bpf_jit_harden: 1
+  nodeLabels:
+    nvidia.com/gpu.present: "true"
+  nodeTaints:
+    nvidia.com/gpu: present:NoSchedule
+"#;
+
+/// RuntimeClass routing GPU pods through the nvidia containerd runtime
+pub const RUNTIME_CLASS_MANIFEST: &str = r#"apiVersion: node.k8s.io/v1
+kind: RuntimeClass
+metadata:
+  name: nvidia
+handler: nvidia
+"#;
+
+/// Install the NVIDIA RuntimeClass and device plugin
+pub async fn install(
+    kubeconfig_path: &Path,
+    config: &NvidiaDevicePluginConfig,
+    helm_mode: HelmMode,
+    rollout_timeout_secs: u64,
+    bundle: Option<&Bundle>,
+) -> Result<()> {
+    info!("Installing NVIDIA device plugin {}...", config.version);
+
+    ResourceManager::apply_stdin(kubeconfig_path, RUNTIME_CLASS_MANIFEST).await?;
+    ResourceManager::ensure_privileged_namespace(kubeconfig_path, NAMESPACE).await?;
+
+    let mut chart = chart(config);
+    if let Some(bundle) = bundle {
+        chart = bundle.local_chart(chart)?;
+    }
+    helm::add_chart_repo(kubeconfig_path, &chart).await?;
+    helm::upgrade_install(kubeconfig_path, helm_mode, &chart).await?;
+
+    helm::wait_for_release(
+        kubeconfig_path,
+        helm_mode,
+        "nvidia-device-plugin",
+        NAMESPACE,
+        rollout_timeout_secs,
+    )
+    .await?;
+
+    info!("NVIDIA device plugin installed successfully");
+    Ok(())
+}
+
+/// The device plugin chart
+///
+/// The chart's default affinity already targets nodes labeled
+/// nvidia.com/gpu.present=true and tolerates the nvidia.com/gpu taint.
+pub fn chart(config: &NvidiaDevicePluginConfig) -> helm::Chart {
+    helm::Chart {
+        release: "nvidia-device-plugin".to_string(),
+        repo_name: "nvdp".to_string(),
+        repo_url: "https://nvidia.github.io/k8s-device-plugin".to_string(),
+        name: "nvidia-device-plugin".to_string(),
+        version: config.version.clone(),
+        namespace: NAMESPACE.to_string(),
+        values: vec!["runtimeClassName=nvidia".to_string()],
+        privileged: true,
+        archive: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_machine_config_patch_is_valid_yaml() {
+        let patch: serde_yaml::Value = serde_yaml::from_str(MACHINE_CONFIG_PATCH).unwrap();
+        assert_eq!(
+            patch["machine"]["nodeTaints"]["nvidia.com/gpu"].as_str(),
+            Some("present:NoSchedule")
+        );
+        assert_eq!(
+            patch["machine"]["nodeLabels"]["nvidia.com/gpu.present"].as_str(),
+            Some("true")
+        );
+    }
+}
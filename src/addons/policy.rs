@@ -0,0 +1,513 @@
+/// Policy engine addon: Kyverno or OPA Gatekeeper with a starter policy pack
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::path::Path;
+use tracing::info;
+
+use crate::bundle::Bundle;
+use crate::config::{HelmMode, PolicyEngine, PolicyEngineConfig};
+use crate::k8s::ResourceManager;
+use crate::utils::command::CommandBuilder;
+use crate::utils::helm;
+use crate::utils::polling::PollingConfig;
+
+/// Name of the managed manifest holding the policy pack
+pub const MANIFEST_NAME: &str = "oxide-policy-pack";
+
+/// Namespaces of the cluster components, never subject to the pack
+const SYSTEM_NAMESPACES: &[&str] = &["kube-system", "kube-public", "kube-node-lease"];
+
+/// Install the policy engine, then apply the policy pack
+///
+/// `exempt_namespaces` are the namespaces of the other addons, whose pods
+/// need host access or come without limits.
+pub async fn install(
+    kubeconfig_path: &Path,
+    config: &PolicyEngineConfig,
+    helm_mode: HelmMode,
+    exempt_namespaces: &[String],
+    rollout_timeout_secs: u64,
+    bundle: Option<&Bundle>,
+) -> Result<()> {
+    let chart = chart(config);
+    info!("Installing {} {}...", config.engine.title(), chart.version);
+
+    let namespace = chart.namespace.clone();
+    let release = chart.release.clone();
+    let chart = match bundle {
+        Some(bundle) => bundle.local_chart(chart)?,
+        None => chart,
+    };
+    helm::add_chart_repo(kubeconfig_path, &chart).await?;
+    helm::upgrade_install(kubeconfig_path, helm_mode, &chart).await?;
+    helm::wait_for_release(
+        kubeconfig_path,
+        helm_mode,
+        &release,
+        &namespace,
+        rollout_timeout_secs,
+    )
+    .await?;
+
+    let exempt = exempt(config, exempt_namespaces);
+    if config.engine == PolicyEngine::Gatekeeper {
+        // Constraints can only be created once Gatekeeper has turned their
+        // templates into CRDs
+        let templates = gatekeeper_templates(config);
+        if !templates.is_empty() {
+            ResourceManager::apply_stdin(kubeconfig_path, &to_manifest(&templates)).await?;
+            for template in &templates {
+                let kind = template["spec"]["crd"]["spec"]["names"]["kind"]
+                    .as_str()
+                    .unwrap_or_default();
+                wait_for_constraint_crd(kubeconfig_path, kind, rollout_timeout_secs).await?;
+            }
+        }
+    }
+    ResourceManager::apply_managed(
+        kubeconfig_path,
+        &namespace,
+        MANIFEST_NAME,
+        &manifest(config, &exempt),
+    )
+    .await?;
+
+    info!("{} installed successfully", config.engine.title());
+    Ok(())
+}
+
+/// The chart of the configured engine
+pub fn chart(config: &PolicyEngineConfig) -> helm::Chart {
+    let version = config
+        .version
+        .clone()
+        .unwrap_or_else(|| config.engine.default_version().to_string());
+    match config.engine {
+        PolicyEngine::Kyverno => helm::Chart {
+            release: "kyverno".to_string(),
+            repo_name: "kyverno".to_string(),
+            repo_url: "https://kyverno.github.io/kyverno".to_string(),
+            name: "kyverno".to_string(),
+            version,
+            namespace: "kyverno".to_string(),
+            values: Vec::new(),
+            privileged: false,
+            archive: None,
+        },
+        PolicyEngine::Gatekeeper => helm::Chart {
+            release: "gatekeeper".to_string(),
+            repo_name: "gatekeeper".to_string(),
+            repo_url: "https://open-policy-agent.github.io/gatekeeper/charts".to_string(),
+            name: "gatekeeper".to_string(),
+            version,
+            namespace: "gatekeeper-system".to_string(),
+            values: Vec::new(),
+            privileged: false,
+            archive: None,
+        },
+    }
+}
+
+/// Namespaces the pack does not apply to, sorted and without duplicates
+fn exempt(config: &PolicyEngineConfig, addon_namespaces: &[String]) -> Vec<String> {
+    let mut namespaces: Vec<String> = SYSTEM_NAMESPACES
+        .iter()
+        .map(|namespace| namespace.to_string())
+        .chain([chart(config).namespace])
+        .chain(addon_namespaces.iter().cloned())
+        .chain(config.exclude_namespaces.iter().cloned())
+        .collect();
+    namespaces.sort();
+    namespaces.dedup();
+    namespaces
+}
+
+/// The policy pack as a multi-document manifest
+///
+/// Gatekeeper policies are the Constraints only; their templates are
+/// applied first by [`install`].
+pub fn manifest(config: &PolicyEngineConfig, exempt: &[String]) -> String {
+    let objects = match config.engine {
+        PolicyEngine::Kyverno => kyverno_policies(config, exempt),
+        PolicyEngine::Gatekeeper => gatekeeper_constraints(config, exempt),
+    };
+    to_manifest(&objects)
+}
+
+/// The policy pack for `oxide render`, with the Gatekeeper templates ahead
+/// of their Constraints
+pub fn render_manifest(config: &PolicyEngineConfig, addon_namespaces: &[String]) -> String {
+    let exempt = exempt(config, addon_namespaces);
+    match config.engine {
+        PolicyEngine::Kyverno => manifest(config, &exempt),
+        PolicyEngine::Gatekeeper => {
+            let mut objects = gatekeeper_templates(config);
+            objects.extend(gatekeeper_constraints(config, &exempt));
+            to_manifest(&objects)
+        }
+    }
+}
+
+fn to_manifest(objects: &[Value]) -> String {
+    objects
+        .iter()
+        .map(|object| serde_yaml::to_string(object).expect("policy object serializes"))
+        .collect::<Vec<_>>()
+        .join("---\n")
+}
+
+fn kyverno_policies(config: &PolicyEngineConfig, exempt: &[String]) -> Vec<Value> {
+    let action = if config.enforce { "Enforce" } else { "Audit" };
+    let policy = |name: &str, message: String, container: Value| {
+        let mut pattern = json!({ "containers": [container] });
+        // `=(field)` only checks the field if the pod has it
+        for field in ["initContainers", "ephemeralContainers"] {
+            pattern[format!("=({})", field)] = json!([container]);
+        }
+        json!({
+            "apiVersion": "kyverno.io/v1",
+            "kind": "ClusterPolicy",
+            "metadata": metadata(&format!("oxide-{}", name)),
+            "spec": {
+                "background": true,
+                "rules": [{
+                    "name": name,
+                    "match": { "any": [{ "resources": { "kinds": ["Pod"] } }] },
+                    "exclude": { "any": [{ "resources": { "namespaces": exempt } }] },
+                    "validate": {
+                        "failureAction": action,
+                        "message": message,
+                        "pattern": { "spec": pattern },
+                    },
+                }],
+            },
+        })
+    };
+
+    let mut policies = Vec::new();
+    if config.disallow_privileged {
+        policies.push(policy(
+            "disallow-privileged",
+            "Privileged containers are not allowed".to_string(),
+            json!({ "=(securityContext)": { "=(privileged)": "false" } }),
+        ));
+    }
+    if config.require_limits {
+        let mut policy = policy(
+            "require-limits",
+            "CPU and memory limits are required".to_string(),
+            json!({ "resources": { "limits": { "cpu": "?*", "memory": "?*" } } }),
+        );
+        // Ephemeral debug containers cannot set resources
+        let pattern = &mut policy["spec"]["rules"][0]["validate"]["pattern"]["spec"];
+        pattern
+            .as_object_mut()
+            .expect("pattern is an object")
+            .remove("=(ephemeralContainers)");
+        policies.push(policy);
+    }
+    if !config.allowed_registries.is_empty() {
+        let images = config
+            .allowed_registries
+            .iter()
+            .map(|registry| format!("{}/*", registry.trim_end_matches('/')))
+            .collect::<Vec<_>>()
+            .join(" | ");
+        policies.push(policy(
+            "restrict-registries",
+            format!(
+                "Images must come from {}",
+                config.allowed_registries.join(", ")
+            ),
+            json!({ "image": images }),
+        ));
+    }
+    policies
+}
+
+/// Rego rules shared by the Gatekeeper templates, collecting every
+/// container of the reviewed pod
+const REGO_CONTAINERS: &str = r#"
+input_containers[c] {
+  c := input.review.object.spec.containers[_]
+}
+input_containers[c] {
+  c := input.review.object.spec.initContainers[_]
+}
+input_containers[c] {
+  c := input.review.object.spec.ephemeralContainers[_]
+}
+"#;
+
+/// Templates of the enabled policies: (policy name, kind, parameters schema, rego)
+fn gatekeeper_policies(
+    config: &PolicyEngineConfig,
+) -> Vec<(&'static str, &'static str, Value, String)> {
+    let mut policies = Vec::new();
+    if config.disallow_privileged {
+        policies.push((
+            "disallow-privileged",
+            "OxideDisallowPrivileged",
+            json!({}),
+            format!(
+                r#"package oxidedisallowprivileged
+
+violation[{{"msg": msg}}] {{
+  c := input_containers[_]
+  c.securityContext.privileged
+  msg := sprintf("Privileged container %v is not allowed", [c.name])
+}}
+{}"#,
+                REGO_CONTAINERS
+            ),
+        ));
+    }
+    if config.require_limits {
+        policies.push((
+            "require-limits",
+            "OxideRequireLimits",
+            json!({}),
+            r#"package oxiderequirelimits
+
+violation[{"msg": msg}] {
+  c := input_containers[_]
+  missing := [r | r := ["cpu", "memory"][_]; not c.resources.limits[r]]
+  count(missing) > 0
+  msg := sprintf("Container %v has no %v limit", [c.name, concat(" or ", missing)])
+}
+
+input_containers[c] {
+  c := input.review.object.spec.containers[_]
+}
+input_containers[c] {
+  c := input.review.object.spec.initContainers[_]
+}
+"#
+            .to_string(),
+        ));
+    }
+    if !config.allowed_registries.is_empty() {
+        policies.push((
+            "restrict-registries",
+            "OxideRestrictRegistries",
+            json!({
+                "type": "object",
+                "properties": {
+                    "registries": { "type": "array", "items": { "type": "string" } },
+                },
+            }),
+            format!(
+                r#"package oxiderestrictregistries
+
+violation[{{"msg": msg}}] {{
+  c := input_containers[_]
+  not allowed(c.image)
+  msg := sprintf("Image %v of container %v is not from %v", [c.image, c.name, input.parameters.registries])
+}}
+
+allowed(image) {{
+  startswith(image, input.parameters.registries[_])
+}}
+{}"#,
+                REGO_CONTAINERS
+            ),
+        ));
+    }
+    policies
+}
+
+fn gatekeeper_templates(config: &PolicyEngineConfig) -> Vec<Value> {
+    gatekeeper_policies(config)
+        .into_iter()
+        .map(|(_, kind, parameters, rego)| {
+            json!({
+                "apiVersion": "templates.gatekeeper.sh/v1",
+                "kind": "ConstraintTemplate",
+                "metadata": metadata(&kind.to_lowercase()),
+                "spec": {
+                    "crd": {
+                        "spec": {
+                            "names": { "kind": kind },
+                            "validation": { "openAPIV3Schema": parameters },
+                        },
+                    },
+                    "targets": [{
+                        "target": "admission.k8s.gatekeeper.sh",
+                        "rego": rego,
+                    }],
+                },
+            })
+        })
+        .collect()
+}
+
+fn gatekeeper_constraints(config: &PolicyEngineConfig, exempt: &[String]) -> Vec<Value> {
+    let action = if config.enforce { "deny" } else { "dryrun" };
+    gatekeeper_policies(config)
+        .into_iter()
+        .map(|(name, kind, _, _)| {
+            let mut constraint = json!({
+                "apiVersion": "constraints.gatekeeper.sh/v1beta1",
+                "kind": kind,
+                "metadata": metadata(&format!("oxide-{}", name)),
+                "spec": {
+                    "enforcementAction": action,
+                    "match": {
+                        "kinds": [{ "apiGroups": [""], "kinds": ["Pod"] }],
+                        "excludedNamespaces": exempt,
+                    },
+                },
+            });
+            if name == "restrict-registries" {
+                // Prefixes end with a slash so `ghcr.io/acme` does not
+                // allow `ghcr.io/acme-evil`
+                let registries: Vec<String> = config
+                    .allowed_registries
+                    .iter()
+                    .map(|registry| format!("{}/", registry.trim_end_matches('/')))
+                    .collect();
+                constraint["spec"]["parameters"] = json!({ "registries": registries });
+            }
+            constraint
+        })
+        .collect()
+}
+
+/// Wait until Gatekeeper has created the CRD of the constraint `kind`
+async fn wait_for_constraint_crd(
+    kubeconfig_path: &Path,
+    kind: &str,
+    timeout_secs: u64,
+) -> Result<()> {
+    let crd = format!("crd/{}.constraints.gatekeeper.sh", kind.to_lowercase());
+    PollingConfig::new(
+        timeout_secs,
+        2,
+        format!("Waiting for the {} constraint CRD", kind),
+    )
+    .poll_until(|| async {
+        Ok(CommandBuilder::new("kubectl")
+            .args(["get", &crd])
+            .kubeconfig(kubeconfig_path)
+            .context(format!("Failed to get {}", crd))
+            .run()
+            .await
+            .is_ok())
+    })
+    .await
+    .context("Gatekeeper did not create the constraint CRDs")
+}
+
+fn metadata(name: &str) -> Value {
+    json!({
+        "name": name,
+        "labels": { "app.kubernetes.io/managed-by": "oxide" },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn documents(manifest: &str) -> Vec<serde_yaml::Value> {
+        manifest
+            .split("---\n")
+            .map(|doc| serde_yaml::from_str(doc).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_kyverno_policies() {
+        let mut config = PolicyEngineConfig {
+            enabled: true,
+            allowed_registries: vec!["ghcr.io/acme/".to_string(), "registry.k8s.io".to_string()],
+            ..Default::default()
+        };
+        let exempt = exempt(&config, &["longhorn-system".to_string()]);
+        assert!(exempt.contains(&"kyverno".to_string()));
+        assert!(exempt.contains(&"longhorn-system".to_string()));
+
+        let policies = documents(&manifest(&config, &exempt));
+        let names: Vec<&str> = policies
+            .iter()
+            .map(|policy| policy["metadata"]["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "oxide-disallow-privileged",
+                "oxide-require-limits",
+                "oxide-restrict-registries"
+            ]
+        );
+
+        let rule = &policies[0]["spec"]["rules"][0];
+        assert_eq!(rule["validate"]["failureAction"], "Audit");
+        assert_eq!(
+            rule["validate"]["pattern"]["spec"]["=(initContainers)"][0]["=(securityContext)"]
+                ["=(privileged)"],
+            "false"
+        );
+        let limits = &policies[1]["spec"]["rules"][0]["validate"]["pattern"]["spec"];
+        assert!(limits["=(ephemeralContainers)"].is_null());
+        assert_eq!(
+            policies[2]["spec"]["rules"][0]["validate"]["pattern"]["spec"]["containers"][0]
+                ["image"],
+            "ghcr.io/acme/* | registry.k8s.io/*"
+        );
+
+        config.enforce = true;
+        config.require_limits = false;
+        config.allowed_registries.clear();
+        let policies = documents(&manifest(&config, &exempt));
+        assert_eq!(policies.len(), 1);
+        assert_eq!(
+            policies[0]["spec"]["rules"][0]["validate"]["failureAction"],
+            "Enforce"
+        );
+
+        config.disallow_privileged = false;
+        assert!(manifest(&config, &exempt).is_empty());
+    }
+
+    #[test]
+    fn test_gatekeeper_policies() {
+        let config = PolicyEngineConfig {
+            enabled: true,
+            engine: PolicyEngine::Gatekeeper,
+            enforce: true,
+            allowed_registries: vec!["ghcr.io/acme".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(chart(&config).namespace, "gatekeeper-system");
+
+        let rendered = documents(&render_manifest(&config, &[]));
+        let kinds: Vec<&str> = rendered
+            .iter()
+            .map(|doc| doc["kind"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                "ConstraintTemplate",
+                "ConstraintTemplate",
+                "ConstraintTemplate",
+                "OxideDisallowPrivileged",
+                "OxideRequireLimits",
+                "OxideRestrictRegistries"
+            ]
+        );
+        assert_eq!(rendered[2]["metadata"]["name"], "oxiderestrictregistries");
+
+        let constraint = &rendered[5];
+        assert_eq!(constraint["spec"]["enforcementAction"], "deny");
+        assert_eq!(
+            constraint["spec"]["parameters"]["registries"][0],
+            "ghcr.io/acme/"
+        );
+        assert_eq!(
+            constraint["spec"]["match"]["excludedNamespaces"][0],
+            "gatekeeper-system"
+        );
+    }
+}
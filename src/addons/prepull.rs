@@ -0,0 +1,123 @@
+/// Image pre-pull addon
+use anyhow::Result;
+use std::collections::BTreeSet;
+use std::path::Path;
+use tracing::info;
+
+use crate::bundle::manifest_images;
+use crate::config::ImagePrepullConfig;
+use crate::k8s::ResourceManager;
+use crate::utils::helm::{self, Chart};
+
+const NAMESPACE: &str = "kube-system";
+
+/// Name of the DaemonSet and of its saved manifest
+const NAME: &str = "oxide-image-prepull";
+
+/// Official busybox images are statically linked
+const BUSYBOX_IMAGE: &str = "docker.io/library/busybox:1.36.1";
+
+const PAUSE_IMAGE: &str = "registry.k8s.io/pause:3.10";
+
+/// Apply the DaemonSet and wait until every node has pulled the images
+///
+/// With `include_cilium` the images are taken from the rendered `cilium`
+/// chart.
+pub async fn install(
+    kubeconfig_path: &Path,
+    config: &ImagePrepullConfig,
+    cilium: &Chart,
+    rollout_timeout_secs: u64,
+) -> Result<()> {
+    let mut images: BTreeSet<String> = config.images.iter().cloned().collect();
+    if config.include_cilium {
+        helm::add_chart_repo(kubeconfig_path, cilium).await?;
+        images.extend(manifest_images(
+            &helm::template(kubeconfig_path, cilium).await?,
+        ));
+    }
+    info!("Pre-pulling {} images on every node...", images.len());
+
+    let images: Vec<String> = images.into_iter().collect();
+    let manifest = manifest(&images);
+    ResourceManager::apply_managed(kubeconfig_path, NAMESPACE, NAME, &manifest).await?;
+    ResourceManager::wait_for_manifest(kubeconfig_path, NAMESPACE, &manifest, rollout_timeout_secs)
+        .await?;
+
+    info!("✓ Images pulled on every node");
+    Ok(())
+}
+
+/// The pre-pull DaemonSet for `images`
+///
+/// It tolerates every taint so control planes and GPU nodes pull too, and
+/// replaces all pods at once, since a changed image list is best pulled
+/// everywhere before it is needed.
+pub fn manifest(images: &[String]) -> String {
+    let tiny = serde_json::json!({ "requests": { "cpu": "1m", "memory": "8Mi" } });
+    let mut init_containers = vec![serde_json::json!({
+        "name": "busybox",
+        "image": BUSYBOX_IMAGE,
+        "command": ["cp", "/bin/busybox", "/prepull/busybox"],
+        "volumeMounts": [{ "name": "prepull", "mountPath": "/prepull" }],
+        "resources": tiny,
+    })];
+    init_containers.extend(images.iter().enumerate().map(|(i, image)| {
+        serde_json::json!({
+            "name": format!("pull-{}", i),
+            "image": image,
+            "imagePullPolicy": "IfNotPresent",
+            "command": ["/prepull/busybox", "true"],
+            "volumeMounts": [{ "name": "prepull", "mountPath": "/prepull" }],
+            "resources": tiny,
+        })
+    }));
+
+    let labels = serde_json::json!({ "app.kubernetes.io/name": NAME });
+    let daemonset = serde_json::json!({
+        "apiVersion": "apps/v1",
+        "kind": "DaemonSet",
+        "metadata": { "name": NAME, "namespace": NAMESPACE, "labels": labels },
+        "spec": {
+            "selector": { "matchLabels": labels },
+            "updateStrategy": {
+                "type": "RollingUpdate",
+                "rollingUpdate": { "maxUnavailable": "100%" },
+            },
+            "template": {
+                "metadata": { "labels": labels },
+                "spec": {
+                    "tolerations": [{ "operator": "Exists" }],
+                    "initContainers": init_containers,
+                    "containers": [{
+                        "name": "pause",
+                        "image": PAUSE_IMAGE,
+                        "resources": tiny,
+                    }],
+                    "volumes": [{ "name": "prepull", "emptyDir": {} }],
+                },
+            },
+        },
+    });
+    serde_yaml::to_string(&daemonset).expect("pre-pull DaemonSet serializes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest() {
+        let images = vec!["ghcr.io/acme/api:1.4.2".to_string()];
+        let daemonset: serde_yaml::Value = serde_yaml::from_str(&manifest(&images)).unwrap();
+        let pod = &daemonset["spec"]["template"]["spec"];
+        assert_eq!(pod["tolerations"][0]["operator"], "Exists");
+        assert_eq!(pod["initContainers"][0]["image"], BUSYBOX_IMAGE);
+        assert_eq!(pod["initContainers"][1]["image"], "ghcr.io/acme/api:1.4.2");
+        assert_eq!(pod["initContainers"][1]["command"][0], "/prepull/busybox");
+
+        let listed = manifest_images(&manifest(&images));
+        assert!(listed.contains("ghcr.io/acme/api:1.4.2"));
+        assert!(listed.contains(PAUSE_IMAGE));
+    }
+}
@@ -0,0 +1,133 @@
+/// Spegel stateless registry mirror addon
+use anyhow::Result;
+use std::path::Path;
+use tracing::info;
+
+use crate::bundle::Bundle;
+use crate::config::{HelmMode, SpegelConfig};
+use crate::k8s::ResourceManager;
+use crate::utils::helm;
+
+const NAMESPACE: &str = "spegel";
+
+/// Where Talos keeps the containerd registry `hosts.toml` files
+const REGISTRY_CONFIG_PATH: &str = "/etc/cri/conf.d/hosts";
+
+/// Machine config patch keeping the image layers Spegel serves
+///
+/// containerd discards compressed layers once they are unpacked, unless
+/// told otherwise. Talos 1.9 moved to containerd 2, which renamed the CRI
+/// image plugin.
+pub fn machine_config_patch(talos_version: &str) -> String {
+    let plugin = if containerd_v2(talos_version) {
+        r#"[plugins."io.containerd.cri.v1.images"]"#
+    } else {
+        r#"[plugins."io.containerd.grpc.v1.cri".containerd]"#
+    };
+    format!(
+        "machine:\n  files:\n    - path: /etc/cri/conf.d/20-customization.part\n      op: create\n      content: |\n        {}\n          discard_unpacked_layers = false\n",
+        plugin
+    )
+}
+
+/// Whether a Talos version ships containerd 2; unparsable versions are
+/// taken as recent
+fn containerd_v2(talos_version: &str) -> bool {
+    let mut parts = talos_version.trim_start_matches('v').split('.');
+    match (
+        parts.next().and_then(|major| major.parse::<u32>().ok()),
+        parts.next().and_then(|minor| minor.parse::<u32>().ok()),
+    ) {
+        (Some(major), Some(minor)) => (major, minor) >= (1, 9),
+        _ => true,
+    }
+}
+
+/// Install Spegel via Helm and wait for the DaemonSet to roll out
+pub async fn install(
+    kubeconfig_path: &Path,
+    config: &SpegelConfig,
+    helm_mode: HelmMode,
+    keep_existing_mirrors: bool,
+    rollout_timeout_secs: u64,
+    bundle: Option<&Bundle>,
+) -> Result<()> {
+    info!("Installing Spegel {}...", config.version);
+
+    // Spegel reads the containerd socket and writes the registry config
+    // on the host
+    ResourceManager::ensure_privileged_namespace(kubeconfig_path, NAMESPACE).await?;
+
+    let mut chart = chart(config, keep_existing_mirrors);
+    if let Some(bundle) = bundle {
+        chart = bundle.local_chart(chart)?;
+    }
+    helm::add_chart_repo(kubeconfig_path, &chart).await?;
+    helm::upgrade_install(kubeconfig_path, helm_mode, &chart).await?;
+
+    helm::wait_for_release(
+        kubeconfig_path,
+        helm_mode,
+        "spegel",
+        NAMESPACE,
+        rollout_timeout_secs,
+    )
+    .await?;
+
+    info!("Spegel installed successfully");
+    Ok(())
+}
+
+/// The Spegel chart
+///
+/// With `keep_existing_mirrors` (a `talos.registry_mirror` is configured)
+/// Spegel is put in front of the mirror instead of replacing it, so nodes
+/// fall back to the mirror rather than the public registries.
+pub fn chart(config: &SpegelConfig, keep_existing_mirrors: bool) -> helm::Chart {
+    let mut values = vec![format!(
+        "spegel.containerdRegistryConfigPath={}",
+        REGISTRY_CONFIG_PATH
+    )];
+    if keep_existing_mirrors {
+        values.push("spegel.prependExisting=true".to_string());
+    }
+    helm::Chart {
+        release: "spegel".to_string(),
+        repo_name: "spegel".to_string(),
+        repo_url: "oci://ghcr.io/spegel-org/helm-charts".to_string(),
+        name: "spegel".to_string(),
+        version: config.version.clone(),
+        namespace: NAMESPACE.to_string(),
+        values,
+        privileged: true,
+        archive: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_machine_config_patch() {
+        let patch: serde_yaml::Value =
+            serde_yaml::from_str(&machine_config_patch("v1.11.2")).unwrap();
+        let content = patch["machine"]["files"][0]["content"].as_str().unwrap();
+        assert!(content.starts_with(r#"[plugins."io.containerd.cri.v1.images"]"#));
+        assert!(content.contains("discard_unpacked_layers = false"));
+
+        assert!(machine_config_patch("v1.8.3").contains("io.containerd.grpc.v1.cri"));
+    }
+
+    #[test]
+    fn test_chart() {
+        let chart = chart(&SpegelConfig::default(), true);
+        assert_eq!(
+            chart.reference(),
+            "oci://ghcr.io/spegel-org/helm-charts/spegel"
+        );
+        assert!(chart
+            .values
+            .contains(&"spegel.prependExisting=true".to_string()));
+    }
+}
@@ -0,0 +1,192 @@
+/// Status of the Helm charts oxide installed
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::Path;
+
+use crate::config::HelmMode;
+use crate::inventory::{align, format_age};
+use crate::k8s::ResourceManager;
+use crate::state::ClusterState;
+use crate::utils::helm;
+
+/// Status of one installed chart
+#[derive(Debug, Clone, Serialize)]
+pub struct AddonStatus {
+    pub release: String,
+    pub namespace: String,
+    /// e.g. `cilium/cilium`
+    pub chart: String,
+    pub chart_version: String,
+    pub app_version: Option<String>,
+    pub mode: HelmMode,
+    /// Last install or upgrade by oxide
+    pub last_deployed: DateTime<Utc>,
+    pub healthy: bool,
+    /// What is not ready, e.g. `daemonset.apps/cilium: 2/3 available`
+    pub problems: Vec<String>,
+}
+
+/// Status of every chart recorded in `output_dir`, by release name
+pub async fn collect(output_dir: &Path) -> Result<Vec<AddonStatus>> {
+    let kubeconfig_path = output_dir.join("kubeconfig");
+    let releases = ClusterState::load(output_dir)?
+        .map(|state| state.releases)
+        .unwrap_or_default();
+
+    let listed = if releases
+        .values()
+        .any(|record| record.mode == HelmMode::Release)
+    {
+        helm::list_releases(&kubeconfig_path).await?
+    } else {
+        Vec::new()
+    };
+
+    let mut statuses = Vec::new();
+    for (release, record) in releases {
+        let mut app_version = record.app_version.clone();
+        let mut problems = Vec::new();
+
+        let installed = match record.mode {
+            HelmMode::Release => {
+                match listed
+                    .iter()
+                    .find(|l| l.name == release && l.namespace == record.namespace)
+                {
+                    Some(listed) => {
+                        if !listed.app_version.is_empty() {
+                            app_version = Some(listed.app_version.clone());
+                        }
+                        if listed.status != "deployed" {
+                            problems.push(format!("Helm release is {}", listed.status));
+                        }
+                        true
+                    }
+                    None => {
+                        problems.push("Helm release not found".to_string());
+                        false
+                    }
+                }
+            }
+            HelmMode::Template => true,
+        };
+
+        if installed {
+            let checked = match helm::release_manifest(
+                &kubeconfig_path,
+                record.mode,
+                &release,
+                &record.namespace,
+            )
+            .await
+            {
+                Ok(manifest) => {
+                    ResourceManager::manifest_problems(
+                        &kubeconfig_path,
+                        &record.namespace,
+                        &manifest,
+                    )
+                    .await
+                }
+                Err(e) => Err(e),
+            };
+            match checked {
+                Ok(found) => problems.extend(found),
+                Err(e) => problems.push(format!("{:#}", e)),
+            }
+        }
+
+        statuses.push(AddonStatus {
+            release,
+            namespace: record.namespace,
+            chart: record.chart,
+            chart_version: record.version,
+            app_version,
+            mode: record.mode,
+            last_deployed: record.deployed,
+            healthy: problems.is_empty(),
+            problems,
+        });
+    }
+    Ok(statuses)
+}
+
+/// Render statuses as an aligned table
+pub fn render_table(statuses: &[AddonStatus], now: DateTime<Utc>) -> String {
+    let header = [
+        "RELEASE",
+        "NAMESPACE",
+        "CHART",
+        "VERSION",
+        "APP-VERSION",
+        "DEPLOYED",
+        "HEALTH",
+    ];
+    let mut table: Vec<Vec<String>> = vec![header.iter().map(|h| h.to_string()).collect()];
+    for status in statuses {
+        let health = if status.healthy {
+            "healthy".to_string()
+        } else {
+            format!("degraded: {}", status.problems.join("; "))
+        };
+        table.push(vec![
+            status.release.clone(),
+            status.namespace.clone(),
+            status.chart.clone(),
+            status.chart_version.clone(),
+            status
+                .app_version
+                .clone()
+                .unwrap_or_else(|| "-".to_string()),
+            format!(
+                "{} ago",
+                format_age(now.signed_duration_since(status.last_deployed))
+            ),
+            health,
+        ]);
+    }
+    align(&table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_table() {
+        let now = Utc::now();
+        let statuses = vec![
+            AddonStatus {
+                release: "cilium".to_string(),
+                namespace: "kube-system".to_string(),
+                chart: "cilium/cilium".to_string(),
+                chart_version: "1.16.5".to_string(),
+                app_version: Some("1.16.5".to_string()),
+                mode: HelmMode::Release,
+                last_deployed: now - chrono::Duration::hours(3),
+                healthy: true,
+                problems: vec![],
+            },
+            AddonStatus {
+                release: "longhorn".to_string(),
+                namespace: "longhorn-system".to_string(),
+                chart: "longhorn/longhorn".to_string(),
+                chart_version: "1.7.2".to_string(),
+                app_version: None,
+                mode: HelmMode::Template,
+                last_deployed: now - chrono::Duration::days(2),
+                healthy: false,
+                problems: vec!["daemonset.apps/longhorn-manager: 2/3 available".to_string()],
+            },
+        ];
+
+        let table = render_table(&statuses, now);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("RELEASE"));
+        assert!(lines[1].contains("3h ago") && lines[1].ends_with("healthy"));
+        assert!(lines[2].contains(" - ") && lines[2].contains("2d ago"));
+        assert!(lines[2].ends_with("degraded: daemonset.apps/longhorn-manager: 2/3 available"));
+    }
+}
@@ -0,0 +1,81 @@
+/// Vertical Pod Autoscaler addon
+use anyhow::Result;
+use std::path::Path;
+use tracing::info;
+
+use crate::bundle::Bundle;
+use crate::config::{HelmMode, VpaConfig};
+use crate::utils::helm;
+
+const NAMESPACE: &str = "vpa";
+
+/// Install the VPA via Helm and wait for its components to roll out
+pub async fn install(
+    kubeconfig_path: &Path,
+    config: &VpaConfig,
+    helm_mode: HelmMode,
+    rollout_timeout_secs: u64,
+    bundle: Option<&Bundle>,
+) -> Result<()> {
+    info!(
+        "Installing the Vertical Pod Autoscaler {}...",
+        config.version
+    );
+
+    let mut chart = chart(config);
+    if let Some(bundle) = bundle {
+        chart = bundle.local_chart(chart)?;
+    }
+    helm::add_chart_repo(kubeconfig_path, &chart).await?;
+    helm::upgrade_install(kubeconfig_path, helm_mode, &chart).await?;
+    helm::wait_for_release(
+        kubeconfig_path,
+        helm_mode,
+        "vpa",
+        NAMESPACE,
+        rollout_timeout_secs,
+    )
+    .await?;
+
+    info!("Vertical Pod Autoscaler installed successfully");
+    Ok(())
+}
+
+/// The Fairwinds VPA chart
+pub fn chart(config: &VpaConfig) -> helm::Chart {
+    helm::Chart {
+        release: "vpa".to_string(),
+        repo_name: "fairwinds-stable".to_string(),
+        repo_url: "https://charts.fairwinds.com/stable".to_string(),
+        name: "vpa".to_string(),
+        version: config.version.clone(),
+        namespace: NAMESPACE.to_string(),
+        values: vec![
+            "recommender.enabled=true".to_string(),
+            format!("updater.enabled={}", config.auto_update),
+            format!("admissionController.enabled={}", config.auto_update),
+        ],
+        privileged: false,
+        archive: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chart() {
+        let mut config = VpaConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        let values = helm::values_document(&chart(&config).values).unwrap();
+        assert_eq!(values["recommender"]["enabled"], true);
+        assert_eq!(values["updater"]["enabled"], false);
+
+        config.auto_update = true;
+        let values = helm::values_document(&chart(&config).values).unwrap();
+        assert_eq!(values["admissionController"]["enabled"], true);
+    }
+}
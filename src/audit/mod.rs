@@ -0,0 +1,186 @@
+/// Append-only audit log of mutating operations
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use tracing::warn;
+
+/// File name of the audit log inside the output directory
+pub const AUDIT_LOG_FILE: &str = "audit.log";
+
+static AUDIT_LOG: OnceLock<AuditLog> = OnceLock::new();
+
+/// A single recorded action
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    /// oxide subcommand that performed the action
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cluster: Option<String>,
+    /// What was done, e.g. `hcloud.post` or `helm.install`
+    pub action: String,
+    /// What it was done to, e.g. `servers/123/actions/poweron`
+    pub target: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, String>,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+struct AuditLog {
+    path: PathBuf,
+    command: String,
+    cluster: Mutex<Option<String>>,
+    /// Successful actions of this process
+    changes: AtomicUsize,
+    // Serialises appends from concurrent tasks
+    write_lock: Mutex<()>,
+}
+
+/// Start recording actions for this process into `<output_dir>/audit.log`
+pub fn init(output_dir: &Path, command: &str) {
+    let _ = AUDIT_LOG.set(AuditLog {
+        path: output_dir.join(AUDIT_LOG_FILE),
+        command: command.to_string(),
+        cluster: Mutex::new(None),
+        changes: AtomicUsize::new(0),
+        write_lock: Mutex::new(()),
+    });
+}
+
+/// Attach the cluster name to all subsequently recorded actions
+pub fn set_cluster(cluster_name: &str) {
+    if let Some(log) = AUDIT_LOG.get() {
+        *log.cluster.lock().unwrap() = Some(cluster_name.to_string());
+    }
+}
+
+/// Record the outcome of a mutating action
+///
+/// Never fails the operation itself: problems writing the log are reported
+/// as warnings. Callers must not pass secrets (tokens, machine configs) in
+/// `params`.
+pub fn record<T>(action: &str, target: &str, params: &[(&str, &str)], outcome: &Result<T>) {
+    let Some(log) = AUDIT_LOG.get() else {
+        return;
+    };
+
+    let entry = AuditEntry {
+        timestamp: Utc::now(),
+        command: log.command.clone(),
+        cluster: log.cluster.lock().unwrap().clone(),
+        action: action.to_string(),
+        target: target.to_string(),
+        params: params
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+        success: outcome.is_ok(),
+        error: outcome.as_ref().err().map(|e| format!("{:#}", e)),
+    };
+
+    if entry.success {
+        log.changes.fetch_add(1, Ordering::Relaxed);
+    }
+    let _guard = log.write_lock.lock().unwrap();
+    if let Err(e) = append(&log.path, &entry) {
+        warn!("Failed to write audit log {}: {:#}", log.path.display(), e);
+    }
+}
+
+/// Number of actions this process has completed so far
+pub fn changes() -> usize {
+    AUDIT_LOG
+        .get()
+        .map_or(0, |log| log.changes.load(Ordering::Relaxed))
+}
+
+fn append(path: &Path, entry: &AuditEntry) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+    file.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+/// Read recorded actions, oldest first
+///
+/// Lines that cannot be parsed (e.g. a partially written last line) are skipped.
+pub fn read_entries(path: &Path) -> Result<Vec<AuditEntry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = std::fs::read_to_string(path)
+        .context(format!("Failed to read audit log {}", path.display()))?;
+    Ok(parse_entries(&content))
+}
+
+fn parse_entries(content: &str) -> Vec<AuditEntry> {
+    content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+impl AuditEntry {
+    /// One-line human-readable summary
+    pub fn summary(&self) -> String {
+        let mut line = format!(
+            "{} {:<7} {:<4} {} {}",
+            self.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            self.command,
+            if self.success { "ok" } else { "FAIL" },
+            self.action,
+            self.target
+        );
+        for (key, value) in &self.params {
+            line.push_str(&format!(" {}={}", key, value));
+        }
+        if let Some(error) = &self.error {
+            line.push_str(&format!(" error=\"{}\"", error));
+        }
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_round_trip() {
+        let entry = AuditEntry {
+            timestamp: Utc::now(),
+            command: "create".to_string(),
+            cluster: Some("demo".to_string()),
+            action: "helm.install".to_string(),
+            target: "cilium".to_string(),
+            params: [("version".to_string(), "1.17.8".to_string())].into(),
+            success: false,
+            error: Some("timed out".to_string()),
+        };
+
+        let mut content = serde_json::to_string(&entry).unwrap();
+        content.push_str("\n{\"truncated\n");
+
+        let entries = parse_entries(&content);
+        assert_eq!(entries, vec![entry.clone()]);
+        assert!(entries[0]
+            .summary()
+            .contains("FAIL helm.install cilium version=1.17.8"));
+    }
+}
@@ -0,0 +1,465 @@
+/// Offline bundles for clusters whose nodes cannot reach the internet
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::info;
+
+use crate::addons::{prepull, AddonManager};
+use crate::cilium::{CiliumManager, GATEWAY_API_CRDS};
+use crate::config::ClusterConfig;
+use crate::render;
+use crate::talos::extensions::ImageFactory;
+use crate::utils::command::CommandBuilder;
+use crate::utils::helm::{self, Chart};
+
+/// Index of a bundle directory
+const INDEX_FILE: &str = "bundle.yaml";
+
+const CHARTS_DIR: &str = "charts";
+
+const GATEWAY_API_CRDS_FILE: &str = "gateway-api-crds.yaml";
+
+const IMAGES_FILE: &str = "images.txt";
+
+/// Images whose tag follows the Kubernetes version of the cluster rather
+/// than the talosctl default
+const KUBERNETES_IMAGES: &[&str] = &[
+    "kube-apiserver",
+    "kube-controller-manager",
+    "kube-scheduler",
+    "kube-proxy",
+    "kubelet",
+];
+
+/// Contents of [`INDEX_FILE`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Index {
+    cluster_name: String,
+    talos_version: String,
+    kubernetes_version: String,
+    created: DateTime<Utc>,
+    charts: Vec<BundledChart>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BundledChart {
+    release: String,
+    /// e.g. `cilium/cilium`
+    chart: String,
+    version: String,
+    /// Relative to the bundle directory
+    archive: String,
+}
+
+/// A bundle directory written by [`create`]
+#[derive(Debug, Clone)]
+pub struct Bundle {
+    dir: PathBuf,
+    index: Index,
+}
+
+impl Bundle {
+    /// Read the bundle in `dir`
+    pub fn open(dir: &Path) -> Result<Self> {
+        let path = dir.join(INDEX_FILE);
+        let content = std::fs::read_to_string(&path).context(format!(
+            "Failed to read {}; create the bundle with `oxide bundle create`",
+            path.display()
+        ))?;
+        let index = serde_yaml::from_str(&content)
+            .context(format!("Invalid bundle index {}", path.display()))?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            index,
+        })
+    }
+
+    /// Fail unless the bundle holds every chart `config` installs, at the
+    /// configured version
+    pub fn check(&self, config: &ClusterConfig, kubeconfig_path: &Path) -> Result<()> {
+        check_supported(config)?;
+        let missing: Vec<String> = render::charts(config, kubeconfig_path)
+            .iter()
+            .filter(|chart| self.archive(chart).is_none())
+            .map(|chart| format!("{} {}", chart.reference(), chart.version))
+            .collect();
+        if !missing.is_empty() {
+            anyhow::bail!(
+                "Bundle {} lacks {}; recreate it with `oxide bundle create`",
+                self.dir.display(),
+                missing.join(", ")
+            );
+        }
+        for file in self
+            .index
+            .charts
+            .iter()
+            .map(|chart| self.dir.join(&chart.archive))
+            .chain([self.gateway_api_crds()])
+        {
+            if !file.exists() {
+                anyhow::bail!("Bundle file {} is missing", file.display());
+            }
+        }
+        Ok(())
+    }
+
+    /// `chart`, installed from its archive in the bundle
+    pub fn local_chart(&self, mut chart: Chart) -> Result<Chart> {
+        let archive = self.archive(&chart).context(format!(
+            "Bundle {} has no {} chart {}",
+            self.dir.display(),
+            chart.reference(),
+            chart.version
+        ))?;
+        chart.archive = Some(archive);
+        Ok(chart)
+    }
+
+    /// The Gateway API CRDs manifest
+    pub fn gateway_api_crds(&self) -> PathBuf {
+        self.dir.join(GATEWAY_API_CRDS_FILE)
+    }
+
+    fn archive(&self, chart: &Chart) -> Option<PathBuf> {
+        self.index
+            .charts
+            .iter()
+            .find(|bundled| {
+                bundled.release == chart.release
+                    && bundled.chart == chart.reference()
+                    && bundled.version == chart.version
+            })
+            .map(|bundled| self.dir.join(&bundled.archive))
+    }
+}
+
+/// Fail for addons installed from something other than a chart
+///
+/// The KubeVirt and Knative release manifests pull images the bundle cannot
+/// list.
+fn check_supported(config: &ClusterConfig) -> Result<()> {
+    for (addon, enabled) in [
+        ("kubevirt", config.addons.kubevirt.enabled),
+        ("knative", config.addons.knative.enabled),
+    ] {
+        if enabled {
+            anyhow::bail!(
+                "addons.{} installs from release manifests and cannot be bundled; \
+                 install it once the cluster can reach the internet",
+                addon
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Download the charts and CRDs of `config` into `out` and list its images
+pub async fn create(config: &ClusterConfig, kubeconfig_path: &Path, out: &Path) -> Result<()> {
+    check_supported(config)?;
+    CiliumManager::check_helm_installed().await?;
+
+    let mut index = Index {
+        cluster_name: config.cluster_name.clone(),
+        talos_version: config.talos.version.clone(),
+        kubernetes_version: config.talos.kubernetes_version.clone(),
+        created: Utc::now(),
+        charts: Vec::new(),
+    };
+    let mut images = BTreeSet::new();
+
+    let charts_dir = out.join(CHARTS_DIR);
+    if charts_dir.exists() {
+        std::fs::remove_dir_all(&charts_dir)
+            .context(format!("Failed to clear {}", charts_dir.display()))?;
+    }
+    for mut chart in render::charts(config, kubeconfig_path) {
+        info!(
+            "Downloading {} chart {}...",
+            chart.reference(),
+            chart.version
+        );
+        helm::add_chart_repo(kubeconfig_path, &chart).await?;
+        let archive = helm::pull(kubeconfig_path, &chart, &charts_dir.join(&chart.release)).await?;
+        chart.archive = Some(archive.clone());
+        images.extend(manifest_images(
+            &helm::template(kubeconfig_path, &chart).await?,
+        ));
+
+        let relative = archive.strip_prefix(out).unwrap_or(&archive);
+        index.charts.push(BundledChart {
+            release: chart.release.clone(),
+            chart: chart.reference(),
+            version: chart.version.clone(),
+            archive: relative.display().to_string(),
+        });
+    }
+    for (_, manifest) in AddonManager::new(config, kubeconfig_path.to_path_buf()).manifests() {
+        images.extend(manifest_images(&manifest));
+    }
+    if config.addons.image_prepull.enabled {
+        images.extend(manifest_images(&prepull::manifest(
+            &config.addons.image_prepull.images,
+        )));
+    }
+
+    info!("Downloading Gateway API CRDs...");
+    let crds = download(GATEWAY_API_CRDS).await?;
+    write(&out.join(GATEWAY_API_CRDS_FILE), &crds)?;
+
+    images.extend(talos_images(config).await?);
+    let mut listing: String = images.iter().map(|image| format!("{}\n", image)).collect();
+    listing.insert_str(
+        0,
+        &format!(
+            "# Images of {} (Talos {}, Kubernetes {}), one per line\n",
+            config.cluster_name, config.talos.version, config.talos.kubernetes_version
+        ),
+    );
+    write(&out.join(IMAGES_FILE), &listing)?;
+    write(&out.join(INDEX_FILE), &serde_yaml::to_string(&index)?)?;
+
+    info!(
+        "✓ Bundled {} charts and {} images in {}",
+        index.charts.len(),
+        images.len(),
+        out.display()
+    );
+    Ok(())
+}
+
+fn write(path: &Path, content: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+    }
+    std::fs::write(path, content).context(format!("Failed to write {}", path.display()))
+}
+
+async fn download(url: &str) -> Result<String> {
+    let client = reqwest::Client::builder()
+        .user_agent(concat!("oxide/", env!("CARGO_PKG_VERSION")))
+        .timeout(Duration::from_secs(120))
+        .build()?;
+    client
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .context(format!("Failed to download {}", url))?
+        .text()
+        .await
+        .context(format!("Failed to download {}", url))
+}
+
+/// Talos and Kubernetes images of the nodes: the talosctl defaults with the
+/// configured versions, plus the Image Factory installers of the extensions
+async fn talos_images(config: &ClusterConfig) -> Result<Vec<String>> {
+    let defaults = CommandBuilder::new("talosctl")
+        .args(["image", "default"])
+        .context("Failed to list the default Talos images")
+        .run()
+        .await?;
+    let mut images = pin_versions(
+        &defaults,
+        &config.talos.version,
+        &config.talos.kubernetes_version,
+    );
+
+    let mut extension_sets = vec![AddonManager::required_extensions(&config.addons)];
+    for pool in &config.workers {
+        extension_sets.push(AddonManager::pool_required_extensions(&config.addons, pool));
+    }
+    for extensions in &mut extension_sets {
+        extensions.splice(0..0, config.talos.extensions());
+    }
+    extension_sets.retain(|extensions| !extensions.is_empty());
+    extension_sets.sort();
+    extension_sets.dedup();
+
+    let factory = ImageFactory::default();
+    for extensions in extension_sets {
+        let factory_images = factory.resolve(&extensions, &config.talos.version).await?;
+        images.push(factory_images.installer_image);
+    }
+    Ok(images)
+}
+
+/// `talosctl image default` output with the installer at `talos_version` and
+/// the Kubernetes components at `kubernetes_version`
+fn pin_versions(defaults: &str, talos_version: &str, kubernetes_version: &str) -> Vec<String> {
+    defaults
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|image| {
+            let (repository, _) = split_tag(image);
+            let name = repository.rsplit('/').next().unwrap_or(repository);
+            if name == "installer" {
+                format!("{}:v{}", repository, talos_version.trim_start_matches('v'))
+            } else if KUBERNETES_IMAGES.contains(&name) {
+                format!(
+                    "{}:v{}",
+                    repository,
+                    kubernetes_version.trim_start_matches('v')
+                )
+            } else {
+                image.to_string()
+            }
+        })
+        .collect()
+}
+
+/// Repository and tag and/or digest of an image reference
+fn split_tag(image: &str) -> (&str, Option<&str>) {
+    let name_start = image.rfind('/').map_or(0, |i| i + 1);
+    match image[name_start..].find([':', '@']) {
+        Some(i) => (&image[..name_start + i], Some(&image[name_start + i + 1..])),
+        None => (image, None),
+    }
+}
+
+/// Images a manifest references, fully qualified
+///
+/// Besides `image` fields, images passed to a workload in its arguments or
+/// environment (as Longhorn does for its engine) are found by their shape:
+/// `path/name:tag` or a digest, without spaces or a URL scheme.
+pub fn manifest_images(manifest: &str) -> BTreeSet<String> {
+    let mut images = BTreeSet::new();
+    for document in serde_yaml::Deserializer::from_str(manifest) {
+        if let Ok(value) = serde_yaml::Value::deserialize(document) {
+            collect_images(&value, false, &mut images);
+        }
+    }
+    images
+}
+
+fn collect_images(value: &serde_yaml::Value, image_field: bool, images: &mut BTreeSet<String>) {
+    match value {
+        serde_yaml::Value::Mapping(mapping) => {
+            for (key, value) in mapping {
+                collect_images(value, key.as_str() == Some("image"), images);
+            }
+        }
+        serde_yaml::Value::Sequence(sequence) => {
+            for value in sequence {
+                collect_images(value, false, images);
+            }
+        }
+        serde_yaml::Value::String(s) => {
+            let candidate = s.rsplit('=').next().unwrap_or(s);
+            if let Some(image) = qualify_image(candidate, image_field) {
+                images.insert(image);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `candidate` as a fully qualified image, e.g. `docker.io/library/busybox:1.36`
+/// for `busybox:1.36`, or `None` if it does not look like an image
+///
+/// Outside `image` fields only references with a path and a tag or digest
+/// count.
+fn qualify_image(candidate: &str, image_field: bool) -> Option<String> {
+    if candidate.is_empty()
+        || candidate.contains(char::is_whitespace)
+        || candidate.contains("://")
+        || candidate.starts_with(['/', '-', '.', ':'])
+    {
+        return None;
+    }
+    let (repository, tag) = split_tag(candidate);
+    if !image_field && (tag.is_none() || !repository.contains('/')) {
+        return None;
+    }
+    let valid_repository = repository.split('/').enumerate().all(|(i, part)| {
+        !part.is_empty()
+            && part.chars().all(|c| {
+                c.is_ascii_lowercase()
+                    || c.is_ascii_digit()
+                    || matches!(c, '.' | '_' | '-')
+                    || (i == 0 && c == ':')
+            })
+    });
+    let valid_tag = tag.is_none_or(|tag| {
+        !tag.is_empty()
+            && tag
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | ':' | '@'))
+    });
+    if !valid_repository || !valid_tag {
+        return None;
+    }
+
+    let qualified = match repository.split_once('/') {
+        Some((host, _)) if host.contains(['.', ':']) || host == "localhost" => {
+            candidate.to_string()
+        }
+        Some(_) => format!("docker.io/{}", candidate),
+        None => format!("docker.io/library/{}", candidate),
+    };
+    Some(qualified)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_images() {
+        let manifest = r#"
+kind: DaemonSet
+spec:
+  template:
+    spec:
+      initContainers:
+        - image: busybox:1.36
+      containers:
+        - name: manager
+          image: "quay.io/cilium/cilium:v1.16.5@sha256:758ca0793f5995bb938a2fa219dcce63dc0b3fa7345f7f47b1bbff8c39b3c5d8"
+          args:
+            - --engine-image
+            - longhornio/longhorn-engine:v1.7.2
+            - --instance-manager-image=longhornio/longhorn-instance-manager:v1.7.2
+            - --socket=unix:///var/run/cilium.sock
+          env:
+            - name: LABEL
+              value: app.kubernetes.io/name
+---
+kind: ConfigMap
+data:
+  default-setting.yaml: "priority-class: longhorn-critical\n"
+  path: /var/lib/longhorn
+"#;
+        let images: Vec<String> = manifest_images(manifest).into_iter().collect();
+        assert_eq!(
+            images,
+            [
+                "docker.io/library/busybox:1.36",
+                "docker.io/longhornio/longhorn-engine:v1.7.2",
+                "docker.io/longhornio/longhorn-instance-manager:v1.7.2",
+                "quay.io/cilium/cilium:v1.16.5@sha256:758ca0793f5995bb938a2fa219dcce63dc0b3fa7345f7f47b1bbff8c39b3c5d8",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pin_versions() {
+        let defaults = "ghcr.io/siderolabs/flannel:v0.25.7\nregistry.k8s.io/coredns/coredns:v1.11.3\nregistry.k8s.io/kube-apiserver:v1.31.1\nghcr.io/siderolabs/kubelet:v1.31.1\nghcr.io/siderolabs/installer:v1.8.0\nregistry.k8s.io/pause:3.8\n";
+        let images = pin_versions(defaults, "v1.7.6", "1.30.4");
+        assert_eq!(
+            images,
+            [
+                "ghcr.io/siderolabs/flannel:v0.25.7",
+                "registry.k8s.io/coredns/coredns:v1.11.3",
+                "registry.k8s.io/kube-apiserver:v1.30.4",
+                "ghcr.io/siderolabs/kubelet:v1.30.4",
+                "ghcr.io/siderolabs/installer:v1.7.6",
+                "registry.k8s.io/pause:3.8",
+            ]
+        );
+    }
+}
@@ -0,0 +1,200 @@
+/// Preview clusters for pull requests, driven from GitHub Actions
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::Path;
+
+use crate::exit::Failure;
+
+/// Overlay naming the preview cluster, written to the output directory
+pub const OVERLAY_FILE: &str = "preview.yaml";
+
+/// Longest preview cluster name; server names append the pool and index
+/// and must stay valid hostnames
+const MAX_NAME_LEN: usize = 40;
+
+/// Longest lifetime of a preview cluster
+pub const MAX_TTL_DAYS: i64 = 7;
+
+/// What a preview cluster is built for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreviewRef {
+    PullRequest(u64),
+    Branch(String),
+}
+
+impl PreviewRef {
+    /// `--pr` or `--branch` if given, otherwise the pull request or branch of
+    /// the GitHub Actions run
+    pub fn detect(pr: Option<u64>, branch: Option<&str>) -> Result<Self> {
+        let env = |var: &str| std::env::var(var).ok().filter(|value| !value.is_empty());
+        Self::resolve(pr, branch, env)
+    }
+
+    fn resolve(
+        pr: Option<u64>,
+        branch: Option<&str>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self> {
+        if let Some(pr) = pr {
+            return Ok(Self::PullRequest(pr));
+        }
+        if let Some(branch) = branch {
+            return Ok(Self::Branch(branch.to_string()));
+        }
+        // Pull request runs check out refs/pull/<number>/merge
+        if let Some(pr) = env("GITHUB_REF")
+            .as_deref()
+            .and_then(|r| r.strip_prefix("refs/pull/"))
+            .and_then(|r| r.split('/').next())
+            .and_then(|number| number.parse().ok())
+        {
+            return Ok(Self::PullRequest(pr));
+        }
+        if let Some(branch) = env("GITHUB_HEAD_REF").or_else(|| env("GITHUB_REF_NAME")) {
+            return Ok(Self::Branch(branch));
+        }
+        anyhow::bail!(Failure::config(
+            "Not running in a GitHub Actions workflow; pass --pr or --branch"
+        ))
+    }
+
+    /// Name of the preview cluster of this ref, derived from `cluster_name`
+    pub fn cluster_name(&self, cluster_name: &str) -> Result<String> {
+        let suffix = match self {
+            Self::PullRequest(pr) => format!("pr-{}", pr),
+            Self::Branch(branch) => slug(branch),
+        };
+        if suffix.is_empty() {
+            anyhow::bail!(Failure::config(format!(
+                "{:?} gives no usable cluster name",
+                self
+            )));
+        }
+        let mut name = format!("{}-{}", cluster_name, suffix);
+        if name.len() > MAX_NAME_LEN {
+            name.truncate(MAX_NAME_LEN);
+            name = name.trim_end_matches('-').to_string();
+        }
+        Ok(name)
+    }
+}
+
+/// Lowercase letters, digits and single dashes, e.g. `feature-x` for
+/// `Feature/X`
+fn slug(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Write the overlay naming the preview cluster
+pub fn write_overlay(output_dir: &Path, cluster_name: &str) -> Result<std::path::PathBuf> {
+    std::fs::create_dir_all(output_dir)
+        .context(format!("Failed to create {}", output_dir.display()))?;
+    let path = output_dir.join(OVERLAY_FILE);
+    std::fs::write(&path, format!("cluster_name: {}\n", cluster_name))
+        .context(format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// API server URL from a kubeconfig, e.g. `https://203.0.113.10:6443`
+pub fn kubeconfig_endpoint(path: &Path) -> Option<String> {
+    let content = std::fs::read_to_string(path).ok()?;
+    let kubeconfig: serde_yaml::Value = serde_yaml::from_str(&content).ok()?;
+    kubeconfig["clusters"][0]["cluster"]["server"]
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Report step outputs: appended to the `GITHUB_OUTPUT` file in a workflow,
+/// printed as `key=value` lines elsewhere
+pub fn write_outputs(outputs: &[(&str, String)]) -> Result<()> {
+    let mut lines = String::new();
+    for (key, value) in outputs {
+        lines.push_str(&format!("{}={}\n", key, value));
+    }
+    match std::env::var_os("GITHUB_OUTPUT").filter(|path| !path.is_empty()) {
+        Some(path) => {
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .context(format!("Failed to open {}", Path::new(&path).display()))?;
+            file.write_all(lines.as_bytes())
+                .context("Failed to write the step outputs")
+        }
+        None => {
+            print!("{}", lines);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        move |var| {
+            vars.iter()
+                .find(|(name, _)| *name == var)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    #[test]
+    fn test_resolve_ref() {
+        let pull_request = [
+            ("GITHUB_REF", "refs/pull/123/merge"),
+            ("GITHUB_HEAD_REF", "feature/x"),
+        ];
+        assert_eq!(
+            PreviewRef::resolve(None, None, env(&pull_request)).unwrap(),
+            PreviewRef::PullRequest(123)
+        );
+        assert_eq!(
+            PreviewRef::resolve(Some(7), None, env(&pull_request)).unwrap(),
+            PreviewRef::PullRequest(7)
+        );
+
+        let push = [
+            ("GITHUB_REF", "refs/heads/main"),
+            ("GITHUB_REF_NAME", "main"),
+        ];
+        assert_eq!(
+            PreviewRef::resolve(None, None, env(&push)).unwrap(),
+            PreviewRef::Branch("main".to_string())
+        );
+        assert!(PreviewRef::resolve(None, None, env(&[])).is_err());
+    }
+
+    #[test]
+    fn test_cluster_name() {
+        assert_eq!(
+            PreviewRef::PullRequest(123)
+                .cluster_name("preview")
+                .unwrap(),
+            "preview-pr-123"
+        );
+        assert_eq!(
+            PreviewRef::Branch("Feature/Add_Login--Page".to_string())
+                .cluster_name("preview")
+                .unwrap(),
+            "preview-feature-add-login-page"
+        );
+        let long = PreviewRef::Branch("renovate/some-very-long-dependency-name-1.2.3".to_string())
+            .cluster_name("preview")
+            .unwrap();
+        assert_eq!(long, "preview-renovate-some-very-long-dependen");
+        assert!(long.len() <= MAX_NAME_LEN);
+        assert!(PreviewRef::Branch("///".to_string())
+            .cluster_name("preview")
+            .is_err());
+    }
+}
@@ -0,0 +1,150 @@
+/// Cilium egress gateway for workloads that need a stable source IP
+use anyhow::Result;
+use std::path::Path;
+use tracing::info;
+
+use crate::config::ClusterConfig;
+use crate::k8s::ResourceManager;
+
+/// Node label selecting the egress gateway nodes
+pub const NODE_LABEL: &str = "egress-gateway.oxide.io/node";
+
+/// Public interface of Hetzner Cloud servers under Talos
+const PUBLIC_INTERFACE: &str = "eth0";
+
+/// Machine config patch of the egress gateway pools
+///
+/// With a floating IP the address is added to the public interface, since
+/// Cilium only masquerades to addresses configured on the gateway node.
+pub fn machine_config_patch(egress_ip: Option<&str>) -> String {
+    let mut patch = serde_json::json!({
+        "machine": { "nodeLabels": { NODE_LABEL: "true" } }
+    });
+    if let Some(ip) = egress_ip {
+        patch["machine"]["network"] = serde_json::json!({
+            "interfaces": [{
+                "interface": PUBLIC_INTERFACE,
+                "dhcp": true,
+                "addresses": [format!("{}/32", ip)],
+            }]
+        });
+    }
+    serde_yaml::to_string(&patch).expect("egress gateway patch serializes")
+}
+
+/// Multi-document manifest of the CiliumEgressGatewayPolicies, if an egress
+/// gateway is configured
+///
+/// Traffic to the cluster networks stays on the private network.
+pub fn manifest(config: &ClusterConfig, egress_ip: Option<&str>) -> Option<String> {
+    let egress = config.cilium.egress_gateway.as_ref()?;
+    let excluded: Vec<&str> = std::iter::once(&config.hcloud.network)
+        .chain(&config.hcloud.regions)
+        .filter(|network| !network.is_auto())
+        .map(|network| network.cidr.as_str())
+        .collect();
+    let mut gateway = serde_json::json!({
+        "nodeSelector": { "matchLabels": { NODE_LABEL: "true" } }
+    });
+    if let Some(ip) = egress_ip {
+        gateway["egressIP"] = ip.into();
+    }
+
+    let documents: Vec<String> = egress
+        .policies
+        .iter()
+        .map(|policy| {
+            let selectors: Vec<_> = policy
+                .namespaces
+                .iter()
+                .map(|namespace| {
+                    let mut labels =
+                        serde_json::json!({ "io.kubernetes.pod.namespace": namespace });
+                    for (key, value) in &policy.pod_labels {
+                        labels[key] = value.as_str().into();
+                    }
+                    serde_json::json!({ "podSelector": { "matchLabels": labels } })
+                })
+                .collect();
+            let document = serde_json::json!({
+                "apiVersion": "cilium.io/v2",
+                "kind": "CiliumEgressGatewayPolicy",
+                "metadata": { "name": policy.name },
+                "spec": {
+                    "selectors": selectors,
+                    "destinationCIDRs": policy.destination_cidrs,
+                    "excludedCIDRs": excluded,
+                    "egressGateway": gateway,
+                },
+            });
+            serde_yaml::to_string(&document).expect("egress gateway policy serializes")
+        })
+        .collect();
+    Some(documents.join("---\n"))
+}
+
+/// Apply the policies if an egress gateway is configured
+pub async fn apply(
+    kubeconfig_path: &Path,
+    config: &ClusterConfig,
+    egress_ip: Option<&str>,
+) -> Result<()> {
+    let Some(manifest) = manifest(config, egress_ip) else {
+        return Ok(());
+    };
+    info!("Applying egress gateway policies...");
+    ResourceManager::apply_stdin(kubeconfig_path, &manifest).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_machine_config_patch() {
+        let patch: serde_yaml::Value = serde_yaml::from_str(&machine_config_patch(None)).unwrap();
+        assert_eq!(patch["machine"]["nodeLabels"][NODE_LABEL], "true");
+        assert!(patch["machine"].get("network").is_none());
+
+        let patch: serde_yaml::Value =
+            serde_yaml::from_str(&machine_config_patch(Some("198.51.100.7"))).unwrap();
+        let interface = &patch["machine"]["network"]["interfaces"][0];
+        assert_eq!(interface["interface"], "eth0");
+        assert_eq!(interface["addresses"][0], "198.51.100.7/32");
+    }
+
+    #[test]
+    fn test_manifest() {
+        let mut config = ClusterConfig::example();
+        assert!(manifest(&config, None).is_none());
+
+        config.cilium.egress_gateway = Some(
+            serde_yaml::from_str(
+                "policies:\n  - name: payments\n    namespaces: [shop, billing]\n    pod_labels:\n      app: api\n    destination_cidrs: [203.0.113.0/24]\n  - name: all\n    namespaces: [batch]\n",
+            )
+            .unwrap(),
+        );
+        let documents: Vec<serde_yaml::Value> = manifest(&config, Some("198.51.100.7"))
+            .unwrap()
+            .split("---\n")
+            .map(|document| serde_yaml::from_str(document).unwrap())
+            .collect();
+        assert_eq!(documents.len(), 2);
+
+        let spec = &documents[0]["spec"];
+        let labels = &spec["selectors"][1]["podSelector"]["matchLabels"];
+        assert_eq!(labels["io.kubernetes.pod.namespace"], "billing");
+        assert_eq!(labels["app"], "api");
+        assert_eq!(spec["destinationCIDRs"][0], "203.0.113.0/24");
+        assert_eq!(
+            spec["excludedCIDRs"][0],
+            config.hcloud.network.cidr.as_str()
+        );
+        assert_eq!(spec["egressGateway"]["egressIP"], "198.51.100.7");
+        assert_eq!(
+            spec["egressGateway"]["nodeSelector"]["matchLabels"][NODE_LABEL],
+            "true"
+        );
+        assert_eq!(documents[1]["spec"]["destinationCIDRs"][0], "0.0.0.0/0");
+    }
+}
@@ -1,16 +1,33 @@
 /// Cilium CNI deployment and management
-use anyhow::Result;
+pub mod egress;
+pub mod policies;
+
+use anyhow::{Context, Result};
 use tracing::info;
 
-use crate::config::CiliumConfig;
+use crate::audit;
+use crate::bundle::Bundle;
+use crate::config::{CiliumConfig, HelmMode};
 use crate::utils::command::CommandBuilder;
+use crate::utils::helm;
 use crate::utils::polling::PollingConfig;
 
+/// Pod MTU when node traffic is carried by KubeSpan
+const KUBESPAN_POD_MTU: u32 = 1370;
+
+/// Gateway API CRDs installed before Cilium, which implements the Gateway API
+pub const GATEWAY_API_CRDS: &str = "https://github.com/kubernetes-sigs/gateway-api/releases/download/v1.3.0/experimental-install.yaml";
+
 /// Cilium deployment manager
 pub struct CiliumManager {
     config: CiliumConfig,
     kubeconfig_path: std::path::PathBuf,
     control_plane_count: u32,
+    helm_mode: HelmMode,
+    kubespan: bool,
+    cloud_load_balancers: bool,
+    istio: bool,
+    bundle: Option<Bundle>,
 }
 
 impl CiliumManager {
@@ -24,9 +41,46 @@ impl CiliumManager {
             config,
             kubeconfig_path,
             control_plane_count,
+            helm_mode: HelmMode::default(),
+            kubespan: false,
+            cloud_load_balancers: false,
+            istio: false,
+            bundle: None,
         }
     }
 
+    /// Set how the Cilium chart is installed
+    pub fn with_helm_mode(mut self, mode: HelmMode) -> Self {
+        self.helm_mode = mode;
+        self
+    }
+
+    /// Size the datapath for node traffic running through KubeSpan
+    pub fn with_kubespan(mut self, enabled: bool) -> Self {
+        self.kubespan = enabled;
+        self
+    }
+
+    /// Leave LoadBalancer Services without a `loadBalancerClass` to the
+    /// hcloud cloud-controller-manager, which backs them with Hetzner load
+    /// balancers; node IPAM then only serves the `io.cilium/node` class
+    pub fn with_cloud_load_balancers(mut self, enabled: bool) -> Self {
+        self.cloud_load_balancers = enabled;
+        self
+    }
+
+    /// Leave room for the Istio CNI plugin and ztunnel of an ambient mesh
+    pub fn with_istio(mut self, enabled: bool) -> Self {
+        self.istio = enabled;
+        self
+    }
+
+    /// Install the chart and Gateway API CRDs from an offline bundle
+    pub fn with_bundle(mut self, bundle: Option<Bundle>) -> Self {
+        self.bundle = bundle;
+        self
+    }
+
     /// Check if helm is installed
     pub async fn check_helm_installed() -> Result<()> {
         crate::utils::command::check_tool_installed(
@@ -42,7 +96,9 @@ impl CiliumManager {
         info!("Installing Cilium CNI version {}...", self.config.version);
 
         // Install Gateway API CRDs first
-        self.install_gateway_api_crds().await?;
+        if self.config.gateway_api {
+            self.install_gateway_api_crds().await?;
+        }
 
         // Add Cilium Helm repository
         self.add_helm_repo().await?;
@@ -59,16 +115,19 @@ impl CiliumManager {
     async fn install_gateway_api_crds(&self) -> Result<()> {
         info!("Installing Gateway API CRDs...");
 
-        CommandBuilder::new("kubectl")
-            .args([
-                "apply",
-                "-f",
-                "https://github.com/kubernetes-sigs/gateway-api/releases/download/v1.3.0/experimental-install.yaml",
-            ])
+        let bundled = self.bundle.as_ref().map(Bundle::gateway_api_crds);
+        let manifest = match &bundled {
+            Some(path) => path.to_str().context("Bundle path is not valid UTF-8")?,
+            None => GATEWAY_API_CRDS,
+        };
+        let result = CommandBuilder::new("kubectl")
+            .args(["apply", "-f", manifest])
             .kubeconfig(&self.kubeconfig_path)
             .context("Failed to install Gateway API CRDs")
             .run_silent()
-            .await?;
+            .await;
+        audit::record("kubectl.apply", manifest, &[], &result);
+        result?;
 
         info!("Gateway API CRDs installed successfully");
         Ok(())
@@ -78,132 +137,138 @@ impl CiliumManager {
     async fn add_helm_repo(&self) -> Result<()> {
         info!("Adding Cilium Helm repository...");
 
-        let output = CommandBuilder::new("helm")
-            .args(["repo", "add", "cilium", "https://helm.cilium.io/"])
-            .kubeconfig(&self.kubeconfig_path)
-            .context("Failed to add Cilium Helm repo")
-            .output()
-            .await?;
-
-        if !output.success {
-            // Ignore "already exists" errors
-            if !output.stderr.contains("already exists") {
-                anyhow::bail!("Failed to add Helm repo: {}", output.stderr);
-            }
-        }
-
-        // Update Helm repositories
-        CommandBuilder::new("helm")
-            .args(["repo", "update"])
-            .kubeconfig(&self.kubeconfig_path)
-            .context("Failed to update Helm repos")
-            .run_silent()
-            .await?;
-
-        Ok(())
+        helm::add_chart_repo(&self.kubeconfig_path, &self.installed_chart()?).await
     }
 
     /// Install Cilium Helm chart
     async fn install_cilium_chart(&self) -> Result<()> {
         info!("Installing Cilium Helm chart...");
 
+        helm::upgrade_install(
+            &self.kubeconfig_path,
+            self.helm_mode,
+            &self.installed_chart()?,
+        )
+        .await
+    }
+
+    /// The chart as installed: from the bundle if there is one
+    fn installed_chart(&self) -> Result<helm::Chart> {
+        match &self.bundle {
+            Some(bundle) => bundle.local_chart(self.chart()),
+            None => Ok(self.chart()),
+        }
+    }
+
+    /// The Cilium chart with the values for this cluster
+    pub fn chart(&self) -> helm::Chart {
+        helm::Chart {
+            release: "cilium".to_string(),
+            repo_name: "cilium".to_string(),
+            repo_url: "https://helm.cilium.io/".to_string(),
+            name: "cilium".to_string(),
+            version: self.config.version.clone(),
+            namespace: "kube-system".to_string(),
+            values: self.chart_values(),
+            privileged: false,
+            archive: None,
+        }
+    }
+
+    /// `--set` values for the Cilium chart
+    pub fn chart_values(&self) -> Vec<String> {
         // Set operator replicas: 2 if we have multiple control planes, 1 otherwise
         let operator_replicas = if self.control_plane_count > 1 {
             "2"
         } else {
             "1"
         };
-        let operator_replicas_arg = format!("operator.replicas={}", operator_replicas);
-
-        let mut args = vec![
-            "install",
-            "cilium",
-            "cilium/cilium",
-            "--version",
-            &self.config.version,
-            "--namespace",
-            "kube-system",
-            "--set",
-            "ipam.mode=kubernetes",
-            "--set",
-            "kubeProxyReplacement=true",
-            "--set",
-            "securityContext.capabilities.ciliumAgent={CHOWN,KILL,NET_ADMIN,NET_RAW,IPC_LOCK,SYS_ADMIN,SYS_RESOURCE,DAC_OVERRIDE,FOWNER,SETGID,SETUID}",
-            "--set",
-            "securityContext.capabilities.cleanCiliumState={NET_ADMIN,SYS_ADMIN,SYS_RESOURCE}",
-            "--set",
-            "cgroup.autoMount.enabled=false",
-            "--set",
-            "cgroup.hostRoot=/sys/fs/cgroup",
-            "--set",
-            &operator_replicas_arg,
+
+        let mut values = vec![
+            "ipam.mode=kubernetes".to_string(),
+            "kubeProxyReplacement=true".to_string(),
+            "securityContext.capabilities.ciliumAgent={CHOWN,KILL,NET_ADMIN,NET_RAW,IPC_LOCK,SYS_ADMIN,SYS_RESOURCE,DAC_OVERRIDE,FOWNER,SETGID,SETUID}".to_string(),
+            "securityContext.capabilities.cleanCiliumState={NET_ADMIN,SYS_ADMIN,SYS_RESOURCE}".to_string(),
+            "cgroup.autoMount.enabled=false".to_string(),
+            "cgroup.hostRoot=/sys/fs/cgroup".to_string(),
+            format!("operator.replicas={}", operator_replicas),
         ];
 
         // Add Hubble settings
         if self.config.enable_hubble {
-            args.extend_from_slice(&[
-                "--set",
+            values.extend([
                 "hubble.enabled=true",
-                "--set",
                 "hubble.relay.enabled=true",
-                "--set",
                 "hubble.ui.enabled=true",
-                "--set",
                 "hubble.metrics.enabled={dns,drop,tcp,flow,port-distribution,icmp,httpV2:exemplars=true;labelsContext=source_ip\\,source_namespace\\,source_workload\\,destination_ip\\,destination_namespace\\,destination_workload\\,traffic_direction}",
-            ]);
+            ].map(String::from));
         } else {
-            args.extend_from_slice(&["--set", "hubble.enabled=false"]);
+            values.push("hubble.enabled=false".to_string());
         }
 
         // Enable Prometheus metrics
-        args.extend_from_slice(&[
-            "--set",
-            "prometheus.enabled=true",
-            "--set",
-            "operator.prometheus.enabled=true",
-        ]);
+        values.extend(
+            [
+                "prometheus.enabled=true",
+                "operator.prometheus.enabled=true",
+            ]
+            .map(String::from),
+        );
 
         // Add IPv6 settings if enabled
+        // Dual-stack: pod IPv6 CIDRs come from the Talos podSubnets via the
+        // kubernetes IPAM mode, so only the datapath needs enabling here
         if self.config.enable_ipv6 {
-            args.extend_from_slice(&["--set", "ipv6.enabled=true"]);
+            values.extend(["ipv6.enabled=true", "enableIPv6Masquerade=true"].map(String::from));
+        }
+
+        // Egress gateway policies need BPF masquerading, which is always on
+        if self.config.egress_gateway.is_some() {
+            values.push("egressGateway.enabled=true".to_string());
         }
 
         // Enable Gateway API support
-        args.extend_from_slice(&["--set", "gatewayAPI.enabled=true"]);
+        values.push(format!("gatewayAPI.enabled={}", self.config.gateway_api));
 
         // Configure KubePrism for API server access (Talos-specific)
-        args.extend_from_slice(&[
-            "--set",
-            "k8sServiceHost=localhost",
-            "--set",
-            "k8sServicePort=7445",
-        ]);
+        values.extend(["k8sServiceHost=localhost", "k8sServicePort=7445"].map(String::from));
 
         // Enable Node IPAM for LoadBalancer services with tunnel mode
         // Hetzner private network requires gateway routing, so use VXLAN tunnel for pod traffic
-        args.extend_from_slice(&[
-            "--set",
-            "nodeIPAM.enabled=true",
-            "--set",
-            "tunnelProtocol=vxlan",
-            "--set",
-            "autoDirectNodeRoutes=false",
-            "--set",
-            "bpf.masquerade=true",
-            "--set",
-            "loadBalancer.acceleration=native",
-            "--set",
-            "defaultLBServiceIPAM=nodeipam",
-        ]);
-
-        CommandBuilder::new("helm")
-            .args(&args)
-            .kubeconfig(&self.kubeconfig_path)
-            .context("Failed to install Cilium")
-            .run_silent()
-            .await?;
+        values.extend(
+            [
+                "nodeIPAM.enabled=true",
+                "tunnelProtocol=vxlan",
+                "autoDirectNodeRoutes=false",
+                "bpf.masquerade=true",
+                "loadBalancer.acceleration=native",
+            ]
+            .map(String::from),
+        );
+        let default_ipam = if self.cloud_load_balancers {
+            "none"
+        } else {
+            "nodeipam"
+        };
+        values.push(format!("defaultLBServiceIPAM={}", default_ipam));
+
+        // Istio chains its CNI plugin after Cilium's, which must not remove
+        // it, and ztunnel redirects traffic inside the pod network namespace,
+        // where socket load balancing would already have translated the
+        // service addresses
+        if self.istio {
+            values.extend(
+                ["cni.exclusive=false", "socketLB.hostNamespaceOnly=true"].map(String::from),
+            );
+        }
 
-        Ok(())
+        // VXLAN inside the KubeSpan WireGuard interface (MTU 1420) leaves 1370
+        // bytes; the auto-detected MTU of the private interface is too large
+        if self.kubespan {
+            values.push(format!("MTU={}", KUBESPAN_POD_MTU));
+        }
+
+        values
     }
 
     /// Wait for Cilium to be ready
@@ -268,6 +333,41 @@ impl CiliumManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::ClusterConfig;
+
+    #[test]
+    fn test_load_balancer_values() {
+        let mut config = ClusterConfig::example().cilium;
+        let values = CiliumManager::new(config.clone(), "kubeconfig".into(), 1).chart_values();
+        assert!(values.contains(&"gatewayAPI.enabled=true".to_string()));
+        assert!(values.contains(&"defaultLBServiceIPAM=nodeipam".to_string()));
+
+        config.gateway_api = false;
+        let values = CiliumManager::new(config, "kubeconfig".into(), 1)
+            .with_cloud_load_balancers(true)
+            .chart_values();
+        assert!(values.contains(&"gatewayAPI.enabled=false".to_string()));
+        assert!(values.contains(&"defaultLBServiceIPAM=none".to_string()));
+        assert!(values.contains(&"nodeIPAM.enabled=true".to_string()));
+    }
+
+    #[test]
+    fn test_istio_values() {
+        let config = ClusterConfig::example().cilium;
+        let values = CiliumManager::new(config, "kubeconfig".into(), 1)
+            .with_istio(true)
+            .chart_values();
+        assert!(values.contains(&"cni.exclusive=false".to_string()));
+        assert!(values.contains(&"socketLB.hostNamespaceOnly=true".to_string()));
+    }
+
+    #[test]
+    fn test_no_istio_values_without_istio() {
+        let config = ClusterConfig::example().cilium;
+        let values = CiliumManager::new(config, "kubeconfig".into(), 1).chart_values();
+        assert!(!values.contains(&"cni.exclusive=false".to_string()));
+        assert!(!values.contains(&"socketLB.hostNamespaceOnly=true".to_string()));
+    }
 
     #[tokio::test]
     async fn test_check_tools() {
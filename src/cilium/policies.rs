@@ -0,0 +1,158 @@
+/// Default-deny CiliumNetworkPolicies applied after Cilium is ready
+use anyhow::Result;
+use std::path::Path;
+use tracing::info;
+
+use crate::config::DefaultDenyConfig;
+use crate::k8s::ResourceManager;
+
+/// Namespaces of Kubernetes, Cilium and the oxide addons, never locked down
+const SYSTEM_NAMESPACES: &[&str] = &[
+    "kube-system",
+    "kube-public",
+    "kube-node-lease",
+    "longhorn-system",
+    "nvidia-device-plugin",
+    "spegel",
+    "istio-system",
+    "kubevirt",
+    "cdi",
+    "knative-serving",
+    "vpa",
+    "headlamp",
+    "oxide-audit",
+    "oxide-smoke-test",
+];
+
+/// Pod label Cilium derives from the namespace of a pod
+const NAMESPACE_LABEL: &str = "k8s:io.kubernetes.pod.namespace";
+
+/// Multi-document manifest of the policies for `config`
+pub fn manifest(config: &DefaultDenyConfig) -> String {
+    let mut exempt: Vec<&str> = SYSTEM_NAMESPACES.to_vec();
+    exempt.extend(config.exempt_namespaces.iter().map(String::as_str));
+    let locked_down = serde_json::json!({
+        "matchExpressions": [{
+            "key": NAMESPACE_LABEL,
+            "operator": "NotIn",
+            "values": exempt,
+        }]
+    });
+
+    let mut documents = vec![
+        serde_json::json!({
+            "apiVersion": "cilium.io/v2",
+            "kind": "CiliumClusterwideNetworkPolicy",
+            "metadata": { "name": "oxide-default-deny" },
+            "spec": {
+                "endpointSelector": locked_down,
+                "ingress": [{}],
+                "egress": [{}],
+            },
+        }),
+        serde_json::json!({
+            "apiVersion": "cilium.io/v2",
+            "kind": "CiliumClusterwideNetworkPolicy",
+            "metadata": { "name": "oxide-allow-kube-dns" },
+            "spec": {
+                "endpointSelector": locked_down,
+                "egress": [{
+                    "toEndpoints": [{
+                        "matchLabels": {
+                            NAMESPACE_LABEL: "kube-system",
+                            "k8s-app": "kube-dns",
+                        }
+                    }],
+                    "toPorts": [{
+                        "ports": [
+                            { "port": "53", "protocol": "UDP" },
+                            { "port": "53", "protocol": "TCP" },
+                        ],
+                        "rules": { "dns": [{ "matchPattern": "*" }] },
+                    }],
+                }],
+            },
+        }),
+    ];
+
+    for namespace in &config.allow_same_namespace {
+        if namespace != "default" {
+            documents.push(serde_json::json!({
+                "apiVersion": "v1",
+                "kind": "Namespace",
+                "metadata": { "name": namespace },
+            }));
+        }
+        documents.push(serde_json::json!({
+            "apiVersion": "cilium.io/v2",
+            "kind": "CiliumNetworkPolicy",
+            "metadata": { "name": "oxide-allow-same-namespace", "namespace": namespace },
+            "spec": {
+                "endpointSelector": {},
+                "ingress": [{ "fromEndpoints": [{}] }],
+                "egress": [{ "toEndpoints": [{}] }],
+            },
+        }));
+    }
+
+    documents
+        .iter()
+        .map(|document| serde_yaml::to_string(document).expect("policy serializes"))
+        .collect::<Vec<_>>()
+        .join("---\n")
+}
+
+/// Apply the policies if `config` enables them
+pub async fn apply(kubeconfig_path: &Path, config: &DefaultDenyConfig) -> Result<()> {
+    if !config.enabled {
+        return Ok(());
+    }
+    info!("Applying default-deny network policies...");
+    ResourceManager::apply_stdin(kubeconfig_path, &manifest(config)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest() {
+        let config = DefaultDenyConfig {
+            enabled: true,
+            allow_same_namespace: vec!["default".to_string(), "shop".to_string()],
+            exempt_namespaces: vec!["monitoring".to_string()],
+        };
+        let documents: Vec<serde_yaml::Value> = manifest(&config)
+            .split("---\n")
+            .map(|document| serde_yaml::from_str(document).unwrap())
+            .collect();
+
+        let kinds: Vec<&str> = documents
+            .iter()
+            .map(|document| document["kind"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                "CiliumClusterwideNetworkPolicy",
+                "CiliumClusterwideNetworkPolicy",
+                "CiliumNetworkPolicy",
+                "Namespace",
+                "CiliumNetworkPolicy",
+            ]
+        );
+
+        let exempt = &documents[0]["spec"]["endpointSelector"]["matchExpressions"][0]["values"];
+        let exempt: Vec<&str> = exempt
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .filter_map(|namespace| namespace.as_str())
+            .collect();
+        assert!(exempt.contains(&"kube-system"));
+        assert!(exempt.contains(&"monitoring"));
+
+        assert_eq!(documents[3]["metadata"]["name"], "shop");
+        assert_eq!(documents[4]["metadata"]["namespace"], "shop");
+    }
+}
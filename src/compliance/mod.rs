@@ -0,0 +1,525 @@
+/// Security audit of a running cluster
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tracing::{info, warn};
+
+use crate::k8s::ResourceManager;
+use crate::utils::command::CommandBuilder;
+
+/// Namespace of the kube-bench Jobs, deleted afterwards
+const NAMESPACE: &str = "oxide-audit";
+
+const KUBE_BENCH_IMAGE: &str = "docker.io/aquasec/kube-bench:v0.10.4";
+
+/// Outcome of one control
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pass,
+    Fail,
+    Warn,
+}
+
+/// Result of one control on one node or role
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    /// `kube-bench` or `talos`
+    pub source: &'static str,
+    /// CIS control number, or the name of the Talos check
+    pub id: String,
+    /// Node name, or the role kube-bench ran as
+    pub node: String,
+    pub description: String,
+    pub status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remediation: Option<String>,
+}
+
+impl Finding {
+    fn talos(node: &str, id: &str, description: &str, failed: Option<(Status, &str)>) -> Self {
+        let (status, remediation) = match failed {
+            Some((status, remediation)) => (status, Some(remediation.to_string())),
+            None => (Status::Pass, None),
+        };
+        Self {
+            source: "talos",
+            id: id.to_string(),
+            node: node.to_string(),
+            description: description.to_string(),
+            status,
+            remediation,
+        }
+    }
+}
+
+/// Check the machine config a node runs
+pub fn talos_checks(node: &str, machine_config: &str) -> Vec<Finding> {
+    let config = serde_yaml::Deserializer::from_str(machine_config)
+        .filter_map(|document| serde_yaml::Value::deserialize(document).ok())
+        .find(|document| document.get("machine").is_some())
+        .unwrap_or_default();
+    let machine = &config["machine"];
+    let cluster = &config["cluster"];
+    let control_plane = machine["type"].as_str() == Some("controlplane");
+    let mut findings = Vec::new();
+
+    let rbac = machine["features"]["rbac"].as_bool() == Some(true);
+    findings.push(Finding::talos(
+        node,
+        "talos.api-rbac",
+        "Talos API enforces role-based access",
+        (!rbac).then_some((
+            Status::Fail,
+            "Set machine.features.rbac: true and issue talosconfigs with restricted roles",
+        )),
+    ));
+
+    let kubelet_anonymous =
+        machine["kubelet"]["extraArgs"]["anonymous-auth"].as_str() == Some("true");
+    findings.push(Finding::talos(
+        node,
+        "talos.kubelet-anonymous-auth",
+        "Kubelet rejects anonymous requests",
+        kubelet_anonymous.then_some((
+            Status::Fail,
+            "Remove anonymous-auth from machine.kubelet.extraArgs",
+        )),
+    ));
+
+    let insecure_registry =
+        machine["registries"]["config"]
+            .as_mapping()
+            .is_some_and(|registries| {
+                registries
+                    .values()
+                    .any(|registry| registry["tls"]["insecureSkipVerify"].as_bool() == Some(true))
+            });
+    findings.push(Finding::talos(
+        node,
+        "talos.registry-tls",
+        "Image registries are verified with TLS",
+        insecure_registry.then_some((
+            Status::Warn,
+            "Give the registry mirror a trusted certificate and drop talos.registry_mirror.insecure_skip_verify",
+        )),
+    ));
+
+    let talos_api_admin = machine["features"]["kubernetesTalosAPIAccess"]["allowedRoles"]
+        .as_sequence()
+        .is_some_and(|roles| roles.iter().any(|role| role.as_str() == Some("os:admin")));
+    findings.push(Finding::talos(
+        node,
+        "talos.kubernetes-talos-api-access",
+        "Pods cannot get admin access to the Talos API",
+        talos_api_admin.then_some((
+            Status::Warn,
+            "Remove os:admin from machine.features.kubernetesTalosAPIAccess.allowedRoles",
+        )),
+    ));
+
+    if !control_plane {
+        return findings;
+    }
+
+    let enforce = cluster["apiServer"]["admissionControl"]
+        .as_sequence()
+        .and_then(|plugins| {
+            plugins
+                .iter()
+                .find(|plugin| plugin["name"].as_str() == Some("PodSecurity"))
+        })
+        .and_then(|plugin| plugin["configuration"]["defaults"]["enforce"].as_str());
+    findings.push(Finding::talos(
+        node,
+        "talos.pod-security",
+        "PodSecurity admission enforces baseline or restricted by default",
+        matches!(enforce, None | Some("privileged")).then_some((
+            Status::Fail,
+            "Keep the PodSecurity plugin in cluster.apiServer.admissionControl with defaults.enforce: baseline",
+        )),
+    ));
+
+    let audit_policy = cluster["apiServer"]["auditPolicy"]["rules"]
+        .as_sequence()
+        .is_some_and(|rules| !rules.is_empty());
+    findings.push(Finding::talos(
+        node,
+        "talos.audit-policy",
+        "API server writes an audit log",
+        (!audit_policy).then_some((
+            Status::Fail,
+            "Set cluster.apiServer.auditPolicy with at least one rule",
+        )),
+    ));
+
+    let encrypted = ["secretboxEncryptionSecret", "aescbcEncryptionSecret"]
+        .iter()
+        .any(|key| {
+            cluster[*key]
+                .as_str()
+                .is_some_and(|secret| !secret.is_empty())
+        });
+    findings.push(Finding::talos(
+        node,
+        "talos.secrets-encryption",
+        "Secrets are encrypted at rest in etcd",
+        (!encrypted).then_some((
+            Status::Fail,
+            "Set cluster.secretboxEncryptionSecret (talosctl gen config sets it by default)",
+        )),
+    ));
+
+    let apiserver_anonymous =
+        cluster["apiServer"]["extraArgs"]["anonymous-auth"].as_str() == Some("true");
+    findings.push(Finding::talos(
+        node,
+        "talos.apiserver-anonymous-auth",
+        "API server rejects anonymous requests",
+        apiserver_anonymous.then_some((
+            Status::Fail,
+            "Remove anonymous-auth from cluster.apiServer.extraArgs",
+        )),
+    ));
+
+    let shared = cluster["allowSchedulingOnControlPlanes"].as_bool() == Some(true);
+    findings.push(Finding::talos(
+        node,
+        "talos.control-plane-isolation",
+        "Regular workloads do not run on control planes",
+        shared.then_some((
+            Status::Warn,
+            "Add worker pools and set allow_workloads: false on the control plane pools",
+        )),
+    ));
+
+    findings
+}
+
+/// `kube-bench run --json` output
+#[derive(Debug, Deserialize)]
+struct KubeBenchOutput {
+    #[serde(rename = "Controls", default)]
+    controls: Vec<KubeBenchControls>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KubeBenchControls {
+    #[serde(default)]
+    tests: Vec<KubeBenchSection>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KubeBenchSection {
+    #[serde(default)]
+    results: Vec<KubeBenchResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KubeBenchResult {
+    test_number: String,
+    test_desc: String,
+    #[serde(default)]
+    remediation: String,
+    status: String,
+}
+
+/// Findings from the logs of a kube-bench Job; INFO results are dropped
+///
+/// The JSON report starts at the first line opening an object, after any
+/// warnings kube-bench logged.
+pub fn parse_kube_bench(role: &str, logs: &str) -> Result<Vec<Finding>> {
+    let start = logs
+        .lines()
+        .position(|line| line.starts_with('{'))
+        .context("kube-bench printed no JSON report")?;
+    let report: String = logs.lines().skip(start).collect::<Vec<_>>().join("\n");
+    let output: KubeBenchOutput = serde_json::Deserializer::from_str(&report)
+        .into_iter::<KubeBenchOutput>()
+        .next()
+        .context("kube-bench printed no JSON report")?
+        .context("Invalid kube-bench report")?;
+
+    Ok(output
+        .controls
+        .into_iter()
+        .flat_map(|controls| controls.tests)
+        .flat_map(|section| section.results)
+        .filter_map(|result| {
+            let status = match result.status.as_str() {
+                "PASS" => Status::Pass,
+                "FAIL" => Status::Fail,
+                "WARN" => Status::Warn,
+                _ => return None,
+            };
+            let remediation = result.remediation.trim();
+            Some(Finding {
+                source: "kube-bench",
+                id: result.test_number,
+                node: role.to_string(),
+                description: result.test_desc,
+                status,
+                remediation: (status != Status::Pass && !remediation.is_empty())
+                    .then(|| remediation.to_string()),
+            })
+        })
+        .collect())
+}
+
+/// Run kube-bench on a control plane and, if there are any, a worker
+///
+/// The Jobs' namespace is deleted afterwards, whether they succeeded or not.
+pub async fn kube_bench(
+    kubeconfig_path: &Path,
+    has_workers: bool,
+    timeout_secs: u64,
+) -> Result<Vec<Finding>> {
+    ResourceManager::ensure_privileged_namespace(kubeconfig_path, NAMESPACE).await?;
+    let result = run_kube_bench(kubeconfig_path, has_workers, timeout_secs).await;
+
+    let cleanup = CommandBuilder::new("kubectl")
+        .args([
+            "delete",
+            "namespace",
+            NAMESPACE,
+            "--ignore-not-found",
+            "--wait=false",
+        ])
+        .kubeconfig(kubeconfig_path)
+        .context(format!("Failed to delete namespace {}", NAMESPACE))
+        .run_silent()
+        .await;
+    if let Err(e) = cleanup {
+        warn!("⚠️  {:#}", e);
+    }
+    result
+}
+
+async fn run_kube_bench(
+    kubeconfig_path: &Path,
+    has_workers: bool,
+    timeout_secs: u64,
+) -> Result<Vec<Finding>> {
+    let mut jobs = vec![("control-plane", "master,etcd,controlplane,policies", true)];
+    jobs.push(("worker", "node", !has_workers));
+
+    for (role, targets, on_control_plane) in &jobs {
+        ResourceManager::apply_stdin(
+            kubeconfig_path,
+            &job_manifest(role, targets, *on_control_plane),
+        )
+        .await?;
+    }
+
+    let mut findings = Vec::new();
+    for (role, _, _) in &jobs {
+        let job = format!("job/kube-bench-{}", role);
+        info!("Waiting for kube-bench on a {} node...", role);
+        CommandBuilder::new("kubectl")
+            .args([
+                "wait",
+                "--for=condition=complete",
+                &job,
+                "-n",
+                NAMESPACE,
+                &format!("--timeout={}s", timeout_secs),
+            ])
+            .kubeconfig(kubeconfig_path)
+            .context(format!("kube-bench did not complete on a {} node", role))
+            .run_silent()
+            .await?;
+
+        let logs = CommandBuilder::new("kubectl")
+            .args(["logs", &job, "-n", NAMESPACE])
+            .kubeconfig(kubeconfig_path)
+            .context("Failed to read the kube-bench report")
+            .run()
+            .await?;
+        findings.extend(parse_kube_bench(role, &logs)?);
+    }
+    Ok(findings)
+}
+
+/// kube-bench Job for `targets`, on a control plane or a worker
+fn job_manifest(role: &str, targets: &str, on_control_plane: bool) -> String {
+    let control_plane_label = "node-role.kubernetes.io/control-plane";
+    let affinity = serde_json::json!({
+        "nodeAffinity": {
+            "requiredDuringSchedulingIgnoredDuringExecution": {
+                "nodeSelectorTerms": [{
+                    "matchExpressions": [{
+                        "key": control_plane_label,
+                        "operator": if on_control_plane { "Exists" } else { "DoesNotExist" },
+                    }]
+                }]
+            }
+        }
+    });
+
+    let mut host_paths = vec![
+        ("var-lib-kubelet", "/var/lib/kubelet"),
+        ("etc-kubernetes", "/etc/kubernetes"),
+        ("etc-cni-netd", "/etc/cni/net.d"),
+    ];
+    if on_control_plane {
+        host_paths.push(("var-lib-etcd", "/var/lib/etcd"));
+    }
+    let mounts: Vec<_> = host_paths
+        .iter()
+        .map(
+            |(name, path)| serde_json::json!({ "name": name, "mountPath": path, "readOnly": true }),
+        )
+        .collect();
+    let volumes: Vec<_> = host_paths
+        .iter()
+        .map(|(name, path)| serde_json::json!({ "name": name, "hostPath": { "path": path } }))
+        .collect();
+
+    let job = serde_json::json!({
+        "apiVersion": "batch/v1",
+        "kind": "Job",
+        "metadata": { "name": format!("kube-bench-{}", role), "namespace": NAMESPACE },
+        "spec": {
+            "backoffLimit": 0,
+            "template": {
+                "spec": {
+                    "hostPID": true,
+                    "restartPolicy": "Never",
+                    "affinity": affinity,
+                    "tolerations": [{ "operator": "Exists" }],
+                    "containers": [{
+                        "name": "kube-bench",
+                        "image": KUBE_BENCH_IMAGE,
+                        "command": ["kube-bench", "run", "--targets", targets, "--json"],
+                        "volumeMounts": mounts,
+                    }],
+                    "volumes": volumes,
+                },
+            },
+        },
+    });
+    serde_yaml::to_string(&job).expect("kube-bench Job serializes")
+}
+
+/// Failed and warned controls with their remediation, then totals per source
+pub fn render_report(findings: &[Finding]) -> String {
+    let mut report = String::new();
+    for status in [Status::Fail, Status::Warn] {
+        for finding in findings.iter().filter(|f| f.status == status) {
+            let label = if status == Status::Fail {
+                "FAIL"
+            } else {
+                "WARN"
+            };
+            report.push_str(&format!(
+                "[{}] {} {} ({}): {}\n",
+                label, finding.source, finding.id, finding.node, finding.description
+            ));
+            if let Some(remediation) = &finding.remediation {
+                for line in remediation.lines().filter(|l| !l.trim().is_empty()) {
+                    report.push_str(&format!("       {}\n", line.trim()));
+                }
+            }
+        }
+    }
+
+    if !report.is_empty() {
+        report.push('\n');
+    }
+    for source in ["kube-bench", "talos"] {
+        let count = |status: Status| {
+            findings
+                .iter()
+                .filter(|f| f.source == source && f.status == status)
+                .count()
+        };
+        report.push_str(&format!(
+            "{}: {} passed, {} failed, {} warnings\n",
+            source,
+            count(Status::Pass),
+            count(Status::Fail),
+            count(Status::Warn)
+        ));
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTROL_PLANE_CONFIG: &str = r#"version: v1alpha1
+machine:
+  type: controlplane
+  features:
+    rbac: true
+    kubernetesTalosAPIAccess:
+      enabled: true
+      allowedRoles:
+        - os:reader
+cluster:
+  secretboxEncryptionSecret: c2VjcmV0
+  apiServer:
+    admissionControl:
+      - name: PodSecurity
+        configuration:
+          defaults:
+            enforce: baseline
+    auditPolicy:
+      rules:
+        - level: Metadata
+  allowSchedulingOnControlPlanes: true
+"#;
+
+    #[test]
+    fn test_talos_checks() {
+        let findings = talos_checks("prod-cp-1", CONTROL_PLANE_CONFIG);
+        let failed: Vec<(&str, Status)> = findings
+            .iter()
+            .filter(|f| f.status != Status::Pass)
+            .map(|f| (f.id.as_str(), f.status))
+            .collect();
+        assert_eq!(failed, [("talos.control-plane-isolation", Status::Warn)]);
+
+        let worker = talos_checks(
+            "prod-worker-1",
+            "version: v1alpha1\nmachine:\n  type: worker\n  kubelet:\n    extraArgs:\n      anonymous-auth: \"true\"\n",
+        );
+        assert_eq!(worker.len(), 4);
+        let failed: Vec<&str> = worker
+            .iter()
+            .filter(|f| f.status == Status::Fail)
+            .map(|f| f.id.as_str())
+            .collect();
+        assert_eq!(failed, ["talos.api-rbac", "talos.kubelet-anonymous-auth"]);
+    }
+
+    #[test]
+    fn test_parse_kube_bench() {
+        let logs = r#"W0101 12:00:00.000000 1 util.go:1] failed to find kubelet config
+{"Controls":[{"id":"4","tests":[{"section":"4.1","results":[
+{"test_number":"4.1.1","test_desc":"Ensure that the kubelet service file permissions are set to 600","remediation":"Run chmod 600 on the file","status":"FAIL","scored":true},
+{"test_number":"4.2.1","test_desc":"Ensure that the --anonymous-auth argument is set to false","remediation":"","status":"PASS","scored":true},
+{"test_number":"4.1.2","test_desc":"Ensure kubelet service file ownership","remediation":"chown","status":"INFO","scored":false}
+]}]}],"Totals":{"total_pass":1,"total_fail":1}}
+"#;
+        let findings = parse_kube_bench("worker", logs).unwrap();
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0].id, "4.1.1");
+        assert_eq!(findings[0].status, Status::Fail);
+        assert_eq!(
+            findings[0].remediation.as_deref(),
+            Some("Run chmod 600 on the file")
+        );
+        assert_eq!(findings[1].status, Status::Pass);
+
+        assert!(parse_kube_bench("worker", "no report").is_err());
+
+        let report = render_report(&findings);
+        assert!(report.starts_with("[FAIL] kube-bench 4.1.1 (worker): Ensure"));
+        assert!(report.contains("       Run chmod 600 on the file\n"));
+        assert!(report.ends_with(
+            "kube-bench: 1 passed, 1 failed, 0 warnings\ntalos: 0 passed, 0 failed, 0 warnings\n"
+        ));
+    }
+}
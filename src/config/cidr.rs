@@ -0,0 +1,214 @@
+/// Network ranges: `auto` allocation and overlaps with the project
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::Path;
+use tracing::info;
+
+use super::{cidrs_overlap, relocate_network, ClusterConfig, NetworkConfig};
+use crate::hcloud::models::Network;
+use crate::hcloud::network::cluster_network_names;
+use crate::state::{ClusterState, NetworkPin};
+
+/// Network and subnet an `auto` network is shaped like
+const ALLOCATED_CIDR: &str = "10.0.0.0/16";
+const ALLOCATED_SUBNET_CIDR: &str = "10.0.1.0/24";
+
+/// The primary network followed by the region networks, in the order of
+/// [`cluster_network_names`]
+fn networks(config: &ClusterConfig) -> impl Iterator<Item = &NetworkConfig> {
+    std::iter::once(&config.hcloud.network).chain(&config.hcloud.regions)
+}
+
+fn networks_mut(config: &mut ClusterConfig) -> impl Iterator<Item = &mut NetworkConfig> {
+    std::iter::once(&mut config.hcloud.network).chain(config.hcloud.regions.iter_mut())
+}
+
+/// Replace `auto` networks with the ranges pinned in `state`
+///
+/// Pins of another cluster are ignored.
+pub fn apply_pins(config: &mut ClusterConfig, state: Option<&ClusterState>) {
+    let Some(state) = state.filter(|state| state.cluster_name == config.cluster_name) else {
+        return;
+    };
+    for network in networks_mut(config).filter(|network| network.is_auto()) {
+        if let Some(pin) = state.networks.get(&network.zone) {
+            network.cidr = pin.cidr.clone();
+            network.subnet_cidr = pin.subnet_cidr.clone();
+        }
+    }
+}
+
+/// Allocate ranges for the `auto` networks left after [`apply_pins`],
+/// returning the pins keyed by network zone
+///
+/// A network of the cluster that already exists keeps its range.
+fn allocate(
+    config: &mut ClusterConfig,
+    existing: &[Network],
+) -> Result<BTreeMap<String, NetworkPin>> {
+    let names = cluster_network_names(&config.cluster_name, &config.hcloud);
+    let mut taken: Vec<String> = existing
+        .iter()
+        .map(|network| network.ip_range.clone())
+        .collect();
+    taken.extend([
+        config.talos.pod_cidr.clone(),
+        config.talos.service_cidr.clone(),
+    ]);
+    taken.extend(
+        networks(config)
+            .filter(|network| !network.is_auto())
+            .map(|network| network.cidr.clone()),
+    );
+
+    let mut pins = BTreeMap::new();
+    for (network, name) in networks_mut(config).zip(&names) {
+        if !network.is_auto() {
+            continue;
+        }
+        let allocated = match existing.iter().find(|found| &found.name == name) {
+            Some(found) => NetworkConfig {
+                cidr: found.ip_range.clone(),
+                subnet_cidr: found
+                    .subnets
+                    .iter()
+                    .find(|subnet| subnet.subnet_type == "cloud")
+                    .map(|subnet| subnet.ip_range.clone())
+                    .ok_or_else(|| anyhow::anyhow!("Network {} has no cloud subnet", name))?,
+                zone: network.zone.clone(),
+            },
+            None => {
+                let shape = NetworkConfig {
+                    cidr: ALLOCATED_CIDR.to_string(),
+                    subnet_cidr: ALLOCATED_SUBNET_CIDR.to_string(),
+                    zone: network.zone.clone(),
+                };
+                relocate_network(&shape, &taken)?.ok_or_else(|| {
+                    anyhow::anyhow!("No free /16 left in 10.0.0.0/8 for network {}", name)
+                })?
+            }
+        };
+        info!(
+            "✓ Network {} uses {} (subnet {})",
+            name, allocated.cidr, allocated.subnet_cidr
+        );
+        taken.push(allocated.cidr.clone());
+        pins.insert(
+            network.zone.clone(),
+            NetworkPin {
+                cidr: allocated.cidr.clone(),
+                subnet_cidr: allocated.subnet_cidr.clone(),
+            },
+        );
+        *network = allocated;
+    }
+    Ok(pins)
+}
+
+/// Allocate the `auto` networks against the `existing` networks of the
+/// project and pin them in the cluster state in `output_dir`
+pub fn resolve_auto(
+    config: &mut ClusterConfig,
+    existing: &[Network],
+    output_dir: &Path,
+) -> Result<()> {
+    let pins = allocate(config, existing)?;
+    if !pins.is_empty() {
+        ClusterState::record_networks(output_dir, &config.cluster_name, &pins)?;
+    }
+    Ok(())
+}
+
+/// Fail if a network of the config overlaps a network of the project that
+/// belongs to something else
+pub fn check_overlaps(config: &ClusterConfig, existing: &[Network]) -> Result<()> {
+    let names = cluster_network_names(&config.cluster_name, &config.hcloud);
+    for network in networks(config).filter(|network| !network.is_auto()) {
+        for other in existing.iter().filter(|other| !names.contains(&other.name)) {
+            if cidrs_overlap(&network.cidr, &other.ip_range)? {
+                anyhow::bail!(
+                    "Network CIDR {} of zone {} overlaps {} of the existing network {}; \
+                    pick a free range or set cidr and subnet_cidr to auto",
+                    network.cidr,
+                    network.zone,
+                    other.ip_range,
+                    other.name
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AUTO_CIDR;
+    use crate::hcloud::models::Subnet;
+
+    fn network(name: &str, cidr: &str, subnet: &str) -> Network {
+        serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "name": name,
+            "ip_range": cidr,
+            "subnets": [Subnet {
+                ip_range: subnet.to_string(),
+                network_zone: "eu-central".to_string(),
+                gateway: String::new(),
+                subnet_type: "cloud".to_string(),
+            }],
+            "routes": [],
+            "servers": [],
+            "created": "2024-01-01T00:00:00+00:00"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_allocate() {
+        let mut config = ClusterConfig::example();
+        config.hcloud.network.cidr = AUTO_CIDR.to_string();
+        config.hcloud.network.subnet_cidr = AUTO_CIDR.to_string();
+        let existing = vec![
+            network("other-network", "10.0.0.0/16", "10.0.1.0/24"),
+            network("legacy", "10.1.0.0/17", "10.1.0.0/24"),
+        ];
+
+        let pins = allocate(&mut config.clone(), &existing).unwrap();
+        assert_eq!(pins["eu-central"].cidr, "10.2.0.0/16");
+        assert_eq!(pins["eu-central"].subnet_cidr, "10.2.1.0/24");
+
+        // The cluster's own network keeps its range
+        let own = format!("{}-network", config.cluster_name);
+        let mut existing = existing;
+        existing.push(network(&own, "10.7.0.0/16", "10.7.1.0/24"));
+        let mut adopted = config.clone();
+        allocate(&mut adopted, &existing).unwrap();
+        assert_eq!(adopted.hcloud.network.cidr, "10.7.0.0/16");
+        assert!(check_overlaps(&adopted, &existing).is_ok());
+
+        let mut state = ClusterState {
+            cluster_name: config.cluster_name.clone(),
+            ..Default::default()
+        };
+        state.networks.insert(
+            "eu-central".to_string(),
+            NetworkPin {
+                cidr: "10.3.0.0/16".to_string(),
+                subnet_cidr: "10.3.1.0/24".to_string(),
+            },
+        );
+        apply_pins(&mut config, Some(&state));
+        assert_eq!(config.hcloud.network.subnet_cidr, "10.3.1.0/24");
+    }
+
+    #[test]
+    fn test_check_overlaps() {
+        let config = ClusterConfig::example();
+        let existing = vec![network("other-network", "10.0.0.0/8", "10.0.1.0/24")];
+        assert!(check_overlaps(&config, &existing).is_err());
+
+        let existing = vec![network("other-network", "10.1.0.0/16", "10.1.1.0/24")];
+        assert!(check_overlaps(&config, &existing).is_ok());
+    }
+}
@@ -0,0 +1,127 @@
+/// Configs for cloned clusters
+use anyhow::{Context, Result};
+
+use super::edit::set_scalar;
+use super::{relocate_network, ClusterConfig, SecretsBackend};
+
+/// Rewrite `content`, the config file of `source`, for a cluster called
+/// `name` whose networks overlap none of `taken`
+pub fn clone_config(
+    content: &str,
+    source: &ClusterConfig,
+    name: &str,
+    taken: &[String],
+) -> Result<String> {
+    if source.robot.is_some() {
+        anyhow::bail!("Clusters with Robot dedicated servers cannot be cloned");
+    }
+
+    let mut taken = taken.to_vec();
+    taken.extend([
+        source.talos.pod_cidr.clone(),
+        source.talos.service_cidr.clone(),
+    ]);
+    // The source may not have been created yet
+    taken.extend(
+        std::iter::once(&source.hcloud.network)
+            .chain(&source.hcloud.regions)
+            .filter(|network| !network.is_auto())
+            .map(|network| network.cidr.clone()),
+    );
+
+    let mut content = set(content, "/cluster_name", name)?;
+
+    let networks = std::iter::once(("/hcloud/network".to_string(), &source.hcloud.network)).chain(
+        source
+            .hcloud
+            .regions
+            .iter()
+            .enumerate()
+            .map(|(i, region)| (format!("/hcloud/regions/{}", i), region)),
+    );
+    // `auto` networks get their own range when the clone is created
+    for (pointer, network) in networks.filter(|(_, network)| !network.is_auto()) {
+        let moved = relocate_network(network, &taken)?.ok_or_else(|| {
+            anyhow::anyhow!(
+                "No free network the size of {} left in 10.0.0.0/8",
+                network.cidr
+            )
+        })?;
+        content = set(&content, &format!("{}/cidr", pointer), &moved.cidr)?;
+        content = set(
+            &content,
+            &format!("{}/subnet_cidr", pointer),
+            &moved.subnet_cidr,
+        )?;
+        taken.push(moved.cidr);
+    }
+
+    let location = match &source.talos.secrets {
+        SecretsBackend::File | SecretsBackend::Age => None,
+        SecretsBackend::Vault { path } => Some(("/talos/secrets/path", path)),
+        SecretsBackend::S3 { uri, .. } => Some(("/talos/secrets/uri", uri)),
+        SecretsBackend::OnePassword { item, .. } => Some(("/talos/secrets/item", item)),
+    };
+    if let Some((pointer, value)) = location {
+        let renamed = value.replace(&source.cluster_name, name);
+        if renamed == *value {
+            anyhow::bail!(
+                "The secrets location {} does not contain the cluster name {}, so the clone would share its secrets; use a location with the cluster name in it",
+                value,
+                source.cluster_name
+            );
+        }
+        content = set(&content, pointer, &renamed)?;
+    }
+
+    Ok(content)
+}
+
+fn set(content: &str, pointer: &str, value: &str) -> Result<String> {
+    set_scalar(content, pointer, value).context(format!(
+        "{} is not a plain value in the source config, so it cannot be changed for the clone",
+        pointer
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "cluster_name: cluster-a # staging\n\
+                          hcloud:\n  \
+                            network:\n    \
+                              cidr: 10.0.0.0/16\n    \
+                              subnet_cidr: 10.0.1.0/24\n    \
+                              zone: eu-central\n\
+                          talos:\n  \
+                            secrets:\n    \
+                              backend: vault\n    \
+                              path: secret/oxide/cluster-a\n";
+
+    #[test]
+    fn test_clone_config() {
+        let mut source = ClusterConfig::example();
+        source.cluster_name = "cluster-a".to_string();
+        source.talos.secrets = SecretsBackend::Vault {
+            path: "secret/oxide/cluster-a".to_string(),
+        };
+        let taken = vec!["10.1.0.0/16".to_string()];
+
+        let cloned = clone_config(SOURCE, &source, "cluster-b", &taken).unwrap();
+        assert_eq!(
+            cloned,
+            SOURCE
+                .replace("cluster-a #", "cluster-b #")
+                .replace("10.0.0.0/16", "10.2.0.0/16")
+                .replace("10.0.1.0/24", "10.2.1.0/24")
+                .replace("oxide/cluster-a", "oxide/cluster-b")
+        );
+
+        source.talos.secrets = SecretsBackend::Vault {
+            path: "secret/oxide/shared".to_string(),
+        };
+        let shared = SOURCE.replace("oxide/cluster-a", "oxide/shared");
+        assert!(clone_config(&shared, &source, "cluster-b", &taken).is_err());
+    }
+}
@@ -0,0 +1,206 @@
+/// In-place edits of config files that keep comments and formatting
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+use super::{schema, ConfigFormat};
+
+/// Set the scalar at `pointer` (e.g. `/talos/version`) to `value`
+///
+/// Returns `None` if the field is not defined in `content` or its value is
+/// not a plain scalar. The original quoting style is kept.
+pub fn set_scalar(content: &str, pointer: &str, value: &str) -> Option<String> {
+    let key = pointer.rsplit('/').next()?;
+    let line_index = schema::field_line(content, pointer)? - 1;
+
+    let mut lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let line = lines[line_index];
+    let key_start = line.find(key)?;
+    let colon = key_start + key.len() + line[key_start + key.len()..].find(':')?;
+
+    let rest = &line[colon + 1..];
+    let (body, newline) = match rest.strip_suffix('\n') {
+        Some(body) => (body, "\n"),
+        None => (rest, ""),
+    };
+    let (old, comment) = match body.find(" #") {
+        Some(pos) => (&body[..pos], &body[pos..]),
+        None => (body, ""),
+    };
+    let old = old.trim();
+    if old.is_empty() || old.contains("${") || old.contains("ENC[") {
+        return None;
+    }
+
+    let quoted = match old.chars().next() {
+        Some(quote @ ('"' | '\'')) => format!("{}{}{}", quote, value, quote),
+        _ => value.to_string(),
+    };
+    let updated = format!("{}: {}{}{}", &line[..colon], quoted, comment, newline);
+    lines[line_index] = &updated;
+
+    Some(lines.concat())
+}
+
+/// Add the field at `pointer` with the raw YAML `value` as the first child of
+/// its parent mapping
+///
+/// Top-level fields go after the leading comments. Returns `None` if the
+/// parent is not a block mapping in `content`.
+pub fn insert_scalar(content: &str, pointer: &str, value: &str) -> Option<String> {
+    let (parent, key) = pointer.rsplit_once('/')?;
+    let mut lines: Vec<&str> = content.split_inclusive('\n').collect();
+
+    let (index, indent) = if parent.is_empty() {
+        let first = lines
+            .iter()
+            .position(|line| !is_blank_or_comment(line))
+            .unwrap_or(lines.len());
+        (first, String::new())
+    } else {
+        let parent_index = schema::field_line(content, parent)? - 1;
+        let line = lines[parent_index];
+        let parent_indent = indent_of(line);
+        let (_, body) = line.split_once(':')?;
+        let body = body.split(" #").next().unwrap_or_default();
+        if line.trim_start().starts_with("- ") || !body.trim().is_empty() {
+            return None;
+        }
+        // Line up with the existing children, if any
+        let child_indent = lines[parent_index + 1..]
+            .iter()
+            .find(|line| !is_blank_or_comment(line))
+            .map(|line| indent_of(line))
+            .filter(|indent| *indent > parent_indent)
+            .unwrap_or(parent_indent + 2);
+        (parent_index + 1, " ".repeat(child_indent))
+    };
+
+    if index > 0 && !lines[index - 1].ends_with('\n') {
+        return None;
+    }
+    let field = format!("{}{}: {}\n", indent, key, value);
+    lines.insert(index, &field);
+    Some(lines.concat())
+}
+
+/// Move the field at `from` to `to`, keeping its value and comment as written
+///
+/// Returns `None` if `from` is not a single-line value or the parent of `to`
+/// is not a block mapping in `content`.
+pub fn move_scalar(content: &str, from: &str, to: &str) -> Option<String> {
+    let line_index = schema::field_line(content, from)? - 1;
+    let mut lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let line = lines[line_index];
+    if line.trim_start().starts_with("- ") {
+        return None;
+    }
+    let (_, rest) = line.split_once(':')?;
+    let value = rest.trim_end_matches('\n').trim();
+    if value.is_empty() || value.starts_with('#') || value.starts_with(['|', '>']) {
+        return None;
+    }
+
+    lines.remove(line_index);
+    insert_scalar(&lines.concat(), to, value)
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+fn is_blank_or_comment(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.is_empty() || trimmed.starts_with('#')
+}
+
+/// Set `pointer` in the last of `files` that defines it
+///
+/// Mirrors how overlays are merged, so the edit changes the effective value.
+/// Returns the file that was changed.
+pub fn set_in_files(files: &[PathBuf], pointer: &str, value: &str) -> Result<PathBuf> {
+    for path in files.iter().rev() {
+        let content = read(path)?;
+        let format = ConfigFormat::from_path(path);
+        if format != ConfigFormat::Yaml {
+            let document = serde_json::to_value(format.parse(&content)?)?;
+            if document.pointer(pointer).is_none() {
+                continue;
+            }
+            anyhow::bail!(
+                "{} is not YAML, so it cannot be edited in place; set {} by hand",
+                path.display(),
+                pointer
+            );
+        }
+        if schema::field_line(&content, pointer).is_none() {
+            continue;
+        }
+        let updated = set_scalar(&content, pointer, value).context(format!(
+            "{} in {} is not a plain value, update it manually",
+            pointer,
+            path.display()
+        ))?;
+        std::fs::write(path, updated).context(format!("Failed to write {}", path.display()))?;
+        return Ok(path.clone());
+    }
+
+    anyhow::bail!("{} is not set in any config file", pointer)
+}
+
+fn read(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path).context(format!("Failed to read {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "cluster_name: test\n\
+                          talos:\n  \
+                            version: v1.10.6 # pinned\n  \
+                            kubernetes_version: \"1.33.4\"\n\
+                          cilium:\n  \
+                            version: ${CILIUM_VERSION}\n";
+
+    #[test]
+    fn test_set_scalar_keeps_formatting() {
+        let updated = set_scalar(CONFIG, "/talos/version", "v1.11.2").unwrap();
+        assert!(updated.contains("  version: v1.11.2 # pinned\n"));
+
+        let updated = set_scalar(&updated, "/talos/kubernetes_version", "1.34.1").unwrap();
+        assert!(updated.contains("  kubernetes_version: \"1.34.1\"\n"));
+        assert_eq!(updated.lines().count(), CONFIG.lines().count());
+    }
+
+    #[test]
+    fn test_set_scalar_skips_references_and_missing_fields() {
+        assert_eq!(set_scalar(CONFIG, "/cilium/version", "1.18.0"), None);
+        assert_eq!(
+            set_scalar(CONFIG, "/addons/longhorn/version", "1.9.1"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_move_and_insert() {
+        let moved = move_scalar(CONFIG, "/talos/version", "/cilium/talos_version").unwrap();
+        assert_eq!(
+            moved,
+            "cluster_name: test\n\
+             talos:\n  \
+               kubernetes_version: \"1.33.4\"\n\
+             cilium:\n  \
+               talos_version: v1.10.6 # pinned\n  \
+               version: ${CILIUM_VERSION}\n"
+        );
+        assert_eq!(move_scalar(CONFIG, "/talos", "/cilium/talos"), None);
+        assert_eq!(move_scalar(CONFIG, "/talos/version", "/addons/x"), None);
+
+        let inserted =
+            insert_scalar(&format!("# header\n{}", CONFIG), "/apiVersion", "v9").unwrap();
+        assert!(inserted.starts_with("# header\napiVersion: v9\ncluster_name: test\n"));
+        let inserted = insert_scalar("a: 1\nb:\n", "/b/c", "2").unwrap();
+        assert_eq!(inserted, "a: 1\nb:\n  c: 2\n");
+        assert_eq!(insert_scalar("a: 1\n", "/a/c", "2"), None);
+    }
+}
@@ -0,0 +1,112 @@
+/// Cluster config file formats
+use anyhow::Result;
+use serde::Serialize;
+use std::path::Path;
+
+/// Format of a config file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ConfigFormat {
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// Format of the file at `path`: `.toml` and `.json` by extension, YAML
+    /// otherwise
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => ConfigFormat::Toml,
+            Some(ext) if ext.eq_ignore_ascii_case("json") => ConfigFormat::Json,
+            _ => ConfigFormat::Yaml,
+        }
+    }
+
+    /// File extension written by `oxide init`
+    pub fn extension(self) -> &'static str {
+        match self {
+            ConfigFormat::Yaml => "yaml",
+            ConfigFormat::Toml => "toml",
+            ConfigFormat::Json => "json",
+        }
+    }
+
+    /// Parse a config document
+    pub fn parse(self, content: &str) -> Result<serde_yaml::Value> {
+        Ok(match self {
+            ConfigFormat::Yaml => serde_yaml::from_str(content)?,
+            ConfigFormat::Toml => toml::from_str(content)?,
+            ConfigFormat::Json => serde_json::from_str(content)?,
+        })
+    }
+
+    /// Render a config document
+    pub fn render<T: Serialize>(self, value: &T) -> Result<String> {
+        Ok(match self {
+            ConfigFormat::Yaml => serde_yaml::to_string(value)?,
+            // TOML has no null, and going through a TOML value puts plain
+            // keys before tables, which TOML requires
+            ConfigFormat::Toml => {
+                let mut value = serde_yaml::to_value(value)?;
+                remove_nulls(&mut value);
+                toml::to_string(&toml::Value::try_from(value)?)?
+            }
+            ConfigFormat::Json => format!("{}\n", serde_json::to_string_pretty(value)?),
+        })
+    }
+}
+
+fn remove_nulls(value: &mut serde_yaml::Value) {
+    match value {
+        serde_yaml::Value::Mapping(mapping) => {
+            mapping.retain(|_, value| !value.is_null());
+            mapping.values_mut().for_each(remove_nulls);
+        }
+        serde_yaml::Value::Sequence(items) => items.iter_mut().for_each(remove_nulls),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ClusterConfig;
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("cluster.yaml")),
+            ConfigFormat::Yaml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("cluster.yml")),
+            ConfigFormat::Yaml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("envs/prod.TOML")),
+            ConfigFormat::Toml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("cluster.json")),
+            ConfigFormat::Json
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let config = ClusterConfig::example();
+        let expected = serde_yaml::to_value(&config).unwrap();
+
+        for format in [ConfigFormat::Toml, ConfigFormat::Json] {
+            let rendered = format.render(&config).unwrap();
+            let parsed: ClusterConfig =
+                serde_yaml::from_value(format.parse(&rendered).unwrap()).unwrap();
+            assert_eq!(
+                serde_yaml::to_value(&parsed).unwrap(),
+                expected,
+                "{:?}",
+                format
+            );
+        }
+    }
+}
@@ -0,0 +1,133 @@
+/// `${...}` interpolation of cluster config files
+use anyhow::Result;
+use std::path::Path;
+
+/// Expand all `${...}` references in `content`, reporting every unresolved one
+pub fn interpolate(content: &str, base_dir: &Path) -> Result<String> {
+    let mut output = String::with_capacity(content.len());
+    let mut errors = Vec::new();
+
+    for (number, line) in content.split_inclusive('\n').enumerate() {
+        if line.trim_start().starts_with('#') {
+            output.push_str(line);
+            continue;
+        }
+        match interpolate_line(line, base_dir) {
+            Ok(expanded) => output.push_str(&expanded),
+            Err(e) => {
+                errors.push(format!("  line {}: {}", number + 1, e));
+                output.push_str(line);
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        anyhow::bail!(
+            "Failed to interpolate config values:\n{}",
+            errors.join("\n")
+        );
+    }
+
+    Ok(output)
+}
+
+fn interpolate_line(line: &str, base_dir: &Path) -> Result<String> {
+    let mut output = String::with_capacity(line.len());
+    let mut rest = line;
+
+    while let Some(start) = rest.find("${") {
+        // `$${` escapes the reference
+        if rest[..start].ends_with('$') {
+            output.push_str(&rest[..start - 1]);
+            output.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        output.push_str(&rest[..start]);
+        let Some(len) = rest[start + 2..].find('}') else {
+            anyhow::bail!("unterminated ${{ reference");
+        };
+        let reference = &rest[start + 2..start + 2 + len];
+        output.push_str(&resolve(reference, base_dir)?);
+        rest = &rest[start + 3 + len..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+fn resolve(reference: &str, base_dir: &Path) -> Result<String> {
+    if let Some(path) = reference.strip_prefix("file:") {
+        let path = base_dir.join(path.trim());
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| anyhow::anyhow!("cannot read {}: {}", path.display(), e))?;
+        return Ok(content.trim_end_matches(['\r', '\n']).to_string());
+    }
+
+    let (name, default) = match reference.split_once(":-") {
+        Some((name, default)) => (name, Some(default)),
+        None => (reference, None),
+    };
+
+    let value = std::env::var(name.trim())
+        .ok()
+        .filter(|value| !value.is_empty() || default.is_none());
+    match (value, default) {
+        (Some(value), _) => Ok(value),
+        (None, Some(default)) => Ok(default.to_string()),
+        (None, None) => anyhow::bail!("environment variable {} is not set", name.trim()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_interpolation() {
+        std::env::set_var("OXIDE_TEST_SNAPSHOT", "123456");
+        std::env::remove_var("OXIDE_TEST_UNSET");
+
+        let content = "id: \"${OXIDE_TEST_SNAPSHOT}\"\n\
+                       location: ${OXIDE_TEST_UNSET:-nbg1}\n\
+                       # token: ${OXIDE_TEST_UNSET}\n\
+                       literal: $${NOT_EXPANDED}\n";
+        let expanded = interpolate(content, Path::new(".")).unwrap();
+
+        assert_eq!(
+            expanded,
+            "id: \"123456\"\n\
+             location: nbg1\n\
+             # token: ${OXIDE_TEST_UNSET}\n\
+             literal: ${NOT_EXPANDED}\n"
+        );
+    }
+
+    #[test]
+    fn test_missing_values_are_reported_with_lines() {
+        std::env::remove_var("OXIDE_TEST_MISSING");
+
+        let err = interpolate(
+            "a: 1\nb: ${OXIDE_TEST_MISSING}\nc: ${file:does-not-exist}\n",
+            Path::new("."),
+        )
+        .unwrap_err()
+        .to_string();
+
+        assert!(err.contains("line 2: environment variable OXIDE_TEST_MISSING is not set"));
+        assert!(err.contains("line 3: cannot read"));
+    }
+
+    #[test]
+    fn test_file_interpolation() {
+        let dir = std::env::temp_dir().join(format!("oxide-interpolate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("token"), "secret-token\n").unwrap();
+
+        let expanded = interpolate("token: ${file:token}\n", &dir).unwrap();
+        assert_eq!(expanded, "token: secret-token\n");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
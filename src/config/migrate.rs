@@ -0,0 +1,272 @@
+/// Config schema versions and migrations between them
+use anyhow::Result;
+use serde_yaml::{Mapping, Value};
+
+use super::{edit, schema, sops, ConfigFormat};
+
+/// Key holding the schema version
+pub const API_VERSION_KEY: &str = "apiVersion";
+
+/// Schema version written by this release
+pub const API_VERSION: &str = "oxide/v1";
+
+/// A schema version and the fields moved on the way to it, as
+/// `(from, to)` JSON pointers into mappings
+pub struct Version {
+    pub name: &'static str,
+    pub moves: &'static [(&'static str, &'static str)],
+}
+
+/// All schema versions, oldest first
+pub const VERSIONS: &[Version] = &[Version {
+    name: "oxide/v1",
+    moves: &[],
+}];
+
+/// Names of all schema versions, oldest first
+pub fn version_names() -> Vec<&'static str> {
+    VERSIONS.iter().map(|version| version.name).collect()
+}
+
+/// Migrate a parsed config file to [`API_VERSION`]
+///
+/// Returns whether any fields were moved, so callers can suggest rewriting
+/// the file.
+pub fn migrate_value(value: &mut Value) -> Result<bool> {
+    migrate_value_with(value, VERSIONS)
+}
+
+/// Migrate the text of a config file to [`API_VERSION`]
+///
+/// YAML keeps its comments and formatting; TOML and JSON files are rendered
+/// again. `None` if the file is already current.
+pub fn migrate_text(content: &str, format: ConfigFormat) -> Result<Option<String>> {
+    if format == ConfigFormat::Yaml {
+        return migrate_text_with(content, VERSIONS);
+    }
+    let mut value = format.parse(content)?;
+    if version_of(&value)? == Some(API_VERSION) {
+        return Ok(None);
+    }
+    migrate_value(&mut value)?;
+    format.render(&value).map(Some)
+}
+
+fn migrate_value_with(value: &mut Value, versions: &[Version]) -> Result<bool> {
+    let pending = pending(value, versions)?;
+    let Value::Mapping(mapping) = value else {
+        return Ok(false);
+    };
+
+    let mut moved = false;
+    for version in pending {
+        for (from, to) in version.moves {
+            let Some(field) = take(mapping, from) else {
+                continue;
+            };
+            if get(mapping, to).is_some() {
+                conflict(from, to, version)?;
+            }
+            put(mapping, to, field);
+            moved = true;
+        }
+    }
+
+    let current = versions[versions.len() - 1].name;
+    mapping.insert(API_VERSION_KEY.into(), current.into());
+    Ok(moved)
+}
+
+fn migrate_text_with(content: &str, versions: &[Version]) -> Result<Option<String>> {
+    let value: Value = serde_yaml::from_str(content)?;
+    if sops::is_encrypted(&value) {
+        anyhow::bail!(
+            "The file is encrypted with SOPS; decrypt it, migrate it and encrypt it again"
+        );
+    }
+    let pending = pending(&value, versions)?;
+    let current = versions[versions.len() - 1].name;
+    let pointer = format!("/{}", API_VERSION_KEY);
+    if pending.is_empty() && version_of(&value)?.is_some() {
+        return Ok(None);
+    }
+
+    let mut content = content.to_string();
+    for version in pending {
+        for (from, to) in version.moves {
+            if schema::field_line(&content, from).is_none() {
+                continue;
+            }
+            if schema::field_line(&content, to).is_some() {
+                conflict(from, to, version)?;
+            }
+            content = edit::move_scalar(&content, from, to).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{} cannot be moved to {} automatically, move it by hand",
+                    from,
+                    to
+                )
+            })?;
+        }
+    }
+
+    let updated = match schema::field_line(&content, &pointer) {
+        Some(_) => edit::set_scalar(&content, &pointer, current),
+        None => edit::insert_scalar(&content, &pointer, current),
+    };
+    updated
+        .map(Some)
+        .ok_or_else(|| anyhow::anyhow!("Failed to set {} to {}", API_VERSION_KEY, current))
+}
+
+/// `apiVersion` of a config file, `None` if it predates versioning
+fn version_of(value: &Value) -> Result<Option<&str>> {
+    match value.get(API_VERSION_KEY) {
+        None => Ok(None),
+        Some(Value::String(version)) => Ok(Some(version)),
+        Some(other) => anyhow::bail!("{} must be a string, got {:?}", API_VERSION_KEY, other),
+    }
+}
+
+/// Versions newer than the one `value` was written for
+fn pending<'a>(value: &Value, versions: &'a [Version]) -> Result<&'a [Version]> {
+    let index = match version_of(value)? {
+        None => 0,
+        Some(name) => versions
+            .iter()
+            .position(|version| version.name == name)
+            .ok_or_else(|| {
+                let known: Vec<&str> = versions.iter().map(|version| version.name).collect();
+                anyhow::anyhow!(
+                    "Unknown {} {}; this oxide reads {}, upgrade oxide if the file is newer",
+                    API_VERSION_KEY,
+                    name,
+                    known.join(", ")
+                )
+            })?,
+    };
+    Ok(&versions[index + 1..])
+}
+
+fn conflict(from: &str, to: &str, version: &Version) -> Result<()> {
+    anyhow::bail!(
+        "{} was moved to {} in {}, but both are set; keep only {}",
+        from,
+        to,
+        version.name,
+        to
+    )
+}
+
+fn segments(pointer: &str) -> impl Iterator<Item = &str> {
+    pointer.split('/').skip(1)
+}
+
+fn get<'a>(mapping: &'a Mapping, pointer: &str) -> Option<&'a Value> {
+    let (parent, key) = pointer.rsplit_once('/')?;
+    let mut current = mapping;
+    for segment in segments(parent) {
+        current = current.get(segment)?.as_mapping()?;
+    }
+    current.get(key)
+}
+
+fn take(mapping: &mut Mapping, pointer: &str) -> Option<Value> {
+    let (parent, key) = pointer.rsplit_once('/')?;
+    let mut current = mapping;
+    for segment in segments(parent) {
+        current = current.get_mut(segment)?.as_mapping_mut()?;
+    }
+    current.remove(key)
+}
+
+/// Set `pointer`, creating missing parent mappings
+fn put(mapping: &mut Mapping, pointer: &str, value: Value) {
+    let Some((parent, key)) = pointer.rsplit_once('/') else {
+        return;
+    };
+    let mut current = mapping;
+    for segment in segments(parent) {
+        let entry = current
+            .entry(segment.into())
+            .or_insert_with(|| Value::Mapping(Mapping::new()));
+        if !entry.is_mapping() {
+            *entry = Value::Mapping(Mapping::new());
+        }
+        current = entry.as_mapping_mut().expect("just made a mapping");
+    }
+    current.insert(key.into(), value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_VERSIONS: &[Version] = &[
+        Version {
+            name: "oxide/v1",
+            moves: &[],
+        },
+        Version {
+            name: "oxide/v2",
+            moves: &[("/talos/hcloud_snapshot_id", "/hcloud/snapshot_id")],
+        },
+    ];
+
+    const OLD: &str = "# staging\n\
+                       cluster_name: test\n\
+                       hcloud:\n  \
+                         location: nbg1\n\
+                       talos:\n  \
+                         version: v1.10.6\n  \
+                         hcloud_snapshot_id: \"123\" # from step 1\n";
+
+    #[test]
+    fn test_current_version_is_last() {
+        assert_eq!(VERSIONS.last().map(|v| v.name), Some(API_VERSION));
+    }
+
+    #[test]
+    fn test_migrate_value() {
+        let mut value: Value = serde_yaml::from_str(OLD).unwrap();
+        assert!(migrate_value_with(&mut value, TEST_VERSIONS).unwrap());
+        assert_eq!(value["hcloud"]["snapshot_id"], Value::from("123"));
+        assert!(value["talos"].get("hcloud_snapshot_id").is_none());
+        assert_eq!(value[API_VERSION_KEY], Value::from("oxide/v2"));
+
+        // Already current
+        assert!(!migrate_value_with(&mut value, TEST_VERSIONS).unwrap());
+
+        let both = OLD.replace("location: nbg1\n", "location: nbg1\n  snapshot_id: \"1\"\n");
+        let mut both: Value = serde_yaml::from_str(&both).unwrap();
+        assert!(migrate_value_with(&mut both, TEST_VERSIONS).is_err());
+
+        let mut newer: Value = serde_yaml::from_str("apiVersion: oxide/v9\n").unwrap();
+        assert!(migrate_value_with(&mut newer, TEST_VERSIONS).is_err());
+    }
+
+    #[test]
+    fn test_migrate_text() {
+        let migrated = migrate_text_with(OLD, TEST_VERSIONS).unwrap().unwrap();
+        assert_eq!(
+            migrated,
+            "# staging\n\
+             apiVersion: oxide/v2\n\
+             cluster_name: test\n\
+             hcloud:\n  \
+               snapshot_id: \"123\" # from step 1\n  \
+               location: nbg1\n\
+             talos:\n  \
+               version: v1.10.6\n"
+        );
+        assert_eq!(migrate_text_with(&migrated, TEST_VERSIONS).unwrap(), None);
+
+        // Unversioned files only gain the apiVersion
+        let stamped = migrate_text(OLD, ConfigFormat::Yaml).unwrap().unwrap();
+        assert_eq!(
+            stamped,
+            OLD.replace("# staging\n", "# staging\napiVersion: oxide/v1\n")
+        );
+        assert_eq!(migrate_text(&stamped, ConfigFormat::Yaml).unwrap(), None);
+    }
+}
@@ -1,11 +1,38 @@
 /// Configuration management for Oxide - Talos Kubernetes with Cilium
+use ipnet::{IpNet, Ipv4Net};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+
+pub mod cidr;
+pub mod clone;
+pub mod edit;
+mod format;
+mod interpolate;
+pub mod migrate;
+mod overlay;
+pub mod schema;
+mod sops;
+mod template;
+pub mod versions;
+pub mod wizard;
+
+pub use format::ConfigFormat;
+pub use template::ConfigTemplate;
 
 /// Main cluster configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct ClusterConfig {
+    /// Version of the config schema the file is written for
+    #[serde(rename = "apiVersion", default = "default_api_version")]
+    #[schemars(schema_with = "schema::api_version")]
+    pub api_version: String,
+
     /// Cluster name (used for resource naming)
+    #[schemars(regex(pattern = r"^[a-zA-Z0-9][a-zA-Z0-9-]*$"))]
     pub cluster_name: String,
 
     /// Hetzner Cloud configuration
@@ -22,46 +49,292 @@ pub struct ClusterConfig {
 
     /// Worker nodes
     pub workers: Vec<NodeConfig>,
+
+    /// Hetzner Robot dedicated servers joined as workers over a vSwitch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub robot: Option<RobotConfig>,
+
+    /// Optional cluster addons installed after Cilium
+    #[serde(default)]
+    pub addons: AddonsConfig,
+
+    /// DNS names the cluster's applications are published under
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dns: Option<DnsConfig>,
+
+    /// How Helm charts (Cilium and addons) are installed
+    #[serde(default)]
+    pub helm: HelmConfig,
+
+    /// Rolling Talos/Kubernetes upgrade settings
+    #[serde(default)]
+    pub upgrade: UpgradeConfig,
+
+    /// Operator access control created right after bootstrap
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rbac: Option<RbacConfig>,
+
+    /// Namespaces with quotas, limits, isolation and access for each team
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tenants: Vec<TenantConfig>,
+
+    /// Per-phase timeouts in seconds
+    #[serde(default)]
+    pub timeouts: TimeoutsConfig,
+
+    /// Webhook notifications when an operation completes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notifications: Option<NotificationsConfig>,
+
+    /// Alertmanager that `oxide node maintenance` silences node alerts in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alertmanager: Option<AlertmanagerConfig>,
+
+    /// S3-compatible bucket for shared artifacts like the kubeconfig
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage: Option<StorageConfig>,
 }
 
 /// Hetzner Cloud API and network configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct HetznerCloudConfig {
     /// Hetzner Cloud API token (can also be set via HCLOUD_TOKEN env var)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub token: Option<String>,
 
+    /// SOPS-encrypted YAML/JSON file whose `token` key holds the API token
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_sops_file: Option<PathBuf>,
+
     /// Hetzner Cloud region
+    #[schemars(schema_with = "schema::location")]
     pub location: String,
 
     /// Private network configuration
     pub network: NetworkConfig,
+
+    /// Private networks in further network zones, for pools placed there
+    ///
+    /// Nodes in different zones are connected with KubeSpan (WireGuard).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub regions: Vec<NetworkConfig>,
+
+    /// NAT gateway giving worker pools without public IPs outbound access
+    #[serde(default)]
+    pub nat_gateway: NatGatewayConfig,
+
+    /// Restrict node egress to the listed destinations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub egress: Option<EgressConfig>,
+
+    /// Addresses or CIDRs allowed to reach the Talos and Kubernetes APIs,
+    /// instead of the detected public IP of the machine running oxide
+    ///
+    /// For networks behind CGNAT or CI runners with a known egress range.
+    /// `OXIDE_PUBLIC_IP` (comma-separated) takes precedence.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_ips: Vec<String>,
+
+    /// Enable hcloud delete protection on control plane servers and the
+    /// networks; `oxide destroy` and scale-down lift it before deleting
+    #[serde(default = "default_true")]
+    pub delete_protection: bool,
+
+    /// Shut servers down via ACPI before deleting them, so Talos can leave
+    /// etcd and unmount its disks; forcibly powered off after
+    /// `timeouts.server_shutdown`
+    #[serde(default)]
+    pub graceful_shutdown: bool,
+
+    /// HTTP client settings for the API: proxy, extra CAs, timeouts
+    #[serde(default)]
+    pub http: HttpConfig,
+
+    /// Servers created at the same time; more are queued so large clusters
+    /// stay under the API rate limit
+    #[serde(default = "default_create_concurrency")]
+    #[schemars(range(min = 1))]
+    pub create_concurrency: usize,
+
+    /// Further Hetzner projects worker pools can be placed in, by name
+    ///
+    /// Private networks cannot span projects, so each project gets its own
+    /// copy of the cluster networks, firewall and SSH key, and nodes reach
+    /// each other over KubeSpan.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub projects: BTreeMap<String, ProjectConfig>,
+}
+
+/// Credentials of a further Hetzner project
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// API token of the project
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+
+    /// Environment variable holding the API token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_env: Option<String>,
+
+    /// SOPS-encrypted YAML/JSON file whose `token` key holds the API token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_sops_file: Option<PathBuf>,
+
+    /// Talos snapshot in this project; snapshots are not shared between
+    /// projects, so pools here cannot use `talos.hcloud_snapshot_id`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hcloud_snapshot_id: Option<String>,
+}
+
+/// HTTP client settings for the Hetzner Cloud API
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct HttpConfig {
+    /// Proxy for all API requests, e.g. `http://proxy.example.com:3128`
+    ///
+    /// Without it, HTTPS_PROXY, HTTP_PROXY and NO_PROXY from the environment
+    /// are used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+
+    /// PEM files with additional root certificates to trust, e.g. the CA of
+    /// a TLS-intercepting corporate proxy
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ca_certificates: Vec<PathBuf>,
+
+    /// Timeout of a single API request in seconds
+    #[serde(default = "default_http_timeout")]
+    #[schemars(range(min = 1))]
+    pub timeout: u64,
+
+    /// Idle connections kept open per host (unlimited if unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool_max_idle_per_host: Option<usize>,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            ca_certificates: Vec::new(),
+            timeout: default_http_timeout(),
+            pool_max_idle_per_host: None,
+        }
+    }
+}
+
+/// Egress lockdown for the cluster firewall
+///
+/// Once a firewall has an outbound rule, Hetzner drops all outbound traffic
+/// that no rule allows. Traffic on the private network is never filtered.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct EgressConfig {
+    /// Also allow what every node needs: DNS, NTP, the metadata service and
+    /// HTTPS for registries, GitHub and the Talos discovery service
+    #[serde(default = "default_true")]
+    pub defaults: bool,
+
+    /// Additional allowed destinations
+    #[serde(default)]
+    pub rules: Vec<EgressRule>,
+}
+
+/// An allowed outbound destination
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct EgressRule {
+    /// Shown in the Hetzner console
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Destination CIDRs (bare addresses are single hosts)
+    pub destination_ips: Vec<String>,
+
+    /// tcp, udp, icmp, esp or gre
+    #[schemars(regex(pattern = r"^(tcp|udp|icmp|esp|gre)$"))]
+    pub protocol: String,
+
+    /// Port or range ("443", "8000-8100"), required for tcp and udp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<String>,
+}
+
+/// NAT gateway server for pools with `public_ip: false`
+///
+/// Only created when at least one pool has no public IPs.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct NatGatewayConfig {
+    /// Hetzner server type of the gateway
+    #[serde(default = "default_nat_gateway_server_type")]
+    #[schemars(regex(pattern = r"^[a-z]+[0-9]+[a-z]*$"))]
+    pub server_type: String,
+
+    /// Hetzner system image of the gateway
+    #[serde(default = "default_nat_gateway_image")]
+    pub image: String,
+}
+
+impl Default for NatGatewayConfig {
+    fn default() -> Self {
+        Self {
+            server_type: default_nat_gateway_server_type(),
+            image: default_nat_gateway_image(),
+        }
+    }
 }
 
 /// Private network configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct NetworkConfig {
-    /// Network CIDR (e.g., "10.0.0.0/16")
+    /// Network CIDR (e.g., "10.0.0.0/16"), or `auto` for the next free /16
+    /// of the Hetzner project
+    #[schemars(regex(pattern = r"^(auto|[0-9a-fA-F:.]+/[0-9]{1,3})$"))]
     pub cidr: String,
 
-    /// Subnet CIDR for the cluster (e.g., "10.0.1.0/24")
+    /// Subnet CIDR for the cluster (e.g., "10.0.1.0/24"), `auto` with an
+    /// `auto` network
+    #[schemars(regex(pattern = r"^(auto|[0-9a-fA-F:.]+/[0-9]{1,3})$"))]
     pub subnet_cidr: String,
 
     /// Network zone (e.g., "eu-central")
+    #[schemars(schema_with = "schema::network_zone")]
     pub zone: String,
 }
 
+/// `cidr` and `subnet_cidr` value asking for a free range of the project
+pub const AUTO_CIDR: &str = "auto";
+
+impl NetworkConfig {
+    /// Whether the range is picked when the cluster is created
+    pub fn is_auto(&self) -> bool {
+        self.cidr == AUTO_CIDR
+    }
+}
+
 /// Talos-specific configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct TalosConfig {
-    /// Talos version to use (e.g., "v1.7.0")
+    /// Talos version to use (e.g., "v1.7.0"), or `latest` or `v1.7` for the
+    /// newest matching release
+    #[schemars(regex(
+        pattern = r"^(latest|v[0-9]+\.[0-9]+|v[0-9]+\.[0-9]+\.[0-9]+(-[0-9A-Za-z.]+)?)$"
+    ))]
     pub version: String,
 
-    /// Kubernetes version (e.g., "1.30.0")
+    /// Kubernetes version (e.g., "1.30.0"), or `latest` or `"1.30"` for the
+    /// newest matching release
+    #[schemars(regex(pattern = r"^(latest|v?[0-9]+\.[0-9]+(\.[0-9]+)?)$"))]
     pub kubernetes_version: String,
 
     /// Cluster endpoint (will be set to first control plane IP if not specified)
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(regex(pattern = r"^https://"))]
     pub cluster_endpoint: Option<String>,
 
     /// Hetzner Cloud snapshot ID containing Talos image
@@ -69,37 +342,233 @@ pub struct TalosConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub hcloud_snapshot_id: Option<String>,
 
+    /// Extra DNS names and IPs for the Kubernetes API server and Talos API
+    /// certificates, e.g. a load balancer or DNS name put in front of the
+    /// control planes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cert_sans: Vec<String>,
+
     /// Additional Talos machine config patches
     #[serde(default)]
     pub config_patches: Vec<String>,
+
+    /// Pod CIDR, must not overlap the Hetzner networks
+    #[serde(default = "default_pod_cidr")]
+    #[schemars(regex(pattern = r"^[0-9a-fA-F:.]+/[0-9]{1,3}$"))]
+    pub pod_cidr: String,
+
+    /// Service CIDR, must not overlap the Hetzner networks
+    #[serde(default = "default_service_cidr")]
+    #[schemars(regex(pattern = r"^[0-9a-fA-F:.]+/[0-9]{1,3}$"))]
+    pub service_cidr: String,
+
+    /// IPv6 pod CIDR used in dual-stack mode (`cilium.enable_ipv6`)
+    #[serde(default = "default_ipv6_pod_cidr")]
+    #[schemars(regex(pattern = r"^[0-9a-fA-F:.]+/[0-9]{1,3}$"))]
+    pub ipv6_pod_cidr: String,
+
+    /// IPv6 service CIDR used in dual-stack mode (`cilium.enable_ipv6`)
+    #[serde(default = "default_ipv6_service_cidr")]
+    #[schemars(regex(pattern = r"^[0-9a-fA-F:.]+/[0-9]{1,3}$"))]
+    pub ipv6_service_cidr: String,
+
+    /// Where the cluster secrets bundle (`secrets.yaml`) is kept
+    #[serde(default)]
+    pub secrets: SecretsBackend,
+
+    /// Private registry the nodes pull every image from, for clusters whose
+    /// nodes cannot reach the public registries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry_mirror: Option<RegistryMirrorConfig>,
+
+    /// Add the qemu-guest-agent extension to the node images, so the
+    /// hypervisor's shutdown requests reach Talos
+    #[serde(default)]
+    pub qemu_guest_agent: bool,
+}
+
+impl TalosConfig {
+    /// System extensions the Talos settings themselves require
+    pub fn extensions(&self) -> Vec<String> {
+        let mut extensions = Vec::new();
+        if self.qemu_guest_agent {
+            extensions.push("siderolabs/qemu-guest-agent".to_string());
+        }
+        extensions
+    }
+}
+
+/// Registry mirror serving the images of `oxide bundle create`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct RegistryMirrorConfig {
+    /// Mirror URL, e.g. `https://registry.internal:5000`
+    #[schemars(regex(pattern = r"^https?://"))]
+    pub endpoint: String,
+
+    /// Registries redirected to the mirror
+    #[serde(default = "default_mirrored_registries")]
+    pub registries: Vec<String>,
+
+    /// Accept a mirror certificate not signed by a trusted CA
+    #[serde(default)]
+    pub insecure_skip_verify: bool,
+}
+
+impl RegistryMirrorConfig {
+    /// `host[:port]` of the endpoint, as Talos keys registry settings
+    pub fn host(&self) -> &str {
+        let host = self
+            .endpoint
+            .split_once("://")
+            .map_or(self.endpoint.as_str(), |(_, rest)| rest);
+        host.split('/').next().unwrap_or(host)
+    }
+}
+
+/// Storage backend for the Talos secrets bundle (cluster PKI and tokens)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum SecretsBackend {
+    /// Plaintext `secrets.yaml` in the output directory
+    #[default]
+    File,
+    /// `secrets.yaml.age` in the output directory, encrypted with the
+    /// passphrase from `OXIDE_SECRETS_PASSPHRASE` (or prompted for)
+    Age,
+    /// HashiCorp Vault KV secret, read and written with the `vault` CLI
+    Vault {
+        /// KV path, e.g. `secret/oxide/prod`
+        path: String,
+    },
+    /// S3 object encrypted with KMS, read and written with the `aws` CLI
+    S3 {
+        /// Object URI, e.g. `s3://bucket/oxide/prod/secrets.yaml`
+        uri: String,
+        /// KMS key for server-side encryption (bucket default if omitted)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        kms_key_id: Option<String>,
+    },
+    /// 1Password document, read and written with the `op` CLI
+    #[serde(rename = "1password")]
+    OnePassword {
+        /// Vault holding the document
+        vault: String,
+        /// Document title
+        item: String,
+    },
 }
 
 /// Cilium CNI configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct CiliumConfig {
-    /// Cilium version (e.g., "1.15.0")
+    /// Cilium version (e.g., "1.15.0"), or `latest` or `"1.15"` for the
+    /// newest matching release
+    #[schemars(regex(
+        pattern = r"^(latest|[0-9]+\.[0-9]+|[0-9]+\.[0-9]+\.[0-9]+(-[0-9A-Za-z.]+)?)$"
+    ))]
     pub version: String,
 
     /// Enable Hubble observability
     #[serde(default = "default_true")]
     pub enable_hubble: bool,
 
-    /// Enable IPv6 support
+    /// Enable IPv6 support (turns the whole cluster into dual-stack mode)
     #[serde(default)]
     pub enable_ipv6: bool,
 
+    /// Install the Gateway API CRDs and the Cilium Gateway controller
+    #[serde(default = "default_true")]
+    pub gateway_api: bool,
+
     /// Additional Cilium Helm values
     #[serde(default)]
+    #[schemars(with = "serde_json::Value")]
     pub helm_values: serde_yaml::Value,
+
+    /// CiliumNetworkPolicies locking workload namespaces down once Cilium
+    /// is ready
+    #[serde(default)]
+    pub default_deny: DefaultDenyConfig,
+
+    /// Egress gateway giving selected workloads a stable source IP through
+    /// the worker pools with `egress_gateway: true`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub egress_gateway: Option<EgressGatewayConfig>,
+}
+
+/// Cilium egress gateway settings
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct EgressGatewayConfig {
+    /// Send the traffic from a floating IP assigned to the gateway node
+    /// instead of its primary IP, so the address outlives the server; needs
+    /// exactly one gateway node
+    #[serde(default)]
+    pub floating_ip: bool,
+
+    /// Traffic leaving the cluster through the gateway
+    pub policies: Vec<EgressGatewayPolicy>,
+}
+
+/// Pods and destinations of one CiliumEgressGatewayPolicy
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct EgressGatewayPolicy {
+    /// Name of the policy
+    pub name: String,
+
+    /// Namespaces of the pods whose traffic leaves through the gateway
+    pub namespaces: Vec<String>,
+
+    /// Only pods with these labels; all pods of the namespaces if empty
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub pod_labels: BTreeMap<String, String>,
+
+    /// Destinations reached through the gateway, e.g. the API that allowlists
+    /// the egress IP; the cluster network is always excluded
+    #[serde(default = "default_egress_destinations")]
+    pub destination_cidrs: Vec<String>,
+}
+
+/// Default-deny network policies applied after Cilium is installed
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct DefaultDenyConfig {
+    /// Deny all traffic of pods outside the system namespaces except DNS
+    /// lookups and what other policies allow
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Namespaces whose pods may talk to each other within the namespace
+    #[serde(default = "default_same_namespace_allowed")]
+    pub allow_same_namespace: Vec<String>,
+
+    /// Namespaces left open besides kube-system and the addon namespaces
+    #[serde(default)]
+    pub exempt_namespaces: Vec<String>,
+}
+
+impl Default for DefaultDenyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            allow_same_namespace: default_same_namespace_allowed(),
+            exempt_namespaces: Vec::new(),
+        }
+    }
 }
 
 /// Node configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct NodeConfig {
     /// Node name prefix
     pub name: String,
 
     /// Hetzner server type (e.g., "cx21", "cpx31")
+    #[schemars(regex(pattern = r"^[a-z]+[0-9]+[a-z]*$"))]
     pub server_type: String,
 
     /// Number of nodes to create with this configuration
@@ -109,119 +578,3001 @@ pub struct NodeConfig {
     /// Additional labels for the node
     #[serde(default)]
     pub labels: std::collections::HashMap<String, String>,
+
+    /// GPU pool: installs NVIDIA extensions/kernel modules and taints nodes
+    #[serde(default)]
+    pub gpu: bool,
+
+    /// Control plane pools only: run regular workloads on the control planes,
+    /// e.g. for a cluster of three nodes with all roles
+    #[serde(default)]
+    pub allow_workloads: bool,
+
+    /// Per-pool Talos snapshot, overriding `talos.hcloud_snapshot_id`
+    /// (GPU pools need an image built with the NVIDIA extensions)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hcloud_snapshot_id: Option<String>,
+
+    /// Location of this pool, overriding `hcloud.location`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(schema_with = "schema::location")]
+    pub location: Option<String>,
+
+    /// Reverse DNS template for node public IPs (e.g. "{node}.k8s.example.com")
+    ///
+    /// Supports `{node}`, `{pool}` and `{cluster}` placeholders.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rdns_template: Option<String>,
+
+    /// Spread the pool's nodes across several locations of the network zone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topology: Option<TopologyConfig>,
+
+    /// Give nodes public IPs; workers without them reach the internet through
+    /// the cluster's NAT gateway
+    #[serde(default = "default_true")]
+    pub public_ip: bool,
+
+    /// Hetzner project of the pool, a key of `hcloud.projects`; unset for
+    /// the project of `hcloud.token`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+
+    /// Worker pools only: traffic matched by `cilium.egress_gateway` leaves
+    /// the cluster through these nodes
+    #[serde(default)]
+    pub egress_gateway: bool,
 }
 
-fn default_true() -> bool {
-    true
+impl NodeConfig {
+    /// Location of the pool's node with the given 0-based index
+    ///
+    /// Spread pools assign locations round-robin, so a pool created with
+    /// `count` nodes is balanced; others use their `location` or `default`
+    /// (`hcloud.location`).
+    pub fn location_for<'a>(&'a self, index: u32, default: &'a str) -> &'a str {
+        match &self.topology {
+            Some(topology) if !topology.locations.is_empty() => {
+                &topology.locations[index as usize % topology.locations.len()]
+            }
+            _ => self.location.as_deref().unwrap_or(default),
+        }
+    }
+
+    /// Location for a new node, given the locations of the pool's current nodes
+    ///
+    /// Picks the least used of the pool's locations (the first on ties), so
+    /// scaling keeps the pool balanced even after manual changes.
+    pub fn next_location<'a>(&'a self, existing: &[&str], default: &'a str) -> &'a str {
+        let Some(topology) = &self.topology else {
+            return self.location.as_deref().unwrap_or(default);
+        };
+        let used = |location: &&String| existing.iter().filter(|e| **e == *location).count();
+        topology
+            .locations
+            .iter()
+            .min_by_key(used)
+            .map_or(default, String::as_str)
+    }
 }
 
-fn default_one() -> u32 {
-    1
+/// Multi-location placement of a node pool
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct TopologyConfig {
+    /// Locations to spread nodes across, all within `hcloud.network.zone`
+    #[schemars(schema_with = "schema::locations")]
+    pub locations: Vec<String>,
 }
 
-impl ClusterConfig {
-    /// Load configuration from a YAML file
-    pub fn from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
-        let content = std::fs::read_to_string(path)?;
-        let config: ClusterConfig = serde_yaml::from_str(&content)?;
-        config.validate()?;
-        Ok(config)
-    }
+/// Optional addons deployed on top of the base cluster
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct AddonsConfig {
+    /// Longhorn distributed block storage
+    #[serde(default)]
+    pub longhorn: LonghornConfig,
 
-    /// Validate the configuration
-    pub fn validate(&self) -> anyhow::Result<()> {
-        if self.cluster_name.is_empty() {
-            anyhow::bail!("cluster_name cannot be empty");
-        }
+    /// NVIDIA device plugin, installed when any worker pool sets `gpu: true`
+    #[serde(default)]
+    pub nvidia_device_plugin: NvidiaDevicePluginConfig,
 
-        if self.control_planes.is_empty() {
-            anyhow::bail!("at least one control plane node is required");
-        }
+    /// DaemonSet pulling images onto every node ahead of the workloads
+    #[serde(default)]
+    pub image_prepull: ImagePrepullConfig,
 
-        // Validate network CIDRs
-        self.validate_cidr(&self.hcloud.network.cidr)?;
-        self.validate_cidr(&self.hcloud.network.subnet_cidr)?;
+    /// Spegel, a stateless registry mirror sharing pulled images between
+    /// nodes
+    #[serde(default)]
+    pub spegel: SpegelConfig,
 
-        Ok(())
-    }
+    /// Kyverno or Gatekeeper with the starter policy pack
+    #[serde(default)]
+    pub policy_engine: PolicyEngineConfig,
 
-    /// Validate CIDR notation
-    fn validate_cidr(&self, cidr: &str) -> anyhow::Result<()> {
-        if !cidr.contains('/') {
-            anyhow::bail!("Invalid CIDR notation: {}", cidr);
+    /// ingress-nginx, for Ingress annotations the Cilium Gateway lacks
+    #[serde(default)]
+    pub ingress_nginx: IngressNginxConfig,
+
+    /// Istio service mesh in ambient mode
+    #[serde(default)]
+    pub istio: IstioConfig,
+
+    /// KubeVirt and CDI, for virtual machines
+    #[serde(default)]
+    pub kubevirt: KubevirtConfig,
+
+    /// Knative Serving on the Cilium Gateway
+    #[serde(default)]
+    pub knative: KnativeConfig,
+
+    /// Descheduler, rebalancing pods across the nodes
+    #[serde(default)]
+    pub descheduler: DeschedulerConfig,
+
+    /// node-problem-detector, reporting kernel problems as node conditions
+    #[serde(default)]
+    pub node_problem_detector: NodeProblemDetectorConfig,
+
+    /// metrics-server, serving the resource metrics API
+    #[serde(default)]
+    pub metrics_server: MetricsServerConfig,
+
+    /// Vertical Pod Autoscaler; installs metrics-server too
+    #[serde(default)]
+    pub vpa: VpaConfig,
+
+    /// Headlamp dashboard, opened with `oxide dashboard-ui`
+    #[serde(default)]
+    pub dashboard: DashboardConfig,
+}
+
+/// Longhorn storage addon configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct LonghornConfig {
+    /// Install Longhorn and its Talos prerequisites
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Longhorn Helm chart version (e.g., "1.9.1")
+    #[serde(default = "default_longhorn_version")]
+    pub version: String,
+
+    /// Number of replicas for the default Longhorn StorageClass
+    #[serde(default = "default_longhorn_replicas")]
+    pub replica_count: u32,
+}
+
+impl Default for LonghornConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            version: default_longhorn_version(),
+            replica_count: default_longhorn_replicas(),
         }
-        Ok(())
     }
+}
 
-    /// Get Hetzner Cloud API token from config or environment
-    pub fn get_hcloud_token(&self) -> anyhow::Result<String> {
-        self.hcloud.token
-            .clone()
-            .or_else(|| std::env::var("HCLOUD_TOKEN").ok())
-            .ok_or_else(|| anyhow::anyhow!(
-                "Hetzner Cloud API token not found. Set HCLOUD_TOKEN environment variable or specify in config"
-            ))
-    }
+/// NVIDIA device plugin addon configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct NvidiaDevicePluginConfig {
+    /// nvidia-device-plugin Helm chart version (e.g., "0.17.1")
+    #[serde(default = "default_nvidia_device_plugin_version")]
+    pub version: String,
+}
 
-    /// Generate an example configuration file
-    pub fn example() -> Self {
+impl Default for NvidiaDevicePluginConfig {
+    fn default() -> Self {
         Self {
-            cluster_name: "talos-cluster".to_string(),
-            hcloud: HetznerCloudConfig {
-                token: None,
-                location: "nbg1".to_string(),
-                network: NetworkConfig {
-                    cidr: "10.0.0.0/16".to_string(),
-                    subnet_cidr: "10.0.1.0/24".to_string(),
-                    zone: "eu-central".to_string(),
-                },
-            },
-            talos: TalosConfig {
-                version: "v1.7.0".to_string(),
-                kubernetes_version: "1.30.0".to_string(),
-                cluster_endpoint: None,
-                hcloud_snapshot_id: None,
-                config_patches: vec![],
-            },
-            cilium: CiliumConfig {
-                version: "1.15.0".to_string(),
-                enable_hubble: true,
-                enable_ipv6: false,
-                helm_values: serde_yaml::Value::Null,
-            },
-            control_planes: vec![NodeConfig {
-                name: "control-plane".to_string(),
-                server_type: "cpx21".to_string(),
-                count: 3,
-                labels: std::collections::HashMap::new(),
-            }],
-            workers: vec![NodeConfig {
-                name: "worker".to_string(),
-                server_type: "cpx31".to_string(),
-                count: 3,
-                labels: std::collections::HashMap::new(),
-            }],
+            version: default_nvidia_device_plugin_version(),
         }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Spegel addon configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct SpegelConfig {
+    /// Install Spegel and its containerd settings
+    #[serde(default)]
+    pub enabled: bool,
 
-    #[test]
-    fn test_config_validation() {
-        let mut config = ClusterConfig::example();
-        assert!(config.validate().is_ok());
+    /// Spegel Helm chart version (e.g., "0.3.0")
+    #[serde(default = "default_spegel_version")]
+    pub version: String,
+}
 
-        config.cluster_name = String::new();
-        assert!(config.validate().is_err());
+impl Default for SpegelConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            version: default_spegel_version(),
+        }
     }
+}
 
-    #[test]
+/// ingress-nginx addon configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct IngressNginxConfig {
+    /// Install the ingress-nginx controller
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// ingress-nginx Helm chart version (e.g., "4.12.1")
+    #[serde(default = "default_ingress_nginx_version")]
+    pub version: String,
+
+    /// Where the controller Service gets its address
+    #[serde(default)]
+    pub service: IngressService,
+
+    /// Hetzner load balancer type with `service: load_balancer`
+    #[serde(default = "default_load_balancer_type")]
+    pub load_balancer_type: String,
+
+    /// HTTP port on the node IPs with `service: node_ipam`
+    #[serde(default = "default_http_port")]
+    pub http_port: u16,
+
+    /// HTTPS port on the node IPs with `service: node_ipam`
+    #[serde(default = "default_https_port")]
+    pub https_port: u16,
+
+    /// Controller replicas
+    #[serde(default = "default_ingress_nginx_replicas")]
+    pub replicas: u32,
+
+    /// Make `nginx` the default IngressClass
+    #[serde(default)]
+    pub default_class: bool,
+}
+
+impl Default for IngressNginxConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            version: default_ingress_nginx_version(),
+            service: IngressService::default(),
+            load_balancer_type: default_load_balancer_type(),
+            http_port: default_http_port(),
+            https_port: default_https_port(),
+            replicas: default_ingress_nginx_replicas(),
+            default_class: false,
+        }
+    }
+}
+
+impl IngressNginxConfig {
+    /// Ports the controller listens on at the public node IPs, which the
+    /// cluster firewall has to admit
+    pub fn node_ports(&self) -> Vec<u16> {
+        if self.enabled && self.service == IngressService::NodeIpam {
+            vec![self.http_port, self.https_port]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// Istio addon configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct IstioConfig {
+    /// Install Istio in ambient mode and the Cilium settings it needs
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Istio Helm chart version (e.g., "1.25.2")
+    #[serde(default = "default_istio_version")]
+    pub version: String,
+
+    /// Namespaces labeled `istio.io/dataplane-mode=ambient`, created if
+    /// missing
+    #[serde(default)]
+    pub ambient_namespaces: Vec<String>,
+}
+
+impl Default for IstioConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            version: default_istio_version(),
+            ambient_namespaces: Vec::new(),
+        }
+    }
+}
+
+/// KubeVirt addon configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct KubevirtConfig {
+    /// Install KubeVirt and the Containerized Data Importer
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// KubeVirt release (e.g., "v1.5.0")
+    #[serde(default = "default_kubevirt_version")]
+    pub version: String,
+
+    /// CDI release (e.g., "v1.61.0")
+    #[serde(default = "default_cdi_version")]
+    pub cdi_version: String,
+
+    /// Run VMs without `/dev/kvm`, emulating the CPU in software; needed on
+    /// Hetzner Cloud servers, and slow
+    #[serde(default)]
+    pub software_emulation: bool,
+}
+
+impl Default for KubevirtConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            version: default_kubevirt_version(),
+            cdi_version: default_cdi_version(),
+            software_emulation: false,
+        }
+    }
+}
+
+/// Knative Serving addon configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct KnativeConfig {
+    /// Install Knative Serving with the Gateway API networking layer
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Knative release (e.g., "1.17.0")
+    #[serde(default = "default_knative_version")]
+    pub version: String,
+}
+
+impl Default for KnativeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            version: default_knative_version(),
+        }
+    }
+}
+
+/// Descheduler addon configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct DeschedulerConfig {
+    /// Install the descheduler
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// descheduler Helm chart version (e.g., "0.32.2")
+    #[serde(default = "default_descheduler_version")]
+    pub version: String,
+
+    /// Time between descheduling runs (e.g., "5m", "1h")
+    #[serde(default = "default_descheduling_interval")]
+    #[schemars(regex(pattern = r"^[0-9]+(s|m|h)$"))]
+    pub interval: String,
+}
+
+impl Default for DeschedulerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            version: default_descheduler_version(),
+            interval: default_descheduling_interval(),
+        }
+    }
+}
+
+/// node-problem-detector addon configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct NodeProblemDetectorConfig {
+    /// Install node-problem-detector
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// node-problem-detector Helm chart version (e.g., "2.3.14")
+    #[serde(default = "default_node_problem_detector_version")]
+    pub version: String,
+}
+
+impl Default for NodeProblemDetectorConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            version: default_node_problem_detector_version(),
+        }
+    }
+}
+
+/// metrics-server addon configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct MetricsServerConfig {
+    /// Install metrics-server
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// metrics-server Helm chart version (e.g., "3.12.2")
+    #[serde(default = "default_metrics_server_version")]
+    pub version: String,
+}
+
+impl Default for MetricsServerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            version: default_metrics_server_version(),
+        }
+    }
+}
+
+/// Vertical Pod Autoscaler addon configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct VpaConfig {
+    /// Install the VPA recommender
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Fairwinds vpa Helm chart version (e.g., "4.7.2")
+    #[serde(default = "default_vpa_version")]
+    pub version: String,
+
+    /// Also install the updater and admission controller, which apply the
+    /// recommendations to pods
+    #[serde(default)]
+    pub auto_update: bool,
+}
+
+impl Default for VpaConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            version: default_vpa_version(),
+            auto_update: false,
+        }
+    }
+}
+
+/// Headlamp dashboard addon configuration
+///
+/// The dashboard is only reachable through the port-forward of
+/// `oxide dashboard-ui`, never through an Ingress or load balancer.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct DashboardConfig {
+    /// Install Headlamp
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Headlamp Helm chart version (e.g., "0.30.1")
+    #[serde(default = "default_dashboard_version")]
+    pub version: String,
+}
+
+impl Default for DashboardConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            version: default_dashboard_version(),
+        }
+    }
+}
+
+/// How the ingress-nginx controller Service is exposed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IngressService {
+    /// Cilium node IPAM, on the public IPs of the nodes
+    #[default]
+    NodeIpam,
+    /// A Hetzner Cloud load balancer created by the hcloud
+    /// cloud-controller-manager, reaching the nodes over the private network
+    LoadBalancer,
+}
+
+/// Policy engine addon configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct PolicyEngineConfig {
+    /// Install the policy engine and the policies enabled below
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Admission controller enforcing the policies
+    #[serde(default)]
+    pub engine: PolicyEngine,
+
+    /// Helm chart version of the engine, defaults to a tested release
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+
+    /// Reject violating pods instead of only reporting them
+    #[serde(default)]
+    pub enforce: bool,
+
+    /// Disallow privileged containers
+    #[serde(default = "default_true")]
+    pub disallow_privileged: bool,
+
+    /// Require CPU and memory limits on every container
+    #[serde(default = "default_true")]
+    pub require_limits: bool,
+
+    /// Registries images must come from, e.g. `ghcr.io/acme`; no
+    /// restriction if empty
+    #[serde(default)]
+    pub allowed_registries: Vec<String>,
+
+    /// Namespaces exempt from the policies, besides the system and addon
+    /// namespaces
+    #[serde(default)]
+    pub exclude_namespaces: Vec<String>,
+}
+
+impl Default for PolicyEngineConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            engine: PolicyEngine::default(),
+            version: None,
+            enforce: false,
+            disallow_privileged: true,
+            require_limits: true,
+            allowed_registries: Vec::new(),
+            exclude_namespaces: Vec::new(),
+        }
+    }
+}
+
+/// Admission controller of the policy engine addon
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyEngine {
+    /// Kyverno ClusterPolicies
+    #[default]
+    Kyverno,
+    /// OPA Gatekeeper ConstraintTemplates and Constraints
+    Gatekeeper,
+}
+
+impl PolicyEngine {
+    pub fn title(self) -> &'static str {
+        match self {
+            PolicyEngine::Kyverno => "Kyverno",
+            PolicyEngine::Gatekeeper => "Gatekeeper",
+        }
+    }
+
+    /// Chart version installed unless `version` is set
+    pub fn default_version(self) -> &'static str {
+        match self {
+            PolicyEngine::Kyverno => "3.3.4",
+            PolicyEngine::Gatekeeper => "3.18.2",
+        }
+    }
+}
+
+/// Image pre-pull addon configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct ImagePrepullConfig {
+    /// Keep the images below pulled on every node, new nodes included
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Images to pull, e.g. `ghcr.io/acme/api:1.4.2`
+    #[serde(default)]
+    pub images: Vec<String>,
+
+    /// Also pull the Cilium and Hubble images of the configured Cilium
+    /// version, before `oxide cni reinstall` replaces the running pods
+    #[serde(default = "default_true")]
+    pub include_cilium: bool,
+}
+
+impl Default for ImagePrepullConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            images: Vec::new(),
+            include_cilium: true,
+        }
+    }
+}
+
+/// Per-phase timeouts (seconds) for waits during cluster operations
+///
+/// The defaults suit small clusters in well-connected locations; large
+/// clusters or slow locations may need higher values.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct TimeoutsConfig {
+    /// Talos booting with its machine config on new servers
+    #[serde(default = "default_talos_boot_timeout")]
+    pub talos_boot: u64,
+
+    /// Kubernetes API server becoming reachable after bootstrap
+    #[serde(default = "default_api_server_timeout")]
+    pub api_server: u64,
+
+    /// Cilium pods becoming ready
+    #[serde(default = "default_cni_timeout")]
+    pub cni: u64,
+
+    /// New or replaced nodes reporting Ready
+    #[serde(default = "default_node_ready_timeout")]
+    pub node_ready: u64,
+
+    /// Nodes being cordoned before removal
+    #[serde(default = "default_node_drain_timeout")]
+    pub node_drain: u64,
+
+    /// Graceful Talos reset of removed nodes
+    #[serde(default = "default_node_reset_timeout")]
+    pub node_reset: u64,
+
+    /// Servers shutting down before a resize
+    #[serde(default = "default_server_shutdown_timeout")]
+    pub server_shutdown: u64,
+
+    /// Addon workloads rolling out
+    #[serde(default = "default_addon_rollout_timeout")]
+    pub addon_rollout: u64,
+
+    /// Rescue system accepting SSH after `oxide ssh --rescue`
+    #[serde(default = "default_rescue_boot_timeout")]
+    pub rescue_boot: u64,
+
+    /// Each wait of the post-create smoke test
+    #[serde(default = "default_smoke_test_timeout")]
+    pub smoke_test: u64,
+
+    /// `talosctl upgrade` installing the new version and rebooting a node
+    #[serde(default = "default_node_upgrade_timeout")]
+    pub node_upgrade: u64,
+
+    /// Health gates passing after a node was upgraded
+    #[serde(default = "default_health_gates_timeout")]
+    pub health_gates: u64,
+
+    /// kube-bench Jobs of `oxide audit` completing
+    #[serde(default = "default_security_audit_timeout")]
+    pub security_audit: u64,
+}
+
+/// Webhook notification settings
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct NotificationsConfig {
+    /// Incoming webhook URL (Slack, Discord or any HTTP endpoint)
+    pub webhook_url: String,
+
+    /// Payload shape expected by the webhook
+    #[serde(default)]
+    pub format: WebhookFormat,
+
+    /// Which operations trigger a notification: a command name (`create`,
+    /// `upgrade`, ...) optionally suffixed with `:success` or `:failure`
+    #[serde(default = "default_notification_events")]
+    pub events: Vec<String>,
+}
+
+impl NotificationsConfig {
+    /// Whether the outcome of `command` should be posted
+    pub fn wants(&self, command: &str, success: bool) -> bool {
+        let outcome = if success { "success" } else { "failure" };
+        self.events.iter().any(|event| match event.split_once(':') {
+            Some((cmd, filter)) => cmd == command && filter == outcome,
+            None => event == command,
+        })
+    }
+}
+
+/// In-cluster Alertmanager, reached through the Kubernetes API service proxy
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct AlertmanagerConfig {
+    /// Namespace of the Alertmanager service
+    #[serde(default = "default_alertmanager_namespace")]
+    pub namespace: String,
+
+    /// Alertmanager service name
+    #[serde(default = "default_alertmanager_service")]
+    pub service: String,
+
+    /// Port of the Alertmanager API on the service
+    #[serde(default = "default_alertmanager_port")]
+    pub port: u16,
+}
+
+/// S3-compatible object storage, Hetzner Object Storage by default
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct StorageConfig {
+    /// Bucket name
+    pub bucket: String,
+
+    /// Hetzner Object Storage location (fsn1, nbg1, hel1); the endpoint is
+    /// `https://<location>.your-objectstorage.com`
+    #[serde(default = "default_storage_location")]
+    pub location: String,
+
+    /// Endpoint of another S3-compatible service, used instead of the
+    /// Hetzner one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+
+    /// Signing region of `endpoint`; defaults to `location`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+
+    /// Key prefix of the cluster's objects; defaults to the cluster name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+
+    /// Environment variable holding the access key
+    #[serde(default = "default_storage_access_key_env")]
+    pub access_key_env: String,
+
+    /// Environment variable holding the secret key
+    #[serde(default = "default_storage_secret_key_env")]
+    pub secret_key_env: String,
+
+    /// Keep `cluster-state.json` in the bucket instead of only in the output
+    /// directory, locked while a command changes the cluster
+    #[serde(default)]
+    pub remote_state: bool,
+}
+
+/// Helm chart installation settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct HelmConfig {
+    /// Install as Helm releases, or render and server-side apply
+    #[serde(default)]
+    pub mode: HelmMode,
+}
+
+/// How Helm charts are installed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HelmMode {
+    /// `helm upgrade --install`, with release state stored in the cluster
+    #[default]
+    Release,
+    /// `helm template` locally, then `kubectl apply --server-side`
+    Template,
+}
+
+/// Rolling upgrade settings
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct UpgradeConfig {
+    /// Checks that must pass after each node before the next one is upgraded
+    #[serde(default = "default_health_gates")]
+    pub health_gates: Vec<HealthGate>,
+}
+
+impl Default for UpgradeConfig {
+    fn default() -> Self {
+        Self {
+            health_gates: default_health_gates(),
+        }
+    }
+}
+
+/// Check run after upgrading a node, in addition to the node being Ready
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthGate {
+    /// Every pod scheduled on the node is Ready
+    PodsReady,
+    /// All etcd members respond (checked after control plane nodes)
+    Etcd,
+    /// The Cilium agent on the node is Ready
+    Cilium,
+}
+
+/// Hetzner Robot dedicated servers connected through a vSwitch
+///
+/// The vSwitch is coupled to the primary cloud network as a subnet, so cloud
+/// nodes reach the Robot servers' VLAN addresses through the network gateway.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct RobotConfig {
+    /// vSwitch ID from the Robot panel
+    pub vswitch_id: u64,
+
+    /// VLAN ID of the vSwitch (4000-4091)
+    #[schemars(range(min = 4000, max = 4091))]
+    pub vlan: u16,
+
+    /// Subnet of the cloud network for the vSwitch (e.g. "10.0.2.0/24")
+    #[schemars(regex(pattern = r"^[0-9a-fA-F:.]+/[0-9]{1,3}$"))]
+    pub subnet_cidr: String,
+
+    /// Dedicated servers joining the cluster as workers
+    #[serde(default)]
+    pub servers: Vec<RobotServer>,
+}
+
+/// A Robot dedicated server booted into Talos maintenance mode
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct RobotServer {
+    /// Node name suffix; the node is named `<cluster>-<name>`
+    pub name: String,
+
+    /// Main public IPv4 address of the server
+    pub public_ip: String,
+
+    /// Address on the vSwitch VLAN, inside `robot.subnet_cidr`
+    pub private_ip: String,
+
+    /// Physical interface carrying the VLAN (default: the first physical one)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interface: Option<String>,
+
+    /// Disk Talos installs to
+    #[serde(default = "default_robot_install_disk")]
+    pub install_disk: String,
+
+    /// Additional Kubernetes labels for the node
+    #[serde(default)]
+    pub labels: std::collections::HashMap<String, String>,
+}
+
+/// DNS names of the cluster's applications
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct DnsConfig {
+    /// Domain whose wildcard record points at the nodes or load balancer
+    /// (e.g. "apps.example.com"); addons like Knative name their routes
+    /// under it
+    pub domain: String,
+}
+
+/// Operator RBAC bootstrapped right after the cluster comes up
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct RbacConfig {
+    /// API server OIDC authentication, so groups can map identity provider groups
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oidc: Option<OidcConfig>,
+
+    /// Custom ClusterRoles, in addition to the built-in `view`, `edit` and `admin`
+    #[serde(default)]
+    pub cluster_roles: Vec<ClusterRoleConfig>,
+
+    /// Groups bound to a ClusterRole
+    #[serde(default)]
+    pub groups: Vec<RbacGroup>,
+
+    /// ServiceAccounts bound to a ClusterRole, e.g. for CI deployments
+    #[serde(default)]
+    pub service_accounts: Vec<RbacServiceAccount>,
+}
+
+/// API server OIDC settings
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct OidcConfig {
+    /// Issuer URL of the identity provider
+    pub issuer_url: String,
+
+    /// Client ID tokens must be issued for
+    pub client_id: String,
+
+    /// Token claim used as the Kubernetes username
+    #[serde(default = "default_oidc_username_claim")]
+    pub username_claim: String,
+
+    /// Token claim holding the user's groups
+    #[serde(default = "default_oidc_groups_claim")]
+    pub groups_claim: String,
+
+    /// Prefix added to OIDC usernames and groups, keeping them apart from
+    /// client certificate identities
+    #[serde(default = "default_oidc_prefix")]
+    pub prefix: String,
+}
+
+/// A ClusterRole created by oxide
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct ClusterRoleConfig {
+    /// ClusterRole name
+    pub name: String,
+
+    /// Policy rules, as in a ClusterRole manifest
+    #[schemars(with = "Vec<serde_json::Value>")]
+    pub rules: Vec<serde_yaml::Value>,
+}
+
+/// A group of operators bound to a ClusterRole
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct RbacGroup {
+    /// Group name, also the Kubernetes group when `oidc_groups` is empty
+    pub name: String,
+
+    /// ClusterRole granted to the group
+    pub cluster_role: String,
+
+    /// Identity provider groups mapped to this group (requires `rbac.oidc`)
+    #[serde(default)]
+    pub oidc_groups: Vec<String>,
+
+    /// Only grant the role in these namespaces instead of cluster-wide
+    #[serde(default)]
+    pub namespaces: Vec<String>,
+}
+
+/// A ServiceAccount bound to a ClusterRole
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct RbacServiceAccount {
+    /// ServiceAccount name
+    pub name: String,
+
+    /// Namespace the ServiceAccount lives in
+    pub namespace: String,
+
+    /// ClusterRole granted to the ServiceAccount
+    pub cluster_role: String,
+
+    /// Only grant the role in these namespaces instead of cluster-wide
+    #[serde(default)]
+    pub namespaces: Vec<String>,
+}
+
+/// A namespace prepared for one team
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct TenantConfig {
+    /// Namespace name
+    #[schemars(regex(pattern = r"^[a-z0-9]([a-z0-9-]{0,61}[a-z0-9])?$"))]
+    pub name: String,
+
+    /// Extra labels of the namespace
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+
+    /// ResourceQuota limits, as in `spec.hard`, e.g. `requests.cpu: "8"`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    #[schemars(with = "BTreeMap<String, serde_json::Value>")]
+    pub quota: BTreeMap<String, serde_yaml::Value>,
+
+    /// LimitRange defaults and bounds for containers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limits: Option<TenantLimits>,
+
+    /// Only accept traffic from pods of the same namespace and of
+    /// `allow_from`
+    #[serde(default = "default_true")]
+    pub isolated: bool,
+
+    /// Namespaces that may still reach the tenant, e.g. an ingress controller
+    #[serde(default)]
+    pub allow_from: Vec<String>,
+
+    /// ClusterRoles granted within the namespace
+    #[serde(default)]
+    pub bindings: Vec<TenantBinding>,
+}
+
+/// Container resources of a LimitRange, each keyed by resource name
+/// (`cpu`, `memory`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct TenantLimits {
+    /// Limits of containers that set none
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub default: BTreeMap<String, String>,
+
+    /// Requests of containers that set none
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub default_request: BTreeMap<String, String>,
+
+    /// Largest limits a container may set
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub max: BTreeMap<String, String>,
+}
+
+/// Kubernetes groups, users and ServiceAccounts bound to a ClusterRole in a
+/// tenant namespace
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct TenantBinding {
+    /// ClusterRole granted, e.g. `admin` or `edit`
+    pub cluster_role: String,
+
+    /// Kubernetes groups, with the `rbac.oidc` prefix for OIDC groups
+    #[serde(default)]
+    pub groups: Vec<String>,
+
+    /// Kubernetes users
+    #[serde(default)]
+    pub users: Vec<String>,
+
+    /// ServiceAccounts of the tenant namespace, created if missing
+    #[serde(default)]
+    pub service_accounts: Vec<String>,
+}
+
+/// Webhook payload format
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookFormat {
+    /// JSON object with the operation fields plus a `text` summary
+    #[default]
+    Generic,
+    /// Slack incoming webhook (`{"text": ...}`)
+    Slack,
+    /// Discord webhook (`{"content": ...}`)
+    Discord,
+}
+
+impl Default for TimeoutsConfig {
+    fn default() -> Self {
+        Self {
+            talos_boot: default_talos_boot_timeout(),
+            api_server: default_api_server_timeout(),
+            cni: default_cni_timeout(),
+            node_ready: default_node_ready_timeout(),
+            node_drain: default_node_drain_timeout(),
+            node_reset: default_node_reset_timeout(),
+            server_shutdown: default_server_shutdown_timeout(),
+            addon_rollout: default_addon_rollout_timeout(),
+            rescue_boot: default_rescue_boot_timeout(),
+            smoke_test: default_smoke_test_timeout(),
+            node_upgrade: default_node_upgrade_timeout(),
+            health_gates: default_health_gates_timeout(),
+            security_audit: default_security_audit_timeout(),
+        }
+    }
+}
+
+impl TimeoutsConfig {
+    /// Apply a `--timeout` CLI override to every phase
+    pub fn with_override(&self, timeout_secs: Option<u64>) -> Self {
+        match timeout_secs {
+            Some(secs) => Self {
+                talos_boot: secs,
+                api_server: secs,
+                cni: secs,
+                node_ready: secs,
+                node_drain: secs,
+                node_reset: secs,
+                server_shutdown: secs,
+                addon_rollout: secs,
+                rescue_boot: secs,
+                smoke_test: secs,
+                node_upgrade: secs,
+                health_gates: secs,
+                security_audit: secs,
+            },
+            None => self.clone(),
+        }
+    }
+
+    fn all(&self) -> [(&'static str, u64); 13] {
+        [
+            ("talos_boot", self.talos_boot),
+            ("api_server", self.api_server),
+            ("cni", self.cni),
+            ("node_ready", self.node_ready),
+            ("node_drain", self.node_drain),
+            ("node_reset", self.node_reset),
+            ("server_shutdown", self.server_shutdown),
+            ("addon_rollout", self.addon_rollout),
+            ("rescue_boot", self.rescue_boot),
+            ("smoke_test", self.smoke_test),
+            ("node_upgrade", self.node_upgrade),
+            ("health_gates", self.health_gates),
+            ("security_audit", self.security_audit),
+        ]
+    }
+}
+
+fn default_talos_boot_timeout() -> u64 {
+    600
+}
+
+fn default_api_server_timeout() -> u64 {
+    300
+}
+
+fn default_cni_timeout() -> u64 {
+    300
+}
+
+fn default_node_ready_timeout() -> u64 {
+    300
+}
+
+fn default_node_drain_timeout() -> u64 {
+    120
+}
+
+fn default_node_reset_timeout() -> u64 {
+    600
+}
+
+fn default_server_shutdown_timeout() -> u64 {
+    120
+}
+
+fn default_addon_rollout_timeout() -> u64 {
+    600
+}
+
+fn default_notification_events() -> Vec<String> {
+    vec![
+        "create".to_string(),
+        "upgrade".to_string(),
+        "destroy:failure".to_string(),
+    ]
+}
+
+fn default_alertmanager_namespace() -> String {
+    "monitoring".to_string()
+}
+
+fn default_alertmanager_service() -> String {
+    "alertmanager-operated".to_string()
+}
+
+fn default_alertmanager_port() -> u16 {
+    9093
+}
+
+fn default_storage_location() -> String {
+    "fsn1".to_string()
+}
+
+fn default_storage_access_key_env() -> String {
+    "OXIDE_S3_ACCESS_KEY".to_string()
+}
+
+fn default_storage_secret_key_env() -> String {
+    "OXIDE_S3_SECRET_KEY".to_string()
+}
+
+fn default_rescue_boot_timeout() -> u64 {
+    300
+}
+
+fn default_smoke_test_timeout() -> u64 {
+    300
+}
+
+fn default_node_upgrade_timeout() -> u64 {
+    900
+}
+
+fn default_health_gates_timeout() -> u64 {
+    600
+}
+
+fn default_security_audit_timeout() -> u64 {
+    300
+}
+
+fn default_health_gates() -> Vec<HealthGate> {
+    vec![HealthGate::PodsReady, HealthGate::Etcd, HealthGate::Cilium]
+}
+
+fn default_oidc_username_claim() -> String {
+    "email".to_string()
+}
+
+fn default_oidc_groups_claim() -> String {
+    "groups".to_string()
+}
+
+fn default_oidc_prefix() -> String {
+    "oidc:".to_string()
+}
+
+fn default_robot_install_disk() -> String {
+    "/dev/nvme0n1".to_string()
+}
+
+fn default_nat_gateway_server_type() -> String {
+    "cx22".to_string()
+}
+
+fn default_nat_gateway_image() -> String {
+    "debian-12".to_string()
+}
+
+fn default_nvidia_device_plugin_version() -> String {
+    "0.17.1".to_string()
+}
+
+fn default_egress_destinations() -> Vec<String> {
+    vec!["0.0.0.0/0".to_string()]
+}
+
+fn default_same_namespace_allowed() -> Vec<String> {
+    vec!["default".to_string()]
+}
+
+fn default_spegel_version() -> String {
+    "0.3.0".to_string()
+}
+
+fn default_ingress_nginx_version() -> String {
+    "4.12.1".to_string()
+}
+
+fn default_istio_version() -> String {
+    "1.25.2".to_string()
+}
+
+fn default_kubevirt_version() -> String {
+    "v1.5.0".to_string()
+}
+
+fn default_cdi_version() -> String {
+    "v1.61.0".to_string()
+}
+
+fn default_knative_version() -> String {
+    "1.17.0".to_string()
+}
+
+fn default_descheduler_version() -> String {
+    "0.32.2".to_string()
+}
+
+fn default_descheduling_interval() -> String {
+    "5m".to_string()
+}
+
+fn default_node_problem_detector_version() -> String {
+    "2.3.14".to_string()
+}
+
+fn default_metrics_server_version() -> String {
+    "3.12.2".to_string()
+}
+
+fn default_vpa_version() -> String {
+    "4.7.2".to_string()
+}
+
+fn default_dashboard_version() -> String {
+    "0.30.1".to_string()
+}
+
+fn default_load_balancer_type() -> String {
+    "lb11".to_string()
+}
+
+fn default_http_port() -> u16 {
+    80
+}
+
+fn default_https_port() -> u16 {
+    443
+}
+
+fn default_ingress_nginx_replicas() -> u32 {
+    2
+}
+
+fn default_longhorn_version() -> String {
+    "1.9.1".to_string()
+}
+
+fn default_longhorn_replicas() -> u32 {
+    3
+}
+
+fn default_mirrored_registries() -> Vec<String> {
+    [
+        "docker.io",
+        "ghcr.io",
+        "quay.io",
+        "registry.k8s.io",
+        "gcr.io",
+        "nvcr.io",
+        "factory.talos.dev",
+    ]
+    .iter()
+    .map(|registry| registry.to_string())
+    .collect()
+}
+
+fn default_api_version() -> String {
+    migrate::API_VERSION.to_string()
+}
+
+/// Talos default pod CIDR
+pub fn default_pod_cidr() -> String {
+    "10.244.0.0/16".to_string()
+}
+
+/// Talos default service CIDR
+pub fn default_service_cidr() -> String {
+    "10.96.0.0/12".to_string()
+}
+
+fn default_ipv6_pod_cidr() -> String {
+    "fd00:10:244::/56".to_string()
+}
+
+fn default_ipv6_service_cidr() -> String {
+    "fd00:10:96::/112".to_string()
+}
+
+fn default_http_timeout() -> u64 {
+    30
+}
+
+fn default_create_concurrency() -> usize {
+    10
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_one() -> u32 {
+    1
+}
+
+impl ClusterConfig {
+    /// Load configuration from a YAML file, with optional overlays deep-merged
+    /// on top of it in order
+    ///
+    /// `${ENV_VAR}` and `${file:...}` references are expanded in each file
+    /// first. The merged config is then checked against the config schema, so
+    /// every problem is reported with its file, line and field instead of
+    /// serde's first error.
+    pub fn from_files<P: AsRef<Path>>(paths: &[P]) -> anyhow::Result<Self> {
+        let mut sources = Vec::new();
+        let mut merged = serde_yaml::Value::Null;
+
+        for path in paths {
+            let path = path.as_ref();
+            let file = path.display().to_string();
+            let mut raw = std::fs::read_to_string(path)
+                .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", file, e))?;
+            let format = ConfigFormat::from_path(path);
+            if format.parse(&raw).is_ok_and(|doc| sops::is_encrypted(&doc)) {
+                raw = sops::decrypt_file(path, format)?;
+            }
+            let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+            let content = interpolate::interpolate(&raw, base_dir)
+                .map_err(|e| anyhow::anyhow!("{}: {}", file, e))?;
+
+            let mut value = format
+                .parse(&content)
+                .map_err(|e| anyhow::anyhow!("{}: {}", file, e))?;
+            if migrate::migrate_value(&mut value).map_err(|e| anyhow::anyhow!("{}: {}", file, e))? {
+                tracing::warn!(
+                    "⚠️  {} uses an older apiVersion, run `oxide config migrate` to update it",
+                    file
+                );
+            }
+            overlay::merge(&mut merged, value);
+            sources.push(schema::Source { file, content });
+        }
+
+        schema::check(&sources, &merged)?;
+
+        let name: Vec<&str> = sources.iter().map(|s| s.file.as_str()).collect();
+        let name = name.join(" + ");
+        let config: ClusterConfig =
+            serde_yaml::from_value(merged).map_err(|e| anyhow::anyhow!("{}: {}", name, e))?;
+        config
+            .validate()
+            .map_err(|e| anyhow::anyhow!("{}: {}", name, e))?;
+        Ok(config)
+    }
+
+    /// Validate the configuration
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.cluster_name.is_empty() {
+            anyhow::bail!("cluster_name cannot be empty");
+        }
+
+        if self.control_planes.is_empty() {
+            anyhow::bail!("at least one control plane node is required");
+        }
+
+        if let Some(pool) = self.control_planes.iter().find(|p| p.gpu) {
+            anyhow::bail!(
+                "control plane pool '{}' cannot be a GPU pool; use a worker pool instead",
+                pool.name
+            );
+        }
+
+        // All control planes share one machine config
+        if let Some(pool) = self.workers.iter().find(|p| p.allow_workloads) {
+            anyhow::bail!(
+                "allow_workloads is a control plane setting, remove it from worker pool '{}'",
+                pool.name
+            );
+        }
+        if self
+            .control_planes
+            .iter()
+            .any(|p| p.allow_workloads != self.control_planes[0].allow_workloads)
+        {
+            anyhow::bail!("allow_workloads must be the same for all control plane pools");
+        }
+
+        for pool in self.control_planes.iter().chain(self.workers.iter()) {
+            if let Some(template) = &pool.rdns_template {
+                // Without {node} every server in the pool would get the same PTR
+                if !template.contains("{node}") {
+                    anyhow::bail!(
+                        "rdns_template for pool '{}' must contain the {{node}} placeholder",
+                        pool.name
+                    );
+                }
+            }
+        }
+
+        // Pools may only use locations that have a cluster network
+        let zones: Vec<&str> = std::iter::once(&self.hcloud.network)
+            .chain(&self.hcloud.regions)
+            .map(|network| network.zone.as_str())
+            .collect();
+        for pool in self.control_planes.iter().chain(self.workers.iter()) {
+            let mut locations: Vec<&String> = pool.location.iter().collect();
+            if let Some(topology) = &pool.topology {
+                if topology.locations.is_empty() {
+                    anyhow::bail!("topology.locations of pool '{}' cannot be empty", pool.name);
+                }
+                for (i, location) in topology.locations.iter().enumerate() {
+                    if topology.locations[..i].contains(location) {
+                        anyhow::bail!(
+                            "topology.locations of pool '{}' lists {} twice",
+                            pool.name,
+                            location
+                        );
+                    }
+                }
+                locations.extend(&topology.locations);
+            }
+            for location in locations {
+                let zone = schema::location_zone(location);
+                if zone.is_some_and(|zone| !zones.contains(&zone)) {
+                    anyhow::bail!(
+                        "location {} of pool '{}' is outside the network zones {}; add its zone to hcloud.regions",
+                        location,
+                        pool.name,
+                        zones.join(", ")
+                    );
+                }
+            }
+        }
+
+        // The API and talosctl reach control planes on their public IPs, and
+        // the NAT gateway only routes the primary network
+        if let Some(pool) = self.control_planes.iter().find(|p| !p.public_ip) {
+            anyhow::bail!(
+                "control plane pool '{}' needs public IPs; only workers can set public_ip: false",
+                pool.name
+            );
+        }
+        if let Some(pool) = self.control_planes.iter().find(|p| p.project.is_some()) {
+            anyhow::bail!(
+                "control plane pool '{}' cannot set project; control planes stay in the project of hcloud.token",
+                pool.name
+            );
+        }
+        for pool in &self.workers {
+            let Some(project) = &pool.project else {
+                continue;
+            };
+            if !self.hcloud.projects.contains_key(project) {
+                anyhow::bail!(
+                    "pool '{}' uses project '{}', which is not defined in hcloud.projects",
+                    pool.name,
+                    project
+                );
+            }
+            // The NAT gateway only routes the networks of the main project
+            if !pool.public_ip {
+                anyhow::bail!(
+                    "pool '{}' in project '{}' needs public IPs",
+                    pool.name,
+                    project
+                );
+            }
+        }
+        for (name, project) in &self.hcloud.projects {
+            if project.token.is_none()
+                && project.token_env.is_none()
+                && project.token_sops_file.is_none()
+            {
+                anyhow::bail!(
+                    "hcloud.projects.{} needs token, token_env or token_sops_file",
+                    name
+                );
+            }
+        }
+        for pool in self.workers.iter().filter(|p| !p.public_ip) {
+            let outside = pool
+                .location
+                .iter()
+                .chain(pool.topology.iter().flat_map(|t| &t.locations))
+                .find(|l| schema::location_zone(l).is_some_and(|z| z != self.hcloud.network.zone));
+            if let Some(location) = outside {
+                anyhow::bail!(
+                    "pool '{}' without public IPs must stay in network zone {}, not {}",
+                    pool.name,
+                    self.hcloud.network.zone,
+                    location
+                );
+            }
+        }
+
+        for ip in &self.hcloud.allowed_ips {
+            if ip.contains('/') {
+                self.validate_cidr(ip)?;
+            } else if ip.parse::<IpAddr>().is_err() {
+                anyhow::bail!("hcloud.allowed_ips: invalid address {}", ip);
+            }
+        }
+
+        if let Some(egress) = &self.hcloud.egress {
+            if !egress.defaults && egress.rules.is_empty() {
+                // Without any outbound rule Hetzner allows all egress again
+                anyhow::bail!("hcloud.egress needs rules when defaults are disabled");
+            }
+            for rule in &egress.rules {
+                let name = rule.description.as_deref().unwrap_or(&rule.protocol);
+                if rule.destination_ips.is_empty() {
+                    anyhow::bail!("egress rule '{}' has no destination_ips", name);
+                }
+                for ip in &rule.destination_ips {
+                    if ip.contains('/') {
+                        self.validate_cidr(ip)?;
+                    } else if ip.parse::<IpAddr>().is_err() {
+                        anyhow::bail!("egress rule '{}': invalid address {}", name, ip);
+                    }
+                }
+                match (rule.protocol.as_str(), &rule.port) {
+                    ("tcp" | "udp", None) => {
+                        anyhow::bail!("egress rule '{}' needs a port for {}", name, rule.protocol)
+                    }
+                    ("tcp" | "udp", Some(_)) => {}
+                    ("icmp" | "esp" | "gre", None) => {}
+                    ("icmp" | "esp" | "gre", Some(_)) => {
+                        anyhow::bail!("egress rule '{}': {} has no ports", name, rule.protocol)
+                    }
+                    (protocol, _) => {
+                        anyhow::bail!("egress rule '{}': unknown protocol {}", name, protocol)
+                    }
+                }
+            }
+        }
+
+        if self.hcloud.http.timeout == 0 {
+            anyhow::bail!("hcloud.http.timeout must be at least 1 second");
+        }
+        if self.hcloud.create_concurrency == 0 {
+            anyhow::bail!("hcloud.create_concurrency must be at least 1");
+        }
+
+        if let Some(robot) = &self.robot {
+            // Robot data centers only connect to the eu-central network zone
+            if self.hcloud.network.zone != "eu-central" {
+                anyhow::bail!("robot servers need hcloud.network.zone eu-central");
+            }
+            if self.hcloud.network.is_auto() {
+                anyhow::bail!("robot servers need a fixed hcloud.network.cidr, not auto");
+            }
+            if !(4000..=4091).contains(&robot.vlan) {
+                anyhow::bail!("robot.vlan must be between 4000 and 4091");
+            }
+            parse_network_cidr(&robot.subnet_cidr)?;
+            if !cidr_contains(&self.hcloud.network.cidr, &robot.subnet_cidr)? {
+                anyhow::bail!(
+                    "robot.subnet_cidr {} is not inside the network CIDR {}",
+                    robot.subnet_cidr,
+                    self.hcloud.network.cidr
+                );
+            }
+            if cidr_contains(&robot.subnet_cidr, &self.hcloud.network.subnet_cidr)?
+                || cidr_contains(&self.hcloud.network.subnet_cidr, &robot.subnet_cidr)?
+            {
+                anyhow::bail!("robot.subnet_cidr overlaps hcloud.network.subnet_cidr");
+            }
+            for (i, server) in robot.servers.iter().enumerate() {
+                if robot.servers[..i].iter().any(|s| s.name == server.name) {
+                    anyhow::bail!("robot server '{}' is listed twice", server.name);
+                }
+                if server.public_ip.parse::<std::net::Ipv4Addr>().is_err() {
+                    anyhow::bail!(
+                        "robot server '{}': invalid public_ip {}",
+                        server.name,
+                        server.public_ip
+                    );
+                }
+                let private = format!("{}/32", server.private_ip);
+                if !cidr_contains(&robot.subnet_cidr, &private).unwrap_or(false) {
+                    anyhow::bail!(
+                        "robot server '{}': private_ip {} is not inside robot.subnet_cidr {}",
+                        server.name,
+                        server.private_ip,
+                        robot.subnet_cidr
+                    );
+                }
+            }
+        }
+
+        if let Some((phase, _)) = self.timeouts.all().iter().find(|(_, secs)| *secs == 0) {
+            anyhow::bail!("timeouts.{} must be greater than 0", phase);
+        }
+
+        if self.addons.longhorn.enabled && self.addons.longhorn.replica_count == 0 {
+            anyhow::bail!("addons.longhorn.replica_count must be at least 1");
+        }
+
+        let prepull = &self.addons.image_prepull;
+        if prepull.enabled && prepull.images.is_empty() && !prepull.include_cilium {
+            anyhow::bail!("addons.image_prepull is enabled but has no images to pull");
+        }
+        if let Some(image) = prepull
+            .images
+            .iter()
+            .find(|image| image.is_empty() || image.contains(char::is_whitespace))
+        {
+            anyhow::bail!(
+                "addons.image_prepull.images entry '{}' is not an image",
+                image
+            );
+        }
+
+        let ingress = &self.addons.ingress_nginx;
+        if ingress.enabled && ingress.replicas == 0 {
+            anyhow::bail!("addons.ingress_nginx.replicas must be at least 1");
+        }
+        if self.cloud_load_balancers() {
+            // The cloud-controller-manager only knows the servers and the
+            // one network of the cluster's project
+            if self.robot.is_some() || self.multi_region() {
+                anyhow::bail!(
+                    "addons.ingress_nginx.service load_balancer needs all nodes in one \
+                     Hetzner Cloud project and network; use node_ipam with robot servers, \
+                     hcloud.regions or worker projects"
+                );
+            }
+        } else if ingress.enabled && ingress.http_port == ingress.https_port {
+            anyhow::bail!("addons.ingress_nginx.http_port and https_port must differ");
+        }
+
+        let policy = &self.addons.policy_engine;
+        if policy.enabled
+            && !policy.disallow_privileged
+            && !policy.require_limits
+            && policy.allowed_registries.is_empty()
+        {
+            anyhow::bail!("addons.policy_engine is enabled but every policy is turned off");
+        }
+        if let Some(registry) = policy.allowed_registries.iter().find(|registry| {
+            registry.trim_end_matches('/').is_empty() || registry.contains(char::is_whitespace)
+        }) {
+            anyhow::bail!(
+                "addons.policy_engine.allowed_registries entry '{}' is not a registry",
+                registry
+            );
+        }
+
+        // The mesh components cannot run inside the mesh themselves
+        if let Some(namespace) = self
+            .addons
+            .istio
+            .ambient_namespaces
+            .iter()
+            .find(|ns| ["istio-system", "kube-system"].contains(&ns.as_str()))
+        {
+            anyhow::bail!(
+                "addons.istio.ambient_namespaces cannot include {}",
+                namespace
+            );
+        }
+
+        // Hetzner Cloud servers do not support nested virtualization, so
+        // only Robot servers expose /dev/kvm
+        let kubevirt = &self.addons.kubevirt;
+        if kubevirt.enabled
+            && !kubevirt.software_emulation
+            && self
+                .robot
+                .as_ref()
+                .is_none_or(|robot| robot.servers.is_empty())
+        {
+            let mut server_types: Vec<&str> = self
+                .control_planes
+                .iter()
+                .chain(&self.workers)
+                .map(|pool| pool.server_type.as_str())
+                .collect();
+            server_types.sort_unstable();
+            server_types.dedup();
+            anyhow::bail!(
+                "addons.kubevirt needs nodes with hardware virtualization, which the Hetzner \
+                 Cloud server types {} lack; add robot servers or set \
+                 addons.kubevirt.software_emulation",
+                server_types.join(", ")
+            );
+        }
+        if kubevirt.enabled {
+            if let Some((field, _)) = [
+                ("version", &kubevirt.version),
+                ("cdi_version", &kubevirt.cdi_version),
+            ]
+            .into_iter()
+            .find(|(_, version)| !version.starts_with('v'))
+            {
+                anyhow::bail!(
+                    "addons.kubevirt.{} must be a release tag like v1.5.0",
+                    field
+                );
+            }
+        }
+
+        if self.addons.knative.enabled {
+            if !self.cilium.gateway_api {
+                anyhow::bail!(
+                    "addons.knative routes through the Cilium Gateway; set cilium.gateway_api"
+                );
+            }
+            // The load balancers of the cloud-controller-manager would put
+            // the cluster-local Gateway on the internet
+            if self.cloud_load_balancers() {
+                anyhow::bail!(
+                    "addons.knative needs the Cilium Gateways on node IPs; it does not \
+                     support addons.ingress_nginx.service load_balancer"
+                );
+            }
+            if self.addons.ingress_nginx.node_ports().contains(&80) {
+                anyhow::bail!(
+                    "addons.knative and addons.ingress_nginx both listen on port 80 of the \
+                     nodes; move ingress-nginx to other ports"
+                );
+            }
+        }
+
+        let interval = &self.addons.descheduler.interval;
+        let (amount, unit) = interval.split_at(interval.len().saturating_sub(1));
+        if !matches!(unit, "s" | "m" | "h") || amount.parse::<u32>().map_or(true, |n| n == 0) {
+            anyhow::bail!(
+                "addons.descheduler.interval '{}' must be a duration like 5m",
+                interval
+            );
+        }
+
+        if let Some(notifications) = &self.notifications {
+            if !notifications.webhook_url.starts_with("https://")
+                && !notifications.webhook_url.starts_with("http://")
+            {
+                anyhow::bail!("notifications.webhook_url must be an http(s) URL");
+            }
+            for event in &notifications.events {
+                if let Some((_, outcome)) = event.split_once(':') {
+                    if outcome != "success" && outcome != "failure" {
+                        anyhow::bail!(
+                            "notifications event '{}' must end in :success or :failure",
+                            event
+                        );
+                    }
+                }
+            }
+        }
+
+        if let Some(rbac) = &self.rbac {
+            if let Some(group) = rbac.groups.iter().find(|g| !g.oidc_groups.is_empty()) {
+                if rbac.oidc.is_none() {
+                    anyhow::bail!(
+                        "rbac group '{}' maps oidc_groups but rbac.oidc is not configured",
+                        group.name
+                    );
+                }
+            }
+            if let Some(oidc) = &rbac.oidc {
+                if !oidc.issuer_url.starts_with("https://") {
+                    anyhow::bail!("rbac.oidc.issuer_url must be an https URL");
+                }
+            }
+        }
+
+        let mut tenants = std::collections::HashSet::new();
+        for tenant in &self.tenants {
+            if !tenants.insert(tenant.name.as_str()) {
+                anyhow::bail!("tenant '{}' is defined more than once", tenant.name);
+            }
+            if let Some(binding) = tenant.bindings.iter().find(|b| {
+                b.groups.is_empty() && b.users.is_empty() && b.service_accounts.is_empty()
+            }) {
+                anyhow::bail!(
+                    "tenant '{}' binds {} to nobody; list groups, users or service_accounts",
+                    tenant.name,
+                    binding.cluster_role
+                );
+            }
+            let mut roles = std::collections::HashSet::new();
+            if let Some(binding) = tenant
+                .bindings
+                .iter()
+                .find(|b| !roles.insert(b.cluster_role.as_str()))
+            {
+                anyhow::bail!(
+                    "tenant '{}' binds {} more than once; list all its subjects in one binding",
+                    tenant.name,
+                    binding.cluster_role
+                );
+            }
+        }
+
+        for san in &self.talos.cert_sans {
+            let name = san.strip_prefix("*.").unwrap_or(san);
+            if san.parse::<IpAddr>().is_err() && !is_dns_name(name) {
+                anyhow::bail!(
+                    "talos.cert_sans entry '{}' is neither an IP address nor a DNS name",
+                    san
+                );
+            }
+        }
+
+        if let Some(endpoint) = &self.talos.cluster_endpoint {
+            validate_endpoint(endpoint)?;
+        }
+
+        if let Some(dns) = &self.dns {
+            if !is_dns_name(&dns.domain) {
+                anyhow::bail!("dns.domain '{}' is not a DNS name", dns.domain);
+            }
+        }
+
+        if let Some(mirror) = &self.talos.registry_mirror {
+            let scheme_ok =
+                mirror.endpoint.starts_with("https://") || mirror.endpoint.starts_with("http://");
+            if !scheme_ok || mirror.host().is_empty() {
+                anyhow::bail!(
+                    "talos.registry_mirror.endpoint '{}' must be an http(s) URL",
+                    mirror.endpoint
+                );
+            }
+            if mirror.registries.is_empty() {
+                anyhow::bail!("talos.registry_mirror.registries cannot be empty");
+            }
+            if let Some(registry) = mirror
+                .registries
+                .iter()
+                .find(|r| r.is_empty() || r.contains('/') || r.contains(char::is_whitespace))
+            {
+                anyhow::bail!(
+                    "talos.registry_mirror.registries entry '{}' is not a registry host",
+                    registry
+                );
+            }
+        }
+
+        // Validate network CIDRs; `auto` ranges are allocated free of overlaps
+        for network in std::iter::once(&self.hcloud.network).chain(&self.hcloud.regions) {
+            if network.is_auto() != (network.subnet_cidr == AUTO_CIDR) {
+                anyhow::bail!(
+                    "cidr and subnet_cidr of network zone {} must both be auto or both be set",
+                    network.zone
+                );
+            }
+        }
+        for network in std::iter::once(&self.hcloud.network).chain(&self.hcloud.regions) {
+            if !schema::NETWORK_ZONES.contains(&network.zone.as_str()) {
+                anyhow::bail!(
+                    "Unknown network zone {}; expected one of {}",
+                    network.zone,
+                    schema::NETWORK_ZONES.join(", ")
+                );
+            }
+        }
+        if !self.hcloud.network.is_auto() {
+            parse_network_cidr(&self.hcloud.network.cidr)?;
+            parse_network_cidr(&self.hcloud.network.subnet_cidr)?;
+            if !cidr_contains(&self.hcloud.network.cidr, &self.hcloud.network.subnet_cidr)? {
+                anyhow::bail!(
+                    "hcloud.network.subnet_cidr {} is not inside the network CIDR {}",
+                    self.hcloud.network.subnet_cidr,
+                    self.hcloud.network.cidr
+                );
+            }
+        }
+
+        for (i, region) in self.hcloud.regions.iter().enumerate() {
+            if zones[..=i].contains(&region.zone.as_str()) {
+                anyhow::bail!(
+                    "hcloud.regions lists network zone {} more than once",
+                    region.zone
+                );
+            }
+            if region.is_auto() {
+                continue;
+            }
+            parse_network_cidr(&region.cidr)?;
+            parse_network_cidr(&region.subnet_cidr)?;
+            if !cidr_contains(&region.cidr, &region.subnet_cidr)? {
+                anyhow::bail!(
+                    "subnet_cidr {} of region {} is not inside its network CIDR {}",
+                    region.subnet_cidr,
+                    region.zone,
+                    region.cidr
+                );
+            }
+            // KubeSpan routes the node addresses of all regions, so they must be unique
+            for other in std::iter::once(&self.hcloud.network)
+                .chain(&self.hcloud.regions[..i])
+                .filter(|other| !other.is_auto())
+            {
+                if cidr_contains(&other.cidr, &region.cidr)?
+                    || cidr_contains(&region.cidr, &other.cidr)?
+                {
+                    anyhow::bail!(
+                        "network CIDR {} of region {} overlaps {} of {}",
+                        region.cidr,
+                        region.zone,
+                        other.cidr,
+                        other.zone
+                    );
+                }
+            }
+        }
+
+        // Overlapping ranges route pod or service traffic to the wrong place
+        // without any error
+        let ranges = [
+            ("talos.pod_cidr", &self.talos.pod_cidr),
+            ("talos.service_cidr", &self.talos.service_cidr),
+        ];
+        for (field, cidr) in ranges {
+            if !matches!(parse_network_cidr(cidr)?, IpNet::V4(_)) {
+                anyhow::bail!("{} must be an IPv4 CIDR: {}", field, cidr);
+            }
+            let networks = std::iter::once(&self.hcloud.network).chain(&self.hcloud.regions);
+            for network in networks.filter(|network| !network.is_auto()) {
+                if cidrs_overlap(cidr, &network.cidr)? {
+                    anyhow::bail!(
+                        "{} {} overlaps the network CIDR {} of zone {}",
+                        field,
+                        cidr,
+                        network.cidr,
+                        network.zone
+                    );
+                }
+            }
+        }
+        if cidrs_overlap(&self.talos.pod_cidr, &self.talos.service_cidr)? {
+            anyhow::bail!(
+                "talos.pod_cidr {} overlaps talos.service_cidr {}",
+                self.talos.pod_cidr,
+                self.talos.service_cidr
+            );
+        }
+
+        if let Some(pool) = self.control_planes.iter().find(|p| p.egress_gateway) {
+            anyhow::bail!(
+                "egress_gateway is a worker setting, remove it from control plane pool '{}'",
+                pool.name
+            );
+        }
+        let gateway_pools: Vec<&NodeConfig> =
+            self.workers.iter().filter(|p| p.egress_gateway).collect();
+        match &self.cilium.egress_gateway {
+            None => {
+                if let Some(pool) = gateway_pools.first() {
+                    anyhow::bail!(
+                        "pool '{}' is an egress gateway, but cilium.egress_gateway is not configured",
+                        pool.name
+                    );
+                }
+            }
+            Some(egress) => {
+                if gateway_pools.is_empty() {
+                    anyhow::bail!(
+                        "cilium.egress_gateway needs a worker pool with egress_gateway: true"
+                    );
+                }
+                // Gateways masquerade to their public address in the main project
+                if let Some(pool) = gateway_pools
+                    .iter()
+                    .find(|p| !p.public_ip || p.project.is_some())
+                {
+                    anyhow::bail!(
+                        "egress gateway pool '{}' needs public IPs in the project of hcloud.token",
+                        pool.name
+                    );
+                }
+                // A floating IP is routed to a single server
+                let gateways: u32 = gateway_pools.iter().map(|p| p.count).sum();
+                if egress.floating_ip && gateways != 1 {
+                    anyhow::bail!(
+                        "cilium.egress_gateway.floating_ip needs exactly one gateway node, not {}",
+                        gateways
+                    );
+                }
+                if egress.policies.is_empty() {
+                    anyhow::bail!("cilium.egress_gateway has no policies");
+                }
+                for (i, policy) in egress.policies.iter().enumerate() {
+                    if egress.policies[..i].iter().any(|p| p.name == policy.name) {
+                        anyhow::bail!("egress gateway policy '{}' is listed twice", policy.name);
+                    }
+                    if policy.namespaces.is_empty() {
+                        anyhow::bail!("egress gateway policy '{}' has no namespaces", policy.name);
+                    }
+                    if policy.destination_cidrs.is_empty() {
+                        anyhow::bail!(
+                            "egress gateway policy '{}' has no destination_cidrs",
+                            policy.name
+                        );
+                    }
+                    for cidr in &policy.destination_cidrs {
+                        self.validate_cidr(cidr)?;
+                    }
+                }
+            }
+        }
+
+        if self.cilium.enable_ipv6 {
+            for cidr in [&self.talos.ipv6_pod_cidr, &self.talos.ipv6_service_cidr] {
+                self.validate_cidr(cidr)?;
+                if !cidr.contains(':') {
+                    anyhow::bail!("Expected an IPv6 CIDR for dual-stack mode: {}", cidr);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validate CIDR notation
+    fn validate_cidr(&self, cidr: &str) -> anyhow::Result<()> {
+        parse_cidr(cidr).map(|_| ())
+    }
+
+    /// Whether nodes sit on several private networks (network zones or
+    /// projects), connected with KubeSpan
+    pub fn multi_region(&self) -> bool {
+        !self.hcloud.regions.is_empty() || self.workers.iter().any(|p| p.project.is_some())
+    }
+
+    /// Whether the hcloud cloud-controller-manager backs LoadBalancer
+    /// Services with Hetzner load balancers
+    pub fn cloud_load_balancers(&self) -> bool {
+        let ingress = &self.addons.ingress_nginx;
+        ingress.enabled && ingress.service == IngressService::LoadBalancer
+    }
+
+    /// Whether a NAT gateway is needed for pools without public IPs
+    pub fn needs_nat_gateway(&self) -> bool {
+        self.workers.iter().any(|p| !p.public_ip)
+    }
+
+    /// Snapshot ID used for servers in the given pool
+    pub fn snapshot_for_pool<'a>(&'a self, pool: &'a NodeConfig) -> Option<&'a str> {
+        match pool
+            .project
+            .as_ref()
+            .and_then(|p| self.hcloud.projects.get(p))
+        {
+            Some(project) => pool
+                .hcloud_snapshot_id
+                .as_deref()
+                .or(project.hcloud_snapshot_id.as_deref()),
+            None => pool
+                .hcloud_snapshot_id
+                .as_deref()
+                .or(self.talos.hcloud_snapshot_id.as_deref()),
+        }
+    }
+
+    /// API token of a project in `hcloud.projects`
+    pub fn project_token(&self, name: &str) -> anyhow::Result<String> {
+        let project = self.hcloud.projects.get(name).ok_or_else(|| {
+            anyhow::anyhow!("Project '{}' is not defined in hcloud.projects", name)
+        })?;
+        if let Some(token) = &project.token {
+            return Ok(token.clone());
+        }
+        if let Some(var) = &project.token_env {
+            return std::env::var(var).map_err(|_| {
+                anyhow::anyhow!(
+                    "Environment variable {} with the token of project '{}' is not set",
+                    var,
+                    name
+                )
+            });
+        }
+        match &project.token_sops_file {
+            Some(path) => sops::decrypt_token(path),
+            None => anyhow::bail!("Project '{}' has no token", name),
+        }
+    }
+
+    /// Get Hetzner Cloud API token from config, a SOPS file or environment
+    pub fn get_hcloud_token(&self) -> anyhow::Result<String> {
+        if self.hcloud.token.is_none() {
+            if let Some(path) = &self.hcloud.token_sops_file {
+                return sops::decrypt_token(path);
+            }
+        }
+
+        self.hcloud.token
+            .clone()
+            .or_else(|| std::env::var("HCLOUD_TOKEN").ok())
+            .ok_or_else(|| anyhow::anyhow!(crate::exit::Failure::config(
+                "Hetzner Cloud API token not found. Set HCLOUD_TOKEN environment variable or specify in config"
+            )))
+    }
+
+    /// Generate an example configuration file
+    pub fn example() -> Self {
+        Self {
+            api_version: migrate::API_VERSION.to_string(),
+            cluster_name: "talos-cluster".to_string(),
+            hcloud: HetznerCloudConfig {
+                token: None,
+                token_sops_file: None,
+                location: "nbg1".to_string(),
+                network: NetworkConfig {
+                    cidr: "10.0.0.0/16".to_string(),
+                    subnet_cidr: "10.0.1.0/24".to_string(),
+                    zone: "eu-central".to_string(),
+                },
+                regions: vec![],
+                nat_gateway: NatGatewayConfig::default(),
+                egress: None,
+                allowed_ips: vec![],
+                delete_protection: true,
+                graceful_shutdown: false,
+                http: HttpConfig::default(),
+                create_concurrency: default_create_concurrency(),
+                projects: BTreeMap::new(),
+            },
+            talos: TalosConfig {
+                version: "v1.7.0".to_string(),
+                kubernetes_version: "1.30.0".to_string(),
+                cluster_endpoint: None,
+                hcloud_snapshot_id: None,
+                cert_sans: vec![],
+                config_patches: vec![],
+                pod_cidr: default_pod_cidr(),
+                service_cidr: default_service_cidr(),
+                ipv6_pod_cidr: default_ipv6_pod_cidr(),
+                ipv6_service_cidr: default_ipv6_service_cidr(),
+                secrets: SecretsBackend::default(),
+                registry_mirror: None,
+                qemu_guest_agent: false,
+            },
+            cilium: CiliumConfig {
+                version: "1.15.0".to_string(),
+                enable_hubble: true,
+                enable_ipv6: false,
+                helm_values: serde_yaml::Value::Null,
+                default_deny: DefaultDenyConfig::default(),
+                egress_gateway: None,
+                gateway_api: true,
+            },
+            control_planes: vec![NodeConfig {
+                name: "control-plane".to_string(),
+                server_type: "cpx21".to_string(),
+                count: 3,
+                labels: std::collections::HashMap::new(),
+                gpu: false,
+                allow_workloads: false,
+                hcloud_snapshot_id: None,
+                location: None,
+                rdns_template: None,
+                topology: None,
+                public_ip: true,
+                project: None,
+                egress_gateway: false,
+            }],
+            workers: vec![NodeConfig {
+                name: "worker".to_string(),
+                server_type: "cpx31".to_string(),
+                count: 3,
+                labels: std::collections::HashMap::new(),
+                gpu: false,
+                allow_workloads: false,
+                hcloud_snapshot_id: None,
+                location: None,
+                rdns_template: None,
+                topology: None,
+                public_ip: true,
+                project: None,
+                egress_gateway: false,
+            }],
+            robot: None,
+            addons: AddonsConfig::default(),
+            dns: None,
+            timeouts: TimeoutsConfig::default(),
+            helm: HelmConfig::default(),
+            upgrade: UpgradeConfig::default(),
+            rbac: None,
+            tenants: Vec::new(),
+            notifications: None,
+            alertmanager: None,
+            storage: None,
+        }
+    }
+}
+
+/// Whether `name` is a DNS name of valid labels, like `k8s.example.com`
+fn is_dns_name(name: &str) -> bool {
+    name.split('.').all(|label| {
+        !label.is_empty()
+            && label.len() <= 63
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    })
+}
+
+/// `talos.cluster_endpoint` must be an HTTPS URL without path, like
+/// `https://k8s.example.com:6443`
+fn validate_endpoint(endpoint: &str) -> anyhow::Result<()> {
+    let url = url::Url::parse(endpoint)
+        .map_err(|e| anyhow::anyhow!("talos.cluster_endpoint {} is not a URL: {}", endpoint, e))?;
+    if url.scheme() != "https"
+        || url.host_str().is_none_or(str::is_empty)
+        || !matches!(url.path(), "" | "/")
+        || url.query().is_some()
+        || !url.username().is_empty()
+    {
+        anyhow::bail!(
+            "talos.cluster_endpoint must look like https://<host>:6443, got {}",
+            endpoint
+        );
+    }
+    Ok(())
+}
+
+/// Parse CIDR notation
+fn parse_cidr(cidr: &str) -> anyhow::Result<IpNet> {
+    cidr.parse()
+        .map_err(|_| anyhow::anyhow!("Invalid CIDR notation: {}", cidr))
+}
+
+/// Parse the CIDR of a network, which must be its network address
+///
+/// The Hetzner API rejects ranges like `10.0.1.5/16`.
+fn parse_network_cidr(cidr: &str) -> anyhow::Result<IpNet> {
+    let net = parse_cidr(cidr)?;
+    if net != net.trunc() {
+        anyhow::bail!("{} has host bits set; the network is {}", cidr, net.trunc());
+    }
+    Ok(net)
+}
+
+/// Whether `inner` lies entirely within `outer`
+fn cidr_contains(outer: &str, inner: &str) -> anyhow::Result<bool> {
+    Ok(parse_cidr(outer)?.contains(&parse_cidr(inner)?))
+}
+
+/// Whether two CIDRs share any address
+pub fn cidrs_overlap(a: &str, b: &str) -> anyhow::Result<bool> {
+    Ok(cidr_contains(a, b)? || cidr_contains(b, a)?)
+}
+
+/// Move `network` to the first block of its size in 10.0.0.0/8 that overlaps
+/// none of `taken`, keeping the subnet at the same offset
+///
+/// `None` if every block of that size is taken.
+pub fn relocate_network(
+    network: &NetworkConfig,
+    taken: &[String],
+) -> anyhow::Result<Option<NetworkConfig>> {
+    let IpNet::V4(net) = parse_cidr(&network.cidr)? else {
+        anyhow::bail!("Only IPv4 networks can be relocated: {}", network.cidr);
+    };
+    let IpNet::V4(subnet) = parse_cidr(&network.subnet_cidr)? else {
+        anyhow::bail!(
+            "Only IPv4 subnets can be relocated: {}",
+            network.subnet_cidr
+        );
+    };
+    let space = Ipv4Net::new(Ipv4Addr::new(10, 0, 0, 0), 8)?;
+    let Ok(blocks) = space.subnets(net.prefix_len()) else {
+        anyhow::bail!("Network {} does not fit into {}", network.cidr, space);
+    };
+
+    let offset = u32::from(subnet.network()) & u32::from(net.hostmask());
+    for block in blocks {
+        let cidr = block.to_string();
+        if taken
+            .iter()
+            .any(|other| cidrs_overlap(&cidr, other).unwrap_or(false))
+        {
+            continue;
+        }
+        let moved = Ipv4Net::new(
+            Ipv4Addr::from(u32::from(block.network()) + offset),
+            subnet.prefix_len(),
+        )?;
+        return Ok(Some(NetworkConfig {
+            cidr,
+            subnet_cidr: moved.to_string(),
+            zone: network.zone.clone(),
+        }));
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_validation() {
+        let mut config = ClusterConfig::example();
+        assert!(config.validate().is_ok());
+
+        config.cluster_name = String::new();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_addons_default_when_omitted() {
+        let mut value = serde_yaml::to_value(ClusterConfig::example()).unwrap();
+        value.as_mapping_mut().unwrap().remove("addons");
+
+        let mut config: ClusterConfig = serde_yaml::from_value(value).unwrap();
+        assert!(!config.addons.longhorn.enabled);
+        assert_eq!(config.addons.longhorn.replica_count, 3);
+
+        config.addons.longhorn.enabled = true;
+        config.addons.longhorn.replica_count = 0;
+        assert!(config.validate().is_err());
+    }
+
+    /// Example config with its `addons` section replaced by `yaml`
+    fn with_addons(yaml: &str) -> ClusterConfig {
+        let mut value = serde_yaml::to_value(ClusterConfig::example()).unwrap();
+        value
+            .as_mapping_mut()
+            .unwrap()
+            .insert("addons".into(), serde_yaml::from_str(yaml).unwrap());
+        serde_yaml::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_longhorn_config() {
+        let longhorn = with_addons("{}").addons.longhorn;
+        assert!(!longhorn.enabled);
+        assert_eq!(longhorn.replica_count, 3);
+
+        let mut config = with_addons("longhorn:\n  enabled: true\n  replica_count: 2\n");
+        assert_eq!(config.addons.longhorn.replica_count, 2);
+        assert!(config.validate().is_ok());
+        config.addons.longhorn.replica_count = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_image_prepull_config() {
+        let prepull = with_addons("{}").addons.image_prepull;
+        assert!(!prepull.enabled);
+        assert!(prepull.include_cilium);
+        assert!(prepull.images.is_empty());
+
+        let mut config = with_addons("image_prepull:\n  enabled: true\n  include_cilium: false\n");
+        assert!(!config.addons.image_prepull.include_cilium);
+        // Nothing to pull
+        assert!(config.validate().is_err());
+        config.addons.image_prepull.images = vec!["ghcr.io/acme/api:1.4.2".to_string()];
+        assert!(config.validate().is_ok());
+        config.addons.image_prepull.images = vec!["ghcr.io/acme/api 1.4.2".to_string()];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_policy_engine_config() {
+        let policy = with_addons("{}").addons.policy_engine;
+        assert!(!policy.enabled);
+        assert_eq!(policy.engine, PolicyEngine::Kyverno);
+        assert!(policy.disallow_privileged && policy.require_limits);
+        assert_eq!(policy.version, None);
+
+        let mut config = with_addons(
+            "policy_engine:\n  enabled: true\n  engine: gatekeeper\n  \
+             disallow_privileged: false\n  require_limits: false\n",
+        );
+        let policy = &config.addons.policy_engine;
+        assert_eq!(policy.engine, PolicyEngine::Gatekeeper);
+        assert_eq!(policy.engine.default_version(), "3.18.2");
+        // Every policy is turned off
+        assert!(config.validate().is_err());
+        config.addons.policy_engine.allowed_registries = vec!["/".to_string()];
+        assert!(config.validate().is_err());
+        config.addons.policy_engine.allowed_registries = vec!["ghcr.io/acme".to_string()];
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_ingress_nginx_config() {
+        let ingress = with_addons("{}").addons.ingress_nginx;
+        assert!(!ingress.enabled);
+        assert_eq!(ingress.service, IngressService::NodeIpam);
+        assert!(ingress.node_ports().is_empty());
+
+        let mut config = with_addons("ingress_nginx:\n  enabled: true\n  https_port: 80\n");
+        assert_eq!(config.addons.ingress_nginx.http_port, 80);
+        assert!(config.validate().is_err());
+        config.addons.ingress_nginx.https_port = 443;
+        assert_eq!(config.addons.ingress_nginx.node_ports(), [80, 443]);
+        assert!(config.validate().is_ok());
+
+        let mut config = with_addons("ingress_nginx:\n  enabled: true\n  service: load_balancer\n");
+        assert!(config.cloud_load_balancers());
+        assert!(config.addons.ingress_nginx.node_ports().is_empty());
+        assert!(config.validate().is_ok());
+        config.hcloud.projects.insert(
+            "other".to_string(),
+            serde_yaml::from_str("token_env: OTHER_TOKEN").unwrap(),
+        );
+        config.workers[0].project = Some("other".to_string());
+        let error = config.validate().unwrap_err().to_string();
+        assert!(error.contains("load_balancer"), "{}", error);
+    }
+
+    #[test]
+    fn test_istio_config() {
+        let istio = with_addons("{}").addons.istio;
+        assert!(!istio.enabled);
+        assert_eq!(istio.version, "1.25.2");
+        assert!(istio.ambient_namespaces.is_empty());
+
+        let mut config = with_addons("istio:\n  enabled: true\n  ambient_namespaces: [shop]\n");
+        assert_eq!(config.addons.istio.ambient_namespaces, ["shop"]);
+        assert!(config.validate().is_ok());
+        config.addons.istio.ambient_namespaces = vec!["istio-system".to_string()];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_kubevirt_config() {
+        let kubevirt = with_addons("{}").addons.kubevirt;
+        assert!(!kubevirt.enabled);
+        assert!(!kubevirt.software_emulation);
+        assert!(kubevirt.version.starts_with('v'));
+        assert!(kubevirt.cdi_version.starts_with('v'));
+
+        // No robot servers, so no /dev/kvm
+        let config = with_addons("kubevirt:\n  enabled: true\n");
+        let error = config.validate().unwrap_err().to_string();
+        assert!(error.contains("cpx21, cpx31"), "{}", error);
+
+        let mut config = with_addons("kubevirt:\n  enabled: true\n  software_emulation: true\n");
+        assert!(config.validate().is_ok());
+        config.addons.kubevirt.version = "1.5.0".to_string();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_knative_config() {
+        assert!(!with_addons("{}").addons.knative.enabled);
+
+        let mut config = with_addons("knative:\n  enabled: true\n  version: 1.17.0\n");
+        assert_eq!(config.addons.knative.version, "1.17.0");
+        config.cilium.gateway_api = false;
+        assert!(config.validate().is_err());
+        config.cilium.gateway_api = true;
+        config.dns = Some(DnsConfig {
+            domain: "apps.example.com".to_string(),
+        });
+        assert!(config.validate().is_ok());
+        config.dns = Some(DnsConfig {
+            domain: "apps..example.com".to_string(),
+        });
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_descheduler_config() {
+        let descheduler = with_addons("{}").addons.descheduler;
+        assert!(!descheduler.enabled);
+        assert_eq!(descheduler.interval, "5m");
+
+        let mut config = with_addons("descheduler:\n  enabled: true\n  interval: 2h\n");
+        assert_eq!(config.addons.descheduler.interval, "2h");
+        assert!(config.validate().is_ok());
+        config.addons.descheduler.interval = "90".to_string();
+        assert!(config.validate().is_err());
+        config.addons.descheduler.interval = "0h".to_string();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_node_problem_detector_config() {
+        assert!(!with_addons("{}").addons.node_problem_detector.enabled);
+
+        let config = with_addons("node_problem_detector:\n  enabled: true\n  version: 2.3.14\n");
+        let npd = &config.addons.node_problem_detector;
+        assert!(npd.enabled);
+        assert_eq!(npd.version, "2.3.14");
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_dashboard_config() {
+        let dashboard = with_addons("{}").addons.dashboard;
+        assert!(!dashboard.enabled);
+        assert_eq!(dashboard.version, "0.30.1");
+
+        let config = with_addons("dashboard:\n  enabled: true\n  version: 0.31.0\n");
+        assert!(config.addons.dashboard.enabled);
+        assert_eq!(config.addons.dashboard.version, "0.31.0");
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_timeouts() {
+        let mut value = serde_yaml::to_value(ClusterConfig::example()).unwrap();
+        value.as_mapping_mut().unwrap().remove("timeouts");
+        value.as_mapping_mut().unwrap().insert(
+            "timeouts".into(),
+            serde_yaml::from_str("api_server: 900").unwrap(),
+        );
+
+        let mut config: ClusterConfig = serde_yaml::from_value(value).unwrap();
+        assert_eq!(config.timeouts.api_server, 900);
+        assert_eq!(config.timeouts.cni, 300);
+
+        let overridden = config.timeouts.with_override(Some(1200));
+        assert_eq!(overridden.cni, 1200);
+        assert_eq!(config.timeouts.with_override(None).api_server, 900);
+
+        config.timeouts.node_ready = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_notification_events() {
+        let mut config = ClusterConfig::example();
+        config.notifications =
+            Some(serde_yaml::from_str("webhook_url: https://hooks.example.com/abc").unwrap());
+        assert!(config.validate().is_ok());
+
+        let notifications = config.notifications.as_mut().unwrap();
+        assert_eq!(notifications.format, WebhookFormat::Generic);
+        assert!(notifications.wants("create", true));
+        assert!(notifications.wants("upgrade", false));
+        assert!(notifications.wants("destroy", false));
+        assert!(!notifications.wants("destroy", true));
+        assert!(!notifications.wants("status", true));
+
+        notifications.events = vec!["scale:done".to_string()];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_rbac_oidc_groups_need_oidc() {
+        let mut config = ClusterConfig::example();
+        config.rbac = Some(
+            serde_yaml::from_str(
+                "groups:\n  - name: viewers\n    cluster_role: view\n    oidc_groups: [platform]\n",
+            )
+            .unwrap(),
+        );
+        assert!(config.validate().is_err());
+
+        config.rbac.as_mut().unwrap().oidc = Some(
+            serde_yaml::from_str("issuer_url: https://auth.example.com\nclient_id: kubernetes\n")
+                .unwrap(),
+        );
+        assert!(config.validate().is_ok());
+        let oidc = config.rbac.as_ref().unwrap().oidc.as_ref().unwrap();
+        assert_eq!(oidc.prefix, "oidc:");
+    }
+
+    #[test]
+    fn test_tenants() {
+        let mut config = ClusterConfig::example();
+        config.tenants = serde_yaml::from_str(
+            "- name: team-a\n  bindings:\n    - cluster_role: admin\n      groups: [team-a]\n",
+        )
+        .unwrap();
+        assert!(config.tenants[0].isolated);
+        assert!(config.validate().is_ok());
+
+        config.tenants.push(config.tenants[0].clone());
+        assert!(config.validate().is_err());
+        config.tenants.pop();
+
+        config.tenants[0].bindings[0].groups.clear();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_pool_topology() {
+        let mut config = ClusterConfig::example();
+        let pool = &mut config.workers[0];
+        assert_eq!(pool.location_for(4, "nbg1"), "nbg1");
+
+        pool.topology = Some(TopologyConfig {
+            locations: vec!["fsn1".to_string(), "nbg1".to_string(), "hel1".to_string()],
+        });
+        assert_eq!(pool.location_for(0, "nbg1"), "fsn1");
+        assert_eq!(pool.location_for(4, "nbg1"), "nbg1");
+        assert_eq!(pool.next_location(&["fsn1", "nbg1"], "nbg1"), "hel1");
+        assert_eq!(
+            pool.next_location(&["fsn1", "nbg1", "hel1"], "nbg1"),
+            "fsn1"
+        );
+        assert!(config.validate().is_ok());
+
+        config.workers[0].topology = Some(TopologyConfig {
+            locations: vec!["fsn1".to_string(), "ash".to_string()],
+        });
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_egress_rules() {
+        let mut config = ClusterConfig::example();
+        config.hcloud.egress = Some(
+            serde_yaml::from_str(
+                "rules:\n  - description: registry mirror\n    destination_ips: [198.51.100.10]\n    protocol: tcp\n    port: \"5000\"\n",
+            )
+            .unwrap(),
+        );
+        assert!(config.validate().is_ok());
+
+        let egress = config.hcloud.egress.as_mut().unwrap();
+        assert!(egress.defaults);
+        egress.rules[0].port = None;
+        assert!(config.validate().is_err());
+
+        let egress = config.hcloud.egress.as_mut().unwrap();
+        egress.rules.clear();
+        egress.defaults = false;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_egress_gateway() {
+        let mut config = ClusterConfig::example();
+        config.workers[0].egress_gateway = true;
+        assert!(config.validate().is_err());
+
+        config.cilium.egress_gateway = Some(
+            serde_yaml::from_str(
+                "floating_ip: true\npolicies:\n  - name: payments\n    namespaces: [shop]\n",
+            )
+            .unwrap(),
+        );
+        config.workers[0].count = 2;
+        assert!(config.validate().is_err());
+        config.workers[0].count = 1;
+        assert!(config.validate().is_ok());
+        let egress = config.cilium.egress_gateway.as_mut().unwrap();
+        assert_eq!(egress.policies[0].destination_cidrs, ["0.0.0.0/0"]);
+
+        egress.policies[0].destination_cidrs = vec!["203.0.113.0/33".to_string()];
+        assert!(config.validate().is_err());
+
+        config.workers[0].egress_gateway = false;
+        config.cilium.egress_gateway.as_mut().unwrap().policies[0].destination_cidrs =
+            vec!["203.0.113.0/24".to_string()];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_allowed_ips() {
+        let mut config = ClusterConfig::example();
+        config.hcloud.allowed_ips = vec!["203.0.113.7".to_string(), "198.51.100.0/24".to_string()];
+        assert!(config.validate().is_ok());
+
+        config.hcloud.allowed_ips = vec!["203.0.113.7/33".to_string()];
+        assert!(config.validate().is_err());
+
+        config.hcloud.allowed_ips = vec!["office".to_string()];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_robot_servers() {
+        let mut config = ClusterConfig::example();
+        config.robot = Some(
+            serde_yaml::from_str(
+                "vswitch_id: 12345\nvlan: 4000\nsubnet_cidr: 10.0.2.0/24\nservers:\n  - name: dedi-1\n    public_ip: 198.51.100.20\n    private_ip: 10.0.2.10\n",
+            )
+            .unwrap(),
+        );
+        assert!(config.validate().is_ok());
+        let robot = config.robot.as_mut().unwrap();
+        assert_eq!(robot.servers[0].install_disk, "/dev/nvme0n1");
+
+        robot.servers[0].private_ip = "10.0.1.10".to_string();
+        assert!(config.validate().is_err());
+
+        let robot = config.robot.as_mut().unwrap();
+        robot.servers[0].private_ip = "10.0.2.10".to_string();
+        robot.subnet_cidr = "10.0.1.0/24".to_string();
+        assert!(config.validate().is_err());
+
+        let robot = config.robot.as_mut().unwrap();
+        robot.subnet_cidr = "10.0.2.0/24".to_string();
+        robot.vlan = 100;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_private_pools() {
+        let mut config = ClusterConfig::example();
+        assert!(!config.needs_nat_gateway());
+
+        let pool: NodeConfig =
+            serde_yaml::from_str("name: batch\nserver_type: cx32\npublic_ip: false\n").unwrap();
+        config.workers.push(pool);
+        assert!(config.needs_nat_gateway());
+        assert_eq!(config.hcloud.nat_gateway.server_type, "cx22");
+        assert!(config.validate().is_ok());
+
+        config.workers[1].location = Some("ash".to_string());
+        config.hcloud.regions = vec![NetworkConfig {
+            cidr: "10.1.0.0/16".to_string(),
+            subnet_cidr: "10.1.1.0/24".to_string(),
+            zone: "us-east".to_string(),
+        }];
+        assert!(config.validate().is_err());
+
+        config.workers[1].location = None;
+        config.control_planes[0].public_ip = false;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_multi_region() {
+        let mut config = ClusterConfig::example();
+        config.workers[0].location = Some("ash".to_string());
+        assert!(config.validate().is_err());
+
+        config.hcloud.regions = vec![NetworkConfig {
+            cidr: "10.1.0.0/16".to_string(),
+            subnet_cidr: "10.1.1.0/24".to_string(),
+            zone: "us-east".to_string(),
+        }];
+        assert!(config.validate().is_ok());
+        assert!(config.multi_region());
+        assert_eq!(config.workers[0].location_for(0, "nbg1"), "ash");
+
+        config.hcloud.regions[0].cidr = "10.0.0.0/8".to_string();
+        config.hcloud.regions[0].subnet_cidr = "10.1.0.0/24".to_string();
+        assert!(config.validate().is_err());
+
+        config.hcloud.regions[0].cidr = "10.1.0.0/16".to_string();
+        config.hcloud.regions[0].zone = "eu-central".to_string();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_projects() {
+        let mut config = ClusterConfig::example();
+        config.workers[0].project = Some("compute".to_string());
+        assert!(config.validate().is_err());
+
+        config.hcloud.projects.insert(
+            "compute".to_string(),
+            ProjectConfig {
+                token_env: Some("OXIDE_TEST_COMPUTE_TOKEN".to_string()),
+                hcloud_snapshot_id: Some("222".to_string()),
+                ..Default::default()
+            },
+        );
+        config.talos.hcloud_snapshot_id = Some("111".to_string());
+        assert!(config.validate().is_ok());
+        assert!(config.multi_region());
+        assert_eq!(config.snapshot_for_pool(&config.workers[0]), Some("222"));
+        assert_eq!(
+            config.snapshot_for_pool(&config.control_planes[0]),
+            Some("111")
+        );
+        assert!(config.project_token("compute").is_err());
+        assert!(config.project_token("images").is_err());
+
+        config.workers[0].public_ip = false;
+        assert!(config.validate().is_err());
+        config.workers[0].public_ip = true;
+
+        config.control_planes[0].project = Some("compute".to_string());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_gpu_pools() {
+        let mut config = ClusterConfig::example();
+        config.talos.hcloud_snapshot_id = Some("100".to_string());
+        config.workers[0].gpu = true;
+        config.workers[0].hcloud_snapshot_id = Some("200".to_string());
+        assert!(config.validate().is_ok());
+        assert_eq!(config.snapshot_for_pool(&config.workers[0]), Some("200"));
+        assert_eq!(
+            config.snapshot_for_pool(&config.control_planes[0]),
+            Some("100")
+        );
+
+        config.control_planes[0].gpu = true;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_rdns_template_requires_node_placeholder() {
+        let mut config = ClusterConfig::example();
+        config.workers[0].rdns_template = Some("{node}.k8s.example.com".to_string());
+        assert!(config.validate().is_ok());
+
+        config.workers[0].rdns_template = Some("k8s.example.com".to_string());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_pod_and_service_cidrs() {
+        let mut config = ClusterConfig::example();
+        assert!(config.validate().is_ok());
+
+        config.hcloud.network.cidr = "10.0.0.0/8".to_string();
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("talos.pod_cidr"), "{}", err);
+
+        let mut config = ClusterConfig::example();
+        config.talos.service_cidr = "10.0.8.0/21".to_string();
+        assert!(config.validate().is_err());
+        config.talos.service_cidr = "10.244.128.0/20".to_string();
+        assert!(config.validate().is_err());
+        config.talos.service_cidr = "172.16.0.0/20".to_string();
+        config.talos.pod_cidr = "172.20.0.0/16".to_string();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_relocate_network() {
+        let network = ClusterConfig::example().hcloud.network;
+        let taken = vec!["10.0.0.0/16".to_string(), "10.1.128.0/17".to_string()];
+
+        let moved = relocate_network(&network, &taken).unwrap().unwrap();
+        assert_eq!(moved.cidr, "10.2.0.0/16");
+        assert_eq!(moved.subnet_cidr, "10.2.1.0/24");
+        assert_eq!(moved.zone, network.zone);
+
+        let everything = vec!["10.0.0.0/8".to_string()];
+        assert!(relocate_network(&network, &everything).unwrap().is_none());
+        assert!(cidrs_overlap("10.0.0.0/16", "10.0.1.0/24").unwrap());
+        assert!(!cidrs_overlap("10.0.0.0/16", "10.1.0.0/16").unwrap());
+    }
+
+    #[test]
+    fn test_allow_workloads() {
+        let mut config = ClusterConfig::example();
+        config.control_planes[0].allow_workloads = true;
+        assert!(config.validate().is_ok());
+
+        let mut second = config.control_planes[0].clone();
+        second.name = "control-plane-b".to_string();
+        second.allow_workloads = false;
+        config.control_planes.push(second);
+        assert!(config.validate().is_err());
+
+        let mut config = ClusterConfig::example();
+        config.workers[0].allow_workloads = true;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_cert_sans() {
+        let mut config = ClusterConfig::example();
+        config.talos.cert_sans = vec![
+            "k8s.example.com".to_string(),
+            "*.lb.example.com".to_string(),
+            "203.0.113.10".to_string(),
+            "2001:db8::1".to_string(),
+        ];
+        assert!(config.validate().is_ok());
+
+        config.talos.cert_sans = vec!["https://k8s.example.com".to_string()];
+        assert!(config.validate().is_err());
+        config.talos.cert_sans = vec!["-k8s.example.com".to_string()];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_registry_mirror() {
+        let mut config = ClusterConfig::example();
+        let yaml = "endpoint: https://registry.internal:5000/v2\n";
+        let mirror: RegistryMirrorConfig = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(mirror.host(), "registry.internal:5000");
+        assert!(mirror.registries.contains(&"docker.io".to_string()));
+        config.talos.registry_mirror = Some(mirror);
+        assert!(config.validate().is_ok());
+
+        let mirror = config.talos.registry_mirror.as_mut().unwrap();
+        mirror.registries = vec!["docker.io/library".to_string()];
+        assert!(config.validate().is_err());
+
+        let mirror = config.talos.registry_mirror.as_mut().unwrap();
+        mirror.registries = vec!["docker.io".to_string()];
+        mirror.endpoint = "registry.internal:5000".to_string();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_dual_stack_requires_ipv6_cidrs() {
+        let mut config = ClusterConfig::example();
+        config.cilium.enable_ipv6 = true;
+        assert!(config.validate().is_ok());
+
+        config.talos.ipv6_pod_cidr = "10.244.0.0/16".to_string();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
     fn test_cidr_validation() {
         let config = ClusterConfig::example();
         assert!(config.validate_cidr("10.0.0.0/16").is_ok());
         assert!(config.validate_cidr("invalid").is_err());
     }
+
+    #[test]
+    fn test_cidr_contains() {
+        let config = ClusterConfig::example();
+        assert!(config.validate_cidr("10.0.0.0/33").is_err());
+        assert!(config.validate_cidr("fd00::/64").is_ok());
+
+        assert!(cidr_contains("10.0.0.0/16", "10.0.1.0/24").unwrap());
+        assert!(!cidr_contains("10.0.0.0/16", "10.1.0.0/24").unwrap());
+        assert!(!cidr_contains("10.0.0.0/24", "10.0.0.0/16").unwrap());
+        assert!(!cidr_contains("10.0.0.0/16", "fd00::/64").unwrap());
+    }
+
+    #[test]
+    fn test_network_validation() {
+        assert!(parse_network_cidr("10.0.0.0/16").is_ok());
+        let err = parse_network_cidr("10.0.1.5/16").unwrap_err().to_string();
+        assert!(err.contains("the network is 10.0.0.0/16"), "{}", err);
+
+        let mut config = ClusterConfig::example();
+        config.hcloud.network.subnet_cidr = "10.0.1.1/24".to_string();
+        assert!(config.validate().is_err());
+        let mut config = ClusterConfig::example();
+        config.hcloud.network.zone = "eu-west".to_string();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_cluster_endpoint_validation() {
+        assert!(validate_endpoint("https://k8s.example.com:6443").is_ok());
+        assert!(validate_endpoint("https://203.0.113.10:6443/").is_ok());
+        assert!(validate_endpoint("http://k8s.example.com:6443").is_err());
+        assert!(validate_endpoint("https://k8s.example.com:6443/api").is_err());
+        assert!(validate_endpoint("https://").is_err());
+        assert!(validate_endpoint("k8s.example.com:6443").is_err());
+
+        let mut config = ClusterConfig::example();
+        config.talos.cluster_endpoint = Some("https://k8s example.com".to_string());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_subnet_outside_network_is_rejected() {
+        let mut config = ClusterConfig::example();
+        config.hcloud.network.subnet_cidr = "192.168.1.0/24".to_string();
+        assert!(config.validate().is_err());
+    }
 }
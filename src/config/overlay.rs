@@ -0,0 +1,98 @@
+/// Deep merge of layered config files (`--config base.yaml --config prod.yaml`)
+use serde_yaml::Value;
+
+/// Merge `overlay` into `base`
+pub fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                if value.is_null() {
+                    base.remove(&key);
+                } else if let Some(existing) = base.get_mut(&key) {
+                    merge(existing, value);
+                } else {
+                    base.insert(key, value);
+                }
+            }
+        }
+        (Value::Sequence(base), Value::Sequence(overlay))
+            if is_named(base) && is_named(&overlay) =>
+        {
+            for item in overlay {
+                match base.iter_mut().find(|b| name_of(b) == name_of(&item)) {
+                    Some(existing) => merge(existing, item),
+                    None => base.push(item),
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+fn is_named(items: &[Value]) -> bool {
+    !items.is_empty() && items.iter().all(|item| name_of(item).is_some())
+}
+
+fn name_of(item: &Value) -> Option<&str> {
+    item.get("name")?.as_str()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(s: &str) -> Value {
+        serde_yaml::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_merge_overrides_and_pools_by_name() {
+        let mut base = yaml(
+            r#"
+cluster_name: base
+hcloud:
+  location: nbg1
+  token: abc
+talos:
+  config_patches: [a.yaml, b.yaml]
+workers:
+  - name: worker
+    server_type: cpx31
+    count: 3
+"#,
+        );
+        let overlay = yaml(
+            r#"
+cluster_name: prod
+hcloud:
+  token: ~
+talos:
+  config_patches: [c.yaml]
+workers:
+  - name: worker
+    count: 10
+  - name: gpu
+    server_type: gex44
+"#,
+        );
+
+        merge(&mut base, overlay);
+
+        let expected = yaml(
+            r#"
+cluster_name: prod
+hcloud:
+  location: nbg1
+talos:
+  config_patches: [c.yaml]
+workers:
+  - name: worker
+    server_type: cpx31
+    count: 10
+  - name: gpu
+    server_type: gex44
+"#,
+        );
+        assert_eq!(base, expected);
+    }
+}
@@ -0,0 +1,424 @@
+/// JSON Schema validation of cluster config files
+use anyhow::{anyhow, Result};
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{ArrayValidation, InstanceType, Schema, SchemaObject};
+
+use std::path::Path;
+
+use super::{ClusterConfig, ConfigFormat};
+
+/// Hetzner Cloud locations
+pub const LOCATIONS: &[&str] = &["nbg1", "fsn1", "hel1", "ash", "hil", "sin"];
+
+/// Hetzner Cloud network zones
+pub const NETWORK_ZONES: &[&str] = &["eu-central", "us-east", "us-west", "ap-southeast"];
+
+/// Network zone of a known location
+pub fn location_zone(location: &str) -> Option<&'static str> {
+    match location {
+        "nbg1" | "fsn1" | "hel1" => Some("eu-central"),
+        "ash" => Some("us-east"),
+        "hil" => Some("us-west"),
+        "sin" => Some("ap-southeast"),
+        _ => None,
+    }
+}
+
+/// A config file after interpolation, kept to locate problems in it
+pub struct Source {
+    pub file: String,
+    pub content: String,
+}
+
+/// A single schema violation
+#[derive(Debug, PartialEq)]
+struct Issue {
+    /// File the problem was located in (the last one defining the field)
+    file: String,
+    /// 1-based line in that file, if it could be located
+    line: Option<usize>,
+    /// Dotted field path, e.g. `workers[0].server_type`
+    field: String,
+    message: String,
+}
+
+/// Check the (merged) config against the schema, reporting all problems at once
+///
+/// Problems are located in the last of `sources` that defines the offending
+/// field, falling back to the closest parent in the base file.
+pub fn check(sources: &[Source], value: &serde_yaml::Value) -> Result<()> {
+    let files: Vec<&str> = sources.iter().map(|s| s.file.as_str()).collect();
+    let name = files.join(" + ");
+
+    let issues = issues(sources, value).map_err(|e| anyhow!("{}: {:#}", name, e))?;
+    if issues.is_empty() {
+        return Ok(());
+    }
+
+    let report: Vec<String> = issues
+        .iter()
+        .map(|issue| match issue.line {
+            Some(line) => format!(
+                "  {}:{}: {}: {}",
+                issue.file, line, issue.field, issue.message
+            ),
+            None => format!("  {}: {}: {}", issue.file, issue.field, issue.message),
+        })
+        .collect();
+
+    anyhow::bail!(
+        "{} has {} problem(s):\n{}",
+        name,
+        issues.len(),
+        report.join("\n")
+    )
+}
+
+fn issues(sources: &[Source], value: &serde_yaml::Value) -> Result<Vec<Issue>> {
+    let instance = serde_json::to_value(value)?;
+
+    let schema = serde_json::to_value(schemars::schema_for!(ClusterConfig))?;
+    let validator =
+        jsonschema::validator_for(&schema).map_err(|e| anyhow!("Invalid config schema: {}", e))?;
+
+    let mut issues: Vec<Issue> = validator
+        .iter_errors(&instance)
+        .map(|error| {
+            let pointer = error.instance_path.to_string();
+            let (file, line) = locate_in_sources(sources, &pointer);
+            Issue {
+                file,
+                line,
+                field: field_path(&pointer),
+                message: error.to_string(),
+            }
+        })
+        .collect();
+
+    issues.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    Ok(issues)
+}
+
+/// Pick the file and line to report a problem at
+fn locate_in_sources(sources: &[Source], pointer: &str) -> (String, Option<usize>) {
+    let exact = sources
+        .iter()
+        .rev()
+        .find_map(|source| match locate(&source.content, pointer) {
+            Some((line, true)) => Some((source.file.clone(), Some(line))),
+            // Lines are only located in YAML; other formats are checked for
+            // the field without one
+            _ if defines(source, pointer) => Some((source.file.clone(), None)),
+            _ => None,
+        });
+
+    exact.unwrap_or_else(|| match sources.first() {
+        Some(base) => (
+            base.file.clone(),
+            locate(&base.content, pointer).map(|(line, _)| line),
+        ),
+        None => (String::new(), None),
+    })
+}
+
+/// Whether a TOML or JSON source defines the field at `pointer`
+fn defines(source: &Source, pointer: &str) -> bool {
+    let format = ConfigFormat::from_path(Path::new(&source.file));
+    format != ConfigFormat::Yaml
+        && format
+            .parse(&source.content)
+            .ok()
+            .and_then(|document| serde_json::to_value(document).ok())
+            .is_some_and(|document| document.pointer(pointer).is_some())
+}
+
+/// 1-based line defining the field at `pointer`, e.g. `/talos/version`
+pub fn field_line(content: &str, pointer: &str) -> Option<usize> {
+    match locate(content, pointer) {
+        Some((line, true)) => Some(line),
+        _ => None,
+    }
+}
+
+/// Schema for `hcloud.location` and pool locations
+pub fn location(_: &mut SchemaGenerator) -> Schema {
+    one_of(LOCATIONS)
+}
+
+/// Schema for a pool's `topology.locations`
+pub fn locations(gen: &mut SchemaGenerator) -> Schema {
+    SchemaObject {
+        instance_type: Some(InstanceType::Array.into()),
+        array: Some(Box::new(ArrayValidation {
+            items: Some(location(gen).into()),
+            min_items: Some(1),
+            unique_items: Some(true),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
+/// Schema for `apiVersion`
+pub fn api_version(_: &mut SchemaGenerator) -> Schema {
+    one_of(&super::migrate::version_names())
+}
+
+/// Schema for `hcloud.network.zone`
+pub fn network_zone(_: &mut SchemaGenerator) -> Schema {
+    one_of(NETWORK_ZONES)
+}
+
+fn one_of(values: &[&str]) -> Schema {
+    SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        enum_values: Some(values.iter().map(|v| (*v).into()).collect()),
+        ..Default::default()
+    }
+    .into()
+}
+
+/// Turn a JSON pointer (`/workers/0/count`) into `workers[0].count`
+fn field_path(pointer: &str) -> String {
+    let mut path = String::new();
+    for segment in pointer.split('/').skip(1) {
+        if segment.parse::<usize>().is_ok() {
+            path.push_str(&format!("[{}]", segment));
+        } else {
+            if !path.is_empty() {
+                path.push('.');
+            }
+            path.push_str(&segment.replace("~1", "/").replace("~0", "~"));
+        }
+    }
+
+    if path.is_empty() {
+        "(root)".to_string()
+    } else {
+        path
+    }
+}
+
+/// Find the line a JSON pointer refers to in block-style YAML
+///
+/// Walks the pointer one segment at a time, only looking inside the block of
+/// the previous match. Returns the deepest line found, so a missing field is
+/// reported at its parent, and whether the whole pointer was resolved.
+fn locate(content: &str, pointer: &str) -> Option<(usize, bool)> {
+    let lines: Vec<&str> = content.lines().collect();
+    // Line and indentation owning the current block; the root owns everything
+    let mut owner: Option<usize> = None;
+    let mut owner_indent: isize = -1;
+    let mut owner_is_item = false;
+    let mut complete = true;
+
+    for segment in pointer.split('/').skip(1) {
+        let start = owner.map_or(0, |line| line + 1);
+        let found = match segment.parse::<usize>() {
+            Ok(index) => find_item(&lines, start, owner_indent, index),
+            Err(_) => {
+                let key = segment.replace("~1", "/").replace("~0", "~");
+                // The first key of a list item sits on the item's own line
+                match owner.filter(|_| owner_is_item) {
+                    Some(line) if key_matches(item_text(lines[line]), &key) => {
+                        Some((line, owner_indent))
+                    }
+                    _ => find_key(&lines, start, owner_indent, &key),
+                }
+            }
+        };
+
+        let Some((line, indent)) = found else {
+            complete = false;
+            break;
+        };
+        owner_is_item = segment.parse::<usize>().is_ok() && owner != Some(line);
+        owner = Some(line);
+        owner_indent = indent;
+    }
+
+    owner.map(|line| (line + 1, complete))
+}
+
+/// Find `key:` among the direct children of the block starting at `start`
+fn find_key(
+    lines: &[&str],
+    start: usize,
+    owner_indent: isize,
+    key: &str,
+) -> Option<(usize, isize)> {
+    let mut child_indent = None;
+
+    for (i, line) in lines.iter().enumerate().skip(start) {
+        let Some(indent) = content_indent(line) else {
+            continue;
+        };
+        if indent <= owner_indent {
+            return None;
+        }
+        let child_indent = *child_indent.get_or_insert(indent);
+        if indent == child_indent && key_matches(line.trim_start(), key) {
+            return Some((i, indent));
+        }
+    }
+
+    None
+}
+
+/// Find the `index`-th `- ` item of the sequence starting at `start`
+fn find_item(
+    lines: &[&str],
+    start: usize,
+    owner_indent: isize,
+    index: usize,
+) -> Option<(usize, isize)> {
+    let mut item_indent = None;
+    let mut count = 0;
+
+    for (i, line) in lines.iter().enumerate().skip(start) {
+        let Some(indent) = content_indent(line) else {
+            continue;
+        };
+        let is_item = line.trim_start().starts_with('-');
+        // Sequences may sit at the same indentation as their key
+        if indent < owner_indent || (indent == owner_indent && !is_item) {
+            return None;
+        }
+        if !is_item || indent != *item_indent.get_or_insert(indent) {
+            continue;
+        }
+        if count == index {
+            return Some((i, indent));
+        }
+        count += 1;
+    }
+
+    None
+}
+
+/// Indentation of a line with content, or `None` for blank and comment lines
+fn content_indent(line: &str) -> Option<isize> {
+    let trimmed = line.trim_start();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        return None;
+    }
+    Some((line.len() - trimmed.len()) as isize)
+}
+
+/// Text of a `- ` item line after the dash
+fn item_text(line: &str) -> &str {
+    line.trim_start().trim_start_matches('-').trim_start()
+}
+
+fn key_matches(text: &str, key: &str) -> bool {
+    text.strip_prefix(key)
+        .is_some_and(|rest| rest.trim_start().starts_with(':'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"cluster_name: test
+hcloud:
+  location: nbg1
+  network:
+    cidr: 10.0.0.0/16
+    subnet_cidr: 10.0.1.0/24
+    zone: eu-central
+talos:
+  version: v1.11.2
+  kubernetes_version: 1.34.1
+cilium:
+  version: 1.17.8
+control_planes:
+  - name: control-plane
+    server_type: cpx21
+    count: 1
+workers:
+  - name: worker
+    server_type: cpx31
+  # second pool
+  - name: big
+    server_type: cpx51
+"#;
+
+    fn source(file: &str, content: &str) -> Source {
+        Source {
+            file: file.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    fn single(content: &str) -> Vec<Issue> {
+        let value = serde_yaml::from_str(content).unwrap();
+        issues(&[source("cluster.yaml", content)], &value).unwrap()
+    }
+
+    #[test]
+    fn test_valid_config_has_no_issues() {
+        assert!(single(CONFIG).is_empty());
+
+        let value = serde_yaml::from_str(CONFIG).unwrap();
+        assert!(check(&[source("cluster.yaml", CONFIG)], &value).is_ok());
+    }
+
+    #[test]
+    fn test_issues_are_located() {
+        let content = CONFIG
+            .replace("location: nbg1", "location: mars1")
+            .replace("server_type: cpx51", "server_type: CPX 51")
+            .replace("  version: v1.11.2\n", "");
+
+        let issues = single(&content);
+        let found: Vec<(Option<usize>, &str)> =
+            issues.iter().map(|i| (i.line, i.field.as_str())).collect();
+
+        assert_eq!(
+            found,
+            vec![
+                (Some(3), "hcloud.location"),
+                (Some(8), "talos"),
+                (Some(21), "workers[1].server_type"),
+            ]
+        );
+        assert!(issues[1].message.contains("version"));
+    }
+
+    #[test]
+    fn test_unknown_fields_are_reported() {
+        let content = CONFIG.replace("    count: 1\n", "    count: 1\n    cuont: 2\n");
+        let issues = single(&content);
+
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].field, "control_planes[0]");
+        assert_eq!(issues[0].line, Some(14));
+    }
+
+    #[test]
+    fn test_issues_are_located_in_overlays() {
+        let overlay = "hcloud:\n  location: mars1\n";
+        let mut value: serde_yaml::Value = serde_yaml::from_str(CONFIG).unwrap();
+        crate::config::overlay::merge(&mut value, serde_yaml::from_str(overlay).unwrap());
+
+        let sources = [source("base.yaml", CONFIG), source("prod.yaml", overlay)];
+        let issues = issues(&sources, &value).unwrap();
+
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].file, "prod.yaml");
+        assert_eq!(issues[0].line, Some(2));
+
+        let overlay = "[hcloud]\nlocation = \"mars1\"\n";
+        let sources = [source("base.yaml", CONFIG), source("prod.toml", overlay)];
+        let toml_issues = super::issues(&sources, &value).unwrap();
+        assert_eq!(toml_issues[0].file, "prod.toml");
+        assert_eq!(toml_issues[0].line, None);
+    }
+
+    #[test]
+    fn test_field_path() {
+        assert_eq!(field_path(""), "(root)");
+        assert_eq!(field_path("/workers/0/count"), "workers[0].count");
+    }
+}
@@ -0,0 +1,73 @@
+/// SOPS-encrypted configuration support
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::process::Command;
+
+use super::ConfigFormat;
+
+const SOPS_INSTALL_URL: &str = "https://github.com/getsops/sops/releases";
+
+/// Whether a parsed YAML document carries SOPS metadata
+pub fn is_encrypted(document: &serde_yaml::Value) -> bool {
+    document
+        .get("sops")
+        .is_some_and(|metadata| metadata.get("mac").is_some())
+}
+
+/// Decrypt a whole SOPS-encrypted YAML or JSON file
+pub fn decrypt_file(path: &Path, format: ConfigFormat) -> Result<String> {
+    let format = format.extension();
+    run_sops(&[
+        "--decrypt".as_ref(),
+        "--input-type".as_ref(),
+        format.as_ref(),
+        "--output-type".as_ref(),
+        format.as_ref(),
+        path.as_os_str(),
+    ])
+    .context(format!("Failed to decrypt {}", path.display()))
+}
+
+/// Decrypt the `token` value of a SOPS-encrypted YAML/JSON file
+pub fn decrypt_token(path: &Path) -> Result<String> {
+    let token = run_sops(&[
+        "--decrypt".as_ref(),
+        "--extract".as_ref(),
+        "[\"token\"]".as_ref(),
+        path.as_os_str(),
+    ])
+    .context(format!("Failed to decrypt the token in {}", path.display()))?;
+
+    Ok(token.trim().to_string())
+}
+
+fn run_sops(args: &[&std::ffi::OsStr]) -> Result<String> {
+    let output = Command::new("sops").args(args).output().context(format!(
+        "sops is not installed or not in PATH. Please install from {}",
+        SOPS_INSTALL_URL
+    ))?;
+
+    if !output.status.success() {
+        anyhow::bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_sops_metadata() {
+        let encrypted: serde_yaml::Value = serde_yaml::from_str(
+            "cluster_name: ENC[AES256_GCM,data:abc,type:str]\n\
+             sops:\n  mac: ENC[AES256_GCM,data:def,type:str]\n  version: 3.9.0\n",
+        )
+        .unwrap();
+        assert!(is_encrypted(&encrypted));
+
+        let plain: serde_yaml::Value = serde_yaml::from_str("cluster_name: test\n").unwrap();
+        assert!(!is_encrypted(&plain));
+    }
+}
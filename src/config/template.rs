@@ -0,0 +1,67 @@
+/// Starting-point presets for `oxide init`
+use super::{ClusterConfig, NodeConfig};
+
+/// Config preset selected with `oxide init --template`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ConfigTemplate {
+    /// One control plane and one worker, the smallest working cluster
+    Minimal,
+    /// Three control planes and three workers for production
+    Ha,
+    /// Cheapest shared-vCPU servers with Hubble disabled, for experiments
+    Dev,
+}
+
+impl ClusterConfig {
+    /// Build a configuration from a preset
+    pub fn from_template(template: ConfigTemplate) -> Self {
+        let mut config = Self::example();
+
+        match template {
+            ConfigTemplate::Ha => {}
+            ConfigTemplate::Minimal => {
+                set_pool(&mut config.control_planes, "cpx21", 1);
+                set_pool(&mut config.workers, "cpx31", 1);
+            }
+            ConfigTemplate::Dev => {
+                config.cluster_name = "dev-cluster".to_string();
+                config.cilium.enable_hubble = false;
+                set_pool(&mut config.control_planes, "cx22", 1);
+                set_pool(&mut config.workers, "cx22", 1);
+            }
+        }
+
+        config
+    }
+}
+
+fn set_pool(pools: &mut [NodeConfig], server_type: &str, count: u32) {
+    for pool in pools {
+        pool.server_type = server_type.to_string();
+        pool.count = count;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_templates_are_valid() {
+        for template in [
+            ConfigTemplate::Minimal,
+            ConfigTemplate::Ha,
+            ConfigTemplate::Dev,
+        ] {
+            let config = ClusterConfig::from_template(template);
+            assert!(config.validate().is_ok(), "{:?}", template);
+        }
+
+        let minimal = ClusterConfig::from_template(ConfigTemplate::Minimal);
+        assert_eq!(minimal.control_planes[0].count, 1);
+        assert_eq!(
+            ClusterConfig::from_template(ConfigTemplate::Ha).control_planes[0].count,
+            3
+        );
+    }
+}
@@ -0,0 +1,159 @@
+/// Version aliases: `latest` and `major.minor`
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::Path;
+use tracing::info;
+
+use super::ClusterConfig;
+use crate::state::{ClusterState, VersionPin};
+use crate::upgrade::check;
+use crate::utils::releases;
+
+/// State key, name, release repository and tag prefix of each component
+const COMPONENTS: [(&str, &str, &str, &str); 3] = [
+    ("talos", "Talos", releases::TALOS_REPO, "v"),
+    ("kubernetes", "Kubernetes", releases::KUBERNETES_REPO, ""),
+    ("cilium", "Cilium", releases::CILIUM_REPO, ""),
+];
+
+fn version_of<'a>(config: &'a mut ClusterConfig, component: &str) -> &'a mut String {
+    match component {
+        "talos" => &mut config.talos.version,
+        "kubernetes" => &mut config.talos.kubernetes_version,
+        _ => &mut config.cilium.version,
+    }
+}
+
+/// `(major, minor)` of a `1.31` or `v1.11` alias
+fn release_line(version: &str) -> Option<(u64, u64)> {
+    let (major, minor) = version.trim_start_matches('v').split_once('.')?;
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+/// Whether `version` is `latest` or `major.minor` rather than a release
+pub fn is_alias(version: &str) -> bool {
+    version == "latest" || release_line(version).is_some()
+}
+
+/// Newest stable release among `releases` that `alias` stands for
+pub fn resolve(alias: &str, releases: &[String], prefix: &str) -> Option<String> {
+    let line = release_line(alias);
+    releases
+        .iter()
+        .filter_map(|release| check::parse(release))
+        .filter(|version| match line {
+            Some((major, minor)) => version.0 == major && version.1 == minor,
+            None => true,
+        })
+        .max()
+        .map(|(major, minor, patch)| format!("{}{}.{}.{}", prefix, major, minor, patch))
+}
+
+/// Replace aliases with the releases pinned in `state`
+///
+/// Pins only apply while the config still has the alias they were resolved
+/// from; pins of another cluster are ignored.
+pub fn apply_pins(config: &mut ClusterConfig, state: Option<&ClusterState>) {
+    let Some(state) = state.filter(|state| state.cluster_name == config.cluster_name) else {
+        return;
+    };
+    for (component, ..) in COMPONENTS {
+        let version = version_of(config, component);
+        if let Some(pin) = state
+            .versions
+            .get(component)
+            .filter(|pin| pin.requested == *version)
+        {
+            *version = pin.resolved.clone();
+        }
+    }
+}
+
+/// Resolve the aliases left after [`apply_pins`] and pin them in the
+/// cluster state in `output_dir`
+pub async fn resolve_aliases(config: &mut ClusterConfig, output_dir: &Path) -> Result<()> {
+    let mut pins = BTreeMap::new();
+    for (component, name, repo, prefix) in COMPONENTS {
+        let version = version_of(config, component);
+        if !is_alias(version) {
+            continue;
+        }
+        let available = releases::list_releases(repo).await?;
+        let resolved = resolve(version, &available, prefix).ok_or_else(|| {
+            anyhow::anyhow!("No stable {} release matches version {}", name, version)
+        })?;
+        info!("✓ Resolved {} {} to {}", name, version, resolved);
+        pins.insert(
+            component.to_string(),
+            VersionPin {
+                requested: std::mem::replace(version, resolved.clone()),
+                resolved,
+            },
+        );
+    }
+
+    if !pins.is_empty() {
+        ClusterState::record_versions(output_dir, &config.cluster_name, &pins)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let available: Vec<String> = ["v1.32.1", "v1.31.4", "v1.31.12", "v1.30.9"]
+            .iter()
+            .map(|tag| tag.to_string())
+            .collect();
+
+        assert!(is_alias("latest"));
+        assert!(is_alias("1.31"));
+        assert!(is_alias("v1.11"));
+        assert!(!is_alias("1.31.4"));
+        assert!(!is_alias("v1.11.2"));
+
+        assert_eq!(resolve("latest", &available, "v").unwrap(), "v1.32.1");
+        assert_eq!(resolve("1.31", &available, "").unwrap(), "1.31.12");
+        assert_eq!(resolve("1.29", &available, ""), None);
+    }
+
+    #[test]
+    fn test_apply_pins() {
+        let mut config = ClusterConfig::example();
+        config.talos.version = "latest".to_string();
+        config.talos.kubernetes_version = "1.31".to_string();
+        let cilium = config.cilium.version.clone();
+
+        let pin = |requested: &str, resolved: &str| VersionPin {
+            requested: requested.to_string(),
+            resolved: resolved.to_string(),
+        };
+        let mut state = ClusterState {
+            cluster_name: config.cluster_name.clone(),
+            ..Default::default()
+        };
+        state
+            .versions
+            .insert("talos".to_string(), pin("latest", "v1.11.2"));
+        // Resolved from an alias the config no longer has
+        state
+            .versions
+            .insert("kubernetes".to_string(), pin("1.30", "1.30.9"));
+        state
+            .versions
+            .insert("cilium".to_string(), pin("latest", "1.18.2"));
+
+        let mut other = config.clone();
+        other.cluster_name = "other".to_string();
+        apply_pins(&mut other, Some(&state));
+        assert_eq!(other.talos.version, "latest");
+
+        apply_pins(&mut config, Some(&state));
+        assert_eq!(config.talos.version, "v1.11.2");
+        assert_eq!(config.talos.kubernetes_version, "1.31");
+        assert_eq!(config.cilium.version, cilium);
+    }
+}
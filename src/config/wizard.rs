@@ -0,0 +1,265 @@
+/// Interactive `oxide init` wizard
+use anyhow::Result;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::{Input, Password, Select};
+use tracing::info;
+
+use super::{ClusterConfig, ConfigTemplate, NodeConfig};
+use crate::hcloud::models::ServerType;
+use crate::hcloud::HetznerCloudClient;
+use crate::utils::releases;
+
+/// Ask for the cluster settings, starting from `template`
+pub async fn run(template: ConfigTemplate) -> Result<ClusterConfig> {
+    let theme = ColorfulTheme::default();
+    let mut config = ClusterConfig::from_template(template);
+
+    config.cluster_name = Input::with_theme(&theme)
+        .with_prompt("Cluster name")
+        .default(config.cluster_name.clone())
+        .validate_with(|name: &String| validate_cluster_name(name))
+        .interact_text()?;
+
+    let client = hcloud_client(&theme)?;
+
+    select_location(&theme, client.as_ref(), &mut config).await?;
+
+    config.talos.version = prompt_version(
+        &theme,
+        "Talos version",
+        releases::TALOS_REPO,
+        &config.talos.version,
+        true,
+    )
+    .await?;
+    config.talos.kubernetes_version = prompt_version(
+        &theme,
+        "Kubernetes version",
+        releases::KUBERNETES_REPO,
+        &config.talos.kubernetes_version,
+        false,
+    )
+    .await?;
+    config.cilium.version = prompt_version(
+        &theme,
+        "Cilium version",
+        releases::CILIUM_REPO,
+        &config.cilium.version,
+        false,
+    )
+    .await?;
+
+    let snapshot: String = Input::with_theme(&theme)
+        .with_prompt("Talos snapshot ID (leave empty to fill in later)")
+        .allow_empty(true)
+        .interact_text()?;
+    config.talos.hcloud_snapshot_id = Some(snapshot.trim().to_string()).filter(|s| !s.is_empty());
+
+    let server_types = match &client {
+        Some(client) => client.list_server_types().await?,
+        None => Vec::new(),
+    };
+    let location = config.hcloud.location.clone();
+    for pool in &mut config.control_planes {
+        prompt_pool(&theme, pool, "Control plane", 1, &server_types, &location)?;
+    }
+    for pool in &mut config.workers {
+        prompt_pool(&theme, pool, "Worker", 0, &server_types, &location)?;
+    }
+
+    config.validate()?;
+    Ok(config)
+}
+
+/// API client for live validation, or `None` if the user has no token at hand
+fn hcloud_client(theme: &ColorfulTheme) -> Result<Option<HetznerCloudClient>> {
+    let token = match std::env::var("HCLOUD_TOKEN") {
+        Ok(token) => token,
+        Err(_) => Password::with_theme(theme)
+            .with_prompt("Hetzner Cloud API token (used for validation only, empty to skip)")
+            .allow_empty_password(true)
+            .interact()?,
+    };
+
+    if token.is_empty() {
+        info!("⚠️  No API token, locations and server types will not be validated");
+        return Ok(None);
+    }
+
+    Ok(Some(HetznerCloudClient::new(token)?))
+}
+
+/// Pick the location (and matching network zone) from the API, or type it in
+async fn select_location(
+    theme: &ColorfulTheme,
+    client: Option<&HetznerCloudClient>,
+    config: &mut ClusterConfig,
+) -> Result<()> {
+    let Some(client) = client else {
+        config.hcloud.location = Input::with_theme(theme)
+            .with_prompt("Location")
+            .default(config.hcloud.location.clone())
+            .interact_text()?;
+        return Ok(());
+    };
+
+    let locations = client.list_locations().await?;
+    let items: Vec<String> = locations
+        .iter()
+        .map(|l| format!("{} ({}, {})", l.name, l.city, l.country))
+        .collect();
+    let default = locations
+        .iter()
+        .position(|l| l.name == config.hcloud.location)
+        .unwrap_or(0);
+
+    let index = Select::with_theme(theme)
+        .with_prompt("Location")
+        .items(&items)
+        .default(default)
+        .interact()?;
+
+    let location = &locations[index];
+    config.hcloud.location = location.name.clone();
+    if !location.network_zone.is_empty() {
+        config.hcloud.network.zone = location.network_zone.clone();
+    }
+    Ok(())
+}
+
+/// Ask for a version, offering the latest upstream release as default
+async fn prompt_version(
+    theme: &ColorfulTheme,
+    prompt: &str,
+    repo: &str,
+    fallback: &str,
+    keep_v_prefix: bool,
+) -> Result<String> {
+    let default = match releases::latest_release(repo).await {
+        Ok(tag) if keep_v_prefix => tag,
+        Ok(tag) => tag.trim_start_matches('v').to_string(),
+        Err(e) => {
+            info!("⚠️  {:#}, defaulting to {}", e, fallback);
+            fallback.to_string()
+        }
+    };
+
+    Ok(Input::with_theme(theme)
+        .with_prompt(prompt)
+        .default(default)
+        .interact_text()?)
+}
+
+/// Ask for the server type and size of a node pool
+fn prompt_pool(
+    theme: &ColorfulTheme,
+    pool: &mut NodeConfig,
+    role: &str,
+    min_count: u32,
+    server_types: &[ServerType],
+    location: &str,
+) -> Result<()> {
+    pool.server_type = Input::with_theme(theme)
+        .with_prompt(format!("{} server type ({})", role, pool.name))
+        .default(pool.server_type.clone())
+        .validate_with(|name: &String| validate_server_type(server_types, location, name))
+        .interact_text()?;
+
+    pool.count = Input::with_theme(theme)
+        .with_prompt(format!("{} count ({})", role, pool.name))
+        .default(pool.count)
+        .validate_with(|count: &u32| {
+            if *count >= min_count {
+                Ok(())
+            } else {
+                Err(format!("at least {} node(s) required", min_count))
+            }
+        })
+        .interact_text()?;
+
+    Ok(())
+}
+
+fn validate_cluster_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("cluster name cannot be empty".to_string());
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    {
+        return Err("use lowercase letters, digits and hyphens only".to_string());
+    }
+    Ok(())
+}
+
+/// Check that a server type exists and can be ordered in `location`
+///
+/// Without API data (no token) every name is accepted.
+fn validate_server_type(
+    server_types: &[ServerType],
+    location: &str,
+    name: &str,
+) -> Result<(), String> {
+    if server_types.is_empty() {
+        return Ok(());
+    }
+
+    let Some(server_type) = server_types.iter().find(|t| t.name == name) else {
+        let known: Vec<&str> = server_types.iter().map(|t| t.name.as_str()).collect();
+        return Err(format!(
+            "unknown server type, available: {}",
+            known.join(", ")
+        ));
+    };
+
+    if !server_type.prices.is_empty() && !server_type.prices.iter().any(|p| p.location == location)
+    {
+        return Err(format!("{} is not available in {}", name, location));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hcloud::models::ServerTypePrice;
+
+    fn server_type(name: &str, locations: &[&str]) -> ServerType {
+        ServerType {
+            id: 1,
+            name: name.to_string(),
+            description: String::new(),
+            cores: 2,
+            memory: 4.0,
+            disk: 40,
+            prices: locations
+                .iter()
+                .map(|l| ServerTypePrice {
+                    location: l.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_validate_server_type() {
+        let types = vec![
+            server_type("cpx21", &["nbg1", "fsn1"]),
+            server_type("cpx31", &["ash"]),
+        ];
+
+        assert!(validate_server_type(&types, "nbg1", "cpx21").is_ok());
+        assert!(validate_server_type(&types, "nbg1", "cpx31").is_err());
+        assert!(validate_server_type(&types, "nbg1", "cx99").is_err());
+        assert!(validate_server_type(&[], "nbg1", "cx99").is_ok());
+    }
+
+    #[test]
+    fn test_validate_cluster_name() {
+        assert!(validate_cluster_name("prod-eu-1").is_ok());
+        assert!(validate_cluster_name("").is_err());
+        assert!(validate_cluster_name("Prod Cluster").is_err());
+    }
+}
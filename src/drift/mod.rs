@@ -0,0 +1,616 @@
+/// `oxide drift`: differences between the config and the running cluster
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+use crate::cilium::CiliumManager;
+use crate::config::{ClusterConfig, HelmMode};
+use crate::hcloud::models::{Firewall, FirewallRule};
+use crate::hcloud::projects::Projects;
+use crate::hcloud::server::{NodeRole, ServerInfo};
+use crate::hcloud::FirewallManager;
+use crate::inventory;
+use crate::k8s::nodes::NodeStatus;
+use crate::k8s::NodeManager;
+use crate::state::ClusterState;
+use crate::upgrade::check;
+use crate::utils::helm;
+
+/// One difference between the config and the cluster
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Drift {
+    /// `pool`, `server-type`, `state`, `version`, `firewall` or `helm`
+    pub kind: &'static str,
+    /// What differs: a pool, node, component, firewall rule or Helm value
+    pub subject: String,
+    /// `-` if the config expects nothing here
+    pub expected: String,
+    /// `-` if the cluster has nothing here
+    pub actual: String,
+}
+
+impl Drift {
+    fn new(
+        kind: &'static str,
+        subject: impl Into<String>,
+        expected: impl Into<String>,
+        actual: impl Into<String>,
+    ) -> Self {
+        Self {
+            kind,
+            subject: subject.into(),
+            expected: expected.into(),
+            actual: actual.into(),
+        }
+    }
+}
+
+/// Outcome of `oxide drift`
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    pub drift: Vec<Drift>,
+    /// Checks that could not run, with the reason
+    pub skipped: Vec<String>,
+}
+
+/// Check every project of the cluster and, if `kubeconfig_path` exists, the
+/// cluster itself
+pub async fn detect(
+    config: &ClusterConfig,
+    projects: &Projects,
+    state: Option<&ClusterState>,
+    kubeconfig_path: &Path,
+) -> Result<Report> {
+    let mut report = Report::default();
+
+    let servers = projects.list_cluster_servers(&config.cluster_name).await?;
+    report.drift.extend(pools(config, &servers));
+    match state {
+        Some(state) => report.drift.extend(recorded_servers(state, &servers)),
+        None => report
+            .skipped
+            .push("state: no cluster state in the output directory".to_string()),
+    }
+
+    let firewall_checks = std::iter::once((None, projects.main(), config.multi_region())).chain(
+        projects
+            .others()
+            .map(|(name, client)| (Some(name), client, true)),
+    );
+    for (project, client, kubespan) in firewall_checks {
+        let manager = FirewallManager::new(client.clone())
+            .with_kubespan(kubespan)
+            .with_egress(config.hcloud.egress.clone())
+            .with_public_ports(config.addons.ingress_nginx.node_ports());
+        let live = manager.get_cluster_firewall(&config.cluster_name).await?;
+        // The admin IPs were detected when the firewall was created, so they
+        // are taken over as they are
+        let allowed_ips = live.as_ref().map(admin_sources).unwrap_or_default();
+        let mut found = firewall(&manager.cluster_rules(&allowed_ips), live.as_ref());
+        if let Some(project) = project {
+            for drift in &mut found {
+                drift.subject = format!("{} (project {})", drift.subject, project);
+            }
+        }
+        report.drift.extend(found);
+    }
+
+    if !kubeconfig_path.exists() {
+        report.skipped.push(format!(
+            "versions, helm: no kubeconfig at {}",
+            kubeconfig_path.display()
+        ));
+        return Ok(report);
+    }
+
+    match NodeManager::node_statuses(kubeconfig_path).await {
+        Ok(statuses) => {
+            let cilium = check::running_cilium_version(kubeconfig_path).await;
+            report
+                .drift
+                .extend(versions(config, &statuses, cilium.as_deref()));
+        }
+        Err(e) => report
+            .skipped
+            .push(format!("versions: cluster unreachable ({:#})", e)),
+    }
+
+    if config.helm.mode == HelmMode::Template {
+        report
+            .skipped
+            .push("helm: Cilium is applied from rendered manifests, there is no release".into());
+    } else {
+        let control_plane_count = config.control_planes.iter().map(|cp| cp.count).sum();
+        let expected = CiliumManager::new(
+            config.cilium.clone(),
+            kubeconfig_path.to_path_buf(),
+            control_plane_count,
+        )
+        .with_kubespan(config.multi_region())
+        .with_cloud_load_balancers(config.cloud_load_balancers())
+        .with_istio(config.addons.istio.enabled)
+        .chart_values();
+        match helm::release_values(kubeconfig_path, "cilium", "kube-system").await {
+            Ok(Some(installed)) => report.drift.extend(helm_values(&expected, &installed)),
+            Ok(None) => {
+                report
+                    .drift
+                    .push(Drift::new("helm", "cilium", "release in kube-system", "-"))
+            }
+            Err(e) => report.skipped.push(format!("helm: {:#}", e)),
+        }
+    }
+
+    Ok(report)
+}
+
+/// Node counts and server types of every pool
+pub fn pools(config: &ClusterConfig, servers: &[ServerInfo]) -> Vec<Drift> {
+    let mut drift = Vec::new();
+    let mut counts: HashMap<String, u32> = HashMap::new();
+
+    for info in servers {
+        let server = &info.server;
+        let Some(pool) = inventory::pool_of(config, info) else {
+            drift.push(Drift::new("pool", &server.name, "-", "server in no pool"));
+            continue;
+        };
+        if server.server_type.name != pool.server_type {
+            drift.push(Drift::new(
+                "server-type",
+                &server.name,
+                &pool.server_type,
+                &server.server_type.name,
+            ));
+        }
+        *counts
+            .entry(format!("{} {}", info.role, pool.name))
+            .or_default() += 1;
+    }
+
+    for role in [NodeRole::ControlPlane, NodeRole::Worker] {
+        for pool in pools_of(config, role) {
+            let subject = format!("{} {}", role, pool.name);
+            let count = counts.get(&subject).copied().unwrap_or(0);
+            if count != pool.count {
+                drift.push(Drift::new(
+                    "pool",
+                    subject,
+                    format!("{} node(s)", pool.count),
+                    format!("{} node(s)", count),
+                ));
+            }
+        }
+    }
+
+    drift
+}
+
+fn pools_of(config: &ClusterConfig, role: NodeRole) -> &[crate::config::NodeConfig] {
+    match role {
+        NodeRole::ControlPlane => &config.control_planes,
+        NodeRole::Worker => &config.workers,
+    }
+}
+
+/// Servers missing from the cluster state, and recorded servers that are gone
+pub fn recorded_servers(state: &ClusterState, servers: &[ServerInfo]) -> Vec<Drift> {
+    let mut drift: Vec<Drift> = state
+        .unknown_servers(servers)
+        .into_iter()
+        .map(|info| {
+            Drift::new(
+                "state",
+                &info.server.name,
+                "-",
+                format!("server {} not in the cluster state", info.server.id),
+            )
+        })
+        .collect();
+    for (id, name) in &state.servers {
+        if !servers.iter().any(|info| info.server.id == *id) {
+            drift.push(Drift::new("state", name, format!("server {}", id), "-"));
+        }
+    }
+    drift
+}
+
+/// Talos and Kubernetes versions of every node, and the Cilium version
+pub fn versions(
+    config: &ClusterConfig,
+    statuses: &HashMap<String, NodeStatus>,
+    cilium: Option<&str>,
+) -> Vec<Drift> {
+    let same = |a: &str, b: &str| a.trim_start_matches('v') == b.trim_start_matches('v');
+    let mut drift = Vec::new();
+
+    let nodes: BTreeMap<&String, &NodeStatus> = statuses.iter().collect();
+    for (node, status) in nodes {
+        let talos = status.talos_version.as_deref().unwrap_or("-");
+        if !same(talos, &config.talos.version) {
+            drift.push(Drift::new(
+                "version",
+                format!("{} talos", node),
+                &config.talos.version,
+                talos,
+            ));
+        }
+        if !same(&status.kubelet_version, &config.talos.kubernetes_version) {
+            drift.push(Drift::new(
+                "version",
+                format!("{} kubernetes", node),
+                &config.talos.kubernetes_version,
+                &status.kubelet_version,
+            ));
+        }
+    }
+
+    let cilium = cilium.unwrap_or("-");
+    if !same(cilium, &config.cilium.version) {
+        drift.push(Drift::new(
+            "version",
+            "cilium",
+            &config.cilium.version,
+            cilium,
+        ));
+    }
+
+    drift
+}
+
+/// Sources of the Talos API rule, i.e. the IPs admitted to the APIs
+fn admin_sources(firewall: &Firewall) -> Vec<String> {
+    firewall
+        .rules
+        .iter()
+        .find(|rule| rule.direction == "in" && rule.port.as_deref() == Some("50000"))
+        .map(|rule| rule.source_ips.clone())
+        .unwrap_or_default()
+}
+
+/// Rules missing from the live firewall, and rules added to it
+pub fn firewall(expected: &[FirewallRule], live: Option<&Firewall>) -> Vec<Drift> {
+    let Some(live) = live else {
+        return vec![Drift::new("firewall", "cluster firewall", "present", "-")];
+    };
+
+    let expected: BTreeSet<String> = expected.iter().map(rule_key).collect();
+    let actual: BTreeSet<String> = live.rules.iter().map(rule_key).collect();
+    let missing = expected
+        .difference(&actual)
+        .map(|rule| Drift::new("firewall", rule, "present", "-"));
+    let added = actual
+        .difference(&expected)
+        .map(|rule| Drift::new("firewall", rule, "-", "present"));
+    missing.chain(added).collect()
+}
+
+/// A rule without its description, e.g. `in tcp 6443 from 198.51.100.7/32`
+fn rule_key(rule: &FirewallRule) -> String {
+    let (preposition, ips) = if rule.direction == "in" {
+        ("from", &rule.source_ips)
+    } else {
+        ("to", &rule.destination_ips)
+    };
+    let mut ips = ips.clone();
+    ips.sort();
+    format!(
+        "{} {} {} {} {}",
+        rule.direction,
+        rule.protocol,
+        rule.port.as_deref().unwrap_or("any"),
+        preposition,
+        ips.join(",")
+    )
+}
+
+/// `--set` values oxide passes that the release does not have, and values
+/// the release has on top
+pub fn helm_values(expected: &[String], installed: &serde_json::Value) -> Vec<Drift> {
+    let expected: BTreeMap<String, String> = expected
+        .iter()
+        .filter_map(|value| value.split_once('='))
+        .map(|(key, value)| (key.to_string(), set_value(value)))
+        .collect();
+    let mut actual = BTreeMap::new();
+    flatten("", installed, &mut actual);
+
+    let mut drift = Vec::new();
+    for (key, value) in &expected {
+        match actual.get(key) {
+            Some(installed) if installed == value => {}
+            installed => drift.push(Drift::new(
+                "helm",
+                format!("cilium {}", key),
+                value,
+                installed.map(String::as_str).unwrap_or("-"),
+            )),
+        }
+    }
+    for (key, value) in actual
+        .iter()
+        .filter(|(key, _)| !expected.contains_key(*key))
+    {
+        drift.push(Drift::new("helm", format!("cilium {}", key), "-", value));
+    }
+    drift
+}
+
+/// A `--set` value as [`flatten`] renders it: `{a,b}` lists become `[a, b]`
+/// and escaped commas plain ones
+fn set_value(value: &str) -> String {
+    let unescape = |item: &str| item.replace("\\,", ",");
+    match value.strip_prefix('{').and_then(|v| v.strip_suffix('}')) {
+        Some(items) => {
+            let mut list = Vec::new();
+            let mut item = String::new();
+            let mut chars = items.chars().peekable();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' if chars.peek() == Some(&',') => item.push_str("\\,"),
+                    ',' => list.push(unescape(&std::mem::take(&mut item))),
+                    c => item.push(c),
+                }
+                if c == '\\' && item.ends_with("\\,") {
+                    chars.next();
+                }
+            }
+            list.push(unescape(&item));
+            format!("[{}]", list.join(", "))
+        }
+        None => unescape(value),
+    }
+}
+
+/// Release values as dotted keys, like the `--set` flags that produced them
+fn flatten(prefix: &str, value: &serde_json::Value, out: &mut BTreeMap<String, String>) {
+    let scalar = |value: &serde_json::Value| match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map {
+                let key = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(&key, value, out);
+            }
+        }
+        serde_json::Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(scalar).collect();
+            out.insert(prefix.to_string(), format!("[{}]", items.join(", ")));
+        }
+        other => {
+            out.insert(prefix.to_string(), scalar(other));
+        }
+    }
+}
+
+/// One line per difference, e.g. `pool   worker workers   3 node(s)   2 node(s)`
+pub fn render(report: &Report) -> String {
+    let width = |column: fn(&Drift) -> &str, header: &str| {
+        report
+            .drift
+            .iter()
+            .map(|drift| column(drift).len())
+            .max()
+            .unwrap_or(0)
+            .max(header.len())
+    };
+    let subject = width(|d| &d.subject, "SUBJECT");
+    let expected = width(|d| &d.expected, "EXPECTED");
+
+    let mut out = String::new();
+    if !report.drift.is_empty() {
+        out.push_str(&format!(
+            "{:<11} {:<subject$} {:<expected$} ACTUAL\n",
+            "KIND", "SUBJECT", "EXPECTED"
+        ));
+        for drift in &report.drift {
+            out.push_str(&format!(
+                "{:<11} {:<subject$} {:<expected$} {}\n",
+                drift.kind, drift.subject, drift.expected, drift.actual
+            ));
+        }
+    }
+    for skipped in &report.skipped {
+        out.push_str(&format!("skipped: {}\n", skipped));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hcloud::models::Server;
+
+    fn server_info(id: u64, name: &str, role: NodeRole, server_type: &str) -> ServerInfo {
+        let server: Server = serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": name,
+            "status": "running",
+            "server_type": {"id": 1, "name": server_type, "description": "", "cores": 2, "memory": 4.0, "disk": 40},
+            "datacenter": {
+                "id": 1,
+                "name": "nbg1-dc3",
+                "description": "",
+                "location": {"id": 1, "name": "nbg1", "description": "", "country": "DE", "city": "", "latitude": 0.0, "longitude": 0.0}
+            },
+            "public_net": {"ipv4": {"ip": "198.51.100.10", "blocked": false}, "ipv6": null, "floating_ips": []},
+            "private_net": [],
+            "created": "2026-01-01T00:00:00Z",
+            "labels": {"managed-by": "oxide"}
+        }))
+        .unwrap();
+        ServerInfo {
+            server,
+            role,
+            index: 1,
+        }
+    }
+
+    #[test]
+    fn test_pools_and_state() {
+        let mut config = ClusterConfig::example();
+        config.control_planes[0].count = 1;
+        config.workers.truncate(1);
+        config.workers[0].count = 2;
+        let cluster = config.cluster_name.clone();
+        let control_plane = &config.control_planes[0];
+        let worker = &config.workers[0];
+
+        let servers = vec![
+            server_info(
+                1,
+                &format!("{}-{}", cluster, control_plane.name),
+                NodeRole::ControlPlane,
+                &control_plane.server_type,
+            ),
+            server_info(
+                2,
+                &format!("{}-{}-1", cluster, worker.name),
+                NodeRole::Worker,
+                "cx52",
+            ),
+            server_info(
+                3,
+                &format!("{}-legacy-1", cluster),
+                NodeRole::Worker,
+                "cx22",
+            ),
+        ];
+
+        let drift = pools(&config, &servers);
+        assert_eq!(
+            drift,
+            vec![
+                Drift::new(
+                    "server-type",
+                    &servers[1].server.name,
+                    &worker.server_type,
+                    "cx52"
+                ),
+                Drift::new("pool", &servers[2].server.name, "-", "server in no pool"),
+                Drift::new(
+                    "pool",
+                    format!("worker {}", worker.name),
+                    "2 node(s)",
+                    "1 node(s)"
+                ),
+            ]
+        );
+
+        let mut state = ClusterState {
+            cluster_name: cluster.clone(),
+            ..Default::default()
+        };
+        state.servers.insert(1, servers[0].server.name.clone());
+        state.servers.insert(2, servers[1].server.name.clone());
+        state.servers.insert(9, "gone".to_string());
+        let drift = recorded_servers(&state, &servers);
+        assert_eq!(drift.len(), 2);
+        assert_eq!(drift[0].subject, servers[2].server.name);
+        assert_eq!(drift[1], Drift::new("state", "gone", "server 9", "-"));
+    }
+
+    #[test]
+    fn test_versions() {
+        let config = ClusterConfig::example();
+        let status = |kubelet: &str, talos: &str| NodeStatus {
+            ready: true,
+            kubelet_version: kubelet.to_string(),
+            talos_version: Some(talos.to_string()),
+        };
+        let kubelet = format!("v{}", config.talos.kubernetes_version);
+        let statuses = HashMap::from([
+            ("a".to_string(), status(&kubelet, &config.talos.version)),
+            ("b".to_string(), status("v1.0.0", &config.talos.version)),
+        ]);
+
+        let drift = versions(
+            &config,
+            &statuses,
+            Some(&format!("v{}", config.cilium.version)),
+        );
+        assert_eq!(
+            drift,
+            vec![Drift::new(
+                "version",
+                "b kubernetes",
+                &config.talos.kubernetes_version,
+                "v1.0.0"
+            )]
+        );
+        assert_eq!(
+            versions(&config, &HashMap::new(), None)[0].subject,
+            "cilium"
+        );
+    }
+
+    #[test]
+    fn test_firewall() {
+        let rule = |port: &str, sources: &[&str]| FirewallRule {
+            description: None,
+            direction: "in".to_string(),
+            source_ips: sources.iter().map(|s| s.to_string()).collect(),
+            destination_ips: vec![],
+            protocol: "tcp".to_string(),
+            port: Some(port.to_string()),
+        };
+        let expected = vec![
+            rule("50000", &["198.51.100.7/32"]),
+            rule("80", &["0.0.0.0/0"]),
+        ];
+        let live: Firewall = serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "name": "prod-firewall",
+            "rules": [rule("50000", &["198.51.100.7/32"]), rule("22", &["0.0.0.0/0"])],
+            "applied_to": [],
+            "created": "2026-01-01T00:00:00Z",
+            "labels": {}
+        }))
+        .unwrap();
+
+        assert_eq!(admin_sources(&live), vec!["198.51.100.7/32"]);
+        assert_eq!(
+            firewall(&expected, Some(&live)),
+            vec![
+                Drift::new("firewall", "in tcp 80 from 0.0.0.0/0", "present", "-"),
+                Drift::new("firewall", "in tcp 22 from 0.0.0.0/0", "-", "present"),
+            ]
+        );
+        assert_eq!(firewall(&expected, None).len(), 1);
+    }
+
+    #[test]
+    fn test_helm_values() {
+        let expected = [
+            "operator.replicas=2".to_string(),
+            "hubble.enabled=true".to_string(),
+            "MTU=1370".to_string(),
+            "hubble.metrics.enabled={dns,httpV2:labelsContext=source_ip\\,source_namespace}"
+                .to_string(),
+        ];
+        let installed = serde_json::json!({
+            "operator": {"replicas": 1},
+            "hubble": {
+                "enabled": true,
+                "metrics": {"enabled": ["dns", "httpV2:labelsContext=source_ip,source_namespace"]}
+            },
+            "MTU": 1370,
+            "debug": {"enabled": true}
+        });
+
+        assert_eq!(
+            helm_values(&expected, &installed),
+            vec![
+                Drift::new("helm", "cilium operator.replicas", "2", "1"),
+                Drift::new("helm", "cilium debug.enabled", "-", "true"),
+            ]
+        );
+    }
+}
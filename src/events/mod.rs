@@ -0,0 +1,262 @@
+/// Cluster events for `oxide events`
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::Path;
+use tracing::warn;
+
+use crate::hcloud::models::Action;
+use crate::hcloud::server::ServerInfo;
+use crate::hcloud::HetznerCloudClient;
+use crate::utils::command::CommandBuilder;
+
+/// One entry of the cluster timeline
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct ClusterEvent {
+    pub time: DateTime<Utc>,
+    /// `kubernetes` or `hcloud`
+    pub source: &'static str,
+    /// `Warning` or `Normal`; failed hcloud actions are warnings
+    pub level: String,
+    /// Affected object, e.g. `pod/kube-system/cilium-x2v9k` or `server/prod-worker-1`
+    pub object: String,
+    pub reason: String,
+    pub message: String,
+}
+
+impl ClusterEvent {
+    pub fn is_warning(&self) -> bool {
+        self.level != "Normal"
+    }
+
+    /// One log line per event
+    pub fn summary(&self) -> String {
+        format!(
+            "{} {:<7} {:<10} {} {}: {}",
+            self.time.format("%Y-%m-%d %H:%M:%S"),
+            self.level,
+            self.source,
+            self.object,
+            self.reason,
+            self.message
+        )
+    }
+}
+
+/// Collect events newer than `since`, oldest first
+///
+/// Kubernetes events are skipped without a kubeconfig; a failing source only
+/// produces a warning so the other one is still shown.
+pub async fn collect(
+    client: &HetznerCloudClient,
+    servers: &[ServerInfo],
+    kubeconfig_path: &Path,
+    since: DateTime<Utc>,
+    warnings_only: bool,
+) -> Vec<ClusterEvent> {
+    let mut events = Vec::new();
+
+    for info in servers {
+        match client.list_server_actions(info.server.id).await {
+            Ok(actions) => events.extend(action_events(&info.server.name, &actions)),
+            Err(e) => warn!(
+                "⚠️  Could not get actions of server {}: {}",
+                info.server.name, e
+            ),
+        }
+    }
+
+    if kubeconfig_path.exists() {
+        match kubernetes_events(kubeconfig_path, warnings_only).await {
+            Ok(kube_events) => events.extend(kube_events),
+            Err(e) => warn!("⚠️  Could not get Kubernetes events: {:#}", e),
+        }
+    }
+
+    events.retain(|e| e.time >= since && (!warnings_only || e.is_warning()));
+    events.sort_by_key(|e| e.time);
+    events
+}
+
+async fn kubernetes_events(
+    kubeconfig_path: &Path,
+    warnings_only: bool,
+) -> Result<Vec<ClusterEvent>> {
+    let mut args = vec!["get", "events", "--all-namespaces", "-o", "json"];
+    if warnings_only {
+        args.extend(["--field-selector", "type=Warning"]);
+    }
+    let output = CommandBuilder::new("kubectl")
+        .args(args)
+        .kubeconfig(kubeconfig_path)
+        .context("Failed to get events")
+        .run()
+        .await?;
+
+    Ok(parse_kubernetes_events(&serde_json::from_str(&output)?))
+}
+
+/// Events of a `kubectl get events -o json` list
+///
+/// Repeated events carry the time of their last occurrence.
+fn parse_kubernetes_events(list: &serde_json::Value) -> Vec<ClusterEvent> {
+    let Some(items) = list["items"].as_array() else {
+        return Vec::new();
+    };
+
+    items
+        .iter()
+        .filter_map(|event| {
+            let time = [
+                &event["lastTimestamp"],
+                &event["eventTime"],
+                &event["metadata"]["creationTimestamp"],
+            ]
+            .iter()
+            .filter_map(|value| value.as_str())
+            .find_map(parse_time)?;
+
+            let involved = &event["involvedObject"];
+            let kind = involved["kind"].as_str().unwrap_or_default().to_lowercase();
+            let name = involved["name"].as_str().unwrap_or_default();
+            let object = match involved["namespace"].as_str() {
+                Some(namespace) if !namespace.is_empty() => {
+                    format!("{}/{}/{}", kind, namespace, name)
+                }
+                _ => format!("{}/{}", kind, name),
+            };
+
+            let mut message = event["message"]
+                .as_str()
+                .unwrap_or_default()
+                .trim()
+                .to_string();
+            if let Some(count) = event["count"].as_u64().filter(|count| *count > 1) {
+                message.push_str(&format!(" (x{})", count));
+            }
+
+            Some(ClusterEvent {
+                time,
+                source: "kubernetes",
+                level: event["type"].as_str().unwrap_or("Normal").to_string(),
+                object,
+                reason: event["reason"].as_str().unwrap_or_default().to_string(),
+                message,
+            })
+        })
+        .collect()
+}
+
+/// Events for the hcloud actions of one server
+fn action_events(server_name: &str, actions: &[Action]) -> Vec<ClusterEvent> {
+    actions
+        .iter()
+        .filter_map(|action| {
+            let message = match &action.error {
+                Some(error) => format!("{}: {}", error.code, error.message),
+                None => action.status.clone(),
+            };
+            Some(ClusterEvent {
+                time: parse_time(&action.started)?,
+                source: "hcloud",
+                level: if action.status == "error" {
+                    "Warning"
+                } else {
+                    "Normal"
+                }
+                .to_string(),
+                object: format!("server/{}", server_name),
+                reason: action.command.clone(),
+                message,
+            })
+        })
+        .collect()
+}
+
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_kubernetes_events() {
+        let list = serde_json::json!({
+            "items": [
+                {
+                    "metadata": {"creationTimestamp": "2026-03-01T10:00:00Z"},
+                    "involvedObject": {"kind": "Pod", "namespace": "kube-system", "name": "cilium-x2v9k"},
+                    "reason": "BackOff",
+                    "message": "Back-off restarting failed container",
+                    "type": "Warning",
+                    "count": 4,
+                    "lastTimestamp": "2026-03-01T10:05:00Z"
+                },
+                {
+                    "metadata": {"creationTimestamp": "2026-03-01T09:00:00Z"},
+                    "involvedObject": {"kind": "Node", "name": "prod-worker-1"},
+                    "reason": "NodeReady",
+                    "message": "Node prod-worker-1 status is now: NodeReady",
+                    "type": "Normal",
+                    "lastTimestamp": null,
+                    "eventTime": null
+                }
+            ]
+        });
+
+        let events = parse_kubernetes_events(&list);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].object, "pod/kube-system/cilium-x2v9k");
+        assert_eq!(events[0].time, parse_time("2026-03-01T10:05:00Z").unwrap());
+        assert_eq!(
+            events[0].message,
+            "Back-off restarting failed container (x4)"
+        );
+        assert!(events[0].is_warning());
+        assert_eq!(events[1].object, "node/prod-worker-1");
+        assert_eq!(events[1].time, parse_time("2026-03-01T09:00:00Z").unwrap());
+        assert!(!events[1].is_warning());
+    }
+
+    #[test]
+    fn test_action_events() {
+        let actions: Vec<Action> = serde_json::from_value(serde_json::json!([
+            {
+                "id": 2,
+                "command": "change_type",
+                "status": "error",
+                "progress": 0,
+                "started": "2026-03-01T10:00:00+00:00",
+                "finished": "2026-03-01T10:00:05+00:00",
+                "error": {"code": "resource_unavailable", "message": "server type not available"}
+            },
+            {
+                "id": 1,
+                "command": "create_server",
+                "status": "success",
+                "progress": 100,
+                "started": "2026-03-01T09:00:00+00:00",
+                "finished": "2026-03-01T09:00:30+00:00",
+                "error": null
+            }
+        ]))
+        .unwrap();
+
+        let events = action_events("prod-worker-1", &actions);
+        assert_eq!(events[0].level, "Warning");
+        assert_eq!(events[0].reason, "change_type");
+        assert_eq!(
+            events[0].message,
+            "resource_unavailable: server type not available"
+        );
+        assert_eq!(events[1].level, "Normal");
+        assert_eq!(events[1].message, "success");
+        assert!(events[1].summary().starts_with(
+            "2026-03-01 09:00:00 Normal  hcloud     server/prod-worker-1 create_server"
+        ));
+    }
+}
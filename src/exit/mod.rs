@@ -0,0 +1,217 @@
+/// Exit codes and the run summary for scripts and CI
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+
+/// Exit code of an error without a known cause
+pub const EXIT_ERROR: i32 = 1;
+
+/// Cause of a failed command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// The config cannot be loaded or is invalid, a credential is missing,
+    /// or a prompt was needed in non-interactive mode
+    Config,
+    /// The Hetzner API or the object storage rejected a request or could not
+    /// be reached
+    Provider,
+    /// Waiting for servers, nodes or the cluster ran out of time
+    Timeout,
+    /// The command changed some resources but failed for others
+    Partial,
+}
+
+impl FailureKind {
+    /// Exit code of the kind, documented in the README; never renumber these
+    pub fn exit_code(self) -> i32 {
+        match self {
+            FailureKind::Config => 3,
+            FailureKind::Provider => 4,
+            FailureKind::Timeout => 5,
+            FailureKind::Partial => 6,
+        }
+    }
+}
+
+/// Error message tagged with its cause
+///
+/// Raised with `anyhow::bail!(Failure::timeout(...))` or attached with
+/// `.context(Failure::config(...))`; the outermost marker of an error
+/// decides the exit code.
+#[derive(Debug)]
+pub struct Failure {
+    kind: FailureKind,
+    message: String,
+}
+
+impl Failure {
+    pub fn config(message: impl Into<String>) -> Self {
+        Self::new(FailureKind::Config, message)
+    }
+
+    pub fn provider(message: impl Into<String>) -> Self {
+        Self::new(FailureKind::Provider, message)
+    }
+
+    pub fn timeout(message: impl Into<String>) -> Self {
+        Self::new(FailureKind::Timeout, message)
+    }
+
+    pub fn partial(message: impl Into<String>) -> Self {
+        Self::new(FailureKind::Partial, message)
+    }
+
+    fn new(kind: FailureKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Cause of `error`, `None` if unknown
+///
+/// Errors without a marker still count as provider errors if a request
+/// failed on the way, and as partial failures if the command had already
+/// changed something (`changed`).
+pub fn classify(error: &anyhow::Error, changed: bool) -> Option<FailureKind> {
+    if let Some(failure) = error.downcast_ref::<Failure>() {
+        return Some(failure.kind);
+    }
+    if error.chain().any(|cause| cause.is::<reqwest::Error>()) {
+        return Some(FailureKind::Provider);
+    }
+    changed.then_some(FailureKind::Partial)
+}
+
+/// Machine-readable outcome of a command, written by `--summary-file`
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    pub command: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cluster: Option<String>,
+    pub success: bool,
+    pub exit_code: i32,
+    /// Cause of the failure, `null` on success or if unknown
+    pub failure: Option<FailureKind>,
+    pub error: Option<String>,
+    pub started: DateTime<Utc>,
+    pub duration_secs: f64,
+    /// Number of successful actions recorded in the audit log, i.e. whether
+    /// the run changed anything that may need cleaning up
+    pub changes: usize,
+    /// Files of the output directory a follow-up step may need, keyed by
+    /// name (`kubeconfig`, `talosconfig`), if they exist
+    pub outputs: BTreeMap<String, String>,
+}
+
+impl Summary {
+    pub fn new(
+        command: &str,
+        result: &Result<()>,
+        changes: usize,
+        started: DateTime<Utc>,
+        duration: Duration,
+    ) -> Self {
+        let failure = result.as_ref().err().and_then(|e| classify(e, changes > 0));
+        Self {
+            command: command.to_string(),
+            cluster: None,
+            success: result.is_ok(),
+            exit_code: exit_code(result, changes > 0),
+            failure,
+            error: result.as_ref().err().map(|e| format!("{:#}", e)),
+            started,
+            duration_secs: duration.as_secs_f64(),
+            changes,
+            outputs: BTreeMap::new(),
+        }
+    }
+
+    /// Name the cluster of the run
+    pub fn with_cluster(mut self, cluster: &str) -> Self {
+        self.cluster = Some(cluster.to_string());
+        self
+    }
+
+    /// List the files of `output_dir` later steps usually pick up
+    pub fn with_outputs(mut self, output_dir: &Path) -> Self {
+        for name in ["kubeconfig", "talosconfig"] {
+            let path = output_dir.join(name);
+            if path.exists() {
+                self.outputs
+                    .insert(name.to_string(), path.display().to_string());
+            }
+        }
+        self
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .context(format!("Failed to create {}", parent.display()))?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .context(format!("Failed to write {}", path.display()))
+    }
+}
+
+/// Exit code of a finished command
+pub fn exit_code(result: &Result<()>, changed: bool) -> i32 {
+    match result {
+        Ok(()) => 0,
+        Err(e) => classify(e, changed).map_or(EXIT_ERROR, FailureKind::exit_code),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let error = anyhow::Error::msg(Failure::timeout("Timeout after 600 seconds"))
+            .context("Failed to bootstrap the cluster");
+        assert_eq!(classify(&error, true), Some(FailureKind::Timeout));
+        assert_eq!(
+            format!("{:#}", error),
+            "Failed to bootstrap the cluster: Timeout after 600 seconds"
+        );
+
+        // The outermost marker wins
+        let error = anyhow::Error::msg(Failure::provider("API error: server_error"))
+            .context(Failure::partial("Failed to reset 1 node(s)"));
+        assert_eq!(classify(&error, false), Some(FailureKind::Partial));
+
+        let error = anyhow::anyhow!("Node prod-worker-9 not found");
+        assert_eq!(classify(&error, false), None);
+        assert_eq!(classify(&error, true), Some(FailureKind::Partial));
+        assert_eq!(exit_code(&Err(error), false), EXIT_ERROR);
+        assert_eq!(exit_code(&Ok(()), true), 0);
+    }
+
+    #[test]
+    fn test_summary() {
+        let result = Err(anyhow::Error::msg(Failure::config(
+            "Failed to load configuration",
+        )));
+        let summary = Summary::new("create", &result, 0, Utc::now(), Duration::from_secs(2))
+            .with_cluster("prod");
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["exit_code"], 3);
+        assert_eq!(json["failure"], "config");
+        assert_eq!(json["cluster"], "prod");
+        assert_eq!(json["success"], false);
+    }
+}
@@ -0,0 +1,453 @@
+/// `oxide export`: the cluster described for other tooling
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+use crate::addons::AddonManager;
+use crate::config::{ClusterConfig, NodeConfig};
+use crate::exit::Failure;
+use crate::hcloud::ssh_key;
+
+const CLUSTER_API: &str = "cluster.x-k8s.io/v1beta1";
+const INFRASTRUCTURE_API: &str = "infrastructure.cluster.x-k8s.io/v1beta1";
+const BOOTSTRAP_API: &str = "bootstrap.cluster.x-k8s.io/v1alpha3";
+const CONTROL_PLANE_API: &str = "controlplane.cluster.x-k8s.io/v1alpha3";
+
+/// Secret CAPH reads the Hetzner Cloud API token from, under key `hcloud`
+const SECRET_NAME: &str = "hetzner";
+
+/// Port of the Kubernetes API on Talos control planes
+const API_PORT: u16 = 6443;
+
+/// Cluster API manifests equivalent to `config`
+///
+/// `endpoint` is the API server URL of the running cluster; without one, CAPH
+/// puts a load balancer in front of the control planes.
+pub fn capi(config: &ClusterConfig, endpoint: Option<&str>) -> Result<String> {
+    let name = &config.cluster_name;
+    let kubernetes_version = kubernetes_version(&config.talos.kubernetes_version)?;
+    let talos_version = talos_minor(&config.talos.version)?;
+    let control_plane = config
+        .control_planes
+        .first()
+        .context(Failure::config("The config has no control plane pool"))?;
+
+    let mut cluster_patches = Vec::new();
+    for patch in &config.talos.config_patches {
+        cluster_patches.push(read_patch(patch)?);
+    }
+    cluster_patches.extend(AddonManager::machine_config_patches(
+        &config.addons,
+        &config.talos.version,
+    ));
+
+    let mut objects = vec![
+        json!({
+            "apiVersion": CLUSTER_API,
+            "kind": "Cluster",
+            "metadata": { "name": name },
+            "spec": {
+                "clusterNetwork": {
+                    "pods": { "cidrBlocks": [config.talos.pod_cidr] },
+                    "services": { "cidrBlocks": [config.talos.service_cidr] },
+                },
+                "controlPlaneRef": {
+                    "apiVersion": CONTROL_PLANE_API,
+                    "kind": "TalosControlPlane",
+                    "name": format!("{}-control-plane", name),
+                },
+                "infrastructureRef": {
+                    "apiVersion": INFRASTRUCTURE_API,
+                    "kind": "HetznerCluster",
+                    "name": name,
+                },
+            },
+        }),
+        hetzner_cluster(config, endpoint)?,
+        machine_template(config, control_plane),
+    ];
+
+    let mut control_plane_patches = cluster_patches.clone();
+    if config
+        .control_planes
+        .iter()
+        .any(|pool| pool.allow_workloads)
+    {
+        control_plane_patches
+            .push("cluster:\n  allowSchedulingOnControlPlanes: true\n".to_string());
+    }
+    control_plane_patches.extend(labels_patch(control_plane)?);
+    objects.push(json!({
+        "apiVersion": CONTROL_PLANE_API,
+        "kind": "TalosControlPlane",
+        "metadata": { "name": format!("{}-control-plane", name) },
+        "spec": {
+            "version": kubernetes_version,
+            "replicas": config.control_planes.iter().map(|pool| pool.count).sum::<u32>(),
+            "infrastructureTemplate": {
+                "apiVersion": INFRASTRUCTURE_API,
+                "kind": "HCloudMachineTemplate",
+                "name": template_name(name, control_plane),
+            },
+            "controlPlaneConfig": {
+                "controlplane": {
+                    "generateType": "controlplane",
+                    "talosVersion": talos_version,
+                    "strategicPatches": control_plane_patches,
+                },
+            },
+        },
+    }));
+
+    for pool in &config.workers {
+        let mut patches = cluster_patches.clone();
+        patches.extend(AddonManager::pool_machine_config_patches(pool));
+        patches.extend(labels_patch(pool)?);
+        objects.push(machine_template(config, pool));
+        objects.push(json!({
+            "apiVersion": BOOTSTRAP_API,
+            "kind": "TalosConfigTemplate",
+            "metadata": { "name": template_name(name, pool) },
+            "spec": {
+                "template": {
+                    "spec": {
+                        "generateType": "worker",
+                        "talosVersion": talos_version,
+                        "strategicPatches": patches,
+                    },
+                },
+            },
+        }));
+
+        // One MachineDeployment per location, as a spread pool places its
+        // nodes round-robin
+        let mut replicas: BTreeMap<&str, u32> = BTreeMap::new();
+        for index in 0..pool.count {
+            *replicas
+                .entry(pool.location_for(index, &config.hcloud.location))
+                .or_default() += 1;
+        }
+        let spread = replicas.len() > 1;
+        for (location, count) in replicas {
+            let deployment = if spread {
+                format!("{}-{}", template_name(name, pool), location)
+            } else {
+                template_name(name, pool)
+            };
+            objects.push(machine_deployment(
+                name,
+                &deployment,
+                pool,
+                location,
+                count,
+                &kubernetes_version,
+            ));
+        }
+    }
+
+    let mut output = header(config);
+    for object in &objects {
+        output.push_str("---\n");
+        output.push_str(&serde_yaml::to_string(object)?);
+    }
+    Ok(output)
+}
+
+/// Comment on what the manifests leave out and what to create first
+fn header(config: &ClusterConfig) -> String {
+    let mut header = format!(
+        "# Cluster API manifests exported by oxide for {}\n\
+         #\n\
+         # Needs the CAPH, Talos bootstrap and Talos control plane providers, and\n\
+         # the API token in a Secret of the same namespace:\n\
+         #   kubectl create secret generic {} --from-literal=hcloud=$HCLOUD_TOKEN\n\
+         # CAPH finds images by name or `caph-image-name` label, so label the Talos\n\
+         # snapshot with the imageName below; system extensions must be part of it.\n\
+         # Addons are not included, see `oxide render`.\n",
+        config.cluster_name, SECRET_NAME
+    );
+    if config.control_planes.len() > 1 {
+        header.push_str(
+            "# TalosControlPlane uses the machine template of the first control plane\n\
+             # pool for all control planes.\n",
+        );
+    }
+    if !config.hcloud.regions.is_empty() {
+        header.push_str("# Only the primary network is exported; CAPH manages a single network.\n");
+    }
+    if config.robot.is_some() {
+        header.push_str(
+            "# Robot servers are not exported; CAPH manages them as HetznerBareMetalHosts.\n",
+        );
+    }
+    header
+}
+
+fn hetzner_cluster(config: &ClusterConfig, endpoint: Option<&str>) -> Result<Value> {
+    let mut regions: Vec<&str> = config
+        .control_planes
+        .iter()
+        .flat_map(|pool| (0..pool.count).map(|i| pool.location_for(i, &config.hcloud.location)))
+        .collect();
+    regions.sort_unstable();
+    regions.dedup();
+
+    let network = &config.hcloud.network;
+    let mut spec = json!({
+        "controlPlaneRegions": regions,
+        "hcloudNetwork": {
+            "enabled": true,
+            "cidrBlock": network.cidr,
+            "subnetCidrBlock": network.subnet_cidr,
+            "networkZone": network.zone,
+        },
+        "sshKeys": { "hcloud": [{ "name": ssh_key::key_name(&config.cluster_name) }] },
+        "hetznerSecretRef": {
+            "name": SECRET_NAME,
+            "key": { "hcloudToken": "hcloud" },
+        },
+    });
+    match endpoint {
+        Some(endpoint) => {
+            let url = url::Url::parse(endpoint)
+                .context(format!("Invalid API server URL {}", endpoint))?;
+            let host = url
+                .host_str()
+                .context(format!("API server URL {} has no host", endpoint))?;
+            spec["controlPlaneEndpoint"] = json!({
+                "host": host,
+                "port": url.port().unwrap_or(API_PORT),
+            });
+            spec["controlPlaneLoadBalancer"] = json!({ "enabled": false });
+        }
+        None => {
+            spec["controlPlaneLoadBalancer"] = json!({
+                "enabled": true,
+                "region": config.hcloud.location,
+            });
+        }
+    }
+    Ok(json!({
+        "apiVersion": INFRASTRUCTURE_API,
+        "kind": "HetznerCluster",
+        "metadata": { "name": config.cluster_name },
+        "spec": spec,
+    }))
+}
+
+fn machine_template(config: &ClusterConfig, pool: &NodeConfig) -> Value {
+    let image = pool
+        .hcloud_snapshot_id
+        .clone()
+        .or_else(|| config.talos.hcloud_snapshot_id.clone())
+        .unwrap_or_else(|| format!("talos-{}", config.talos.version));
+    json!({
+        "apiVersion": INFRASTRUCTURE_API,
+        "kind": "HCloudMachineTemplate",
+        "metadata": { "name": template_name(&config.cluster_name, pool) },
+        "spec": {
+            "template": {
+                "spec": {
+                    "type": pool.server_type,
+                    "imageName": image,
+                    "publicNetwork": {
+                        "enableIPv4": pool.public_ip,
+                        "enableIPv6": pool.public_ip,
+                    },
+                },
+            },
+        },
+    })
+}
+
+fn machine_deployment(
+    cluster_name: &str,
+    name: &str,
+    pool: &NodeConfig,
+    location: &str,
+    replicas: u32,
+    kubernetes_version: &str,
+) -> Value {
+    let template = template_name(cluster_name, pool);
+    json!({
+        "apiVersion": CLUSTER_API,
+        "kind": "MachineDeployment",
+        "metadata": {
+            "name": name,
+            "labels": { "cluster.x-k8s.io/cluster-name": cluster_name },
+        },
+        "spec": {
+            "clusterName": cluster_name,
+            "replicas": replicas,
+            "selector": { "matchLabels": {} },
+            "template": {
+                "spec": {
+                    "clusterName": cluster_name,
+                    "version": kubernetes_version,
+                    "failureDomain": location,
+                    "bootstrap": {
+                        "configRef": {
+                            "apiVersion": BOOTSTRAP_API,
+                            "kind": "TalosConfigTemplate",
+                            "name": template,
+                        },
+                    },
+                    "infrastructureRef": {
+                        "apiVersion": INFRASTRUCTURE_API,
+                        "kind": "HCloudMachineTemplate",
+                        "name": template,
+                    },
+                },
+            },
+        },
+    })
+}
+
+fn template_name(cluster_name: &str, pool: &NodeConfig) -> String {
+    format!("{}-{}", cluster_name, pool.name)
+}
+
+/// Machine config patch with the node labels of a pool, if it has any
+fn labels_patch(pool: &NodeConfig) -> Result<Option<String>> {
+    if pool.labels.is_empty() {
+        return Ok(None);
+    }
+    let labels: BTreeMap<&String, &String> = pool.labels.iter().collect();
+    let patch = json!({ "machine": { "nodeLabels": labels } });
+    Ok(Some(serde_yaml::to_string(&patch)?))
+}
+
+/// Contents of a `talos.config_patches` entry, which like for talosctl is
+/// inline YAML or `@` and a file path
+fn read_patch(patch: &str) -> Result<String> {
+    match patch.strip_prefix('@') {
+        Some(path) => {
+            std::fs::read_to_string(path).context(format!("Failed to read config patch {}", path))
+        }
+        None => Ok(patch.to_string()),
+    }
+}
+
+/// `v`-prefixed Kubernetes version, once aliases are resolved
+fn kubernetes_version(version: &str) -> Result<String> {
+    let version = version.trim_start_matches('v');
+    if version.split('.').count() != 3 {
+        anyhow::bail!(Failure::config(format!(
+            "Kubernetes version {} is an alias; create the cluster first so it is pinned",
+            version
+        )));
+    }
+    Ok(format!("v{}", version))
+}
+
+/// Minor release of a Talos version, as the Talos providers take it
+fn talos_minor(version: &str) -> Result<String> {
+    let parts: Vec<&str> = version.split('.').collect();
+    if version == "latest" || parts.len() < 2 {
+        anyhow::bail!(Failure::config(format!(
+            "Talos version {} is an alias; create the cluster first so it is pinned",
+            version
+        )));
+    }
+    Ok(format!("{}.{}", parts[0], parts[1]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TopologyConfig;
+
+    fn parse(output: &str) -> Vec<serde_yaml::Value> {
+        output
+            .split("---\n")
+            .skip(1)
+            .map(|doc| serde_yaml::from_str(doc).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_capi() {
+        let mut config = ClusterConfig::example();
+        config.workers[0].labels =
+            std::collections::HashMap::from([("tier".to_string(), "web".to_string())]);
+        config.workers[0].topology = Some(TopologyConfig {
+            locations: vec!["fsn1".to_string(), "nbg1".to_string()],
+        });
+        let output = capi(&config, Some("https://203.0.113.10:6443")).unwrap();
+        assert!(output.starts_with("# Cluster API manifests exported by oxide"));
+
+        let documents = parse(&output);
+        let kinds: Vec<&str> = documents
+            .iter()
+            .map(|doc| doc["kind"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                "Cluster",
+                "HetznerCluster",
+                "HCloudMachineTemplate",
+                "TalosControlPlane",
+                "HCloudMachineTemplate",
+                "TalosConfigTemplate",
+                "MachineDeployment",
+                "MachineDeployment",
+            ]
+        );
+
+        let hetzner = &documents[1]["spec"];
+        assert_eq!(hetzner["controlPlaneEndpoint"]["host"], "203.0.113.10");
+        assert_eq!(hetzner["controlPlaneLoadBalancer"]["enabled"], false);
+        assert_eq!(
+            hetzner["sshKeys"]["hcloud"][0]["name"],
+            "talos-cluster-oxide"
+        );
+
+        let control_plane = &documents[3]["spec"];
+        assert_eq!(control_plane["version"], "v1.30.0");
+        assert_eq!(control_plane["replicas"], 3);
+        assert_eq!(
+            control_plane["controlPlaneConfig"]["controlplane"]["talosVersion"],
+            "v1.7"
+        );
+
+        let patches = &documents[5]["spec"]["template"]["spec"]["strategicPatches"];
+        let labels: serde_yaml::Value = serde_yaml::from_str(patches[0].as_str().unwrap()).unwrap();
+        assert_eq!(labels["machine"]["nodeLabels"]["tier"], "web");
+
+        // Three nodes round-robin over two locations
+        let replicas: Vec<(&str, u64)> = documents[6..]
+            .iter()
+            .map(|doc| {
+                (
+                    doc["spec"]["template"]["spec"]["failureDomain"]
+                        .as_str()
+                        .unwrap(),
+                    doc["spec"]["replicas"].as_u64().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(replicas, [("fsn1", 2), ("nbg1", 1)]);
+        assert_eq!(
+            documents[6]["metadata"]["name"],
+            "talos-cluster-worker-fsn1"
+        );
+
+        // Without an endpoint CAPH creates a load balancer
+        let documents = parse(&capi(&config, None).unwrap());
+        assert_eq!(
+            documents[1]["spec"]["controlPlaneLoadBalancer"]["region"],
+            "nbg1"
+        );
+    }
+
+    #[test]
+    fn test_versions() {
+        assert_eq!(kubernetes_version("1.34.1").unwrap(), "v1.34.1");
+        assert_eq!(kubernetes_version("v1.34.1").unwrap(), "v1.34.1");
+        assert!(kubernetes_version("1.34").is_err());
+        assert!(kubernetes_version("latest").is_err());
+        assert_eq!(talos_minor("v1.11.2").unwrap(), "v1.11");
+        assert!(talos_minor("latest").is_err());
+    }
+}
@@ -1,25 +1,92 @@
 /// Hetzner Cloud API client
 use anyhow::{Context, Result};
-use reqwest::{header, Client};
+use chrono::SecondsFormat;
+use reqwest::{header, Certificate, Client, Proxy};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tracing::{debug, warn};
 
 use super::models::*;
+use crate::audit;
+use crate::config::{ClusterConfig, HttpConfig};
+use crate::exit::Failure;
 
 const HCLOUD_API_BASE: &str = "https://api.hetzner.cloud/v1";
 
+/// Overrides the API base URL, e.g. for a proxy or a mock server
+const API_BASE_ENV: &str = "HCLOUD_API_BASE";
+
+/// How long a cached list response is served before it is fetched again
+const CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// Retries of a rate-limited request, and the first delay (doubled each time)
+const RATE_LIMIT_RETRIES: u32 = 5;
+const RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Delay between two polls of an action
+const ACTION_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Minimum time between two requests refreshing the waited-for actions
+const ACTION_POLL_GAP: Duration = Duration::from_secs(1);
+
+/// Actions refreshed by one request (the API's page size limit)
+const ACTIONS_PER_POLL: usize = 50;
+
+/// Seconds to wait for a server snapshot, which copies the whole disk
+const SNAPSHOT_TIMEOUT_SECS: u64 = 3600;
+
+/// `delay` stretched by up to half, so requests started together (like the
+/// actions of servers created at once) do not poll in lockstep
+fn jittered(delay: Duration) -> Duration {
+    use rand::Rng;
+    delay.mul_f64(rand::thread_rng().gen_range(1.0..1.5))
+}
+
+/// Actions being waited for, shared by all clones of a client
+#[derive(Default)]
+struct PendingActions {
+    /// Actions by ID, `None` until they finished
+    waiting: Mutex<HashMap<u64, Option<Action>>>,
+    /// Held while polling, with the time of the last poll
+    poll: tokio::sync::Mutex<Option<Instant>>,
+}
+
+/// Permission of an API token
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenAccess {
+    ReadOnly,
+    ReadWrite,
+}
+
 /// Main Hetzner Cloud API client
 #[derive(Clone)]
 pub struct HetznerCloudClient {
     client: Client,
     #[allow(dead_code)]
     api_token: String,
+    api_base: String,
+    /// List responses by endpoint, shared by all clones of the client
+    cache: Arc<Mutex<HashMap<String, (Instant, serde_json::Value)>>>,
+    actions: Arc<PendingActions>,
 }
 
 impl HetznerCloudClient {
     /// Create a new Hetzner Cloud API client
     pub fn new(api_token: String) -> Result<Self> {
+        Self::with_http_config(api_token, &HttpConfig::default())
+    }
+
+    /// Create a client for a cluster, with its token and HTTP settings
+    pub fn from_config(config: &ClusterConfig) -> Result<Self> {
+        Self::with_http_config(config.get_hcloud_token()?, &config.hcloud.http)
+    }
+
+    /// Create a client using a proxy, extra root certificates, the request
+    /// timeout and the connection pool size from `http`
+    pub fn with_http_config(api_token: String, http: &HttpConfig) -> Result<Self> {
         let mut headers = header::HeaderMap::new();
         headers.insert(
             header::AUTHORIZATION,
@@ -31,74 +98,315 @@ impl HetznerCloudClient {
             header::HeaderValue::from_static("application/json"),
         );
 
-        let client = Client::builder()
+        let mut builder = Client::builder()
             .default_headers(headers)
-            .timeout(std::time::Duration::from_secs(30))
-            .build()
-            .context("Failed to create HTTP client")?;
+            .timeout(Duration::from_secs(http.timeout));
+        if let Some(proxy) = &http.proxy {
+            builder = builder
+                .proxy(Proxy::all(proxy).context(format!("Invalid hcloud.http.proxy {}", proxy))?);
+        }
+        for path in &http.ca_certificates {
+            let pem = std::fs::read(path)
+                .context(format!("Failed to read CA certificate {}", path.display()))?;
+            let certificates = Certificate::from_pem_bundle(&pem)
+                .context(format!("Invalid CA certificate {}", path.display()))?;
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        if let Some(idle) = http.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(idle);
+        }
+        let client = builder.build().context("Failed to create HTTP client")?;
+
+        let api_base = std::env::var(API_BASE_ENV)
+            .ok()
+            .filter(|base| !base.is_empty())
+            .unwrap_or_else(|| HCLOUD_API_BASE.to_string());
 
-        Ok(Self { client, api_token })
+        Ok(Self {
+            client,
+            api_token,
+            api_base: api_base.trim_end_matches('/').to_string(),
+            cache: Arc::default(),
+            actions: Arc::default(),
+        })
+    }
+
+    /// Send requests to another API base URL
+    #[cfg(test)]
+    pub fn with_api_base(mut self, api_base: &str) -> Self {
+        self.api_base = api_base.trim_end_matches('/').to_string();
+        self
     }
 
     /// Make a GET request to the API
+    #[tracing::instrument(name = "hcloud.request", skip(self), fields(method = "GET", status))]
     pub(crate) async fn get<T: DeserializeOwned>(&self, endpoint: &str) -> Result<T> {
-        let url = format!("{}/{}", HCLOUD_API_BASE, endpoint);
+        let url = format!("{}/{}", self.api_base, endpoint);
         debug!("GET {}", url);
 
         let response = self
-            .client
-            .get(&url)
-            .send()
+            .send(|| self.client.get(&url))
             .await
             .context("Failed to send GET request")?;
 
         self.handle_response(response).await
     }
 
+    /// Send a request, backing off and retrying while the API answers with
+    /// `429 Too Many Requests`
+    ///
+    /// A rate-limited request was not processed, so retrying a POST does not
+    /// create anything twice.
+    async fn send(
+        &self,
+        request: impl Fn() -> reqwest::RequestBuilder,
+    ) -> reqwest::Result<reqwest::Response> {
+        let mut delay = RATE_LIMIT_BACKOFF;
+        for _ in 0..RATE_LIMIT_RETRIES {
+            let response = request().send().await?;
+            if response.status() != reqwest::StatusCode::TOO_MANY_REQUESTS {
+                return Ok(response);
+            }
+            warn!(
+                "⚠️  Hetzner Cloud API rate limit reached, retrying in {}s",
+                delay.as_secs()
+            );
+            tokio::time::sleep(jittered(delay)).await;
+            delay = (delay * 2).min(Duration::from_secs(60));
+        }
+        request().send().await
+    }
+
+    /// Make a GET request, answering repeats within [`CACHE_TTL`] from memory
+    ///
+    /// For the list endpoints a single command looks up many times (servers,
+    /// networks, firewalls, SSH keys). Any POST or DELETE through this client
+    /// or one of its clones drops the cache, so a list after a change always
+    /// sees it.
+    pub(crate) async fn get_cached<T: DeserializeOwned>(&self, endpoint: &str) -> Result<T> {
+        let cached = self
+            .cache
+            .lock()
+            .unwrap()
+            .get(endpoint)
+            .filter(|(fetched, _)| fetched.elapsed() < CACHE_TTL)
+            .map(|(_, value)| value.clone());
+        let value = match cached {
+            Some(value) => {
+                debug!("GET {}/{} (cached)", self.api_base, endpoint);
+                value
+            }
+            None => {
+                let value: serde_json::Value = self.get(endpoint).await?;
+                self.cache
+                    .lock()
+                    .unwrap()
+                    .insert(endpoint.to_string(), (Instant::now(), value.clone()));
+                value
+            }
+        };
+        serde_json::from_value(value).context("Failed to parse API response")
+    }
+
+    /// Forget all cached list responses
+    fn invalidate_cache(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+
     /// Make a POST request to the API
+    #[tracing::instrument(
+        name = "hcloud.request",
+        skip(self, body),
+        fields(method = "POST", status)
+    )]
     pub(crate) async fn post<T: Serialize, R: DeserializeOwned>(
         &self,
         endpoint: &str,
         body: &T,
     ) -> Result<R> {
-        let url = format!("{}/{}", HCLOUD_API_BASE, endpoint);
+        let url = format!("{}/{}", self.api_base, endpoint);
         debug!("POST {}", url);
+        self.invalidate_cache();
 
-        let response = self
-            .client
-            .post(&url)
-            .json(body)
-            .send()
-            .await
-            .context("Failed to send POST request")?;
+        let result = async {
+            let response = self
+                .send(|| self.client.post(&url).json(body))
+                .await
+                .context("Failed to send POST request")?;
 
-        self.handle_response(response).await
+            self.handle_response(response).await
+        }
+        .await;
+
+        // Request bodies are not recorded: they can contain user_data secrets
+        audit::record("hcloud.post", endpoint, &[], &result);
+        result
+    }
+
+    /// Make a PUT request to the API, e.g. to change labels
+    #[tracing::instrument(
+        name = "hcloud.request",
+        skip(self, body),
+        fields(method = "PUT", status)
+    )]
+    pub(crate) async fn put<T: Serialize, R: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        body: &T,
+    ) -> Result<R> {
+        let url = format!("{}/{}", self.api_base, endpoint);
+        debug!("PUT {}", url);
+        self.invalidate_cache();
+
+        let result = async {
+            let response = self
+                .send(|| self.client.put(&url).json(body))
+                .await
+                .context("Failed to send PUT request")?;
+
+            self.handle_response(response).await
+        }
+        .await;
+
+        audit::record("hcloud.put", endpoint, &[], &result);
+        result
     }
 
     /// Make a DELETE request to the API
+    #[tracing::instrument(name = "hcloud.request", skip(self), fields(method = "DELETE", status))]
     pub(crate) async fn delete(&self, endpoint: &str) -> Result<()> {
-        let url = format!("{}/{}", HCLOUD_API_BASE, endpoint);
+        let url = format!("{}/{}", self.api_base, endpoint);
         debug!("DELETE {}", url);
+        self.invalidate_cache();
 
-        let response = self
-            .client
-            .delete(&url)
-            .send()
-            .await
-            .context("Failed to send DELETE request")?;
+        let result = async {
+            let response = self
+                .send(|| self.client.delete(&url))
+                .await
+                .context("Failed to send DELETE request")?;
+            tracing::Span::current().record("status", response.status().as_u16());
 
-        if response.status().is_success() {
-            Ok(())
-        } else {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_default();
-            anyhow::bail!("API request failed with status {}: {}", status, error_text)
+            if response.status().is_success() {
+                Ok(())
+            } else {
+                let status = response.status();
+                let error_text = response.text().await.unwrap_or_default();
+                anyhow::bail!(Failure::provider(format!(
+                    "API request failed with status {}: {}",
+                    status, error_text
+                )))
+            }
+        }
+        .await;
+
+        audit::record("hcloud.delete", endpoint, &[], &result);
+        result
+    }
+
+    /// Make a DELETE request to an endpoint that answers with a body, like
+    /// the action of a server deletion
+    #[tracing::instrument(name = "hcloud.request", skip(self), fields(method = "DELETE", status))]
+    pub(crate) async fn delete_with_response<R: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+    ) -> Result<R> {
+        let url = format!("{}/{}", self.api_base, endpoint);
+        debug!("DELETE {}", url);
+        self.invalidate_cache();
+
+        let result = async {
+            let response = self
+                .send(|| self.client.delete(&url))
+                .await
+                .context("Failed to send DELETE request")?;
+
+            self.handle_response(response).await
+        }
+        .await;
+
+        audit::record("hcloud.delete", endpoint, &[], &result);
+        result
+    }
+
+    /// Find out what the API token may do, without changing anything
+    ///
+    /// Lists one server, then deletes an SSH key ID that is never assigned: a
+    /// read-write token gets `not_found` back, while a read-only token is
+    /// rejected with `forbidden` before the key is looked up.
+    pub async fn token_access(&self) -> Result<TokenAccess> {
+        let (status, code) = self
+            .probe(
+                self.client
+                    .get(format!("{}/servers?per_page=1", self.api_base)),
+            )
+            .await?;
+        if status == reqwest::StatusCode::UNAUTHORIZED {
+            anyhow::bail!(
+                "The Hetzner Cloud API token was rejected ({}). It may have been revoked; \
+                create a new one in the Cloud Console under Security → API tokens.",
+                code.as_deref().unwrap_or("unauthorized")
+            );
+        }
+        if !status.is_success() {
+            anyhow::bail!(
+                "Listing servers failed with status {} ({}); the token needs at least read access",
+                status,
+                code.as_deref().unwrap_or("unknown error")
+            );
+        }
+
+        let (status, code) = self
+            .probe(self.client.delete(format!("{}/ssh_keys/0", self.api_base)))
+            .await?;
+        match status {
+            reqwest::StatusCode::FORBIDDEN => Ok(TokenAccess::ReadOnly),
+            reqwest::StatusCode::NOT_FOUND => Ok(TokenAccess::ReadWrite),
+            status => anyhow::bail!(
+                "Checking write access failed with status {} ({})",
+                status,
+                code.as_deref().unwrap_or("unknown error")
+            ),
+        }
+    }
+
+    /// Fail early if the token cannot make the changes `command` needs,
+    /// instead of deep inside server creation
+    pub async fn require_write_access(&self, command: &str) -> Result<()> {
+        if self.token_access().await? == TokenAccess::ReadOnly {
+            anyhow::bail!(
+                "The Hetzner Cloud API token is read-only, but `oxide {}` creates or deletes resources.\n\
+                Create a token with \"Read & Write\" permission in the Cloud Console under Security → API tokens.",
+                command
+            );
         }
+        debug!("API token has read-write access");
+        Ok(())
+    }
+
+    /// Send a request outside the audit log, returning its status and error code
+    async fn probe(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<(reqwest::StatusCode, Option<String>)> {
+        let response = request
+            .send()
+            .await
+            .context("Failed to reach the Hetzner Cloud API")?;
+        let status = response.status();
+        let code = response
+            .json::<ErrorResponse>()
+            .await
+            .ok()
+            .map(|error| error.error.code);
+        Ok((status, code))
     }
 
     /// Handle API response, checking for errors
     async fn handle_response<T: DeserializeOwned>(&self, response: reqwest::Response) -> Result<T> {
         let status = response.status();
+        tracing::Span::current().record("status", status.as_u16());
 
         if status.is_success() {
             response
@@ -110,20 +418,34 @@ impl HetznerCloudClient {
 
             // Try to parse as error response
             if let Ok(error_response) = serde_json::from_str::<ErrorResponse>(&error_text) {
-                anyhow::bail!(
+                anyhow::bail!(Failure::provider(format!(
                     "API error: {} - {}",
-                    error_response.error.code,
-                    error_response.error.message
-                );
+                    error_response.error.code, error_response.error.message
+                )));
             }
 
-            anyhow::bail!("API request failed with status {}: {}", status, error_text)
+            anyhow::bail!(Failure::provider(format!(
+                "API request failed with status {}: {}",
+                status, error_text
+            )))
         }
     }
 
+    /// List all locations
+    pub async fn list_locations(&self) -> Result<Vec<Location>> {
+        let response: LocationListResponse = self.get_cached("locations").await?;
+        Ok(response.locations)
+    }
+
+    /// List all server types
+    pub async fn list_server_types(&self) -> Result<Vec<ServerType>> {
+        let response: ServerTypeListResponse = self.get_cached("server_types?per_page=50").await?;
+        Ok(response.server_types)
+    }
+
     /// List all servers
     pub async fn list_servers(&self) -> Result<Vec<Server>> {
-        let response: ServerListResponse = self.get("servers").await?;
+        let response: ServerListResponse = self.get_cached("servers").await?;
         Ok(response.servers)
     }
 
@@ -137,6 +459,43 @@ impl HetznerCloudClient {
         Ok(response.server)
     }
 
+    /// Get metrics of a server for the given types and time range
+    pub async fn get_server_metrics(
+        &self,
+        server_id: u64,
+        types: &[MetricType],
+        range: MetricsRange,
+    ) -> Result<ServerMetrics> {
+        let types: Vec<&str> = types.iter().map(MetricType::as_str).collect();
+        let mut endpoint = format!(
+            "servers/{}/metrics?type={}&start={}&end={}",
+            server_id,
+            types.join(","),
+            range.start.to_rfc3339_opts(SecondsFormat::Secs, true),
+            range.end.to_rfc3339_opts(SecondsFormat::Secs, true)
+        );
+        if let Some(step) = range.step {
+            endpoint.push_str(&format!("&step={}", step));
+        }
+        let response: ServerMetricsResponse = self.get(&endpoint).await?;
+        Ok(response.metrics)
+    }
+
+    /// List the most recent actions of a server, newest first
+    pub async fn list_server_actions(&self, server_id: u64) -> Result<Vec<Action>> {
+        #[derive(serde::Deserialize)]
+        struct Response {
+            actions: Vec<Action>,
+        }
+        let response: Response = self
+            .get(&format!(
+                "servers/{}/actions?sort=started:desc&per_page=50",
+                server_id
+            ))
+            .await?;
+        Ok(response.actions)
+    }
+
     /// Create a new server
     pub async fn create_server(
         &self,
@@ -146,74 +505,219 @@ impl HetznerCloudClient {
     }
 
     /// Delete a server
-    pub async fn delete_server(&self, server_id: u64) -> Result<()> {
-        self.delete(&format!("servers/{}", server_id)).await
+    pub async fn delete_server(&self, server_id: u64) -> Result<Action> {
+        let response: ActionResponse = self
+            .delete_with_response(&format!("servers/{}", server_id))
+            .await?;
+        Ok(response.action)
     }
 
     /// Power on a server
-    #[allow(dead_code)]
     pub async fn power_on_server(&self, server_id: u64) -> Result<Action> {
+        self.server_action(server_id, "poweron", &serde_json::json!({}))
+            .await
+    }
+
+    /// Cut power to a server (equivalent to pulling the plug)
+    pub async fn power_off_server(&self, server_id: u64) -> Result<Action> {
+        self.server_action(server_id, "poweroff", &serde_json::json!({}))
+            .await
+    }
+
+    /// Hard-reset a server (equivalent to pressing the reset button)
+    pub async fn reset_server(&self, server_id: u64) -> Result<Action> {
+        self.server_action(server_id, "reset", &serde_json::json!({}))
+            .await
+    }
+
+    /// Boot a server into the Linux rescue system on its next restart
+    pub async fn enable_rescue(&self, server_id: u64, ssh_key_ids: &[u64]) -> Result<Action> {
+        self.server_action(
+            server_id,
+            "enable_rescue",
+            &serde_json::json!({
+                "type": "linux64",
+                "ssh_keys": ssh_key_ids,
+            }),
+        )
+        .await
+    }
+
+    /// Request a graceful ACPI shutdown of a server
+    pub async fn shutdown_server(&self, server_id: u64) -> Result<Action> {
+        self.server_action(server_id, "shutdown", &serde_json::json!({}))
+            .await
+    }
+
+    /// Enable or disable delete protection of a server
+    ///
+    /// Hetzner requires rebuild protection to match, so both are set.
+    pub async fn change_server_protection(&self, server_id: u64, delete: bool) -> Result<Action> {
+        self.server_action(
+            server_id,
+            "change_protection",
+            &serde_json::json!({ "delete": delete, "rebuild": delete }),
+        )
+        .await
+    }
+
+    /// Change the type of a stopped server
+    ///
+    /// With `upgrade_disk` false the disk keeps its size, which allows the
+    /// server to be downgraded again later.
+    pub async fn change_server_type(
+        &self,
+        server_id: u64,
+        server_type: &str,
+        upgrade_disk: bool,
+    ) -> Result<Action> {
+        self.server_action(
+            server_id,
+            "change_type",
+            &serde_json::json!({
+                "server_type": server_type,
+                "upgrade_disk": upgrade_disk,
+            }),
+        )
+        .await
+    }
+
+    /// Set the reverse DNS (PTR) record for one of a server's public IPs
+    pub async fn change_dns_ptr(&self, server_id: u64, ip: &str, dns_ptr: &str) -> Result<Action> {
+        self.server_action(
+            server_id,
+            "change_dns_ptr",
+            &serde_json::json!({
+                "ip": ip,
+                "dns_ptr": dns_ptr,
+            }),
+        )
+        .await
+    }
+
+    /// Trigger a server action and return the resulting action
+    async fn server_action<T: Serialize>(
+        &self,
+        server_id: u64,
+        action: &str,
+        body: &T,
+    ) -> Result<Action> {
         let response: ActionResponse = self
-            .post(
-                &format!("servers/{}/actions/poweron", server_id),
-                &serde_json::json!({}),
-            )
+            .post(&format!("servers/{}/actions/{}", server_id, action), body)
             .await?;
         Ok(response.action)
     }
 
     /// Wait for an action to complete
+    ///
+    /// Every action waited for through this client or one of its clones is
+    /// polled together with a single `GET /actions?id=...`, so creating many
+    /// servers at once does not poll each of their actions separately.
+    #[tracing::instrument(skip(self))]
     pub async fn wait_for_action(&self, action_id: u64, timeout_secs: u64) -> Result<Action> {
-        use tokio::time::{sleep, Duration};
-
-        let start = std::time::Instant::now();
+        let start = Instant::now();
         let timeout = Duration::from_secs(timeout_secs);
 
-        loop {
-            let action = self.get_action(action_id).await?;
-
-            match action.status.as_str() {
-                "success" => return Ok(action),
-                "error" => {
+        self.actions.waiting.lock().unwrap().insert(action_id, None);
+        let result = loop {
+            match self.poll_actions(action_id, start).await {
+                Ok(Some(action)) if action.status == "success" => break Ok(action),
+                Ok(Some(action)) => {
                     let error_msg = action
                         .error
                         .map(|e| format!("{}: {}", e.code, e.message))
                         .unwrap_or_else(|| "Unknown error".to_string());
-                    anyhow::bail!("Action {} failed: {}", action_id, error_msg);
+                    break Err(anyhow::anyhow!(Failure::provider(format!(
+                        "Action {} failed: {}",
+                        action_id, error_msg
+                    ))));
                 }
-                "running" => {
-                    if start.elapsed() > timeout {
-                        anyhow::bail!(
-                            "Action {} timed out after {} seconds",
-                            action_id,
-                            timeout_secs
-                        );
+                Ok(None) => {}
+                Err(e) => break Err(e),
+            }
+            if start.elapsed() > timeout {
+                break Err(anyhow::anyhow!(Failure::timeout(format!(
+                    "Action {} timed out after {} seconds",
+                    action_id, timeout_secs
+                ))));
+            }
+            tokio::time::sleep(jittered(ACTION_POLL_INTERVAL)).await;
+        };
+        self.actions.waiting.lock().unwrap().remove(&action_id);
+        result
+    }
+
+    /// Refresh all waited-for actions unless that happened just now, and
+    /// return `action_id` once it has finished
+    ///
+    /// An action waited for since `since` that no poll has covered yet is
+    /// refreshed right away, so waiting for one action after another is not
+    /// slowed down.
+    async fn poll_actions(&self, action_id: u64, since: Instant) -> Result<Option<Action>> {
+        let finished = |id: u64| {
+            self.actions
+                .waiting
+                .lock()
+                .unwrap()
+                .get(&id)
+                .cloned()
+                .flatten()
+        };
+
+        let mut last_poll = self.actions.poll.lock().await;
+        let due =
+            last_poll.is_none_or(|polled| polled < since || polled.elapsed() >= ACTION_POLL_GAP);
+        if !due || finished(action_id).is_some() {
+            return Ok(finished(action_id));
+        }
+
+        let running: Vec<u64> = self
+            .actions
+            .waiting
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, action)| action.is_none())
+            .map(|(id, _)| *id)
+            .collect();
+        let polled = Instant::now();
+        for ids in running.chunks(ACTIONS_PER_POLL) {
+            let filter: Vec<String> = ids.iter().map(|id| format!("id={}", id)).collect();
+            let response: ActionListResponse = self
+                .get(&format!(
+                    "actions?{}&per_page={}",
+                    filter.join("&"),
+                    ACTIONS_PER_POLL
+                ))
+                .await?;
+
+            let mut waiting = self.actions.waiting.lock().unwrap();
+            for action in response.actions {
+                match action.status.as_str() {
+                    "success" | "error" => {
+                        if let Some(entry) = waiting.get_mut(&action.id) {
+                            *entry = Some(action);
+                        }
                     }
-                    debug!("Action {} progress: {}%", action_id, action.progress);
-                    sleep(Duration::from_secs(2)).await;
-                }
-                status => {
-                    warn!("Unknown action status: {}", status);
-                    sleep(Duration::from_secs(2)).await;
+                    "running" => {
+                        debug!("Action {} progress: {}%", action.id, action.progress)
+                    }
+                    status => warn!("Unknown action status: {}", status),
                 }
             }
         }
-    }
+        *last_poll = Some(polled);
 
-    /// Get action status
-    pub async fn get_action(&self, action_id: u64) -> Result<Action> {
-        let response: ActionResponse = self.get(&format!("actions/{}", action_id)).await?;
-        Ok(response.action)
+        Ok(finished(action_id))
     }
 
     /// List all networks
     pub async fn list_networks(&self) -> Result<Vec<Network>> {
-        let response: NetworkListResponse = self.get("networks").await?;
+        let response: NetworkListResponse = self.get_cached("networks").await?;
         Ok(response.networks)
     }
 
     /// Get network by ID
-    #[allow(dead_code)]
     pub async fn get_network(&self, network_id: u64) -> Result<Network> {
         #[derive(serde::Deserialize)]
         struct Response {
@@ -229,13 +733,93 @@ impl HetznerCloudClient {
         Ok(response.network)
     }
 
+    /// Replace the labels of a network
+    pub async fn update_network_labels(
+        &self,
+        network_id: u64,
+        labels: &std::collections::HashMap<String, String>,
+    ) -> Result<Network> {
+        let response: CreateNetworkResponse = self
+            .put(
+                &format!("networks/{}", network_id),
+                &serde_json::json!({ "labels": labels }),
+            )
+            .await?;
+        Ok(response.network)
+    }
+
     /// Delete a network
     pub async fn delete_network(&self, network_id: u64) -> Result<()> {
         self.delete(&format!("networks/{}", network_id)).await
     }
 
+    /// Add a subnet to an existing network
+    pub async fn add_subnet(&self, network_id: u64, subnet: &SubnetRequest) -> Result<Action> {
+        let response: ActionResponse = self
+            .post(
+                &format!("networks/{}/actions/add_subnet", network_id),
+                subnet,
+            )
+            .await?;
+        Ok(response.action)
+    }
+
+    /// Enable or disable delete protection of a network
+    pub async fn change_network_protection(&self, network_id: u64, delete: bool) -> Result<Action> {
+        let response: ActionResponse = self
+            .post(
+                &format!("networks/{}/actions/change_protection", network_id),
+                &serde_json::json!({ "delete": delete }),
+            )
+            .await?;
+        Ok(response.action)
+    }
+
+    /// Add a route to a network, sending `destination` to `gateway`
+    pub async fn add_route(
+        &self,
+        network_id: u64,
+        destination: &str,
+        gateway: &str,
+    ) -> Result<Action> {
+        self.route_action(network_id, "add_route", destination, gateway)
+            .await
+    }
+
+    /// Delete a route from a network
+    ///
+    /// Hetzner identifies routes by both destination and gateway.
+    pub async fn delete_route(
+        &self,
+        network_id: u64,
+        destination: &str,
+        gateway: &str,
+    ) -> Result<Action> {
+        self.route_action(network_id, "delete_route", destination, gateway)
+            .await
+    }
+
+    async fn route_action(
+        &self,
+        network_id: u64,
+        command: &str,
+        destination: &str,
+        gateway: &str,
+    ) -> Result<Action> {
+        let request = RouteRequest {
+            destination: destination.to_string(),
+            gateway: gateway.to_string(),
+        };
+        let response: ActionResponse = self
+            .post(
+                &format!("networks/{}/actions/{}", network_id, command),
+                &request,
+            )
+            .await?;
+        Ok(response.action)
+    }
+
     /// Attach server to network
-    #[allow(dead_code)]
     pub async fn attach_to_network(
         &self,
         server_id: u64,
@@ -263,24 +847,189 @@ impl HetznerCloudClient {
         Ok(response.action)
     }
 
+    /// Detach server from network
+    pub async fn detach_from_network(&self, server_id: u64, network_id: u64) -> Result<Action> {
+        let response: ActionResponse = self
+            .post(
+                &format!("servers/{}/actions/detach_from_network", server_id),
+                &serde_json::json!({ "network": network_id }),
+            )
+            .await?;
+        Ok(response.action)
+    }
+
+    /// List all volumes
+    pub async fn list_volumes(&self) -> Result<Vec<Volume>> {
+        let response: VolumeListResponse = self.get("volumes").await?;
+        Ok(response.volumes)
+    }
+
+    /// List all floating IPs
+    pub async fn list_floating_ips(&self) -> Result<Vec<FloatingIp>> {
+        let response: FloatingIpListResponse = self.get("floating_ips").await?;
+        Ok(response.floating_ips)
+    }
+
+    /// Create a floating IP, optionally assigned to a server right away
+    pub async fn create_floating_ip(
+        &self,
+        request: &CreateFloatingIpRequest,
+    ) -> Result<FloatingIp> {
+        let response: CreateFloatingIpResponse = self.post("floating_ips", request).await?;
+        if let Some(action) = response.action {
+            self.wait_for_action(action.id, 60).await?;
+        }
+        Ok(response.floating_ip)
+    }
+
+    /// Assign a floating IP to a server, moving it off its current one
+    pub async fn assign_floating_ip(&self, floating_ip_id: u64, server_id: u64) -> Result<Action> {
+        let response: ActionResponse = self
+            .post(
+                &format!("floating_ips/{}/actions/assign", floating_ip_id),
+                &serde_json::json!({ "server": server_id }),
+            )
+            .await?;
+        Ok(response.action)
+    }
+
+    /// Unassign a floating IP from its server
+    pub async fn unassign_floating_ip(&self, floating_ip_id: u64) -> Result<Action> {
+        let response: ActionResponse = self
+            .post(
+                &format!("floating_ips/{}/actions/unassign", floating_ip_id),
+                &serde_json::json!({}),
+            )
+            .await?;
+        Ok(response.action)
+    }
+
+    /// Delete a floating IP
+    pub async fn delete_floating_ip(&self, floating_ip_id: u64) -> Result<()> {
+        self.delete(&format!("floating_ips/{}", floating_ip_id))
+            .await
+    }
+
+    /// List all primary IPs
+    pub async fn list_primary_ips(&self) -> Result<Vec<PrimaryIp>> {
+        let response: PrimaryIpListResponse = self.get("primary_ips").await?;
+        Ok(response.primary_ips)
+    }
+
+    /// Unassign a primary IP from its powered-off server
+    pub async fn unassign_primary_ip(&self, primary_ip_id: u64) -> Result<Action> {
+        let response: ActionResponse = self
+            .post(
+                &format!("primary_ips/{}/actions/unassign", primary_ip_id),
+                &serde_json::json!({}),
+            )
+            .await?;
+        Ok(response.action)
+    }
+
+    /// Delete an unassigned primary IP
+    pub async fn delete_primary_ip(&self, primary_ip_id: u64) -> Result<()> {
+        self.delete(&format!("primary_ips/{}", primary_ip_id)).await
+    }
+
+    /// Change the auto-delete flag or the labels of a primary IP
+    pub async fn update_primary_ip(
+        &self,
+        primary_ip_id: u64,
+        request: &UpdatePrimaryIpRequest,
+    ) -> Result<PrimaryIp> {
+        let response: PrimaryIpResponse = self
+            .put(&format!("primary_ips/{}", primary_ip_id), request)
+            .await?;
+        Ok(response.primary_ip)
+    }
+
+    /// Snapshot the disk of a server and wait until the image is available
+    ///
+    /// Hetzner copies the whole disk, which takes several minutes for large
+    /// ones; the server should be stopped for a consistent image.
+    pub async fn create_snapshot(
+        &self,
+        server_id: u64,
+        description: &str,
+        labels: HashMap<String, String>,
+    ) -> Result<Image> {
+        let response: CreateImageResponse = self
+            .post(
+                &format!("servers/{}/actions/create_image", server_id),
+                &serde_json::json!({
+                    "type": "snapshot",
+                    "description": description,
+                    "labels": labels,
+                }),
+            )
+            .await?;
+        self.wait_for_action(response.action.id, SNAPSHOT_TIMEOUT_SECS)
+            .await
+            .context("Snapshot action failed")?;
+        Ok(response.image)
+    }
+
+    /// List the snapshots carrying a label selector like `cluster=prod`
+    pub async fn list_snapshots(&self, label_selector: &str) -> Result<Vec<Image>> {
+        let response: ImageListResponse = self
+            .get(&format!(
+                "images?type=snapshot&label_selector={}",
+                label_selector
+            ))
+            .await?;
+        Ok(response.images)
+    }
+
+    /// Delete an image
+    pub async fn delete_image(&self, image_id: u64) -> Result<()> {
+        self.delete(&format!("images/{}", image_id)).await
+    }
+
+    /// List all load balancers
+    pub async fn list_load_balancers(&self) -> Result<Vec<LoadBalancer>> {
+        let response: LoadBalancerListResponse = self.get("load_balancers").await?;
+        Ok(response.load_balancers)
+    }
+
+    /// Delete a load balancer
+    pub async fn delete_load_balancer(&self, load_balancer_id: u64) -> Result<()> {
+        self.delete(&format!("load_balancers/{}", load_balancer_id))
+            .await
+    }
+
     /// List SSH keys
     #[allow(dead_code)]
     pub async fn list_ssh_keys(&self) -> Result<Vec<SSHKey>> {
-        let response: SSHKeyListResponse = self.get("ssh_keys").await?;
+        let response: SSHKeyListResponse = self.get_cached("ssh_keys").await?;
         Ok(response.ssh_keys)
     }
 
     /// Create SSH key
     #[allow(dead_code)]
-    pub async fn create_ssh_key(&self, name: String, public_key: String) -> Result<SSHKey> {
+    pub async fn create_ssh_key(
+        &self,
+        name: String,
+        public_key: String,
+        labels: std::collections::HashMap<String, String>,
+    ) -> Result<SSHKey> {
         #[derive(serde::Serialize)]
         struct Request {
             name: String,
             public_key: String,
+            labels: std::collections::HashMap<String, String>,
         }
 
-        let response: CreateSSHKeyResponse =
-            self.post("ssh_keys", &Request { name, public_key }).await?;
+        let response: CreateSSHKeyResponse = self
+            .post(
+                "ssh_keys",
+                &Request {
+                    name,
+                    public_key,
+                    labels,
+                },
+            )
+            .await?;
         Ok(response.ssh_key)
     }
 
@@ -310,6 +1059,46 @@ pub struct CreateServerRequest {
     pub automount: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub start_after_create: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_net: Option<PublicNetRequest>,
+}
+
+/// Public network options for a new server
+#[derive(Debug, Serialize)]
+pub struct PublicNetRequest {
+    pub enable_ipv4: bool,
+    pub enable_ipv6: bool,
+    /// Existing primary IPv4 to assign instead of a new one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipv4: Option<u64>,
+    /// Existing primary IPv6 to assign instead of a new one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipv6: Option<u64>,
+}
+
+/// Request structure for creating a floating IP
+#[derive(Debug, Serialize)]
+pub struct CreateFloatingIpRequest {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ip_type: IpType,
+    /// Location the IP is routed to while unassigned; required without `server`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub home_location: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<std::collections::HashMap<String, String>>,
+}
+
+/// Request structure for updating a primary IP
+#[derive(Debug, Default, Serialize)]
+pub struct UpdatePrimaryIpRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_delete: Option<bool>,
+    /// Replaces all labels of the IP
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<std::collections::HashMap<String, String>>,
 }
 
 /// Request structure for creating a network
@@ -332,6 +1121,9 @@ pub struct SubnetRequest {
     pub network_zone: String,
     #[serde(rename = "type")]
     pub subnet_type: String,
+    /// Robot vSwitch coupled to a subnet of type `vswitch`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vswitch_id: Option<u64>,
 }
 
 /// Request structure for creating a route
@@ -350,4 +1142,213 @@ mod tests {
         let result = HetznerCloudClient::new("test-token".to_string());
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_token_access() {
+        let mock = super::super::mock::MockHcloud::start().await;
+        let client = mock.client();
+        assert_eq!(client.token_access().await.unwrap(), TokenAccess::ReadWrite);
+        client.require_write_access("create").await.unwrap();
+        assert!(mock.state().ssh_keys.is_empty());
+        assert!(mock
+            .state()
+            .requests
+            .iter()
+            .all(|request| !request.starts_with("POST")));
+
+        mock.state().read_only = true;
+        assert_eq!(client.token_access().await.unwrap(), TokenAccess::ReadOnly);
+        let err = client.require_write_access("scale").await.unwrap_err();
+        assert!(err.to_string().contains("read-only"));
+    }
+
+    #[tokio::test]
+    async fn test_list_cache() {
+        let mock = super::super::mock::MockHcloud::start().await;
+        let client = mock.client();
+        let fetches = |mock: &super::super::mock::MockHcloud| {
+            mock.state()
+                .requests
+                .iter()
+                .filter(|request| *request == "GET /ssh_keys")
+                .count()
+        };
+
+        assert!(client.list_ssh_keys().await.unwrap().is_empty());
+        assert!(client.clone().list_ssh_keys().await.unwrap().is_empty());
+        assert_eq!(fetches(&mock), 1);
+
+        // A write through any clone is seen by the next list
+        client
+            .clone()
+            .create_ssh_key(
+                "prod".to_string(),
+                "ssh-ed25519 AAAA".to_string(),
+                Default::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(client.list_ssh_keys().await.unwrap().len(), 1);
+        assert_eq!(fetches(&mock), 2);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_backoff() {
+        let mock = super::super::mock::MockHcloud::start().await;
+        let client = mock.client();
+        mock.state().rate_limited = 1;
+
+        let key = client
+            .create_ssh_key(
+                "prod".to_string(),
+                "ssh-ed25519 AAAA".to_string(),
+                Default::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(key.name, "prod");
+        let state = mock.state();
+        assert_eq!(state.ssh_keys.len(), 1);
+        assert_eq!(state.requests, ["POST /ssh_keys", "POST /ssh_keys"]);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_actions_together() {
+        let mock = super::super::mock::MockHcloud::start().await;
+        let client = mock.client();
+        for id in [1, 2] {
+            mock.state().actions.insert(
+                id,
+                serde_json::json!({
+                    "id": id, "command": "create_server", "status": "running",
+                    "progress": 0, "started": "2024-01-01T00:00:00+00:00",
+                    "finished": null, "error": null
+                }),
+            );
+        }
+
+        let finish = async {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            for action in mock.state().actions.values_mut() {
+                action["status"] = "success".into();
+            }
+        };
+        let clone = client.clone();
+        let (first, second, ()) = tokio::join!(
+            client.wait_for_action(1, 30),
+            clone.wait_for_action(2, 30),
+            finish
+        );
+        assert_eq!(first.unwrap().id, 1);
+        assert_eq!(second.unwrap().id, 2);
+
+        // Each action was polled once on its own, after that both together
+        let polls: Vec<String> = mock
+            .state()
+            .requests
+            .iter()
+            .filter(|request| request.starts_with("GET /actions"))
+            .cloned()
+            .collect();
+        assert_eq!(polls.len(), 3, "{:?}", polls);
+        assert!(polls[2].contains("id=1&") && polls[2].contains("id=2&"));
+    }
+
+    #[test]
+    fn test_http_config() {
+        let http = HttpConfig {
+            proxy: Some("http://proxy.example.com:3128".to_string()),
+            timeout: 5,
+            pool_max_idle_per_host: Some(2),
+            ..Default::default()
+        };
+        assert!(HetznerCloudClient::with_http_config("test-token".to_string(), &http).is_ok());
+
+        let missing_ca = HttpConfig {
+            ca_certificates: vec!["/nonexistent/corporate-ca.pem".into()],
+            ..Default::default()
+        };
+        let err = HetznerCloudClient::with_http_config("test-token".to_string(), &missing_ca)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("corporate-ca.pem"));
+    }
+
+    #[tokio::test]
+    async fn test_server_metrics() {
+        let mock = super::super::mock::MockHcloud::start().await;
+        let client = mock.client();
+        let server = client
+            .create_server(CreateServerRequest {
+                name: "metrics-1".to_string(),
+                server_type: "cpx21".to_string(),
+                location: "fsn1".to_string(),
+                image: "debian-12".to_string(),
+                ssh_keys: None,
+                user_data: None,
+                networks: None,
+                labels: None,
+                automount: None,
+                start_after_create: None,
+                public_net: None,
+            })
+            .await
+            .unwrap()
+            .server;
+
+        let all = [MetricType::Cpu, MetricType::Disk, MetricType::Network];
+        let metrics = client
+            .get_server_metrics(
+                server.id,
+                &all,
+                MetricsRange::last(chrono::Duration::minutes(5)),
+            )
+            .await
+            .unwrap();
+        assert_eq!(metrics.average("cpu"), Some(20.0));
+        assert_eq!(metrics.average("network.0.bandwidth.out"), Some(1024.0));
+        assert_eq!(metrics.average("memory"), None);
+
+        let range = MetricsRange::last(chrono::Duration::minutes(5));
+        assert!(client
+            .get_server_metrics(server.id + 100, &all, range)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_server_metric_samples() {
+        let mock = super::super::mock::MockHcloud::start().await;
+        let client = mock.client();
+        let server = client
+            .create_server(CreateServerRequest {
+                name: "metrics-1".to_string(),
+                server_type: "cpx21".to_string(),
+                location: "fsn1".to_string(),
+                image: "debian-12".to_string(),
+                ssh_keys: None,
+                user_data: None,
+                networks: None,
+                labels: None,
+                automount: None,
+                start_after_create: None,
+                public_net: None,
+            })
+            .await
+            .unwrap()
+            .server;
+
+        let metrics = client
+            .get_server_metrics(
+                server.id,
+                &[MetricType::Cpu],
+                MetricsRange::last(chrono::Duration::minutes(5)),
+            )
+            .await
+            .unwrap();
+        assert_eq!(metrics.step, 60.0);
+        let samples = &metrics.time_series["cpu"].values;
+        assert_eq!(samples[1].time.timestamp(), 1704067260);
+        assert_eq!(samples[1].value, 30.0);
+    }
 }
@@ -0,0 +1,316 @@
+/// Expiry of preview clusters
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::BTreeMap;
+use tracing::info;
+
+use super::hibernate;
+use super::ip::IpManager;
+use super::models::is_cluster_resource;
+use super::nat::NatGatewayManager;
+use super::network::{cluster_network_names, NetworkManager};
+use super::projects::Projects;
+use super::server::ServerManager;
+use super::{FirewallManager, HetznerCloudClient, SSHKeyManager};
+use crate::config::HetznerCloudConfig;
+
+/// Network label holding the expiry as Unix seconds
+pub const EXPIRES_AT_LABEL: &str = "expires-at";
+
+/// Parse a TTL like `4h`, `90m`, `1d` or `1h30m`
+pub fn parse_ttl(ttl: &str) -> Result<Duration> {
+    let mut total = Duration::zero();
+    let mut digits = String::new();
+    for c in ttl.trim().chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let value: i64 = digits
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Invalid TTL '{}': expected e.g. 4h or 1h30m", ttl))?;
+        total += match c {
+            's' => Duration::seconds(value),
+            'm' => Duration::minutes(value),
+            'h' => Duration::hours(value),
+            'd' => Duration::days(value),
+            _ => anyhow::bail!("Invalid TTL '{}': unknown unit '{}'", ttl, c),
+        };
+        digits.clear();
+    }
+    if !digits.is_empty() {
+        anyhow::bail!("Invalid TTL '{}': missing unit (s, m, h or d)", ttl);
+    }
+    if total <= Duration::zero() {
+        anyhow::bail!("Invalid TTL '{}': must be longer than zero", ttl);
+    }
+    Ok(total)
+}
+
+/// A cluster labelled with an expiry
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpiringCluster {
+    pub name: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl ExpiringCluster {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at <= now
+    }
+}
+
+/// Label the primary network of a cluster with its expiry
+pub async fn set_expiry(
+    client: &HetznerCloudClient,
+    cluster_name: &str,
+    config: &HetznerCloudConfig,
+    expires_at: DateTime<Utc>,
+) -> Result<()> {
+    let primary = &cluster_network_names(cluster_name, config)[0];
+    let network = client
+        .list_networks()
+        .await?
+        .into_iter()
+        .find(|network| &network.name == primary)
+        .ok_or_else(|| anyhow::anyhow!("Network {} not found", primary))?;
+
+    let mut labels = network.labels.clone();
+    labels.insert(
+        EXPIRES_AT_LABEL.to_string(),
+        expires_at.timestamp().to_string(),
+    );
+    client
+        .update_network_labels(network.id, &labels)
+        .await
+        .context(format!(
+            "Failed to label network {} with its expiry",
+            primary
+        ))?;
+
+    info!("✓ Cluster {} expires at {}", cluster_name, expires_at);
+    Ok(())
+}
+
+/// Clusters that carry an expiry label in any of the projects, soonest first
+///
+/// A cluster labelled in several projects is listed once, with its earliest
+/// expiry.
+pub async fn expiring_clusters(projects: &Projects) -> Result<Vec<ExpiringCluster>> {
+    let mut expiries: BTreeMap<String, DateTime<Utc>> = BTreeMap::new();
+    for client in projects.all() {
+        for network in client.list_networks().await? {
+            if network.labels.get("managed-by").map(String::as_str) != Some("oxide") {
+                continue;
+            }
+            let (Some(name), Some(expires_at)) = (
+                network.labels.get("cluster"),
+                network
+                    .labels
+                    .get(EXPIRES_AT_LABEL)
+                    .and_then(|seconds| seconds.parse().ok())
+                    .and_then(|seconds| DateTime::from_timestamp(seconds, 0)),
+            ) else {
+                continue;
+            };
+            let earliest = expiries.entry(name.clone()).or_insert(expires_at);
+            *earliest = (*earliest).min(expires_at);
+        }
+    }
+
+    let mut clusters: Vec<ExpiringCluster> = expiries
+        .into_iter()
+        .map(|(name, expires_at)| ExpiringCluster { name, expires_at })
+        .collect();
+    clusters.sort_by_key(|cluster| cluster.expires_at);
+    Ok(clusters)
+}
+
+/// Delete everything labelled with the cluster in one project
+///
+/// Nothing is derived from the config: the reaper runs with the config of
+/// whichever cluster it was started for, so names and networks of the config
+/// may not match the expired cluster. Servers, IPs and snapshots are matched
+/// by their `cluster` label, the NAT gateway by its `nat-gateway` label, and
+/// load balancers by their label or by being attached to one of the
+/// cluster's labelled networks (like the one of ingress-nginx, which the
+/// cloud-controller-manager creates without labels).
+pub async fn delete_labelled(
+    client: &HetznerCloudClient,
+    cluster_name: &str,
+    graceful_shutdown: Option<u64>,
+) -> Result<()> {
+    ServerManager::new(client.clone())
+        .with_graceful_shutdown(graceful_shutdown)
+        .delete_cluster_servers(cluster_name)
+        .await?;
+    NatGatewayManager::new(client.clone())
+        .delete_gateway(cluster_name)
+        .await?;
+    IpManager::new(client.clone())
+        .release_cluster_ips(cluster_name)
+        .await?;
+    hibernate::delete_snapshots(client, cluster_name).await?;
+
+    let networks: Vec<u64> = client
+        .list_networks()
+        .await?
+        .into_iter()
+        .filter(|network| is_cluster_resource(&network.labels, cluster_name))
+        .map(|network| network.id)
+        .collect();
+    for load_balancer in client.list_load_balancers().await? {
+        let attached = load_balancer
+            .private_net
+            .iter()
+            .any(|net| networks.contains(&net.network));
+        if !attached && !is_cluster_resource(&load_balancer.labels, cluster_name) {
+            continue;
+        }
+        info!(
+            "Deleting load balancer: {} (ID: {})",
+            load_balancer.name, load_balancer.id
+        );
+        client.delete_load_balancer(load_balancer.id).await?;
+    }
+
+    FirewallManager::new(client.clone())
+        .delete_labelled_firewalls(cluster_name)
+        .await?;
+    SSHKeyManager::new(client.clone())
+        .delete_labelled_ssh_keys(cluster_name)
+        .await?;
+    NetworkManager::new(client.clone())
+        .delete_labelled_networks(cluster_name)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ClusterConfig;
+    use crate::hcloud::mock::MockHcloud;
+    use crate::hcloud::network::NetworkManager;
+
+    #[test]
+    fn test_parse_ttl() {
+        assert_eq!(parse_ttl("4h").unwrap(), Duration::hours(4));
+        assert_eq!(parse_ttl("90m").unwrap(), Duration::minutes(90));
+        assert_eq!(parse_ttl("1d").unwrap(), Duration::days(1));
+        assert_eq!(parse_ttl("1h30m").unwrap(), Duration::minutes(90));
+        assert!(parse_ttl("4").is_err());
+        assert!(parse_ttl("4w").is_err());
+        assert!(parse_ttl("h").is_err());
+        assert!(parse_ttl("0m").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_expiring_clusters() {
+        let mock = MockHcloud::start().await;
+        let client = mock.client();
+        let projects = Projects::single(client.clone());
+        let config = ClusterConfig::example();
+
+        let networks = NetworkManager::new(client.clone());
+        networks
+            .ensure_networks("preview-1", &config.hcloud)
+            .await
+            .unwrap();
+        networks
+            .ensure_networks("production", &config.hcloud)
+            .await
+            .unwrap();
+        assert!(expiring_clusters(&projects).await.unwrap().is_empty());
+
+        let expires_at = DateTime::from_timestamp(1_900_000_000, 0).unwrap();
+        set_expiry(&client, "preview-1", &config.hcloud, expires_at)
+            .await
+            .unwrap();
+
+        let clusters = expiring_clusters(&projects).await.unwrap();
+        assert_eq!(
+            clusters,
+            vec![ExpiringCluster {
+                name: "preview-1".to_string(),
+                expires_at,
+            }]
+        );
+        assert!(!clusters[0].is_expired(expires_at - Duration::seconds(1)));
+        assert!(clusters[0].is_expired(expires_at));
+
+        // The other labels stay
+        let network = &client.list_networks().await.unwrap()[0];
+        assert_eq!(network.labels["cluster"], "preview-1");
+    }
+
+    #[tokio::test]
+    async fn test_expiring_clusters_in_every_project() {
+        let main = MockHcloud::start().await;
+        let compute = MockHcloud::start().await;
+        let projects = Projects::single(main.client()).with_project("compute", compute.client());
+        let config = ClusterConfig::example();
+
+        NetworkManager::new(compute.client())
+            .ensure_networks("preview-2", &config.hcloud)
+            .await
+            .unwrap();
+        let expires_at = DateTime::from_timestamp(1_900_000_000, 0).unwrap();
+        set_expiry(&compute.client(), "preview-2", &config.hcloud, expires_at)
+            .await
+            .unwrap();
+
+        let clusters = expiring_clusters(&projects).await.unwrap();
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].name, "preview-2");
+    }
+
+    #[tokio::test]
+    async fn test_delete_labelled() {
+        let mock = MockHcloud::start().await;
+        let client = mock.client();
+        let config = ClusterConfig::example();
+
+        let networks = NetworkManager::new(client.clone());
+        let preview = networks
+            .ensure_networks("preview-1", &config.hcloud)
+            .await
+            .unwrap();
+        networks
+            .ensure_networks("production", &config.hcloud)
+            .await
+            .unwrap();
+        let keys = SSHKeyManager::new(client.clone());
+        keys.ensure_ssh_key("preview-1").await.unwrap();
+        keys.ensure_ssh_key("production").await.unwrap();
+        {
+            let mut state = mock.state();
+            state.load_balancers.insert(
+                100,
+                serde_json::json!({
+                    "id": 100,
+                    "name": "preview-1-ingress-nginx",
+                    "labels": {},
+                    "private_net": [{ "network": preview.primary().id }]
+                }),
+            );
+            state.load_balancers.insert(
+                101,
+                serde_json::json!({ "id": 101, "name": "preview-1-other", "labels": {} }),
+            );
+        }
+
+        delete_labelled(&client, "preview-1", None).await.unwrap();
+
+        let state = mock.state();
+        assert!(state
+            .networks
+            .values()
+            .all(|network| network["labels"]["cluster"] == "production"));
+        assert!(!state.networks.is_empty());
+        assert_eq!(state.ssh_keys.len(), 1);
+        assert_eq!(
+            state.load_balancers.keys().copied().collect::<Vec<_>>(),
+            [101]
+        );
+    }
+}
@@ -1,47 +1,139 @@
 /// Firewall management for Hetzner Cloud
 use anyhow::{Context, Result};
-use tracing::info;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+use tracing::{debug, info};
 
 use super::client::HetznerCloudClient;
-use super::models::{Firewall, FirewallRule};
+use super::models::{is_cluster_resource, Firewall, FirewallRule};
+use crate::config::{ClusterConfig, EgressConfig};
 
 /// Firewall manager
 pub struct FirewallManager {
     client: HetznerCloudClient,
+    kubespan: bool,
+    egress: Option<EgressConfig>,
+    public_ports: Vec<u16>,
 }
 
+/// UDP port of the KubeSpan WireGuard mesh
+const KUBESPAN_PORT: &str = "51820";
+
+/// Overrides the detected public IP with comma-separated addresses or CIDRs
+pub const PUBLIC_IP_ENV: &str = "OXIDE_PUBLIC_IP";
+
+/// Services answering with the caller's address as plain text, tried in
+/// order until one answers
+const IP_RESOLVERS: &[&str] = &[
+    "https://icanhazip.com",
+    "https://api64.ipify.org",
+    "https://ifconfig.me/ip",
+    "https://checkip.amazonaws.com",
+];
+
+/// How long each resolver gets to answer
+const IP_RESOLVER_TIMEOUT: Duration = Duration::from_secs(5);
+
 impl FirewallManager {
     /// Create a new firewall manager
     pub fn new(client: HetznerCloudClient) -> Self {
-        Self { client }
+        Self {
+            client,
+            kubespan: false,
+            egress: None,
+            public_ports: Vec::new(),
+        }
+    }
+
+    /// Admit KubeSpan WireGuard traffic for clusters spanning network zones
+    pub fn with_kubespan(mut self, enabled: bool) -> Self {
+        self.kubespan = enabled;
+        self
+    }
+
+    /// Restrict outbound traffic to the allowed destinations
+    pub fn with_egress(mut self, egress: Option<EgressConfig>) -> Self {
+        self.egress = egress;
+        self
+    }
+
+    /// Open more TCP ports to everyone besides HTTP and HTTPS, e.g. the
+    /// ingress-nginx ports on the node IPs
+    pub fn with_public_ports(mut self, ports: Vec<u16>) -> Self {
+        self.public_ports = ports;
+        self
+    }
+
+    /// Sources allowed to reach the Talos and Kubernetes APIs
+    ///
+    /// `OXIDE_PUBLIC_IP` wins over `hcloud.allowed_ips`; without either, the
+    /// public IPv4 address of this machine is detected, plus its IPv6
+    /// address on dual-stack clusters.
+    pub async fn allowed_ips(config: &ClusterConfig) -> Result<Vec<String>> {
+        if let Some(ips) = std::env::var(PUBLIC_IP_ENV)
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+        {
+            let ips = parse_ip_list(&ips).context(format!("Invalid {}", PUBLIC_IP_ENV))?;
+            info!("Allowing {} from {}", ips.join(", "), PUBLIC_IP_ENV);
+            return Ok(ips);
+        }
+        if !config.hcloud.allowed_ips.is_empty() {
+            info!(
+                "Allowing {} from hcloud.allowed_ips",
+                config.hcloud.allowed_ips.join(", ")
+            );
+            return Ok(config.hcloud.allowed_ips.clone());
+        }
+
+        let current_ip = Self::get_current_ip().await?;
+        info!("Detected current IP address: {}", current_ip);
+        let mut allowed_ips = vec![current_ip];
+
+        // Dual-stack clusters are also reachable over IPv6, so admit the admin's
+        // IPv6 address too when there is one
+        if config.cilium.enable_ipv6 {
+            match Self::get_current_ipv6().await {
+                Ok(ipv6) => {
+                    info!("Detected current IPv6 address: {}", ipv6);
+                    allowed_ips.push(ipv6);
+                }
+                Err(e) => info!(
+                    "⚠️  No IPv6 connectivity detected, allowing IPv4 only: {}",
+                    e
+                ),
+            }
+        }
+        Ok(allowed_ips)
     }
 
     /// Get current public IP address
     pub async fn get_current_ip() -> Result<String> {
-        let client = reqwest::Client::new();
-        let response = client
-            .get("https://ipv4.icanhazip.com")
-            .send()
+        lookup_ip(IP_RESOLVERS, IpAddr::V4(Ipv4Addr::UNSPECIFIED))
             .await
-            .context("Failed to get current IP address")?;
-
-        let ip = response
-            .text()
-            .await
-            .context("Failed to read IP address response")?;
+            .context(format!(
+                "Could not detect the public IPv4 address; set {} or hcloud.allowed_ips",
+                PUBLIC_IP_ENV
+            ))
+    }
 
-        Ok(ip.trim().to_string())
+    /// Get current public IPv6 address
+    ///
+    /// Fails on networks without IPv6 connectivity, so callers should treat
+    /// errors as "no IPv6 available" rather than fatal.
+    pub async fn get_current_ipv6() -> Result<String> {
+        lookup_ip(IP_RESOLVERS, IpAddr::V6(Ipv6Addr::UNSPECIFIED)).await
     }
 
     /// Create firewall with Talos/Cilium ports
     pub async fn create_cluster_firewall(
         &self,
         cluster_name: &str,
-        allowed_ip: &str,
+        allowed_ips: &[String],
     ) -> Result<Firewall> {
         info!(
-            "Creating firewall for cluster with allowed IP: {}",
-            allowed_ip
+            "Creating firewall for cluster with allowed IPs: {}",
+            allowed_ips.join(", ")
         );
 
         let firewall_name = format!("{}-firewall", cluster_name);
@@ -56,33 +148,74 @@ impl FirewallManager {
             return Ok(firewall);
         }
 
-        let allowed_ip_cidr = if allowed_ip.contains('/') {
-            allowed_ip.to_string()
-        } else {
-            format!("{}/32", allowed_ip)
+        let rules = self.cluster_rules(allowed_ips);
+        if self.egress.is_some() {
+            info!(
+                "Egress lockdown: {} outbound rule(s)",
+                rules.iter().filter(|r| r.direction == "out").count()
+            );
+        }
+
+        #[derive(serde::Serialize)]
+        struct CreateFirewallRequest {
+            name: String,
+            rules: Vec<FirewallRule>,
+            labels: std::collections::HashMap<String, String>,
+        }
+
+        let request = CreateFirewallRequest {
+            name: firewall_name,
+            rules,
+            labels: [
+                ("cluster".to_string(), cluster_name.to_string()),
+                ("managed-by".to_string(), "oxide".to_string()),
+            ]
+            .into_iter()
+            .collect(),
         };
 
+        let firewall = self
+            .create_firewall(request)
+            .await
+            .context("Failed to create firewall")?;
+
+        info!(
+            "Firewall created successfully: {} (ID: {})",
+            firewall.name, firewall.id
+        );
+
+        Ok(firewall)
+    }
+
+    /// Rules of the cluster firewall, with the Talos and Kubernetes APIs
+    /// open to `allowed_ips`
+    pub fn cluster_rules(&self, allowed_ips: &[String]) -> Vec<FirewallRule> {
+        let allowed_cidrs: Vec<String> = allowed_ips.iter().map(|ip| host_cidr(ip)).collect();
+
         // Define firewall rules for external access only
         // Note: Internal cluster communication (10.0.0.0/16) is not affected by Hetzner Cloud firewalls
-        let rules = vec![
+        let mut rules = vec![
             // Talos API (apid) - port 50000
             FirewallRule {
+                description: None,
                 direction: "in".to_string(),
-                source_ips: vec![allowed_ip_cidr.clone()],
+                source_ips: allowed_cidrs.clone(),
                 destination_ips: vec![],
                 protocol: "tcp".to_string(),
                 port: Some("50000".to_string()),
             },
             // Kubernetes API - port 6443
             FirewallRule {
+                description: None,
                 direction: "in".to_string(),
-                source_ips: vec![allowed_ip_cidr.clone()],
+                source_ips: allowed_cidrs.clone(),
                 destination_ips: vec![],
                 protocol: "tcp".to_string(),
                 port: Some("6443".to_string()),
             },
             // HTTP - port 80
             FirewallRule {
+                description: None,
                 direction: "in".to_string(),
                 source_ips: vec!["0.0.0.0/0".to_string(), "::/0".to_string()],
                 destination_ips: vec![],
@@ -91,6 +224,7 @@ impl FirewallManager {
             },
             // HTTPS - port 443
             FirewallRule {
+                description: None,
                 direction: "in".to_string(),
                 source_ips: vec!["0.0.0.0/0".to_string(), "::/0".to_string()],
                 destination_ips: vec![],
@@ -99,35 +233,40 @@ impl FirewallManager {
             },
         ];
 
-        #[derive(serde::Serialize)]
-        struct CreateFirewallRequest {
-            name: String,
-            rules: Vec<FirewallRule>,
-            labels: std::collections::HashMap<String, String>,
+        for port in &self.public_ports {
+            if rules.iter().any(|rule| {
+                rule.protocol == "tcp" && rule.port.as_deref() == Some(&port.to_string())
+            }) {
+                continue;
+            }
+            rules.push(FirewallRule {
+                description: None,
+                direction: "in".to_string(),
+                source_ips: vec!["0.0.0.0/0".to_string(), "::/0".to_string()],
+                destination_ips: vec![],
+                protocol: "tcp".to_string(),
+                port: Some(port.to_string()),
+            });
         }
 
-        let request = CreateFirewallRequest {
-            name: firewall_name,
-            rules,
-            labels: [
-                ("cluster".to_string(), cluster_name.to_string()),
-                ("managed-by".to_string(), "oxide".to_string()),
-            ]
-            .into_iter()
-            .collect(),
-        };
-
-        let firewall = self
-            .create_firewall(request)
-            .await
-            .context("Failed to create firewall")?;
+        // Nodes in other network zones only reach each other over public IPs;
+        // WireGuard authenticates peers itself, so the port is open to all
+        if self.kubespan {
+            rules.push(FirewallRule {
+                description: None,
+                direction: "in".to_string(),
+                source_ips: vec!["0.0.0.0/0".to_string(), "::/0".to_string()],
+                destination_ips: vec![],
+                protocol: "udp".to_string(),
+                port: Some(KUBESPAN_PORT.to_string()),
+            });
+        }
 
-        info!(
-            "Firewall created successfully: {} (ID: {})",
-            firewall.name, firewall.id
-        );
+        if let Some(egress) = &self.egress {
+            rules.extend(egress_rules(egress, self.kubespan));
+        }
 
-        Ok(firewall)
+        rules
     }
 
     /// Apply firewall to servers
@@ -178,7 +317,7 @@ impl FirewallManager {
     /// List all firewalls
     async fn list_firewalls(&self) -> Result<Vec<Firewall>> {
         use super::models::FirewallListResponse;
-        let response: FirewallListResponse = self.client.get("firewalls").await?;
+        let response: FirewallListResponse = self.client.get_cached("firewalls").await?;
         Ok(response.firewalls)
     }
 
@@ -199,9 +338,10 @@ impl FirewallManager {
     }
 
     /// Delete firewall
+    ///
+    /// Call once the cluster servers are deleted: Hetzner refuses to delete
+    /// a firewall that is still applied to a server.
     pub async fn delete_cluster_firewall(&self, cluster_name: &str) -> Result<()> {
-        use tokio::time::{sleep, Duration};
-
         let firewalls = self.list_firewalls().await?;
 
         if let Some(firewall) = firewalls
@@ -209,48 +349,268 @@ impl FirewallManager {
             .find(|f| f.name == format!("{}-firewall", cluster_name))
         {
             info!("Deleting firewall: {} (ID: {})", firewall.name, firewall.id);
+            self.client
+                .delete(&format!("firewalls/{}", firewall.id))
+                .await
+                .context("Failed to delete firewall")?;
+            info!("Firewall deleted successfully");
+        }
 
-            // Retry deletion if firewall is still in use (servers may still be deleting)
-            for attempt in 1..=12 {
-                match self
-                    .client
-                    .delete(&format!("firewalls/{}", firewall.id))
-                    .await
-                {
-                    Ok(_) => {
-                        info!("Firewall deleted successfully");
-                        return Ok(());
-                    }
-                    Err(e) => {
-                        let err_msg = e.to_string();
-                        if err_msg.contains("resource_in_use") || err_msg.contains("still in use") {
-                            if attempt < 12 {
-                                info!(
-                                    "Firewall still in use, waiting for servers to be deleted (attempt {}/12)...",
-                                    attempt
-                                );
-                                sleep(Duration::from_secs(5)).await;
-                            } else {
-                                return Err(e).context(
-                                    "Failed to delete firewall after waiting for servers",
-                                );
-                            }
-                        } else {
-                            return Err(e).context("Failed to delete firewall");
-                        }
-                    }
-                }
+        Ok(())
+    }
+
+    /// Delete the firewalls labelled with the cluster
+    ///
+    /// Like [`Self::delete_cluster_firewall`], only once the servers are gone.
+    pub async fn delete_labelled_firewalls(&self, cluster_name: &str) -> Result<()> {
+        for firewall in self.list_firewalls().await? {
+            if !is_cluster_resource(&firewall.labels, cluster_name) {
+                continue;
             }
+            info!("Deleting firewall: {} (ID: {})", firewall.name, firewall.id);
+            self.client
+                .delete(&format!("firewalls/{}", firewall.id))
+                .await
+                .context("Failed to delete firewall")?;
         }
-
         Ok(())
     }
 }
 
+/// Hetzner's recursive resolvers
+const HETZNER_DNS: [&str; 4] = [
+    "185.12.64.1/32",
+    "185.12.64.2/32",
+    "2a01:4ff:ff00::add:1/128",
+    "2a01:4ff:ff00::add:2/128",
+];
+
+/// time.cloudflare.com, Talos' default time server
+const CLOUDFLARE_NTP: [&str; 4] = [
+    "162.159.200.1/32",
+    "162.159.200.123/32",
+    "2606:4700:f1::1/128",
+    "2606:4700:f1::123/128",
+];
+
+/// Hetzner metadata service, where Talos reads its config from
+const METADATA_SERVICE: &str = "169.254.169.254/32";
+
+const ANYWHERE: [&str; 2] = ["0.0.0.0/0", "::/0"];
+
+/// Outbound rules for egress lockdown
+///
+/// Registries, GitHub and the Talos discovery service sit behind CDNs with
+/// changing addresses, and Hetzner firewalls only match IPs, so the defaults
+/// allow HTTPS to anywhere; disable them to pin HTTPS to known mirrors.
+fn egress_rules(egress: &EgressConfig, kubespan: bool) -> Vec<FirewallRule> {
+    let mut rules = Vec::new();
+    let mut allow = |description: &str, ips: &[&str], protocol: &str, port: Option<&str>| {
+        rules.push(FirewallRule {
+            description: Some(description.to_string()),
+            direction: "out".to_string(),
+            source_ips: vec![],
+            destination_ips: ips.iter().map(|ip| ip.to_string()).collect(),
+            protocol: protocol.to_string(),
+            port: port.map(str::to_string),
+        });
+    };
+
+    if egress.defaults {
+        allow("DNS", &HETZNER_DNS, "udp", Some("53"));
+        allow("DNS", &HETZNER_DNS, "tcp", Some("53"));
+        allow("NTP", &CLOUDFLARE_NTP, "udp", Some("123"));
+        allow("Metadata", &[METADATA_SERVICE], "tcp", Some("80"));
+        allow("HTTPS", &ANYWHERE, "tcp", Some("443"));
+    }
+    // The mesh would otherwise only work in one direction
+    if kubespan {
+        allow("KubeSpan", &ANYWHERE, "udp", Some(KUBESPAN_PORT));
+    }
+
+    for rule in &egress.rules {
+        rules.push(FirewallRule {
+            description: rule.description.clone(),
+            direction: "out".to_string(),
+            source_ips: vec![],
+            destination_ips: rule
+                .destination_ips
+                .iter()
+                .map(|ip| host_cidr(ip))
+                .collect(),
+            protocol: rule.protocol.clone(),
+            port: rule.port.clone(),
+        });
+    }
+
+    rules
+}
+
+/// Ask `resolvers` in turn for the public address of the family of `local`
+///
+/// Binding to the unspecified address of a family forces the connection,
+/// and so the answer, onto that family. Answers that are not an address of
+/// that family (captive portals, error pages) count as failures.
+async fn lookup_ip(resolvers: &[&str], local: IpAddr) -> Result<String> {
+    let client = reqwest::Client::builder()
+        .local_address(local)
+        .timeout(IP_RESOLVER_TIMEOUT)
+        .build()?;
+
+    let mut failures = Vec::new();
+    for url in resolvers {
+        let answer = match client.get(*url).send().await {
+            Ok(response) => match response.error_for_status() {
+                Ok(response) => response.text().await.map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            },
+            Err(e) => Err(e.to_string()),
+        };
+        match answer.map(|text| text.trim().parse::<IpAddr>()) {
+            Ok(Ok(ip)) if ip.is_ipv4() == local.is_ipv4() => return Ok(ip.to_string()),
+            Ok(_) => failures.push(format!("{}: not an address of the right family", url)),
+            Err(e) => failures.push(format!("{}: {}", url, e)),
+        }
+        debug!("Public IP lookup failed: {}", failures[failures.len() - 1]);
+    }
+    anyhow::bail!("{}", failures.join("; "))
+}
+
+/// Parse comma-separated addresses or CIDRs
+fn parse_ip_list(value: &str) -> Result<Vec<String>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            let valid = match item.split_once('/') {
+                Some(_) => item.parse::<ipnet::IpNet>().is_ok(),
+                None => item.parse::<IpAddr>().is_ok(),
+            };
+            if !valid {
+                anyhow::bail!("{} is not an IP address or CIDR", item);
+            }
+            Ok(item.to_string())
+        })
+        .collect()
+}
+
+/// Turn a bare address into a single-host CIDR, leaving CIDRs untouched
+fn host_cidr(ip: &str) -> String {
+    if ip.contains('/') {
+        ip.to_string()
+    } else if ip.contains(':') {
+        format!("{}/128", ip)
+    } else {
+        format!("{}/32", ip)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_host_cidr() {
+        assert_eq!(host_cidr("203.0.113.7"), "203.0.113.7/32");
+        assert_eq!(host_cidr("2001:db8::1"), "2001:db8::1/128");
+        assert_eq!(host_cidr("10.0.0.0/8"), "10.0.0.0/8");
+    }
+
+    #[test]
+    fn test_egress_rules() {
+        let egress: EgressConfig = serde_yaml::from_str(
+            "rules:\n  - destination_ips: [198.51.100.10]\n    protocol: tcp\n    port: \"5000\"\n",
+        )
+        .unwrap();
+        let rules = egress_rules(&egress, true);
+        assert!(rules.iter().all(|r| r.direction == "out"));
+        assert!(rules
+            .iter()
+            .any(|r| r.port.as_deref() == Some("123") && r.protocol == "udp"));
+        assert!(rules
+            .iter()
+            .any(|r| r.port.as_deref() == Some(KUBESPAN_PORT)));
+        let custom = rules.last().unwrap();
+        assert_eq!(custom.destination_ips, ["198.51.100.10/32"]);
+
+        let egress = EgressConfig {
+            defaults: false,
+            ..egress
+        };
+        assert_eq!(egress_rules(&egress, false).len(), 1);
+    }
+
+    #[test]
+    fn test_public_ports() {
+        let client = HetznerCloudClient::new("token".to_string()).unwrap();
+        let manager = FirewallManager::new(client).with_public_ports(vec![8080, 443]);
+        let rules = manager.cluster_rules(&["203.0.113.7".to_string()]);
+        let public: Vec<&str> = rules
+            .iter()
+            .filter(|r| r.source_ips.contains(&"0.0.0.0/0".to_string()))
+            .filter_map(|r| r.port.as_deref())
+            .collect();
+        assert_eq!(public, ["80", "443", "8080"]);
+    }
+
+    #[test]
+    fn test_parse_ip_list() {
+        assert_eq!(
+            parse_ip_list("203.0.113.7, 198.51.100.0/24,2001:db8::1,").unwrap(),
+            ["203.0.113.7", "198.51.100.0/24", "2001:db8::1"]
+        );
+        assert!(parse_ip_list("203.0.113.7,office").is_err());
+        assert!(parse_ip_list("10.0.0.0/33").is_err());
+    }
+
+    /// Answer one request with `body` and return the URL
+    async fn serve_once(body: &'static str) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request).await;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_lookup_ip_falls_back() {
+        // Nothing listens on the port once the listener is dropped
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed = format!("http://{}/", listener.local_addr().unwrap());
+        drop(listener);
+        let portal = serve_once("<html>Sign in to the Wi-Fi</html>").await;
+        let resolver = serve_once("203.0.113.7\n").await;
+
+        let local = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+        let resolvers = [closed.as_str(), portal.as_str(), resolver.as_str()];
+        assert_eq!(lookup_ip(&resolvers, local).await.unwrap(), "203.0.113.7");
+
+        // Every failure is reported when no resolver answers
+        let portal = serve_once("<html>Sign in to the Wi-Fi</html>").await;
+        let error = lookup_ip(&[closed.as_str(), portal.as_str()], local)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains(&closed), "{}", error);
+        assert!(
+            error.contains(&format!("{}: not an address", portal)),
+            "{}",
+            error
+        );
+    }
+
     #[tokio::test]
     async fn test_get_current_ip() {
         let result = FirewallManager::get_current_ip().await;
@@ -0,0 +1,526 @@
+/// Snapshot-based hibernation of a paused cluster
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use tracing::{info, warn};
+
+use super::client::{
+    CreateServerRequest, HetznerCloudClient, PublicNetRequest, UpdatePrimaryIpRequest,
+};
+use super::firewall::FirewallManager;
+use super::models::{Image, IpType, PrimaryIp, Server};
+use super::projects::Projects;
+use super::server::{server_index, NodeRole, ServerInfo, ServerManager};
+use crate::state::ClusterState;
+
+/// File in the output directory recording the hibernated servers
+pub const STATE_FILE: &str = "hibernation.json";
+
+/// Snapshot label holding the name of the server it was taken of
+const SNAPSHOT_LABEL: &str = "hibernated-server";
+
+/// Servers of a hibernated cluster, saved until they are restored
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hibernation {
+    pub cluster_name: String,
+    pub nodes: Vec<HibernatedNode>,
+}
+
+/// Everything needed to recreate one server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HibernatedNode {
+    pub name: String,
+    /// Index of the server within its pool
+    #[serde(default)]
+    pub index: u32,
+    /// Project from `hcloud.projects`, `None` for the one of `hcloud.token`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    pub server_type: String,
+    pub location: String,
+    pub labels: BTreeMap<String, String>,
+    pub snapshot_id: u64,
+    /// Private IP in each attached network, keyed by network ID
+    pub networks: BTreeMap<u64, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub primary_ipv4: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub primary_ipv6: Option<u64>,
+    /// Primary IPs that were deleted together with the server, switched back
+    /// once it is restored
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub auto_delete_ips: Vec<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub floating_ips: Vec<u64>,
+    #[serde(default)]
+    pub delete_protection: bool,
+}
+
+impl Hibernation {
+    /// Hibernated servers recorded in `output_dir`, if any
+    pub fn load(output_dir: &Path) -> Result<Option<Self>> {
+        let path = output_dir.join(STATE_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content =
+            std::fs::read_to_string(&path).context(format!("Failed to read {}", path.display()))?;
+        let hibernation = serde_json::from_str(&content)
+            .context(format!("Invalid hibernation record in {}", path.display()))?;
+        Ok(Some(hibernation))
+    }
+
+    fn save(&self, output_dir: &Path) -> Result<()> {
+        let path = output_dir.join(STATE_FILE);
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)
+            .context(format!("Failed to write {}", path.display()))
+    }
+
+    /// Remove the record, e.g. once the cluster is destroyed
+    pub fn clear(output_dir: &Path) -> Result<()> {
+        let path = output_dir.join(STATE_FILE);
+        if path.exists() {
+            std::fs::remove_file(&path).context(format!("Failed to remove {}", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+impl HibernatedNode {
+    fn new(
+        server: &Server,
+        project: Option<&str>,
+        snapshot_id: u64,
+        primary_ips: &[PrimaryIp],
+    ) -> Self {
+        let primary_ip = |ip_type: IpType| {
+            primary_ips
+                .iter()
+                .find(|ip| ip.assignee_id == Some(server.id) && ip.ip_type == ip_type)
+        };
+        let primary_ipv4 = primary_ip(IpType::Ipv4);
+        let primary_ipv6 = primary_ip(IpType::Ipv6);
+
+        Self {
+            name: server.name.clone(),
+            index: server_index(&server.name),
+            project: project.map(str::to_string),
+            server_type: server.server_type.name.clone(),
+            location: ServerManager::location(server).to_string(),
+            labels: server.labels.clone().into_iter().collect(),
+            snapshot_id,
+            networks: server
+                .private_net
+                .iter()
+                .map(|private_net| (private_net.network, private_net.ip.clone()))
+                .collect(),
+            primary_ipv4: primary_ipv4.map(|ip| ip.id),
+            primary_ipv6: primary_ipv6.map(|ip| ip.id),
+            auto_delete_ips: [primary_ipv4, primary_ipv6]
+                .into_iter()
+                .flatten()
+                .filter(|ip| ip.auto_delete)
+                .map(|ip| ip.id)
+                .collect(),
+            floating_ips: server.public_net.floating_ips.clone(),
+            delete_protection: server.protection.delete,
+        }
+    }
+
+    fn role(&self) -> NodeRole {
+        match self.labels.get("role").map(String::as_str) {
+            Some("control-plane") => NodeRole::ControlPlane,
+            _ => NodeRole::Worker,
+        }
+    }
+
+    fn is_public(&self) -> bool {
+        self.primary_ipv4.is_some() || self.primary_ipv6.is_some()
+    }
+}
+
+/// Snapshot the stopped servers of a cluster and delete them
+///
+/// The record is saved after every server, so an interrupted run can be
+/// repeated and picks up the servers that are left.
+pub async fn hibernate(projects: &Projects, cluster_name: &str, output_dir: &Path) -> Result<()> {
+    let mut hibernation = Hibernation::load(output_dir)?.unwrap_or_else(|| Hibernation {
+        cluster_name: cluster_name.to_string(),
+        nodes: Vec::new(),
+    });
+    if hibernation.cluster_name != cluster_name {
+        anyhow::bail!(
+            "{} records hibernated servers of cluster {}, not {}",
+            output_dir.join(STATE_FILE).display(),
+            hibernation.cluster_name,
+            cluster_name
+        );
+    }
+
+    for (project, client) in projects.named() {
+        let manager = ServerManager::new(client.clone());
+        let primary_ips = client.list_primary_ips().await?;
+        for info in manager.list_cluster_servers(cluster_name).await? {
+            let server = &info.server;
+            if server.status != "off" {
+                anyhow::bail!("Server {} is still running", server.name);
+            }
+
+            info!("Snapshotting server {}", server.name);
+            let labels = HashMap::from([
+                ("cluster".to_string(), cluster_name.to_string()),
+                ("managed-by".to_string(), "oxide".to_string()),
+                (SNAPSHOT_LABEL.to_string(), server.name.clone()),
+            ]);
+            let image = client
+                .create_snapshot(
+                    server.id,
+                    &format!("{} hibernated by oxide", server.name),
+                    labels,
+                )
+                .await
+                .context(format!("Failed to snapshot server {}", server.name))?;
+
+            let mut node = HibernatedNode::new(server, project, image.id, &primary_ips);
+            // Left over from an interrupted run, which already kept the IPs
+            if let Some(index) = hibernation.nodes.iter().position(|n| n.name == node.name) {
+                let stale = hibernation.nodes.remove(index);
+                node.auto_delete_ips.extend(stale.auto_delete_ips);
+                node.auto_delete_ips.sort_unstable();
+                node.auto_delete_ips.dedup();
+                if let Err(e) = client.delete_image(stale.snapshot_id).await {
+                    warn!(
+                        "⚠️  Failed to delete snapshot {} of {}: {:#}",
+                        stale.snapshot_id, node.name, e
+                    );
+                }
+            }
+            keep_primary_ips(client, &primary_ips, &node, cluster_name).await?;
+            hibernation.nodes.push(node);
+            hibernation.save(output_dir)?;
+
+            if server.protection.delete {
+                manager.set_delete_protection(server, false).await?;
+            }
+            let action = client
+                .delete_server(server.id)
+                .await
+                .context(format!("Failed to delete server {}", server.name))?;
+            client.wait_for_action(action.id, 120).await?;
+            ClusterState::forget_servers(output_dir, std::slice::from_ref(&info))?;
+            info!(
+                "✓ Server {} hibernated as snapshot {}",
+                server.name, image.id
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Keep the primary IPs of a server when it is deleted
+///
+/// They get the cluster label, so `destroy` releases them if the cluster is
+/// never restored.
+async fn keep_primary_ips(
+    client: &HetznerCloudClient,
+    primary_ips: &[PrimaryIp],
+    node: &HibernatedNode,
+    cluster_name: &str,
+) -> Result<()> {
+    for primary_ip in primary_ips
+        .iter()
+        .filter(|ip| [node.primary_ipv4, node.primary_ipv6].contains(&Some(ip.id)))
+    {
+        let mut labels = primary_ip.labels.clone();
+        labels.insert("cluster".to_string(), cluster_name.to_string());
+        client
+            .update_primary_ip(
+                primary_ip.id,
+                &UpdatePrimaryIpRequest {
+                    auto_delete: Some(false),
+                    labels: Some(labels),
+                },
+            )
+            .await
+            .context(format!("Failed to keep primary IP {}", primary_ip.ip))?;
+    }
+    Ok(())
+}
+
+/// Recreate the hibernated servers from their snapshots, powered off
+///
+/// Servers that already exist, from an interrupted run, are left alone.
+/// Does nothing without a hibernation record.
+pub async fn restore(projects: &Projects, output_dir: &Path) -> Result<()> {
+    let Some(hibernation) = Hibernation::load(output_dir)? else {
+        return Ok(());
+    };
+    let cluster_name = &hibernation.cluster_name;
+    let existing: HashSet<String> = projects
+        .list_cluster_servers(cluster_name)
+        .await?
+        .into_iter()
+        .map(|info| info.server.name)
+        .collect();
+
+    for node in &hibernation.nodes {
+        if existing.contains(&node.name) {
+            continue;
+        }
+        let client = projects.get(node.project.as_deref())?;
+        let info = restore_node(client, node, cluster_name)
+            .await
+            .context(format!("Failed to restore server {}", node.name))?;
+        ClusterState::record_servers(output_dir, cluster_name, std::slice::from_ref(&info))?;
+        info!("✓ Server {} restored", node.name);
+    }
+    Ok(())
+}
+
+async fn restore_node(
+    client: &HetznerCloudClient,
+    node: &HibernatedNode,
+    cluster_name: &str,
+) -> Result<ServerInfo> {
+    info!(
+        "Restoring server {} from snapshot {}",
+        node.name, node.snapshot_id
+    );
+    let request = CreateServerRequest {
+        name: node.name.clone(),
+        server_type: node.server_type.clone(),
+        location: node.location.clone(),
+        image: node.snapshot_id.to_string(),
+        ssh_keys: None,
+        user_data: None,
+        // A server needs some network to be created; one without public IPs
+        // gets its first private network and is re-attached below
+        networks: (!node.is_public()).then(|| node.networks.keys().take(1).copied().collect()),
+        labels: Some(node.labels.clone().into_iter().collect()),
+        automount: Some(false),
+        start_after_create: Some(false),
+        public_net: Some(PublicNetRequest {
+            enable_ipv4: node.primary_ipv4.is_some(),
+            enable_ipv6: node.primary_ipv6.is_some(),
+            ipv4: node.primary_ipv4,
+            ipv6: node.primary_ipv6,
+        }),
+    };
+    let response = client.create_server(request).await?;
+    client
+        .wait_for_action(response.action.id, 300)
+        .await
+        .context("Server creation action failed")?;
+    let server_id = response.server.id;
+
+    // The private IPs are part of the Talos certificates and etcd peer URLs
+    let server = client.get_server(server_id).await?;
+    for (network, ip) in &node.networks {
+        match server.private_net.iter().find(|n| n.network == *network) {
+            Some(attached) if attached.ip == *ip => continue,
+            Some(_) => {
+                let action = client.detach_from_network(server_id, *network).await?;
+                client.wait_for_action(action.id, 60).await?;
+            }
+            None => {}
+        }
+        let action = client
+            .attach_to_network(server_id, *network, Some(ip.clone()))
+            .await
+            .context(format!("Failed to attach {} to network {}", ip, network))?;
+        client.wait_for_action(action.id, 60).await?;
+    }
+
+    for floating_ip in &node.floating_ips {
+        let action = client.assign_floating_ip(*floating_ip, server_id).await?;
+        client.wait_for_action(action.id, 60).await?;
+    }
+
+    // Private servers have no interface to filter
+    if node.is_public() {
+        let firewall = FirewallManager::new(client.clone())
+            .get_cluster_firewall(cluster_name)
+            .await?;
+        if let Some(firewall) = firewall {
+            FirewallManager::new(client.clone())
+                .apply_to_servers(firewall.id, vec![server_id])
+                .await?;
+        }
+    }
+
+    let server = client.get_server(server_id).await?;
+    if node.delete_protection {
+        ServerManager::new(client.clone())
+            .set_delete_protection(&server, true)
+            .await?;
+    }
+
+    Ok(ServerInfo {
+        server,
+        role: node.role(),
+        index: node.index,
+    })
+}
+
+/// Delete the snapshots of the restored servers and let their primary IPs
+/// be deleted with them again
+pub async fn finish(projects: &Projects, output_dir: &Path) -> Result<()> {
+    let Some(hibernation) = Hibernation::load(output_dir)? else {
+        return Ok(());
+    };
+    for node in &hibernation.nodes {
+        let client = projects.get(node.project.as_deref())?;
+        for primary_ip in &node.auto_delete_ips {
+            client
+                .update_primary_ip(
+                    *primary_ip,
+                    &UpdatePrimaryIpRequest {
+                        auto_delete: Some(true),
+                        ..Default::default()
+                    },
+                )
+                .await
+                .context(format!("Failed to update primary IP {}", primary_ip))?;
+        }
+        if let Err(e) = client.delete_image(node.snapshot_id).await {
+            warn!(
+                "⚠️  Failed to delete snapshot {} of {}: {:#}",
+                node.snapshot_id, node.name, e
+            );
+        }
+    }
+    Hibernation::clear(output_dir)
+}
+
+/// Hibernation snapshots of a cluster in one project
+pub async fn snapshots(client: &HetznerCloudClient, cluster_name: &str) -> Result<Vec<Image>> {
+    Ok(client
+        .list_snapshots(&format!("cluster={}", cluster_name))
+        .await?
+        .into_iter()
+        .filter(|image| image.labels.contains_key(SNAPSHOT_LABEL))
+        .collect())
+}
+
+/// Delete the hibernation snapshots of a cluster in one project
+pub async fn delete_snapshots(client: &HetznerCloudClient, cluster_name: &str) -> Result<()> {
+    for image in snapshots(client, cluster_name).await? {
+        info!(
+            "Deleting snapshot: {} (ID: {})",
+            image.description, image.id
+        );
+        client
+            .delete_image(image.id)
+            .await
+            .context(format!("Failed to delete snapshot {}", image.id))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hcloud::mock::MockHcloud;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_hibernate_and_restore() {
+        let mock = MockHcloud::start().await;
+        let client = mock.client();
+        let projects = Projects::single(client.clone());
+        let dir = std::env::temp_dir().join(format!("oxide-hibernation-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let manager = ServerManager::new(client.clone());
+
+        let mut servers = Vec::new();
+        for (name, role, public_ip) in [
+            ("prod-control-plane-1", NodeRole::ControlPlane, true),
+            ("prod-worker-2", NodeRole::Worker, false),
+        ] {
+            servers.push(
+                manager
+                    .create_single_node(
+                        "prod",
+                        name,
+                        "cpx21",
+                        "nbg1",
+                        1,
+                        role,
+                        "v1.9.0",
+                        Some("1"),
+                        None,
+                        None,
+                        HashMap::new(),
+                        public_ip,
+                    )
+                    .await
+                    .unwrap(),
+            );
+        }
+        let control_plane = &servers[0].server;
+        mock.state().primary_ips.insert(
+            90,
+            json!({
+                "id": 90,
+                "name": "prod-control-plane-1",
+                "ip": "198.51.100.90",
+                "type": "ipv4",
+                "assignee_id": control_plane.id,
+                "assignee_type": "server",
+                "auto_delete": true,
+                "protection": { "delete": false },
+                "labels": {}
+            }),
+        );
+        let primary_ip = client.list_primary_ips().await.unwrap().remove(0);
+        ClusterState::record_servers(&dir, "prod", &servers).unwrap();
+        for info in &servers {
+            client.power_off_server(info.server.id).await.unwrap();
+        }
+
+        hibernate(&projects, "prod", &dir).await.unwrap();
+        assert!(mock.state().servers.is_empty());
+        assert_eq!(mock.state().images.len(), 2);
+        assert_eq!(
+            mock.state().primary_ips[&primary_ip.id]["auto_delete"],
+            false
+        );
+        let hibernation = Hibernation::load(&dir).unwrap().unwrap();
+        assert_eq!(hibernation.nodes[0].primary_ipv4, Some(primary_ip.id));
+        assert_eq!(hibernation.nodes[0].auto_delete_ips, [primary_ip.id]);
+        assert_eq!(hibernation.nodes[1].primary_ipv4, None);
+        assert_eq!(hibernation.nodes[1].role(), NodeRole::Worker);
+        assert_eq!(hibernation.nodes[1].index, 1);
+        assert!(ClusterState::load(&dir)
+            .unwrap()
+            .unwrap()
+            .servers
+            .is_empty());
+
+        restore(&projects, &dir).await.unwrap();
+        let restored = projects.list_cluster_servers("prod").await.unwrap();
+        assert_eq!(restored.len(), 2);
+        for (before, after) in servers.iter().zip(&restored) {
+            assert_eq!(before.server.name, after.server.name);
+            assert_eq!(after.server.status, "off");
+            assert_eq!(
+                after.server.private_net[0].ip,
+                before.server.private_net[0].ip
+            );
+        }
+        assert_eq!(
+            restored[0].server.public_net.ipv4.as_ref().unwrap().ip,
+            primary_ip.ip
+        );
+
+        finish(&projects, &dir).await.unwrap();
+        assert!(mock.state().images.is_empty());
+        assert_eq!(
+            mock.state().primary_ips[&primary_ip.id]["auto_delete"],
+            true
+        );
+        assert!(Hibernation::load(&dir).unwrap().is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,222 @@
+/// Floating and primary IPs of a cluster
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+use super::client::{CreateFloatingIpRequest, HetznerCloudClient};
+use super::models::{FloatingIp, IpType, Server};
+
+/// Label marking the floating IP of a cluster's egress gateway
+const EGRESS_IP_LABEL: &str = "egress-gateway";
+
+/// Manager for the floating and primary IPs of a cluster
+pub struct IpManager {
+    client: HetznerCloudClient,
+}
+
+impl IpManager {
+    /// Create a new IP manager
+    pub fn new(client: HetznerCloudClient) -> Self {
+        Self { client }
+    }
+
+    /// Floating IP the egress gateway of the cluster sends traffic from
+    pub async fn find_egress_ip(&self, cluster_name: &str) -> Result<Option<FloatingIp>> {
+        Ok(self
+            .client
+            .list_floating_ips()
+            .await?
+            .into_iter()
+            .find(|ip| {
+                ip.labels.get("cluster").map(String::as_str) == Some(cluster_name)
+                    && ip.labels.contains_key(EGRESS_IP_LABEL)
+            }))
+    }
+
+    /// Get or create the egress floating IP, routed to `location` until it is
+    /// assigned
+    pub async fn ensure_egress_ip(&self, cluster_name: &str, location: &str) -> Result<FloatingIp> {
+        if let Some(floating_ip) = self.find_egress_ip(cluster_name).await? {
+            info!("Found existing egress IP: {}", floating_ip.ip);
+            return Ok(floating_ip);
+        }
+        let floating_ip = self
+            .client
+            .create_floating_ip(&CreateFloatingIpRequest {
+                name: format!("{}-egress", cluster_name),
+                ip_type: IpType::Ipv4,
+                home_location: Some(location.to_string()),
+                server: None,
+                labels: Some(HashMap::from([
+                    ("cluster".to_string(), cluster_name.to_string()),
+                    (EGRESS_IP_LABEL.to_string(), "true".to_string()),
+                ])),
+            })
+            .await
+            .context("Failed to create the egress floating IP")?;
+        info!("Created egress IP: {}", floating_ip.ip);
+        Ok(floating_ip)
+    }
+
+    /// Assign a floating IP to `server` unless it already is
+    pub async fn assign(&self, floating_ip: &FloatingIp, server: &Server) -> Result<()> {
+        if floating_ip.server == Some(server.id) {
+            return Ok(());
+        }
+        info!("Assigning {} to {}", floating_ip.ip, server.name);
+        let action = self
+            .client
+            .assign_floating_ip(floating_ip.id, server.id)
+            .await
+            .context(format!(
+                "Failed to assign floating IP {} to {}",
+                floating_ip.ip, server.name
+            ))?;
+        self.client.wait_for_action(action.id, 60).await?;
+        Ok(())
+    }
+
+    /// Unassign and delete every floating and primary IP labelled with the cluster
+    ///
+    /// Run after the servers are gone: a primary IP can only be unassigned
+    /// from a powered-off server, so one still assigned to a running server
+    /// is left in place with a warning.
+    pub async fn release_cluster_ips(&self, cluster_name: &str) -> Result<()> {
+        let labelled = |labels: &std::collections::HashMap<String, String>| {
+            labels.get("cluster").map(String::as_str) == Some(cluster_name)
+        };
+
+        for floating_ip in self.client.list_floating_ips().await? {
+            if !labelled(&floating_ip.labels) {
+                continue;
+            }
+            info!(
+                "Releasing floating IP: {} ({})",
+                floating_ip.name, floating_ip.ip
+            );
+            if floating_ip.server.is_some() {
+                let action = self
+                    .client
+                    .unassign_floating_ip(floating_ip.id)
+                    .await
+                    .context(format!("Failed to unassign floating IP {}", floating_ip.ip))?;
+                self.client.wait_for_action(action.id, 60).await?;
+            }
+            self.client
+                .delete_floating_ip(floating_ip.id)
+                .await
+                .context(format!("Failed to delete floating IP {}", floating_ip.ip))?;
+        }
+
+        for primary_ip in self.client.list_primary_ips().await? {
+            if !labelled(&primary_ip.labels) {
+                continue;
+            }
+            if let Some(server_id) = primary_ip.assignee_id {
+                let server = self.client.get_server(server_id).await?;
+                if server.status != "off" {
+                    warn!(
+                        "⚠️  Primary IP {} is still assigned to running server {}, not releasing it",
+                        primary_ip.ip, server.name
+                    );
+                    continue;
+                }
+                let action = self
+                    .client
+                    .unassign_primary_ip(primary_ip.id)
+                    .await
+                    .context(format!("Failed to unassign primary IP {}", primary_ip.ip))?;
+                self.client.wait_for_action(action.id, 60).await?;
+            }
+            info!(
+                "Releasing primary IP: {} ({})",
+                primary_ip.name, primary_ip.ip
+            );
+            self.client
+                .delete_primary_ip(primary_ip.id)
+                .await
+                .context(format!("Failed to delete primary IP {}", primary_ip.ip))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hcloud::mock::MockHcloud;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_release_cluster_ips() {
+        let mock = MockHcloud::start().await;
+        let client = mock.client();
+        let labels = |cluster: &str| {
+            Some(HashMap::from([(
+                "cluster".to_string(),
+                cluster.to_string(),
+            )]))
+        };
+
+        let floating_ip = |name: &str, cluster: &str| CreateFloatingIpRequest {
+            name: name.to_string(),
+            ip_type: IpType::Ipv4,
+            home_location: Some("nbg1".to_string()),
+            server: None,
+            labels: labels(cluster),
+        };
+        let endpoint = client
+            .create_floating_ip(&floating_ip("prod-endpoint", "prod"))
+            .await
+            .unwrap();
+        client
+            .create_floating_ip(&floating_ip("staging-endpoint", "staging"))
+            .await
+            .unwrap();
+        let action = client.assign_floating_ip(endpoint.id, 7).await.unwrap();
+        client.wait_for_action(action.id, 5).await.unwrap();
+
+        mock.state().primary_ips.insert(
+            90,
+            json!({
+                "id": 90,
+                "name": "prod-egress",
+                "ip": "198.51.100.90",
+                "type": "ipv4",
+                "assignee_id": null,
+                "assignee_type": "server",
+                "auto_delete": false,
+                "protection": { "delete": false },
+                "labels": { "cluster": "prod" }
+            }),
+        );
+
+        IpManager::new(client.clone())
+            .release_cluster_ips("prod")
+            .await
+            .unwrap();
+
+        let floating_ips = client.list_floating_ips().await.unwrap();
+        assert_eq!(floating_ips.len(), 1);
+        assert_eq!(floating_ips[0].name, "staging-endpoint");
+        assert!(client.list_primary_ips().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_ensure_egress_ip() {
+        let mock = MockHcloud::start().await;
+        let manager = IpManager::new(mock.client());
+        assert!(manager.find_egress_ip("prod").await.unwrap().is_none());
+
+        let created = manager.ensure_egress_ip("prod", "nbg1").await.unwrap();
+        assert_eq!(created.name, "prod-egress");
+        let again = manager.ensure_egress_ip("prod", "nbg1").await.unwrap();
+        assert_eq!(again.id, created.id);
+        assert!(manager.find_egress_ip("staging").await.unwrap().is_none());
+
+        // Released with the other IPs of the cluster
+        manager.release_cluster_ips("prod").await.unwrap();
+        assert!(manager.find_egress_ip("prod").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,812 @@
+/// In-process mock of the Hetzner Cloud API for tests
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use super::HetznerCloudClient;
+
+const CREATED: &str = "2024-01-01T00:00:00+00:00";
+
+/// Running mock API server
+pub struct MockHcloud {
+    base_url: String,
+    state: Arc<Mutex<State>>,
+}
+
+/// Resources known to the mock, as API JSON objects keyed by ID
+#[derive(Default)]
+pub struct State {
+    next_id: u64,
+    pub servers: BTreeMap<u64, Value>,
+    pub networks: BTreeMap<u64, Value>,
+    pub firewalls: BTreeMap<u64, Value>,
+    pub ssh_keys: BTreeMap<u64, Value>,
+    pub volumes: BTreeMap<u64, Value>,
+    pub floating_ips: BTreeMap<u64, Value>,
+    pub primary_ips: BTreeMap<u64, Value>,
+    pub images: BTreeMap<u64, Value>,
+    pub load_balancers: BTreeMap<u64, Value>,
+    pub actions: BTreeMap<u64, Value>,
+    /// Reject every write like a read-only API token
+    pub read_only: bool,
+    /// Every request received, as `METHOD path`
+    pub requests: Vec<String>,
+    /// Answer this many of the next requests with `429 Too Many Requests`
+    pub rate_limited: u32,
+}
+
+impl MockHcloud {
+    /// Start the server on a random local port
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let server_state = state.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, server_state.clone()));
+            }
+        });
+
+        Self { base_url, state }
+    }
+
+    /// Client talking to this server
+    pub fn client(&self) -> HetznerCloudClient {
+        HetznerCloudClient::new("mock-token".to_string())
+            .unwrap()
+            .with_api_base(&self.base_url)
+    }
+
+    /// Inspect the current resources
+    pub fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+}
+
+/// Handle a single request and close the connection
+async fn serve(mut stream: TcpStream, state: Arc<Mutex<State>>) {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 8192];
+
+    let header_end = loop {
+        if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let content_length = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    while buffer.len() < header_end + content_length {
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+        }
+    }
+
+    let mut request_line = head.lines().next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let target = request_line.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let body: Value = serde_json::from_slice(&buffer[header_end..header_end + content_length])
+        .unwrap_or(Value::Null);
+
+    let (status, response) = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(format!("{} {}", method, target));
+        state.handle(&method, path, query, body)
+    };
+
+    let payload = response.to_string();
+    let reply = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        payload.len(),
+        payload
+    );
+    let _ = stream.write_all(reply.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+impl State {
+    /// Route a request, returning the status line and JSON body
+    fn handle(
+        &mut self,
+        method: &str,
+        path: &str,
+        query: &str,
+        body: Value,
+    ) -> (&'static str, Value) {
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        let id = |index: usize| segments.get(index).and_then(|s| s.parse::<u64>().ok());
+
+        if self.rate_limited > 0 {
+            self.rate_limited -= 1;
+            return (
+                "429 Too Many Requests",
+                json!({
+                    "error": {
+                        "code": "rate_limit_exceeded",
+                        "message": "limit of 3600 requests per hour reached",
+                        "details": null
+                    }
+                }),
+            );
+        }
+
+        if self.read_only && method != "GET" {
+            return (
+                "403 Forbidden",
+                json!({
+                    "error": {
+                        "code": "forbidden",
+                        "message": "insufficient permissions for this request",
+                        "details": null
+                    }
+                }),
+            );
+        }
+
+        if method == "DELETE" && self.is_protected(&segments) {
+            return (
+                "403 Forbidden",
+                json!({
+                    "error": {
+                        "code": "protected",
+                        "message": format!("{} is delete protected", path),
+                        "details": null
+                    }
+                }),
+            );
+        }
+
+        if method == "DELETE" && self.firewall_in_use(&segments) {
+            return (
+                "409 Conflict",
+                json!({
+                    "error": {
+                        "code": "resource_in_use",
+                        "message": format!("{} is still in use", path),
+                        "details": null
+                    }
+                }),
+            );
+        }
+
+        let result = match (method, segments.as_slice()) {
+            ("GET", ["locations"]) => Some(json!({ "locations": [location()] })),
+            ("GET", ["server_types"]) => Some(json!({ "server_types": [server_type("cpx21")] })),
+
+            ("GET", ["servers"]) => Some(json!({ "servers": values(&self.servers) })),
+            ("POST", ["servers"]) => Some(self.create_server(&body)),
+            ("GET", ["servers", _]) => id(1)
+                .and_then(|id| self.servers.get(&id))
+                .map(|server| json!({ "server": server })),
+            ("DELETE", ["servers", _]) => id(1)
+                .and_then(|id| self.servers.remove(&id))
+                .map(|_| json!({ "action": self.action("delete_server") })),
+            ("GET", ["servers", _, "metrics"]) => id(1)
+                .filter(|id| self.servers.contains_key(id))
+                .map(|_| metrics()),
+            ("POST", ["servers", _, "actions", command]) => {
+                id(1).and_then(|id| self.server_action(id, command, &body))
+            }
+
+            ("GET", ["actions"]) => {
+                let actions: Vec<&Value> = query
+                    .split('&')
+                    .filter_map(|param| param.strip_prefix("id="))
+                    .filter_map(|id| id.parse::<u64>().ok())
+                    .filter_map(|id| self.actions.get(&id))
+                    .collect();
+                Some(json!({ "actions": actions }))
+            }
+            ("GET", ["actions", _]) => id(1)
+                .and_then(|id| self.actions.get(&id))
+                .map(|action| json!({ "action": action })),
+
+            ("GET", ["networks"]) => Some(json!({ "networks": values(&self.networks) })),
+            ("POST", ["networks"]) => Some(self.create_network(&body)),
+            ("GET", ["networks", _]) => id(1)
+                .and_then(|id| self.networks.get(&id))
+                .map(|network| json!({ "network": network })),
+            ("POST", ["networks", _, "actions", command]) => {
+                id(1).and_then(|id| self.network_action(id, command, &body))
+            }
+            ("PUT", ["networks", _]) => id(1).and_then(|id| {
+                let network = self.networks.get_mut(&id)?;
+                network["labels"] = body["labels"].clone();
+                Some(json!({ "network": network }))
+            }),
+            ("DELETE", ["networks", _]) => id(1)
+                .and_then(|id| self.networks.remove(&id))
+                .map(|_| json!({})),
+
+            ("GET", ["firewalls"]) => Some(json!({ "firewalls": values(&self.firewalls) })),
+            ("POST", ["firewalls"]) => Some(self.create_firewall(&body)),
+            ("DELETE", ["firewalls", _]) => id(1)
+                .and_then(|id| self.firewalls.remove(&id))
+                .map(|_| json!({})),
+            ("POST", ["firewalls", _, "actions", "apply_to_resources"]) => {
+                id(1).and_then(|id| self.apply_firewall(id, &body))
+            }
+
+            ("GET", ["ssh_keys"]) => Some(json!({ "ssh_keys": values(&self.ssh_keys) })),
+            ("POST", ["ssh_keys"]) => Some(self.create_ssh_key(&body)),
+            ("DELETE", ["ssh_keys", _]) => id(1)
+                .and_then(|id| self.ssh_keys.remove(&id))
+                .map(|_| json!({})),
+
+            ("GET", ["volumes"]) => Some(json!({ "volumes": values(&self.volumes) })),
+            ("GET", ["floating_ips"]) => {
+                Some(json!({ "floating_ips": values(&self.floating_ips) }))
+            }
+            ("POST", ["floating_ips"]) => Some(self.create_floating_ip(&body)),
+            ("POST", ["floating_ips", _, "actions", command]) => {
+                id(1).and_then(|id| self.floating_ip_action(id, command, &body))
+            }
+            ("DELETE", ["floating_ips", _]) => id(1)
+                .and_then(|id| self.floating_ips.remove(&id))
+                .map(|_| json!({})),
+
+            ("GET", ["primary_ips"]) => Some(json!({ "primary_ips": values(&self.primary_ips) })),
+            ("POST", ["primary_ips", _, "actions", command]) => {
+                id(1).and_then(|id| self.primary_ip_action(id, command, &body))
+            }
+            ("PUT", ["primary_ips", _]) => id(1).and_then(|id| {
+                let primary_ip = self.primary_ips.get_mut(&id)?;
+                for field in ["auto_delete", "labels"] {
+                    if !body[field].is_null() {
+                        primary_ip[field] = body[field].clone();
+                    }
+                }
+                Some(json!({ "primary_ip": primary_ip }))
+            }),
+            ("DELETE", ["primary_ips", _]) => id(1)
+                .and_then(|id| self.primary_ips.remove(&id))
+                .map(|_| json!({})),
+
+            ("GET", ["images"]) => {
+                let selector = query
+                    .split('&')
+                    .find_map(|param| param.strip_prefix("label_selector="))
+                    .and_then(|selector| selector.split_once('='));
+                let images: Vec<&Value> = self
+                    .images
+                    .values()
+                    .filter(|image| {
+                        selector.is_none_or(|(key, value)| image["labels"][key] == json!(value))
+                    })
+                    .collect();
+                Some(json!({ "images": images }))
+            }
+            ("DELETE", ["images", _]) => id(1)
+                .and_then(|id| self.images.remove(&id))
+                .map(|_| json!({})),
+            ("GET", ["load_balancers"]) => {
+                Some(json!({ "load_balancers": values(&self.load_balancers) }))
+            }
+            ("DELETE", ["load_balancers", _]) => id(1)
+                .and_then(|id| self.load_balancers.remove(&id))
+                .map(|_| json!({})),
+
+            _ => None,
+        };
+
+        match result {
+            Some(response) if method == "POST" => ("201 Created", response),
+            Some(response) => ("200 OK", response),
+            None => (
+                "404 Not Found",
+                json!({
+                    "error": {
+                        "code": "not_found",
+                        "message": format!("{} {} not found", method, path),
+                        "details": null
+                    }
+                }),
+            ),
+        }
+    }
+
+    /// Whether the resource at `segments` has delete protection enabled
+    fn is_protected(&self, segments: &[&str]) -> bool {
+        let resources = match segments.first() {
+            Some(&"servers") => &self.servers,
+            Some(&"networks") => &self.networks,
+            _ => return false,
+        };
+        segments
+            .get(1)
+            .and_then(|id| id.parse::<u64>().ok())
+            .and_then(|id| resources.get(&id))
+            .is_some_and(|resource| resource["protection"]["delete"] == json!(true))
+    }
+
+    /// A firewall cannot be deleted while applied to an existing server
+    fn firewall_in_use(&self, segments: &[&str]) -> bool {
+        let ["firewalls", id] = segments else {
+            return false;
+        };
+        id.parse::<u64>()
+            .ok()
+            .and_then(|id| self.firewalls.get(&id))
+            .and_then(|firewall| firewall["applied_to"].as_array())
+            .is_some_and(|applied_to| {
+                applied_to.iter().any(|resource| {
+                    resource["server"]["id"]
+                        .as_u64()
+                        .is_some_and(|id| self.servers.contains_key(&id))
+                })
+            })
+    }
+
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    /// Record an action that has already succeeded
+    fn action(&mut self, command: &str) -> Value {
+        let id = self.next_id();
+        let action = json!({
+            "id": id,
+            "command": command,
+            "status": "success",
+            "progress": 100,
+            "started": CREATED,
+            "finished": CREATED,
+            "error": null
+        });
+        self.actions.insert(id, action.clone());
+        action
+    }
+
+    fn create_server(&mut self, body: &Value) -> Value {
+        let id = self.next_id();
+        let private_net: Vec<Value> = body["networks"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_u64)
+            .map(|network| {
+                json!({
+                    "network": network,
+                    "ip": format!("10.0.1.{}", id),
+                    "alias_ips": [],
+                    "mac_address": "86:00:00:00:00:01"
+                })
+            })
+            .collect();
+
+        let public_ipv4 = if body["public_net"]["enable_ipv4"] == json!(false) {
+            Value::Null
+        } else if let Some(primary_ip) = body["public_net"]["ipv4"]
+            .as_u64()
+            .and_then(|primary_ip| self.primary_ips.get_mut(&primary_ip))
+        {
+            primary_ip["assignee_id"] = json!(id);
+            json!({ "ip": primary_ip["ip"], "blocked": false })
+        } else {
+            json!({ "ip": format!("192.0.2.{}", id), "blocked": false })
+        };
+
+        let status = if body["start_after_create"] == json!(false) {
+            "off"
+        } else {
+            "running"
+        };
+        let server = json!({
+            "id": id,
+            "name": body["name"],
+            "status": status,
+            "server_type": server_type(body["server_type"].as_str().unwrap_or_default()),
+            "datacenter": {
+                "id": 1,
+                "name": "nbg1-dc3",
+                "description": "Nuremberg 1 virtual DC 3",
+                "location": location()
+            },
+            "public_net": {
+                "ipv4": public_ipv4,
+                "ipv6": null,
+                "floating_ips": []
+            },
+            "private_net": private_net,
+            "created": CREATED,
+            "protection": { "delete": false, "rebuild": false },
+            "labels": body["labels"].as_object().cloned().unwrap_or_default()
+        });
+        self.servers.insert(id, server.clone());
+
+        json!({
+            "server": server,
+            "action": self.action("create_server"),
+            "root_password": null
+        })
+    }
+
+    fn server_action(&mut self, id: u64, command: &str, body: &Value) -> Option<Value> {
+        if command == "create_image" {
+            return self.create_image(id, body);
+        }
+        let server = self.servers.get_mut(&id)?;
+        match command {
+            "poweron" | "reset" => server["status"] = json!("running"),
+            "poweroff" | "shutdown" => server["status"] = json!("off"),
+            "change_type" => {
+                server["server_type"] =
+                    server_type(body["server_type"].as_str().unwrap_or_default())
+            }
+            "change_protection" => {
+                server["protection"] =
+                    json!({ "delete": body["delete"], "rebuild": body["rebuild"] })
+            }
+            "attach_to_network" => {
+                let private_net = json!({
+                    "network": body["network"],
+                    "ip": body["ip"],
+                    "alias_ips": [],
+                    "mac_address": "86:00:00:00:00:01"
+                });
+                server["private_net"].as_array_mut()?.push(private_net);
+            }
+            "detach_from_network" => server["private_net"]
+                .as_array_mut()?
+                .retain(|private_net| private_net["network"] != body["network"]),
+            "enable_rescue" | "change_dns_ptr" => {}
+            _ => return None,
+        }
+        Some(json!({ "action": self.action(command) }))
+    }
+
+    fn create_image(&mut self, server_id: u64, body: &Value) -> Option<Value> {
+        self.servers.get(&server_id)?;
+        let id = self.next_id();
+        let image = json!({
+            "id": id,
+            "type": body["type"],
+            "description": body["description"],
+            "created_from": { "id": server_id },
+            "labels": body["labels"].as_object().cloned().unwrap_or_default()
+        });
+        self.images.insert(id, image.clone());
+        Some(json!({ "image": image, "action": self.action("create_image") }))
+    }
+
+    fn create_network(&mut self, body: &Value) -> Value {
+        let id = self.next_id();
+        let subnets: Vec<Value> = body["subnets"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(subnet)
+            .collect();
+
+        let network = json!({
+            "id": id,
+            "name": body["name"],
+            "ip_range": body["ip_range"],
+            "subnets": subnets,
+            "routes": body["routes"].as_array().cloned().unwrap_or_default(),
+            "servers": [],
+            "created": CREATED,
+            "protection": { "delete": false },
+            "labels": body["labels"].as_object().cloned().unwrap_or_default()
+        });
+        self.networks.insert(id, network.clone());
+        json!({ "network": network })
+    }
+
+    fn network_action(&mut self, id: u64, command: &str, body: &Value) -> Option<Value> {
+        if command == "change_protection" {
+            self.networks.get_mut(&id)?["protection"] = json!({ "delete": body["delete"] });
+            return Some(json!({ "action": self.action(command) }));
+        }
+        if command == "add_subnet" {
+            let subnet = subnet(body);
+            self.networks.get_mut(&id)?["subnets"]
+                .as_array_mut()?
+                .push(subnet);
+            return Some(json!({ "action": self.action(command) }));
+        }
+        let routes = self.networks.get_mut(&id)?["routes"].as_array_mut()?;
+        let route = json!({
+            "destination": body["destination"],
+            "gateway": body["gateway"]
+        });
+        match command {
+            "add_route" => routes.push(route),
+            "delete_route" => routes.retain(|r| *r != route),
+            _ => return None,
+        }
+        Some(json!({ "action": self.action(command) }))
+    }
+
+    fn create_firewall(&mut self, body: &Value) -> Value {
+        let id = self.next_id();
+        let firewall = json!({
+            "id": id,
+            "name": body["name"],
+            "rules": body["rules"],
+            "applied_to": [],
+            "created": CREATED,
+            "labels": body["labels"]
+        });
+        self.firewalls.insert(id, firewall.clone());
+        json!({ "firewall": firewall, "actions": [] })
+    }
+
+    fn apply_firewall(&mut self, id: u64, body: &Value) -> Option<Value> {
+        let firewall = self.firewalls.get_mut(&id)?;
+        let applied_to = firewall["applied_to"].as_array_mut()?;
+        applied_to.extend(body["apply_to"].as_array().cloned().unwrap_or_default());
+        Some(json!({ "actions": [self.action("apply_firewall")] }))
+    }
+
+    fn create_ssh_key(&mut self, body: &Value) -> Value {
+        let id = self.next_id();
+        let ssh_key = json!({
+            "id": id,
+            "name": body["name"],
+            "fingerprint": format!("mock:{}", id),
+            "public_key": body["public_key"],
+            "labels": body["labels"].as_object().cloned().unwrap_or_default(),
+            "created": CREATED
+        });
+        self.ssh_keys.insert(id, ssh_key.clone());
+        json!({ "ssh_key": ssh_key })
+    }
+
+    fn create_floating_ip(&mut self, body: &Value) -> Value {
+        let id = self.next_id();
+        let floating_ip = json!({
+            "id": id,
+            "name": body["name"],
+            "ip": format!("203.0.113.{}", id),
+            "type": body["type"],
+            "server": body["server"],
+            "protection": { "delete": false },
+            "labels": body["labels"].as_object().cloned().unwrap_or_default()
+        });
+        self.floating_ips.insert(id, floating_ip.clone());
+        json!({ "floating_ip": floating_ip, "action": self.action("create_floating_ip") })
+    }
+
+    fn floating_ip_action(&mut self, id: u64, command: &str, body: &Value) -> Option<Value> {
+        let floating_ip = self.floating_ips.get_mut(&id)?;
+        match command {
+            "assign" => floating_ip["server"] = body["server"].clone(),
+            "unassign" => floating_ip["server"] = Value::Null,
+            _ => return None,
+        }
+        Some(json!({ "action": self.action(&format!("{}_floating_ip", command)) }))
+    }
+
+    fn primary_ip_action(&mut self, id: u64, command: &str, body: &Value) -> Option<Value> {
+        let primary_ip = self.primary_ips.get_mut(&id)?;
+        match command {
+            "assign" => primary_ip["assignee_id"] = body["assignee_id"].clone(),
+            "unassign" => primary_ip["assignee_id"] = Value::Null,
+            _ => return None,
+        }
+        Some(json!({ "action": self.action(&format!("{}_primary_ip", command)) }))
+    }
+}
+
+/// Subnet as returned by the API; vSwitch subnets get their own gateway
+fn subnet(request: &Value) -> Value {
+    let gateway = match (request["type"].as_str(), request["ip_range"].as_str()) {
+        (Some("vswitch"), Some(range)) => {
+            let network = range.split('/').next().unwrap_or_default();
+            let prefix = network.rsplit_once('.').map_or(network, |(p, _)| p);
+            format!("{}.1", prefix)
+        }
+        _ => "10.0.0.1".to_string(),
+    };
+    json!({
+        "ip_range": request["ip_range"],
+        "network_zone": request["network_zone"],
+        "gateway": gateway,
+        "type": request["type"],
+        "vswitch_id": request["vswitch_id"]
+    })
+}
+
+fn values(map: &BTreeMap<u64, Value>) -> Vec<Value> {
+    map.values().cloned().collect()
+}
+
+fn location() -> Value {
+    json!({
+        "id": 1,
+        "name": "nbg1",
+        "description": "Nuremberg DC Park 1",
+        "country": "DE",
+        "city": "Nuremberg",
+        "latitude": 49.452102,
+        "longitude": 11.076665,
+        "network_zone": "eu-central"
+    })
+}
+
+fn server_type(name: &str) -> Value {
+    json!({
+        "id": 1,
+        "name": name,
+        "description": name.to_uppercase(),
+        "cores": 2,
+        "memory": 4.0,
+        "disk": 40,
+        "prices": [{ "location": "nbg1" }]
+    })
+}
+
+/// Two samples of every metric oxide reads
+fn metrics() -> Value {
+    let series = |first: &str, second: &str| json!({ "values": [[1704067200.0, first], [1704067260.0, second]] });
+    json!({
+        "metrics": {
+            "start": CREATED,
+            "end": CREATED,
+            "step": 60.0,
+            "time_series": {
+                "cpu": series("10", "30"),
+                "disk.0.bandwidth.read": series("1000", "3000"),
+                "disk.0.bandwidth.write": series("0", "0"),
+                "network.0.bandwidth.in": series("2048", "2048"),
+                "network.0.bandwidth.out": series("512", "1536")
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ClusterConfig;
+    use crate::hcloud::network::NetworkManager;
+    use crate::hcloud::server::{NodeRole, ServerManager};
+    use crate::hcloud::{FirewallManager, SSHKeyManager};
+
+    #[tokio::test]
+    async fn test_cluster_lifecycle() {
+        let mock = MockHcloud::start().await;
+        let client = mock.client();
+        let mut config = ClusterConfig::example();
+        config.control_planes[0].count = 1;
+        config.workers[0].count = 2;
+        let cluster = config.cluster_name.as_str();
+
+        // Create
+        let networks = NetworkManager::new(client.clone())
+            .ensure_networks(cluster, &config.hcloud)
+            .await
+            .unwrap();
+        let (ssh_key, private_key) = SSHKeyManager::new(client.clone())
+            .ensure_ssh_key(cluster)
+            .await
+            .unwrap();
+        assert!(private_key.is_some());
+        let firewall_manager = FirewallManager::new(client.clone());
+        let firewall = firewall_manager
+            .create_cluster_firewall(cluster, &["203.0.113.7".to_string()])
+            .await
+            .unwrap();
+
+        let server_manager = ServerManager::new(client.clone());
+        let control_planes = server_manager
+            .create_control_planes(
+                cluster,
+                &config.control_planes,
+                &config.hcloud.location,
+                &networks,
+                &config.talos.version,
+                Some("123"),
+                Some(ssh_key.id),
+                None,
+            )
+            .await
+            .unwrap();
+        let workers = server_manager
+            .create_workers(
+                cluster,
+                &config.workers,
+                &config.hcloud.location,
+                &networks,
+                &config.talos.version,
+                Some("123"),
+                Some(ssh_key.id),
+                None,
+                &Default::default(),
+            )
+            .await
+            .unwrap();
+        let ids: Vec<u64> = control_planes
+            .iter()
+            .chain(&workers)
+            .map(|s| s.server.id)
+            .collect();
+        firewall_manager
+            .apply_to_servers(firewall.id, ids)
+            .await
+            .unwrap();
+
+        let servers = server_manager.list_cluster_servers(cluster).await.unwrap();
+        assert_eq!(servers.len(), 3);
+        assert_eq!(
+            ServerManager::filter_by_role_and_pool(&servers, NodeRole::Worker, Some("worker"))
+                .len(),
+            2
+        );
+
+        // Re-running create reuses existing resources
+        let (_, private_key) = SSHKeyManager::new(client.clone())
+            .ensure_ssh_key(cluster)
+            .await
+            .unwrap();
+        assert!(private_key.is_none());
+        assert_eq!(mock.state().networks.len(), 1);
+
+        // Scale: resize one worker in place, remove the other
+        server_manager
+            .resize_server(&workers[0].server, "cpx41", 10)
+            .await
+            .unwrap();
+        server_manager
+            .delete_servers(std::slice::from_ref(&workers[1]))
+            .await
+            .unwrap();
+        let servers = server_manager.list_cluster_servers(cluster).await.unwrap();
+        assert_eq!(servers.len(), 2);
+        let resized = servers
+            .iter()
+            .find(|s| s.server.id == workers[0].server.id)
+            .unwrap();
+        assert_eq!(resized.server.server_type.name, "cpx41");
+        assert_eq!(resized.server.status, "running");
+
+        // Destroy
+        server_manager
+            .delete_cluster_servers(cluster)
+            .await
+            .unwrap();
+        firewall_manager
+            .delete_cluster_firewall(cluster)
+            .await
+            .unwrap();
+        NetworkManager::new(client.clone())
+            .delete_networks(cluster, &config.hcloud)
+            .await
+            .unwrap();
+        SSHKeyManager::new(client)
+            .delete_cluster_ssh_key(cluster)
+            .await
+            .unwrap();
+
+        let state = mock.state();
+        assert!(state.servers.is_empty());
+        assert!(state.firewalls.is_empty());
+        assert!(state.networks.is_empty());
+        assert!(state.ssh_keys.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_resources_return_api_errors() {
+        let mock = MockHcloud::start().await;
+        let err = mock.client().get_server(42).await.unwrap_err().to_string();
+        assert!(err.contains("not_found"), "{}", err);
+    }
+}
@@ -1,10 +1,19 @@
 /// Hetzner Cloud API client implementation
 pub mod client;
+pub mod expiry;
 pub mod firewall;
+pub mod hibernate;
+pub mod ip;
+#[cfg(test)]
+pub mod mock;
 pub mod models;
+pub mod nat;
 pub mod network;
+pub mod projects;
 pub mod server;
 pub mod ssh_key;
+pub mod teardown;
+pub mod user_data;
 
 pub use client::HetznerCloudClient;
 pub use firewall::FirewallManager;
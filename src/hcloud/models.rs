@@ -1,5 +1,7 @@
 /// Hetzner Cloud API data models
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 /// Hetzner Cloud server resource
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,6 +16,14 @@ pub struct Server {
     pub created: String,
     #[serde(default)]
     pub labels: std::collections::HashMap<String, String>,
+    #[serde(default)]
+    pub protection: Protection,
+}
+
+/// Protection flags of a resource
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Protection {
+    pub delete: bool,
 }
 
 /// Server type information
@@ -25,6 +35,15 @@ pub struct ServerType {
     pub cores: u32,
     pub memory: f64,
     pub disk: u64,
+    /// Per-location prices; a type is only orderable where it has a price
+    #[serde(default)]
+    pub prices: Vec<ServerTypePrice>,
+}
+
+/// Price entry of a server type in one location
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerTypePrice {
+    pub location: String,
 }
 
 /// Datacenter information
@@ -46,6 +65,8 @@ pub struct Location {
     pub city: String,
     pub latitude: f64,
     pub longitude: f64,
+    #[serde(default)]
+    pub network_zone: String,
 }
 
 /// Public network configuration
@@ -61,6 +82,9 @@ pub struct PublicNetwork {
 pub struct IPv4 {
     pub ip: String,
     pub blocked: bool,
+    /// Reverse DNS entry of the address
+    #[serde(default)]
+    pub dns_ptr: Option<String>,
 }
 
 /// IPv6 address information
@@ -89,6 +113,10 @@ pub struct Network {
     pub routes: Vec<Route>,
     pub servers: Vec<u64>,
     pub created: String,
+    #[serde(default)]
+    pub protection: Protection,
+    #[serde(default)]
+    pub labels: std::collections::HashMap<String, String>,
 }
 
 /// Network subnet
@@ -120,6 +148,194 @@ pub struct SSHKey {
     pub created: String,
 }
 
+/// Volume resource
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Volume {
+    pub id: u64,
+    pub name: String,
+    /// Server the volume is attached to
+    pub server: Option<u64>,
+    #[serde(default)]
+    pub labels: std::collections::HashMap<String, String>,
+}
+
+/// Address family of a floating or primary IP
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpType {
+    Ipv4,
+    Ipv6,
+}
+
+/// Floating IP resource
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FloatingIp {
+    pub id: u64,
+    pub name: String,
+    /// Address, or the /64 network for IPv6
+    pub ip: String,
+    #[serde(rename = "type")]
+    pub ip_type: IpType,
+    /// Server the IP is assigned to
+    pub server: Option<u64>,
+    #[serde(default)]
+    pub protection: Protection,
+    #[serde(default)]
+    pub labels: std::collections::HashMap<String, String>,
+}
+
+/// Primary IP resource, the public address of a server
+///
+/// Unlike floating IPs, a primary IP can only be (un)assigned while its
+/// server is powered off. With `auto_delete` it is deleted with the server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrimaryIp {
+    pub id: u64,
+    pub name: String,
+    pub ip: String,
+    #[serde(rename = "type")]
+    pub ip_type: IpType,
+    /// Server the IP is assigned to
+    pub assignee_id: Option<u64>,
+    pub auto_delete: bool,
+    #[serde(default)]
+    pub protection: Protection,
+    #[serde(default)]
+    pub labels: std::collections::HashMap<String, String>,
+}
+
+/// Image resource, e.g. a server snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Image {
+    pub id: u64,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub labels: std::collections::HashMap<String, String>,
+}
+
+/// Load balancer resource
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoadBalancer {
+    pub id: u64,
+    pub name: String,
+    #[serde(default)]
+    pub labels: std::collections::HashMap<String, String>,
+    #[serde(default)]
+    pub private_net: Vec<LoadBalancerPrivateNet>,
+}
+
+/// Network a load balancer is attached to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoadBalancerPrivateNet {
+    pub network: u64,
+}
+
+/// Metric group of `GET /servers/{id}/metrics`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricType {
+    /// `cpu`, in percent
+    Cpu,
+    /// `disk.0.iops.read`/`write` and `disk.0.bandwidth.read`/`write` (bytes/s)
+    Disk,
+    /// `network.0.pps.in`/`out` and `network.0.bandwidth.in`/`out` (bytes/s)
+    Network,
+}
+
+impl MetricType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MetricType::Cpu => "cpu",
+            MetricType::Disk => "disk",
+            MetricType::Network => "network",
+        }
+    }
+}
+
+/// Time range of a metrics query
+#[derive(Debug, Clone, Copy)]
+pub struct MetricsRange {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Seconds between samples; the API picks one from the range length if unset
+    pub step: Option<u32>,
+}
+
+impl MetricsRange {
+    /// The given duration up to now, with the step picked by the API
+    pub fn last(duration: chrono::Duration) -> Self {
+        let end = Utc::now();
+        Self {
+            start: end - duration,
+            end,
+            step: None,
+        }
+    }
+}
+
+/// Metrics of a server, one time series per metric name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerMetrics {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Seconds between samples
+    pub step: f64,
+    pub time_series: std::collections::HashMap<String, TimeSeries>,
+}
+
+/// Samples of one metric
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeSeries {
+    pub values: Vec<MetricSample>,
+}
+
+/// One sample, sent by the API as a `[unix timestamp, "value"]` pair
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "(f64, String)", into = "(f64, String)")]
+pub struct MetricSample {
+    pub time: DateTime<Utc>,
+    pub value: f64,
+}
+
+impl TryFrom<(f64, String)> for MetricSample {
+    type Error = String;
+
+    fn try_from((timestamp, value): (f64, String)) -> Result<Self, Self::Error> {
+        let time = DateTime::from_timestamp_millis((timestamp * 1000.0) as i64)
+            .ok_or_else(|| format!("invalid metric timestamp {}", timestamp))?;
+        let value = value
+            .parse()
+            .map_err(|_| format!("invalid metric value {:?}", value))?;
+        Ok(Self { time, value })
+    }
+}
+
+impl From<MetricSample> for (f64, String) {
+    fn from(sample: MetricSample) -> Self {
+        (
+            sample.time.timestamp_millis() as f64 / 1000.0,
+            sample.value.to_string(),
+        )
+    }
+}
+
+impl ServerMetrics {
+    /// Mean of a metric over the queried range, None without samples
+    pub fn average(&self, name: &str) -> Option<f64> {
+        let values = &self.time_series.get(name)?.values;
+        if values.is_empty() {
+            return None;
+        }
+        Some(values.iter().map(|sample| sample.value).sum::<f64>() / values.len() as f64)
+    }
+}
+
+/// Server metrics response
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServerMetricsResponse {
+    pub metrics: ServerMetrics,
+}
+
 /// Action represents an asynchronous operation
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Action {
@@ -174,6 +390,18 @@ pub struct ServerListResponse {
     pub servers: Vec<Server>,
 }
 
+/// Location list response
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LocationListResponse {
+    pub locations: Vec<Location>,
+}
+
+/// Server type list response
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServerTypeListResponse {
+    pub server_types: Vec<ServerType>,
+}
+
 /// Network list response
 #[derive(Debug, Serialize, Deserialize)]
 pub struct NetworkListResponse {
@@ -187,12 +415,68 @@ pub struct SSHKeyListResponse {
     pub ssh_keys: Vec<SSHKey>,
 }
 
+/// Volume list response
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VolumeListResponse {
+    pub volumes: Vec<Volume>,
+}
+
+/// Floating IP list response
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FloatingIpListResponse {
+    pub floating_ips: Vec<FloatingIp>,
+}
+
+/// Primary IP list response
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PrimaryIpListResponse {
+    pub primary_ips: Vec<PrimaryIp>,
+}
+
+/// Floating IP creation response
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateFloatingIpResponse {
+    pub floating_ip: FloatingIp,
+    pub action: Option<Action>,
+}
+
+/// Primary IP response
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PrimaryIpResponse {
+    pub primary_ip: PrimaryIp,
+}
+
+/// Image list response
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImageListResponse {
+    pub images: Vec<Image>,
+}
+
+/// Image creation response of the `create_image` server action
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateImageResponse {
+    pub image: Image,
+    pub action: Action,
+}
+
+/// Load balancer list response
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoadBalancerListResponse {
+    pub load_balancers: Vec<LoadBalancer>,
+}
+
 /// Action response
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ActionResponse {
     pub action: Action,
 }
 
+/// Action list response
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ActionListResponse {
+    pub actions: Vec<Action>,
+}
+
 /// Error response from API
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ErrorResponse {
@@ -221,6 +505,8 @@ pub struct Firewall {
 /// Firewall rule
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FirewallRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
     pub direction: String,
     pub source_ips: Vec<String>,
     pub destination_ips: Vec<String>,
@@ -254,3 +540,9 @@ pub struct CreateFirewallResponse {
 pub struct FirewallListResponse {
     pub firewalls: Vec<Firewall>,
 }
+
+/// Whether `labels` mark a resource oxide created for `cluster_name`
+pub fn is_cluster_resource(labels: &HashMap<String, String>, cluster_name: &str) -> bool {
+    labels.get("cluster").map(String::as_str) == Some(cluster_name)
+        && labels.get("managed-by").map(String::as_str) == Some("oxide")
+}
@@ -0,0 +1,254 @@
+/// NAT gateway for worker pools without public IPs
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+use super::client::{CreateServerRequest, HetznerCloudClient};
+use super::models::{Network, Server};
+use super::network::NetworkManager;
+use crate::config::HetznerCloudConfig;
+
+/// Label marking the NAT gateway of a cluster
+///
+/// Deliberately not the `cluster` label, so the gateway never shows up as a
+/// Kubernetes node.
+const NAT_GATEWAY_LABEL: &str = "nat-gateway";
+
+/// Destination of the network route through the gateway
+const DEFAULT_ROUTE: &str = "0.0.0.0/0";
+
+/// NAT gateway manager for handling the gateway server and its route
+pub struct NatGatewayManager {
+    client: HetznerCloudClient,
+}
+
+impl NatGatewayManager {
+    /// Create a new NAT gateway manager
+    pub fn new(client: HetznerCloudClient) -> Self {
+        Self { client }
+    }
+
+    /// Create or get the NAT gateway and route the network's egress through it
+    pub async fn ensure_gateway(
+        &self,
+        cluster_name: &str,
+        config: &HetznerCloudConfig,
+        network: &Network,
+        ssh_key_id: Option<u64>,
+    ) -> Result<Server> {
+        let server = match self.find_gateway(cluster_name).await? {
+            Some(server) => {
+                info!(
+                    "Found existing NAT gateway: {} (ID: {})",
+                    server.name, server.id
+                );
+                server
+            }
+            None => {
+                self.create_gateway(cluster_name, config, network, ssh_key_id)
+                    .await?
+            }
+        };
+
+        let private_ip = server
+            .private_net
+            .iter()
+            .find(|net| net.network == network.id)
+            .map(|net| net.ip.clone())
+            .context(format!(
+                "NAT gateway {} is not attached to network {}",
+                server.name, network.name
+            ))?;
+
+        NetworkManager::new(self.client.clone())
+            .ensure_route(network.id, DEFAULT_ROUTE, &private_ip)
+            .await?;
+        info!("✓ NAT gateway ready: {}", server.name);
+
+        Ok(server)
+    }
+
+    async fn create_gateway(
+        &self,
+        cluster_name: &str,
+        config: &HetznerCloudConfig,
+        network: &Network,
+        ssh_key_id: Option<u64>,
+    ) -> Result<Server> {
+        let name = format!("{}-nat", cluster_name);
+        info!(
+            "Creating NAT gateway: {} (type: {})",
+            name, config.nat_gateway.server_type
+        );
+
+        let labels: HashMap<String, String> = [
+            (NAT_GATEWAY_LABEL.to_string(), cluster_name.to_string()),
+            ("managed-by".to_string(), "oxide".to_string()),
+        ]
+        .into_iter()
+        .collect();
+
+        let request = CreateServerRequest {
+            name: name.clone(),
+            server_type: config.nat_gateway.server_type.clone(),
+            location: config.location.clone(),
+            image: config.nat_gateway.image.clone(),
+            ssh_keys: ssh_key_id.map(|id| vec![id]),
+            user_data: Some(cloud_init(&network.ip_range)),
+            networks: Some(vec![network.id]),
+            labels: Some(labels),
+            automount: Some(false),
+            start_after_create: Some(true),
+            public_net: None,
+        };
+
+        let response = self
+            .client
+            .create_server(request)
+            .await
+            .context(format!("Failed to create NAT gateway {}", name))?;
+        self.client
+            .wait_for_action(response.action.id, 300)
+            .await
+            .context("NAT gateway creation action failed")?;
+
+        self.client
+            .get_server(response.server.id)
+            .await
+            .context("Failed to get NAT gateway details")
+    }
+
+    /// Find the NAT gateway of a cluster
+    pub async fn find_gateway(&self, cluster_name: &str) -> Result<Option<Server>> {
+        let servers = self.client.list_servers().await?;
+        Ok(servers.into_iter().find(|server| {
+            server.labels.get(NAT_GATEWAY_LABEL).map(String::as_str) == Some(cluster_name)
+        }))
+    }
+
+    /// Delete the NAT gateway of a cluster and its route
+    pub async fn delete_gateway(&self, cluster_name: &str) -> Result<()> {
+        let Some(server) = self.find_gateway(cluster_name).await? else {
+            return Ok(());
+        };
+        let network_manager = NetworkManager::new(self.client.clone());
+        for net in &server.private_net {
+            network_manager
+                .delete_route(net.network, DEFAULT_ROUTE)
+                .await?;
+        }
+        info!("Deleting NAT gateway: {} (ID: {})", server.name, server.id);
+        let deleted = async {
+            let action = self.client.delete_server(server.id).await?;
+            self.client.wait_for_action(action.id, 120).await
+        };
+        if let Err(e) = deleted.await {
+            warn!("Failed to delete NAT gateway {}: {}", server.name, e);
+        }
+        Ok(())
+    }
+}
+
+/// cloud-init enabling forwarding and masquerading for the network CIDR
+///
+/// nftables ships with Debian and restores `/etc/nftables.conf` on boot.
+fn cloud_init(network_cidr: &str) -> String {
+    format!(
+        r#"#cloud-config
+write_files:
+  - path: /etc/sysctl.d/99-oxide-nat.conf
+    content: |
+      net.ipv4.ip_forward = 1
+  - path: /etc/nftables.conf
+    content: |
+      #!/usr/sbin/nft -f
+      flush ruleset
+      table ip nat {{
+        chain postrouting {{
+          type nat hook postrouting priority srcnat; policy accept;
+          ip saddr {cidr} oifname "eth0" masquerade
+        }}
+      }}
+runcmd:
+  - sysctl --system
+  - systemctl enable --now nftables
+"#,
+        cidr = network_cidr
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ClusterConfig;
+    use crate::hcloud::mock::MockHcloud;
+
+    #[test]
+    fn test_cloud_init() {
+        let user_data = cloud_init("10.0.0.0/16");
+        assert!(user_data.starts_with("#cloud-config\n"));
+        assert!(user_data.contains("ip saddr 10.0.0.0/16 oifname \"eth0\" masquerade"));
+
+        let parsed: serde_yaml::Value = serde_yaml::from_str(&user_data).unwrap();
+        assert_eq!(parsed["write_files"].as_sequence().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_gateway_lifecycle() {
+        let mock = MockHcloud::start().await;
+        let client = mock.client();
+        let config = ClusterConfig::example();
+        let cluster = config.cluster_name.as_str();
+
+        let networks = NetworkManager::new(client.clone())
+            .ensure_networks(cluster, &config.hcloud)
+            .await
+            .unwrap();
+        let manager = NatGatewayManager::new(client.clone());
+        let gateway = manager
+            .ensure_gateway(cluster, &config.hcloud, networks.primary(), None)
+            .await
+            .unwrap();
+        assert_eq!(gateway.name, "talos-cluster-nat");
+
+        let routes = mock.state().networks[&networks.primary().id]["routes"].clone();
+        assert_eq!(routes[0]["destination"], DEFAULT_ROUTE);
+        assert_eq!(routes[0]["gateway"], gateway.private_net[0].ip.as_str());
+
+        // Gateways are not cluster nodes
+        let nodes = crate::hcloud::server::ServerManager::new(client.clone())
+            .list_cluster_servers(cluster)
+            .await
+            .unwrap();
+        assert!(nodes.is_empty());
+
+        manager.delete_gateway(cluster).await.unwrap();
+        assert!(mock.state().servers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_gateway_routes() {
+        let mock = MockHcloud::start().await;
+        let client = mock.client();
+        let config = ClusterConfig::example();
+        let cluster = config.cluster_name.as_str();
+
+        let networks = NetworkManager::new(client.clone())
+            .ensure_networks(cluster, &config.hcloud)
+            .await
+            .unwrap();
+        let network_id = networks.primary().id;
+        let manager = NatGatewayManager::new(client.clone());
+        manager
+            .ensure_gateway(cluster, &config.hcloud, networks.primary(), None)
+            .await
+            .unwrap();
+        let routes = mock.state().networks[&network_id]["routes"].clone();
+        assert_eq!(routes.as_array().unwrap().len(), 1);
+
+        // The route goes with the gateway, not only with the network
+        manager.delete_gateway(cluster).await.unwrap();
+        let routes = mock.state().networks[&network_id]["routes"].clone();
+        assert!(routes.as_array().unwrap().is_empty());
+    }
+}
@@ -1,34 +1,112 @@
 /// Network management for Hetzner Cloud
 use anyhow::{Context, Result};
+use std::collections::HashMap;
 use tracing::info;
 
 use super::client::{CreateNetworkRequest, HetznerCloudClient, SubnetRequest};
-use super::models::Network;
-use crate::config::NetworkConfig;
+use super::models::{is_cluster_resource, Network};
+use crate::config::schema::location_zone;
+use crate::config::{HetznerCloudConfig, NetworkConfig, RobotConfig};
 
 /// Network manager for handling Hetzner Cloud networks
 pub struct NetworkManager {
     client: HetznerCloudClient,
+    delete_protection: bool,
+}
+
+/// Private networks of a cluster
+///
+/// Hetzner networks cannot span network zones, so multi-region clusters get
+/// one network per extra zone; KubeSpan connects the nodes across them.
+pub struct ClusterNetworks {
+    primary: Network,
+    /// Extra networks keyed by network zone
+    regions: HashMap<String, Network>,
+}
+
+impl ClusterNetworks {
+    /// Network of the main network zone (`hcloud.network`)
+    pub fn primary(&self) -> &Network {
+        &self.primary
+    }
+
+    /// Network that servers in `location` attach to
+    pub fn for_location(&self, location: &str) -> &Network {
+        location_zone(location)
+            .and_then(|zone| self.regions.get(zone))
+            .unwrap_or(&self.primary)
+    }
+}
+
+/// Name of the cluster network in a network zone
+///
+/// The primary network (`zone` None) keeps the plain `<cluster>-network` name.
+fn network_name(cluster_name: &str, zone: Option<&str>) -> String {
+    match zone {
+        Some(zone) => format!("{}-network-{}", cluster_name, zone),
+        None => format!("{}-network", cluster_name),
+    }
+}
+
+/// Names of the primary network and the per-region networks of a cluster
+pub fn cluster_network_names(cluster_name: &str, config: &HetznerCloudConfig) -> Vec<String> {
+    std::iter::once(network_name(cluster_name, None))
+        .chain(
+            config
+                .regions
+                .iter()
+                .map(|region| network_name(cluster_name, Some(&region.zone))),
+        )
+        .collect()
 }
 
 impl NetworkManager {
     /// Create a new network manager
     pub fn new(client: HetznerCloudClient) -> Self {
-        Self { client }
+        Self {
+            client,
+            delete_protection: false,
+        }
     }
 
-    /// Create or get existing network for the cluster
-    pub async fn ensure_network(
+    /// Enable delete protection on new networks
+    pub fn with_delete_protection(mut self, enabled: bool) -> Self {
+        self.delete_protection = enabled;
+        self
+    }
+
+    /// Create or get the existing networks of the cluster
+    pub async fn ensure_networks(
         &self,
         cluster_name: &str,
+        config: &HetznerCloudConfig,
+    ) -> Result<ClusterNetworks> {
+        let primary = self
+            .ensure_network(
+                cluster_name,
+                &network_name(cluster_name, None),
+                &config.network,
+            )
+            .await?;
+        let mut regions = HashMap::new();
+        for region in &config.regions {
+            let name = network_name(cluster_name, Some(&region.zone));
+            let network = self.ensure_network(cluster_name, &name, region).await?;
+            regions.insert(region.zone.clone(), network);
+        }
+        Ok(ClusterNetworks { primary, regions })
+    }
+
+    /// Create or get an existing network
+    async fn ensure_network(
+        &self,
+        cluster_name: &str,
+        name: &str,
         config: &NetworkConfig,
     ) -> Result<Network> {
         // Check if network already exists
         let networks = self.client.list_networks().await?;
-        if let Some(network) = networks
-            .into_iter()
-            .find(|n| n.name == format!("{}-network", cluster_name))
-        {
+        if let Some(network) = networks.into_iter().find(|n| n.name == name) {
             info!(
                 "Found existing network: {} (ID: {})",
                 network.name, network.id
@@ -36,15 +114,16 @@ impl NetworkManager {
             return Ok(network);
         }
 
-        info!("Creating new private network: {}-network", cluster_name);
+        info!("Creating new private network: {}", name);
 
         let request = CreateNetworkRequest {
-            name: format!("{}-network", cluster_name),
+            name: name.to_string(),
             ip_range: config.cidr.clone(),
             subnets: Some(vec![SubnetRequest {
                 ip_range: config.subnet_cidr.clone(),
                 network_zone: config.zone.clone(),
                 subnet_type: "cloud".to_string(),
+                vswitch_id: None,
             }]),
             routes: None,
             labels: Some(
@@ -68,49 +147,240 @@ impl NetworkManager {
             network.name, network.id
         );
 
+        if self.delete_protection {
+            self.set_delete_protection(&network, true).await?;
+        }
+
         Ok(network)
     }
 
-    /// Delete network by name
-    pub async fn delete_network(&self, cluster_name: &str) -> Result<()> {
-        let networks = self.client.list_networks().await?;
+    /// Couple the Robot vSwitch to a network, returning the subnet gateway
+    ///
+    /// Robot servers route the cloud network through this gateway on their
+    /// VLAN interface.
+    pub async fn ensure_vswitch_subnet(
+        &self,
+        network_id: u64,
+        network_zone: &str,
+        robot: &RobotConfig,
+    ) -> Result<String> {
+        let network = self.client.get_network(network_id).await?;
+        if let Some(subnet) = network
+            .subnets
+            .iter()
+            .find(|s| s.ip_range == robot.subnet_cidr)
+        {
+            if subnet.subnet_type != "vswitch" {
+                anyhow::bail!(
+                    "Subnet {} of network {} is a {} subnet, not the vSwitch",
+                    subnet.ip_range,
+                    network.name,
+                    subnet.subnet_type
+                );
+            }
+            return Ok(subnet.gateway.clone());
+        }
+
+        info!(
+            "Coupling vSwitch {} to network {} as {}",
+            robot.vswitch_id, network.name, robot.subnet_cidr
+        );
+        let subnet = SubnetRequest {
+            ip_range: robot.subnet_cidr.clone(),
+            network_zone: network_zone.to_string(),
+            subnet_type: "vswitch".to_string(),
+            vswitch_id: Some(robot.vswitch_id),
+        };
+        let action = self
+            .client
+            .add_subnet(network.id, &subnet)
+            .await
+            .context("Failed to add the vSwitch subnet")?;
+        self.client.wait_for_action(action.id, 120).await?;
 
-        if let Some(network) = networks
+        let network = self.client.get_network(network_id).await?;
+        network
+            .subnets
             .into_iter()
-            .find(|n| n.name == format!("{}-network", cluster_name))
+            .find(|s| s.ip_range == robot.subnet_cidr)
+            .map(|s| s.gateway)
+            .context("vSwitch subnet missing after adding it")
+    }
+
+    /// Route `destination` of a network to `gateway`, replacing a route of
+    /// the same destination through another gateway
+    pub async fn ensure_route(
+        &self,
+        network_id: u64,
+        destination: &str,
+        gateway: &str,
+    ) -> Result<()> {
+        let network = self.client.get_network(network_id).await?;
+        for route in network
+            .routes
+            .iter()
+            .filter(|r| r.destination == destination)
         {
-            info!("Deleting network: {} (ID: {})", network.name, network.id);
-            self.client
-                .delete_network(network.id)
+            if route.gateway == gateway {
+                return Ok(());
+            }
+            info!(
+                "Replacing route {} via {} in network {}",
+                route.destination, route.gateway, network.name
+            );
+            let action = self
+                .client
+                .delete_route(network.id, &route.destination, &route.gateway)
                 .await
-                .context("Failed to delete network")?;
-            info!("Network deleted successfully");
-        } else {
-            info!("Network not found, nothing to delete");
+                .context("Failed to delete route")?;
+            self.client.wait_for_action(action.id, 60).await?;
         }
 
+        info!(
+            "Routing {} via {} in network {}",
+            destination, gateway, network.name
+        );
+        let action = self
+            .client
+            .add_route(network.id, destination, gateway)
+            .await
+            .context(format!(
+                "Failed to add route {} via {}",
+                destination, gateway
+            ))?;
+        self.client.wait_for_action(action.id, 60).await?;
+        Ok(())
+    }
+
+    /// Remove the routes for `destination` from a network, if any
+    pub async fn delete_route(&self, network_id: u64, destination: &str) -> Result<()> {
+        let network = self.client.get_network(network_id).await?;
+        for route in network
+            .routes
+            .iter()
+            .filter(|r| r.destination == destination)
+        {
+            info!(
+                "Deleting route {} via {} from network {}",
+                route.destination, route.gateway, network.name
+            );
+            let action = self
+                .client
+                .delete_route(network.id, &route.destination, &route.gateway)
+                .await
+                .context("Failed to delete route")?;
+            self.client.wait_for_action(action.id, 60).await?;
+        }
         Ok(())
     }
 
-    /// Get existing network or find it by cluster name
-    pub async fn get_or_find_network(&self, cluster_name: &str) -> Result<Network> {
+    /// Delete the networks of the cluster
+    pub async fn delete_networks(
+        &self,
+        cluster_name: &str,
+        config: &HetznerCloudConfig,
+    ) -> Result<()> {
         let networks = self.client.list_networks().await?;
 
-        networks
-            .into_iter()
-            .find(|n| n.name == format!("{}-network", cluster_name))
-            .ok_or_else(|| {
-                anyhow::anyhow!(
-                    "Network not found for cluster '{}'. Please create the cluster first.",
-                    cluster_name
-                )
-            })
+        for name in cluster_network_names(cluster_name, config) {
+            if let Some(network) = networks.iter().find(|n| n.name == name) {
+                self.delete_network(network).await?;
+            } else {
+                info!("Network {} not found, nothing to delete", name);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Delete the networks labelled with the cluster, whatever their names
+    pub async fn delete_labelled_networks(&self, cluster_name: &str) -> Result<()> {
+        for network in self.client.list_networks().await? {
+            if is_cluster_resource(&network.labels, cluster_name) {
+                self.delete_network(&network).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Delete a network, lifting its delete protection first
+    async fn delete_network(&self, network: &Network) -> Result<()> {
+        info!("Deleting network: {} (ID: {})", network.name, network.id);
+        if network.protection.delete {
+            self.set_delete_protection(network, false).await?;
+        }
+        self.client
+            .delete_network(network.id)
+            .await
+            .context("Failed to delete network")?;
+        info!("Network deleted successfully");
+        Ok(())
+    }
+
+    /// Enable or disable delete protection of a network
+    async fn set_delete_protection(&self, network: &Network, enabled: bool) -> Result<()> {
+        let action = self
+            .client
+            .change_network_protection(network.id, enabled)
+            .await
+            .context(format!(
+                "Failed to change delete protection of network {}",
+                network.name
+            ))?;
+        self.client.wait_for_action(action.id, 60).await?;
+        info!(
+            "Delete protection {} for network {}",
+            if enabled { "enabled" } else { "disabled" },
+            network.name
+        );
+        Ok(())
+    }
+
+    /// Find the existing networks of the cluster
+    pub async fn find_networks(
+        &self,
+        cluster_name: &str,
+        config: &HetznerCloudConfig,
+    ) -> Result<ClusterNetworks> {
+        let mut networks = self.client.list_networks().await?;
+        let mut take = |name: String| {
+            let index = networks
+                .iter()
+                .position(|n| n.name == name)
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Network {} not found for cluster '{}'. Please create the cluster first.",
+                        name,
+                        cluster_name
+                    )
+                })?;
+            Ok::<_, anyhow::Error>(networks.swap_remove(index))
+        };
+
+        let primary = take(network_name(cluster_name, None))?;
+        let mut regions = HashMap::new();
+        for region in &config.regions {
+            let network = take(network_name(cluster_name, Some(&region.zone)))?;
+            regions.insert(region.zone.clone(), network);
+        }
+        Ok(ClusterNetworks { primary, regions })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::ClusterConfig;
+    use crate::hcloud::mock::MockHcloud;
+
+    #[test]
+    fn test_network_name() {
+        assert_eq!(network_name("prod", None), "prod-network");
+        assert_eq!(
+            network_name("prod", Some("us-east")),
+            "prod-network-us-east"
+        );
+    }
 
     #[tokio::test]
     #[ignore] // Requires API token
@@ -122,4 +392,82 @@ mod tests {
         // Test would create and delete a network
         // This is ignored by default to avoid API calls
     }
+
+    #[tokio::test]
+    async fn test_routes() {
+        let mock = MockHcloud::start().await;
+        let config = ClusterConfig::example();
+        let manager = NetworkManager::new(mock.client());
+        let networks = manager
+            .ensure_networks(&config.cluster_name, &config.hcloud)
+            .await
+            .unwrap();
+        let id = networks.primary().id;
+        let routes = |mock: &MockHcloud| mock.state().networks[&id]["routes"].clone();
+
+        manager
+            .ensure_route(id, "0.0.0.0/0", "10.0.1.5")
+            .await
+            .unwrap();
+        manager
+            .ensure_route(id, "0.0.0.0/0", "10.0.1.5")
+            .await
+            .unwrap();
+        assert_eq!(routes(&mock).as_array().unwrap().len(), 1);
+
+        manager
+            .ensure_route(id, "0.0.0.0/0", "10.0.1.6")
+            .await
+            .unwrap();
+        assert_eq!(routes(&mock)[0]["gateway"], "10.0.1.6");
+        assert_eq!(routes(&mock).as_array().unwrap().len(), 1);
+
+        manager.delete_route(id, "0.0.0.0/0").await.unwrap();
+        assert!(routes(&mock).as_array().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_vswitch_subnet() {
+        let mock = MockHcloud::start().await;
+        let config = ClusterConfig::example();
+        let manager = NetworkManager::new(mock.client());
+        let networks = manager
+            .ensure_networks(&config.cluster_name, &config.hcloud)
+            .await
+            .unwrap();
+        let id = networks.primary().id;
+        let robot: RobotConfig =
+            serde_yaml::from_str("vswitch_id: 42\nvlan: 4000\nsubnet_cidr: 10.0.2.0/24\n").unwrap();
+
+        for _ in 0..2 {
+            let gateway = manager
+                .ensure_vswitch_subnet(id, "eu-central", &robot)
+                .await
+                .unwrap();
+            assert_eq!(gateway, "10.0.2.1");
+        }
+        let subnets = mock.state().networks[&id]["subnets"].clone();
+        assert_eq!(subnets.as_array().unwrap().len(), 2);
+        assert_eq!(subnets[1]["vswitch_id"], 42);
+    }
+
+    #[tokio::test]
+    async fn test_delete_protection() {
+        let mock = MockHcloud::start().await;
+        let config = ClusterConfig::example();
+        let manager = NetworkManager::new(mock.client()).with_delete_protection(true);
+        let networks = manager
+            .ensure_networks(&config.cluster_name, &config.hcloud)
+            .await
+            .unwrap();
+        let id = networks.primary().id;
+        assert_eq!(mock.state().networks[&id]["protection"]["delete"], true);
+        assert!(mock.client().delete_network(id).await.is_err());
+
+        manager
+            .delete_networks(&config.cluster_name, &config.hcloud)
+            .await
+            .unwrap();
+        assert!(mock.state().networks.is_empty());
+    }
 }
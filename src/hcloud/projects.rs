@@ -0,0 +1,169 @@
+/// Hetzner projects of a cluster
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+
+use super::client::HetznerCloudClient;
+use super::server::{ServerInfo, ServerManager};
+use crate::config::{ClusterConfig, NodeConfig};
+
+/// API clients of the main project and the further projects
+pub struct Projects {
+    main: HetznerCloudClient,
+    others: BTreeMap<String, HetznerCloudClient>,
+}
+
+impl Projects {
+    /// Clients for every project of the config
+    pub fn from_config(config: &ClusterConfig) -> Result<Self> {
+        let mut others = BTreeMap::new();
+        for name in config.hcloud.projects.keys() {
+            let client = HetznerCloudClient::with_http_config(
+                config.project_token(name)?,
+                &config.hcloud.http,
+            )
+            .context(format!("Failed to create client for project '{}'", name))?;
+            others.insert(name.clone(), client);
+        }
+        Ok(Self {
+            main: HetznerCloudClient::from_config(config)?,
+            others,
+        })
+    }
+
+    /// Only a main project, for tests against the API mock
+    #[cfg(test)]
+    pub fn single(main: HetznerCloudClient) -> Self {
+        Self {
+            main,
+            others: BTreeMap::new(),
+        }
+    }
+
+    /// Add a further project, for tests against the API mock
+    #[cfg(test)]
+    pub fn with_project(mut self, name: &str, client: HetznerCloudClient) -> Self {
+        self.others.insert(name.to_string(), client);
+        self
+    }
+
+    /// Client of the project of `hcloud.token`
+    pub fn main(&self) -> &HetznerCloudClient {
+        &self.main
+    }
+
+    /// Clients of the further projects with their names
+    pub fn others(&self) -> impl Iterator<Item = (&str, &HetznerCloudClient)> {
+        self.others
+            .iter()
+            .map(|(name, client)| (name.as_str(), client))
+    }
+
+    /// Clients of all projects, the main one first
+    pub fn all(&self) -> impl Iterator<Item = &HetznerCloudClient> {
+        std::iter::once(&self.main).chain(self.others.values())
+    }
+
+    /// Clients of all projects with their names, `None` for the main one
+    pub fn named(&self) -> impl Iterator<Item = (Option<&str>, &HetznerCloudClient)> {
+        std::iter::once((None, &self.main)).chain(
+            self.others
+                .iter()
+                .map(|(name, client)| (Some(name.as_str()), client)),
+        )
+    }
+
+    /// Client of a project by name, `None` for the main one
+    pub fn get(&self, name: Option<&str>) -> Result<&HetznerCloudClient> {
+        match name {
+            None => Ok(&self.main),
+            Some(name) => self
+                .others
+                .get(name)
+                .context(format!("Project '{}' is not in hcloud.projects", name)),
+        }
+    }
+
+    /// Client of the project a pool lives in
+    pub fn for_pool(&self, pool: &NodeConfig) -> &HetznerCloudClient {
+        pool.project
+            .as_ref()
+            .and_then(|name| self.others.get(name))
+            .unwrap_or(&self.main)
+    }
+
+    /// Check every project token before changing anything
+    pub async fn require_write_access(&self, command: &str) -> Result<()> {
+        self.main.require_write_access(command).await?;
+        for (name, client) in &self.others {
+            client
+                .require_write_access(command)
+                .await
+                .context(format!("Token of project '{}'", name))?;
+        }
+        Ok(())
+    }
+
+    /// Servers of the cluster across all projects
+    pub async fn list_cluster_servers(&self, cluster_name: &str) -> Result<Vec<ServerInfo>> {
+        let mut servers = Vec::new();
+        for client in self.all() {
+            servers.extend(
+                ServerManager::new(client.clone())
+                    .list_cluster_servers(cluster_name)
+                    .await?,
+            );
+        }
+        Ok(servers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hcloud::mock::MockHcloud;
+    use crate::hcloud::server::NodeRole;
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn test_projects() {
+        let main = MockHcloud::start().await;
+        let compute = MockHcloud::start().await;
+        let projects = Projects {
+            main: main.client(),
+            others: BTreeMap::from([("compute".to_string(), compute.client())]),
+        };
+
+        let mut config = ClusterConfig::example();
+        config.workers[0].project = Some("compute".to_string());
+        for (pool, name) in [
+            (&config.control_planes[0], "prod-control-plane-1"),
+            (&config.workers[0], "prod-worker-1"),
+        ] {
+            ServerManager::new(projects.for_pool(pool).clone())
+                .create_single_node(
+                    "prod",
+                    name,
+                    "cpx21",
+                    "nbg1",
+                    1,
+                    NodeRole::Worker,
+                    "v1.9.0",
+                    Some("1"),
+                    None,
+                    None,
+                    HashMap::new(),
+                    true,
+                )
+                .await
+                .unwrap();
+        }
+        assert_eq!(main.state().servers.len(), 1);
+        assert_eq!(compute.state().servers.len(), 1);
+
+        let servers = projects.list_cluster_servers("prod").await.unwrap();
+        let names: Vec<&str> = servers.iter().map(|s| s.server.name.as_str()).collect();
+        assert_eq!(names, ["prod-control-plane-1", "prod-worker-1"]);
+        assert_eq!(projects.others().count(), 1);
+        projects.require_write_access("create").await.unwrap();
+    }
+}
@@ -1,15 +1,34 @@
 /// Server management for Hetzner Cloud
 use anyhow::{Context, Result};
 use futures::future::join_all;
+use futures::stream::{self, StreamExt};
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
 use tracing::{info, warn};
 
-use super::client::{CreateServerRequest, HetznerCloudClient};
-use super::models::{Network, Server};
+use super::client::{CreateServerRequest, HetznerCloudClient, PublicNetRequest};
+use super::models::Server;
+use super::network::ClusterNetworks;
 use crate::config::NodeConfig;
+use crate::utils::polling::PollingConfig;
+use crate::utils::progress;
+
+/// Servers deleted at the same time
+const DELETE_CONCURRENCY: usize = 8;
+
+/// Servers created at the same time unless configured otherwise
+const DEFAULT_CREATE_CONCURRENCY: usize = 10;
 
 /// Server manager for handling Hetzner Cloud servers
 pub struct ServerManager {
     client: HetznerCloudClient,
+    enable_ipv6: bool,
+    delete_protection: bool,
+    shutdown_timeout: Option<u64>,
+    progress: ProgressBar,
+    create_slots: Arc<Semaphore>,
 }
 
 /// Information about a created server
@@ -54,7 +73,72 @@ struct CreateServerParams<'a> {
 impl ServerManager {
     /// Create a new server manager
     pub fn new(client: HetznerCloudClient) -> Self {
-        Self { client }
+        Self {
+            client,
+            enable_ipv6: false,
+            delete_protection: false,
+            shutdown_timeout: None,
+            progress: ProgressBar::hidden(),
+            create_slots: Arc::new(Semaphore::new(DEFAULT_CREATE_CONCURRENCY)),
+        }
+    }
+
+    /// Create at most `limit` servers at the same time
+    ///
+    /// Creating a server holds its slot until the server is running, which
+    /// also bounds how many create actions are polled at once.
+    pub fn with_create_concurrency(mut self, limit: usize) -> Self {
+        self.create_slots = Arc::new(Semaphore::new(limit.max(1)));
+        self
+    }
+
+    /// Report each created server on the given progress bar
+    pub fn with_progress(mut self, progress: ProgressBar) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Explicitly request a public IPv6 /64 for new servers (dual-stack clusters)
+    pub fn with_ipv6(mut self, enabled: bool) -> Self {
+        self.enable_ipv6 = enabled;
+        self
+    }
+
+    /// Enable delete protection on new control plane servers
+    pub fn with_delete_protection(mut self, enabled: bool) -> Self {
+        self.delete_protection = enabled;
+        self
+    }
+
+    /// Shut servers down gracefully before deleting them, powering them off
+    /// after `timeout_secs`
+    ///
+    /// An ACPI shutdown lets Talos leave etcd and unmount its disks cleanly,
+    /// which a plain delete does not.
+    pub fn with_graceful_shutdown(mut self, timeout_secs: Option<u64>) -> Self {
+        self.shutdown_timeout = timeout_secs;
+        self
+    }
+
+    /// Public network options for new servers
+    ///
+    /// Left unset in single-stack mode so Hetzner's project defaults apply.
+    /// Servers of pools without public IPs only get the private network.
+    fn public_net(&self, public_ip: bool) -> Option<PublicNetRequest> {
+        if !public_ip {
+            return Some(PublicNetRequest {
+                enable_ipv4: false,
+                enable_ipv6: false,
+                ipv4: None,
+                ipv6: None,
+            });
+        }
+        self.enable_ipv6.then_some(PublicNetRequest {
+            enable_ipv4: true,
+            enable_ipv6: true,
+            ipv4: None,
+            ipv6: None,
+        })
     }
 
     /// Create control plane servers
@@ -64,7 +148,7 @@ impl ServerManager {
         cluster_name: &str,
         configs: &[NodeConfig],
         location: &str,
-        network: &Network,
+        networks: &ClusterNetworks,
         talos_version: &str,
         snapshot_id: Option<&str>,
         ssh_key_id: Option<u64>,
@@ -74,15 +158,16 @@ impl ServerManager {
 
         for config in configs {
             for i in 0..config.count {
+                let location = config.location_for(i, location);
                 let params = CreateServerParams {
                     cluster_name,
                     config,
                     index: i,
                     role: NodeRole::ControlPlane,
                     location,
-                    network_id: network.id,
+                    network_id: networks.for_location(location).id,
                     talos_version,
-                    snapshot_id,
+                    snapshot_id: config.hcloud_snapshot_id.as_deref().or(snapshot_id),
                     ssh_key_id,
                     user_data: user_data.clone(),
                 };
@@ -100,33 +185,41 @@ impl ServerManager {
     }
 
     /// Create worker servers
+    ///
+    /// `pool_user_data` overrides `user_data` for pools that need a dedicated
+    /// machine config (e.g. GPU pools), keyed by pool name.
     #[allow(clippy::too_many_arguments)]
     pub async fn create_workers(
         &self,
         cluster_name: &str,
         configs: &[NodeConfig],
         location: &str,
-        network: &Network,
+        networks: &ClusterNetworks,
         talos_version: &str,
         snapshot_id: Option<&str>,
         ssh_key_id: Option<u64>,
         user_data: Option<String>,
+        pool_user_data: &HashMap<String, String>,
     ) -> Result<Vec<ServerInfo>> {
         let mut tasks = Vec::new();
 
         for config in configs {
             for i in 0..config.count {
+                let location = config.location_for(i, location);
                 let params = CreateServerParams {
                     cluster_name,
                     config,
                     index: i,
                     role: NodeRole::Worker,
                     location,
-                    network_id: network.id,
+                    network_id: networks.for_location(location).id,
                     talos_version,
-                    snapshot_id,
+                    snapshot_id: config.hcloud_snapshot_id.as_deref().or(snapshot_id),
                     ssh_key_id,
-                    user_data: user_data.clone(),
+                    user_data: pool_user_data
+                        .get(&config.name)
+                        .cloned()
+                        .or_else(|| user_data.clone()),
                 };
                 tasks.push(self.create_server(params));
             }
@@ -142,6 +235,11 @@ impl ServerManager {
     }
 
     /// Create a single server
+    #[tracing::instrument(
+        name = "server",
+        skip_all,
+        fields(pool = %params.config.name, node = tracing::field::Empty, server_id = tracing::field::Empty)
+    )]
     async fn create_server(&self, params: CreateServerParams<'_>) -> Result<ServerInfo> {
         let server_name = if params.config.count == 1 {
             format!("{}-{}", params.cluster_name, params.config.name)
@@ -154,6 +252,8 @@ impl ServerManager {
             )
         };
 
+        tracing::Span::current().record("node", server_name.as_str());
+
         info!(
             "Creating {} server: {} (type: {})",
             params.role, server_name, params.config.server_type
@@ -193,13 +293,25 @@ impl ServerManager {
             labels: Some(labels),
             automount: Some(false),
             start_after_create: Some(true),
+            public_net: self.public_net(params.config.public_ip),
         };
 
+        let status = progress::item(&server_name);
+        status.set_message("queued");
+        let _slot = self
+            .create_slots
+            .acquire()
+            .await
+            .context("Server creation was cancelled")?;
+        status.set_message("creating");
+
         let response = self
             .client
             .create_server(request)
             .await
             .context(format!("Failed to create server {}", server_name))?;
+        status.set_message("provisioning");
+        tracing::Span::current().record("server_id", response.server.id);
 
         info!(
             "Server {} created successfully (ID: {}), waiting for provisioning...",
@@ -219,7 +331,16 @@ impl ServerManager {
             .await
             .context("Failed to get server details")?;
 
+        let server = self.protect_control_plane(server, params.role).await?;
+
         info!("Server {} is ready", server_name);
+        progress::finish_item(&status, "running");
+        self.progress.inc(1);
+
+        if let Some(template) = &params.config.rdns_template {
+            self.configure_rdns(&server, template, params.cluster_name, &params.config.name)
+                .await?;
+        }
 
         Ok(ServerInfo {
             server,
@@ -276,21 +397,44 @@ impl ServerManager {
             servers.len(),
             cluster_name
         );
+        self.delete_servers(&servers).await
+    }
+
+    /// Point the reverse DNS of a server's public IPs at its rendered hostname
+    ///
+    /// IPv6 servers get a /64, so the PTR is set for the `::1` address that
+    /// Talos uses by default.
+    pub async fn configure_rdns(
+        &self,
+        server: &Server,
+        template: &str,
+        cluster_name: &str,
+        pool_name: &str,
+    ) -> Result<()> {
+        let hostname = render_rdns(template, cluster_name, pool_name, &server.name);
+
+        let mut ips = Vec::new();
+        if let Some(ipv4) = &server.public_net.ipv4 {
+            ips.push(ipv4.ip.clone());
+        }
+        if let Some(ipv6) = &server.public_net.ipv6 {
+            let prefix = ipv6.ip.split('/').next().unwrap_or(&ipv6.ip);
+            ips.push(format!("{}1", prefix));
+        }
 
-        for server_info in servers {
+        for ip in ips {
             info!(
-                "Deleting server: {} (ID: {})",
-                server_info.server.name, server_info.server.id
+                "Setting reverse DNS for {} ({}) to {}",
+                server.name, ip, hostname
             );
-            if let Err(e) = self.client.delete_server(server_info.server.id).await {
-                warn!(
-                    "Failed to delete server {} (ID: {}): {}",
-                    server_info.server.name, server_info.server.id, e
-                );
-            }
+            let action = self
+                .client
+                .change_dns_ptr(server.id, &ip, &hostname)
+                .await
+                .context(format!("Failed to set reverse DNS for {}", ip))?;
+            self.client.wait_for_action(action.id, 60).await?;
         }
 
-        info!("All servers deleted");
         Ok(())
     }
 
@@ -299,31 +443,239 @@ impl ServerManager {
         server.public_net.ipv4.as_ref().map(|ipv4| ipv4.ip.clone())
     }
 
+    /// Address talosctl targets a node with
+    ///
+    /// Nodes without a public IP are reached on their private IP, proxied by
+    /// the control plane endpoints in the talosconfig.
+    pub fn talos_ip(server: &Server) -> Option<String> {
+        Self::get_server_ip(server).or_else(|| Self::get_server_private_ip(server))
+    }
+
     /// Get the private IP address of a server
     pub fn get_server_private_ip(server: &Server) -> Option<String> {
         server.private_net.first().map(|net| net.ip.clone())
     }
 
-    /// Delete specific servers by ID
-    pub async fn delete_servers(&self, server_ids: Vec<u64>) -> Result<()> {
-        if server_ids.is_empty() {
+    /// Delete specific servers, lifting their delete protection first
+    ///
+    /// Up to [`DELETE_CONCURRENCY`] servers are deleted at a time and every
+    /// delete action is waited for, so once this returns the deleted servers
+    /// no longer hold on to the firewall or the networks. A server that fails
+    /// to delete is logged and skipped.
+    pub async fn delete_servers(&self, servers: &[ServerInfo]) -> Result<()> {
+        if servers.is_empty() {
             info!("No servers to delete");
             return Ok(());
         }
 
-        info!("Deleting {} servers", server_ids.len());
+        info!("Deleting {} servers", servers.len());
 
-        for server_id in server_ids {
-            info!("Deleting server ID: {}", server_id);
-            if let Err(e) = self.client.delete_server(server_id).await {
-                warn!("Failed to delete server {}: {}", server_id, e);
+        let failed = stream::iter(servers)
+            .map(|server_info| async move {
+                let server = &server_info.server;
+                info!("Deleting server: {} (ID: {})", server.name, server.id);
+                let result = self.delete_server(server).await;
+                if let Err(e) = &result {
+                    warn!(
+                        "Failed to delete server {} (ID: {}): {}",
+                        server.name, server.id, e
+                    );
+                }
+                result.is_err()
+            })
+            .buffer_unordered(DELETE_CONCURRENCY)
+            .filter(|failed| futures::future::ready(*failed))
+            .count()
+            .await;
+
+        if failed == 0 {
+            info!("✓ Deleted {} servers", servers.len());
+        } else {
+            warn!(
+                "⚠️  Deleted {} of {} servers",
+                servers.len() - failed,
+                servers.len()
+            );
+        }
+        Ok(())
+    }
+
+    async fn delete_server(&self, server: &Server) -> Result<()> {
+        self.unprotect(server).await?;
+        if let Some(timeout_secs) = self.shutdown_timeout {
+            if let Err(e) = self.shut_down(server, timeout_secs).await {
+                warn!("Failed to shut down server {}: {}", server.name, e);
             }
         }
+        let action = self.client.delete_server(server.id).await?;
+        self.client.wait_for_action(action.id, 120).await?;
+        Ok(())
+    }
+
+    /// Resize a server in place via the `change_type` action
+    ///
+    /// Hetzner only changes the type of stopped servers, so the server is
+    /// shut down gracefully first (ACPI, which Talos handles as a clean
+    /// shutdown), forcibly powered off if it does not stop in time, resized,
+    /// and powered back on. The disk is left at its current size so the
+    /// change can be reverted to a smaller type.
+    #[tracing::instrument(
+        name = "server",
+        skip_all,
+        fields(node = %server.name, server_id = server.id)
+    )]
+    pub async fn resize_server(
+        &self,
+        server: &Server,
+        server_type: &str,
+        shutdown_timeout_secs: u64,
+    ) -> Result<()> {
+        info!(
+            "Resizing server {} from {} to {}",
+            server.name, server.server_type.name, server_type
+        );
+
+        self.shut_down(server, shutdown_timeout_secs).await?;
+
+        let action = self
+            .client
+            .change_server_type(server.id, server_type, false)
+            .await
+            .context(format!("Failed to change type of server {}", server.name))?;
+        self.client
+            .wait_for_action(action.id, 600)
+            .await
+            .context("Server type change action failed")?;
+
+        let action = self.client.power_on_server(server.id).await?;
+        self.client.wait_for_action(action.id, 120).await?;
+
+        info!("Server {} resized to {}", server.name, server_type);
+        Ok(())
+    }
+
+    /// Shut a server down via ACPI, which Talos handles as a clean shutdown,
+    /// and power it off if it is still running after `timeout_secs`
+    async fn shut_down(&self, server: &Server, timeout_secs: u64) -> Result<()> {
+        if server.status == "off" {
+            return Ok(());
+        }
+        let action = self
+            .client
+            .shutdown_server(server.id)
+            .await
+            .context(format!("Failed to shut down server {}", server.name))?;
+        self.client.wait_for_action(action.id, 60).await?;
+        self.ensure_stopped(server, timeout_secs).await
+    }
+
+    /// Wait for a server that is shutting down to stop, and power it off if
+    /// it is still running after `timeout_secs`
+    pub async fn ensure_stopped(&self, server: &Server, timeout_secs: u64) -> Result<()> {
+        let shutdown = PollingConfig::new(
+            timeout_secs,
+            5,
+            format!("Waiting for server {} to shut down", server.name),
+        );
+        let client = &self.client;
+        let server_id = server.id;
+        let stopped = shutdown
+            .poll_until(|| async move { Ok(client.get_server(server_id).await?.status == "off") })
+            .await;
+
+        if stopped.is_err() {
+            warn!(
+                "Server {} did not shut down gracefully, powering off",
+                server.name
+            );
+            let action = self.client.power_off_server(server.id).await?;
+            self.client.wait_for_action(action.id, 60).await?;
+        }
+        Ok(())
+    }
+
+    /// Power a stopped server on
+    pub async fn power_on(&self, server: &Server) -> Result<()> {
+        if server.status == "running" {
+            return Ok(());
+        }
+        info!("Powering on server {}", server.name);
+        let action = self
+            .client
+            .power_on_server(server.id)
+            .await
+            .context(format!("Failed to power on server {}", server.name))?;
+        self.client.wait_for_action(action.id, 120).await?;
+        Ok(())
+    }
 
-        info!("Servers deleted");
+    /// Reboot a server into the Hetzner rescue system and wait for SSH
+    ///
+    /// Rescue mode only applies to the next boot, so `reboot_server` brings
+    /// the server back into Talos from its disk afterwards.
+    #[tracing::instrument(
+        name = "server",
+        skip_all,
+        fields(node = %server.name, server_id = server.id)
+    )]
+    pub async fn boot_rescue(
+        &self,
+        server: &Server,
+        ssh_key_id: u64,
+        timeout_secs: u64,
+    ) -> Result<()> {
+        let ip = Self::get_server_ip(server)
+            .context(format!("Server {} has no public IPv4 address", server.name))?;
+
+        info!("Enabling rescue mode on {}", server.name);
+        let action = self
+            .client
+            .enable_rescue(server.id, &[ssh_key_id])
+            .await
+            .context(format!("Failed to enable rescue mode on {}", server.name))?;
+        self.client.wait_for_action(action.id, 60).await?;
+
+        let action = if server.status == "off" {
+            self.client.power_on_server(server.id).await?
+        } else {
+            self.client.reset_server(server.id).await?
+        };
+        self.client.wait_for_action(action.id, 120).await?;
+
+        PollingConfig::new(
+            timeout_secs,
+            5,
+            format!("Waiting for rescue system on {}", server.name),
+        )
+        .poll_until(|| async { Ok(Self::ssh_reachable(&ip).await) })
+        .await
+    }
+
+    /// Hard-reset a server, e.g. to leave the rescue system
+    pub async fn reboot_server(&self, server: &Server) -> Result<()> {
+        let action = self
+            .client
+            .reset_server(server.id)
+            .await
+            .context(format!("Failed to reset server {}", server.name))?;
+        self.client.wait_for_action(action.id, 120).await?;
         Ok(())
     }
 
+    /// Whether port 22 on `ip` accepts connections
+    pub async fn ssh_reachable(ip: &str) -> bool {
+        let connect = tokio::net::TcpStream::connect((ip, 22));
+        matches!(
+            tokio::time::timeout(std::time::Duration::from_secs(3), connect).await,
+            Ok(Ok(_))
+        )
+    }
+
+    /// Hetzner location a server runs in
+    pub fn location(server: &Server) -> &str {
+        &server.datacenter.location.name
+    }
+
     /// Get servers by role and pool name
     pub fn filter_by_role_and_pool(
         servers: &[ServerInfo],
@@ -339,13 +691,7 @@ impl ServerManager {
 
                 // If pool name is specified, match it
                 if let Some(pool) = pool_name {
-                    // Extract pool name from server name (format: cluster-poolname-index)
-                    let server_name_parts: Vec<&str> = s.server.name.split('-').collect();
-                    if server_name_parts.len() >= 2 {
-                        let server_pool = server_name_parts[server_name_parts.len() - 2];
-                        return server_pool == pool;
-                    }
-                    return false;
+                    return in_pool(&s.server.name, pool);
                 }
 
                 true
@@ -368,7 +714,8 @@ impl ServerManager {
         snapshot_id: Option<&str>,
         ssh_key_id: Option<u64>,
         user_data: Option<String>,
-        labels: std::collections::HashMap<String, String>,
+        labels: HashMap<String, String>,
+        public_ip: bool,
     ) -> Result<ServerInfo> {
         info!(
             "Creating {} server: {} (type: {})",
@@ -398,6 +745,7 @@ impl ServerManager {
             labels: Some(server_labels),
             automount: Some(false),
             start_after_create: Some(true),
+            public_net: self.public_net(public_ip),
         };
 
         let response = self
@@ -422,6 +770,8 @@ impl ServerManager {
             .await
             .context("Failed to get server details")?;
 
+        let server = self.protect_control_plane(server, role).await?;
+
         info!("Server {} is ready", node_name);
 
         Ok(ServerInfo {
@@ -430,15 +780,359 @@ impl ServerManager {
             index: 0,
         })
     }
+
+    /// Enable delete protection on a new control plane server if configured
+    async fn protect_control_plane(&self, server: Server, role: NodeRole) -> Result<Server> {
+        if !self.delete_protection || role != NodeRole::ControlPlane {
+            return Ok(server);
+        }
+        self.set_delete_protection(&server, true).await?;
+        self.client.get_server(server.id).await
+    }
+
+    /// Enable or disable delete protection of a server
+    pub async fn set_delete_protection(&self, server: &Server, enabled: bool) -> Result<()> {
+        let action = self
+            .client
+            .change_server_protection(server.id, enabled)
+            .await
+            .context(format!(
+                "Failed to change delete protection of server {}",
+                server.name
+            ))?;
+        self.client.wait_for_action(action.id, 60).await?;
+        info!(
+            "Delete protection {} for server {}",
+            if enabled { "enabled" } else { "disabled" },
+            server.name
+        );
+        Ok(())
+    }
+
+    /// Lift delete protection so the server can be deleted
+    async fn unprotect(&self, server: &Server) -> Result<()> {
+        if server.protection.delete {
+            self.set_delete_protection(server, false).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Pick `count` servers to remove from a pool
+///
+/// Takes the newest node of whichever location currently has the most, so a
+/// pool spread across locations stays balanced; for single-location pools
+/// this is simply the highest indices first.
+pub fn removal_order(mut servers: Vec<ServerInfo>, count: usize) -> Vec<ServerInfo> {
+    let mut removed = Vec::new();
+    while removed.len() < count {
+        let mut per_location: HashMap<&str, usize> = HashMap::new();
+        for s in &servers {
+            *per_location
+                .entry(ServerManager::location(&s.server))
+                .or_default() += 1;
+        }
+        let next = servers
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, s)| {
+                (
+                    per_location[ServerManager::location(&s.server)],
+                    node_index(&s.server.name),
+                )
+            })
+            .map(|(i, _)| i);
+        match next {
+            Some(i) => removed.push(servers.remove(i)),
+            None => break,
+        }
+    }
+    removed
+}
+
+/// Numeric suffix of a server name (`prod-worker-10` -> 10), 0 if there is none
+fn node_index(server_name: &str) -> u32 {
+    server_name
+        .rsplit_once('-')
+        .and_then(|(_, index)| index.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Whether a server name (format: cluster-poolname-index) belongs to `pool`
+///
+/// Pool names may contain dashes, so the index is stripped and the rest is
+/// matched as a suffix.
+fn in_pool(server_name: &str, pool: &str) -> bool {
+    match server_name.rsplit_once('-') {
+        Some((prefix, index)) if index.chars().all(|c| c.is_ascii_digit()) => prefix
+            .strip_suffix(pool)
+            .is_some_and(|cluster| cluster.ends_with('-')),
+        _ => false,
+    }
+}
+
+/// Index of a server within its pool, from its name (format:
+/// cluster-poolname-index)
+///
+/// Servers of single-server pools have no numeric suffix and index 0.
+pub fn server_index(server_name: &str) -> u32 {
+    server_name
+        .rsplit_once('-')
+        .and_then(|(_, suffix)| suffix.parse::<u32>().ok())
+        .map_or(0, |number| number.saturating_sub(1))
+}
+
+/// Render a reverse DNS template for a node
+fn render_rdns(template: &str, cluster_name: &str, pool_name: &str, node_name: &str) -> String {
+    template
+        .replace("{node}", node_name)
+        .replace("{pool}", pool_name)
+        .replace("{cluster}", cluster_name)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_render_rdns() {
+        assert_eq!(
+            render_rdns(
+                "{node}.{pool}.{cluster}.example.com",
+                "prod",
+                "worker",
+                "prod-worker-1"
+            ),
+            "prod-worker-1.worker.prod.example.com"
+        );
+    }
+
+    #[test]
+    fn test_in_pool() {
+        assert!(in_pool("prod-worker-1", "worker"));
+        assert!(in_pool("prod-workers-canary-2", "workers-canary"));
+        assert!(!in_pool("prod-workers-canary-2", "worker"));
+        assert!(!in_pool("prod-worker-1", "control-plane"));
+        assert!(!in_pool("prod-worker", "worker"));
+    }
+
+    #[test]
+    fn test_server_index() {
+        assert_eq!(server_index("prod-worker-1"), 0);
+        assert_eq!(server_index("prod-workers-canary-3"), 2);
+        assert_eq!(server_index("prod-control-plane"), 0);
+    }
+
+    fn server(name: &str, location: &str) -> ServerInfo {
+        let server = serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "name": name,
+            "status": "running",
+            "server_type": {"id": 1, "name": "cpx31", "description": "", "cores": 4, "memory": 8.0, "disk": 160},
+            "datacenter": {
+                "id": 1,
+                "name": format!("{}-dc1", location),
+                "description": "",
+                "location": {"id": 1, "name": location, "description": "", "country": "DE", "city": "", "latitude": 0.0, "longitude": 0.0}
+            },
+            "public_net": {"ipv4": null, "ipv6": null, "floating_ips": []},
+            "private_net": [],
+            "created": "2024-01-01T00:00:00Z"
+        }))
+        .unwrap();
+        ServerInfo {
+            server,
+            role: NodeRole::Worker,
+            index: 0,
+        }
+    }
+
+    #[test]
+    fn test_removal_order() {
+        let names = |servers: Vec<ServerInfo>| -> Vec<String> {
+            servers.into_iter().map(|s| s.server.name).collect()
+        };
+
+        let single: Vec<_> = (1..=10)
+            .map(|i| server(&format!("prod-worker-{}", i), "nbg1"))
+            .collect();
+        assert_eq!(
+            names(removal_order(single, 2)),
+            ["prod-worker-10", "prod-worker-9"]
+        );
+
+        let spread = vec![
+            server("prod-worker-1", "fsn1"),
+            server("prod-worker-2", "nbg1"),
+            server("prod-worker-3", "fsn1"),
+            server("prod-worker-4", "fsn1"),
+            server("prod-worker-5", "nbg1"),
+        ];
+        assert_eq!(
+            names(removal_order(spread, 3)),
+            ["prod-worker-4", "prod-worker-5", "prod-worker-3"]
+        );
+    }
+
+    #[test]
+    fn test_node_index() {
+        assert_eq!(node_index("prod-worker-10"), 10);
+        assert_eq!(node_index("prod-worker"), 0);
+    }
+
     #[test]
     fn test_node_role_display() {
         assert_eq!(NodeRole::ControlPlane.to_string(), "control-plane");
         assert_eq!(NodeRole::Worker.to_string(), "worker");
     }
+
+    #[tokio::test]
+    async fn test_delete_protection() {
+        let mock = crate::hcloud::mock::MockHcloud::start().await;
+        let manager = ServerManager::new(mock.client()).with_delete_protection(true);
+        let create = |name: &'static str, role| {
+            manager.create_single_node(
+                "prod",
+                name,
+                "cpx21",
+                "nbg1",
+                1,
+                role,
+                "v1.9.0",
+                Some("1"),
+                None,
+                None,
+                HashMap::new(),
+                true,
+            )
+        };
+        let control_plane = create("prod-control-plane-1", NodeRole::ControlPlane)
+            .await
+            .unwrap();
+        let worker = create("prod-worker-1", NodeRole::Worker).await.unwrap();
+        assert!(control_plane.server.protection.delete);
+        assert!(!worker.server.protection.delete);
+
+        // A plain API delete is refused while protected
+        let client = mock.client();
+        assert!(client.delete_server(control_plane.server.id).await.is_err());
+
+        manager
+            .delete_servers(&[control_plane, worker])
+            .await
+            .unwrap();
+        assert!(mock.state().servers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delete_servers_waits_for_actions() {
+        let mock = crate::hcloud::mock::MockHcloud::start().await;
+        let manager = ServerManager::new(mock.client());
+        let mut servers = Vec::new();
+        for name in ["prod-worker-1", "prod-worker-2"] {
+            let info = manager
+                .create_single_node(
+                    "prod",
+                    name,
+                    "cpx21",
+                    "nbg1",
+                    1,
+                    NodeRole::Worker,
+                    "v1.9.0",
+                    Some("1"),
+                    None,
+                    None,
+                    HashMap::new(),
+                    true,
+                )
+                .await
+                .unwrap();
+            servers.push(info);
+        }
+
+        manager.delete_servers(&servers).await.unwrap();
+        let state = mock.state();
+        assert!(state.servers.is_empty());
+        let delete_actions: Vec<u64> = state
+            .actions
+            .values()
+            .filter(|action| action["command"] == "delete_server")
+            .filter_map(|action| action["id"].as_u64())
+            .collect();
+        assert_eq!(delete_actions.len(), 2);
+        for id in delete_actions {
+            assert!(state
+                .requests
+                .iter()
+                .any(|request| request.starts_with("GET /actions?")
+                    && request.contains(&format!("id={}&", id))));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_graceful_shutdown() {
+        let mock = crate::hcloud::mock::MockHcloud::start().await;
+        let manager = ServerManager::new(mock.client()).with_graceful_shutdown(Some(30));
+        let worker = manager
+            .create_single_node(
+                "prod",
+                "prod-worker-1",
+                "cpx21",
+                "nbg1",
+                1,
+                NodeRole::Worker,
+                "v1.9.0",
+                Some("1"),
+                None,
+                None,
+                HashMap::new(),
+                true,
+            )
+            .await
+            .unwrap();
+
+        manager.delete_servers(&[worker]).await.unwrap();
+        let state = mock.state();
+        assert!(state.servers.is_empty());
+        let commands: Vec<&str> = state
+            .actions
+            .values()
+            .filter_map(|action| action["command"].as_str())
+            .collect();
+        assert!(commands.contains(&"shutdown"));
+        assert!(!commands.contains(&"poweroff"));
+    }
+
+    #[tokio::test]
+    async fn test_power_cycle() {
+        let mock = crate::hcloud::mock::MockHcloud::start().await;
+        let client = mock.client();
+        let manager = ServerManager::new(client.clone());
+        let worker = manager
+            .create_single_node(
+                "prod",
+                "prod-worker-1",
+                "cpx21",
+                "nbg1",
+                1,
+                NodeRole::Worker,
+                "v1.9.0",
+                Some("1"),
+                None,
+                None,
+                HashMap::new(),
+                true,
+            )
+            .await
+            .unwrap();
+
+        client.shutdown_server(worker.server.id).await.unwrap();
+        let stopped = client.get_server(worker.server.id).await.unwrap();
+        manager.ensure_stopped(&stopped, 30).await.unwrap();
+        assert_eq!(stopped.status, "off");
+
+        manager.power_on(&stopped).await.unwrap();
+        let started = client.get_server(worker.server.id).await.unwrap();
+        assert_eq!(started.status, "running");
+    }
 }
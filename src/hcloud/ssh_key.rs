@@ -3,7 +3,22 @@ use anyhow::{Context, Result};
 use tracing::info;
 
 use super::client::HetznerCloudClient;
-use super::models::SSHKey;
+use super::models::{is_cluster_resource, SSHKey};
+
+/// Name of the cluster's SSH key in Hetzner Cloud
+pub fn key_name(cluster_name: &str) -> String {
+    format!("{}-oxide", cluster_name)
+}
+
+/// Labels of the cluster's SSH key
+fn key_labels(cluster_name: &str) -> std::collections::HashMap<String, String> {
+    [
+        ("cluster".to_string(), cluster_name.to_string()),
+        ("managed-by".to_string(), "oxide".to_string()),
+    ]
+    .into_iter()
+    .collect()
+}
 
 /// SSH key manager for handling Hetzner Cloud SSH keys
 pub struct SSHKeyManager {
@@ -24,21 +39,15 @@ impl SSHKeyManager {
     ///
     /// The private key is returned along with the SSH key metadata for secure storage.
     pub async fn ensure_ssh_key(&self, cluster_name: &str) -> Result<(SSHKey, Option<String>)> {
-        let key_name = format!("{}-oxide", cluster_name);
+        let key_name = key_name(cluster_name);
 
         // Check if key already exists
-        let existing_keys = self
-            .client
-            .list_ssh_keys()
-            .await
-            .context("Failed to list SSH keys")?;
-
-        if let Some(existing_key) = existing_keys.iter().find(|k| k.name == key_name) {
+        if let Some(existing_key) = self.find_cluster_ssh_key(cluster_name).await? {
             info!(
                 "Using existing SSH key: {} (ID: {})",
                 existing_key.name, existing_key.id
             );
-            return Ok((existing_key.clone(), None));
+            return Ok((existing_key, None));
         }
 
         // Generate new ED25519 key pair
@@ -49,7 +58,7 @@ impl SSHKeyManager {
         info!("Uploading SSH key to Hetzner Cloud...");
         let ssh_key = self
             .client
-            .create_ssh_key(key_name.clone(), public_key)
+            .create_ssh_key(key_name.clone(), public_key, key_labels(cluster_name))
             .await
             .context("Failed to create SSH key")?;
 
@@ -61,12 +70,27 @@ impl SSHKeyManager {
         Ok((ssh_key, Some(private_key)))
     }
 
-    /// Delete SSH key for a cluster
+    /// Upload an existing public key for the cluster, unless already there
     ///
-    /// This method finds and deletes the SSH key associated with the given cluster name.
-    /// If the key doesn't exist, it silently succeeds (idempotent operation).
-    pub async fn delete_cluster_ssh_key(&self, cluster_name: &str) -> Result<()> {
-        let key_name = format!("{}-oxide", cluster_name);
+    /// Used for further projects, so one private key reaches every node.
+    pub async fn import_ssh_key(&self, cluster_name: &str, public_key: &str) -> Result<SSHKey> {
+        if let Some(existing_key) = self.find_cluster_ssh_key(cluster_name).await? {
+            return Ok(existing_key);
+        }
+        info!("Uploading SSH key to Hetzner Cloud...");
+        self.client
+            .create_ssh_key(
+                key_name(cluster_name),
+                public_key.to_string(),
+                key_labels(cluster_name),
+            )
+            .await
+            .context("Failed to create SSH key")
+    }
+
+    /// Find the SSH key uploaded for a cluster, if any
+    pub async fn find_cluster_ssh_key(&self, cluster_name: &str) -> Result<Option<SSHKey>> {
+        let key_name = key_name(cluster_name);
 
         let existing_keys = self
             .client
@@ -74,7 +98,15 @@ impl SSHKeyManager {
             .await
             .context("Failed to list SSH keys")?;
 
-        if let Some(key) = existing_keys.iter().find(|k| k.name == key_name) {
+        Ok(existing_keys.into_iter().find(|k| k.name == key_name))
+    }
+
+    /// Delete SSH key for a cluster
+    ///
+    /// This method finds and deletes the SSH key associated with the given cluster name.
+    /// If the key doesn't exist, it silently succeeds (idempotent operation).
+    pub async fn delete_cluster_ssh_key(&self, cluster_name: &str) -> Result<()> {
+        if let Some(key) = self.find_cluster_ssh_key(cluster_name).await? {
             info!("Deleting SSH key: {} (ID: {})", key.name, key.id);
             self.client
                 .delete_ssh_key(key.id)
@@ -87,6 +119,21 @@ impl SSHKeyManager {
 
         Ok(())
     }
+
+    /// Delete the SSH keys labelled with the cluster
+    pub async fn delete_labelled_ssh_keys(&self, cluster_name: &str) -> Result<()> {
+        for key in self.client.list_ssh_keys().await? {
+            if !is_cluster_resource(&key.labels, cluster_name) {
+                continue;
+            }
+            info!("Deleting SSH key: {} (ID: {})", key.name, key.id);
+            self.client
+                .delete_ssh_key(key.id)
+                .await
+                .context("Failed to delete SSH key")?;
+        }
+        Ok(())
+    }
 }
 
 /// Generate an ED25519 key pair
@@ -0,0 +1,384 @@
+/// Preview of `oxide destroy`
+use anyhow::Result;
+use std::collections::HashSet;
+
+use super::hibernate;
+use super::nat::NatGatewayManager;
+use super::network::cluster_network_names;
+use super::server::ServerManager;
+use super::{FirewallManager, HetznerCloudClient, SSHKeyManager};
+use crate::config::ClusterConfig;
+use crate::state::ClusterState;
+
+/// What a destroy does with a resource
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Disposal {
+    Delete,
+    /// Recorded in the cluster state but not found in the API
+    Missing,
+    /// Left in place
+    Keep,
+}
+
+impl std::fmt::Display for Disposal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            Disposal::Delete => "delete",
+            Disposal::Missing => "missing",
+            Disposal::Keep => "keep",
+        })
+    }
+}
+
+/// One resource of the destroy plan
+#[derive(Debug, Clone)]
+pub struct PlannedResource {
+    pub disposal: Disposal,
+    /// `server`, `dns-ptr`, `nat-gateway`, `volume`, `floating-ip`,
+    /// `primary-ip`, `snapshot`, `load-balancer`, `firewall`, `ssh-key` or
+    /// `network`
+    pub kind: &'static str,
+    pub name: String,
+    pub id: Option<u64>,
+    pub note: Option<String>,
+}
+
+impl PlannedResource {
+    fn new(disposal: Disposal, kind: &'static str, name: &str, id: Option<u64>) -> Self {
+        Self {
+            disposal,
+            kind,
+            name: name.to_string(),
+            id,
+            note: None,
+        }
+    }
+
+    fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.note = Some(note.into());
+        self
+    }
+}
+
+/// Resources `oxide destroy` would touch, in deletion order
+///
+/// With `target_ids` only those servers (and their reverse DNS entries) are
+/// planned, like `destroy --target-id`.
+pub async fn plan(
+    client: &HetznerCloudClient,
+    config: &ClusterConfig,
+    state: Option<&ClusterState>,
+    target_ids: &[u64],
+) -> Result<Vec<PlannedResource>> {
+    let cluster_name = &config.cluster_name;
+    let mut planned = Vec::new();
+
+    let mut servers = ServerManager::new(client.clone())
+        .list_cluster_servers(cluster_name)
+        .await?;
+    if !target_ids.is_empty() {
+        servers.retain(|info| target_ids.contains(&info.server.id));
+    }
+    let server_ids: HashSet<u64> = servers.iter().map(|info| info.server.id).collect();
+
+    for info in &servers {
+        let server = &info.server;
+        let mut resource =
+            PlannedResource::new(Disposal::Delete, "server", &server.name, Some(server.id));
+        if state.is_some_and(|state| !state.unknown_servers(std::slice::from_ref(info)).is_empty())
+        {
+            resource = resource.with_note("not in the cluster state, destroy needs --force");
+        } else if state.is_none() {
+            resource = resource.with_note("no cluster state, destroy needs --force");
+        }
+        planned.push(resource);
+
+        if let Some(ptr) = server
+            .public_net
+            .ipv4
+            .as_ref()
+            .and_then(|ipv4| ipv4.dns_ptr.as_ref().map(|ptr| (&ipv4.ip, ptr)))
+        {
+            planned.push(
+                PlannedResource::new(
+                    Disposal::Delete,
+                    "dns-ptr",
+                    &format!("{} -> {}", ptr.0, ptr.1),
+                    None,
+                )
+                .with_note(format!("removed with {}", server.name)),
+            );
+        }
+    }
+
+    if let Some(state) = state {
+        for (id, name) in &state.servers {
+            if (target_ids.is_empty() || target_ids.contains(id)) && !server_ids.contains(id) {
+                planned.push(
+                    PlannedResource::new(Disposal::Missing, "server", name, Some(*id))
+                        .with_note("recorded in the cluster state but not found"),
+                );
+            }
+        }
+    }
+    for id in target_ids {
+        let recorded = state.is_some_and(|state| state.servers.contains_key(id));
+        if !server_ids.contains(id) && !recorded {
+            planned.push(
+                PlannedResource::new(Disposal::Missing, "server", "-", Some(*id))
+                    .with_note(format!("no server of cluster {} has this ID", cluster_name)),
+            );
+        }
+    }
+
+    if !target_ids.is_empty() {
+        return Ok(planned);
+    }
+
+    if let Some(gateway) = NatGatewayManager::new(client.clone())
+        .find_gateway(cluster_name)
+        .await?
+    {
+        planned.push(PlannedResource::new(
+            Disposal::Delete,
+            "nat-gateway",
+            &gateway.name,
+            Some(gateway.id),
+        ));
+    }
+
+    let belongs = |labels: &std::collections::HashMap<String, String>, server: Option<u64>| {
+        labels.get("cluster") == Some(cluster_name)
+            || server.is_some_and(|id| server_ids.contains(&id))
+    };
+    for volume in client.list_volumes().await? {
+        if belongs(&volume.labels, volume.server) {
+            planned.push(
+                PlannedResource::new(Disposal::Keep, "volume", &volume.name, Some(volume.id))
+                    .with_note("detached from its server, not deleted"),
+            );
+        }
+    }
+    let labelled = |labels: &std::collections::HashMap<String, String>| {
+        labels.get("cluster") == Some(cluster_name)
+    };
+    for floating_ip in client.list_floating_ips().await? {
+        let name = format!("{} ({})", floating_ip.name, floating_ip.ip);
+        if labelled(&floating_ip.labels) {
+            planned.push(PlannedResource::new(
+                Disposal::Delete,
+                "floating-ip",
+                &name,
+                Some(floating_ip.id),
+            ));
+        } else if belongs(&floating_ip.labels, floating_ip.server) {
+            planned.push(
+                PlannedResource::new(Disposal::Keep, "floating-ip", &name, Some(floating_ip.id))
+                    .with_note("unassigned from its server, not deleted"),
+            );
+        }
+    }
+    for primary_ip in client.list_primary_ips().await? {
+        let name = format!("{} ({})", primary_ip.name, primary_ip.ip);
+        if labelled(&primary_ip.labels) {
+            planned.push(PlannedResource::new(
+                Disposal::Delete,
+                "primary-ip",
+                &name,
+                Some(primary_ip.id),
+            ));
+        } else if belongs(&primary_ip.labels, primary_ip.assignee_id) {
+            let (disposal, note) = if primary_ip.auto_delete {
+                (Disposal::Delete, "deleted with its server")
+            } else {
+                (Disposal::Keep, "unassigned from its server, not deleted")
+            };
+            planned.push(
+                PlannedResource::new(disposal, "primary-ip", &name, Some(primary_ip.id))
+                    .with_note(note),
+            );
+        }
+    }
+    for image in hibernate::snapshots(client, cluster_name).await? {
+        planned.push(
+            PlannedResource::new(
+                Disposal::Delete,
+                "snapshot",
+                &image.description,
+                Some(image.id),
+            )
+            .with_note("taken by pause --snapshot"),
+        );
+    }
+    let ingress_load_balancer = crate::addons::ingress_nginx::load_balancer_name(cluster_name);
+    for load_balancer in client.list_load_balancers().await? {
+        if load_balancer.name == ingress_load_balancer {
+            planned.push(
+                PlannedResource::new(
+                    Disposal::Delete,
+                    "load-balancer",
+                    &load_balancer.name,
+                    Some(load_balancer.id),
+                )
+                .with_note("created for ingress-nginx"),
+            );
+        } else if belongs(&load_balancer.labels, None) {
+            planned.push(
+                PlannedResource::new(
+                    Disposal::Keep,
+                    "load-balancer",
+                    &load_balancer.name,
+                    Some(load_balancer.id),
+                )
+                .with_note("not managed by oxide, not deleted"),
+            );
+        }
+    }
+
+    if let Some(firewall) = FirewallManager::new(client.clone())
+        .get_cluster_firewall(cluster_name)
+        .await?
+    {
+        planned.push(PlannedResource::new(
+            Disposal::Delete,
+            "firewall",
+            &firewall.name,
+            Some(firewall.id),
+        ));
+    }
+
+    if let Some(key) = SSHKeyManager::new(client.clone())
+        .find_cluster_ssh_key(cluster_name)
+        .await?
+    {
+        planned.push(PlannedResource::new(
+            Disposal::Delete,
+            "ssh-key",
+            &key.name,
+            Some(key.id),
+        ));
+    }
+
+    let networks = client.list_networks().await?;
+    for name in cluster_network_names(cluster_name, &config.hcloud) {
+        if let Some(network) = networks.iter().find(|n| n.name == name) {
+            planned.push(PlannedResource::new(
+                Disposal::Delete,
+                "network",
+                &network.name,
+                Some(network.id),
+            ));
+        }
+    }
+
+    Ok(planned)
+}
+
+/// One line per resource, e.g. `delete   server   prod-worker-1 (ID: 7)`
+pub fn render(planned: &[PlannedResource]) -> String {
+    planned
+        .iter()
+        .map(|resource| {
+            let mut line = format!(
+                "{:<8} {:<14} {}",
+                resource.disposal, resource.kind, resource.name
+            );
+            if let Some(id) = resource.id {
+                line.push_str(&format!(" (ID: {})", id));
+            }
+            if let Some(note) = &resource.note {
+                line.push_str(&format!(" - {}", note));
+            }
+            format!("{}\n", line)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hcloud::mock::MockHcloud;
+    use crate::hcloud::network::NetworkManager;
+    use crate::hcloud::server::NodeRole;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn test_plan() {
+        let mock = MockHcloud::start().await;
+        let client = mock.client();
+        let config = ClusterConfig::example();
+        let cluster = config.cluster_name.clone();
+
+        let networks = NetworkManager::new(client.clone())
+            .ensure_networks(&cluster, &config.hcloud)
+            .await
+            .unwrap();
+        let worker = ServerManager::new(client.clone())
+            .create_single_node(
+                &cluster,
+                &format!("{}-worker-1", cluster),
+                "cpx21",
+                "nbg1",
+                networks.primary().id,
+                NodeRole::Worker,
+                "v1.9.0",
+                Some("1"),
+                None,
+                None,
+                HashMap::new(),
+                true,
+            )
+            .await
+            .unwrap();
+        let worker_id = worker.server.id;
+        mock.state().volumes.insert(
+            90,
+            json!({ "id": 90, "name": "pvc-data", "server": worker_id, "labels": {} }),
+        );
+        mock.state().volumes.insert(
+            91,
+            json!({ "id": 91, "name": "unrelated", "server": null, "labels": {} }),
+        );
+
+        let mut state = ClusterState {
+            cluster_name: cluster.clone(),
+            ..Default::default()
+        };
+        state.servers.insert(worker_id, worker.server.name.clone());
+        state.servers.insert(42, format!("{}-worker-2", cluster));
+
+        let planned = plan(&client, &config, Some(&state), &[]).await.unwrap();
+        let entries: Vec<(Disposal, &str, Option<u64>)> =
+            planned.iter().map(|r| (r.disposal, r.kind, r.id)).collect();
+        assert_eq!(entries[0], (Disposal::Delete, "server", Some(worker_id)));
+        assert_eq!(planned[0].note, None);
+        assert_eq!(entries[1], (Disposal::Missing, "server", Some(42)));
+        assert!(entries.contains(&(Disposal::Keep, "volume", Some(90))));
+        assert!(!entries.iter().any(|entry| entry.2 == Some(91)));
+        assert_eq!(
+            entries.iter().filter(|entry| entry.1 == "network").count(),
+            cluster_network_names(&cluster, &config.hcloud).len()
+        );
+
+        let targeted = plan(&client, &config, Some(&state), &[42]).await.unwrap();
+        assert_eq!(targeted.len(), 1);
+        assert_eq!(targeted[0].disposal, Disposal::Missing);
+
+        let without_state = plan(&client, &config, None, &[worker_id]).await.unwrap();
+        assert!(without_state[0]
+            .note
+            .as_deref()
+            .unwrap()
+            .contains("--force"));
+
+        let rendered = render(&planned);
+        assert!(rendered.starts_with(&format!(
+            "delete   server         {}-worker-1 (ID: {})",
+            cluster, worker_id
+        )));
+
+        // Nothing was deleted
+        assert_eq!(mock.state().servers.len(), 1);
+    }
+}
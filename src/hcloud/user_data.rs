@@ -0,0 +1,146 @@
+/// Size validation and compression of server user_data
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::Write;
+use tracing::info;
+
+/// Maximum user_data size accepted by the Hetzner Cloud API
+pub const MAX_USER_DATA_BYTES: usize = 32 * 1024;
+
+/// Size above which user_data is compressed before upload
+///
+/// Leaves some headroom below the hard limit so that small config changes
+/// (an extra label, a longer endpoint) don't suddenly break server creation.
+const COMPRESSION_THRESHOLD_BYTES: usize = MAX_USER_DATA_BYTES * 9 / 10;
+
+/// Prepare a machine config for use as user_data
+///
+/// Configs close to the limit are gzip-compressed and base64-encoded, which
+/// Talos detects and decompresses when reading its config from the metadata
+/// service. If the config is still too large, the error lists the patches
+/// contributing to it, largest first.
+pub fn prepare(name: &str, config: String, patches: &[String]) -> Result<String> {
+    if config.len() <= COMPRESSION_THRESHOLD_BYTES {
+        return Ok(config);
+    }
+
+    let compressed = compress(&config)?;
+    info!(
+        "Machine config {} is {} bytes, compressed user_data to {} bytes",
+        name,
+        config.len(),
+        compressed.len()
+    );
+
+    if compressed.len() > MAX_USER_DATA_BYTES {
+        anyhow::bail!(
+            "Machine config {} is too large for Hetzner user_data even when compressed ({} bytes, limit {} bytes).{}",
+            name,
+            compressed.len(),
+            MAX_USER_DATA_BYTES,
+            patch_report(patches)
+        );
+    }
+
+    Ok(compressed)
+}
+
+/// Gzip and base64-encode a config
+fn compress(config: &str) -> Result<String> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(config.as_bytes())?;
+    let gzipped = encoder.finish()?;
+    Ok(STANDARD.encode(gzipped))
+}
+
+/// Describe the patches applied to a config, largest first
+fn patch_report(patches: &[String]) -> String {
+    if patches.is_empty() {
+        return String::new();
+    }
+
+    let mut sizes: Vec<(usize, &str)> = patches
+        .iter()
+        .map(|patch| (patch_size(patch), patch.as_str()))
+        .collect();
+    sizes.sort_by_key(|size| std::cmp::Reverse(size.0));
+
+    let mut report = String::from("\nApplied config patches by size:");
+    for (size, patch) in sizes {
+        report.push_str(&format!("\n  {:>6} bytes  {}", size, patch_label(patch)));
+    }
+    report
+}
+
+/// Size of an inline patch, or of the file referenced by an `@file` patch
+fn patch_size(patch: &str) -> usize {
+    match patch.strip_prefix('@') {
+        Some(path) => std::fs::metadata(path)
+            .map(|m| m.len() as usize)
+            .unwrap_or(0),
+        None => patch.len(),
+    }
+}
+
+/// Short human-readable label for a patch
+fn patch_label(patch: &str) -> String {
+    if patch.starts_with('@') {
+        return patch.to_string();
+    }
+
+    let first_line = patch.lines().next().unwrap_or_default();
+    format!("inline patch starting with `{}`", first_line.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn test_small_config_is_unchanged() {
+        let config = "version: v1alpha1\n".to_string();
+        assert_eq!(prepare("worker.yaml", config.clone(), &[]).unwrap(), config);
+    }
+
+    #[test]
+    fn test_large_config_is_compressed() {
+        let config = "machine:\n  type: worker\n".repeat(2000);
+        assert!(config.len() > MAX_USER_DATA_BYTES);
+
+        let prepared = prepare("worker.yaml", config.clone(), &[]).unwrap();
+        assert!(prepared.len() <= MAX_USER_DATA_BYTES);
+
+        let gzipped = STANDARD.decode(prepared).unwrap();
+        let mut decoded = String::new();
+        GzDecoder::new(gzipped.as_slice())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, config);
+    }
+
+    #[test]
+    fn test_incompressible_config_reports_patches() {
+        // Pseudo-random content does not compress below the limit
+        let mut state: u64 = 42;
+        let config: String = (0..MAX_USER_DATA_BYTES * 2)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                char::from(b'!' + ((state >> 33) % 90) as u8)
+            })
+            .collect();
+
+        let patches = vec![
+            "small: patch".to_string(),
+            "machine:\n  big: patch".to_string(),
+        ];
+        let err = prepare("controlplane.yaml", config, &patches)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("controlplane.yaml"));
+        assert!(err.contains("inline patch starting with `machine:`"));
+    }
+}
@@ -0,0 +1,334 @@
+/// Node inventory for `oxide node list`
+pub mod top;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::config::{ClusterConfig, NodeConfig};
+use crate::hcloud::server::{NodeRole, ServerInfo, ServerManager};
+use crate::k8s::nodes::NodeStatus;
+use crate::robot;
+
+/// One node of the cluster
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeRow {
+    pub name: String,
+    /// Hetzner server ID; Robot servers have none
+    pub server_id: Option<u64>,
+    pub role: String,
+    pub pool: Option<String>,
+    pub location: Option<String>,
+    pub server_type: Option<String>,
+    /// Hetzner server status; Robot servers have none
+    pub status: Option<String>,
+    pub public_ip: Option<String>,
+    pub private_ip: Option<String>,
+    pub talos_version: Option<String>,
+    pub kubernetes_version: Option<String>,
+    /// None if the node is unknown to Kubernetes or the cluster is unreachable
+    pub ready: Option<bool>,
+    /// RFC 3339 creation time of the server
+    pub created: Option<String>,
+}
+
+/// Filters of `oxide node list`; unset filters match everything
+#[derive(Debug, Default)]
+pub struct NodeFilter {
+    pub role: Option<NodeRole>,
+    pub pool: Option<String>,
+    pub location: Option<String>,
+}
+
+impl NodeFilter {
+    fn matches(&self, row: &NodeRow) -> bool {
+        self.role.is_none_or(|role| row.role == role.to_string())
+            && self
+                .pool
+                .as_ref()
+                .is_none_or(|pool| row.pool.as_ref() == Some(pool))
+            && self
+                .location
+                .as_ref()
+                .is_none_or(|location| row.location.as_ref() == Some(location))
+    }
+}
+
+/// Build the node rows matching `filter`, sorted by role and name
+pub fn rows(
+    config: &ClusterConfig,
+    servers: &[ServerInfo],
+    statuses: &HashMap<String, NodeStatus>,
+    filter: &NodeFilter,
+) -> Vec<NodeRow> {
+    let mut rows: Vec<NodeRow> = servers
+        .iter()
+        .map(|info| {
+            let server = &info.server;
+            let status = statuses.get(&server.name);
+            NodeRow {
+                name: server.name.clone(),
+                server_id: Some(server.id),
+                role: info.role.to_string(),
+                pool: pool_of(config, info).map(|pool| pool.name.clone()),
+                location: Some(ServerManager::location(server).to_string()),
+                server_type: Some(server.server_type.name.clone()),
+                status: Some(server.status.clone()),
+                public_ip: ServerManager::get_server_ip(server),
+                private_ip: ServerManager::get_server_private_ip(server),
+                talos_version: server.labels.get("talos-version").cloned(),
+                kubernetes_version: status.map(|s| s.kubelet_version.clone()),
+                ready: status.map(|s| s.ready),
+                created: Some(server.created.clone()),
+            }
+        })
+        .collect();
+
+    if let Some(robot_config) = &config.robot {
+        rows.extend(robot_config.servers.iter().map(|server| {
+            let name = robot::node_name(&config.cluster_name, server);
+            let status = statuses.get(&name);
+            NodeRow {
+                server_id: None,
+                role: NodeRole::Worker.to_string(),
+                pool: Some("robot".to_string()),
+                location: None,
+                server_type: None,
+                status: None,
+                public_ip: Some(server.public_ip.clone()),
+                private_ip: Some(server.private_ip.clone()),
+                talos_version: Some(config.talos.version.clone()),
+                kubernetes_version: status.map(|s| s.kubelet_version.clone()),
+                ready: status.map(|s| s.ready),
+                created: None,
+                name,
+            }
+        }));
+    }
+
+    rows.retain(|row| filter.matches(row));
+    rows.sort_by(|a, b| (&a.role, &a.name).cmp(&(&b.role, &b.name)));
+    rows
+}
+
+/// Config pool a server belongs to
+pub fn pool_of<'a>(config: &'a ClusterConfig, info: &ServerInfo) -> Option<&'a NodeConfig> {
+    let pools = match info.role {
+        NodeRole::ControlPlane => &config.control_planes,
+        NodeRole::Worker => &config.workers,
+    };
+    pools.iter().find(|pool| {
+        // Single-node pools have no index suffix
+        info.server.name == format!("{}-{}", config.cluster_name, pool.name)
+            || !ServerManager::filter_by_role_and_pool(
+                std::slice::from_ref(info),
+                info.role,
+                Some(&pool.name),
+            )
+            .is_empty()
+    })
+}
+
+/// Render rows as an aligned table; `wide` adds IPs, server type and versions
+pub fn render_table(rows: &[NodeRow], wide: bool, now: DateTime<Utc>) -> String {
+    let mut header = vec!["NAME", "ROLE", "POOL", "STATUS", "READY", "AGE"];
+    if wide {
+        header.extend([
+            "LOCATION",
+            "TYPE",
+            "PUBLIC-IP",
+            "PRIVATE-IP",
+            "TALOS",
+            "KUBERNETES",
+        ]);
+    }
+
+    let dash = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+    let mut table: Vec<Vec<String>> = vec![header.iter().map(|h| h.to_string()).collect()];
+    for row in rows {
+        let ready = match row.ready {
+            Some(true) => "Ready",
+            Some(false) => "NotReady",
+            None => "Unknown",
+        };
+        let age = row
+            .created
+            .as_deref()
+            .and_then(|created| DateTime::parse_from_rfc3339(created).ok())
+            .map(|created| format_age(now.signed_duration_since(created.with_timezone(&Utc))))
+            .unwrap_or_else(|| "-".to_string());
+
+        let mut cells = vec![
+            row.name.clone(),
+            row.role.clone(),
+            dash(&row.pool),
+            dash(&row.status),
+            ready.to_string(),
+            age,
+        ];
+        if wide {
+            cells.extend([
+                dash(&row.location),
+                dash(&row.server_type),
+                dash(&row.public_ip),
+                dash(&row.private_ip),
+                dash(&row.talos_version),
+                dash(&row.kubernetes_version),
+            ]);
+        }
+        table.push(cells);
+    }
+
+    align(&table)
+}
+
+/// Left-align the cells of a table with a header row into columns
+pub(crate) fn align(table: &[Vec<String>]) -> String {
+    let widths: Vec<usize> = (0..table[0].len())
+        .map(|column| table.iter().map(|cells| cells[column].len()).max().unwrap())
+        .collect();
+    table
+        .iter()
+        .map(|cells| {
+            let line: Vec<String> = cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect();
+            format!("{}\n", line.join("   ").trim_end())
+        })
+        .collect()
+}
+
+/// Compact age like kubectl: `45s`, `12m`, `5h`, `3d`
+pub(crate) fn format_age(age: chrono::Duration) -> String {
+    let secs = age.num_seconds().max(0);
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m", secs / 60),
+        3600..86400 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hcloud::models::Server;
+
+    fn server_info(name: &str, role: NodeRole, location: &str) -> ServerInfo {
+        let server: Server = serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "name": name,
+            "status": "running",
+            "server_type": {"id": 1, "name": "cx22", "description": "", "cores": 2, "memory": 4.0, "disk": 40},
+            "datacenter": {
+                "id": 1,
+                "name": format!("{}-dc1", location),
+                "description": "",
+                "location": {"id": 1, "name": location, "description": "", "country": "DE", "city": "", "latitude": 0.0, "longitude": 0.0}
+            },
+            "public_net": {"ipv4": {"ip": "198.51.100.10", "blocked": false}, "ipv6": null, "floating_ips": []},
+            "private_net": [{"network": 1, "ip": "10.0.1.2", "alias_ips": [], "mac_address": ""}],
+            "created": "2026-01-01T00:00:00Z",
+            "labels": {"talos-version": "v1.9.0"}
+        }))
+        .unwrap();
+        ServerInfo {
+            server,
+            role,
+            index: 1,
+        }
+    }
+
+    #[test]
+    fn test_rows_filter() {
+        let config = ClusterConfig::example();
+        let cluster = &config.cluster_name;
+        let servers = vec![
+            server_info(
+                &format!("{}-{}-1", cluster, config.workers[0].name),
+                NodeRole::Worker,
+                "fsn1",
+            ),
+            server_info(
+                &format!("{}-{}-1", cluster, config.control_planes[0].name),
+                NodeRole::ControlPlane,
+                "nbg1",
+            ),
+        ];
+        let statuses = HashMap::from([(
+            servers[0].server.name.clone(),
+            NodeStatus {
+                ready: true,
+                kubelet_version: "v1.31.1".to_string(),
+                talos_version: None,
+            },
+        )]);
+
+        let all = rows(&config, &servers, &statuses, &NodeFilter::default());
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].role, "control-plane");
+        assert_eq!(all[0].ready, None);
+        assert_eq!(
+            all[1].pool.as_deref(),
+            Some(config.workers[0].name.as_str())
+        );
+        assert_eq!(all[1].kubernetes_version.as_deref(), Some("v1.31.1"));
+
+        let workers = NodeFilter {
+            role: Some(NodeRole::Worker),
+            ..Default::default()
+        };
+        assert_eq!(rows(&config, &servers, &statuses, &workers).len(), 1);
+
+        let nbg1 = NodeFilter {
+            location: Some("nbg1".to_string()),
+            ..Default::default()
+        };
+        let matched = rows(&config, &servers, &statuses, &nbg1);
+        assert_eq!(matched.len(), 1);
+        assert_eq!(matched[0].role, "control-plane");
+
+        let unknown_pool = NodeFilter {
+            pool: Some("gpu".to_string()),
+            ..Default::default()
+        };
+        assert!(rows(&config, &servers, &statuses, &unknown_pool).is_empty());
+    }
+
+    #[test]
+    fn test_render_table() {
+        let config = ClusterConfig::example();
+        let servers = vec![server_info(
+            "talos-cluster-worker-1",
+            NodeRole::Worker,
+            "fsn1",
+        )];
+        let rows = rows(&config, &servers, &HashMap::new(), &NodeFilter::default());
+        let now = DateTime::parse_from_rfc3339("2026-01-03T06:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let table = render_table(&rows, false, now);
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[0].starts_with("NAME"));
+        assert!(lines[1].contains("Unknown"));
+        assert!(lines[1].ends_with("2d"));
+        assert!(!table.contains("PUBLIC-IP"));
+
+        let wide = render_table(&rows, true, now);
+        assert!(wide.contains("PUBLIC-IP"));
+        assert!(wide.contains("198.51.100.10"));
+        assert!(wide.contains("v1.9.0"));
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(chrono::Duration::seconds(45)), "45s");
+        assert_eq!(format_age(chrono::Duration::minutes(12)), "12m");
+        assert_eq!(format_age(chrono::Duration::hours(5)), "5h");
+        assert_eq!(format_age(chrono::Duration::days(3)), "3d");
+        assert_eq!(format_age(chrono::Duration::seconds(-5)), "0s");
+    }
+}
@@ -0,0 +1,183 @@
+/// Node resource usage for `oxide top`
+use serde::Serialize;
+use std::collections::HashMap;
+
+use super::{align, NodeRow};
+use crate::hcloud::models::ServerMetrics;
+use crate::k8s::nodes::NodeUsage;
+
+/// Resource usage of one node
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageRow {
+    pub name: String,
+    pub pool: Option<String>,
+    pub server_type: Option<String>,
+    /// CPU usage in cores from Kubernetes, e.g. `250m`
+    pub cpu: Option<String>,
+    pub cpu_percent: Option<u32>,
+    /// Memory usage from Kubernetes, e.g. `1024Mi`
+    pub memory: Option<String>,
+    pub memory_percent: Option<u32>,
+    /// Host CPU usage in percent from Hetzner
+    pub host_cpu_percent: Option<f64>,
+    /// Bytes per second
+    pub disk_read: Option<f64>,
+    pub disk_write: Option<f64>,
+    pub network_in: Option<f64>,
+    pub network_out: Option<f64>,
+}
+
+/// Combine node rows with Hetzner metrics (by server ID) and Kubernetes usage (by name)
+pub fn rows(
+    nodes: &[NodeRow],
+    metrics: &HashMap<u64, ServerMetrics>,
+    usage: &HashMap<String, NodeUsage>,
+) -> Vec<UsageRow> {
+    nodes
+        .iter()
+        .map(|node| {
+            let metrics = node.server_id.and_then(|id| metrics.get(&id));
+            let average = |name: &str| metrics.and_then(|m| m.average(name));
+            let usage = usage.get(&node.name);
+            UsageRow {
+                name: node.name.clone(),
+                pool: node.pool.clone(),
+                server_type: node.server_type.clone(),
+                cpu: usage.map(|u| u.cpu.clone()),
+                cpu_percent: usage.and_then(|u| u.cpu_percent),
+                memory: usage.map(|u| u.memory.clone()),
+                memory_percent: usage.and_then(|u| u.memory_percent),
+                host_cpu_percent: average("cpu"),
+                disk_read: average("disk.0.bandwidth.read"),
+                disk_write: average("disk.0.bandwidth.write"),
+                network_in: average("network.0.bandwidth.in"),
+                network_out: average("network.0.bandwidth.out"),
+            }
+        })
+        .collect()
+}
+
+/// Render usage rows as an aligned table
+pub fn render_table(rows: &[UsageRow]) -> String {
+    let header = [
+        "NAME",
+        "POOL",
+        "TYPE",
+        "CPU",
+        "CPU%",
+        "MEMORY",
+        "MEMORY%",
+        "HOST-CPU%",
+        "DISK-READ",
+        "DISK-WRITE",
+        "NET-IN",
+        "NET-OUT",
+    ];
+    let dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+    let percent = |value: Option<u32>| dash(value.map(|p| format!("{}%", p)));
+    let rate = |value: Option<f64>| dash(value.map(format_rate));
+
+    let mut table: Vec<Vec<String>> = vec![header.iter().map(|h| h.to_string()).collect()];
+    for row in rows {
+        table.push(vec![
+            row.name.clone(),
+            dash(row.pool.clone()),
+            dash(row.server_type.clone()),
+            dash(row.cpu.clone()),
+            percent(row.cpu_percent),
+            dash(row.memory.clone()),
+            percent(row.memory_percent),
+            dash(row.host_cpu_percent.map(|p| format!("{:.0}%", p))),
+            rate(row.disk_read),
+            rate(row.disk_write),
+            rate(row.network_in),
+            rate(row.network_out),
+        ]);
+    }
+    align(&table)
+}
+
+/// Human-readable throughput, e.g. `1.5MB/s`
+fn format_rate(bytes_per_sec: f64) -> String {
+    const UNITS: [&str; 4] = ["B/s", "KB/s", "MB/s", "GB/s"];
+    let mut value = bytes_per_sec;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{:.0}{}", value, UNITS[unit])
+    } else {
+        format!("{:.1}{}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str, server_id: Option<u64>) -> NodeRow {
+        NodeRow {
+            name: name.to_string(),
+            server_id,
+            role: "worker".to_string(),
+            pool: Some("worker".to_string()),
+            location: None,
+            server_type: Some("cpx31".to_string()),
+            status: None,
+            public_ip: None,
+            private_ip: None,
+            talos_version: None,
+            kubernetes_version: None,
+            ready: None,
+            created: None,
+        }
+    }
+
+    #[test]
+    fn test_rows() {
+        let nodes = vec![node("prod-worker-1", Some(7)), node("prod-dedi-1", None)];
+        let metrics: ServerMetrics = serde_json::from_value(serde_json::json!({
+            "start": "2026-01-01T00:00:00+00:00",
+            "end": "2026-01-01T00:05:00+00:00",
+            "step": 60.0,
+            "time_series": {
+                "cpu": {"values": [[1.0, "40"], [2.0, "60"]]},
+                "network.0.bandwidth.in": {"values": [[1.0, "1500000"]]}
+            }
+        }))
+        .unwrap();
+        let usage = HashMap::from([(
+            "prod-dedi-1".to_string(),
+            NodeUsage {
+                cpu: "900m".to_string(),
+                cpu_percent: Some(11),
+                memory: "30Gi".to_string(),
+                memory_percent: Some(47),
+            },
+        )]);
+
+        let rows = rows(&nodes, &HashMap::from([(7, metrics)]), &usage);
+        assert_eq!(rows[0].host_cpu_percent, Some(50.0));
+        assert_eq!(rows[0].network_in, Some(1_500_000.0));
+        assert_eq!(rows[0].disk_read, None);
+        assert_eq!(rows[0].cpu, None);
+        assert_eq!(rows[1].memory_percent, Some(47));
+        assert_eq!(rows[1].host_cpu_percent, None);
+
+        let table = render_table(&rows);
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[0].starts_with("NAME"));
+        assert!(lines[1].contains("50%"));
+        assert!(lines[1].contains("1.5MB/s"));
+        assert!(lines[2].contains("900m"));
+    }
+
+    #[test]
+    fn test_format_rate() {
+        assert_eq!(format_rate(512.0), "512B/s");
+        assert_eq!(format_rate(1_500.0), "1.5KB/s");
+        assert_eq!(format_rate(2_000_000_000.0), "2.0GB/s");
+    }
+}
@@ -1,5 +1,8 @@
 /// Kubernetes operations client
-use anyhow::Result;
+use anyhow::{Context, Result};
+use std::path::Path;
+
+use crate::utils::command::CommandBuilder;
 
 /// Kubernetes client for kubectl operations
 pub struct KubernetesClient;
@@ -14,6 +17,22 @@ impl KubernetesClient {
         )
         .await
     }
+
+    /// Version of the API server, e.g. `v1.34.1`
+    pub async fn server_version(kubeconfig_path: &Path) -> Result<String> {
+        let output = CommandBuilder::new("kubectl")
+            .args(["version", "-o", "json"])
+            .kubeconfig(kubeconfig_path)
+            .context("Failed to get the Kubernetes version")
+            .run()
+            .await?;
+
+        let json: serde_json::Value = serde_json::from_str(&output)?;
+        json["serverVersion"]["gitVersion"]
+            .as_str()
+            .map(str::to_string)
+            .context("kubectl did not report a server version")
+    }
 }
 
 #[cfg(test)]
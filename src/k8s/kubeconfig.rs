@@ -0,0 +1,338 @@
+/// Kubeconfig printing, merging and scoped ServiceAccount access
+use anyhow::{Context, Result};
+use serde_yaml::{Mapping, Value};
+use std::path::{Path, PathBuf};
+
+use crate::audit;
+use crate::utils::command::CommandBuilder;
+use crate::utils::platform;
+
+/// Namespace holding the ServiceAccounts created for shared access
+const ACCESS_NAMESPACE: &str = "oxide-access";
+
+/// A ServiceAccount with a role, for a token-based kubeconfig
+pub struct ServiceAccountAccess {
+    pub name: String,
+    /// ClusterRole to bind, e.g. `view`, `edit` or `admin`
+    pub role: String,
+    /// Bind the role in this namespace only, instead of cluster-wide
+    pub namespace: Option<String>,
+    /// Token lifetime as accepted by `kubectl create token`, e.g. `24h`
+    pub duration: String,
+}
+
+impl ServiceAccountAccess {
+    /// Create the ServiceAccount and binding, and mint a kubeconfig with a token
+    ///
+    /// Runs without logging so the result can be printed to stdout.
+    pub async fn kubeconfig(&self, admin_kubeconfig: &Path, cluster_name: &str) -> Result<String> {
+        let token = self.token(admin_kubeconfig).await?;
+
+        let admin = read(admin_kubeconfig)?;
+        let (_, cluster) = current_entry(&admin, "clusters", "cluster")
+            .context("Admin kubeconfig has no cluster for its current context")?;
+
+        let name = format!("{}-{}", cluster_name, self.name);
+        let mut context = Mapping::new();
+        context.insert("cluster".into(), name.clone().into());
+        context.insert("user".into(), name.clone().into());
+        if let Some(namespace) = &self.namespace {
+            context.insert("namespace".into(), namespace.clone().into());
+        }
+        let mut user = Mapping::new();
+        user.insert("token".into(), token.into());
+
+        let mut config = empty_config();
+        push_entry(&mut config, "clusters", "cluster", &name, cluster);
+        push_entry(&mut config, "users", "user", &name, user.into());
+        push_entry(&mut config, "contexts", "context", &name, context.into());
+        config["current-context"] = name.into();
+
+        Ok(serde_yaml::to_string(&config)?)
+    }
+
+    /// Create the ServiceAccount and binding, and mint a token for it
+    pub async fn token(&self, admin_kubeconfig: &Path) -> Result<String> {
+        let manifest = self.manifest();
+        let result = CommandBuilder::new("kubectl")
+            .args(["apply", "-f", "-"])
+            .kubeconfig(admin_kubeconfig)
+            .stdin(&manifest)
+            .context(format!("Failed to create ServiceAccount {}", self.name))
+            .run_silent()
+            .await;
+        audit::record(
+            "kubectl.apply",
+            &format!("serviceaccount/{}/{}", ACCESS_NAMESPACE, self.name),
+            &[
+                ("role", &self.role),
+                ("namespace", self.namespace.as_deref().unwrap_or("*")),
+            ],
+            &result,
+        );
+        result?;
+
+        let result = CommandBuilder::new("kubectl")
+            .args([
+                "-n",
+                ACCESS_NAMESPACE,
+                "create",
+                "token",
+                &self.name,
+                "--duration",
+                &self.duration,
+            ])
+            .kubeconfig(admin_kubeconfig)
+            .context(format!("Failed to create a token for {}", self.name))
+            .run()
+            .await;
+        audit::record(
+            "kubectl.create_token",
+            &format!("serviceaccount/{}/{}", ACCESS_NAMESPACE, self.name),
+            &[("duration", &self.duration)],
+            &result,
+        );
+        Ok(result?.trim().to_string())
+    }
+
+    fn manifest(&self) -> String {
+        let (kind, namespace) = match &self.namespace {
+            Some(namespace) => ("RoleBinding", format!("\n  namespace: {}", namespace)),
+            None => ("ClusterRoleBinding", String::new()),
+        };
+        format!(
+            "apiVersion: v1
+kind: Namespace
+metadata:
+  name: {access}
+---
+apiVersion: v1
+kind: ServiceAccount
+metadata:
+  name: {name}
+  namespace: {access}
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: {kind}
+metadata:
+  name: {access}-{name}{namespace}
+roleRef:
+  apiGroup: rbac.authorization.k8s.io
+  kind: ClusterRole
+  name: {role}
+subjects:
+  - kind: ServiceAccount
+    name: {name}
+    namespace: {access}
+",
+            access = ACCESS_NAMESPACE,
+            name = self.name,
+            role = self.role,
+        )
+    }
+}
+
+/// Merge the current context of `new` into `existing` under `name`
+///
+/// The cluster, user and context are all renamed to `name`, replacing any
+/// entries of that name, and `name` becomes the current context.
+pub fn merge(existing: Option<&str>, new: &str, name: &str) -> Result<String> {
+    let new: Value = serde_yaml::from_str(new).context("Invalid kubeconfig")?;
+    let mut merged = match existing {
+        Some(content) if !content.trim().is_empty() => {
+            serde_yaml::from_str(content).context("Invalid existing kubeconfig")?
+        }
+        _ => empty_config(),
+    };
+
+    let (_, mut context) =
+        current_entry(&new, "contexts", "context").context("Kubeconfig has no current context")?;
+    let (_, cluster) = current_entry(&new, "clusters", "cluster")
+        .context("Kubeconfig has no cluster for its current context")?;
+    let (_, user) = current_entry(&new, "users", "user")
+        .context("Kubeconfig has no user for its current context")?;
+    context["cluster"] = name.into();
+    context["user"] = name.into();
+
+    push_entry(&mut merged, "clusters", "cluster", name, cluster);
+    push_entry(&mut merged, "users", "user", name, user);
+    push_entry(&mut merged, "contexts", "context", name, context);
+    merged["current-context"] = name.into();
+
+    Ok(serde_yaml::to_string(&merged)?)
+}
+
+/// Kubeconfig kubectl uses by default: the first `$KUBECONFIG` entry, or
+/// `~/.kube/config`
+pub fn default_path() -> Result<PathBuf> {
+    if let Some(path) = std::env::var_os("KUBECONFIG")
+        .and_then(|paths| std::env::split_paths(&paths).find(|p| !p.as_os_str().is_empty()))
+    {
+        return Ok(path);
+    }
+    let home = platform::home_dir().context("Cannot locate ~/.kube/config, set KUBECONFIG")?;
+    Ok(home.join(".kube").join("config"))
+}
+
+/// Write a kubeconfig readable only by the owner
+pub fn write(path: &Path, content: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+    }
+    platform::write_private(path, content)
+}
+
+/// API server URL of the current context of the kubeconfig at `path`
+pub fn server(path: &Path) -> Result<String> {
+    let config = read(path)?;
+    let (_, cluster) = current_entry(&config, "clusters", "cluster").context(format!(
+        "{} has no cluster for its current context",
+        path.display()
+    ))?;
+    cluster["server"]
+        .as_str()
+        .map(String::from)
+        .context(format!("{} has no API server URL", path.display()))
+}
+
+fn read(path: &Path) -> Result<Value> {
+    let content =
+        std::fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+    serde_yaml::from_str(&content).context(format!("Invalid kubeconfig {}", path.display()))
+}
+
+fn empty_config() -> Value {
+    serde_yaml::from_str("apiVersion: v1\nkind: Config\nclusters: []\nusers: []\ncontexts: []\n")
+        .expect("static kubeconfig is valid")
+}
+
+/// Name and body of the `list` entry the current context refers to
+///
+/// For `contexts` that is the current context itself; for `clusters` and
+/// `users` the entry named by the current context's `cluster`/`user`.
+fn current_entry(config: &Value, list: &str, key: &str) -> Option<(String, Value)> {
+    let current = config["current-context"].as_str()?;
+    let context = find_entry(config, "contexts", "context", current)?;
+    if list == "contexts" {
+        return Some((current.to_string(), context));
+    }
+    let name = context[key].as_str()?.to_string();
+    let body = find_entry(config, list, key, &name)?;
+    Some((name, body))
+}
+
+/// Body (under `key`) of the entry `name` in `config[list]`
+fn find_entry(config: &Value, list: &str, key: &str, name: &str) -> Option<Value> {
+    config[list]
+        .as_sequence()?
+        .iter()
+        .find(|entry| entry["name"].as_str() == Some(name))
+        .map(|entry| entry[key].clone())
+}
+
+/// Replace or add the entry `name` in `config[list]`
+fn push_entry(config: &mut Value, list: &str, key: &str, name: &str, body: Value) {
+    let mut entry = Mapping::new();
+    entry.insert("name".into(), name.into());
+    entry.insert(key.into(), body);
+
+    if !config[list].is_sequence() {
+        config[list] = Value::Sequence(Vec::new());
+    }
+    if let Some(entries) = config[list].as_sequence_mut() {
+        entries.retain(|e| e["name"].as_str() != Some(name));
+        entries.push(entry.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TALOS_KUBECONFIG: &str = r#"apiVersion: v1
+kind: Config
+clusters:
+  - name: prod
+    cluster:
+      server: https://10.0.0.1:6443
+      certificate-authority-data: Q0E=
+users:
+  - name: admin@prod
+    user:
+      client-certificate-data: Q0VSVA==
+      client-key-data: S0VZ
+contexts:
+  - name: admin@prod
+    context:
+      cluster: prod
+      namespace: default
+      user: admin@prod
+current-context: admin@prod
+"#;
+
+    #[test]
+    fn test_merge_into_existing() {
+        let existing = r#"apiVersion: v1
+kind: Config
+clusters:
+  - name: other
+    cluster:
+      server: https://other:6443
+  - name: prod
+    cluster:
+      server: https://stale:6443
+users:
+  - name: other
+    user:
+      token: abc
+contexts:
+  - name: other
+    context:
+      cluster: other
+      user: other
+current-context: other
+"#;
+
+        let merged: Value =
+            serde_yaml::from_str(&merge(Some(existing), TALOS_KUBECONFIG, "prod").unwrap())
+                .unwrap();
+
+        assert_eq!(merged["current-context"], "prod");
+        assert_eq!(merged["clusters"].as_sequence().unwrap().len(), 2);
+        let (name, cluster) = current_entry(&merged, "clusters", "cluster").unwrap();
+        assert_eq!(name, "prod");
+        assert_eq!(cluster["server"], "https://10.0.0.1:6443");
+        let (_, user) = current_entry(&merged, "users", "user").unwrap();
+        assert_eq!(user["client-key-data"], "S0VZ");
+        let (_, context) = current_entry(&merged, "contexts", "context").unwrap();
+        assert_eq!(context["namespace"], "default");
+    }
+
+    #[test]
+    fn test_merge_into_empty() {
+        let merged: Value =
+            serde_yaml::from_str(&merge(None, TALOS_KUBECONFIG, "prod").unwrap()).unwrap();
+        assert_eq!(merged["contexts"].as_sequence().unwrap().len(), 1);
+        assert_eq!(merged["kind"], "Config");
+    }
+
+    #[test]
+    fn test_service_account_manifest() {
+        let mut access = ServiceAccountAccess {
+            name: "ci".to_string(),
+            role: "edit".to_string(),
+            namespace: Some("apps".to_string()),
+            duration: "1h".to_string(),
+        };
+        let manifest = access.manifest();
+        assert!(manifest.contains(
+            "kind: RoleBinding\nmetadata:\n  name: oxide-access-ci\n  namespace: apps\n"
+        ));
+        assert!(manifest.contains("  kind: ClusterRole\n  name: edit\n"));
+
+        access.namespace = None;
+        assert!(access
+            .manifest()
+            .contains("kind: ClusterRoleBinding\nmetadata:\n  name: oxide-access-ci\nroleRef:"));
+    }
+}
@@ -0,0 +1,270 @@
+/// Node maintenance windows
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use std::path::Path;
+use tracing::{info, warn};
+
+use super::NodeManager;
+use crate::audit;
+use crate::config::AlertmanagerConfig;
+use crate::utils::command::CommandBuilder;
+
+/// Annotation holding the reason of the maintenance
+pub const REASON_ANNOTATION: &str = "oxide.io/maintenance";
+
+/// Annotation holding when the maintenance started
+pub const SINCE_ANNOTATION: &str = "oxide.io/maintenance-since";
+
+/// Annotation holding the ID of the Alertmanager silence
+pub const SILENCE_ANNOTATION: &str = "oxide.io/maintenance-silence";
+
+/// Alert label carrying the node name (kube-state-metrics, node-exporter
+/// relabelled by kube-prometheus-stack)
+const NODE_ALERT_LABEL: &str = "node";
+
+/// Put a node into maintenance
+pub async fn enter(
+    kubeconfig_path: &Path,
+    node_name: &str,
+    reason: &str,
+    duration: Duration,
+    drain_timeout_secs: u64,
+    alertmanager: Option<&AlertmanagerConfig>,
+) -> Result<()> {
+    let now = Utc::now();
+    let mut annotations = vec![
+        format!("{}={}", REASON_ANNOTATION, reason),
+        format!(
+            "{}={}",
+            SINCE_ANNOTATION,
+            now.to_rfc3339_opts(SecondsFormat::Secs, true)
+        ),
+    ];
+
+    // Silenced before the drain, which makes pods of the node alert
+    if let Some(alertmanager) = alertmanager {
+        let request = silence(node_name, reason, now, duration);
+        let id = create_silence(kubeconfig_path, alertmanager, &request)
+            .await
+            .context(format!("Failed to silence the alerts of {}", node_name))?;
+        info!(
+            "✓ Silenced alerts of {} until {}",
+            node_name,
+            (now + duration).to_rfc3339_opts(SecondsFormat::Secs, true)
+        );
+        annotations.push(format!("{}={}", SILENCE_ANNOTATION, id));
+    }
+
+    let mut args = vec![
+        "annotate".to_string(),
+        "node".to_string(),
+        node_name.to_string(),
+        "--overwrite".to_string(),
+    ];
+    args.extend(annotations);
+    CommandBuilder::new("kubectl")
+        .args(&args)
+        .kubeconfig(kubeconfig_path)
+        .context(format!("Failed to annotate node {}", node_name))
+        .run()
+        .await?;
+
+    info!("Draining {}...", node_name);
+    let timeout = format!("--timeout={}s", drain_timeout_secs);
+    let result = CommandBuilder::new("kubectl")
+        .args([
+            "drain",
+            node_name,
+            "--ignore-daemonsets",
+            "--delete-emptydir-data",
+            &timeout,
+        ])
+        .kubeconfig(kubeconfig_path)
+        .context(format!("Failed to drain node {}", node_name))
+        .run()
+        .await;
+    audit::record(
+        "kubectl.drain",
+        &format!("node/{}", node_name),
+        &[("reason", reason)],
+        &result,
+    );
+    result?;
+
+    info!("✓ {} is in maintenance", node_name);
+    Ok(())
+}
+
+/// Take a node out of maintenance
+pub async fn exit(
+    kubeconfig_path: &Path,
+    node_name: &str,
+    ready_timeout_secs: u64,
+    alertmanager: Option<&AlertmanagerConfig>,
+) -> Result<()> {
+    let silence_id = CommandBuilder::new("kubectl")
+        .args([
+            "get",
+            "node",
+            node_name,
+            "-o",
+            &format!(
+                "jsonpath={{.metadata.annotations.{}}}",
+                SILENCE_ANNOTATION.replace('.', "\\.")
+            ),
+        ])
+        .kubeconfig(kubeconfig_path)
+        .context(format!("Failed to get node {}", node_name))
+        .run()
+        .await?;
+    let silence_id = silence_id.trim();
+
+    // A node that rebooted during maintenance has to be back before it takes
+    // workloads again
+    NodeManager::wait_for_node_ready(kubeconfig_path, node_name, ready_timeout_secs).await?;
+
+    let result = CommandBuilder::new("kubectl")
+        .args(["uncordon", node_name])
+        .kubeconfig(kubeconfig_path)
+        .context(format!("Failed to uncordon node {}", node_name))
+        .run()
+        .await;
+    audit::record(
+        "kubectl.uncordon",
+        &format!("node/{}", node_name),
+        &[],
+        &result,
+    );
+    result?;
+
+    CommandBuilder::new("kubectl")
+        .args([
+            "annotate",
+            "node",
+            node_name,
+            &format!("{}-", REASON_ANNOTATION),
+            &format!("{}-", SINCE_ANNOTATION),
+            &format!("{}-", SILENCE_ANNOTATION),
+        ])
+        .kubeconfig(kubeconfig_path)
+        .context(format!("Failed to annotate node {}", node_name))
+        .run()
+        .await?;
+
+    if !silence_id.is_empty() {
+        match alertmanager {
+            Some(alertmanager) => {
+                match expire_silence(kubeconfig_path, alertmanager, silence_id).await {
+                    Ok(()) => info!("✓ Expired the alert silence of {}", node_name),
+                    Err(e) => warn!("⚠️  Failed to expire alert silence {}: {:#}", silence_id, e),
+                }
+            }
+            None => warn!(
+                "⚠️  {} has alert silence {}, but no alertmanager is configured to expire it",
+                node_name, silence_id
+            ),
+        }
+    }
+
+    info!("✓ {} is back in service", node_name);
+    Ok(())
+}
+
+/// Alertmanager silence of the alerts of one node
+fn silence(
+    node_name: &str,
+    reason: &str,
+    start: DateTime<Utc>,
+    duration: Duration,
+) -> serde_json::Value {
+    serde_json::json!({
+        "matchers": [{
+            "name": NODE_ALERT_LABEL,
+            "value": node_name,
+            "isRegex": false,
+            "isEqual": true,
+        }],
+        "startsAt": start.to_rfc3339_opts(SecondsFormat::Secs, true),
+        "endsAt": (start + duration).to_rfc3339_opts(SecondsFormat::Secs, true),
+        "createdBy": "oxide",
+        "comment": format!("oxide node maintenance: {}", reason),
+    })
+}
+
+/// Alertmanager API path through the Kubernetes API service proxy, so no
+/// port-forward or ingress is needed
+fn api_path(alertmanager: &AlertmanagerConfig, path: &str) -> String {
+    format!(
+        "/api/v1/namespaces/{}/services/{}:{}/proxy/api/v2/{}",
+        alertmanager.namespace, alertmanager.service, alertmanager.port, path
+    )
+}
+
+async fn create_silence(
+    kubeconfig_path: &Path,
+    alertmanager: &AlertmanagerConfig,
+    silence: &serde_json::Value,
+) -> Result<String> {
+    let output = CommandBuilder::new("kubectl")
+        .args([
+            "create",
+            "--raw",
+            &api_path(alertmanager, "silences"),
+            "-f",
+            "-",
+        ])
+        .kubeconfig(kubeconfig_path)
+        .stdin(silence.to_string())
+        .context("Failed to reach Alertmanager")
+        .run()
+        .await?;
+
+    let response: serde_json::Value =
+        serde_json::from_str(&output).context("Invalid Alertmanager response")?;
+    response["silenceID"]
+        .as_str()
+        .map(str::to_string)
+        .context("Alertmanager returned no silence ID")
+}
+
+async fn expire_silence(
+    kubeconfig_path: &Path,
+    alertmanager: &AlertmanagerConfig,
+    id: &str,
+) -> Result<()> {
+    CommandBuilder::new("kubectl")
+        .args([
+            "delete",
+            "--raw",
+            &api_path(alertmanager, &format!("silence/{}", id)),
+        ])
+        .kubeconfig(kubeconfig_path)
+        .context("Failed to reach Alertmanager")
+        .run()
+        .await
+        .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_silence() {
+        let start = DateTime::parse_from_rfc3339("2025-03-01T10:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let silence = silence("prod-worker-1", "kernel update", start, Duration::hours(2));
+        assert_eq!(silence["matchers"][0]["name"], "node");
+        assert_eq!(silence["matchers"][0]["value"], "prod-worker-1");
+        assert_eq!(silence["startsAt"], "2025-03-01T10:00:00Z");
+        assert_eq!(silence["endsAt"], "2025-03-01T12:00:00Z");
+        assert_eq!(silence["comment"], "oxide node maintenance: kernel update");
+
+        let alertmanager: AlertmanagerConfig = serde_yaml::from_str("{}").unwrap();
+        assert_eq!(
+            api_path(&alertmanager, "silences"),
+            "/api/v1/namespaces/monitoring/services/alertmanager-operated:9093/proxy/api/v2/silences"
+        );
+    }
+}
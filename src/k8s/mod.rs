@@ -1,7 +1,12 @@
 /// Kubernetes cluster operations
 pub mod client;
+pub mod kubeconfig;
+pub mod maintenance;
 pub mod nodes;
+pub mod rbac;
 pub mod resources;
+pub mod smoke;
+pub mod tenants;
 
 pub use client::KubernetesClient;
 pub use nodes::NodeManager;
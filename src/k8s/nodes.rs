@@ -1,8 +1,10 @@
 /// Kubernetes node operations
 use anyhow::Result;
+use std::collections::HashMap;
 use std::path::Path;
 use tracing::info;
 
+use crate::audit;
 use crate::utils::command::CommandBuilder;
 use crate::utils::polling::PollingConfig;
 
@@ -14,6 +16,17 @@ impl NodeManager {
     pub async fn delete_node(kubeconfig_path: &Path, node_name: &str) -> Result<()> {
         info!("Deleting Kubernetes node: {}", node_name);
 
+        let result = Self::delete_node_inner(kubeconfig_path, node_name).await;
+        audit::record(
+            "kubectl.delete",
+            &format!("node/{}", node_name),
+            &[],
+            &result,
+        );
+        result
+    }
+
+    async fn delete_node_inner(kubeconfig_path: &Path, node_name: &str) -> Result<()> {
         let output = CommandBuilder::new("kubectl")
             .args(["delete", "node", node_name])
             .kubeconfig(kubeconfig_path)
@@ -81,6 +94,34 @@ impl NodeManager {
             .await
     }
 
+    /// Label a node with its Hetzner location and network zone
+    ///
+    /// Sets the well-known `topology.kubernetes.io/zone` (location, e.g. `fsn1`)
+    /// and `topology.kubernetes.io/region` (network zone, e.g. `eu-central`)
+    /// labels used by topology spread constraints and zone-aware storage.
+    pub async fn label_topology(
+        kubeconfig_path: &Path,
+        node_name: &str,
+        location: &str,
+        network_zone: &str,
+    ) -> Result<()> {
+        let zone = format!("topology.kubernetes.io/zone={}", location);
+        let region = format!("topology.kubernetes.io/region={}", network_zone);
+        let result = CommandBuilder::new("kubectl")
+            .args(["label", "node", node_name, "--overwrite", &zone, &region])
+            .kubeconfig(kubeconfig_path)
+            .context(format!("Failed to label node {}", node_name))
+            .run_silent()
+            .await;
+        audit::record(
+            "kubectl.label",
+            &format!("node/{}", node_name),
+            &[("zone", location), ("region", network_zone)],
+            &result,
+        );
+        result
+    }
+
     /// Wait for all Kubernetes nodes to be Ready
     pub async fn wait_for_all_nodes_ready(kubeconfig_path: &Path, timeout_secs: u64) -> Result<()> {
         info!("Waiting for all nodes to be Ready...");
@@ -215,6 +256,64 @@ impl NodeManager {
         Ok(pods)
     }
 
+    /// Readiness of the pods on a node, optionally filtered by label selector
+    ///
+    /// Completed pods (Succeeded or Failed) are left out.
+    pub async fn pod_readiness(
+        kubeconfig_path: &Path,
+        node_name: &str,
+        selector: Option<&str>,
+    ) -> Result<Vec<(String, bool)>> {
+        let field_selector = format!("spec.nodeName={}", node_name);
+        let mut args = vec![
+            "get",
+            "pods",
+            "--all-namespaces",
+            "--field-selector",
+            &field_selector,
+            "-o",
+            "json",
+        ];
+        if let Some(selector) = selector {
+            args.extend(["-l", selector]);
+        }
+
+        let output = CommandBuilder::new("kubectl")
+            .args(args)
+            .kubeconfig(kubeconfig_path)
+            .context(format!("Failed to get pods on node {}", node_name))
+            .run()
+            .await?;
+
+        Ok(parse_pod_readiness(&serde_json::from_str(&output)?))
+    }
+
+    /// Ready state and kubelet version of every node, keyed by node name
+    pub async fn node_statuses(kubeconfig_path: &Path) -> Result<HashMap<String, NodeStatus>> {
+        let output = CommandBuilder::new("kubectl")
+            .args(["get", "nodes", "-o", "json"])
+            .kubeconfig(kubeconfig_path)
+            .context("Failed to get nodes")
+            .run()
+            .await?;
+
+        Ok(parse_node_statuses(&serde_json::from_str(&output)?))
+    }
+
+    /// CPU and memory usage of every node from `kubectl top nodes`
+    ///
+    /// Needs the metrics API, i.e. metrics-server running in the cluster.
+    pub async fn top_nodes(kubeconfig_path: &Path) -> Result<HashMap<String, NodeUsage>> {
+        let output = CommandBuilder::new("kubectl")
+            .args(["top", "nodes", "--no-headers"])
+            .kubeconfig(kubeconfig_path)
+            .context("Failed to get node usage; is metrics-server installed?")
+            .run()
+            .await?;
+
+        Ok(parse_top_nodes(&output))
+    }
+
     /// Monitor pod draining progress on a node
     /// Returns when all pods are drained or timeout is reached
     pub async fn monitor_drain_progress(
@@ -339,3 +438,220 @@ impl NodeManager {
         Ok(())
     }
 }
+
+/// Node state as reported by Kubernetes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeStatus {
+    pub ready: bool,
+    pub kubelet_version: String,
+    /// Talos version from the OS image, e.g. `v1.11.2` for `Talos (v1.11.2)`
+    pub talos_version: Option<String>,
+}
+
+/// Status of each node in a `kubectl get nodes -o json` list
+fn parse_node_statuses(list: &serde_json::Value) -> HashMap<String, NodeStatus> {
+    let Some(items) = list["items"].as_array() else {
+        return HashMap::new();
+    };
+
+    items
+        .iter()
+        .map(|node| {
+            let ready = node["status"]["conditions"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .any(|c| c["type"] == "Ready" && c["status"] == "True");
+            let status = NodeStatus {
+                ready,
+                kubelet_version: node["status"]["nodeInfo"]["kubeletVersion"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                talos_version: node["status"]["nodeInfo"]["osImage"]
+                    .as_str()
+                    .and_then(|image| image.strip_prefix("Talos ("))
+                    .and_then(|rest| rest.split(')').next())
+                    .map(str::to_string),
+            };
+            (
+                node["metadata"]["name"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                status,
+            )
+        })
+        .collect()
+}
+
+/// Node usage as reported by the metrics API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeUsage {
+    /// CPU usage in cores, e.g. `250m`
+    pub cpu: String,
+    pub cpu_percent: Option<u32>,
+    /// Memory usage, e.g. `1024Mi`
+    pub memory: String,
+    pub memory_percent: Option<u32>,
+}
+
+/// Usage of each node in `kubectl top nodes --no-headers` output
+///
+/// Nodes without metrics yet show `<unknown>` and are left out.
+fn parse_top_nodes(output: &str) -> HashMap<String, NodeUsage> {
+    let percent = |value: &str| value.trim_end_matches('%').parse().ok();
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [name, cpu, cpu_percent, memory, memory_percent] = fields[..] else {
+                return None;
+            };
+            if cpu == "<unknown>" {
+                return None;
+            }
+            let usage = NodeUsage {
+                cpu: cpu.to_string(),
+                cpu_percent: percent(cpu_percent),
+                memory: memory.to_string(),
+                memory_percent: percent(memory_percent),
+            };
+            Some((name.to_string(), usage))
+        })
+        .collect()
+}
+
+/// Name and readiness of each running pod in a `kubectl get pods -o json` list
+fn parse_pod_readiness(list: &serde_json::Value) -> Vec<(String, bool)> {
+    let Some(items) = list["items"].as_array() else {
+        return Vec::new();
+    };
+
+    items
+        .iter()
+        .filter(|pod| {
+            !matches!(
+                pod["status"]["phase"].as_str(),
+                Some("Succeeded" | "Failed")
+            )
+        })
+        .map(|pod| {
+            let name = format!(
+                "{}/{}",
+                pod["metadata"]["namespace"].as_str().unwrap_or_default(),
+                pod["metadata"]["name"].as_str().unwrap_or_default()
+            );
+            let ready = pod["status"]["conditions"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .any(|c| c["type"] == "Ready" && c["status"] == "True");
+            (name, ready)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pod_readiness() {
+        let list = serde_json::json!({
+            "items": [
+                {
+                    "metadata": {"namespace": "kube-system", "name": "cilium-abc"},
+                    "status": {"phase": "Running", "conditions": [{"type": "Ready", "status": "True"}]}
+                },
+                {
+                    "metadata": {"namespace": "default", "name": "web-1"},
+                    "status": {"phase": "Pending", "conditions": [{"type": "Ready", "status": "False"}]}
+                },
+                {
+                    "metadata": {"namespace": "default", "name": "job-xyz"},
+                    "status": {"phase": "Succeeded"}
+                }
+            ]
+        });
+
+        assert_eq!(
+            parse_pod_readiness(&list),
+            vec![
+                ("kube-system/cilium-abc".to_string(), true),
+                ("default/web-1".to_string(), false)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_node_statuses() {
+        let list = serde_json::json!({
+            "items": [
+                {
+                    "metadata": {"name": "prod-control-plane-1"},
+                    "status": {
+                        "conditions": [{"type": "Ready", "status": "True"}],
+                        "nodeInfo": {"kubeletVersion": "v1.31.1"}
+                    }
+                },
+                {
+                    "metadata": {"name": "prod-worker-1"},
+                    "status": {
+                        "conditions": [{"type": "Ready", "status": "Unknown"}],
+                        "nodeInfo": {"kubeletVersion": "v1.30.5"}
+                    }
+                }
+            ]
+        });
+
+        let statuses = parse_node_statuses(&list);
+        assert_eq!(statuses.len(), 2);
+        assert!(statuses["prod-control-plane-1"].ready);
+        assert!(!statuses["prod-worker-1"].ready);
+        assert_eq!(statuses["prod-worker-1"].kubelet_version, "v1.30.5");
+    }
+
+    #[test]
+    fn test_parse_talos_version() {
+        let list = serde_json::json!({
+            "items": [
+                {
+                    "metadata": {"name": "prod-control-plane-1"},
+                    "status": {"nodeInfo": {"osImage": "Talos (v1.11.2)"}}
+                },
+                {
+                    "metadata": {"name": "prod-worker-1"},
+                    "status": {"nodeInfo": {"osImage": "Ubuntu 24.04.1 LTS"}}
+                }
+            ]
+        });
+
+        let statuses = parse_node_statuses(&list);
+        assert_eq!(
+            statuses["prod-control-plane-1"].talos_version.as_deref(),
+            Some("v1.11.2")
+        );
+        assert_eq!(statuses["prod-worker-1"].talos_version, None);
+    }
+
+    #[test]
+    fn test_parse_top_nodes() {
+        let output = "\
+prod-control-plane-1   250m        12%    1843Mi          48%
+prod-worker-1          1200m       30%    6012Mi          76%
+prod-worker-2          <unknown>   <unknown>   <unknown>   <unknown>
+";
+        let usage = parse_top_nodes(output);
+        assert_eq!(usage.len(), 2);
+        assert_eq!(
+            usage["prod-worker-1"],
+            NodeUsage {
+                cpu: "1200m".to_string(),
+                cpu_percent: Some(30),
+                memory: "6012Mi".to_string(),
+                memory_percent: Some(76),
+            }
+        );
+    }
+}
@@ -0,0 +1,246 @@
+/// Operator RBAC created right after bootstrap
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use std::path::Path;
+use tracing::info;
+
+use super::ResourceManager;
+use crate::config::{RbacConfig, RbacGroup};
+
+/// Apply the configured roles, bindings and ServiceAccounts
+pub async fn apply(config: &RbacConfig, kubeconfig_path: &Path) -> Result<()> {
+    let manifest = manifest(config);
+    if manifest.is_empty() {
+        return Ok(());
+    }
+    info!(
+        "Applying operator RBAC: {} group(s), {} ServiceAccount(s)",
+        config.groups.len(),
+        config.service_accounts.len()
+    );
+    ResourceManager::apply_stdin(kubeconfig_path, &manifest).await?;
+    info!("✓ Operator RBAC applied");
+    Ok(())
+}
+
+/// Render all RBAC objects as a multi-document manifest
+pub fn manifest(config: &RbacConfig) -> String {
+    let mut objects = Vec::new();
+
+    // RoleBindings and ServiceAccounts need their namespaces to exist
+    let namespaces: BTreeSet<&str> = config
+        .groups
+        .iter()
+        .flat_map(|g| g.namespaces.iter())
+        .chain(
+            config
+                .service_accounts
+                .iter()
+                .flat_map(|sa| sa.namespaces.iter().chain(std::iter::once(&sa.namespace))),
+        )
+        .map(String::as_str)
+        .collect();
+    for namespace in namespaces {
+        objects.push(json!({
+            "apiVersion": "v1",
+            "kind": "Namespace",
+            "metadata": metadata(namespace, None),
+        }));
+    }
+
+    for role in &config.cluster_roles {
+        objects.push(json!({
+            "apiVersion": "rbac.authorization.k8s.io/v1",
+            "kind": "ClusterRole",
+            "metadata": metadata(&role.name, None),
+            "rules": role.rules,
+        }));
+    }
+
+    let prefix = config.oidc.as_ref().map_or("", |oidc| oidc.prefix.as_str());
+    for group in &config.groups {
+        let subjects: Vec<Value> = group_names(group, prefix)
+            .into_iter()
+            .map(|name| {
+                json!({
+                    "apiGroup": "rbac.authorization.k8s.io",
+                    "kind": "Group",
+                    "name": name,
+                })
+            })
+            .collect();
+        objects.extend(bindings(
+            &format!("oxide-group-{}", group.name),
+            &group.cluster_role,
+            &group.namespaces,
+            &subjects,
+        ));
+    }
+
+    for sa in &config.service_accounts {
+        objects.push(json!({
+            "apiVersion": "v1",
+            "kind": "ServiceAccount",
+            "metadata": metadata(&sa.name, Some(&sa.namespace)),
+        }));
+        let subjects = [json!({
+            "kind": "ServiceAccount",
+            "name": sa.name,
+            "namespace": sa.namespace,
+        })];
+        objects.extend(bindings(
+            &format!("oxide-sa-{}-{}", sa.namespace, sa.name),
+            &sa.cluster_role,
+            &sa.namespaces,
+            &subjects,
+        ));
+    }
+
+    objects
+        .iter()
+        .map(|object| serde_yaml::to_string(object).expect("RBAC object serializes"))
+        .collect::<Vec<_>>()
+        .join("---\n")
+}
+
+/// Kubernetes group names a configured group binds
+///
+/// Without OIDC mappings the group name itself is used, e.g. for the
+/// organization of client certificates.
+fn group_names(group: &RbacGroup, prefix: &str) -> Vec<String> {
+    if group.oidc_groups.is_empty() {
+        return vec![group.name.clone()];
+    }
+    group
+        .oidc_groups
+        .iter()
+        .map(|g| format!("{}{}", prefix, g))
+        .collect()
+}
+
+/// A ClusterRoleBinding, or one RoleBinding per namespace
+fn bindings(name: &str, role: &str, namespaces: &[String], subjects: &[Value]) -> Vec<Value> {
+    let role_ref = json!({
+        "apiGroup": "rbac.authorization.k8s.io",
+        "kind": "ClusterRole",
+        "name": role,
+    });
+    if namespaces.is_empty() {
+        return vec![json!({
+            "apiVersion": "rbac.authorization.k8s.io/v1",
+            "kind": "ClusterRoleBinding",
+            "metadata": metadata(name, None),
+            "roleRef": role_ref,
+            "subjects": subjects,
+        })];
+    }
+    namespaces
+        .iter()
+        .map(|namespace| {
+            json!({
+                "apiVersion": "rbac.authorization.k8s.io/v1",
+                "kind": "RoleBinding",
+                "metadata": metadata(name, Some(namespace)),
+                "roleRef": role_ref,
+                "subjects": subjects,
+            })
+        })
+        .collect()
+}
+
+fn metadata(name: &str, namespace: Option<&str>) -> Value {
+    let mut metadata = json!({
+        "name": name,
+        "labels": { "app.kubernetes.io/managed-by": "oxide" },
+    });
+    if let Some(namespace) = namespace {
+        metadata["namespace"] = namespace.into();
+    }
+    metadata
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+oidc:
+  issuer_url: https://auth.example.com
+  client_id: kubernetes
+cluster_roles:
+  - name: deployer
+    rules:
+      - apiGroups: [apps]
+        resources: [deployments]
+        verbs: [get, list, patch]
+groups:
+  - name: viewers
+    cluster_role: view
+    oidc_groups: [platform, support]
+  - name: deployers
+    cluster_role: deployer
+    oidc_groups: [developers]
+    namespaces: [apps, staging]
+service_accounts:
+  - name: ci
+    namespace: ci
+    cluster_role: deployer
+    namespaces: [apps]
+"#;
+
+    fn documents(manifest: &str) -> Vec<serde_yaml::Value> {
+        manifest
+            .split("---\n")
+            .map(|doc| serde_yaml::from_str(doc).unwrap())
+            .collect()
+    }
+
+    fn find<'a>(
+        docs: &'a [serde_yaml::Value],
+        kind: &str,
+        name: &str,
+    ) -> Vec<&'a serde_yaml::Value> {
+        docs.iter()
+            .filter(|d| d["kind"] == kind && d["metadata"]["name"] == name)
+            .collect()
+    }
+
+    #[test]
+    fn test_manifest() {
+        let config: RbacConfig = serde_yaml::from_str(CONFIG).unwrap();
+        let docs = documents(&manifest(&config));
+
+        let namespaces: Vec<_> = docs
+            .iter()
+            .filter(|d| d["kind"] == "Namespace")
+            .map(|d| d["metadata"]["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(namespaces, ["apps", "ci", "staging"]);
+
+        let role = find(&docs, "ClusterRole", "deployer");
+        assert_eq!(role[0]["rules"][0]["resources"][0], "deployments");
+
+        let viewers = find(&docs, "ClusterRoleBinding", "oxide-group-viewers");
+        assert_eq!(viewers[0]["roleRef"]["name"], "view");
+        assert_eq!(viewers[0]["subjects"][1]["name"], "oidc:support");
+
+        let deployers = find(&docs, "RoleBinding", "oxide-group-deployers");
+        assert_eq!(deployers.len(), 2);
+        assert_eq!(deployers[1]["metadata"]["namespace"], "staging");
+
+        let ci = find(&docs, "RoleBinding", "oxide-sa-ci-ci");
+        assert_eq!(ci[0]["metadata"]["namespace"], "apps");
+        assert_eq!(ci[0]["subjects"][0]["kind"], "ServiceAccount");
+        assert_eq!(ci[0]["subjects"][0]["namespace"], "ci");
+    }
+
+    #[test]
+    fn test_group_without_oidc() {
+        let config: RbacConfig =
+            serde_yaml::from_str("groups:\n  - name: ops\n    cluster_role: admin\n").unwrap();
+        let docs = documents(&manifest(&config));
+        assert_eq!(docs.len(), 1);
+        assert_eq!(docs[0]["subjects"][0]["name"], "ops");
+    }
+}
@@ -1,27 +1,664 @@
 /// Generic Kubernetes resource operations
-use anyhow::Result;
-use std::path::Path;
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use tracing::info;
 
+use crate::audit;
 use crate::utils::command::CommandBuilder;
 
+/// Directory next to the kubeconfig holding the last applied managed manifests
+pub const MANIFESTS_DIR: &str = "manifests";
+
+/// Label marking objects applied by oxide
+const MANAGED_BY_LABEL: &str = "app.kubernetes.io/managed-by";
+
+/// Label naming the managed manifest an object was applied from, so objects
+/// dropped from a later version of that manifest can be pruned
+pub const MANIFEST_LABEL: &str = "oxide/manifest";
+
+/// Workload resources whose rollout is awaited after applying a manifest
+const ROLLOUT_RESOURCES: &[&str] = &["deployment.apps", "daemonset.apps", "statefulset.apps"];
+
+/// Gateway API gateways, awaited until programmed
+const GATEWAY_RESOURCE: &str = "gateway.gateway.networking.k8s.io";
+
 /// Generic Kubernetes resource management
 pub struct ResourceManager;
 
 impl ResourceManager {
-    /// Apply a Kubernetes manifest file
-    pub async fn apply_manifest(kubeconfig_path: &Path, manifest_path: &Path) -> Result<()> {
+    /// Apply a Kubernetes manifest file and wait for what it declares to roll out
+    ///
+    /// The file is applied as the managed manifest named after the file, so
+    /// objects removed from it are pruned when it is applied again.
+    pub async fn apply_manifest(
+        kubeconfig_path: &Path,
+        manifest_path: &Path,
+        timeout_secs: u64,
+    ) -> Result<()> {
         info!("Applying Kubernetes manifest: {}", manifest_path.display());
 
-        let stdout = CommandBuilder::new("kubectl")
-            .args(["apply", "-f", manifest_path.to_str().unwrap()])
+        let manifest = std::fs::read_to_string(manifest_path)
+            .context(format!("Failed to read {}", manifest_path.display()))?;
+        let name = manifest_path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| anyhow::anyhow!("Invalid manifest path {}", manifest_path.display()))?;
+
+        Self::apply_managed(kubeconfig_path, "default", name, &manifest).await?;
+        Self::wait_for_manifest(kubeconfig_path, "default", &manifest, timeout_secs).await
+    }
+
+    /// Server-side apply a manifest as the managed manifest `name`, pruning
+    /// the objects the previous version of it had and this one does not
+    ///
+    /// Every object is labeled with [`MANIFEST_LABEL`]. The manifest is saved
+    /// to `manifests/<name>.yaml` next to the kubeconfig, and only objects
+    /// still carrying the label of this manifest are pruned, so objects that
+    /// were adopted by something else in the meantime survive.
+    pub async fn apply_managed(
+        kubeconfig_path: &Path,
+        namespace: &str,
+        name: &str,
+        manifest: &str,
+    ) -> Result<()> {
+        let path = Self::saved_manifest_path(kubeconfig_path, name);
+        let previous = std::fs::read_to_string(&path).ok();
+
+        let labeled = label_manifest(manifest, name)?;
+        Self::apply_server_side(kubeconfig_path, namespace, &labeled).await?;
+
+        if let Some(previous) = previous {
+            for object in stale_objects(&previous, manifest) {
+                Self::prune(kubeconfig_path, namespace, name, &object).await?;
+            }
+        }
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+        }
+        std::fs::write(&path, manifest).context(format!("Failed to save {}", path.display()))?;
+        info!("✓ Applied {}", name);
+        Ok(())
+    }
+
+    /// Where the last applied version of the managed manifest `name` is kept
+    pub fn saved_manifest_path(kubeconfig_path: &Path, name: &str) -> PathBuf {
+        kubeconfig_path
+            .with_file_name(MANIFESTS_DIR)
+            .join(format!("{}.yaml", name))
+    }
+
+    /// Delete an object dropped from the managed manifest `name`, if it still
+    /// carries the label of that manifest
+    async fn prune(
+        kubeconfig_path: &Path,
+        namespace: &str,
+        name: &str,
+        object: &ObjectRef,
+    ) -> Result<()> {
+        let selector = format!("{}={}", MANIFEST_LABEL, name);
+        let field_selector = format!("metadata.name={}", object.name);
+        let result = CommandBuilder::new("kubectl")
+            .args([
+                "delete",
+                &object.resource,
+                "-n",
+                object.namespace.as_deref().unwrap_or(namespace),
+                "-l",
+                &selector,
+                "--field-selector",
+                &field_selector,
+                "--ignore-not-found",
+            ])
             .kubeconfig(kubeconfig_path)
-            .context("Failed to apply manifest")
+            .context(format!(
+                "Failed to prune {}/{}",
+                object.resource, object.name
+            ))
+            .run()
+            .await;
+        audit::record(
+            "kubectl.prune",
+            &format!("{}/{}", object.resource, object.name),
+            &[("manifest", name)],
+            &result,
+        );
+        if !result?.trim().is_empty() {
+            info!("✓ Pruned {}/{}", object.resource, object.name);
+        }
+        Ok(())
+    }
+
+    /// Create a namespace (if missing) labeled for the privileged Pod Security Standard
+    ///
+    /// Talos enables PodSecurity admission with a `baseline` default, so
+    /// workloads that need host access must live in an explicitly exempted namespace.
+    pub async fn ensure_privileged_namespace(
+        kubeconfig_path: &Path,
+        namespace: &str,
+    ) -> Result<()> {
+        let manifest = CommandBuilder::new("kubectl")
+            .args([
+                "create",
+                "namespace",
+                namespace,
+                "--dry-run=client",
+                "-o",
+                "yaml",
+            ])
+            .kubeconfig(kubeconfig_path)
+            .context("Failed to render namespace manifest")
             .run()
             .await?;
 
+        Self::apply_stdin(kubeconfig_path, &manifest).await?;
+
+        CommandBuilder::new("kubectl")
+            .args([
+                "label",
+                "namespace",
+                namespace,
+                "--overwrite",
+                "pod-security.kubernetes.io/enforce=privileged",
+            ])
+            .kubeconfig(kubeconfig_path)
+            .context(format!("Failed to label namespace {}", namespace))
+            .run_silent()
+            .await
+    }
+
+    /// Apply a manifest passed as a string
+    pub async fn apply_stdin(kubeconfig_path: &Path, manifest: &str) -> Result<()> {
+        let result = CommandBuilder::new("kubectl")
+            .args(["apply", "-f", "-"])
+            .kubeconfig(kubeconfig_path)
+            .stdin(manifest)
+            .context("Failed to apply manifest")
+            .run()
+            .await;
+        audit::record("kubectl.apply", &manifest_summary(manifest), &[], &result);
+        let stdout = result?;
+
         info!("{}", stdout.trim());
 
         Ok(())
     }
+
+    /// Server-side apply a manifest, taking over fields from other managers
+    ///
+    /// `namespace` is used for objects that do not set one themselves.
+    pub async fn apply_server_side(
+        kubeconfig_path: &Path,
+        namespace: &str,
+        manifest: &str,
+    ) -> Result<()> {
+        let result = CommandBuilder::new("kubectl")
+            .args([
+                "apply",
+                "--server-side",
+                "--field-manager=oxide",
+                "--force-conflicts",
+                "-n",
+                namespace,
+                "-f",
+                "-",
+            ])
+            .kubeconfig(kubeconfig_path)
+            .stdin(manifest)
+            .context("Failed to apply manifest")
+            .run()
+            .await;
+        audit::record(
+            "kubectl.apply_server_side",
+            &manifest_summary(manifest),
+            &[("namespace", namespace)],
+            &result,
+        );
+        result?;
+
+        Ok(())
+    }
+
+    /// Server-side apply a manifest published at `url`, like the release
+    /// manifests of an operator
+    ///
+    /// Server-side, since large CRDs do not fit the last-applied annotation
+    /// of a client-side apply.
+    pub async fn apply_remote(kubeconfig_path: &Path, url: &str) -> Result<()> {
+        let result = CommandBuilder::new("kubectl")
+            .args([
+                "apply",
+                "--server-side",
+                "--field-manager=oxide",
+                "--force-conflicts",
+                "-f",
+                url,
+            ])
+            .kubeconfig(kubeconfig_path)
+            .context(format!("Failed to apply {}", url))
+            .run_silent()
+            .await;
+        audit::record("kubectl.apply_server_side", url, &[], &result);
+        result
+    }
+
+    /// Wait for the Deployments, DaemonSets and StatefulSets of a manifest to
+    /// roll out and its Gateways to be programmed, within `timeout_secs` in total
+    ///
+    /// `namespace` is used for objects that do not set one themselves.
+    pub async fn wait_for_manifest(
+        kubeconfig_path: &Path,
+        namespace: &str,
+        manifest: &str,
+        timeout_secs: u64,
+    ) -> Result<()> {
+        let deadline = Instant::now() + Duration::from_secs(timeout_secs);
+        for object in rollout_objects(manifest) {
+            let remaining = deadline
+                .saturating_duration_since(Instant::now())
+                .as_secs()
+                .max(1);
+            let namespace = object.namespace.as_deref().unwrap_or(namespace);
+            let resource = format!("{}/{}", object.resource, object.name);
+            if object.resource == GATEWAY_RESOURCE {
+                Self::wait_for_gateway(kubeconfig_path, namespace, &resource, remaining).await?;
+            } else {
+                Self::wait_for_rollout(kubeconfig_path, namespace, &resource, remaining).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Workloads and Gateways of a manifest that are missing or not ready, as
+    /// `resource/name: reason`; empty when everything is ready
+    ///
+    /// `namespace` is used for objects that do not set one themselves.
+    pub async fn manifest_problems(
+        kubeconfig_path: &Path,
+        namespace: &str,
+        manifest: &str,
+    ) -> Result<Vec<String>> {
+        let mut by_namespace: BTreeMap<String, Vec<ObjectRef>> = BTreeMap::new();
+        for object in rollout_objects(manifest) {
+            let object_namespace = object.namespace.as_deref().unwrap_or(namespace);
+            by_namespace
+                .entry(object_namespace.to_string())
+                .or_default()
+                .push(object);
+        }
+
+        let mut problems = Vec::new();
+        for (namespace, objects) in by_namespace {
+            let resources: Vec<String> = objects
+                .iter()
+                .map(|object| format!("{}/{}", object.resource, object.name))
+                .collect();
+            let output = CommandBuilder::new("kubectl")
+                .arg("get")
+                .args(&resources)
+                .args(["-n", &namespace, "-o", "json", "--ignore-not-found"])
+                .kubeconfig(kubeconfig_path)
+                .context(format!("Failed to get the workloads in {}", namespace))
+                .run()
+                .await?;
+            let live = live_objects(&output)?;
+
+            for (object, resource) in objects.iter().zip(&resources) {
+                let found = live.iter().find(|doc| {
+                    ObjectRef::from_document(doc).is_some_and(|live| {
+                        live.resource == object.resource && live.name == object.name
+                    })
+                });
+                match found {
+                    None => problems.push(format!("{}: not found", resource)),
+                    Some(doc) => {
+                        if let Some(reason) = unready_reason(doc) {
+                            problems.push(format!("{}: {}", resource, reason));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(problems)
+    }
+
+    /// Wait for a Gateway to be accepted and programmed by its controller
+    async fn wait_for_gateway(
+        kubeconfig_path: &Path,
+        namespace: &str,
+        resource: &str,
+        timeout_secs: u64,
+    ) -> Result<()> {
+        info!(
+            "Waiting for {} in {} to be programmed...",
+            resource, namespace
+        );
+
+        CommandBuilder::new("kubectl")
+            .args([
+                "wait",
+                "--for=condition=Programmed",
+                resource,
+                "-n",
+                namespace,
+                &format!("--timeout={}s", timeout_secs),
+            ])
+            .kubeconfig(kubeconfig_path)
+            .context(format!("{} was not programmed", resource))
+            .run_silent()
+            .await?;
+
+        info!("✓ {} programmed", resource);
+        Ok(())
+    }
+
+    /// Wait for a workload (e.g. `daemonset/foo`) to finish rolling out
+    pub async fn wait_for_rollout(
+        kubeconfig_path: &Path,
+        namespace: &str,
+        resource: &str,
+        timeout_secs: u64,
+    ) -> Result<()> {
+        info!("Waiting for {} in {} to roll out...", resource, namespace);
+
+        CommandBuilder::new("kubectl")
+            .args([
+                "rollout",
+                "status",
+                resource,
+                "-n",
+                namespace,
+                &format!("--timeout={}s", timeout_secs),
+            ])
+            .kubeconfig(kubeconfig_path)
+            .context(format!("Failed to wait for rollout of {}", resource))
+            .run_streaming()
+            .await?;
+
+        info!("✓ {} rolled out", resource);
+        Ok(())
+    }
+}
+
+/// Object of a manifest, addressed the way `kubectl delete` expects
+#[derive(Debug, PartialEq, Eq)]
+struct ObjectRef {
+    /// `kind.group`, or just `kind` for the core group
+    resource: String,
+    namespace: Option<String>,
+    name: String,
+}
+
+/// Objects of a multi-document manifest keyed by `kind/[namespace/]name`
+pub fn manifest_objects(manifest: &str) -> BTreeMap<String, serde_yaml::Value> {
+    use serde::Deserialize;
+
+    serde_yaml::Deserializer::from_str(manifest)
+        .filter_map(|doc| serde_yaml::Value::deserialize(doc).ok())
+        .filter_map(|doc| {
+            let kind = doc["kind"].as_str()?.to_lowercase();
+            let name = doc["metadata"]["name"].as_str()?;
+            let key = match doc["metadata"]["namespace"].as_str() {
+                Some(namespace) => format!("{}/{}/{}", kind, namespace, name),
+                None => format!("{}/{}", kind, name),
+            };
+            Some((key, doc))
+        })
+        .collect()
+}
+
+impl ObjectRef {
+    fn from_document(doc: &serde_yaml::Value) -> Option<Self> {
+        let kind = doc["kind"].as_str()?.to_lowercase();
+        // Kinds are only unique within an API group
+        let resource = match doc["apiVersion"].as_str()?.split_once('/') {
+            Some((group, _)) => format!("{}.{}", kind, group),
+            None => kind,
+        };
+        Some(ObjectRef {
+            resource,
+            namespace: doc["metadata"]["namespace"].as_str().map(str::to_string),
+            name: doc["metadata"]["name"].as_str()?.to_string(),
+        })
+    }
+}
+
+/// Objects of `previous` that are gone from `current`
+fn stale_objects(previous: &str, current: &str) -> Vec<ObjectRef> {
+    let current = manifest_objects(current);
+    manifest_objects(previous)
+        .into_iter()
+        .filter(|(key, _)| !current.contains_key(key))
+        .filter_map(|(_, doc)| ObjectRef::from_document(&doc))
+        .collect()
+}
+
+/// Workloads and Gateways of a manifest, in manifest order
+fn rollout_objects(manifest: &str) -> Vec<ObjectRef> {
+    use serde::Deserialize;
+
+    serde_yaml::Deserializer::from_str(manifest)
+        .filter_map(|doc| serde_yaml::Value::deserialize(doc).ok())
+        .filter_map(|doc| ObjectRef::from_document(&doc))
+        .filter(|object| {
+            ROLLOUT_RESOURCES.contains(&object.resource.as_str())
+                || object.resource == GATEWAY_RESOURCE
+        })
+        .collect()
+}
+
+/// Objects printed by `kubectl get -o json`, which prints a single object
+/// as is and several as a `List`
+fn live_objects(output: &str) -> Result<Vec<serde_yaml::Value>> {
+    if output.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let doc: serde_yaml::Value =
+        serde_json::from_str(output).context("Invalid output of kubectl get")?;
+    Ok(match doc["kind"].as_str() {
+        Some("List") => doc["items"].as_sequence().cloned().unwrap_or_default(),
+        _ => vec![doc],
+    })
+}
+
+/// Why a live workload or Gateway is not ready, `None` if it is
+fn unready_reason(doc: &serde_yaml::Value) -> Option<String> {
+    let count = |value: &serde_yaml::Value| value.as_u64().unwrap_or(0);
+    let status = &doc["status"];
+    let wanted = doc["spec"]["replicas"].as_u64().unwrap_or(1);
+
+    match doc["kind"].as_str()? {
+        "Deployment" => {
+            let available = count(&status["availableReplicas"]);
+            let updated = count(&status["updatedReplicas"]);
+            (available < wanted || updated < wanted).then(|| {
+                format!(
+                    "{}/{} available, {}/{} updated",
+                    available, wanted, updated, wanted
+                )
+            })
+        }
+        "StatefulSet" => {
+            let ready = count(&status["readyReplicas"]);
+            (ready < wanted).then(|| format!("{}/{} ready", ready, wanted))
+        }
+        "DaemonSet" => {
+            let desired = count(&status["desiredNumberScheduled"]);
+            let available = count(&status["numberAvailable"]);
+            (available < desired).then(|| format!("{}/{} available", available, desired))
+        }
+        "Gateway" => {
+            let programmed = status["conditions"]
+                .as_sequence()
+                .is_some_and(|conditions| {
+                    conditions
+                        .iter()
+                        .any(|c| c["type"] == "Programmed" && c["status"] == "True")
+                });
+            (!programmed).then(|| "not programmed".to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Add the management labels to every object of a manifest
+fn label_manifest(manifest: &str, name: &str) -> Result<String> {
+    use serde::Deserialize;
+
+    let mut documents = Vec::new();
+    for doc in serde_yaml::Deserializer::from_str(manifest) {
+        let mut doc = serde_yaml::Value::deserialize(doc).context("Invalid manifest")?;
+        let Some(metadata) = doc.get_mut("metadata").and_then(|m| m.as_mapping_mut()) else {
+            // Empty documents between separators
+            continue;
+        };
+        let labels = metadata
+            .entry("labels".into())
+            .or_insert_with(|| serde_yaml::Mapping::new().into());
+        if labels.is_null() {
+            *labels = serde_yaml::Mapping::new().into();
+        }
+        let labels = labels
+            .as_mapping_mut()
+            .ok_or_else(|| anyhow::anyhow!("metadata.labels must be a mapping"))?;
+        labels.insert(MANAGED_BY_LABEL.into(), "oxide".into());
+        labels.insert(MANIFEST_LABEL.into(), name.into());
+        documents.push(serde_yaml::to_string(&doc)?);
+    }
+    Ok(documents.join("---\n"))
+}
+
+/// Describe the objects in a manifest as `kind/name` for the audit log
+fn manifest_summary(manifest: &str) -> String {
+    use serde::Deserialize;
+
+    let objects: Vec<String> = serde_yaml::Deserializer::from_str(manifest)
+        .filter_map(|doc| serde_yaml::Value::deserialize(doc).ok())
+        .filter_map(|doc| {
+            let kind = doc["kind"].as_str()?;
+            let name = doc["metadata"]["name"].as_str().unwrap_or("<unnamed>");
+            Some(format!("{}/{}", kind.to_lowercase(), name))
+        })
+        .collect();
+
+    if objects.is_empty() {
+        "<stdin>".to_string()
+    } else {
+        objects.join(",")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_summary() {
+        let manifest = "apiVersion: v1\nkind: Namespace\nmetadata:\n  name: longhorn-system\n---\napiVersion: node.k8s.io/v1\nkind: RuntimeClass\nmetadata:\n  name: nvidia\n";
+        assert_eq!(
+            manifest_summary(manifest),
+            "namespace/longhorn-system,runtimeclass/nvidia"
+        );
+        assert_eq!(manifest_summary("not: a resource"), "<stdin>");
+    }
+
+    #[test]
+    fn test_label_manifest() {
+        let manifest = "---\napiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: config\n  labels:\n    app: cilium\n---\napiVersion: v1\nkind: Namespace\nmetadata:\n  name: longhorn-system\n";
+        let labeled = label_manifest(manifest, "cilium").unwrap();
+        let objects = manifest_objects(&labeled);
+        assert_eq!(objects.len(), 2);
+        for object in objects.values() {
+            let labels = &object["metadata"]["labels"];
+            assert_eq!(labels[MANIFEST_LABEL], "cilium");
+            assert_eq!(labels[MANAGED_BY_LABEL], "oxide");
+        }
+        assert_eq!(
+            objects["configmap/config"]["metadata"]["labels"]["app"],
+            "cilium"
+        );
+    }
+
+    #[test]
+    fn test_stale_objects() {
+        let previous = "apiVersion: v1\nkind: Service\nmetadata:\n  name: old\n  namespace: kube-system\n---\n\
+                        apiVersion: gateway.networking.k8s.io/v1\nkind: Gateway\nmetadata:\n  name: web\n---\n\
+                        apiVersion: apps/v1\nkind: Deployment\nmetadata:\n  name: same\n";
+        let current = "apiVersion: apps/v1\nkind: Deployment\nmetadata:\n  name: same\n  labels:\n    changed: \"true\"\n";
+
+        assert_eq!(
+            stale_objects(previous, current),
+            vec![
+                ObjectRef {
+                    resource: "gateway.gateway.networking.k8s.io".to_string(),
+                    namespace: None,
+                    name: "web".to_string(),
+                },
+                ObjectRef {
+                    resource: "service".to_string(),
+                    namespace: Some("kube-system".to_string()),
+                    name: "old".to_string(),
+                },
+            ]
+        );
+        assert!(stale_objects(current, current).is_empty());
+    }
+
+    #[test]
+    fn test_unready_reason() {
+        let deployment: serde_yaml::Value = serde_yaml::from_str(
+            "kind: Deployment\nspec:\n  replicas: 2\nstatus:\n  availableReplicas: 1\n  updatedReplicas: 2\n",
+        )
+        .unwrap();
+        assert_eq!(
+            unready_reason(&deployment).as_deref(),
+            Some("1/2 available, 2/2 updated")
+        );
+
+        let daemonset: serde_yaml::Value = serde_yaml::from_str(
+            "kind: DaemonSet\nstatus:\n  desiredNumberScheduled: 3\n  numberAvailable: 3\n",
+        )
+        .unwrap();
+        assert_eq!(unready_reason(&daemonset), None);
+
+        let gateway: serde_yaml::Value = serde_yaml::from_str(
+            "kind: Gateway\nstatus:\n  conditions:\n    - type: Accepted\n      status: \"True\"\n    - type: Programmed\n      status: \"False\"\n",
+        )
+        .unwrap();
+        assert_eq!(unready_reason(&gateway).as_deref(), Some("not programmed"));
+
+        let list = r#"{"kind": "List", "items": [{"kind": "StatefulSet", "spec": {"replicas": 1}, "status": {}}]}"#;
+        let live = live_objects(list).unwrap();
+        assert_eq!(live.len(), 1);
+        assert_eq!(unready_reason(&live[0]).as_deref(), Some("0/1 ready"));
+        assert!(live_objects("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rollout_objects() {
+        let manifest = "apiVersion: v1\nkind: Service\nmetadata:\n  name: nginx\n---\n\
+                        apiVersion: apps/v1\nkind: Deployment\nmetadata:\n  name: nginx\n---\n\
+                        apiVersion: gateway.networking.k8s.io/v1\nkind: Gateway\nmetadata:\n  name: web\n  namespace: edge\n---\n\
+                        apiVersion: gateway.networking.k8s.io/v1\nkind: HTTPRoute\nmetadata:\n  name: nginx\n---\n\
+                        apiVersion: apps/v1\nkind: DaemonSet\nmetadata:\n  name: agent\n";
+
+        let objects: Vec<String> = rollout_objects(manifest)
+            .into_iter()
+            .map(|o| format!("{}/{}", o.resource, o.name))
+            .collect();
+        assert_eq!(
+            objects,
+            [
+                "deployment.apps/nginx",
+                "gateway.gateway.networking.k8s.io/web",
+                "daemonset.apps/agent"
+            ]
+        );
+        assert_eq!(
+            rollout_objects(manifest)[1].namespace.as_deref(),
+            Some("edge")
+        );
+    }
 }
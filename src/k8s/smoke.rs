@@ -0,0 +1,262 @@
+/// End-to-end smoke test of a freshly created cluster
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{info, warn};
+
+use super::ResourceManager;
+use crate::audit;
+use crate::utils::command::CommandBuilder;
+use crate::utils::polling::PollingConfig;
+
+const NAMESPACE: &str = "oxide-smoke-test";
+
+/// Server pods spread across nodes (when there are several) so the
+/// pod-to-pod check crosses the network, plus a client pod to run checks from
+const MANIFEST: &str = r#"apiVersion: v1
+kind: Namespace
+metadata:
+  name: oxide-smoke-test
+---
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: smoke-server
+  namespace: oxide-smoke-test
+spec:
+  replicas: 2
+  selector:
+    matchLabels:
+      app: smoke-server
+  template:
+    metadata:
+      labels:
+        app: smoke-server
+    spec:
+      affinity:
+        podAntiAffinity:
+          preferredDuringSchedulingIgnoredDuringExecution:
+            - weight: 100
+              podAffinityTerm:
+                topologyKey: kubernetes.io/hostname
+                labelSelector:
+                  matchLabels:
+                    app: smoke-server
+      containers:
+        - name: nginx
+          image: nginx:1.27-alpine
+          ports:
+            - containerPort: 80
+---
+apiVersion: v1
+kind: Service
+metadata:
+  name: smoke-server
+  namespace: oxide-smoke-test
+spec:
+  type: LoadBalancer
+  selector:
+    app: smoke-server
+  ports:
+    - port: 80
+      targetPort: 80
+---
+apiVersion: v1
+kind: Pod
+metadata:
+  name: smoke-client
+  namespace: oxide-smoke-test
+spec:
+  containers:
+    - name: busybox
+      image: busybox:1.36
+      command: ["sleep", "3600"]
+"#;
+
+/// Smoke test runner
+pub struct SmokeTest {
+    kubeconfig_path: PathBuf,
+    timeout_secs: u64,
+}
+
+impl SmokeTest {
+    /// Create a smoke test with a timeout for each wait
+    pub fn new(kubeconfig_path: &Path, timeout_secs: u64) -> Self {
+        Self {
+            kubeconfig_path: kubeconfig_path.to_path_buf(),
+            timeout_secs,
+        }
+    }
+
+    /// Run all checks, then clean up
+    pub async fn run(&self) -> Result<()> {
+        let result = self.checks().await;
+        self.cleanup().await;
+        result.context("Cluster smoke test failed")
+    }
+
+    async fn checks(&self) -> Result<()> {
+        ResourceManager::apply_stdin(&self.kubeconfig_path, MANIFEST).await?;
+        ResourceManager::wait_for_rollout(
+            &self.kubeconfig_path,
+            NAMESPACE,
+            "deployment/smoke-server",
+            self.timeout_secs,
+        )
+        .await?;
+        self.kubectl(&[
+            "wait",
+            "--for=condition=Ready",
+            "pod/smoke-client",
+            &format!("--timeout={}s", self.timeout_secs),
+        ])
+        .context("Smoke test client pod did not become ready")
+        .run_silent()
+        .await?;
+
+        let ip = self.wait_for_load_balancer().await?;
+        self.check_external(&ip).await?;
+        self.check_dns().await?;
+        self.check_pod_to_pod().await?;
+
+        info!("✓ Smoke test passed: external access, DNS and pod-to-pod traffic work");
+        Ok(())
+    }
+
+    /// Wait for the service to get an address, preferring IPv4
+    async fn wait_for_load_balancer(&self) -> Result<String> {
+        PollingConfig::new(
+            self.timeout_secs,
+            5,
+            "Waiting for the smoke test LoadBalancer address",
+        )
+        .poll(|| async {
+            let output = self
+                .kubectl(&[
+                    "get",
+                    "service",
+                    "smoke-server",
+                    "-o",
+                    "jsonpath={.status.loadBalancer.ingress[*].ip}",
+                ])
+                .run()
+                .await?;
+            let ips: Vec<&str> = output.split_whitespace().collect();
+            Ok(ips
+                .iter()
+                .find(|ip| ip.contains('.'))
+                .or(ips.first())
+                .map(|ip| ip.to_string()))
+        })
+        .await
+    }
+
+    /// Fetch the page through the LoadBalancer from this machine
+    async fn check_external(&self, ip: &str) -> Result<()> {
+        let host = if ip.contains(':') {
+            format!("[{}]", ip)
+        } else {
+            ip.to_string()
+        };
+        let url = format!("http://{}/", host);
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(5))
+            .build()?;
+
+        PollingConfig::new(self.timeout_secs, 5, format!("Fetching {}", url))
+            .poll_until(|| async {
+                Ok(match client.get(&url).send().await {
+                    Ok(response) => response.status().is_success(),
+                    Err(_) => false,
+                })
+            })
+            .await
+    }
+
+    /// Resolve the service name through cluster DNS
+    async fn check_dns(&self) -> Result<()> {
+        let name = format!("smoke-server.{}.svc.cluster.local", NAMESPACE);
+        self.exec_client(&["nslookup", &name])
+            .await
+            .context(format!("Cluster DNS could not resolve {}", name))?;
+        info!("✓ Cluster DNS resolves {}", name);
+        Ok(())
+    }
+
+    /// Reach every server pod directly from the client pod
+    async fn check_pod_to_pod(&self) -> Result<()> {
+        let output = self
+            .kubectl(&[
+                "get",
+                "pods",
+                "-l",
+                "app=smoke-server",
+                "-o",
+                "jsonpath={.items[*].status.podIP}",
+            ])
+            .run()
+            .await?;
+
+        for ip in output.split_whitespace() {
+            let host = if ip.contains(':') {
+                format!("[{}]", ip)
+            } else {
+                ip.to_string()
+            };
+            self.exec_client(&[
+                "wget",
+                "-q",
+                "-O",
+                "/dev/null",
+                "-T",
+                "5",
+                &format!("http://{}/", host),
+            ])
+            .await
+            .context(format!("Pod at {} is not reachable from another pod", ip))?;
+        }
+
+        info!("✓ Pod-to-pod traffic works");
+        Ok(())
+    }
+
+    async fn exec_client(&self, command: &[&str]) -> Result<String> {
+        let mut args = vec!["exec", "smoke-client", "--"];
+        args.extend_from_slice(command);
+        self.kubectl(&args).run().await
+    }
+
+    /// Delete the namespace without waiting for it to be gone
+    async fn cleanup(&self) {
+        let result = CommandBuilder::new("kubectl")
+            .args([
+                "delete",
+                "namespace",
+                NAMESPACE,
+                "--wait=false",
+                "--ignore-not-found",
+            ])
+            .kubeconfig(&self.kubeconfig_path)
+            .context("Failed to delete smoke test namespace")
+            .run_silent()
+            .await;
+        audit::record(
+            "kubectl.delete",
+            &format!("namespace/{}", NAMESPACE),
+            &[],
+            &result,
+        );
+
+        if let Err(e) = result {
+            warn!("{:#}, delete it manually", e);
+        }
+    }
+
+    /// kubectl in the smoke test namespace
+    fn kubectl(&self, args: &[&str]) -> CommandBuilder {
+        CommandBuilder::new("kubectl")
+            .args(["-n", NAMESPACE])
+            .args(args)
+            .kubeconfig(&self.kubeconfig_path)
+    }
+}
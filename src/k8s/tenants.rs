@@ -0,0 +1,232 @@
+/// Tenant namespaces bootstrapped at cluster creation
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::path::Path;
+use tracing::info;
+
+use super::ResourceManager;
+use crate::config::{TenantBinding, TenantConfig};
+
+/// Namespace label Kubernetes sets to the namespace name
+const NAMESPACE_NAME_LABEL: &str = "kubernetes.io/metadata.name";
+
+/// Apply the namespaces and policies of every tenant
+pub async fn apply(tenants: &[TenantConfig], kubeconfig_path: &Path) -> Result<()> {
+    if tenants.is_empty() {
+        return Ok(());
+    }
+    info!("Creating {} tenant namespace(s)...", tenants.len());
+    ResourceManager::apply_stdin(kubeconfig_path, &manifest(tenants)).await?;
+    info!("✓ Tenant namespaces ready");
+    Ok(())
+}
+
+/// Render all tenant objects as a multi-document manifest
+pub fn manifest(tenants: &[TenantConfig]) -> String {
+    let mut objects = Vec::new();
+    for tenant in tenants {
+        objects.extend(tenant_objects(tenant));
+    }
+    objects
+        .iter()
+        .map(|object| serde_yaml::to_string(object).expect("tenant object serializes"))
+        .collect::<Vec<_>>()
+        .join("---\n")
+}
+
+fn tenant_objects(tenant: &TenantConfig) -> Vec<Value> {
+    let namespace = tenant.name.as_str();
+    let mut namespace_metadata = metadata(namespace, None);
+    for (key, value) in &tenant.labels {
+        namespace_metadata["labels"][key] = value.as_str().into();
+    }
+    let mut objects = vec![json!({
+        "apiVersion": "v1",
+        "kind": "Namespace",
+        "metadata": namespace_metadata,
+    })];
+
+    if !tenant.quota.is_empty() {
+        objects.push(json!({
+            "apiVersion": "v1",
+            "kind": "ResourceQuota",
+            "metadata": metadata("oxide-tenant", Some(namespace)),
+            "spec": { "hard": tenant.quota },
+        }));
+    }
+
+    if let Some(limits) = &tenant.limits {
+        let mut limit = json!({ "type": "Container" });
+        for (field, resources) in [
+            ("default", &limits.default),
+            ("defaultRequest", &limits.default_request),
+            ("max", &limits.max),
+        ] {
+            if !resources.is_empty() {
+                limit[field] = json!(resources);
+            }
+        }
+        objects.push(json!({
+            "apiVersion": "v1",
+            "kind": "LimitRange",
+            "metadata": metadata("oxide-tenant", Some(namespace)),
+            "spec": { "limits": [limit] },
+        }));
+    }
+
+    if tenant.isolated {
+        let mut from = vec![json!({ "podSelector": {} })];
+        for other in &tenant.allow_from {
+            from.push(json!({
+                "namespaceSelector": {
+                    "matchLabels": { NAMESPACE_NAME_LABEL: other },
+                }
+            }));
+        }
+        objects.push(json!({
+            "apiVersion": "networking.k8s.io/v1",
+            "kind": "NetworkPolicy",
+            "metadata": metadata("oxide-tenant-isolation", Some(namespace)),
+            "spec": {
+                "podSelector": {},
+                "policyTypes": ["Ingress"],
+                "ingress": [{ "from": from }],
+            },
+        }));
+    }
+
+    for binding in &tenant.bindings {
+        for account in &binding.service_accounts {
+            objects.push(json!({
+                "apiVersion": "v1",
+                "kind": "ServiceAccount",
+                "metadata": metadata(account, Some(namespace)),
+            }));
+        }
+        let name = format!("oxide-tenant-{}", binding.cluster_role);
+        objects.push(json!({
+            "apiVersion": "rbac.authorization.k8s.io/v1",
+            "kind": "RoleBinding",
+            "metadata": metadata(&name, Some(namespace)),
+            "roleRef": {
+                "apiGroup": "rbac.authorization.k8s.io",
+                "kind": "ClusterRole",
+                "name": binding.cluster_role,
+            },
+            "subjects": subjects(binding, namespace),
+        }));
+    }
+    objects
+}
+
+fn subjects(binding: &TenantBinding, namespace: &str) -> Vec<Value> {
+    let named = |kind: &str, name: &String| {
+        json!({
+            "apiGroup": "rbac.authorization.k8s.io",
+            "kind": kind,
+            "name": name,
+        })
+    };
+    let mut subjects: Vec<Value> = binding
+        .groups
+        .iter()
+        .map(|group| named("Group", group))
+        .chain(binding.users.iter().map(|user| named("User", user)))
+        .collect();
+    subjects.extend(binding.service_accounts.iter().map(|account| {
+        json!({
+            "kind": "ServiceAccount",
+            "name": account,
+            "namespace": namespace,
+        })
+    }));
+    subjects
+}
+
+fn metadata(name: &str, namespace: Option<&str>) -> Value {
+    let mut metadata = json!({
+        "name": name,
+        "labels": { "app.kubernetes.io/managed-by": "oxide" },
+    });
+    if let Some(namespace) = namespace {
+        metadata["namespace"] = namespace.into();
+    }
+    metadata
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TENANTS: &str = r#"
+- name: team-a
+  labels:
+    team: a
+  quota:
+    requests.cpu: "8"
+    limits.memory: 32Gi
+    pods: 100
+  limits:
+    default: { cpu: 500m, memory: 512Mi }
+    default_request: { cpu: 100m, memory: 128Mi }
+  allow_from: [ingress-nginx]
+  bindings:
+    - cluster_role: admin
+      groups: ["oidc:team-a"]
+    - cluster_role: edit
+      service_accounts: [deployer]
+- name: sandbox
+  isolated: false
+"#;
+
+    #[test]
+    fn test_manifest() {
+        let tenants: Vec<TenantConfig> = serde_yaml::from_str(TENANTS).unwrap();
+        let documents: Vec<serde_yaml::Value> = manifest(&tenants)
+            .split("---\n")
+            .map(|doc| serde_yaml::from_str(doc).unwrap())
+            .collect();
+        let kinds: Vec<&str> = documents
+            .iter()
+            .map(|doc| doc["kind"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                "Namespace",
+                "ResourceQuota",
+                "LimitRange",
+                "NetworkPolicy",
+                "RoleBinding",
+                "ServiceAccount",
+                "RoleBinding",
+                "Namespace",
+            ]
+        );
+
+        let namespace = &documents[0];
+        assert_eq!(namespace["metadata"]["labels"]["team"], "a");
+        assert_eq!(documents[1]["spec"]["hard"]["pods"], 100);
+        assert_eq!(
+            documents[2]["spec"]["limits"][0]["defaultRequest"]["cpu"],
+            "100m"
+        );
+        assert!(documents[2]["spec"]["limits"][0]["max"].is_null());
+
+        let from = &documents[3]["spec"]["ingress"][0]["from"];
+        assert_eq!(
+            from[0]["podSelector"],
+            serde_yaml::from_str::<serde_yaml::Value>("{}").unwrap()
+        );
+        assert_eq!(
+            from[1]["namespaceSelector"]["matchLabels"][NAMESPACE_NAME_LABEL],
+            "ingress-nginx"
+        );
+
+        let binding = &documents[6];
+        assert_eq!(binding["metadata"]["name"], "oxide-tenant-edit");
+        assert_eq!(binding["subjects"][0]["kind"], "ServiceAccount");
+        assert_eq!(binding["subjects"][0]["namespace"], "team-a");
+        assert_eq!(documents[7]["metadata"]["name"], "sandbox");
+    }
+}
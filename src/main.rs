@@ -2,26 +2,64 @@
 ///
 /// A Rust-based tool for deploying Talos Linux Kubernetes clusters with Cilium CNI.
 /// Currently supports Hetzner Cloud, with more providers coming soon.
+mod addons;
+mod audit;
+mod bundle;
+mod ci;
 mod cilium;
+mod compliance;
 mod config;
+mod drift;
+mod events;
+mod exit;
+mod export;
 mod hcloud;
+mod inventory;
 mod k8s;
+mod notify;
+mod render;
+mod robot;
+mod state;
+mod storage;
 mod talos;
+mod upgrade;
 mod utils;
 
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
-use tracing::{error, info};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::engine::ArgValueCompleter;
+use clap_complete::env::{CompleteEnv, Shells};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::{error, info, info_span, warn, Instrument};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+use crate::addons::AddonManager;
+use crate::bundle::Bundle;
 use crate::cilium::CiliumManager;
-use crate::config::ClusterConfig;
-use crate::hcloud::network::NetworkManager;
-use crate::hcloud::server::{NodeRole, ServerInfo, ServerManager};
+use crate::config::{cidr, versions, ClusterConfig, ConfigFormat, ConfigTemplate, TimeoutsConfig};
+use crate::exit::{Failure, Summary};
+use crate::hcloud::hibernate::{self, Hibernation};
+use crate::hcloud::ip::IpManager;
+use crate::hcloud::models::{MetricType, MetricsRange};
+use crate::hcloud::nat::NatGatewayManager;
+use crate::hcloud::network::{ClusterNetworks, NetworkManager};
+use crate::hcloud::projects::Projects;
+use crate::hcloud::server::{self, NodeRole, ServerInfo, ServerManager};
+use crate::hcloud::{expiry, teardown, user_data};
 use crate::hcloud::{FirewallManager, HetznerCloudClient, SSHKeyManager};
+use crate::k8s::kubeconfig::{self, ServiceAccountAccess};
+use crate::k8s::maintenance;
+use crate::k8s::smoke::SmokeTest;
 use crate::k8s::{KubernetesClient, NodeManager, ResourceManager};
-use crate::talos::{TalosClient, TalosConfigGenerator};
+use crate::state::remote::{self, RemoteState};
+use crate::state::ClusterState;
+use crate::storage::ObjectStorage;
+use crate::talos::{ApplyMode, TalosClient, TalosConfigGenerator};
+use crate::upgrade::rolling::{Outcome, PausePoint, RollingUpgrade, UpgradeState};
+use crate::upgrade::schedule;
+use crate::utils::telemetry::Telemetry;
+use crate::utils::{completion, helm, platform, progress, prompt, releases, tools};
 
 #[derive(Parser)]
 #[command(name = "oxide")]
@@ -30,9 +68,10 @@ struct Cli {
     #[command(subcommand)]
     command: Commands,
 
-    /// Configuration file path
+    /// Configuration file path; repeat to deep-merge overlays onto the base
+    /// (e.g. `-c base.yaml -c prod.yaml`)
     #[arg(short, long, default_value = "cluster.yaml")]
-    config: PathBuf,
+    config: Vec<PathBuf>,
 
     /// Output directory for generated files
     #[arg(short, long, default_value = "./output")]
@@ -41,21 +80,188 @@ struct Cli {
     /// Enable verbose logging
     #[arg(short, long)]
     verbose: bool,
+
+    /// Override every phase timeout from the `timeouts` config (seconds)
+    #[arg(long, global = true)]
+    timeout: Option<u64>,
+
+    /// Disable the progress display and print plain logs
+    #[arg(long, global = true)]
+    no_progress: bool,
+
+    /// Log output format
+    #[arg(long, global = true, value_enum, default_value = "text")]
+    log_format: LogFormat,
+
+    /// Never prompt; fail with exit code 3 where input would be needed
+    #[arg(long, global = true)]
+    non_interactive: bool,
+
+    /// Write the outcome of the command as JSON to this file, also when it
+    /// fails
+    #[arg(long, global = true, value_name = "PATH")]
+    summary_file: Option<PathBuf>,
+}
+
+/// Log output format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum LogFormat {
+    /// Human-readable log lines
+    Text,
+    /// One JSON object per event, including cluster, phase and node fields
+    Json,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Create a new Talos cluster
-    Create,
+    Create {
+        /// Download version-matched talosctl, kubectl and helm first
+        #[arg(long)]
+        install_tools: bool,
+
+        /// Deploy a test workload afterwards and fail unless it is reachable
+        #[arg(long)]
+        smoke_test: bool,
+
+        /// Let the cluster expire after this long, e.g. 4h or 1h30m; expired
+        /// clusters are destroyed by `oxide reaper`
+        #[arg(long, value_name = "DURATION", value_parser = expiry::parse_ttl)]
+        ttl: Option<chrono::Duration>,
+
+        /// Install the charts and Gateway API CRDs from an offline bundle
+        /// written by `oxide bundle create`
+        #[arg(long, value_name = "DIR")]
+        bundle: Option<PathBuf>,
+    },
+
+    /// Create a new cluster from the config of an existing one
+    ///
+    /// Writes a copy of the config with the new name, private networks that
+    /// overlap no network of the Hetzner project and its own secrets
+    /// location, then creates the cluster with fresh secrets in --output.
+    Clone {
+        /// Config file of the cluster to copy
+        #[arg(long, value_name = "FILE")]
+        from: PathBuf,
+
+        /// Name of the new cluster
+        #[arg(long)]
+        name: String,
+
+        /// Where to write the new config (default: <name>.yaml next to --from)
+        #[arg(long, value_name = "FILE")]
+        to: Option<PathBuf>,
+
+        /// Only write the config, without creating the cluster
+        #[arg(long)]
+        no_create: bool,
+    },
 
     /// Destroy an existing cluster
-    Destroy,
+    ///
+    /// Refuses to run when labelled servers are not recorded in the cluster
+    /// state of the output directory, unless --force is given.
+    Destroy {
+        /// Only delete these servers (hcloud IDs); the rest of the cluster stays
+        #[arg(long = "target-id", value_name = "ID")]
+        target_ids: Vec<u64>,
+
+        /// Destroy even if servers cannot be matched to the cluster state
+        #[arg(long)]
+        force: bool,
+
+        /// List the resources that would be removed without deleting anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Destroy clusters created with --ttl once they expire
+    ///
+    /// Watches the Hetzner project of the config and destroys every expired
+    /// cluster with the hcloud settings of the config, so preview clusters
+    /// should be created from the same config with another cluster_name.
+    Reaper {
+        /// Minutes between checks
+        #[arg(long, default_value_t = 5)]
+        interval: u32,
+
+        /// Check once and exit, e.g. from a scheduled CI job
+        #[arg(long)]
+        once: bool,
+
+        /// List expired clusters without destroying them
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Create and destroy preview clusters from GitHub Actions workflows
+    Ci {
+        #[command(subcommand)]
+        command: CiCommand,
+    },
+
+    /// Shut every node down and power the servers off
+    ///
+    /// Workers stop first, then the control planes; etcd membership and all
+    /// disks are kept. Hetzner keeps billing stopped servers, so this saves
+    /// CPU time and bandwidth, not the server price, unless `--snapshot`
+    /// replaces the servers with snapshots.
+    Pause {
+        /// Snapshot the stopped servers and delete them, keeping their
+        /// networks and IPs, so only the snapshots and IPs are billed
+        #[arg(long)]
+        snapshot: bool,
+    },
+
+    /// Power a paused cluster back on
+    ///
+    /// Servers hibernated with `pause --snapshot` are recreated from their
+    /// snapshots first. Control planes start first; once the Kubernetes API
+    /// answers the workers follow, and the command waits until every node is
+    /// Ready.
+    Resume,
 
     /// Show cluster status
     Status,
 
-    /// Generate example configuration file
-    Init,
+    /// Compare the config with the running cluster
+    ///
+    /// Reports differences in node counts, server types, Talos, Kubernetes
+    /// and Cilium versions, firewall rules and Cilium Helm values, and exits
+    /// non-zero when there are any.
+    Drift {
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Check the cluster against the CIS Kubernetes Benchmark
+    ///
+    /// Runs kube-bench as Jobs on a control plane and a worker and checks the
+    /// Talos machine config of every node, then lists failed controls with
+    /// their remediation. Exits non-zero when any control failed.
+    Audit {
+        /// Print every result as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Generate a configuration file
+    Init {
+        /// Ask for the settings interactively
+        #[arg(short, long)]
+        interactive: bool,
+
+        /// Preset to start from
+        #[arg(long, value_enum, default_value = "ha")]
+        template: ConfigTemplate,
+
+        /// File format; defaults to the extension of --config, so
+        /// `--format toml` writes cluster.toml unless --config is given
+        #[arg(long, value_enum)]
+        format: Option<ConfigFormat>,
+    },
 
     /// Scale cluster nodes
     Scale {
@@ -68,20 +274,20 @@ enum Commands {
         count: u32,
 
         /// Node pool name (optional, uses first pool if not specified)
-        #[arg(short, long)]
+        #[arg(short, long, add = ArgValueCompleter::new(completion::pool_names))]
         pool: Option<String>,
 
         /// Force non-graceful scale down (skip drain, immediate removal)
         #[arg(long)]
         force: bool,
-
-        /// Timeout in seconds for graceful node reset (default: 600)
-        #[arg(long, default_value = "600")]
-        timeout: u64,
     },
 
     /// Upgrade cluster
+    #[command(args_conflicts_with_subcommands = true)]
     Upgrade {
+        #[command(subcommand)]
+        command: Option<UpgradeCommand>,
+
         /// New Talos version
         #[arg(long)]
         talos_version: Option<String>,
@@ -89,879 +295,4510 @@ enum Commands {
         /// New Kubernetes version
         #[arg(long)]
         kubernetes_version: Option<String>,
+
+        /// How to apply changed pool server types
+        #[arg(long, value_enum, default_value = "in-place")]
+        resize_strategy: ResizeStrategy,
+
+        /// Stop for verification after this stage; continue with `oxide upgrade resume`
+        #[arg(long, value_enum)]
+        pause_after: Option<PausePoint>,
+
+        /// Only upgrade this node pool (canary); Kubernetes and the config stay unchanged
+        #[arg(long, add = ArgValueCompleter::new(completion::pool_names))]
+        pool: Option<String>,
+    },
+
+    /// Run maintenance on a schedule
+    Schedule {
+        #[command(subcommand)]
+        command: ScheduleCommand,
     },
 
     /// Deploy nginx with Gateway API
     DeployNginx,
-}
 
-#[derive(Debug, Clone, clap::ValueEnum)]
-enum NodeType {
-    ControlPlane,
-    Worker,
-}
+    /// Print the cluster kubeconfig, or merge it into your kubectl config
+    ///
+    /// With --service-account, a ServiceAccount bound to --role is created and
+    /// the kubeconfig uses a token that expires after --duration instead of
+    /// the cluster-admin certificates.
+    Kubeconfig {
+        /// Merge into ~/.kube/config (or the first $KUBECONFIG file) instead of printing
+        #[arg(long)]
+        merge: bool,
 
-#[tokio::main]
-async fn main() {
-    let cli = Cli::parse();
+        /// Context name used when merging (default: cluster name)
+        #[arg(long, requires = "merge")]
+        context: Option<String>,
 
-    // Initialize tracing
-    let log_level = if cli.verbose { "debug" } else { "info" };
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| format!("oxide={}", log_level).into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+        /// Create this ServiceAccount and use a token for it
+        #[arg(long)]
+        service_account: Option<String>,
 
-    // Execute command
-    let result = match cli.command {
-        Commands::Create => create_cluster(&cli).await,
-        Commands::Destroy => destroy_cluster(&cli).await,
-        Commands::Status => show_status(&cli).await,
-        Commands::Init => init_config(&cli).await,
-        Commands::Scale {
-            ref node_type,
-            count,
-            ref pool,
-            force,
-            timeout,
-        } => scale_cluster(&cli, node_type.clone(), count, pool.clone(), force, timeout).await,
-        Commands::Upgrade {
-            ref talos_version,
-            ref kubernetes_version,
-        } => upgrade_cluster(&cli, talos_version.clone(), kubernetes_version.clone()).await,
-        Commands::DeployNginx => deploy_nginx(&cli).await,
-    };
+        /// ClusterRole granted to the ServiceAccount, e.g. view, edit or admin
+        #[arg(long, default_value = "view", requires = "service_account")]
+        role: String,
 
-    if let Err(e) = result {
-        error!("Error: {:#}", e);
-        std::process::exit(1);
-    }
-}
+        /// Only grant the role in this namespace
+        #[arg(long, requires = "service_account")]
+        namespace: Option<String>,
 
-/// Create a new Talos cluster
-async fn create_cluster(cli: &Cli) -> Result<()> {
-    info!("Starting cluster creation...");
+        /// Token lifetime
+        #[arg(long, default_value = "24h", requires = "service_account")]
+        duration: String,
+    },
 
-    // Check prerequisites
-    TalosClient::check_talosctl_installed()
-        .await
-        .context("talosctl is required")?;
-    KubernetesClient::check_kubectl_installed()
-        .await
-        .context("kubectl is required")?;
-    CiliumManager::check_helm_installed()
-        .await
-        .context("helm is required")?;
+    /// Open the Headlamp dashboard through a local port-forward
+    ///
+    /// Prints a token of a ServiceAccount bound to the read-only `view`
+    /// ClusterRole to sign in with, then forwards a port on 127.0.0.1 to the
+    /// dashboard until interrupted. Needs `addons.dashboard.enabled`.
+    DashboardUi {
+        /// Local port to forward
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+
+        /// Token lifetime
+        #[arg(long, default_value = "1h")]
+        duration: String,
+    },
 
-    // Load configuration
-    let config = ClusterConfig::from_file(&cli.config).context("Failed to load configuration")?;
+    /// Open an SSH session on a node
+    ///
+    /// Talos nodes run no SSH daemon, so this is mainly useful with --rescue
+    /// to debug a node that no longer boots.
+    Ssh {
+        /// Server name, e.g. my-cluster-worker-1
+        node: String,
 
-    info!("Cluster name: {}", config.cluster_name);
+        /// Reboot the node into the Hetzner rescue system first
+        #[arg(long)]
+        rescue: bool,
 
-    // Create Hetzner Cloud client
-    let hcloud_token = config.get_hcloud_token()?;
-    let hcloud_client = HetznerCloudClient::new(hcloud_token)?;
+        /// Stay in the rescue system after the session ends
+        #[arg(long, requires = "rescue")]
+        no_reboot: bool,
 
-    // Get current IP for firewall
-    let current_ip = FirewallManager::get_current_ip().await?;
-    info!("Detected current IP address: {}", current_ip);
+        /// Command to run instead of an interactive shell
+        #[arg(last = true)]
+        command: Vec<String>,
+    },
 
-    // Create firewall
-    let firewall_manager = FirewallManager::new(hcloud_client.clone());
-    let firewall = firewall_manager
-        .create_cluster_firewall(&config.cluster_name, &current_ip)
-        .await?;
+    /// List and inspect cluster nodes
+    Node {
+        #[command(subcommand)]
+        command: NodeCommand,
+    },
 
-    // Create network
-    let network_manager = NetworkManager::new(hcloud_client.clone());
-    let network = network_manager
-        .ensure_network(&config.cluster_name, &config.hcloud.network)
-        .await?;
+    /// Inspect and uninstall the addons installed by oxide
+    Addons {
+        #[command(subcommand)]
+        command: AddonsCommand,
+    },
 
-    // Ensure SSH key exists for cluster
-    let ssh_key_manager = SSHKeyManager::new(hcloud_client.clone());
-    let (ssh_key, private_key) = ssh_key_manager.ensure_ssh_key(&config.cluster_name).await?;
+    /// Manage the Cilium CNI
+    Cni {
+        #[command(subcommand)]
+        command: CniCommand,
+    },
 
-    // Save private key if it was newly generated
-    if let Some(private_key_content) = private_key {
-        let ssh_key_path = cli.output.join("id_ed25519");
-        tokio::fs::write(&ssh_key_path, private_key_content)
-            .await
-            .context("Failed to save SSH private key")?;
-        info!("SSH private key saved to: {}", ssh_key_path.display());
+    /// Write the Helm values and manifests oxide would apply, for a GitOps
+    /// repository
+    ///
+    /// Each chart (Cilium and the enabled addons) gets `<release>/values.yaml`
+    /// and `<release>/manifest.yaml`; `charts.yaml` lists the chart
+    /// repositories and versions.
+    Render {
+        /// Directory to write to
+        #[arg(long, value_name = "DIR")]
+        out: PathBuf,
+    },
 
-        // Set appropriate permissions (0600)
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let mut perms = tokio::fs::metadata(&ssh_key_path)
-                .await
-                .context("Failed to get SSH key metadata")?
-                .permissions();
-            perms.set_mode(0o600);
-            tokio::fs::set_permissions(&ssh_key_path, perms)
-                .await
-                .context("Failed to set SSH key permissions")?;
-        }
-    }
+    /// Export the cluster as manifests of other tooling
+    ///
+    /// `--format capi` writes Cluster API manifests for the Hetzner (CAPH)
+    /// and Talos providers describing the same network, pools and versions,
+    /// for a move to Cluster API.
+    Export {
+        /// Manifest format
+        #[arg(long, value_enum)]
+        format: ExportFormat,
+
+        /// Write to this file instead of stdout
+        #[arg(long, value_name = "FILE")]
+        out: Option<PathBuf>,
+    },
 
-    // Generate Talos configuration first (using placeholder endpoint if needed)
-    let cluster_endpoint = config
-        .talos
-        .cluster_endpoint
-        .clone()
-        .unwrap_or_else(|| format!("https://{}:6443", "127.0.0.1"));
+    /// Prepare clusters whose nodes cannot reach the public internet
+    Bundle {
+        #[command(subcommand)]
+        command: BundleCommand,
+    },
 
-    info!(
-        "Generating Talos configuration with endpoint: {}",
-        cluster_endpoint
-    );
+    /// Inspect the Talos machine configs of the nodes
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
 
-    let config_generator =
-        TalosConfigGenerator::new(config.cluster_name.clone(), config.talos.clone());
+    /// Show CPU, memory, disk and network usage per node
+    ///
+    /// Combines `kubectl top nodes` (needs metrics-server) with Hetzner server
+    /// metrics averaged over --window.
+    Top {
+        /// Only show nodes with this role
+        #[arg(long, value_enum)]
+        role: Option<NodeType>,
+
+        /// Only show nodes of this pool
+        #[arg(long, add = ArgValueCompleter::new(completion::pool_names))]
+        pool: Option<String>,
 
-    let configs = config_generator
-        .generate_configs(&cluster_endpoint, &cli.output)
-        .await?;
+        /// Only show nodes in this location, e.g. fsn1
+        #[arg(long)]
+        location: Option<String>,
 
-    // Read generated configs as user_data
-    let controlplane_user_data = tokio::fs::read_to_string(&configs.controlplane)
-        .await
-        .context("Failed to read controlplane config")?;
-    let worker_user_data = tokio::fs::read_to_string(&configs.worker)
-        .await
-        .context("Failed to read worker config")?;
+        /// Minutes of Hetzner metrics to average
+        #[arg(long, default_value = "5")]
+        window: u32,
 
-    // Create servers (all in parallel) with user_data
-    let server_manager = ServerManager::new(hcloud_client.clone());
+        /// Print the usage as JSON
+        #[arg(long)]
+        json: bool,
+    },
 
-    info!("Creating all servers with Talos configuration...");
-    let (control_planes, workers) = tokio::join!(
-        server_manager.create_control_planes(
-            &config.cluster_name,
-            &config.control_planes,
-            &config.hcloud.location,
-            &network,
-            &config.talos.version,
-            config.talos.hcloud_snapshot_id.as_deref(),
-            Some(ssh_key.id),
-            Some(controlplane_user_data),
-        ),
-        server_manager.create_workers(
-            &config.cluster_name,
-            &config.workers,
-            &config.hcloud.location,
-            &network,
-            &config.talos.version,
-            config.talos.hcloud_snapshot_id.as_deref(),
-            Some(ssh_key.id),
-            Some(worker_user_data),
-        )
-    );
-    let control_planes = control_planes?;
-    let workers = workers?;
+    /// Show Kubernetes events and hcloud server actions in one timeline
+    Events {
+        /// Include normal events and successful actions, not only warnings
+        #[arg(long)]
+        all: bool,
 
-    // Apply firewall to all servers
-    let server_ids: Vec<u64> = control_planes
-        .iter()
-        .chain(workers.iter())
-        .map(|s| s.server.id)
-        .collect();
-    firewall_manager
-        .apply_to_servers(firewall.id, server_ids)
-        .await?;
+        /// Minutes of history to show
+        #[arg(long, default_value = "60")]
+        since: u32,
 
-    // Get first control plane IP
-    let first_cp = control_planes
-        .first()
-        .context("No control plane nodes created")?;
-    let cluster_endpoint_ip =
-        ServerManager::get_server_ip(&first_cp.server).context("Control plane has no public IP")?;
-    let actual_cluster_endpoint = config
-        .talos
-        .cluster_endpoint
-        .clone()
-        .unwrap_or_else(|| format!("https://{}:6443", cluster_endpoint_ip));
+        /// Keep polling and print new events as they happen
+        #[arg(short, long)]
+        follow: bool,
 
-    info!("Actual cluster endpoint: {}", actual_cluster_endpoint);
+        /// Print events as JSON lines
+        #[arg(long)]
+        json: bool,
+    },
 
-    // Configure talosconfig with control plane endpoints
-    let talos_client = TalosClient::new(configs.talosconfig.clone());
-    let control_plane_ips: Vec<String> = control_planes
-        .iter()
-        .filter_map(|cp| ServerManager::get_server_ip(&cp.server))
-        .collect();
-    talos_client.configure_endpoints(&control_plane_ips).await?;
+    /// Join or remove Hetzner Robot dedicated servers
+    Robot {
+        #[command(subcommand)]
+        command: RobotCommand,
+    },
 
-    // Patch control plane nodes with actual endpoint if it differs from placeholder
-    // Workers use private network and don't need endpoint patching
-    if cluster_endpoint != actual_cluster_endpoint {
-        info!("Waiting for Talos API and patching control plane with actual endpoint...");
-        talos_client
-            .patch_cluster_endpoint(&control_planes, &actual_cluster_endpoint)
-            .await?;
+    /// Show the audit log of mutating operations
+    History {
+        /// Number of most recent entries to show
+        #[arg(short = 'n', long, default_value = "50")]
+        limit: usize,
 
-        info!("Control plane patched successfully");
-    } else {
-        info!("Endpoint already correct, skipping patch");
-    }
+        /// Only show failed actions
+        #[arg(long)]
+        failed: bool,
 
-    // Bootstrap cluster
-    talos_client.bootstrap(first_cp).await?;
+        /// Print entries as JSON lines
+        #[arg(long)]
+        json: bool,
+    },
 
-    // Wait for API server
-    talos_client
-        .wait_for_api_server(&cluster_endpoint_ip, 300)
-        .await?;
+    /// Manage the version-matched talosctl, kubectl and helm binaries
+    Tools {
+        #[command(subcommand)]
+        command: ToolsCommand,
+    },
 
-    // Generate kubeconfig
-    let kubeconfig_path = cli.output.join("kubeconfig");
-    talos_client
-        .generate_kubeconfig(&cluster_endpoint_ip, &kubeconfig_path)
-        .await?;
+    /// Share the kubeconfig, talosconfig and other output files through the
+    /// bucket of `storage`
+    Artifacts {
+        #[command(subcommand)]
+        command: ArtifactsCommand,
+    },
 
-    // Install Cilium
-    info!("Installing Cilium CNI...");
-    let control_plane_count = config.control_planes.iter().map(|cp| cp.count).sum();
-    let cilium_manager = CiliumManager::new(
-        config.cilium.clone(),
-        kubeconfig_path.clone(),
-        control_plane_count,
-    );
-    cilium_manager.install().await?;
-    cilium_manager.wait_for_ready(300).await?;
+    /// Manage the cluster state kept in the bucket of `storage`
+    State {
+        #[command(subcommand)]
+        command: StateCommand,
+    },
 
-    info!("✓ Cluster creation completed successfully!");
-    info!("");
-    info!("Cluster details:");
-    info!("  Name: {}", config.cluster_name);
-    info!("  Endpoint: {}", cluster_endpoint);
-    info!("  Control planes: {}", control_planes.len());
-    info!("  Workers: {}", workers.len());
-    info!("");
-    info!("Configuration files:");
-    info!("  Talosconfig: {}", configs.talosconfig.display());
-    info!("  Kubeconfig: {}", kubeconfig_path.display());
-    info!("");
-    info!("To access your cluster:");
-    info!("  export KUBECONFIG={}", kubeconfig_path.display());
-    info!("  kubectl get nodes");
+    /// Print a shell completion script
+    Completions {
+        /// Shell to generate the script for
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
 
-    Ok(())
+    /// Print the man page in roff format
+    #[command(hide = true)]
+    Mangen,
 }
 
-/// Destroy an existing cluster
-async fn destroy_cluster(cli: &Cli) -> Result<()> {
-    info!("Starting cluster destruction...");
+#[derive(Subcommand)]
+enum NodeCommand {
+    /// List nodes with their pool, addresses, versions and Ready state
+    List {
+        /// Only list nodes with this role
+        #[arg(long, value_enum)]
+        role: Option<NodeType>,
+
+        /// Only list nodes of this pool
+        #[arg(long, add = ArgValueCompleter::new(completion::pool_names))]
+        pool: Option<String>,
 
-    let config = ClusterConfig::from_file(&cli.config).context("Failed to load configuration")?;
+        /// Only list nodes in this location, e.g. fsn1
+        #[arg(long)]
+        location: Option<String>,
 
-    info!("Cluster name: {}", config.cluster_name);
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "table")]
+        output: ListFormat,
+    },
 
-    let hcloud_token = config.get_hcloud_token()?;
-    let hcloud_client = HetznerCloudClient::new(hcloud_token)?;
+    /// Take a node out of service for maintenance and bring it back
+    Maintenance {
+        #[command(subcommand)]
+        command: MaintenanceCommand,
+    },
+}
 
-    // Delete servers
-    let server_manager = ServerManager::new(hcloud_client.clone());
-    server_manager
-        .delete_cluster_servers(&config.cluster_name)
-        .await?;
+#[derive(Subcommand)]
+enum MaintenanceCommand {
+    /// Cordon and drain a node and mark it as under maintenance
+    ///
+    /// With `alertmanager` configured, the alerts of the node are silenced
+    /// for --duration first.
+    Enter {
+        /// Node name, e.g. my-cluster-worker-1
+        node: String,
+
+        /// Why the node is in maintenance, recorded on the node
+        #[arg(long, default_value = "maintenance")]
+        reason: String,
+
+        /// How long to silence the node's alerts, e.g. 2h or 1h30m
+        #[arg(long, value_name = "DURATION", value_parser = expiry::parse_ttl, default_value = "2h")]
+        duration: chrono::Duration,
+    },
+
+    /// Expire the alert silence and make the node schedulable again once it
+    /// is Ready
+    Exit {
+        /// Node name, e.g. my-cluster-worker-1
+        node: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum AddonsCommand {
+    /// List the Helm charts oxide installed with their versions, last
+    /// deployment and health
+    Status {
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "table")]
+        output: ListFormat,
+    },
+
+    /// Uninstall an addon chart installed by oxide
+    ///
+    /// CustomResourceDefinitions and the namespace are kept. Disable the addon
+    /// in the config too, or the next `oxide create` installs it again.
+    Uninstall {
+        /// Release name as listed by `oxide addons status`, e.g. longhorn
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum BundleCommand {
+    /// Download the charts and Gateway API CRDs and list the container images
+    /// of the cluster
+    ///
+    /// Copy the images of `images.txt` to the registry mirror of
+    /// `talos.registry_mirror`, then run `oxide create --bundle DIR`.
+    Create {
+        /// Directory to write the bundle to
+        #[arg(long, value_name = "DIR")]
+        out: PathBuf,
+
+        /// Also upload the bundle to the bucket of `storage`, below `bundle/`
+        #[arg(long)]
+        upload: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum CniCommand {
+    /// Uninstall Cilium and install it again with the values from the config
+    ///
+    /// Recovers a cluster whose CNI broke after a bad values change. Pod
+    /// networking is down until the new Cilium pods are ready; the Gateway API
+    /// CRDs and Gateways are kept.
+    Reinstall,
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Diff the machine config of each node against one regenerated from the
+    /// config file
+    ///
+    /// Nodes without changes are listed as up to date.
+    Diff {
+        /// Only diff this node, e.g. my-cluster-worker-1
+        #[arg(long)]
+        node: Option<String>,
+
+        /// Also let Talos check the new config without applying it
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Apply machine configs regenerated from the config file to the nodes
+    ///
+    /// Changed nodes are updated one at a time, control planes first, each
+    /// followed by the upgrade health gates.
+    Apply {
+        /// Only update this node, e.g. my-cluster-worker-1
+        #[arg(long)]
+        node: Option<String>,
+
+        /// Whether nodes reboot into the new config
+        #[arg(long, value_enum, default_value = "auto")]
+        mode: ApplyMode,
+
+        /// Show the changes and let Talos check them without applying anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Upgrade the config files to the current apiVersion
+    ///
+    /// Renamed and moved fields are rewritten in place; comments and
+    /// formatting are kept.
+    Migrate {
+        /// Print the changes without writing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// Output format of listing commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum ListFormat {
+    /// Aligned columns
+    Table,
+    /// Table with IPs, server type and versions
+    Wide,
+    /// JSON array
+    Json,
+    /// YAML list
+    Yaml,
+}
+
+#[derive(Subcommand)]
+enum RobotCommand {
+    /// Apply machine configs to Robot servers in Talos maintenance mode
+    Join {
+        /// Only join this server (default: all configured servers)
+        server: Option<String>,
+    },
+
+    /// Reset a Robot server with Talos and delete its node
+    Remove {
+        /// Server name from the robot section of the config
+        server: String,
+
+        /// Skip graceful draining
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ArtifactsCommand {
+    /// Upload files of the output directory
+    Push {
+        /// File names in the output directory
+        #[arg(default_values = DEFAULT_ARTIFACTS)]
+        names: Vec<String>,
+    },
+
+    /// Download files into the output directory, replacing local copies
+    Pull {
+        /// File names in the output directory
+        #[arg(default_values = DEFAULT_ARTIFACTS)]
+        names: Vec<String>,
+    },
+
+    /// List the uploaded files of the cluster
+    List,
+}
+
+#[derive(Subcommand)]
+enum CiCommand {
+    /// Create the preview cluster of a pull request or branch
+    ///
+    /// The cluster is named `<cluster_name>-pr-<number>` or
+    /// `<cluster_name>-<branch>`, always expires, and its kubeconfig,
+    /// talosconfig and API endpoint are written to `GITHUB_OUTPUT`.
+    CreatePreview {
+        #[command(flatten)]
+        target: PreviewTarget,
+
+        /// Lifetime of the cluster, at most 7 days; expired clusters are
+        /// destroyed by `oxide reaper`
+        #[arg(long, value_name = "DURATION", value_parser = expiry::parse_ttl, default_value = "4h")]
+        ttl: chrono::Duration,
+    },
+
+    /// Destroy the preview cluster of a pull request or branch
+    DestroyPreview {
+        #[command(flatten)]
+        target: PreviewTarget,
+
+        /// Destroy servers the local cluster state does not know, e.g. in a
+        /// job that did not create the cluster and has no remote state
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+/// Pull request or branch a preview cluster is for, detected from the
+/// GitHub Actions environment if neither is given
+#[derive(clap::Args)]
+struct PreviewTarget {
+    /// Pull request number
+    #[arg(long, conflicts_with = "branch")]
+    pr: Option<u64>,
+
+    /// Branch name
+    #[arg(long)]
+    branch: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum StateCommand {
+    /// Release the lock of a run that did not finish, e.g. a cancelled CI job
+    Unlock,
+}
+
+/// Files `oxide artifacts push` and `pull` share without arguments
+const DEFAULT_ARTIFACTS: [&str; 2] = ["kubeconfig", "talosconfig"];
+
+#[derive(Subcommand)]
+enum ToolsCommand {
+    /// Download the tool versions matching the cluster config
+    Install,
+}
+
+#[derive(Subcommand)]
+enum UpgradeCommand {
+    /// Show available Talos, Kubernetes and Cilium upgrades
+    Check {
+        /// Write the recommended versions to the config file
+        #[arg(long)]
+        update_config: bool,
+    },
+
+    /// Continue a paused or failed version upgrade
+    Resume,
+}
+
+#[derive(Subcommand)]
+enum ScheduleCommand {
+    /// Roll out Talos patch releases in recurring maintenance windows
+    ///
+    /// Runs in the foreground (e.g. as a systemd service next to the output
+    /// directory) and, in each window of --cron, upgrades the nodes to the
+    /// newest patch release of the configured Talos minor version. Nodes not
+    /// reached before the window closes are upgraded in the next one. Each
+    /// window is reported to the `upgrade` notification events.
+    Upgrade {
+        /// Start of each maintenance window, as a cron expression in UTC,
+        /// e.g. "0 3 * * 6" for Saturdays at 03:00
+        #[arg(long, value_parser = schedule::Cron::parse)]
+        cron: schedule::Cron,
+
+        /// Length of each window; no node upgrade starts after it
+        #[arg(long, value_name = "DURATION", value_parser = expiry::parse_ttl, default_value = "4h")]
+        window: chrono::Duration,
+
+        /// Run one window now and exit, e.g. from a scheduled CI job
+        #[arg(long)]
+        once: bool,
+
+        /// Only report the patch release a window would roll out
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+impl Commands {
+    /// Subcommand name used in log fields
+    fn name(&self) -> &'static str {
+        match self {
+            Commands::Create { .. } => "create",
+            Commands::Clone { .. } => "clone",
+            Commands::Destroy { .. } => "destroy",
+            Commands::Reaper { .. } => "reaper",
+            Commands::Ci { .. } => "ci",
+            Commands::Pause { .. } => "pause",
+            Commands::Resume => "resume",
+            Commands::Status => "status",
+            Commands::Drift { .. } => "drift",
+            Commands::Audit { .. } => "audit",
+            Commands::Init { .. } => "init",
+            Commands::Scale { .. } => "scale",
+            Commands::Upgrade { .. } => "upgrade",
+            Commands::Schedule { .. } => "schedule",
+            Commands::DeployNginx => "deploy-nginx",
+            Commands::Kubeconfig { .. } => "kubeconfig",
+            Commands::DashboardUi { .. } => "dashboard-ui",
+            Commands::Ssh { .. } => "ssh",
+            Commands::Node { .. } => "node",
+            Commands::Addons { .. } => "addons",
+            Commands::Cni { .. } => "cni",
+            Commands::Render { .. } => "render",
+            Commands::Export { .. } => "export",
+            Commands::Bundle { .. } => "bundle",
+            Commands::Config { .. } => "config",
+            Commands::Top { .. } => "top",
+            Commands::Events { .. } => "events",
+            Commands::Robot { .. } => "robot",
+            Commands::History { .. } => "history",
+            Commands::Tools { .. } => "tools",
+            Commands::Artifacts { .. } => "artifacts",
+            Commands::State { .. } => "state",
+            Commands::Completions { .. } => "completions",
+            Commands::Mangen => "mangen",
+        }
+    }
+
+    /// Whether the command has phases worth a progress display
+    fn shows_progress(&self) -> bool {
+        matches!(
+            self,
+            Commands::Create { .. }
+                | Commands::Clone {
+                    no_create: false,
+                    ..
+                }
+                | Commands::Scale { .. }
+                | Commands::Pause { .. }
+                | Commands::Resume
+                | Commands::Upgrade {
+                    command: None | Some(UpgradeCommand::Resume),
+                    ..
+                }
+        )
+    }
+
+    /// Whether the command reads or writes the cluster state, so a remote
+    /// state is pulled before it runs
+    ///
+    /// Commands that manage several clusters or none, and `state` itself,
+    /// work without it; `ci` pulls the state of the preview cluster itself.
+    fn uses_remote_state(&self) -> bool {
+        !matches!(
+            self,
+            Commands::Init { .. }
+                | Commands::Reaper { .. }
+                | Commands::Ci { .. }
+                | Commands::Schedule { .. }
+                | Commands::History { .. }
+                | Commands::Tools { .. }
+                | Commands::Artifacts { .. }
+                | Commands::State { .. }
+                | Commands::Completions { .. }
+                | Commands::Mangen
+        )
+    }
+
+    /// Whether the command changes the cluster, so it holds the remote state
+    /// lock while it runs
+    fn changes_cluster(&self) -> bool {
+        matches!(
+            self,
+            Commands::Create { .. }
+                | Commands::Clone {
+                    no_create: false,
+                    ..
+                }
+                | Commands::Destroy { dry_run: false, .. }
+                | Commands::Pause { .. }
+                | Commands::Resume
+                | Commands::Scale { .. }
+                | Commands::Upgrade {
+                    command: None | Some(UpgradeCommand::Resume),
+                    ..
+                }
+                | Commands::Robot { .. }
+                | Commands::Addons {
+                    command: AddonsCommand::Uninstall { .. }
+                }
+                | Commands::Cni { .. }
+                | Commands::Config {
+                    command: ConfigCommand::Apply { dry_run: false, .. }
+                }
+        )
+    }
+}
+
+#[derive(Debug, Clone, clap::ValueEnum)]
+enum NodeType {
+    ControlPlane,
+    Worker,
+}
+
+impl NodeType {
+    fn role(&self) -> NodeRole {
+        match self {
+            NodeType::ControlPlane => NodeRole::ControlPlane,
+            NodeType::Worker => NodeRole::Worker,
+        }
+    }
+}
+
+/// Manifest format of `oxide export`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum ExportFormat {
+    /// Cluster API with the Hetzner and Talos providers
+    Capi,
+}
+
+/// Strategy for applying a changed `server_type` to existing nodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum ResizeStrategy {
+    /// Stop each server, change its type via the hcloud API, and start it again
+    InPlace,
+    /// Gracefully remove each node and create a new server with the new type
+    Replace,
+}
+
+#[tokio::main]
+async fn main() {
+    // Answers completion requests from the shell (COMPLETE=<shell>) and exits
+    CompleteEnv::with_factory(Cli::command).complete();
+
+    let cli = Cli::parse();
+
+    // The progress display replaces routine info logs unless verbose; JSON
+    // output is meant for machines, so it never gets a progress display
+    progress::init(
+        cli.command.shows_progress() && !cli.no_progress && cli.log_format == LogFormat::Text,
+    );
+    audit::init(&cli.output, cli.command.name());
+    if cli.non_interactive {
+        prompt::disable();
+    }
+
+    // Initialize tracing
+    let log_level = if cli.verbose {
+        "debug"
+    } else if progress::is_enabled() {
+        "warn"
+    } else {
+        "info"
+    };
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| format!("oxide={}", log_level).into());
+
+    // Spans are exported via OTLP only when an endpoint is configured
+    let (telemetry, otel_layer) = match Telemetry::from_env() {
+        Ok(Some((telemetry, tracer))) => (
+            Some(telemetry),
+            Some(tracing_opentelemetry::layer().with_tracer(tracer)),
+        ),
+        Ok(None) => (None, None),
+        Err(e) => {
+            eprintln!("OpenTelemetry export disabled: {:#}", e);
+            (None, None)
+        }
+    };
+
+    match cli.log_format {
+        LogFormat::Text => tracing_subscriber::registry()
+            .with(filter)
+            .with(otel_layer)
+            .with(tracing_subscriber::fmt::layer().with_writer(progress::LogWriter))
+            .init(),
+        LogFormat::Json => tracing_subscriber::registry()
+            .with(filter)
+            .with(otel_layer)
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_current_span(true)
+                    .with_span_list(true),
+            )
+            .init(),
+    }
+
+    // Every event carries the command, cluster and current phase as fields
+    let command_span = info_span!(
+        "command",
+        command = cli.command.name(),
+        cluster = tracing::field::Empty,
+        phase = tracing::field::Empty,
+    );
+
+    // Execute command
+    let started = Instant::now();
+    let started_at = chrono::Utc::now();
+    let (result, changes) = async {
+        let remote_state = match pull_remote_state(&cli).await {
+            Ok(remote_state) => remote_state,
+            Err(e) => return (Err(e), 0),
+        };
+        let before = audit::changes();
+        let result = run_command(&cli).await;
+        let changes = audit::changes() - before;
+        (push_remote_state(remote_state, result).await, changes)
+    }
+    .instrument(command_span.clone())
+    .await;
+
+    if let Err(e) = &result {
+        command_span.in_scope(|| error!("Error: {:#}", e));
+    }
+
+    notify_completion(&cli, &result, started.elapsed())
+        .instrument(command_span.clone())
+        .await;
+
+    if let Some(path) = &cli.summary_file {
+        let mut summary = Summary::new(
+            cli.command.name(),
+            &result,
+            changes,
+            started_at,
+            started.elapsed(),
+        )
+        .with_outputs(&cli.output);
+        if let Ok(config) = ClusterConfig::from_files(&cli.config) {
+            summary = summary.with_cluster(&config.cluster_name);
+        }
+        if let Err(e) = summary.write(path) {
+            command_span.in_scope(|| warn!("Failed to write the summary file: {:#}", e));
+        }
+    }
+
+    // Close the command span before flushing so it is exported too
+    drop(command_span);
+    if let Some(telemetry) = telemetry {
+        telemetry.shutdown();
+    }
+
+    if result.is_err() {
+        std::process::exit(exit::exit_code(&result, changes > 0));
+    }
+}
+
+/// Run the subcommand of `cli`
+async fn run_command(cli: &Cli) -> Result<()> {
+    match cli.command {
+        Commands::Create {
+            install_tools,
+            smoke_test,
+            ttl,
+            ref bundle,
+        } => create_cluster(cli, install_tools, smoke_test, ttl, bundle.as_deref()).await,
+        Commands::Clone {
+            ref from,
+            ref name,
+            ref to,
+            no_create,
+        } => clone_cluster(cli, from, name, to.as_deref(), no_create).await,
+        Commands::Destroy {
+            ref target_ids,
+            force,
+            dry_run,
+        } => {
+            if dry_run {
+                preview_destroy(cli, target_ids).await
+            } else {
+                destroy_cluster(cli, target_ids, force).await
+            }
+        }
+        Commands::Reaper {
+            interval,
+            once,
+            dry_run,
+        } => reap_clusters(cli, interval, once, dry_run).await,
+        Commands::Ci { ref command } => ci_command(cli, command).await,
+        Commands::Pause { snapshot } => pause_cluster(cli, snapshot).await,
+        Commands::Resume => resume_cluster(cli).await,
+        Commands::Status => show_status(cli).await,
+        Commands::Drift { json } => detect_drift(cli, json).await,
+        Commands::Audit { json } => audit_cluster(cli, json).await,
+        Commands::Init {
+            interactive,
+            template,
+            format,
+        } => init_config(cli, interactive, template, format).await,
+        Commands::Scale {
+            ref node_type,
+            count,
+            ref pool,
+            force,
+        } => scale_cluster(cli, node_type.clone(), count, pool.clone(), force).await,
+        Commands::Upgrade {
+            command: Some(UpgradeCommand::Check { update_config }),
+            ..
+        } => check_upgrades(cli, update_config).await,
+        Commands::Upgrade {
+            command: Some(UpgradeCommand::Resume),
+            ..
+        } => resume_upgrade(cli).await,
+        Commands::Upgrade {
+            command: None,
+            ref talos_version,
+            ref kubernetes_version,
+            resize_strategy,
+            pause_after,
+            ref pool,
+        } => {
+            upgrade_cluster(
+                cli,
+                talos_version.clone(),
+                kubernetes_version.clone(),
+                resize_strategy,
+                pause_after,
+                pool.clone(),
+            )
+            .await
+        }
+        Commands::Schedule {
+            command:
+                ScheduleCommand::Upgrade {
+                    ref cron,
+                    window,
+                    once,
+                    dry_run,
+                },
+        } => schedule_upgrades(cli, cron, window, once, dry_run).await,
+        Commands::DeployNginx => deploy_nginx(cli).await,
+        Commands::Kubeconfig {
+            merge,
+            ref context,
+            ref service_account,
+            ref role,
+            ref namespace,
+            ref duration,
+        } => {
+            let access = service_account.as_ref().map(|name| ServiceAccountAccess {
+                name: name.clone(),
+                role: role.clone(),
+                namespace: namespace.clone(),
+                duration: duration.clone(),
+            });
+            export_kubeconfig(cli, merge, context.as_deref(), access).await
+        }
+        Commands::DashboardUi { port, ref duration } => open_dashboard(cli, port, duration).await,
+        Commands::Ssh {
+            ref node,
+            rescue,
+            no_reboot,
+            ref command,
+        } => ssh_node(cli, node, rescue, no_reboot, command).await,
+        Commands::Node {
+            command:
+                NodeCommand::List {
+                    ref role,
+                    ref pool,
+                    ref location,
+                    output,
+                },
+        } => {
+            let filter = inventory::NodeFilter {
+                role: role.as_ref().map(NodeType::role),
+                pool: pool.clone(),
+                location: location.clone(),
+            };
+            list_nodes(cli, &filter, output).await
+        }
+        Commands::Node {
+            command: NodeCommand::Maintenance { ref command },
+        } => node_maintenance(cli, command).await,
+        Commands::Config {
+            command: ConfigCommand::Diff { ref node, dry_run },
+        } => reconcile_machine_configs(cli, node.as_deref(), None, dry_run).await,
+        Commands::Config {
+            command:
+                ConfigCommand::Apply {
+                    ref node,
+                    mode,
+                    dry_run,
+                },
+        } => reconcile_machine_configs(cli, node.as_deref(), Some(mode), dry_run).await,
+        Commands::Config {
+            command: ConfigCommand::Migrate { dry_run },
+        } => migrate_config_files(cli, dry_run),
+        Commands::Top {
+            ref role,
+            ref pool,
+            ref location,
+            window,
+            json,
+        } => {
+            let filter = inventory::NodeFilter {
+                role: role.as_ref().map(NodeType::role),
+                pool: pool.clone(),
+                location: location.clone(),
+            };
+            show_top(cli, &filter, window, json).await
+        }
+        Commands::Events {
+            all,
+            since,
+            follow,
+            json,
+        } => show_events(cli, !all, since, follow, json).await,
+        Commands::Addons {
+            command: AddonsCommand::Status { output },
+        } => show_addons_status(cli, output).await,
+        Commands::Addons {
+            command: AddonsCommand::Uninstall { ref name },
+        } => uninstall_addon(cli, name).await,
+        Commands::Cni {
+            command: CniCommand::Reinstall,
+        } => reinstall_cni(cli).await,
+        Commands::Render { ref out } => render_charts(cli, out).await,
+        Commands::Export { format, ref out } => export_cluster(cli, format, out.as_deref()),
+        Commands::Bundle {
+            command: BundleCommand::Create { ref out, upload },
+        } => create_bundle(cli, out, upload).await,
+        Commands::Robot { ref command } => robot_command(cli, command).await,
+        Commands::History {
+            limit,
+            failed,
+            json,
+        } => show_history(cli, limit, failed, json),
+        Commands::Tools {
+            command: ToolsCommand::Install,
+        } => install_tools(cli).await,
+        Commands::Artifacts { ref command } => artifacts_command(cli, command).await,
+        Commands::State { ref command } => state_command(cli, command).await,
+        Commands::Completions { shell } => print_completions(shell),
+        Commands::Mangen => print_man_page(),
+    }
+}
+
+/// Pull the remote cluster state, locking it for commands that change the
+/// cluster
+///
+/// Without a loadable config there is no remote state to pull; the command
+/// reports the config error itself.
+async fn pull_remote_state(cli: &Cli) -> Result<Option<RemoteState>> {
+    if !cli.command.uses_remote_state() {
+        return Ok(None);
+    }
+    let Ok(config) = ClusterConfig::from_files(&cli.config) else {
+        return Ok(None);
+    };
+    RemoteState::pull(
+        &config,
+        &cli.output,
+        cli.command.name(),
+        cli.command.changes_cluster(),
+    )
+    .await
+}
+
+/// Push the state after a command that changes the cluster, also after a
+/// failed one, since a failed create or destroy still changed which servers
+/// exist
+///
+/// A failed push is reported unless the command itself failed.
+async fn push_remote_state(remote_state: Option<RemoteState>, result: Result<()>) -> Result<()> {
+    let Some(remote_state) = remote_state else {
+        return result;
+    };
+    match (result, remote_state.push().await) {
+        (Err(e), Err(push)) => {
+            error!("Failed to save the cluster state: {:#}", push);
+            Err(e)
+        }
+        (result, push) => result.and(push),
+    }
+}
+
+/// Create or destroy a preview cluster, named by the overlay written to the
+/// output directory
+async fn ci_command(cli: &Cli, command: &CiCommand) -> Result<()> {
+    let (CiCommand::CreatePreview { target, .. } | CiCommand::DestroyPreview { target, .. }) =
+        command;
+    if let CiCommand::CreatePreview { ttl, .. } = command {
+        if *ttl > chrono::Duration::days(ci::MAX_TTL_DAYS) {
+            anyhow::bail!(Failure::config(format!(
+                "Preview clusters live at most {} days, --ttl is longer",
+                ci::MAX_TTL_DAYS
+            )));
+        }
+    }
+    let base = ClusterConfig::from_files(&cli.config)
+        .context(Failure::config("Failed to load configuration"))?;
+    let name = ci::PreviewRef::detect(target.pr, target.branch.as_deref())?
+        .cluster_name(&base.cluster_name)?;
+    info!("Preview cluster: {}", name);
+    let overlay = ci::write_overlay(&cli.output, &name)?;
+
+    let mut config = cli.config.clone();
+    config.push(overlay);
+    let preview_cli = Cli {
+        command: match command {
+            CiCommand::CreatePreview { ttl, .. } => Commands::Create {
+                install_tools: false,
+                smoke_test: false,
+                ttl: Some(*ttl),
+                bundle: None,
+            },
+            CiCommand::DestroyPreview { force, .. } => Commands::Destroy {
+                target_ids: Vec::new(),
+                force: *force,
+                dry_run: false,
+            },
+        },
+        config,
+        output: cli.output.clone(),
+        verbose: cli.verbose,
+        timeout: cli.timeout,
+        no_progress: cli.no_progress,
+        log_format: cli.log_format,
+        non_interactive: cli.non_interactive,
+        summary_file: None,
+    };
+
+    // The remote state of the preview is stored below its own name
+    let remote_state = pull_remote_state(&preview_cli).await?;
+    let result = match command {
+        CiCommand::CreatePreview { ttl, .. } => {
+            create_cluster(&preview_cli, false, false, Some(*ttl), None).await
+        }
+        CiCommand::DestroyPreview { force, .. } => destroy_cluster(&preview_cli, &[], *force).await,
+    };
+    push_remote_state(remote_state, result).await?;
+
+    let mut outputs = vec![("cluster-name", name)];
+    if let CiCommand::CreatePreview { ttl, .. } = command {
+        let kubeconfig = cli.output.join("kubeconfig");
+        if let Some(endpoint) = ci::kubeconfig_endpoint(&kubeconfig) {
+            outputs.push(("endpoint", endpoint));
+        }
+        outputs.push(("kubeconfig", kubeconfig.display().to_string()));
+        outputs.push((
+            "talosconfig",
+            cli.output.join("talosconfig").display().to_string(),
+        ));
+        outputs.push(("expires-at", (chrono::Utc::now() + *ttl).to_rfc3339()));
+    }
+    ci::write_outputs(&outputs)
+}
+
+async fn state_command(cli: &Cli, command: &StateCommand) -> Result<()> {
+    let config = load_config(cli)?;
+    match command {
+        StateCommand::Unlock => remote::unlock(&config).await,
+    }
+}
+
+/// Post the command outcome to the configured webhook, if any
+///
+/// Best-effort: a failing webhook is logged but never changes the exit status.
+async fn notify_completion(cli: &Cli, result: &Result<()>, duration: Duration) {
+    let Ok(config) = ClusterConfig::from_files(&cli.config) else {
+        return;
+    };
+    let Some(notifications) = &config.notifications else {
+        return;
+    };
+
+    let command = cli.command.name();
+    if !notifications.wants(command, result.is_ok()) {
+        return;
+    }
+
+    let outcome = notify::Outcome {
+        command,
+        cluster: &config.cluster_name,
+        duration,
+        error: result.as_ref().err().map(|e| format!("{:#}", e)),
+    };
+    if let Err(e) = notify::send(notifications, &outcome).await {
+        warn!("Failed to send notification: {:#}", e);
+    }
+}
+
+/// Load the cluster configuration, applying CLI overrides
+fn load_config(cli: &Cli) -> Result<ClusterConfig> {
+    let mut config = ClusterConfig::from_files(&cli.config)
+        .context(Failure::config("Failed to load configuration"))?;
+    config.timeouts = config.timeouts.with_override(cli.timeout);
+    let state = ClusterState::load(&cli.output)?;
+    versions::apply_pins(&mut config, state.as_ref());
+    cidr::apply_pins(&mut config, state.as_ref());
+    tracing::Span::current().record("cluster", config.cluster_name.as_str());
+    audit::set_cluster(&config.cluster_name);
+    tools::activate(&config);
+    Ok(config)
+}
+
+/// Create a new Talos cluster
+async fn create_cluster(
+    cli: &Cli,
+    install_tools: bool,
+    smoke_test: bool,
+    ttl: Option<chrono::Duration>,
+    bundle_dir: Option<&Path>,
+) -> Result<()> {
+    info!("Starting cluster creation...");
+
+    // Load configuration
+    let mut config = load_config(cli)?;
+    versions::resolve_aliases(&mut config, &cli.output).await?;
+    let hcloud_networks = HetznerCloudClient::from_config(&config)?
+        .list_networks()
+        .await?;
+    cidr::resolve_auto(&mut config, &hcloud_networks, &cli.output)?;
+    cidr::check_overlaps(&config, &hcloud_networks)?;
+
+    if install_tools {
+        let phase = progress::phase("Tools");
+        tools::install(&config).await?;
+        tools::activate(&config);
+        phase.finish();
+    }
+
+    // Check prerequisites
+    TalosClient::check_talosctl_installed()
+        .await
+        .context("talosctl is required")?;
+    KubernetesClient::check_kubectl_installed()
+        .await
+        .context("kubectl is required")?;
+    CiliumManager::check_helm_installed()
+        .await
+        .context("helm is required")?;
+    tools::check_versions(&config.talos.version, &config.talos.kubernetes_version).await?;
+
+    // A bundle missing a chart would only fail after the servers exist
+    let bundle = match bundle_dir {
+        Some(dir) => {
+            let bundle = Bundle::open(dir)?;
+            bundle.check(&config, &cli.output.join("kubeconfig"))?;
+            if config.talos.registry_mirror.is_none() {
+                warn!("⚠️  No talos.registry_mirror configured; nodes pull images from the public registries");
+            }
+            Some(bundle)
+        }
+        None => None,
+    };
+
+    info!("Cluster name: {}", config.cluster_name);
+
+    // Create Hetzner Cloud clients
+    let projects = Projects::from_config(&config)?;
+    projects.require_write_access("create").await?;
+    let hcloud_client = projects.main().clone();
+
+    let phase = progress::phase("Network");
+    phase.set_message("detecting public IP");
+
+    let allowed_ips = FirewallManager::allowed_ips(&config).await?;
+
+    // Create firewall
+    phase.set_message("firewall");
+    let firewall_manager = FirewallManager::new(hcloud_client.clone())
+        .with_kubespan(config.multi_region())
+        .with_egress(config.hcloud.egress.clone())
+        .with_public_ports(config.addons.ingress_nginx.node_ports());
+    let firewall = firewall_manager
+        .create_cluster_firewall(&config.cluster_name, &allowed_ips)
+        .await?;
+
+    // Create networks, one per network zone
+    phase.set_message("private network");
+    let network_manager = NetworkManager::new(hcloud_client.clone())
+        .with_delete_protection(config.hcloud.delete_protection);
+    let networks = network_manager
+        .ensure_networks(&config.cluster_name, &config.hcloud)
+        .await?;
+    // Labelled right away, so a cluster whose creation fails expires too
+    if let Some(ttl) = ttl {
+        expiry::set_expiry(
+            &hcloud_client,
+            &config.cluster_name,
+            &config.hcloud,
+            chrono::Utc::now() + ttl,
+        )
+        .await?;
+    }
+    if let Some(robot) = &config.robot {
+        network_manager
+            .ensure_vswitch_subnet(networks.primary().id, &config.hcloud.network.zone, robot)
+            .await?;
+    }
+
+    // Ensure SSH key exists for cluster
+    phase.set_message("SSH key");
+    let ssh_key_manager = SSHKeyManager::new(hcloud_client.clone());
+    let (ssh_key, private_key) = ssh_key_manager.ensure_ssh_key(&config.cluster_name).await?;
+
+    // Save private key if it was newly generated
+    if let Some(private_key_content) = private_key {
+        let ssh_key_path = cli.output.join("id_ed25519");
+        // ssh refuses keys others can read
+        platform::write_private(&ssh_key_path, private_key_content)
+            .context("Failed to save SSH private key")?;
+        info!("SSH private key saved to: {}", ssh_key_path.display());
+    }
+
+    // Workers without public IPs reach the internet through a NAT gateway
+    let nat_gateway = if config.needs_nat_gateway() {
+        phase.set_message("NAT gateway");
+        Some(
+            NatGatewayManager::new(hcloud_client.clone())
+                .ensure_gateway(
+                    &config.cluster_name,
+                    &config.hcloud,
+                    networks.primary(),
+                    Some(ssh_key.id),
+                )
+                .await?,
+        )
+    } else {
+        None
+    };
+
+    // The egress IP is configured on the gateway node by its machine config
+    let ip_manager = IpManager::new(hcloud_client.clone());
+    let egress_ip = match &config.cilium.egress_gateway {
+        Some(egress) if egress.floating_ip => {
+            phase.set_message("egress IP");
+            let pool = config
+                .workers
+                .iter()
+                .find(|pool| pool.egress_gateway)
+                .context("No egress gateway pool configured")?;
+            let location = pool.location_for(0, &config.hcloud.location);
+            Some(
+                ip_manager
+                    .ensure_egress_ip(&config.cluster_name, location)
+                    .await?,
+            )
+        }
+        _ => None,
+    };
+
+    phase.finish();
+
+    // Generate Talos configuration first (using placeholder endpoint if needed)
+    let phase = progress::phase("Talos configs");
+    let cluster_endpoint = config
+        .talos
+        .cluster_endpoint
+        .clone()
+        .unwrap_or_else(|| talos::diff::PLACEHOLDER_ENDPOINT.to_string());
+
+    info!(
+        "Generating Talos configuration with endpoint: {}",
+        cluster_endpoint
+    );
+
+    let generated = generate_machine_configs(
+        &config,
+        &networks,
+        &cluster_endpoint,
+        &cli.output,
+        None,
+        egress_ip.as_ref().map(|ip| ip.ip.as_str()),
+    )
+    .await?;
+    let config_generator = &generated.generator;
+    let configs = &generated.files;
+
+    // Read generated configs as user_data
+    let controlplane_user_data = user_data::prepare(
+        "controlplane.yaml",
+        tokio::fs::read_to_string(&configs.controlplane)
+            .await
+            .context("Failed to read controlplane config")?,
+        &config_generator.applied_patches(true),
+    )?;
+    let worker_user_data = user_data::prepare(
+        "worker.yaml",
+        tokio::fs::read_to_string(&configs.worker)
+            .await
+            .context("Failed to read worker config")?,
+        &config_generator.applied_patches(false),
+    )?;
+
+    // Pools with hardware requirements get their own config derived from worker.yaml
+    let mut pool_user_data = std::collections::HashMap::new();
+    for (pool_name, pool_config, patches) in &generated.pools {
+        let mut applied_patches = config_generator.applied_patches(false);
+        applied_patches.extend(patches.iter().cloned());
+        let user_data = user_data::prepare(
+            &format!("worker-{}.yaml", pool_name),
+            tokio::fs::read_to_string(pool_config)
+                .await
+                .context(format!("Failed to read config for pool {}", pool_name))?,
+            &applied_patches,
+        )?;
+        pool_user_data.insert(pool_name.clone(), user_data);
+    }
+
+    phase.finish();
+
+    // Create servers (all in parallel) with user_data
+    let total_servers: u32 = config
+        .control_planes
+        .iter()
+        .chain(config.workers.iter())
+        .map(|p| p.count)
+        .sum();
+    let server_manager = ServerManager::new(hcloud_client.clone())
+        .with_ipv6(config.cilium.enable_ipv6)
+        .with_delete_protection(config.hcloud.delete_protection)
+        .with_create_concurrency(config.hcloud.create_concurrency)
+        .with_progress(progress::counter("Servers", total_servers as u64));
+
+    info!("Creating all servers with Talos configuration...");
+    let main_workers: Vec<crate::config::NodeConfig> = config
+        .workers
+        .iter()
+        .filter(|pool| pool.project.is_none())
+        .cloned()
+        .collect();
+    let (control_planes, workers) = tokio::join!(
+        server_manager.create_control_planes(
+            &config.cluster_name,
+            &config.control_planes,
+            &config.hcloud.location,
+            &networks,
+            &config.talos.version,
+            config.talos.hcloud_snapshot_id.as_deref(),
+            Some(ssh_key.id),
+            Some(controlplane_user_data),
+        ),
+        server_manager.create_workers(
+            &config.cluster_name,
+            &main_workers,
+            &config.hcloud.location,
+            &networks,
+            &config.talos.version,
+            config.talos.hcloud_snapshot_id.as_deref(),
+            Some(ssh_key.id),
+            Some(worker_user_data.clone()),
+            &pool_user_data,
+        )
+    );
+    // Record what was created before bailing out, so destroy can clean it up
+    for servers in [&control_planes, &workers].into_iter().flatten() {
+        ClusterState::record_servers(&cli.output, &config.cluster_name, servers)?;
+    }
+    let control_planes = control_planes?;
+    let mut workers = workers?;
+
+    for (project, client) in projects.others() {
+        let project_workers = create_project_workers(
+            &config,
+            project,
+            client,
+            &allowed_ips,
+            &ssh_key.public_key,
+            &worker_user_data,
+            &pool_user_data,
+        )
+        .await?;
+        ClusterState::record_servers(&cli.output, &config.cluster_name, &project_workers)?;
+        workers.extend(project_workers);
+    }
+
+    // Apply firewall to all servers with a public interface
+    let server_ids: Vec<u64> = control_planes
+        .iter()
+        .chain(workers.iter())
+        .map(|s| &s.server)
+        .chain(nat_gateway.iter())
+        .filter(|server| server.public_net.ipv4.is_some() || server.public_net.ipv6.is_some())
+        .map(|server| server.id)
+        .collect();
+    firewall_manager
+        .apply_to_servers(firewall.id, server_ids)
+        .await?;
+
+    if let Some(floating_ip) = &egress_ip {
+        let gateway = workers
+            .iter()
+            .find(|info| inventory::pool_of(&config, info).is_some_and(|pool| pool.egress_gateway))
+            .context("No egress gateway node created")?;
+        ip_manager.assign(floating_ip, &gateway.server).await?;
+    }
+
+    // Get first control plane IP
+    let phase = progress::phase("Talos boot");
+    let first_cp = control_planes
+        .first()
+        .context("No control plane nodes created")?;
+    let cluster_endpoint_ip =
+        ServerManager::get_server_ip(&first_cp.server).context("Control plane has no public IP")?;
+    let actual_cluster_endpoint = config
+        .talos
+        .cluster_endpoint
+        .clone()
+        .unwrap_or_else(|| format!("https://{}:6443", cluster_endpoint_ip));
+
+    info!("Actual cluster endpoint: {}", actual_cluster_endpoint);
+
+    // Configure talosconfig with control plane endpoints
+    let talos_client = TalosClient::new(configs.talosconfig.clone());
+    let control_plane_ips: Vec<String> = control_planes
+        .iter()
+        .filter_map(|cp| ServerManager::get_server_ip(&cp.server))
+        .collect();
+    talos_client.configure_endpoints(&control_plane_ips).await?;
+
+    // Servers report running long before Talos has applied its config
+    let all_nodes: Vec<ServerInfo> = control_planes
+        .iter()
+        .chain(workers.iter())
+        .cloned()
+        .collect();
+    talos_client
+        .wait_for_nodes_booted(&all_nodes, config.timeouts.talos_boot)
+        .await?;
+    if config.addons.longhorn.enabled {
+        addons::longhorn::check_extensions(&talos_client, &all_nodes).await?;
+    }
+
+    phase.finish();
+
+    // Patch control plane nodes with actual endpoint if it differs from placeholder
+    // Workers use private network and don't need endpoint patching
+    if cluster_endpoint != actual_cluster_endpoint {
+        info!("Patching nodes with actual endpoint...");
+        // Workers in other network zones cannot fall back to the private
+        // network, so give KubePrism a reachable endpoint there as well
+        let endpoint_nodes = if config.multi_region() {
+            &all_nodes
+        } else {
+            &control_planes
+        };
+        talos_client
+            .patch_cluster_endpoint(endpoint_nodes, &actual_cluster_endpoint)
+            .await?;
+
+        info!("Endpoint patched successfully");
+    } else {
+        info!("Endpoint already correct, skipping patch");
+    }
+
+    // Bootstrap cluster
+    let phase = progress::phase("Bootstrap");
+    talos_client.bootstrap(first_cp).await?;
+
+    // Wait for API server
+    phase.set_message("waiting for Kubernetes API");
+    talos_client
+        .wait_for_api_server(&cluster_endpoint_ip, config.timeouts.api_server)
+        .await?;
+
+    // Generate kubeconfig
+    let kubeconfig_path = cli.output.join("kubeconfig");
+    talos_client
+        .generate_kubeconfig(&cluster_endpoint_ip, &kubeconfig_path)
+        .await?;
+
+    // Operator access first, so it exists even if a later phase fails
+    if let Some(rbac) = &config.rbac {
+        phase.set_message("applying operator RBAC");
+        k8s::rbac::apply(rbac, &kubeconfig_path).await?;
+    }
+
+    phase.finish();
+
+    // Install Cilium
+    let phase = progress::phase("CNI");
+    info!("Installing Cilium CNI...");
+    let control_plane_count = config.control_planes.iter().map(|cp| cp.count).sum();
+    let cilium_manager = CiliumManager::new(
+        config.cilium.clone(),
+        kubeconfig_path.clone(),
+        control_plane_count,
+    )
+    .with_helm_mode(config.helm.mode)
+    .with_kubespan(config.multi_region())
+    .with_cloud_load_balancers(config.cloud_load_balancers())
+    .with_istio(config.addons.istio.enabled)
+    .with_bundle(bundle.clone());
+    cilium_manager.install().await?;
+    phase.set_message("waiting for Cilium pods");
+    cilium_manager.wait_for_ready(config.timeouts.cni).await?;
+    cilium::policies::apply(&kubeconfig_path, &config.cilium.default_deny).await?;
+    // Their NetworkPolicies need the CNI to be enforced
+    k8s::tenants::apply(&config.tenants, &kubeconfig_path).await?;
+    cilium::egress::apply(
+        &kubeconfig_path,
+        &config,
+        egress_ip.as_ref().map(|ip| ip.ip.as_str()),
+    )
+    .await?;
+
+    // Zone labels for topology spread constraints, from each server's location
+    phase.set_message("labeling node zones");
+    for node in &all_nodes {
+        NodeManager::wait_for_node_ready(
+            &kubeconfig_path,
+            &node.server.name,
+            config.timeouts.node_ready,
+        )
+        .await?;
+        NodeManager::label_topology(
+            &kubeconfig_path,
+            &node.server.name,
+            ServerManager::location(&node.server),
+            &config.hcloud.network.zone,
+        )
+        .await?;
+    }
+    phase.finish();
+
+    // Install optional addons
+    let phase = progress::phase("Addons");
+    let addon_manager = AddonManager::new(&config, kubeconfig_path.clone())
+        .with_hcloud_token(config.get_hcloud_token()?)
+        .with_bundle(bundle);
+    addon_manager.install().await?;
+    phase.finish();
+
+    if smoke_test {
+        let phase = progress::phase("Smoke test");
+        SmokeTest::new(&kubeconfig_path, config.timeouts.smoke_test)
+            .run()
+            .await?;
+        phase.finish();
+    }
+
+    info!("✓ Cluster creation completed successfully!");
+    info!("");
+    info!("Cluster details:");
+    info!("  Name: {}", config.cluster_name);
+    info!("  Endpoint: {}", cluster_endpoint);
+    info!("  Control planes: {}", control_planes.len());
+    info!("  Workers: {}", workers.len());
+    info!("");
+    info!("Configuration files:");
+    info!("  Talosconfig: {}", configs.talosconfig.display());
+    info!("  Kubeconfig: {}", kubeconfig_path.display());
+    info!("");
+    info!("To access your cluster:");
+    info!("  export KUBECONFIG={}", kubeconfig_path.display());
+    info!("  kubectl get nodes");
+
+    Ok(())
+}
+
+/// Write a config for a copy of the cluster in `from` and create it
+async fn clone_cluster(
+    cli: &Cli,
+    from: &Path,
+    name: &str,
+    to: Option<&Path>,
+    no_create: bool,
+) -> Result<()> {
+    // The clone is written by editing the source text, keeping its comments
+    if ConfigFormat::from_path(from) != ConfigFormat::Yaml {
+        anyhow::bail!("Only YAML configs can be cloned, {} is not", from.display());
+    }
+    let to = to.map(Path::to_path_buf).unwrap_or_else(|| {
+        from.parent()
+            .unwrap_or(Path::new(""))
+            .join(format!("{}.yaml", name))
+    });
+    if to.exists() {
+        anyhow::bail!("{} already exists", to.display());
+    }
+    // Secrets and state in --output would be picked up by the new cluster
+    if !no_create
+        && std::fs::read_dir(&cli.output).is_ok_and(|mut entries| entries.next().is_some())
+    {
+        anyhow::bail!(
+            "The output directory {} is not empty; pass --output with a new directory for {}",
+            cli.output.display(),
+            name
+        );
+    }
+
+    let source =
+        ClusterConfig::from_files(&[from]).context(format!("Failed to load {}", from.display()))?;
+    if source.cluster_name == name {
+        anyhow::bail!("The clone needs another name than {}", name);
+    }
+    let content =
+        std::fs::read_to_string(from).context(format!("Failed to read {}", from.display()))?;
+
+    let taken: Vec<String> = HetznerCloudClient::from_config(&source)?
+        .list_networks()
+        .await?
+        .into_iter()
+        .map(|network| network.ip_range)
+        .collect();
+    let cloned = crate::config::clone::clone_config(&content, &source, name, &taken)?;
+    std::fs::write(&to, cloned).context(format!("Failed to write {}", to.display()))?;
+    let config = ClusterConfig::from_files(&[&to])
+        .context(format!("The cloned config {} is invalid", to.display()))?;
+    info!(
+        "✓ Wrote {} for cluster {} (network {})",
+        to.display(),
+        name,
+        config.hcloud.network.cidr
+    );
+    if let Some(endpoint) = &config.talos.cluster_endpoint {
+        warn!(
+            "⚠️  talos.cluster_endpoint is still {} from {}; change it in {} if it names the source cluster",
+            endpoint,
+            source.cluster_name,
+            to.display()
+        );
+    }
+
+    if no_create {
+        return Ok(());
+    }
+    let clone_cli = Cli {
+        command: Commands::Create {
+            install_tools: false,
+            smoke_test: false,
+            ttl: None,
+            bundle: None,
+        },
+        config: vec![to],
+        output: cli.output.clone(),
+        verbose: cli.verbose,
+        timeout: cli.timeout,
+        no_progress: cli.no_progress,
+        log_format: cli.log_format,
+        non_interactive: cli.non_interactive,
+        summary_file: None,
+    };
+    create_cluster(&clone_cli, false, false, None, None).await
+}
+
+/// Create the worker pools placed in a further project
+///
+/// Networks, firewalls and SSH keys belong to a project, so the project gets
+/// its own copies; the nodes join the cluster over KubeSpan.
+async fn create_project_workers(
+    config: &ClusterConfig,
+    project: &str,
+    client: &HetznerCloudClient,
+    allowed_ips: &[String],
+    public_key: &str,
+    worker_user_data: &str,
+    pool_user_data: &std::collections::HashMap<String, String>,
+) -> Result<Vec<ServerInfo>> {
+    let pools: Vec<crate::config::NodeConfig> = config
+        .workers
+        .iter()
+        .filter(|pool| pool.project.as_deref() == Some(project))
+        .cloned()
+        .collect();
+    if pools.is_empty() {
+        return Ok(Vec::new());
+    }
+    info!(
+        "Preparing project {} for pools with their own network",
+        project
+    );
+
+    let firewall_manager = FirewallManager::new(client.clone())
+        .with_kubespan(true)
+        .with_egress(config.hcloud.egress.clone())
+        .with_public_ports(config.addons.ingress_nginx.node_ports());
+    let firewall = firewall_manager
+        .create_cluster_firewall(&config.cluster_name, allowed_ips)
+        .await?;
+    let networks = NetworkManager::new(client.clone())
+        .with_delete_protection(config.hcloud.delete_protection)
+        .ensure_networks(&config.cluster_name, &config.hcloud)
+        .await?;
+    let ssh_key = SSHKeyManager::new(client.clone())
+        .import_ssh_key(&config.cluster_name, public_key)
+        .await?;
+
+    let workers = ServerManager::new(client.clone())
+        .with_ipv6(config.cilium.enable_ipv6)
+        .with_create_concurrency(config.hcloud.create_concurrency)
+        .create_workers(
+            &config.cluster_name,
+            &pools,
+            &config.hcloud.location,
+            &networks,
+            &config.talos.version,
+            config.hcloud.projects[project]
+                .hcloud_snapshot_id
+                .as_deref(),
+            Some(ssh_key.id),
+            Some(worker_user_data.to_string()),
+            pool_user_data,
+        )
+        .await?;
+
+    firewall_manager
+        .apply_to_servers(firewall.id, workers.iter().map(|w| w.server.id).collect())
+        .await?;
+    Ok(workers)
+}
+
+/// Destroy an existing cluster
+async fn destroy_cluster(cli: &Cli, target_ids: &[u64], force: bool) -> Result<()> {
+    info!("Starting cluster destruction...");
+
+    let config = load_config(cli)?;
+
+    info!("Cluster name: {}", config.cluster_name);
+
+    let projects = Projects::from_config(&config)?;
+    projects.require_write_access("destroy").await?;
+
+    let mut project_servers = Vec::new();
+    for client in projects.all() {
+        let server_manager = ServerManager::new(client.clone())
+            .with_ipv6(config.cilium.enable_ipv6)
+            .with_graceful_shutdown(graceful_shutdown(&config));
+        let servers = server_manager
+            .list_cluster_servers(&config.cluster_name)
+            .await?;
+        project_servers.push((client, server_manager, servers));
+    }
+    let servers: Vec<ServerInfo> = project_servers
+        .iter()
+        .flat_map(|(_, _, servers)| servers.iter().cloned())
+        .collect();
+    check_destroy_targets(cli, &config.cluster_name, &servers, force)?;
+
+    if !target_ids.is_empty() {
+        if let Some(id) = target_ids
+            .iter()
+            .find(|id| !servers.iter().any(|info| info.server.id == **id))
+        {
+            anyhow::bail!("Server {} is not a server of this cluster", id);
+        }
+        for (_, server_manager, servers) in project_servers {
+            let ids: Vec<u64> = target_ids
+                .iter()
+                .copied()
+                .filter(|id| servers.iter().any(|info| info.server.id == *id))
+                .collect();
+            if !ids.is_empty() {
+                destroy_servers(cli, &server_manager, servers, &ids).await?;
+            }
+        }
+        return Ok(());
+    }
+
+    teardown_cluster(&config, &projects).await?;
+
+    ClusterState::clear(&cli.output)?;
+    Hibernation::clear(&cli.output)?;
+    info!("✓ Cluster destroyed successfully");
+
+    Ok(())
+}
+
+/// Shutdown timeout for servers about to be deleted, if the config asks for
+/// graceful shutdowns
+fn graceful_shutdown(config: &ClusterConfig) -> Option<u64> {
+    config
+        .hcloud
+        .graceful_shutdown
+        .then_some(config.timeouts.server_shutdown)
+}
+
+/// Delete the servers and all other resources of a cluster in every project
+async fn teardown_cluster(config: &ClusterConfig, projects: &Projects) -> Result<()> {
+    for client in projects.all() {
+        // Delete servers
+        ServerManager::new(client.clone())
+            .with_ipv6(config.cilium.enable_ipv6)
+            .with_graceful_shutdown(graceful_shutdown(config))
+            .delete_cluster_servers(&config.cluster_name)
+            .await?;
+
+        // Only the main project has one
+        NatGatewayManager::new((*client).clone())
+            .delete_gateway(&config.cluster_name)
+            .await?;
+
+        IpManager::new((*client).clone())
+            .release_cluster_ips(&config.cluster_name)
+            .await?;
+
+        // Left by `pause --snapshot`
+        hibernate::delete_snapshots(client, &config.cluster_name).await?;
+
+        // Created by the cloud-controller-manager for ingress-nginx, and
+        // attached to the network
+        let name = addons::ingress_nginx::load_balancer_name(&config.cluster_name);
+        for load_balancer in client.list_load_balancers().await? {
+            if load_balancer.name == name {
+                info!(
+                    "Deleting load balancer: {} (ID: {})",
+                    load_balancer.name, load_balancer.id
+                );
+                client.delete_load_balancer(load_balancer.id).await?;
+            }
+        }
+
+        // Delete firewall
+        let firewall_manager = FirewallManager::new((*client).clone());
+        firewall_manager
+            .delete_cluster_firewall(&config.cluster_name)
+            .await?;
+
+        // Delete SSH key
+        let ssh_key_manager = SSHKeyManager::new((*client).clone());
+        ssh_key_manager
+            .delete_cluster_ssh_key(&config.cluster_name)
+            .await?;
+
+        // Delete network
+        let network_manager = NetworkManager::new((*client).clone());
+        network_manager
+            .delete_networks(&config.cluster_name, &config.hcloud)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Destroy clusters whose TTL has passed, every `interval_minutes` or once
+async fn reap_clusters(cli: &Cli, interval_minutes: u32, once: bool, dry_run: bool) -> Result<()> {
+    let config = load_config(cli)?;
+    let projects = Projects::from_config(&config)?;
+    if !dry_run {
+        projects.require_write_access("reaper").await?;
+    }
+
+    loop {
+        match reap_expired(cli, &config, &projects, dry_run).await {
+            Ok(()) => {}
+            Err(e) if once => return Err(e),
+            Err(e) => warn!("⚠️  Failed to check for expired clusters: {:#}", e),
+        }
+        if once {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_secs(u64::from(interval_minutes.max(1)) * 60)).await;
+    }
+}
+
+/// One pass of the reaper
+///
+/// Expired clusters are found in every project and deleted by their labels,
+/// once their servers check out against the cluster's own state. A cluster
+/// that fails either is retried on the next pass; the others are still
+/// destroyed.
+async fn reap_expired(
+    cli: &Cli,
+    config: &ClusterConfig,
+    projects: &Projects,
+    dry_run: bool,
+) -> Result<()> {
+    let now = chrono::Utc::now();
+    let mut failed = 0;
+    for cluster in expiry::expiring_clusters(projects).await? {
+        if !cluster.is_expired(now) {
+            info!("Cluster {} expires at {}", cluster.name, cluster.expires_at);
+            continue;
+        }
+        if dry_run {
+            println!(
+                "{} expired at {} and would be destroyed",
+                cluster.name, cluster.expires_at
+            );
+            continue;
+        }
+
+        warn!(
+            "⚠️  Cluster {} expired at {}, destroying it",
+            cluster.name, cluster.expires_at
+        );
+        let verified = async {
+            let (state, source) = reaped_cluster_state(cli, config, &cluster.name)
+                .await?
+                .context(format!(
+                    "No cluster state of {} to check its servers against",
+                    cluster.name
+                ))?;
+            let servers = projects.list_cluster_servers(&cluster.name).await?;
+            match destroy_target_problem(&state, &source, &cluster.name, &servers) {
+                Some(problem) => anyhow::bail!(problem),
+                None => Ok(()),
+            }
+        };
+        if let Err(e) = verified.await {
+            error!("Not destroying cluster {}: {:#}", cluster.name, e);
+            failed += 1;
+            continue;
+        }
+        let deleted = async {
+            for client in projects.all() {
+                expiry::delete_labelled(client, &cluster.name, graceful_shutdown(config)).await?;
+            }
+            Ok::<(), anyhow::Error>(())
+        };
+        if let Err(e) = deleted.await {
+            error!("Failed to destroy cluster {}: {:#}", cluster.name, e);
+            failed += 1;
+            continue;
+        }
+        if let Err(e) = forget_reaped_cluster(cli, config, &cluster.name).await {
+            warn!(
+                "⚠️  Failed to remove the state of cluster {}: {:#}",
+                cluster.name, e
+            );
+        }
+        info!("✓ Expired cluster {} destroyed", cluster.name);
+    }
+
+    if failed > 0 {
+        anyhow::bail!(Failure::partial(format!(
+            "{} expired cluster(s) could not be destroyed",
+            failed
+        )));
+    }
+    Ok(())
+}
+
+/// State of a cluster found by the reaper, with where it was read from
+///
+/// With `storage.remote_state` every cluster of the config, previews
+/// included, keeps its state in the bucket below its own name; otherwise only
+/// the state in `--output` is known, if it is that cluster's.
+async fn reaped_cluster_state(
+    cli: &Cli,
+    config: &ClusterConfig,
+    cluster_name: &str,
+) -> Result<Option<(ClusterState, String)>> {
+    if config.storage.as_ref().is_some_and(|s| s.remote_state) {
+        return remote::load_cluster(config, cluster_name).await;
+    }
+    Ok(ClusterState::load(&cli.output)?
+        .filter(|state| state.cluster_name == cluster_name)
+        .map(|state| (state, cli.output.display().to_string())))
+}
+
+/// Remove the state of a cluster the reaper destroyed
+async fn forget_reaped_cluster(
+    cli: &Cli,
+    config: &ClusterConfig,
+    cluster_name: &str,
+) -> Result<()> {
+    if config.storage.as_ref().is_some_and(|s| s.remote_state) {
+        return remote::forget_cluster(config, cluster_name).await;
+    }
+    if ClusterState::load(&cli.output)?.is_some_and(|state| state.cluster_name == cluster_name) {
+        ClusterState::clear(&cli.output)?;
+    }
+    Ok(())
+}
+
+/// Servers of the cluster in every project, with a manager for their project
+async fn cluster_servers_by_project(
+    config: &ClusterConfig,
+    projects: &Projects,
+) -> Result<Vec<(ServerManager, ServerInfo)>> {
+    let mut servers = Vec::new();
+    for client in projects.all() {
+        let server_manager = ServerManager::new(client.clone());
+        for info in server_manager
+            .list_cluster_servers(&config.cluster_name)
+            .await?
+        {
+            servers.push((ServerManager::new(client.clone()), info));
+        }
+    }
+    if servers.is_empty() {
+        anyhow::bail!("No servers found for cluster {}", config.cluster_name);
+    }
+    servers.sort_by_key(|(_, info)| info.server.name.clone());
+    Ok(servers)
+}
+
+/// Shut the nodes down with talosctl and power the servers off, and with
+/// `snapshot` replace them with snapshots
+async fn pause_cluster(cli: &Cli, snapshot: bool) -> Result<()> {
+    let config = load_config(cli)?;
+    let projects = Projects::from_config(&config)?;
+    projects.require_write_access("pause").await?;
+    if snapshot && Hibernation::load(&cli.output)?.is_some() {
+        // Continue a hibernation that was interrupted
+        if projects
+            .list_cluster_servers(&config.cluster_name)
+            .await?
+            .is_empty()
+        {
+            info!("Cluster {} is already hibernated", config.cluster_name);
+            return Ok(());
+        }
+    }
+    let servers = cluster_servers_by_project(&config, &projects).await?;
+    let talos_client = TalosClient::new(cli.output.join("talosconfig"));
+
+    // Control planes last, so the API stays up while the workers stop
+    for role in [NodeRole::Worker, NodeRole::ControlPlane] {
+        let running: Vec<&(ServerManager, ServerInfo)> = servers
+            .iter()
+            .filter(|(_, info)| info.role == role && info.server.status != "off")
+            .collect();
+        if running.is_empty() {
+            continue;
+        }
+        let phase = progress::phase(&format!("Stop {} nodes", role));
+        for (_, info) in &running {
+            let name = &info.server.name;
+            let Some(ip) = ServerManager::talos_ip(&info.server) else {
+                warn!("⚠️  {} has no IP address, it will be powered off", name);
+                continue;
+            };
+            if let Err(e) = talos_client.shutdown(&ip, name).await {
+                warn!("⚠️  {:#}; {} will be powered off", e, name);
+            }
+        }
+        let stopped = futures::future::join_all(running.iter().map(|(manager, info)| {
+            manager.ensure_stopped(&info.server, config.timeouts.server_shutdown)
+        }))
+        .await;
+        for result in stopped {
+            result?;
+        }
+        phase.finish();
+    }
+
+    if snapshot {
+        let phase = progress::phase("Snapshot servers");
+        hibernate::hibernate(&projects, &config.cluster_name, &cli.output).await?;
+        phase.finish();
+    }
+
+    info!(
+        "✓ Cluster {} is paused; run `oxide resume` to start it again",
+        config.cluster_name
+    );
+    Ok(())
+}
+
+/// Power the servers on, control planes first, and wait for the nodes
+async fn resume_cluster(cli: &Cli) -> Result<()> {
+    let config = load_config(cli)?;
+    let projects = Projects::from_config(&config)?;
+    projects.require_write_access("resume").await?;
+    let hibernated = Hibernation::load(&cli.output)?.is_some();
+    if hibernated {
+        let phase = progress::phase("Restore servers from snapshots");
+        hibernate::restore(&projects, &cli.output).await?;
+        phase.finish();
+    }
+    let servers = cluster_servers_by_project(&config, &projects).await?;
+    let talos_client = TalosClient::new(cli.output.join("talosconfig"));
+
+    let phase = progress::phase("Start control plane nodes");
+    let control_planes: Vec<&(ServerManager, ServerInfo)> = servers
+        .iter()
+        .filter(|(_, info)| info.role == NodeRole::ControlPlane)
+        .collect();
+    for (manager, info) in &control_planes {
+        manager.power_on(&info.server).await?;
+    }
+    let endpoint = control_planes
+        .iter()
+        .find_map(|(_, info)| ServerManager::talos_ip(&info.server))
+        .context("No control plane node has an IP address")?;
+    talos_client
+        .wait_for_api_server(&endpoint, config.timeouts.api_server)
+        .await?;
+    phase.finish();
+
+    let phase = progress::phase("Start worker nodes");
+    for (manager, info) in servers
+        .iter()
+        .filter(|(_, info)| info.role == NodeRole::Worker)
+    {
+        manager.power_on(&info.server).await?;
+    }
+    NodeManager::wait_for_all_nodes_ready(
+        &cli.output.join("kubeconfig"),
+        config.timeouts.node_ready,
+    )
+    .await?;
+    phase.finish();
+
+    if hibernated {
+        hibernate::finish(&projects, &cli.output).await?;
+    }
+
+    info!("✓ Cluster {} is running again", config.cluster_name);
+    Ok(())
+}
+
+/// Print what `destroy` would remove, including servers of the cluster state
+/// that no longer exist, without deleting anything
+async fn preview_destroy(cli: &Cli, target_ids: &[u64]) -> Result<()> {
+    let config = load_config(cli)?;
+    let hcloud_client = HetznerCloudClient::from_config(&config)?;
+    let state = ClusterState::load(&cli.output)?;
+    if let Some(state) = state
+        .as_ref()
+        .filter(|state| state.cluster_name != config.cluster_name)
+    {
+        warn!(
+            "⚠️  The output directory {} belongs to cluster {}, not {}; destroy needs --force",
+            cli.output.display(),
+            state.cluster_name,
+            config.cluster_name
+        );
+    }
+
+    let planned = teardown::plan(&hcloud_client, &config, state.as_ref(), target_ids).await?;
+    if planned.is_empty() {
+        println!("Nothing to destroy for cluster {}", config.cluster_name);
+        return Ok(());
+    }
+    print!("{}", teardown::render(&planned));
+    let deleted = planned
+        .iter()
+        .filter(|resource| resource.disposal == teardown::Disposal::Delete)
+        .count();
+    println!(
+        "\n{} resource(s) would be deleted (dry run, nothing changed)",
+        deleted
+    );
+    Ok(())
+}
+
+/// Compare the config with the running cluster and fail on differences
+async fn detect_drift(cli: &Cli, json: bool) -> Result<()> {
+    let config = load_config(cli)?;
+    let projects = Projects::from_config(&config)?;
+    let state = ClusterState::load(&cli.output)?;
+    let kubeconfig_path = cli.output.join("kubeconfig");
+
+    let report = drift::detect(&config, &projects, state.as_ref(), &kubeconfig_path).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else if report.drift.is_empty() {
+        print!("{}", drift::render(&report));
+        println!("No drift for cluster {}", config.cluster_name);
+    } else {
+        print!("{}", drift::render(&report));
+    }
+
+    if !report.drift.is_empty() {
+        anyhow::bail!(
+            "{} difference(s) between the config and cluster {}",
+            report.drift.len(),
+            config.cluster_name
+        );
+    }
+    Ok(())
+}
+
+/// Run kube-bench and the Talos checks and report failed controls
+async fn audit_cluster(cli: &Cli, json: bool) -> Result<()> {
+    let config = load_config(cli)?;
+    let projects = Projects::from_config(&config)?;
+    let kubeconfig_path = cli.output.join("kubeconfig");
+    if !kubeconfig_path.exists() {
+        anyhow::bail!(
+            "Kubeconfig not found at {}. Please create the cluster first.",
+            kubeconfig_path.display()
+        );
+    }
+
+    let mut servers = projects.list_cluster_servers(&config.cluster_name).await?;
+    servers.sort_by_key(|info| {
+        (
+            info.role != NodeRole::ControlPlane,
+            info.server.name.clone(),
+        )
+    });
+
+    let talos_client = TalosClient::new(cli.output.join("talosconfig"));
+    let mut findings = Vec::new();
+    for info in &servers {
+        let name = &info.server.name;
+        let Some(ip) = ServerManager::talos_ip(&info.server) else {
+            warn!("⚠️  {} has no IP address, skipping", name);
+            continue;
+        };
+        let machine_config = talos_client.machine_config(&ip).await?;
+        findings.extend(compliance::talos_checks(name, &machine_config));
+    }
+
+    let has_workers = servers.iter().any(|info| info.role == NodeRole::Worker);
+    findings.extend(
+        compliance::kube_bench(
+            &kubeconfig_path,
+            has_workers,
+            config.timeouts.security_audit,
+        )
+        .await?,
+    );
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&findings)?);
+    } else {
+        print!("{}", compliance::render_report(&findings));
+    }
+
+    let failed = findings
+        .iter()
+        .filter(|finding| finding.status == compliance::Status::Fail)
+        .count();
+    if failed > 0 {
+        anyhow::bail!(
+            "{} failed control(s) in cluster {}",
+            failed,
+            config.cluster_name
+        );
+    }
+    Ok(())
+}
+
+/// Make sure the labelled servers are the ones oxide created for this cluster
+///
+/// Servers only need a `cluster` label to be picked up, so a token for the
+/// wrong project or a copied config could otherwise delete someone else's
+/// servers.
+fn check_destroy_targets(
+    cli: &Cli,
+    cluster_name: &str,
+    servers: &[ServerInfo],
+    force: bool,
+) -> Result<()> {
+    let source = cli.output.display().to_string();
+    let problem = match ClusterState::load(&cli.output)? {
+        None if servers.is_empty() => return Ok(()),
+        None => format!(
+            "No cluster state in {}, so none of the {} server(s) labelled cluster={} can be verified",
+            source,
+            servers.len(),
+            cluster_name
+        ),
+        Some(state) => match destroy_target_problem(&state, &source, cluster_name, servers) {
+            Some(problem) => problem,
+            None => return Ok(()),
+        },
+    };
+
+    if force {
+        warn!("⚠️  {}; destroying anyway (--force)", problem);
+        return Ok(());
+    }
+    anyhow::bail!(
+        "{}.\n\
+        Check the Hetzner project and --output directory, then re-run with --force if these servers really belong to this cluster.",
+        problem
+    )
+}
+
+/// Why the labelled servers cannot be destroyed on the strength of the
+/// cluster state read from `source`, `None` if they are all in it
+fn destroy_target_problem(
+    state: &ClusterState,
+    source: &str,
+    cluster_name: &str,
+    servers: &[ServerInfo],
+) -> Option<String> {
+    if state.cluster_name != cluster_name {
+        return Some(format!(
+            "The cluster state in {} belongs to cluster {}, not {}",
+            source, state.cluster_name, cluster_name
+        ));
+    }
+    let unknown = state.unknown_servers(servers);
+    if unknown.is_empty() {
+        return None;
+    }
+    for info in &unknown {
+        warn!(
+            "⚠️  {} (ID: {}) is labelled cluster={} but was not created by oxide from {}",
+            info.server.name, info.server.id, cluster_name, source
+        );
+    }
+    Some(format!(
+        "{} server(s) labelled cluster={} are not in the cluster state",
+        unknown.len(),
+        cluster_name
+    ))
+}
+
+/// Delete selected servers of the cluster and their Kubernetes nodes
+async fn destroy_servers(
+    cli: &Cli,
+    server_manager: &ServerManager,
+    servers: Vec<ServerInfo>,
+    target_ids: &[u64],
+) -> Result<()> {
+    if let Some(id) = target_ids
+        .iter()
+        .find(|id| !servers.iter().any(|info| info.server.id == **id))
+    {
+        anyhow::bail!("Server {} is not a server of this cluster", id);
+    }
+    let targets: Vec<ServerInfo> = servers
+        .into_iter()
+        .filter(|info| target_ids.contains(&info.server.id))
+        .collect();
+
+    if targets
+        .iter()
+        .any(|info| info.role == NodeRole::ControlPlane)
+    {
+        warn!(
+            "⚠️  Deleting control plane servers without resetting them leaves stale etcd members"
+        );
+    }
+    server_manager.delete_servers(&targets).await?;
+    ClusterState::forget_servers(&cli.output, &targets)?;
+
+    let kubeconfig_path = cli.output.join("kubeconfig");
+    if kubeconfig_path.exists() {
+        for info in &targets {
+            if let Err(e) = NodeManager::delete_node(&kubeconfig_path, &info.server.name).await {
+                warn!("⚠️  Failed to delete node {}: {}", info.server.name, e);
+            }
+        }
+    }
+
+    info!("✓ Deleted {} server(s)", targets.len());
+    Ok(())
+}
+
+/// Show cluster status
+async fn show_status(cli: &Cli) -> Result<()> {
+    let config = load_config(cli)?;
+
+    let servers = Projects::from_config(&config)?
+        .list_cluster_servers(&config.cluster_name)
+        .await?;
+
+    if servers.is_empty() {
+        info!("No servers found for cluster: {}", config.cluster_name);
+        return Ok(());
+    }
+
+    info!("Cluster: {}", config.cluster_name);
+    info!("");
+
+    let mut control_planes: Vec<_> = servers
+        .iter()
+        .filter(|s| s.role == NodeRole::ControlPlane)
+        .collect();
+    control_planes.sort_by_key(|s| &s.server.name);
+
+    let mut workers: Vec<_> = servers
+        .iter()
+        .filter(|s| s.role == NodeRole::Worker)
+        .collect();
+    workers.sort_by_key(|s| &s.server.name);
+
+    // Display control plane node pools
+    info!("Control Plane Pools:");
+    for pool in &config.control_planes {
+        let pool_servers = ServerManager::filter_by_role_and_pool(
+            &servers,
+            NodeRole::ControlPlane,
+            Some(&pool.name),
+        );
+        info!(
+            "  {} - {} node(s) (server type: {}){}",
+            pool.name,
+            pool_servers.len(),
+            pool.server_type,
+            location_summary(pool, &pool_servers)
+        );
+        for server_info in pool_servers {
+            let ip = ServerManager::get_server_ip(&server_info.server)
+                .unwrap_or_else(|| "N/A".to_string());
+            let private_ip = ServerManager::get_server_private_ip(&server_info.server)
+                .unwrap_or_else(|| "N/A".to_string());
+            info!(
+                "    - {} (ID: {}, Status: {}, Location: {}, IP: {}, Private IP: {})",
+                server_info.server.name,
+                server_info.server.id,
+                server_info.server.status,
+                ServerManager::location(&server_info.server),
+                ip,
+                private_ip
+            );
+        }
+    }
+
+    info!("");
+    info!("Worker Pools:");
+    for pool in &config.workers {
+        let pool_servers =
+            ServerManager::filter_by_role_and_pool(&servers, NodeRole::Worker, Some(&pool.name));
+        info!(
+            "  {} - {} node(s) (server type: {}){}",
+            pool.name,
+            pool_servers.len(),
+            pool.server_type,
+            location_summary(pool, &pool_servers)
+        );
+        for server_info in pool_servers {
+            let ip = ServerManager::get_server_ip(&server_info.server)
+                .unwrap_or_else(|| "N/A".to_string());
+            let private_ip = ServerManager::get_server_private_ip(&server_info.server)
+                .unwrap_or_else(|| "N/A".to_string());
+            info!(
+                "    - {} (ID: {}, Status: {}, Location: {}, IP: {}, Private IP: {})",
+                server_info.server.name,
+                server_info.server.id,
+                server_info.server.status,
+                ServerManager::location(&server_info.server),
+                ip,
+                private_ip
+            );
+        }
+    }
+
+    if let Some(robot) = &config.robot {
+        info!("");
+        info!(
+            "Robot Servers (vSwitch {}, VLAN {}):",
+            robot.vswitch_id, robot.vlan
+        );
+        for server in &robot.servers {
+            info!(
+                "    - {} (IP: {}, Private IP: {})",
+                robot::node_name(&config.cluster_name, server),
+                server.public_ip,
+                server.private_ip
+            );
+        }
+    }
+
+    // Try to show Cilium status if kubeconfig exists
+    let kubeconfig_path = cli.output.join("kubeconfig");
+    if kubeconfig_path.exists() {
+        info!("");
+        info!("Cilium Status:");
+        let control_plane_count = config.control_planes.iter().map(|cp| cp.count).sum();
+        let cilium_manager =
+            CiliumManager::new(config.cilium.clone(), kubeconfig_path, control_plane_count);
+        match cilium_manager.get_status().await {
+            Ok(status) => info!("{}", status),
+            Err(e) => info!("Could not get Cilium status: {}", e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Node count per location of a spread pool, e.g. ` [fsn1: 2, nbg1: 2, hel1: 1]`
+fn location_summary(pool: &crate::config::NodeConfig, servers: &[ServerInfo]) -> String {
+    let Some(topology) = &pool.topology else {
+        return String::new();
+    };
+    let counts: Vec<String> = topology
+        .locations
+        .iter()
+        .map(|location| {
+            let count = servers
+                .iter()
+                .filter(|s| ServerManager::location(&s.server) == location)
+                .count();
+            format!("{}: {}", location, count)
+        })
+        .collect();
+    format!(" [{}]", counts.join(", "))
+}
+
+/// Generate a configuration file from a template or the interactive wizard
+async fn init_config(
+    cli: &Cli,
+    interactive: bool,
+    template: ConfigTemplate,
+    format: Option<ConfigFormat>,
+) -> Result<()> {
+    let [path] = cli.config.as_slice() else {
+        anyhow::bail!("init writes a single configuration file, pass one --config");
+    };
+    let format = format.unwrap_or_else(|| ConfigFormat::from_path(path));
+    let path = if ConfigFormat::from_path(path) == format {
+        path.clone()
+    } else {
+        path.with_extension(format.extension())
+    };
+    if path.exists() {
+        anyhow::bail!("Configuration file already exists: {}", path.display());
+    }
+
+    let config = if interactive {
+        if !prompt::is_available() {
+            anyhow::bail!(Failure::config("--interactive requires a terminal"));
+        }
+        config::wizard::run(template).await?
+    } else {
+        ClusterConfig::from_template(template)
+    };
+    let content = format.render(&config)?;
+
+    tokio::fs::write(&path, content)
+        .await
+        .context("Failed to write configuration file")?;
+
+    info!("Example configuration created: {}", path.display());
+    info!("");
+    info!("Next steps:");
+    info!("  1. Edit the configuration file to match your requirements");
+    info!("  2. Set your Hetzner Cloud API token:");
+    info!("     export HCLOUD_TOKEN=your-token-here");
+    info!("  3. Create the cluster:");
+    if path == Path::new("cluster.yaml") {
+        info!("     oxide create");
+    } else {
+        info!("     oxide --config {} create", path.display());
+    }
+
+    Ok(())
+}
+
+/// Scale cluster nodes
+async fn scale_cluster(
+    cli: &Cli,
+    node_type: NodeType,
+    target_count: u32,
+    pool_name: Option<String>,
+    force: bool,
+) -> Result<()> {
+    info!("Starting cluster scaling...");
+
+    let mut config = load_config(cli)?;
+    versions::resolve_aliases(&mut config, &cli.output).await?;
+    let hcloud_networks = HetznerCloudClient::from_config(&config)?
+        .list_networks()
+        .await?;
+    cidr::resolve_auto(&mut config, &hcloud_networks, &cli.output)?;
+
+    info!("Cluster name: {}", config.cluster_name);
+
+    // Determine role and pool configuration
+    let (role, pool_config) = match node_type {
+        NodeType::ControlPlane => {
+            let pool = if let Some(ref name) = pool_name {
+                config
+                    .control_planes
+                    .iter()
+                    .find(|p| &p.name == name)
+                    .ok_or_else(|| anyhow::anyhow!("Control plane pool '{}' not found", name))?
+            } else {
+                config
+                    .control_planes
+                    .first()
+                    .ok_or_else(|| anyhow::anyhow!("No control plane pools configured"))?
+            };
+            (NodeRole::ControlPlane, pool)
+        }
+        NodeType::Worker => {
+            let pool = if let Some(ref name) = pool_name {
+                config
+                    .workers
+                    .iter()
+                    .find(|p| &p.name == name)
+                    .ok_or_else(|| anyhow::anyhow!("Worker pool '{}' not found", name))?
+            } else {
+                config
+                    .workers
+                    .first()
+                    .ok_or_else(|| anyhow::anyhow!("No worker pools configured"))?
+            };
+            (NodeRole::Worker, pool)
+        }
+    };
 
-    // Delete firewall
-    let firewall_manager = FirewallManager::new(hcloud_client.clone());
-    firewall_manager
-        .delete_cluster_firewall(&config.cluster_name)
+    // The pool's servers live in its project
+    let hcloud_client = Projects::from_config(&config)?
+        .for_pool(pool_config)
+        .clone();
+    hcloud_client.require_write_access("scale").await?;
+
+    // Get existing servers
+    let server_manager = ServerManager::new(hcloud_client.clone())
+        .with_ipv6(config.cilium.enable_ipv6)
+        .with_graceful_shutdown(graceful_shutdown(&config));
+    let all_servers = server_manager
+        .list_cluster_servers(&config.cluster_name)
         .await?;
 
-    // Delete SSH key
-    let ssh_key_manager = SSHKeyManager::new(hcloud_client.clone());
-    ssh_key_manager
-        .delete_cluster_ssh_key(&config.cluster_name)
+    // Filter servers by role and pool
+    let pool_servers =
+        ServerManager::filter_by_role_and_pool(&all_servers, role, Some(&pool_config.name));
+
+    let current_count = pool_servers.len() as u32;
+
+    info!(
+        "Current {} count in pool '{}': {}",
+        role, pool_config.name, current_count
+    );
+    info!("Target count: {}", target_count);
+
+    if current_count == target_count {
+        info!("Cluster is already at the target size");
+        return Ok(());
+    }
+
+    if target_count > current_count {
+        // Scale up
+        let nodes_to_add = target_count - current_count;
+        info!("Scaling up: adding {} nodes", nodes_to_add);
+
+        scale_up(
+            cli,
+            &config,
+            &hcloud_client,
+            &pool_config.name,
+            pool_config,
+            role,
+            nodes_to_add,
+            &pool_servers,
+        )
+        .await?;
+    } else {
+        // Scale down
+        let nodes_to_remove = current_count - target_count;
+        info!("Scaling down: removing {} nodes", nodes_to_remove);
+
+        if force {
+            info!(
+                "⚠️  FORCE mode enabled: nodes will be removed immediately without graceful drain"
+            );
+        }
+
+        scale_down(
+            cli,
+            &server_manager,
+            pool_servers,
+            nodes_to_remove,
+            force,
+            &config.timeouts,
+        )
         .await?;
+    }
+
+    info!("✓ Cluster scaling completed successfully!");
+
+    Ok(())
+}
+
+/// Scale up by adding new nodes
+#[allow(clippy::too_many_arguments)]
+async fn scale_up(
+    cli: &Cli,
+    config: &ClusterConfig,
+    hcloud_client: &HetznerCloudClient,
+    pool_name: &str,
+    pool_config: &crate::config::NodeConfig,
+    role: NodeRole,
+    nodes_to_add: u32,
+    pool_servers: &[ServerInfo],
+) -> Result<()> {
+    let current_count = pool_servers.len() as u32;
+    let mut locations: Vec<String> = pool_servers
+        .iter()
+        .map(|s| ServerManager::location(&s.server).to_string())
+        .collect();
 
-    // Delete network
+    // Get networks
     let network_manager = NetworkManager::new(hcloud_client.clone());
-    network_manager.delete_network(&config.cluster_name).await?;
+    let networks = network_manager
+        .find_networks(&config.cluster_name, &config.hcloud)
+        .await?;
+
+    // Get SSH key
+    let ssh_key_manager = SSHKeyManager::new(hcloud_client.clone());
+    let ssh_key = ssh_key_manager
+        .ensure_ssh_key(&config.cluster_name)
+        .await?
+        .0;
+
+    // Get firewall
+    let firewall_manager = FirewallManager::new(hcloud_client.clone());
+    let firewall = firewall_manager
+        .get_cluster_firewall(&config.cluster_name)
+        .await?;
+
+    let user_data = read_node_user_data(cli, role, pool_config).await?;
+
+    let server_manager = ServerManager::new(hcloud_client.clone())
+        .with_ipv6(config.cilium.enable_ipv6)
+        .with_delete_protection(config.hcloud.delete_protection);
+
+    // Create new nodes
+    let phase = progress::phase("Servers");
+    let created = progress::counter("Servers", nodes_to_add as u64);
+    let mut new_server_ids = Vec::new();
+    let mut new_servers = Vec::new();
+    for i in 0..nodes_to_add {
+        let node_index = current_count + i + 1;
+        let node_name = format!("{}-{}-{}", config.cluster_name, pool_name, node_index);
+        let existing: Vec<&str> = locations.iter().map(String::as_str).collect();
+        let location = pool_config
+            .next_location(&existing, &config.hcloud.location)
+            .to_string();
+
+        let server_info = server_manager
+            .create_single_node(
+                &config.cluster_name,
+                &node_name,
+                &pool_config.server_type,
+                &location,
+                networks.for_location(&location).id,
+                role,
+                &config.talos.version,
+                config.snapshot_for_pool(pool_config),
+                Some(ssh_key.id),
+                Some(user_data.clone()),
+                pool_config.labels.clone(),
+                pool_config.public_ip,
+            )
+            .await?;
+
+        if let Some(template) = &pool_config.rdns_template {
+            server_manager
+                .configure_rdns(
+                    &server_info.server,
+                    template,
+                    &config.cluster_name,
+                    pool_name,
+                )
+                .await?;
+        }
+
+        ClusterState::record_servers(
+            &cli.output,
+            &config.cluster_name,
+            std::slice::from_ref(&server_info),
+        )?;
+        new_server_ids.push(server_info.server.id);
+        new_servers.push(server_info);
+        locations.push(location);
+        created.inc(1);
+        info!("✓ Node {} created successfully", node_name);
+    }
+    phase.finish();
+
+    // A new egress gateway takes over the egress IP
+    if pool_config.egress_gateway {
+        let ip_manager = IpManager::new(hcloud_client.clone());
+        let egress_ip = ip_manager.find_egress_ip(&config.cluster_name).await?;
+        if let (Some(floating_ip), Some(info)) = (egress_ip, new_servers.first()) {
+            ip_manager.assign(&floating_ip, &info.server).await?;
+        }
+    }
+
+    let phase = progress::phase("Talos boot");
+    TalosClient::new(cli.output.join("talosconfig"))
+        .wait_for_nodes_booted(&new_servers, config.timeouts.talos_boot)
+        .await?;
+    phase.finish();
+
+    // Wait for new nodes to become Ready
+    info!("Waiting for new nodes to become Ready...");
+    let phase = progress::phase("Node ready");
+    let kubeconfig_path = cli.output.join("kubeconfig");
+
+    for server_info in &new_servers {
+        let node_name = &server_info.server.name;
+        NodeManager::wait_for_node_ready(&kubeconfig_path, node_name, config.timeouts.node_ready)
+            .await?;
+        NodeManager::label_topology(
+            &kubeconfig_path,
+            node_name,
+            ServerManager::location(&server_info.server),
+            &config.hcloud.network.zone,
+        )
+        .await?;
+    }
+
+    // Apply firewall to new servers (private ones have no interface to filter)
+    if let (Some(fw), true) = (firewall, pool_config.public_ip) {
+        firewall_manager
+            .apply_to_servers(fw.id, new_server_ids)
+            .await?;
+    }
+    phase.finish();
+
+    info!("All new nodes created and configured");
+
+    Ok(())
+}
+
+/// Rewrite each `--config` file for the current config apiVersion
+fn migrate_config_files(cli: &Cli, dry_run: bool) -> Result<()> {
+    for path in &cli.config {
+        let file = path.display().to_string();
+        let content = std::fs::read_to_string(path).context(format!("Failed to read {}", file))?;
+        let format = ConfigFormat::from_path(path);
+        let Some(migrated) = config::migrate::migrate_text(&content, format)
+            .map_err(|e| anyhow::anyhow!("{}: {}", file, e))?
+        else {
+            info!("✓ {} already uses {}", file, config::migrate::API_VERSION);
+            continue;
+        };
+        if dry_run {
+            if let Some(diff) = talos::diff::unified_diff(&content, &migrated, &file, &file) {
+                print!("{}", diff);
+            }
+            continue;
+        }
+        std::fs::write(path, migrated).context(format!("Failed to write {}", file))?;
+        info!("✓ Migrated {} to {}", file, config::migrate::API_VERSION);
+    }
+    Ok(())
+}
+
+/// Diff each node's machine config against the config regenerated from the
+/// cluster config and, with `apply`, roll the changes out one node at a time
+///
+/// The regenerated configs replace the ones in the output directory once
+/// every node has them, so new nodes get them as well.
+async fn reconcile_machine_configs(
+    cli: &Cli,
+    node: Option<&str>,
+    apply: Option<ApplyMode>,
+    dry_run: bool,
+) -> Result<()> {
+    let config = load_config(cli)?;
+    let projects = Projects::from_config(&config)?;
+    if apply.is_some() && !dry_run {
+        projects.require_write_access("config apply").await?;
+    }
+    let mut servers = projects.list_cluster_servers(&config.cluster_name).await?;
+    let control_plane_ips: Vec<String> = servers
+        .iter()
+        .filter(|info| info.role == NodeRole::ControlPlane)
+        .filter_map(|info| ServerManager::talos_ip(&info.server))
+        .collect();
+    if let Some(node) = node {
+        servers.retain(|info| info.server.name == node);
+        if servers.is_empty() {
+            anyhow::bail!("Node {} not found in cluster {}", node, config.cluster_name);
+        }
+    }
+    servers.sort_by_key(|info| {
+        (
+            info.role != NodeRole::ControlPlane,
+            info.server.name.clone(),
+        )
+    });
+
+    // Regenerated next to the output directory, whose configs are still the
+    // ones the nodes were created with
+    let networks = NetworkManager::new(projects.main().clone())
+        .find_networks(&config.cluster_name, &config.hcloud)
+        .await?;
+    let staging = cli.output.join("regenerated");
+    let cluster_endpoint = config
+        .talos
+        .cluster_endpoint
+        .clone()
+        .unwrap_or_else(|| talos::diff::PLACEHOLDER_ENDPOINT.to_string());
+    let egress_ip = IpManager::new(projects.main().clone())
+        .find_egress_ip(&config.cluster_name)
+        .await?;
+    generate_machine_configs(
+        &config,
+        &networks,
+        &cluster_endpoint,
+        &staging,
+        Some(&cli.output),
+        egress_ip.as_ref().map(|ip| ip.ip.as_str()),
+    )
+    .await?;
+
+    let talos_client = TalosClient::new(cli.output.join("talosconfig"));
+    let runner = RollingUpgrade::new(&cli.output, &config);
+    let mut changed = 0;
+    for info in &servers {
+        let name = &info.server.name;
+        let Some(pool) = inventory::pool_of(&config, info) else {
+            warn!("⚠️  {} belongs to no configured pool, skipping", name);
+            continue;
+        };
+        let Some(ip) = ServerManager::talos_ip(&info.server) else {
+            warn!("⚠️  {} has no IP address, skipping", name);
+            continue;
+        };
+
+        let path = node_config_path(&staging, info.role, pool);
+        let generated = tokio::fs::read_to_string(&path)
+            .await
+            .context(format!("Failed to read {}", path.display()))?;
+        let live = talos_client.machine_config(&ip).await?;
+        let desired = talos::diff::with_live_endpoint(&generated, &live);
+
+        let Some(diff) = talos::diff::unified_diff(&live, &desired, name, "cluster.yaml") else {
+            println!("{}: up to date", name);
+            continue;
+        };
+        changed += 1;
+        print!("{}", diff);
+
+        if dry_run {
+            let scratch = talos::diff::ScratchConfig::write(&desired).await?;
+            let mode = apply.unwrap_or(ApplyMode::Auto);
+            let outcome = talos_client
+                .apply_config(&ip, name, scratch.path(), mode, true)
+                .await?;
+            println!("{}", outcome.trim_end());
+        } else if let Some(mode) = apply {
+            let scratch = talos::diff::ScratchConfig::write(&desired).await?;
+            runner
+                .apply_config(info, &ip, scratch.path(), mode, &control_plane_ips)
+                .await?;
+        }
+    }
+
+    if apply.is_some() && !dry_run && node.is_none() {
+        for entry in std::fs::read_dir(&staging)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "yaml")
+            {
+                std::fs::copy(&path, cli.output.join(path.file_name().unwrap()))?;
+            }
+        }
+    }
+    std::fs::remove_dir_all(&staging).context(format!("Failed to remove {}", staging.display()))?;
+
+    let verb = if apply.is_some() && !dry_run {
+        "were updated"
+    } else {
+        "differ from cluster.yaml"
+    };
+    println!("\n{} of {} node(s) {}", changed, servers.len(), verb);
+    Ok(())
+}
+
+/// Machine configs generated from the cluster config
+struct MachineConfigs {
+    generator: TalosConfigGenerator,
+    files: talos::config::GeneratedConfigs,
+    /// Configs of pools with patches of their own, with those patches
+    pools: Vec<(String, PathBuf, Vec<String>)>,
+}
+
+/// Generate the machine configs of every role and pool into `output_dir`
+///
+/// With `existing_secrets` the configs of a running cluster are regenerated
+/// with the secrets stored for that output directory.
+async fn generate_machine_configs(
+    config: &ClusterConfig,
+    networks: &ClusterNetworks,
+    cluster_endpoint: &str,
+    output_dir: &Path,
+    existing_secrets: Option<&Path>,
+    egress_ip: Option<&str>,
+) -> Result<MachineConfigs> {
+    let mut cluster_patches =
+        AddonManager::machine_config_patches(&config.addons, &config.talos.version);
+    cluster_patches.push(talos::config::cluster_network_patch(
+        &config.talos,
+        config.cilium.enable_ipv6,
+    ));
+    if config.multi_region() {
+        cluster_patches.push(talos::config::KUBESPAN_PATCH.to_string());
+    }
+    if let Some(mirror) = &config.talos.registry_mirror {
+        cluster_patches.push(talos::config::registry_mirror_patch(mirror));
+    }
+
+    let mut control_plane_patches: Vec<String> = config
+        .rbac
+        .iter()
+        .filter_map(|rbac| rbac.oidc.as_ref())
+        .map(talos::config::oidc_patch)
+        .collect();
+    if !config.talos.cert_sans.is_empty() {
+        control_plane_patches.push(talos::config::cert_sans_patch(&config.talos.cert_sans));
+    }
+    if config
+        .control_planes
+        .iter()
+        .any(|pool| pool.allow_workloads)
+    {
+        control_plane_patches.push(talos::config::ALLOW_SCHEDULING_PATCH.to_string());
+    }
+
+    let mut generator =
+        TalosConfigGenerator::new(config.cluster_name.clone(), config.talos.clone())
+            .with_patches(cluster_patches)
+            .with_control_plane_patches(control_plane_patches)
+            .with_extensions(AddonManager::required_extensions(&config.addons));
+    if let Some(dir) = existing_secrets {
+        generator = generator.with_existing_secrets(dir);
+    }
+
+    let files = generator
+        .generate_configs(cluster_endpoint, output_dir)
+        .await?;
+
+    // Pools with hardware requirements get their own config derived from worker.yaml
+    let mut pools = Vec::new();
+    for pool in &config.workers {
+        let mut patches = AddonManager::pool_machine_config_patches(pool);
+        if !pool.public_ip {
+            let gateway = &networks
+                .primary()
+                .subnets
+                .first()
+                .context("Cluster network has no subnet")?
+                .gateway;
+            patches.push(talos::config::private_egress_patch(gateway));
+        }
+        if pool.egress_gateway {
+            patches.push(cilium::egress::machine_config_patch(egress_ip));
+        }
+        if patches.is_empty() {
+            continue;
+        }
+        let extensions = AddonManager::pool_required_extensions(&config.addons, pool);
+        let pool_config = generator
+            .generate_pool_config(&pool.name, &files.worker, output_dir, &patches, &extensions)
+            .await?;
+        pools.push((pool.name.clone(), pool_config, patches));
+    }
+
+    Ok(MachineConfigs {
+        generator,
+        files,
+        pools,
+    })
+}
+
+/// Generated machine config for nodes of a pool
+fn node_config_path(
+    output_dir: &Path,
+    role: NodeRole,
+    pool_config: &crate::config::NodeConfig,
+) -> PathBuf {
+    if role == NodeRole::ControlPlane {
+        output_dir.join("controlplane.yaml")
+    } else if !pool_config.public_ip
+        || pool_config.egress_gateway
+        || !AddonManager::pool_machine_config_patches(pool_config).is_empty()
+    {
+        talos::config::pool_config_path(output_dir, &pool_config.name)
+    } else {
+        output_dir.join("worker.yaml")
+    }
+}
+
+/// Read the existing Talos machine config used for new nodes of a pool
+async fn read_node_user_data(
+    cli: &Cli,
+    role: NodeRole,
+    pool_config: &crate::config::NodeConfig,
+) -> Result<String> {
+    // Read existing Talos configuration files (cluster must already exist)
+    let config_path = node_config_path(&cli.output, role, pool_config);
+
+    if !config_path.exists() {
+        anyhow::bail!(
+            "Talos configuration file not found: {}\n\
+            This operation requires an existing cluster. Please run 'oxide create' first.",
+            config_path.display()
+        );
+    }
+
+    info!(
+        "Using existing {} configuration from {}",
+        role,
+        config_path.display()
+    );
 
-    info!("✓ Cluster destroyed successfully");
+    let content = tokio::fs::read_to_string(&config_path)
+        .await
+        .context(format!(
+            "Failed to read config from {}",
+            config_path.display()
+        ))?;
 
-    Ok(())
+    let name = config_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("machine config");
+    user_data::prepare(name, content, &[])
 }
 
-/// Show cluster status
-async fn show_status(cli: &Cli) -> Result<()> {
-    let config = ClusterConfig::from_file(&cli.config).context("Failed to load configuration")?;
+/// Scale down by removing nodes with parallel reset and validation
+async fn scale_down(
+    cli: &Cli,
+    server_manager: &ServerManager,
+    pool_servers: Vec<ServerInfo>,
+    nodes_to_remove: u32,
+    force: bool,
+    timeouts: &TimeoutsConfig,
+) -> Result<()> {
+    let timeout = timeouts.node_reset;
+    // Newest nodes first, from the most populated location of spread pools
+    let servers_to_remove = server::removal_order(pool_servers, nodes_to_remove as usize);
 
-    let hcloud_token = config.get_hcloud_token()?;
-    let hcloud_client = HetznerCloudClient::new(hcloud_token)?;
+    if servers_to_remove.is_empty() {
+        info!("No servers to remove");
+        return Ok(());
+    }
 
-    let server_manager = ServerManager::new(hcloud_client.clone());
-    let servers = server_manager
-        .list_cluster_servers(&config.cluster_name)
-        .await?;
+    info!("Gracefully removing {} node(s)...", servers_to_remove.len());
 
-    if servers.is_empty() {
-        info!("No servers found for cluster: {}", config.cluster_name);
-        return Ok(());
+    // Initialize Talos client
+    let talosconfig_path = cli.output.join("talosconfig");
+    if !talosconfig_path.exists() {
+        anyhow::bail!(
+            "Talosconfig not found at {}. Cannot perform graceful node removal.",
+            talosconfig_path.display()
+        );
     }
 
-    info!("Cluster: {}", config.cluster_name);
-    info!("");
+    // Kubeconfig for kubectl operations
+    let kubeconfig_path = cli.output.join("kubeconfig");
+    if !kubeconfig_path.exists() {
+        anyhow::bail!(
+            "Kubeconfig not found at {}. Cannot perform graceful node removal.",
+            kubeconfig_path.display()
+        );
+    }
 
-    let mut control_planes: Vec<_> = servers
+    // PRE-FLIGHT VALIDATION
+    let node_names: Vec<String> = servers_to_remove
         .iter()
-        .filter(|s| s.role == NodeRole::ControlPlane)
+        .map(|s| s.server.name.clone())
         .collect();
-    control_planes.sort_by_key(|s| &s.server.name);
 
-    let mut workers: Vec<_> = servers
-        .iter()
-        .filter(|s| s.role == NodeRole::Worker)
-        .collect();
-    workers.sort_by_key(|s| &s.server.name);
+    info!("Running pre-flight validation checks...");
 
-    // Display control plane node pools
-    info!("Control Plane Pools:");
-    for pool in &config.control_planes {
-        let pool_servers = ServerManager::filter_by_role_and_pool(
-            &servers,
-            NodeRole::ControlPlane,
-            Some(&pool.name),
-        );
-        info!(
-            "  {} - {} node(s) (server type: {})",
-            pool.name,
-            pool_servers.len(),
-            pool.server_type
+    // Validate etcd quorum won't be broken
+    NodeManager::validate_etcd_quorum(&kubeconfig_path, &node_names).await?;
+
+    info!("✓ Pre-flight validation passed");
+
+    // PHASE 1: PARALLEL NODE RESET
+    info!("Phase 1/3: Resetting nodes in parallel...");
+    let phase = progress::phase("Node reset");
+
+    let mut reset_tasks = Vec::new();
+
+    for server_info in &servers_to_remove {
+        let node_name = server_info.server.name.clone();
+        let node_ip = ServerManager::talos_ip(&server_info.server);
+        let talos_client_clone = TalosClient::new(talosconfig_path.clone());
+        let kubeconfig_path_clone = kubeconfig_path.clone();
+
+        let node_span = info_span!("node", node = %node_name);
+        let task = tokio::spawn(
+            async move {
+                if let Some(ip) = node_ip {
+                    info!("Resetting node {} ({})...", node_name, ip);
+
+                    // Proceed with reset (talosctl will handle connectivity)
+                    let reset_result = talos_client_clone
+                        .reset_node_with_timeout(&ip, &node_name, timeout, force, 2)
+                        .await;
+
+                    match reset_result {
+                        Ok(_) => {
+                            info!("✓ Node {} reset completed", node_name);
+                        }
+                        Err(e) => {
+                            // Check if this is an expected error (node powered down during reset)
+                            let err_msg = e.to_string();
+                            if err_msg.contains("connection closed")
+                                || err_msg.contains("broken pipe")
+                                || err_msg.contains("reset by peer")
+                            {
+                                info!("✓ Node {} powered down during reset (expected)", node_name);
+                            } else {
+                                return Err(e);
+                            }
+                        }
+                    }
+
+                    // Monitor drain progress if not in force mode
+                    if !force {
+                        info!("Monitoring drain progress for {}...", node_name);
+                        if let Err(e) = NodeManager::monitor_drain_progress(
+                            &kubeconfig_path_clone,
+                            &node_name,
+                            timeout,
+                        )
+                        .await
+                        {
+                            info!(
+                                "Warning: Failed to monitor drain progress for {}: {}",
+                                node_name, e
+                            );
+                        }
+                    }
+
+                    Ok::<String, anyhow::Error>(node_name)
+                } else {
+                    info!(
+                        "⚠️  Warning: Node {} has no public IP, skipping reset",
+                        node_name
+                    );
+                    Ok::<String, anyhow::Error>(node_name)
+                }
+            }
+            .instrument(node_span),
         );
-        for server_info in pool_servers {
-            let ip = ServerManager::get_server_ip(&server_info.server)
-                .unwrap_or_else(|| "N/A".to_string());
-            let private_ip = ServerManager::get_server_private_ip(&server_info.server)
-                .unwrap_or_else(|| "N/A".to_string());
+
+        reset_tasks.push(task);
+    }
+
+    // Wait for all resets to complete
+    info!("Waiting for all node resets to complete...");
+    let reset_results = futures::future::join_all(reset_tasks).await;
+
+    let mut successfully_reset = Vec::new();
+    let mut failed_resets = Vec::new();
+
+    for (idx, result) in reset_results.into_iter().enumerate() {
+        match result {
+            Ok(Ok(node_name)) => {
+                successfully_reset.push(node_name);
+            }
+            Ok(Err(e)) => {
+                let node_name = &servers_to_remove[idx].server.name;
+                failed_resets.push(format!("{}: {}", node_name, e));
+            }
+            Err(e) => {
+                let node_name = &servers_to_remove[idx].server.name;
+                failed_resets.push(format!("{}: task join error: {}", node_name, e));
+            }
+        }
+    }
+
+    if !failed_resets.is_empty() {
+        anyhow::bail!(Failure::partial(format!(
+            "Failed to reset {} node(s):\n  {}",
+            failed_resets.len(),
+            failed_resets.join("\n  ")
+        )));
+    }
+
+    info!(
+        "✓ Phase 1 complete: {} nodes reset successfully",
+        successfully_reset.len()
+    );
+    phase.finish();
+
+    // PHASE 2: DELETE FROM KUBERNETES
+    info!("Phase 2/3: Removing nodes from Kubernetes...");
+    let phase = progress::phase("Kubernetes cleanup");
+
+    for node_name in &successfully_reset {
+        // Wait for node to be cordoned and NotReady before deleting
+        if let Err(e) =
+            NodeManager::wait_for_node_cordoned(&kubeconfig_path, node_name, timeouts.node_drain)
+                .await
+        {
             info!(
-                "    - {} (ID: {}, Status: {}, IP: {}, Private IP: {})",
-                server_info.server.name,
-                server_info.server.id,
-                server_info.server.status,
-                ip,
-                private_ip
+                "⚠️  Warning: Could not verify node {} cordon status: {}. Proceeding with deletion...",
+                node_name, e
             );
         }
+
+        match NodeManager::delete_node(&kubeconfig_path, node_name).await {
+            Ok(_) => {
+                info!("✓ Node {} removed from Kubernetes", node_name);
+            }
+            Err(e) => {
+                info!(
+                    "⚠️  Warning: Failed to delete node {} from Kubernetes: {}",
+                    node_name, e
+                );
+            }
+        }
     }
 
-    info!("");
-    info!("Worker Pools:");
-    for pool in &config.workers {
-        let pool_servers =
-            ServerManager::filter_by_role_and_pool(&servers, NodeRole::Worker, Some(&pool.name));
+    info!("✓ Phase 2 complete");
+    phase.finish();
+
+    // PHASE 3: DELETE FROM HETZNER CLOUD
+    info!("Phase 3/3: Deleting servers from Hetzner Cloud...");
+    let phase = progress::phase("Server deletion");
+
+    server_manager.delete_servers(&servers_to_remove).await?;
+    ClusterState::forget_servers(&cli.output, &servers_to_remove)?;
+
+    info!("✓ Phase 3 complete");
+    phase.finish();
+    info!(
+        "✓ All {} nodes removed successfully",
+        servers_to_remove.len()
+    );
+
+    Ok(())
+}
+
+/// Show available upgrades and optionally write them to the config
+async fn check_upgrades(cli: &Cli, update_config: bool) -> Result<()> {
+    let config = load_config(cli)?;
+    let report = upgrade::check::run(&config, &cli.output.join("kubeconfig")).await?;
+    report.print();
+
+    if !update_config {
+        return Ok(());
+    }
+
+    let updates = [
+        ("/talos/version", &report.talos),
+        ("/talos/kubernetes_version", &report.kubernetes),
+        ("/cilium/version", &report.cilium),
+    ];
+    let mut changed = false;
+    for (pointer, component) in updates {
+        let Some(recommended) = &component.recommended else {
+            continue;
+        };
+        // Keep the `v` prefix convention of the existing value
+        let value = if component.configured.starts_with('v') {
+            recommended.clone()
+        } else {
+            recommended.trim_start_matches('v').to_string()
+        };
+        let file = crate::config::edit::set_in_files(&cli.config, pointer, &value)?;
         info!(
-            "  {} - {} node(s) (server type: {})",
-            pool.name,
-            pool_servers.len(),
-            pool.server_type
+            "✓ {} {} -> {} in {}",
+            component.name,
+            component.configured,
+            value,
+            file.display()
         );
-        for server_info in pool_servers {
-            let ip = ServerManager::get_server_ip(&server_info.server)
-                .unwrap_or_else(|| "N/A".to_string());
-            let private_ip = ServerManager::get_server_private_ip(&server_info.server)
-                .unwrap_or_else(|| "N/A".to_string());
-            info!(
-                "    - {} (ID: {}, Status: {}, IP: {}, Private IP: {})",
-                server_info.server.name,
-                server_info.server.id,
-                server_info.server.status,
-                ip,
-                private_ip
-            );
+        changed = true;
+    }
+
+    if changed {
+        info!("Run `oxide upgrade` to roll out the new versions");
+    } else {
+        info!("Config already uses the recommended versions");
+    }
+
+    Ok(())
+}
+
+/// Continue a paused or failed version upgrade
+async fn resume_upgrade(cli: &Cli) -> Result<()> {
+    let config = load_config(cli)?;
+    let state = UpgradeState::load(&cli.output)?
+        .context("No unfinished upgrade found, start one with `oxide upgrade`")?;
+    info!(
+        "Resuming upgrade to Talos {} and Kubernetes {}",
+        state.talos_version, state.kubernetes_version
+    );
+    tools::check_versions(&state.talos_version, &state.kubernetes_version).await?;
+
+    let projects = Projects::from_config(&config)?;
+    projects.require_write_access("upgrade resume").await?;
+    let servers = projects.list_cluster_servers(&config.cluster_name).await?;
+
+    run_version_upgrade(cli, &config, &state, &servers, None).await?;
+    Ok(())
+}
+
+/// Roll out the versions in `state` and record them in the config once done
+async fn run_version_upgrade(
+    cli: &Cli,
+    config: &ClusterConfig,
+    state: &UpgradeState,
+    servers: &[ServerInfo],
+    deadline: Option<chrono::DateTime<chrono::Utc>>,
+) -> Result<Outcome> {
+    let outcome = RollingUpgrade::new(&cli.output, config)
+        .with_deadline(deadline)
+        .run(state, servers)
+        .await?;
+    if outcome == Outcome::WindowClosed {
+        return Ok(outcome);
+    }
+
+    if outcome == Outcome::Paused {
+        info!("Control plane nodes upgraded; verify the cluster, then run `oxide upgrade resume`");
+        return Ok(outcome);
+    }
+
+    if let Some(pool) = &state.pool {
+        info!(
+            "✓ Pool {} runs Talos {}; once it is verified, run `oxide upgrade` without --pool to roll it out cluster-wide",
+            pool, state.talos_version
+        );
+        return Ok(outcome);
+    }
+
+    info!(
+        "✓ Cluster runs Talos {} and Kubernetes {}",
+        state.talos_version, state.kubernetes_version
+    );
+
+    // Keep the config in step so new nodes join with the upgraded versions
+    let fields = [
+        (
+            "/talos/version",
+            &config.talos.version,
+            &state.talos_version,
+        ),
+        (
+            "/talos/kubernetes_version",
+            &config.talos.kubernetes_version,
+            &state.kubernetes_version,
+        ),
+    ];
+    for (pointer, configured, version) in fields {
+        if upgrade::rolling::same_version(configured, version) {
+            continue;
+        }
+        let value = if configured.starts_with('v') {
+            format!("v{}", version.trim_start_matches('v'))
+        } else {
+            version.trim_start_matches('v').to_string()
+        };
+        match crate::config::edit::set_in_files(&cli.config, pointer, &value) {
+            Ok(file) => info!("✓ Set {} to {} in {}", pointer, value, file.display()),
+            Err(e) => warn!("⚠️  {:#}; set it to {} manually", e, value),
         }
     }
 
-    // Try to show Cilium status if kubeconfig exists
-    let kubeconfig_path = cli.output.join("kubeconfig");
-    if kubeconfig_path.exists() {
-        info!("");
-        info!("Cilium Status:");
-        let control_plane_count = config.control_planes.iter().map(|cp| cp.count).sum();
-        let cilium_manager =
-            CiliumManager::new(config.cilium.clone(), kubeconfig_path, control_plane_count);
-        match cilium_manager.get_status().await {
-            Ok(status) => info!("{}", status),
-            Err(e) => info!("Could not get Cilium status: {}", e),
+    Ok(outcome)
+}
+
+/// Run Talos patch upgrades in the maintenance windows of `cron`
+async fn schedule_upgrades(
+    cli: &Cli,
+    cron: &schedule::Cron,
+    window: chrono::Duration,
+    once: bool,
+    dry_run: bool,
+) -> Result<()> {
+    loop {
+        if !once {
+            let now = chrono::Utc::now();
+            let next = cron
+                .next_after(now)
+                .context("The cron expression never matches")?;
+            info!("Next maintenance window starts at {}", next);
+            tokio::time::sleep((next - now).to_std().unwrap_or_default()).await;
+        }
+
+        let started = Instant::now();
+        let deadline = chrono::Utc::now() + window;
+        let result = patch_window(cli, deadline, dry_run).await;
+        // Reloaded, since a completed upgrade writes the new version to it
+        let config = load_config(cli)?;
+        if let (Some(notifications), Ok(Some(_)) | Err(_)) = (&config.notifications, &result) {
+            if notifications.wants("upgrade", result.is_ok()) {
+                let outcome = notify::Outcome {
+                    command: "upgrade",
+                    cluster: &config.cluster_name,
+                    duration: started.elapsed(),
+                    error: result.as_ref().err().map(|e| format!("{:#}", e)),
+                };
+                if let Err(e) = notify::send(notifications, &outcome).await {
+                    warn!("Failed to send notification: {:#}", e);
+                }
+            }
         }
-    }
 
-    Ok(())
+        match result {
+            Ok(_) => {}
+            Err(e) if once => return Err(e),
+            Err(e) => error!("Scheduled upgrade failed: {:#}", e),
+        }
+        if once {
+            return Ok(());
+        }
+    }
 }
 
-/// Initialize example configuration file
-async fn init_config(cli: &Cli) -> Result<()> {
-    if cli.config.exists() {
-        anyhow::bail!(
-            "Configuration file already exists: {}",
-            cli.config.display()
+/// One maintenance window: continue an unfinished upgrade or start one to the
+/// newest Talos patch release
+///
+/// Returns the Talos version rolled out, `None` if there was nothing to do.
+async fn patch_window(
+    cli: &Cli,
+    deadline: chrono::DateTime<chrono::Utc>,
+    dry_run: bool,
+) -> Result<Option<String>> {
+    let config = load_config(cli)?;
+    let state = match UpgradeState::load(&cli.output)? {
+        Some(state) => {
+            info!(
+                "Continuing the upgrade to Talos {} and Kubernetes {}",
+                state.talos_version, state.kubernetes_version
+            );
+            state
+        }
+        None => {
+            let talos_releases = releases::list_releases(releases::TALOS_REPO).await?;
+            let Some(target) = schedule::patch_target(&config.talos.version, &talos_releases)
+            else {
+                info!(
+                    "✓ Talos {} is the newest patch release, nothing to do",
+                    config.talos.version
+                );
+                return Ok(None);
+            };
+            info!(
+                "Talos {} is available, upgrading from {}",
+                target, config.talos.version
+            );
+            UpgradeState {
+                talos_version: target,
+                kubernetes_version: config.talos.kubernetes_version.clone(),
+                pause_after: None,
+                pool: None,
+            }
+        }
+    };
+    if dry_run {
+        println!(
+            "Would upgrade to Talos {} and Kubernetes {}",
+            state.talos_version, state.kubernetes_version
         );
+        return Ok(None);
     }
+    tools::check_versions(&state.talos_version, &state.kubernetes_version).await?;
 
-    let example_config = ClusterConfig::example();
-    let yaml = serde_yaml::to_string(&example_config)?;
-
-    tokio::fs::write(&cli.config, yaml)
-        .await
-        .context("Failed to write configuration file")?;
-
-    info!("Example configuration created: {}", cli.config.display());
-    info!("");
-    info!("Next steps:");
-    info!("  1. Edit the configuration file to match your requirements");
-    info!("  2. Set your Hetzner Cloud API token:");
-    info!("     export HCLOUD_TOKEN=your-token-here");
-    info!("  3. Create the cluster:");
-    info!("     oxide create");
+    let projects = Projects::from_config(&config)?;
+    projects.require_write_access("schedule upgrade").await?;
+    let servers = projects.list_cluster_servers(&config.cluster_name).await?;
 
-    Ok(())
+    if run_version_upgrade(cli, &config, &state, &servers, Some(deadline)).await?
+        == Outcome::WindowClosed
+    {
+        anyhow::bail!(
+            "The maintenance window closed before all nodes ran Talos {}",
+            state.talos_version
+        );
+    }
+    Ok(Some(state.talos_version))
 }
 
-/// Scale cluster nodes
-async fn scale_cluster(
+/// Upgrade cluster
+async fn upgrade_cluster(
     cli: &Cli,
-    node_type: NodeType,
-    target_count: u32,
-    pool_name: Option<String>,
-    force: bool,
-    timeout: u64,
+    talos_version: Option<String>,
+    kubernetes_version: Option<String>,
+    resize_strategy: ResizeStrategy,
+    pause_after: Option<PausePoint>,
+    pool: Option<String>,
 ) -> Result<()> {
-    info!("Starting cluster scaling...");
-
-    let config = ClusterConfig::from_file(&cli.config).context("Failed to load configuration")?;
-
+    let config = load_config(cli)?;
     info!("Cluster name: {}", config.cluster_name);
 
-    let hcloud_token = config.get_hcloud_token()?;
-    let hcloud_client = HetznerCloudClient::new(hcloud_token)?;
+    if let Some(pool) = &pool {
+        if !config
+            .control_planes
+            .iter()
+            .chain(&config.workers)
+            .any(|p| p.name == *pool)
+        {
+            anyhow::bail!("Node pool {} not found in the config", pool);
+        }
+    }
 
-    // Get existing servers
-    let server_manager = ServerManager::new(hcloud_client.clone());
-    let all_servers = server_manager
-        .list_cluster_servers(&config.cluster_name)
-        .await?;
+    if let Some(state) = UpgradeState::load(&cli.output)? {
+        anyhow::bail!(
+            "An upgrade to Talos {} and Kubernetes {} is unfinished; run `oxide upgrade resume`",
+            state.talos_version,
+            state.kubernetes_version
+        );
+    }
 
-    // Determine role and pool configuration
-    let (role, pool_config) = match node_type {
-        NodeType::ControlPlane => {
-            let pool = if let Some(ref name) = pool_name {
-                config
-                    .control_planes
-                    .iter()
-                    .find(|p| &p.name == name)
-                    .ok_or_else(|| anyhow::anyhow!("Control plane pool '{}' not found", name))?
-            } else {
-                config
-                    .control_planes
-                    .first()
-                    .ok_or_else(|| anyhow::anyhow!("No control plane pools configured"))?
-            };
-            (NodeRole::ControlPlane, pool)
-        }
-        NodeType::Worker => {
-            let pool = if let Some(ref name) = pool_name {
-                config
-                    .workers
-                    .iter()
-                    .find(|p| &p.name == name)
-                    .ok_or_else(|| anyhow::anyhow!("Worker pool '{}' not found", name))?
-            } else {
-                config
-                    .workers
-                    .first()
-                    .ok_or_else(|| anyhow::anyhow!("No worker pools configured"))?
-            };
-            (NodeRole::Worker, pool)
-        }
+    // Without explicit versions, roll out whatever the config asks for
+    let state = UpgradeState {
+        talos_version: talos_version.unwrap_or_else(|| config.talos.version.clone()),
+        kubernetes_version: kubernetes_version
+            .unwrap_or_else(|| config.talos.kubernetes_version.clone()),
+        pause_after,
+        pool: pool.clone(),
     };
+    tools::check_versions(&state.talos_version, &state.kubernetes_version).await?;
 
-    // Filter servers by role and pool
-    let pool_servers =
-        ServerManager::filter_by_role_and_pool(&all_servers, role, Some(&pool_config.name));
+    let projects = Projects::from_config(&config)?;
+    projects.require_write_access("upgrade").await?;
+    let servers = projects.list_cluster_servers(&config.cluster_name).await?;
 
-    let current_count = pool_servers.len() as u32;
+    // Server types are applied once the version upgrade has completed
+    if run_version_upgrade(cli, &config, &state, &servers, None).await? == Outcome::Paused {
+        return Ok(());
+    }
 
-    info!(
-        "Current {} count in pool '{}': {}",
-        role, pool_config.name, current_count
-    );
-    info!("Target count: {}", target_count);
+    // Collect nodes whose server type no longer matches their pool
+    let mut pending = Vec::new();
+    let pools = config
+        .control_planes
+        .iter()
+        .map(|p| (NodeRole::ControlPlane, p))
+        .chain(config.workers.iter().map(|p| (NodeRole::Worker, p)))
+        .filter(|(_, p)| pool.as_deref().is_none_or(|name| p.name == name));
+    for (role, pool) in pools {
+        for server_info in ServerManager::filter_by_role_and_pool(&servers, role, Some(&pool.name))
+        {
+            if server_info.server.server_type.name != pool.server_type {
+                pending.push((server_info, pool));
+            }
+        }
+    }
 
-    if current_count == target_count {
-        info!("Cluster is already at the target size");
+    if pending.is_empty() {
+        info!("All servers already match their pool server types, nothing to do");
         return Ok(());
     }
 
-    if target_count > current_count {
-        // Scale up
-        let nodes_to_add = target_count - current_count;
-        info!("Scaling up: adding {} nodes", nodes_to_add);
+    info!(
+        "{} server(s) need a new server type ({:?} strategy)",
+        pending.len(),
+        resize_strategy
+    );
 
-        scale_up(
-            cli,
-            &config,
-            &hcloud_client,
-            &pool_config.name,
-            pool_config,
-            role,
-            nodes_to_add,
-            current_count,
-        )
-        .await?;
-    } else {
-        // Scale down
-        let nodes_to_remove = current_count - target_count;
-        info!("Scaling down: removing {} nodes", nodes_to_remove);
+    // Control planes first, one node at a time, so etcd never loses more
+    // than one member and workers always have a healthy API to rejoin
+    pending.sort_by_key(|(s, _)| (s.role != NodeRole::ControlPlane, s.server.name.clone()));
 
-        if force {
-            info!(
-                "⚠️  FORCE mode enabled: nodes will be removed immediately without graceful drain"
-            );
+    let kubeconfig_path = cli.output.join("kubeconfig");
+    for (server_info, pool) in pending {
+        let phase = progress::phase(&format!("Resize {}", server_info.server.name));
+        let hcloud_client = projects.for_pool(pool);
+        let server_manager = ServerManager::new(hcloud_client.clone())
+            .with_ipv6(config.cilium.enable_ipv6)
+            .with_delete_protection(config.hcloud.delete_protection)
+            .with_graceful_shutdown(graceful_shutdown(&config));
+        match resize_strategy {
+            ResizeStrategy::InPlace => {
+                server_manager
+                    .resize_server(
+                        &server_info.server,
+                        &pool.server_type,
+                        config.timeouts.server_shutdown,
+                    )
+                    .await?;
+                NodeManager::wait_for_node_ready(
+                    &kubeconfig_path,
+                    &server_info.server.name,
+                    config.timeouts.node_ready,
+                )
+                .await?;
+            }
+            ResizeStrategy::Replace => {
+                replace_node(
+                    cli,
+                    &config,
+                    hcloud_client,
+                    &server_manager,
+                    server_info,
+                    pool,
+                )
+                .await?;
+            }
         }
-
-        scale_down(
-            cli,
-            &server_manager,
-            pool_servers,
-            nodes_to_remove,
-            force,
-            timeout,
-        )
-        .await?;
+        phase.finish();
     }
 
-    info!("✓ Cluster scaling completed successfully!");
+    info!("✓ All servers now match their configured server types");
 
     Ok(())
 }
 
-/// Scale up by adding new nodes
-#[allow(clippy::too_many_arguments)]
-async fn scale_up(
+/// Replace a node with a fresh server of the pool's current server type
+async fn replace_node(
     cli: &Cli,
     config: &ClusterConfig,
     hcloud_client: &HetznerCloudClient,
-    pool_name: &str,
-    pool_config: &crate::config::NodeConfig,
-    role: NodeRole,
-    nodes_to_add: u32,
-    current_count: u32,
+    server_manager: &ServerManager,
+    server_info: ServerInfo,
+    pool: &crate::config::NodeConfig,
 ) -> Result<()> {
-    // Get network
-    let network_manager = NetworkManager::new(hcloud_client.clone());
-    let network = network_manager
-        .get_or_find_network(&config.cluster_name)
-        .await?;
+    let node_name = server_info.server.name.clone();
+    let role = server_info.role;
+    let location = ServerManager::location(&server_info.server).to_string();
+    info!(
+        "Replacing {} ({} -> {})",
+        node_name, server_info.server.server_type.name, pool.server_type
+    );
 
-    // Get SSH key
-    let ssh_key_manager = SSHKeyManager::new(hcloud_client.clone());
-    let ssh_key = ssh_key_manager
+    // Read everything needed for the new server before tearing down the old one
+    let user_data = read_node_user_data(cli, role, pool).await?;
+    let networks = NetworkManager::new(hcloud_client.clone())
+        .find_networks(&config.cluster_name, &config.hcloud)
+        .await?;
+    let (ssh_key, _) = SSHKeyManager::new(hcloud_client.clone())
         .ensure_ssh_key(&config.cluster_name)
-        .await?
-        .0;
-
-    // Get firewall
+        .await?;
     let firewall_manager = FirewallManager::new(hcloud_client.clone());
     let firewall = firewall_manager
         .get_cluster_firewall(&config.cluster_name)
         .await?;
 
-    // Read existing Talos configuration files (cluster must already exist)
-    let config_path = if role == NodeRole::ControlPlane {
-        cli.output.join("controlplane.yaml")
-    } else {
-        cli.output.join("worker.yaml")
-    };
+    scale_down(
+        cli,
+        server_manager,
+        vec![server_info],
+        1,
+        false,
+        &config.timeouts,
+    )
+    .await?;
+
+    let new_server = server_manager
+        .create_single_node(
+            &config.cluster_name,
+            &node_name,
+            &pool.server_type,
+            &location,
+            networks.for_location(&location).id,
+            role,
+            &config.talos.version,
+            config.snapshot_for_pool(pool),
+            Some(ssh_key.id),
+            Some(user_data),
+            pool.labels.clone(),
+            pool.public_ip,
+        )
+        .await?;
+    ClusterState::record_servers(
+        &cli.output,
+        &config.cluster_name,
+        std::slice::from_ref(&new_server),
+    )?;
+
+    if let Some(template) = &pool.rdns_template {
+        server_manager
+            .configure_rdns(
+                &new_server.server,
+                template,
+                &config.cluster_name,
+                &pool.name,
+            )
+            .await?;
+    }
 
-    if !config_path.exists() {
-        anyhow::bail!(
-            "Talos configuration file not found: {}\n\
-            Scaling requires an existing cluster. Please run 'oxide create' first.",
-            config_path.display()
-        );
+    if let (Some(fw), true) = (firewall, pool.public_ip) {
+        firewall_manager
+            .apply_to_servers(fw.id, vec![new_server.server.id])
+            .await?;
     }
 
-    info!(
-        "Using existing {} configuration from {}",
-        role,
-        config_path.display()
-    );
+    TalosClient::new(cli.output.join("talosconfig"))
+        .wait_for_nodes_booted(
+            std::slice::from_ref(&new_server),
+            config.timeouts.talos_boot,
+        )
+        .await?;
+    let kubeconfig_path = cli.output.join("kubeconfig");
+    NodeManager::wait_for_node_ready(&kubeconfig_path, &node_name, config.timeouts.node_ready)
+        .await?;
+    NodeManager::label_topology(
+        &kubeconfig_path,
+        &node_name,
+        &location,
+        &config.hcloud.network.zone,
+    )
+    .await?;
+
+    info!("✓ Node {} replaced", node_name);
+    Ok(())
+}
 
-    let user_data = tokio::fs::read_to_string(&config_path)
-        .await
-        .context(format!(
-            "Failed to read config from {}",
-            config_path.display()
-        ))?;
+/// Open an SSH session on a node, optionally through the rescue system
+async fn ssh_node(
+    cli: &Cli,
+    node: &str,
+    rescue: bool,
+    no_reboot: bool,
+    command: &[String],
+) -> Result<()> {
+    let config = load_config(cli)?;
+    if command.is_empty() && prompt::is_disabled() {
+        anyhow::bail!(Failure::config(
+            "oxide ssh opens an interactive shell; pass a command to run with --non-interactive"
+        ));
+    }
+
+    let key_path = cli.output.join("id_ed25519");
+    if !key_path.exists() {
+        anyhow::bail!(
+            "SSH private key not found at {}. It is only saved when the cluster SSH key is created",
+            key_path.display()
+        );
+    }
 
+    let hcloud_client = HetznerCloudClient::from_config(&config)?;
     let server_manager = ServerManager::new(hcloud_client.clone());
+    let servers = server_manager
+        .list_cluster_servers(&config.cluster_name)
+        .await?;
+    let Some(server_info) = servers.iter().find(|s| s.server.name == node) else {
+        let names: Vec<&str> = servers.iter().map(|s| s.server.name.as_str()).collect();
+        anyhow::bail!(
+            "Node {} not found in cluster {}. Available nodes: {}",
+            node,
+            config.cluster_name,
+            names.join(", ")
+        );
+    };
+    let server = &server_info.server;
+    let ip = ServerManager::get_server_ip(server)
+        .context(format!("Node {} has no public IPv4 address", node))?;
+
+    if rescue {
+        let ssh_key = SSHKeyManager::new(hcloud_client.clone())
+            .find_cluster_ssh_key(&config.cluster_name)
+            .await?
+            .context("Cluster SSH key not found in Hetzner Cloud")?;
+
+        let result = server_manager
+            .boot_rescue(server, ssh_key.id, config.timeouts.rescue_boot)
+            .await;
+        audit::record("server.rescue", node, &[("ip", ip.as_str())], &result);
+        result?;
+    } else if !ServerManager::ssh_reachable(&ip).await {
+        anyhow::bail!(
+            "Port 22 on {} ({}) is not reachable. Talos nodes run no SSH daemon; \
+             use --rescue to boot the node into the rescue system",
+            node,
+            ip
+        );
+    }
 
-    // Create new nodes
-    let mut new_server_ids = Vec::new();
-    for i in 0..nodes_to_add {
-        let node_index = current_count + i + 1;
-        let node_name = format!("{}-{}-{}", config.cluster_name, pool_name, node_index);
+    let mut ssh = tokio::process::Command::new("ssh");
+    ssh.arg("-i")
+        .arg(&key_path)
+        .args(["-o", "IdentitiesOnly=yes"]);
+    if rescue {
+        // The rescue system gets a fresh host key on every boot
+        ssh.args([
+            "-o",
+            "StrictHostKeyChecking=no",
+            "-o",
+            &format!("UserKnownHostsFile={}", platform::NULL_DEVICE),
+        ]);
+    }
+    ssh.arg(format!("root@{}", ip)).args(command);
 
-        let server_info = server_manager
-            .create_single_node(
-                &config.cluster_name,
-                &node_name,
-                &pool_config.server_type,
-                &config.hcloud.location,
-                network.id,
-                role,
-                &config.talos.version,
-                config.talos.hcloud_snapshot_id.as_deref(),
-                Some(ssh_key.id),
-                Some(user_data.clone()),
-                pool_config.labels.clone(),
-            )
-            .await?;
+    let status = ssh.status().await.context("Failed to run ssh")?;
 
-        new_server_ids.push(server_info.server.id);
-        info!("✓ Node {} created successfully", node_name);
+    if rescue && !no_reboot {
+        info!("Rebooting {} back into Talos", node);
+        let result = server_manager.reboot_server(server).await;
+        audit::record("server.reboot", node, &[], &result);
+        result?;
+        info!("✓ {} is booting from disk", node);
+    } else if rescue {
+        info!(
+            "⚠️  {} stays in the rescue system until it is rebooted",
+            node
+        );
     }
 
-    // Wait for new nodes to become Ready
-    info!("Waiting for new nodes to become Ready...");
+    if !status.success() {
+        anyhow::bail!("ssh exited with {}", status);
+    }
+    Ok(())
+}
+
+/// Join or remove Robot servers
+/// Print the cluster nodes matching `filter`
+///
+/// Only the listing goes to stdout so json and yaml output can be piped.
+async fn list_nodes(cli: &Cli, filter: &inventory::NodeFilter, format: ListFormat) -> Result<()> {
+    let config = load_config(cli)?;
+    let servers = Projects::from_config(&config)?
+        .list_cluster_servers(&config.cluster_name)
+        .await?;
+
+    // Ready state and versions are best effort; the servers are listed anyway
     let kubeconfig_path = cli.output.join("kubeconfig");
+    let statuses = if kubeconfig_path.exists() {
+        NodeManager::node_statuses(&kubeconfig_path)
+            .await
+            .unwrap_or_else(|e| {
+                warn!("⚠️  Could not get node status from Kubernetes: {}", e);
+                Default::default()
+            })
+    } else {
+        Default::default()
+    };
 
-    for i in 0..nodes_to_add {
-        let node_index = current_count + i + 1;
-        let node_name = format!("{}-{}-{}", config.cluster_name, pool_name, node_index);
-        NodeManager::wait_for_node_ready(&kubeconfig_path, &node_name, 300).await?;
+    let rows = inventory::rows(&config, &servers, &statuses, filter);
+    match format {
+        ListFormat::Table | ListFormat::Wide => print!(
+            "{}",
+            inventory::render_table(&rows, format == ListFormat::Wide, chrono::Utc::now())
+        ),
+        ListFormat::Json => println!("{}", serde_json::to_string_pretty(&rows)?),
+        ListFormat::Yaml => print!("{}", serde_yaml::to_string(&rows)?),
     }
+    Ok(())
+}
 
-    // Apply firewall to new servers
-    if let Some(fw) = firewall {
-        firewall_manager
-            .apply_to_servers(fw.id, new_server_ids)
-            .await?;
+/// Enter or exit maintenance on a node
+async fn node_maintenance(cli: &Cli, command: &MaintenanceCommand) -> Result<()> {
+    let config = load_config(cli)?;
+    let kubeconfig_path = cli.output.join("kubeconfig");
+    if !kubeconfig_path.exists() {
+        anyhow::bail!(
+            "Kubeconfig not found at {}. Please create the cluster first.",
+            kubeconfig_path.display()
+        );
     }
 
-    info!("All new nodes created and configured");
+    match command {
+        MaintenanceCommand::Enter {
+            node,
+            reason,
+            duration,
+        } => {
+            maintenance::enter(
+                &kubeconfig_path,
+                node,
+                reason,
+                *duration,
+                config.timeouts.node_drain,
+                config.alertmanager.as_ref(),
+            )
+            .await
+        }
+        MaintenanceCommand::Exit { node } => {
+            maintenance::exit(
+                &kubeconfig_path,
+                node,
+                config.timeouts.node_ready,
+                config.alertmanager.as_ref(),
+            )
+            .await
+        }
+    }
+}
 
+/// Print the Helm charts oxide installed and their health
+async fn show_addons_status(cli: &Cli, format: ListFormat) -> Result<()> {
+    let statuses = addons::status::collect(&cli.output).await?;
+    match format {
+        ListFormat::Table | ListFormat::Wide => {
+            if statuses.is_empty() {
+                info!(
+                    "No Helm charts installed by oxide are recorded in {}",
+                    cli.output.display()
+                );
+                return Ok(());
+            }
+            print!(
+                "{}",
+                addons::status::render_table(&statuses, chrono::Utc::now())
+            )
+        }
+        ListFormat::Json => println!("{}", serde_json::to_string_pretty(&statuses)?),
+        ListFormat::Yaml => print!("{}", serde_yaml::to_string(&statuses)?),
+    }
+    if statuses.iter().any(|status| !status.healthy) {
+        anyhow::bail!("Some charts are not healthy");
+    }
     Ok(())
 }
 
-/// Scale down by removing nodes with parallel reset and validation
-async fn scale_down(
-    cli: &Cli,
-    server_manager: &ServerManager,
-    mut pool_servers: Vec<ServerInfo>,
-    nodes_to_remove: u32,
-    force: bool,
-    timeout: u64,
-) -> Result<()> {
-    // Sort servers by index (highest first) to remove newest nodes first
-    pool_servers.sort_by(|a, b| b.server.name.cmp(&a.server.name));
+/// Write the Helm values and manifests of Cilium and the addons to `out`
+async fn render_charts(cli: &Cli, out: &Path) -> Result<()> {
+    let config = load_config(cli)?;
+    let kubeconfig_path = cli.output.join("kubeconfig");
+    let written = render::render(&config, &kubeconfig_path, out).await?;
+    for path in &written {
+        info!("  {}", path.display());
+    }
+    info!("✓ Rendered {} files to {}", written.len(), out.display());
+    Ok(())
+}
 
-    let servers_to_remove: Vec<ServerInfo> = pool_servers
-        .into_iter()
-        .take(nodes_to_remove as usize)
-        .collect();
+/// Print or write the cluster as manifests of `format`
+///
+/// The control plane endpoint comes from the kubeconfig of a created
+/// cluster, so the exported cluster keeps the API address.
+fn export_cluster(cli: &Cli, format: ExportFormat, out: Option<&Path>) -> Result<()> {
+    let config = load_config(cli)?;
+    let kubeconfig_path = cli.output.join("kubeconfig");
+    let endpoint = if kubeconfig_path.exists() {
+        Some(kubeconfig::server(&kubeconfig_path)?)
+    } else {
+        config.talos.cluster_endpoint.clone()
+    };
 
-    if servers_to_remove.is_empty() {
-        info!("No servers to remove");
-        return Ok(());
+    let manifests = match format {
+        ExportFormat::Capi => export::capi(&config, endpoint.as_deref())?,
+    };
+    match out {
+        Some(out) => {
+            std::fs::write(out, manifests).context(format!("Failed to write {}", out.display()))?;
+            info!("✓ Exported {} to {}", config.cluster_name, out.display());
+        }
+        None => print!("{}", manifests),
     }
+    Ok(())
+}
 
-    info!("Gracefully removing {} node(s)...", servers_to_remove.len());
+/// Download the charts, CRDs and image list of the cluster into `out`
+async fn create_bundle(cli: &Cli, out: &Path, upload: bool) -> Result<()> {
+    let config = load_config(cli)?;
+    // Fails before the download when the bucket is not set up
+    let storage = upload
+        .then(|| ObjectStorage::from_config(&config))
+        .transpose()?;
+    let kubeconfig_path = cli.output.join("kubeconfig");
+    bundle::create(&config, &kubeconfig_path, out).await?;
+    if let Some(storage) = storage {
+        storage.put_dir("bundle", out).await?;
+    }
+    if config.talos.registry_mirror.is_none() {
+        info!("Set talos.registry_mirror before `oxide create --bundle` so nodes pull from the mirror");
+    }
+    Ok(())
+}
 
-    // Initialize Talos client
-    let talosconfig_path = cli.output.join("talosconfig");
-    if !talosconfig_path.exists() {
+/// Uninstall an addon chart recorded in the cluster state
+async fn uninstall_addon(cli: &Cli, name: &str) -> Result<()> {
+    if name == "cilium" {
+        anyhow::bail!("Cilium is the cluster network; use `oxide cni reinstall` to recover it");
+    }
+    let releases = ClusterState::load(&cli.output)?
+        .map(|state| state.releases)
+        .unwrap_or_default();
+    let Some(record) = releases.get(name) else {
+        let installed: Vec<&str> = releases
+            .keys()
+            .map(String::as_str)
+            .filter(|release| *release != "cilium")
+            .collect();
         anyhow::bail!(
-            "Talosconfig not found at {}. Cannot perform graceful node removal.",
-            talosconfig_path.display()
+            "{} is not an addon installed by oxide (installed: {})",
+            name,
+            if installed.is_empty() {
+                "none".to_string()
+            } else {
+                installed.join(", ")
+            }
         );
-    }
+    };
 
-    // Kubeconfig for kubectl operations
+    let kubeconfig_path = cli.output.join("kubeconfig");
+    helm::uninstall(&kubeconfig_path, record.mode, name, &record.namespace).await?;
+    info!(
+        "Disable {} in the config, or the next `oxide create` installs it again",
+        name
+    );
+    Ok(())
+}
+
+/// Uninstall Cilium and install it again from the config
+async fn reinstall_cni(cli: &Cli) -> Result<()> {
+    let config = load_config(cli)?;
     let kubeconfig_path = cli.output.join("kubeconfig");
     if !kubeconfig_path.exists() {
         anyhow::bail!(
-            "Kubeconfig not found at {}. Cannot perform graceful node removal.",
+            "Kubeconfig not found at {}. Please create the cluster first.",
             kubeconfig_path.display()
         );
     }
+    CiliumManager::check_helm_installed().await?;
+
+    // The chart may have been installed in a mode other than the configured one
+    let mode = ClusterState::load(&cli.output)?
+        .and_then(|state| state.releases.get("cilium").map(|record| record.mode))
+        .unwrap_or(config.helm.mode);
+    // The new pods start without waiting for image pulls
+    AddonManager::new(&config, kubeconfig_path.clone())
+        .prepull_images()
+        .await?;
 
-    // PRE-FLIGHT VALIDATION
-    let node_names: Vec<String> = servers_to_remove
-        .iter()
-        .map(|s| s.server.name.clone())
-        .collect();
-
-    info!("Running pre-flight validation checks...");
-
-    // Validate etcd quorum won't be broken
-    NodeManager::validate_etcd_quorum(&kubeconfig_path, &node_names).await?;
+    warn!("⚠️  Pod networking is down until Cilium is ready again");
+    helm::uninstall(&kubeconfig_path, mode, "cilium", "kube-system").await?;
 
-    info!("✓ Pre-flight validation passed");
+    let control_plane_count = config.control_planes.iter().map(|cp| cp.count).sum();
+    let cilium_manager = CiliumManager::new(
+        config.cilium.clone(),
+        kubeconfig_path.clone(),
+        control_plane_count,
+    )
+    .with_helm_mode(config.helm.mode)
+    .with_kubespan(config.multi_region())
+    .with_cloud_load_balancers(config.cloud_load_balancers())
+    .with_istio(config.addons.istio.enabled);
+    cilium_manager.install().await?;
+    cilium_manager.wait_for_ready(config.timeouts.cni).await?;
 
-    // PHASE 1: PARALLEL NODE RESET
-    info!("Phase 1/3: Resetting nodes in parallel...");
+    info!("✓ Cilium {} reinstalled", config.cilium.version);
+    Ok(())
+}
 
-    let mut reset_tasks = Vec::new();
+/// Print resource usage of the cluster nodes matching `filter`
+async fn show_top(
+    cli: &Cli,
+    filter: &inventory::NodeFilter,
+    window_minutes: u32,
+    json: bool,
+) -> Result<()> {
+    let config = load_config(cli)?;
+    let hcloud_client = HetznerCloudClient::from_config(&config)?;
+    let servers = ServerManager::new(hcloud_client.clone())
+        .list_cluster_servers(&config.cluster_name)
+        .await?;
+    let nodes = inventory::rows(&config, &servers, &Default::default(), filter);
+
+    let range = MetricsRange::last(chrono::Duration::minutes(window_minutes.max(1).into()));
+    let types = [MetricType::Cpu, MetricType::Disk, MetricType::Network];
+    let metric_tasks = nodes.iter().filter_map(|node| node.server_id).map(|id| {
+        let client = hcloud_client.clone();
+        async move { (id, client.get_server_metrics(id, &types, range).await) }
+    });
+    let mut metrics = std::collections::HashMap::new();
+    for (id, result) in futures::future::join_all(metric_tasks).await {
+        match result {
+            Ok(server_metrics) => {
+                metrics.insert(id, server_metrics);
+            }
+            Err(e) => warn!("⚠️  Could not get metrics of server {}: {}", id, e),
+        }
+    }
 
-    for server_info in &servers_to_remove {
-        let node_name = server_info.server.name.clone();
-        let node_ip = ServerManager::get_server_ip(&server_info.server);
-        let talos_client_clone = TalosClient::new(talosconfig_path.clone());
-        let kubeconfig_path_clone = kubeconfig_path.clone();
+    let kubeconfig_path = cli.output.join("kubeconfig");
+    let usage = if kubeconfig_path.exists() {
+        NodeManager::top_nodes(&kubeconfig_path)
+            .await
+            .unwrap_or_else(|e| {
+                warn!("⚠️  {:#}", e);
+                Default::default()
+            })
+    } else {
+        Default::default()
+    };
 
-        let task = tokio::spawn(async move {
-            if let Some(ip) = node_ip {
-                info!("Resetting node {} ({})...", node_name, ip);
+    let rows = inventory::top::rows(&nodes, &metrics, &usage);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&rows)?);
+    } else {
+        print!("{}", inventory::top::render_table(&rows));
+    }
+    Ok(())
+}
 
-                // Proceed with reset (talosctl will handle connectivity)
-                let reset_result = talos_client_clone
-                    .reset_node_with_timeout(&ip, &node_name, timeout, force, 2)
-                    .await;
+/// Print merged Kubernetes events and hcloud actions, optionally following them
+async fn show_events(
+    cli: &Cli,
+    warnings_only: bool,
+    since_minutes: u32,
+    follow: bool,
+    json: bool,
+) -> Result<()> {
+    const FOLLOW_INTERVAL: Duration = Duration::from_secs(10);
 
-                match reset_result {
-                    Ok(_) => {
-                        info!("✓ Node {} reset completed", node_name);
-                    }
-                    Err(e) => {
-                        // Check if this is an expected error (node powered down during reset)
-                        let err_msg = e.to_string();
-                        if err_msg.contains("connection closed")
-                            || err_msg.contains("broken pipe")
-                            || err_msg.contains("reset by peer")
-                        {
-                            info!("✓ Node {} powered down during reset (expected)", node_name);
-                        } else {
-                            return Err(e);
-                        }
-                    }
-                }
+    let config = load_config(cli)?;
+    let hcloud_client = HetznerCloudClient::from_config(&config)?;
+    let server_manager = ServerManager::new(hcloud_client.clone());
+    let kubeconfig_path = cli.output.join("kubeconfig");
+    let since = chrono::Utc::now() - chrono::Duration::minutes(since_minutes.into());
 
-                // Monitor drain progress if not in force mode
-                if !force {
-                    info!("Monitoring drain progress for {}...", node_name);
-                    if let Err(e) = NodeManager::monitor_drain_progress(
-                        &kubeconfig_path_clone,
-                        &node_name,
-                        timeout,
-                    )
-                    .await
-                    {
-                        info!(
-                            "Warning: Failed to monitor drain progress for {}: {}",
-                            node_name, e
-                        );
-                    }
-                }
+    let mut seen = std::collections::HashSet::new();
+    loop {
+        // Servers are listed on every poll so nodes added by a scale show up
+        let servers = server_manager
+            .list_cluster_servers(&config.cluster_name)
+            .await?;
+        let events = events::collect(
+            &hcloud_client,
+            &servers,
+            &kubeconfig_path,
+            since,
+            warnings_only,
+        )
+        .await;
 
-                Ok::<String, anyhow::Error>(node_name)
+        for event in events {
+            if !seen.insert(event.clone()) {
+                continue;
+            }
+            if json {
+                println!("{}", serde_json::to_string(&event)?);
             } else {
+                info!("{}", event.summary());
+            }
+        }
+
+        if !follow {
+            if seen.is_empty() {
                 info!(
-                    "⚠️  Warning: Node {} has no public IP, skipping reset",
-                    node_name
+                    "No {}events in the last {} minutes",
+                    if warnings_only { "warning " } else { "" },
+                    since_minutes
                 );
-                Ok::<String, anyhow::Error>(node_name)
             }
-        });
-
-        reset_tasks.push(task);
+            return Ok(());
+        }
+        tokio::time::sleep(FOLLOW_INTERVAL).await;
     }
+}
 
-    // Wait for all resets to complete
-    info!("Waiting for all node resets to complete...");
-    let reset_results = futures::future::join_all(reset_tasks).await;
+async fn robot_command(cli: &Cli, command: &RobotCommand) -> Result<()> {
+    let config = load_config(cli)?;
+    let robot_config = robot::robot_config(&config)?;
 
-    let mut successfully_reset = Vec::new();
-    let mut failed_resets = Vec::new();
+    match command {
+        RobotCommand::Join { server } => {
+            let servers = robot::select(robot_config, server.as_deref())?;
+            info!("Joining {} Robot server(s)...", servers.len());
+            for server in servers {
+                robot::join(&config, &cli.output, server).await?;
+            }
+            Ok(())
+        }
+        RobotCommand::Remove { server, force } => {
+            let servers = robot::select(robot_config, Some(server.as_str()))?;
+            robot::remove(&config, &cli.output, servers[0], *force).await
+        }
+    }
+}
 
-    for (idx, result) in reset_results.into_iter().enumerate() {
-        match result {
-            Ok(Ok(node_name)) => {
-                successfully_reset.push(node_name);
+/// Push, pull or list the shared output files of the cluster
+async fn artifacts_command(cli: &Cli, command: &ArtifactsCommand) -> Result<()> {
+    let config = load_config(cli)?;
+    let storage = ObjectStorage::from_config(&config)?;
+    // Plain file names only, so a pull cannot write outside the output dir
+    let check = |name: &str| -> Result<()> {
+        if name.is_empty() || name.contains(['/', '\\']) || name == "." || name == ".." {
+            anyhow::bail!("'{}' is not a file name in the output directory", name);
+        }
+        Ok(())
+    };
+
+    match command {
+        ArtifactsCommand::Push { names } => {
+            for name in names {
+                check(name)?;
+                let path = cli.output.join(name);
+                let content =
+                    std::fs::read(&path).context(format!("Failed to read {}", path.display()))?;
+                storage.put(name, content).await?;
+                info!("✓ Pushed {} to {}", path.display(), storage.location(name));
             }
-            Ok(Err(e)) => {
-                let node_name = &servers_to_remove[idx].server.name;
-                failed_resets.push(format!("{}: {}", node_name, e));
+        }
+        ArtifactsCommand::Pull { names } => {
+            std::fs::create_dir_all(&cli.output)
+                .context(format!("Failed to create {}", cli.output.display()))?;
+            for name in names {
+                check(name)?;
+                let content = storage
+                    .get(name)
+                    .await?
+                    .context(format!("{} does not exist", storage.location(name)))?;
+                let path = cli.output.join(name);
+                // Kubeconfig and talosconfig hold client certificates
+                platform::write_private(&path, content)?;
+                info!("✓ Pulled {} to {}", storage.location(name), path.display());
             }
-            Err(e) => {
-                let node_name = &servers_to_remove[idx].server.name;
-                failed_resets.push(format!("{}: task join error: {}", node_name, e));
+        }
+        ArtifactsCommand::List => {
+            let names = storage.list().await?;
+            if names.is_empty() {
+                println!("No artifacts at {}", storage.location(""));
+            }
+            for name in names {
+                println!("{}", name);
             }
         }
     }
+    Ok(())
+}
 
-    if !failed_resets.is_empty() {
-        anyhow::bail!(
-            "Failed to reset {} node(s):\n  {}",
-            failed_resets.len(),
-            failed_resets.join("\n  ")
+/// Download the tools matching the cluster config into the cache
+async fn install_tools(cli: &Cli) -> Result<()> {
+    let config = load_config(cli)?;
+    tools::install(&config).await?;
+
+    for (tool, version) in tools::wanted(&config) {
+        info!(
+            "{} {}: {}",
+            tool.name(),
+            version,
+            tools::binary_path(tool, &version)?.display()
         );
     }
+    info!("Installed tools are used automatically for this cluster config");
+    Ok(())
+}
 
-    info!(
-        "✓ Phase 1 complete: {} nodes reset successfully",
-        successfully_reset.len()
-    );
+/// Show recorded mutating operations, most recent last
+fn show_history(cli: &Cli, limit: usize, failed_only: bool, json: bool) -> Result<()> {
+    let path = cli.output.join(audit::AUDIT_LOG_FILE);
+    let entries: Vec<_> = audit::read_entries(&path)?
+        .into_iter()
+        .filter(|e| !failed_only || !e.success)
+        .collect();
 
-    // PHASE 2: DELETE FROM KUBERNETES
-    info!("Phase 2/3: Removing nodes from Kubernetes...");
+    if entries.is_empty() {
+        info!("No recorded operations in {}", path.display());
+        return Ok(());
+    }
 
-    for node_name in &successfully_reset {
-        // Wait for node to be cordoned and NotReady before deleting
-        if let Err(e) = NodeManager::wait_for_node_cordoned(&kubeconfig_path, node_name, 120).await
-        {
-            info!(
-                "⚠️  Warning: Could not verify node {} cordon status: {}. Proceeding with deletion...",
-                node_name, e
-            );
+    let skip = entries.len().saturating_sub(limit);
+    for entry in &entries[skip..] {
+        if json {
+            println!("{}", serde_json::to_string(entry)?);
+        } else {
+            info!("{}", entry.summary());
         }
+    }
 
-        match NodeManager::delete_node(&kubeconfig_path, node_name).await {
-            Ok(_) => {
-                info!("✓ Node {} removed from Kubernetes", node_name);
-            }
-            Err(e) => {
-                info!(
-                    "⚠️  Warning: Failed to delete node {} from Kubernetes: {}",
-                    node_name, e
-                );
-            }
-        }
+    Ok(())
+}
+
+/// Print or merge the admin kubeconfig, or one for a scoped ServiceAccount
+///
+/// Printing logs nothing so the output can be redirected to a file.
+async fn export_kubeconfig(
+    cli: &Cli,
+    merge: bool,
+    context: Option<&str>,
+    access: Option<ServiceAccountAccess>,
+) -> Result<()> {
+    let config = load_config(cli)?;
+    let kubeconfig_path = cli.output.join("kubeconfig");
+    if !kubeconfig_path.exists() {
+        anyhow::bail!(
+            "No kubeconfig at {}, create the cluster first",
+            kubeconfig_path.display()
+        );
     }
 
-    info!("✓ Phase 2 complete");
+    let content = match &access {
+        Some(access) => {
+            access
+                .kubeconfig(&kubeconfig_path, &config.cluster_name)
+                .await?
+        }
+        None => std::fs::read_to_string(&kubeconfig_path)
+            .context(format!("Failed to read {}", kubeconfig_path.display()))?,
+    };
 
-    // PHASE 3: DELETE FROM HETZNER CLOUD
-    info!("Phase 3/3: Deleting servers from Hetzner Cloud...");
+    if !merge {
+        print!("{}", content);
+        return Ok(());
+    }
 
-    let server_ids_to_delete: Vec<u64> = servers_to_remove.iter().map(|s| s.server.id).collect();
+    let name = match (context, &access) {
+        (Some(context), _) => context.to_string(),
+        (None, Some(access)) => format!("{}-{}", config.cluster_name, access.name),
+        (None, None) => config.cluster_name.clone(),
+    };
+    let target = kubeconfig::default_path()?;
+    let existing = match std::fs::read_to_string(&target) {
+        Ok(existing) => Some(existing),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e).context(format!("Failed to read {}", target.display())),
+    };
+    let merged = kubeconfig::merge(existing.as_deref(), &content, &name)?;
+    kubeconfig::write(&target, &merged)?;
 
-    server_manager.delete_servers(server_ids_to_delete).await?;
+    info!(
+        "✓ Merged context {} into {} and made it current",
+        name,
+        target.display()
+    );
+    Ok(())
+}
 
-    info!("✓ Phase 3 complete");
+/// Print a read-only sign-in token and port-forward to the dashboard
+///
+/// The forward listens on 127.0.0.1 only, so the dashboard never becomes
+/// reachable from other machines. Only the token goes to stdout.
+async fn open_dashboard(cli: &Cli, port: u16, duration: &str) -> Result<()> {
+    let config = load_config(cli)?;
+    if !config.addons.dashboard.enabled {
+        anyhow::bail!(Failure::config(
+            "The dashboard addon is not installed; set addons.dashboard.enabled and run oxide create"
+        ));
+    }
+    let kubeconfig_path = cli.output.join("kubeconfig");
+    if !kubeconfig_path.exists() {
+        anyhow::bail!(
+            "No kubeconfig at {}, create the cluster first",
+            kubeconfig_path.display()
+        );
+    }
+
+    let access = ServiceAccountAccess {
+        name: addons::dashboard::SERVICE_ACCOUNT.to_string(),
+        role: addons::dashboard::ROLE.to_string(),
+        namespace: None,
+        duration: duration.to_string(),
+    };
+    let token = access.token(&kubeconfig_path).await?;
     info!(
-        "✓ All {} nodes removed successfully",
-        servers_to_remove.len()
+        "Sign in at http://127.0.0.1:{} with this token, valid for {}:",
+        port, duration
     );
+    println!("{}", token);
+
+    let status = tokio::process::Command::new("kubectl")
+        .args([
+            "port-forward",
+            "-n",
+            addons::dashboard::NAMESPACE,
+            addons::dashboard::SERVICE,
+            "--address",
+            "127.0.0.1",
+            &format!("{}:{}", port, addons::dashboard::SERVICE_PORT),
+        ])
+        .env("KUBECONFIG", &kubeconfig_path)
+        .status()
+        .await
+        .context("Failed to run kubectl port-forward")?;
+    if !status.success() {
+        anyhow::bail!("kubectl port-forward exited with {}", status);
+    }
+    Ok(())
+}
 
+/// Print the script that registers oxide's dynamic completions with `shell`
+///
+/// Completions call back into oxide, so pool names are read from the config.
+fn print_completions(shell: clap_complete::Shell) -> Result<()> {
+    let name = shell.to_string();
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(&name)
+        .with_context(|| format!("Completions are not supported for {}", name))?;
+
+    completer.write_registration(
+        "COMPLETE",
+        "oxide",
+        "oxide",
+        "oxide",
+        &mut std::io::stdout(),
+    )?;
     Ok(())
 }
 
-/// Upgrade cluster
-async fn upgrade_cluster(
-    _cli: &Cli,
-    _talos_version: Option<String>,
-    _kubernetes_version: Option<String>,
-) -> Result<()> {
-    anyhow::bail!("Cluster upgrade is not yet implemented");
+/// Print the man page to stdout
+fn print_man_page() -> Result<()> {
+    clap_mangen::Man::new(Cli::command()).render(&mut std::io::stdout())?;
+    Ok(())
 }
 
 /// Deploy nginx with Gateway API
@@ -981,14 +4818,17 @@ async fn deploy_nginx(cli: &Cli) -> Result<()> {
     if !nginx_deployment_path.exists() {
         anyhow::bail!("nginx-deployment.yaml not found in current directory");
     }
-    ResourceManager::apply_manifest(&kubeconfig_path, nginx_deployment_path).await?;
+    let timeout = TimeoutsConfig::default()
+        .with_override(cli.timeout)
+        .addon_rollout;
+    ResourceManager::apply_manifest(&kubeconfig_path, nginx_deployment_path, timeout).await?;
 
     // Apply Gateway and HTTPRoute
     let nginx_gateway_path = std::path::Path::new("nginx-gateway.yaml");
     if !nginx_gateway_path.exists() {
         anyhow::bail!("nginx-gateway.yaml not found in current directory");
     }
-    ResourceManager::apply_manifest(&kubeconfig_path, nginx_gateway_path).await?;
+    ResourceManager::apply_manifest(&kubeconfig_path, nginx_gateway_path, timeout).await?;
 
     info!("✓ nginx deployed successfully with Gateway API!");
     info!("");
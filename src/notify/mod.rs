@@ -0,0 +1,114 @@
+/// Webhook notifications on operation completion
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::config::{NotificationsConfig, WebhookFormat};
+
+/// How long to wait for the webhook before giving up
+const WEBHOOK_TIMEOUT_SECS: u64 = 10;
+
+/// Result of a finished command
+pub struct Outcome<'a> {
+    pub command: &'a str,
+    pub cluster: &'a str,
+    pub duration: Duration,
+    /// Error message, `None` if the command succeeded
+    pub error: Option<String>,
+}
+
+impl Outcome<'_> {
+    /// One-line human-readable summary
+    pub fn summary(&self) -> String {
+        let elapsed = format_duration(self.duration);
+        match &self.error {
+            None => format!(
+                "✅ oxide {} succeeded for cluster {} ({})",
+                self.command, self.cluster, elapsed
+            ),
+            Some(error) => format!(
+                "❌ oxide {} failed for cluster {} after {}: {}",
+                self.command, self.cluster, elapsed, error
+            ),
+        }
+    }
+}
+
+/// Post the outcome to the configured webhook
+pub async fn send(config: &NotificationsConfig, outcome: &Outcome<'_>) -> Result<()> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(WEBHOOK_TIMEOUT_SECS))
+        .build()?;
+
+    let response = client
+        .post(&config.webhook_url)
+        .json(&payload(config.format, outcome))
+        .send()
+        .await
+        .context("Failed to send notification")?;
+
+    if !response.status().is_success() {
+        anyhow::bail!("Notification webhook returned {}", response.status());
+    }
+
+    Ok(())
+}
+
+/// Build the request body in the shape the webhook expects
+fn payload(format: WebhookFormat, outcome: &Outcome<'_>) -> Value {
+    let text = outcome.summary();
+    match format {
+        WebhookFormat::Slack => json!({ "text": text }),
+        WebhookFormat::Discord => json!({ "content": text }),
+        WebhookFormat::Generic => json!({
+            "command": outcome.command,
+            "cluster": outcome.cluster,
+            "success": outcome.error.is_none(),
+            "duration_secs": outcome.duration.as_secs(),
+            "error": outcome.error,
+            "text": text,
+        }),
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 60 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload_formats() {
+        let outcome = Outcome {
+            command: "create",
+            cluster: "prod",
+            duration: Duration::from_secs(754),
+            error: None,
+        };
+
+        let slack = payload(WebhookFormat::Slack, &outcome);
+        assert_eq!(
+            slack["text"],
+            "✅ oxide create succeeded for cluster prod (12m34s)"
+        );
+
+        let failed = Outcome {
+            error: Some("boom".to_string()),
+            ..outcome
+        };
+        let discord = payload(WebhookFormat::Discord, &failed);
+        assert!(discord["content"].as_str().unwrap().ends_with(": boom"));
+
+        let generic = payload(WebhookFormat::Generic, &failed);
+        assert_eq!(generic["success"], false);
+        assert_eq!(generic["duration_secs"], 754);
+        assert_eq!(generic["error"], "boom");
+    }
+}
@@ -0,0 +1,212 @@
+/// Render the Helm charts oxide installs for a GitOps handover
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::addons::AddonManager;
+use crate::cilium::{egress, policies, CiliumManager, GATEWAY_API_CRDS};
+use crate::config::ClusterConfig;
+use crate::hcloud::ip::IpManager;
+use crate::hcloud::HetznerCloudClient;
+use crate::utils::helm::{self, Chart};
+
+/// Index of the rendered directory
+const INDEX_FILE: &str = "charts.yaml";
+
+/// Contents of [`INDEX_FILE`]
+#[derive(Debug, Serialize)]
+struct Index {
+    cluster_name: String,
+    /// Applied with `kubectl apply` before Cilium
+    gateway_api_crds: String,
+    charts: Vec<IndexChart>,
+    /// Plain manifests applied besides the charts
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    manifests: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct IndexChart {
+    release: String,
+    repository: String,
+    chart: String,
+    version: String,
+    namespace: String,
+    values: String,
+    manifest: String,
+}
+
+/// Cilium and the enabled addon charts, in install order
+pub fn charts(config: &ClusterConfig, kubeconfig_path: &Path) -> Vec<Chart> {
+    let control_plane_count = config.control_planes.iter().map(|cp| cp.count).sum();
+    let cilium = CiliumManager::new(
+        config.cilium.clone(),
+        kubeconfig_path.to_path_buf(),
+        control_plane_count,
+    )
+    .with_kubespan(config.multi_region())
+    .with_cloud_load_balancers(config.cloud_load_balancers())
+    .with_istio(config.addons.istio.enabled)
+    .chart();
+
+    let mut charts = vec![cilium];
+    charts.extend(AddonManager::new(config, kubeconfig_path.to_path_buf()).charts());
+    charts
+}
+
+/// Render every chart into `out` and return the files written
+pub async fn render(
+    config: &ClusterConfig,
+    kubeconfig_path: &Path,
+    out: &Path,
+) -> Result<Vec<PathBuf>> {
+    CiliumManager::check_helm_installed().await?;
+
+    let mut rendered = Vec::new();
+    for chart in charts(config, kubeconfig_path) {
+        helm::add_repo(kubeconfig_path, &chart.repo_name, &chart.repo_url).await?;
+        let manifest = helm::template(kubeconfig_path, &chart).await?;
+        rendered.push((chart, manifest));
+    }
+    let policies = policies::manifest(&config.cilium.default_deny);
+    let egress_ip = match &config.cilium.egress_gateway {
+        Some(egress) if egress.floating_ip => {
+            IpManager::new(HetznerCloudClient::from_config(config)?)
+                .find_egress_ip(&config.cluster_name)
+                .await?
+        }
+        _ => None,
+    };
+    let egress_policies = egress::manifest(config, egress_ip.as_ref().map(|ip| ip.ip.as_str()));
+    let addon_manifests = AddonManager::new(config, kubeconfig_path.to_path_buf()).manifests();
+    let mut manifests: Vec<(&str, &str)> = addon_manifests
+        .iter()
+        .map(|(name, manifest)| (*name, manifest.as_str()))
+        .collect();
+    if config.cilium.default_deny.enabled {
+        manifests.insert(0, ("cilium-default-deny", policies.as_str()));
+    }
+    if let Some(egress_policies) = &egress_policies {
+        manifests.insert(0, ("cilium-egress-gateway", egress_policies.as_str()));
+    }
+
+    let mut written = Vec::new();
+    for (relative, content) in files(&config.cluster_name, &rendered, &manifests)? {
+        let path = out.join(relative);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+        }
+        std::fs::write(&path, content).context(format!("Failed to write {}", path.display()))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Files of the rendered directory, by path relative to it
+fn files(
+    cluster_name: &str,
+    rendered: &[(Chart, String)],
+    manifests: &[(&str, &str)],
+) -> Result<Vec<(PathBuf, String)>> {
+    let mut files = Vec::new();
+    let mut index = Index {
+        cluster_name: cluster_name.to_string(),
+        gateway_api_crds: GATEWAY_API_CRDS.to_string(),
+        charts: Vec::new(),
+        manifests: Vec::new(),
+    };
+
+    for (chart, manifest) in rendered {
+        let values = format!("{}/values.yaml", chart.release);
+        let manifest_file = format!("{}/manifest.yaml", chart.release);
+        let header = format!(
+            "# Rendered by oxide for {} from {} {}\n",
+            cluster_name,
+            chart.reference(),
+            chart.version
+        );
+
+        let document = helm::values_document(&chart.values)?;
+        files.push((
+            PathBuf::from(&values),
+            format!("{}{}", header, serde_yaml::to_string(&document)?),
+        ));
+        files.push((
+            PathBuf::from(&manifest_file),
+            format!("{}{}", header, manifest),
+        ));
+        index.charts.push(IndexChart {
+            release: chart.release.clone(),
+            repository: chart.repo_url.clone(),
+            chart: chart.name.clone(),
+            version: chart.version.clone(),
+            namespace: chart.namespace.clone(),
+            values,
+            manifest: manifest_file,
+        });
+    }
+
+    for (name, manifest) in manifests {
+        let file = format!("{}.yaml", name);
+        files.push((PathBuf::from(&file), manifest.to_string()));
+        index.manifests.push(file);
+    }
+
+    files.push((PathBuf::from(INDEX_FILE), serde_yaml::to_string(&index)?));
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_charts_follow_config() {
+        let mut config = ClusterConfig::example();
+        config.addons.longhorn.enabled = false;
+        let kubeconfig = Path::new("output/kubeconfig");
+        let releases: Vec<String> = charts(&config, kubeconfig)
+            .into_iter()
+            .map(|chart| chart.release)
+            .collect();
+        assert_eq!(releases, ["cilium"]);
+
+        config.addons.longhorn.enabled = true;
+        let charts = charts(&config, kubeconfig);
+        assert_eq!(charts[1].release, "longhorn");
+        assert!(charts[1].privileged);
+    }
+
+    #[test]
+    fn test_files() {
+        let config = ClusterConfig::example();
+        let chart = charts(&config, Path::new("output/kubeconfig")).remove(0);
+        let rendered = vec![(chart, "kind: DaemonSet\n".to_string())];
+        let files = files("prod", &rendered, &[("extra", "kind: RuntimeClass\n")]).unwrap();
+
+        let paths: Vec<&str> = files
+            .iter()
+            .map(|(path, _)| path.to_str().unwrap())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "cilium/values.yaml",
+                "cilium/manifest.yaml",
+                "extra.yaml",
+                "charts.yaml"
+            ]
+        );
+
+        let values: serde_yaml::Value = serde_yaml::from_str(&files[0].1).unwrap();
+        assert_eq!(values["ipam"]["mode"], "kubernetes");
+        assert!(files[1]
+            .1
+            .starts_with("# Rendered by oxide for prod from cilium/cilium"));
+
+        let index: serde_yaml::Value = serde_yaml::from_str(&files[3].1).unwrap();
+        assert_eq!(index["charts"][0]["repository"], "https://helm.cilium.io/");
+        assert_eq!(index["charts"][0]["values"], "cilium/values.yaml");
+        assert_eq!(index["manifests"][0], "extra.yaml");
+    }
+}
@@ -0,0 +1,193 @@
+/// Hetzner Robot dedicated servers as cluster workers
+use anyhow::{Context, Result};
+use std::path::Path;
+use tracing::info;
+
+use crate::audit;
+use crate::config::{ClusterConfig, RobotConfig, RobotServer};
+use crate::hcloud::network::NetworkManager;
+use crate::hcloud::HetznerCloudClient;
+use crate::k8s::NodeManager;
+use crate::talos::{self, TalosClient, TalosConfigGenerator};
+use crate::utils::command::CommandBuilder;
+
+/// Kubernetes node name of a Robot server
+pub fn node_name(cluster_name: &str, server: &RobotServer) -> String {
+    format!("{}-{}", cluster_name, server.name)
+}
+
+/// Robot section of the config, or an error pointing at it
+pub fn robot_config(config: &ClusterConfig) -> Result<&RobotConfig> {
+    config
+        .robot
+        .as_ref()
+        .context("No robot servers configured; add a `robot` section to the config")
+}
+
+/// Servers selected by name, or all configured servers
+pub fn select<'a>(robot: &'a RobotConfig, name: Option<&str>) -> Result<Vec<&'a RobotServer>> {
+    match name {
+        None => Ok(robot.servers.iter().collect()),
+        Some(name) => {
+            let server = robot.servers.iter().find(|s| s.name == name);
+            let names: Vec<&str> = robot.servers.iter().map(|s| s.name.as_str()).collect();
+            Ok(vec![server.context(format!(
+                "Robot server {} not found. Configured servers: {}",
+                name,
+                names.join(", ")
+            ))?])
+        }
+    }
+}
+
+/// Configure a Robot server in maintenance mode and wait until it is Ready
+pub async fn join(config: &ClusterConfig, output_dir: &Path, server: &RobotServer) -> Result<()> {
+    let robot = robot_config(config)?;
+    let node = node_name(&config.cluster_name, server);
+    let base_config = output_dir.join("worker.yaml");
+    if !base_config.exists() {
+        anyhow::bail!(
+            "Talos configuration file not found: {}\n\
+            Joining Robot servers requires an existing cluster. Please run 'oxide create' first.",
+            base_config.display()
+        );
+    }
+
+    let client = HetznerCloudClient::from_config(config)?;
+    client.require_write_access("robot join").await?;
+    let network_manager = NetworkManager::new(client);
+    let networks = network_manager
+        .find_networks(&config.cluster_name, &config.hcloud)
+        .await?;
+    let gateway = network_manager
+        .ensure_vswitch_subnet(networks.primary().id, &config.hcloud.network.zone, robot)
+        .await?;
+
+    let patch =
+        talos::config::robot_patch(&node, server, robot, &config.hcloud.network.cidr, &gateway);
+    let machine_config =
+        TalosConfigGenerator::new(config.cluster_name.clone(), config.talos.clone())
+            .generate_pool_config(
+                &format!("robot-{}", server.name),
+                &base_config,
+                output_dir,
+                &[patch],
+                &[],
+            )
+            .await?;
+
+    info!("Applying machine config to {} ({})", node, server.public_ip);
+    let result = CommandBuilder::new("talosctl")
+        .args([
+            "apply-config",
+            "--insecure",
+            "--nodes",
+            &server.public_ip,
+            "--file",
+            machine_config.to_str().unwrap(),
+        ])
+        .context(format!(
+            "Failed to apply the machine config to {}; is it in Talos maintenance mode?",
+            node
+        ))
+        .run_silent()
+        .await;
+    audit::record(
+        "talos.apply_config",
+        &node,
+        &[("ip", &server.public_ip), ("vlan", &robot.vlan.to_string())],
+        &result,
+    );
+    result?;
+
+    TalosClient::new(output_dir.join("talosconfig"))
+        .wait_for_ip_booted(&node, &server.public_ip, config.timeouts.talos_boot)
+        .await?;
+    NodeManager::wait_for_node_ready(
+        &output_dir.join("kubeconfig"),
+        &node,
+        config.timeouts.node_ready,
+    )
+    .await?;
+
+    info!("✓ Robot server {} joined the cluster", node);
+    Ok(())
+}
+
+/// Reset a Robot server with Talos and remove its node
+///
+/// The server powers down afterwards; it is not cancelled in Robot.
+pub async fn remove(
+    config: &ClusterConfig,
+    output_dir: &Path,
+    server: &RobotServer,
+    force: bool,
+) -> Result<()> {
+    let node = node_name(&config.cluster_name, server);
+    let talos_client = TalosClient::new(output_dir.join("talosconfig"));
+
+    let result = talos_client
+        .reset_node_with_timeout(
+            &server.public_ip,
+            &node,
+            config.timeouts.node_reset,
+            force,
+            2,
+        )
+        .await;
+    match result {
+        Ok(()) => info!("✓ Node {} reset completed", node),
+        // The node powers down while talosctl is still connected
+        Err(e)
+            if ["connection closed", "broken pipe", "reset by peer"]
+                .iter()
+                .any(|msg| e.to_string().contains(msg)) =>
+        {
+            info!("✓ Node {} powered down during reset (expected)", node)
+        }
+        Err(e) => return Err(e),
+    }
+
+    NodeManager::delete_node(&output_dir.join("kubeconfig"), &node).await?;
+    info!(
+        "✓ Robot server {} removed; remove it from the config or rejoin it after reinstalling Talos",
+        node
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOT: &str = r#"
+vswitch_id: 1
+vlan: 4000
+subnet_cidr: 10.0.2.0/24
+servers:
+  - name: dedi-1
+    public_ip: 198.51.100.20
+    private_ip: 10.0.2.10
+  - name: dedi-2
+    public_ip: 198.51.100.21
+    private_ip: 10.0.2.11
+"#;
+
+    #[test]
+    fn test_select() {
+        let robot: RobotConfig = serde_yaml::from_str(ROBOT).unwrap();
+        assert_eq!(select(&robot, None).unwrap().len(), 2);
+        assert_eq!(
+            select(&robot, Some("dedi-2")).unwrap()[0].public_ip,
+            "198.51.100.21"
+        );
+        assert!(select(&robot, Some("dedi-3")).is_err());
+    }
+
+    #[test]
+    fn test_node_name() {
+        let robot: RobotConfig = serde_yaml::from_str(ROBOT).unwrap();
+        let server = &robot.servers[0];
+        assert_eq!(node_name("prod", server), "prod-dedi-1");
+    }
+}
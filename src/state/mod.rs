@@ -0,0 +1,254 @@
+/// Local record of the resources oxide created for a cluster
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::config::HelmMode;
+use crate::hcloud::server::ServerInfo;
+
+pub mod remote;
+
+/// File in the output directory holding the cluster state
+const STATE_FILE: &str = "cluster-state.json";
+
+/// Resources created for a cluster
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClusterState {
+    pub cluster_name: String,
+    /// Server names keyed by hcloud server ID
+    #[serde(default)]
+    pub servers: BTreeMap<u64, String>,
+    /// Releases that version aliases resolved to, keyed by component
+    /// (`talos`, `kubernetes`, `cilium`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub versions: BTreeMap<String, VersionPin>,
+    /// Ranges allocated for `auto` networks, keyed by network zone
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub networks: BTreeMap<String, NetworkPin>,
+    /// Helm charts installed by oxide, keyed by release name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub releases: BTreeMap<String, ReleaseRecord>,
+}
+
+/// A version alias and the release it resolved to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionPin {
+    /// Version as written in the config, e.g. `latest` or `1.31`
+    pub requested: String,
+    /// e.g. `v1.11.2` or `1.31.4`
+    pub resolved: String,
+}
+
+/// A Helm chart installed by oxide (Cilium or an addon)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReleaseRecord {
+    /// e.g. `cilium/cilium`
+    pub chart: String,
+    /// Chart version
+    pub version: String,
+    /// Version of the packaged application, if the chart declares one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_version: Option<String>,
+    pub namespace: String,
+    pub mode: HelmMode,
+    /// Last successful install or upgrade
+    pub deployed: chrono::DateTime<chrono::Utc>,
+}
+
+/// Range allocated for an `auto` network
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkPin {
+    pub cidr: String,
+    pub subnet_cidr: String,
+}
+
+impl ClusterState {
+    /// State in `output_dir`, if any
+    pub fn load(output_dir: &Path) -> Result<Option<Self>> {
+        let path = output_dir.join(STATE_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content =
+            std::fs::read_to_string(&path).context(format!("Failed to read {}", path.display()))?;
+        let state = serde_json::from_str(&content)
+            .context(format!("Invalid cluster state in {}", path.display()))?;
+        Ok(Some(state))
+    }
+
+    fn save(&self, output_dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(output_dir)
+            .context(format!("Failed to create {}", output_dir.display()))?;
+        let path = output_dir.join(STATE_FILE);
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)
+            .context(format!("Failed to write {}", path.display()))
+    }
+
+    /// Record servers created for `cluster_name`
+    pub fn record_servers(
+        output_dir: &Path,
+        cluster_name: &str,
+        servers: &[ServerInfo],
+    ) -> Result<()> {
+        let mut state = Self::load(output_dir)?.unwrap_or_else(|| Self {
+            cluster_name: cluster_name.to_string(),
+            ..Default::default()
+        });
+        for info in servers {
+            state
+                .servers
+                .insert(info.server.id, info.server.name.clone());
+        }
+        state.save(output_dir)
+    }
+
+    /// Record the releases version aliases resolved to for `cluster_name`
+    pub fn record_versions(
+        output_dir: &Path,
+        cluster_name: &str,
+        pins: &BTreeMap<String, VersionPin>,
+    ) -> Result<()> {
+        let mut state = Self::load(output_dir)?.unwrap_or_else(|| Self {
+            cluster_name: cluster_name.to_string(),
+            ..Default::default()
+        });
+        state.versions.extend(pins.clone());
+        state.save(output_dir)
+    }
+
+    /// Pin the ranges allocated for `auto` networks
+    pub fn record_networks(
+        output_dir: &Path,
+        cluster_name: &str,
+        pins: &BTreeMap<String, NetworkPin>,
+    ) -> Result<()> {
+        let mut state = Self::load(output_dir)?.unwrap_or_else(|| Self {
+            cluster_name: cluster_name.to_string(),
+            ..Default::default()
+        });
+        state.networks.extend(pins.clone());
+        state.save(output_dir)
+    }
+
+    /// Record a successful install or upgrade of a Helm chart
+    ///
+    /// Charts are installed after the servers are recorded, so the state
+    /// already names the cluster.
+    pub fn record_release(output_dir: &Path, release: &str, record: ReleaseRecord) -> Result<()> {
+        let mut state = Self::load(output_dir)?.unwrap_or_default();
+        state.releases.insert(release.to_string(), record);
+        state.save(output_dir)
+    }
+
+    /// Drop an uninstalled Helm chart from the state
+    pub fn forget_release(output_dir: &Path, release: &str) -> Result<()> {
+        let Some(mut state) = Self::load(output_dir)? else {
+            return Ok(());
+        };
+        state.releases.remove(release);
+        state.save(output_dir)
+    }
+
+    /// Drop deleted servers from the state
+    pub fn forget_servers(output_dir: &Path, servers: &[ServerInfo]) -> Result<()> {
+        let Some(mut state) = Self::load(output_dir)? else {
+            return Ok(());
+        };
+        for info in servers {
+            state.servers.remove(&info.server.id);
+        }
+        state.save(output_dir)
+    }
+
+    /// Remove the state once the cluster is gone
+    pub fn clear(output_dir: &Path) -> Result<()> {
+        let path = output_dir.join(STATE_FILE);
+        if path.exists() {
+            std::fs::remove_file(&path).context(format!("Failed to remove {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Labelled servers that oxide did not create for this cluster
+    ///
+    /// A server counts as unknown if its ID is not recorded or it lacks the
+    /// `managed-by: oxide` label.
+    pub fn unknown_servers<'a>(&self, servers: &'a [ServerInfo]) -> Vec<&'a ServerInfo> {
+        servers
+            .iter()
+            .filter(|info| {
+                !self.servers.contains_key(&info.server.id)
+                    || info.server.labels.get("managed-by").map(String::as_str) != Some("oxide")
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server(id: u64, name: &str, managed: bool) -> ServerInfo {
+        let mut labels = serde_json::json!({ "cluster": "prod" });
+        if managed {
+            labels["managed-by"] = "oxide".into();
+        }
+        let server = serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": name,
+            "status": "running",
+            "server_type": {"id": 1, "name": "cpx21", "description": "", "cores": 3, "memory": 4.0, "disk": 80},
+            "datacenter": {
+                "id": 1,
+                "name": "nbg1-dc3",
+                "description": "",
+                "location": {"id": 1, "name": "nbg1", "description": "", "country": "DE", "city": "", "latitude": 0.0, "longitude": 0.0}
+            },
+            "public_net": {"ipv4": null, "ipv6": null, "floating_ips": []},
+            "private_net": [],
+            "created": "2024-01-01T00:00:00Z",
+            "labels": labels
+        }))
+        .unwrap();
+        ServerInfo {
+            server,
+            role: crate::hcloud::server::NodeRole::Worker,
+            index: 0,
+        }
+    }
+
+    #[test]
+    fn test_record_and_check_servers() {
+        let dir = std::env::temp_dir().join(format!("oxide-state-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(ClusterState::load(&dir).unwrap(), None);
+
+        let created = [
+            server(1, "prod-worker-1", true),
+            server(2, "prod-worker-2", true),
+        ];
+        ClusterState::record_servers(&dir, "prod", &created).unwrap();
+        ClusterState::forget_servers(&dir, &created[1..]).unwrap();
+
+        let state = ClusterState::load(&dir).unwrap().unwrap();
+        assert_eq!(state.cluster_name, "prod");
+        assert_eq!(state.servers.len(), 1);
+
+        let found = [
+            server(1, "prod-worker-1", true),
+            server(3, "prod-worker-3", true),
+            server(4, "prod-worker-4", false),
+        ];
+        let unknown: Vec<u64> = state
+            .unknown_servers(&found)
+            .iter()
+            .map(|info| info.server.id)
+            .collect();
+        assert_eq!(unknown, [3, 4]);
+
+        ClusterState::clear(&dir).unwrap();
+        assert_eq!(ClusterState::load(&dir).unwrap(), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,509 @@
+/// Cluster state shared through the bucket of `storage`
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+use super::{ClusterState, STATE_FILE};
+use crate::config::ClusterConfig;
+use crate::hcloud::hibernate;
+use crate::k8s::resources::MANIFESTS_DIR;
+use crate::storage::{ObjectStorage, Precondition};
+
+/// Object held while a command changes the cluster
+const LOCK_OBJECT: &str = "cluster-state.lock";
+
+/// Who holds the state lock
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockInfo {
+    /// `user@host` of the run
+    pub holder: String,
+    /// oxide subcommand, e.g. `scale`
+    pub command: String,
+    pub since: DateTime<Utc>,
+}
+
+impl LockInfo {
+    fn new(command: &str) -> Self {
+        let env = |vars: &[&str]| {
+            vars.iter()
+                .find_map(|var| std::env::var(var).ok().filter(|value| !value.is_empty()))
+                .unwrap_or_else(|| "unknown".to_string())
+        };
+        Self {
+            holder: format!(
+                "{}@{}",
+                env(&["USER", "USERNAME"]),
+                env(&["HOSTNAME", "COMPUTERNAME"])
+            ),
+            command: command.to_string(),
+            since: Utc::now(),
+        }
+    }
+
+    /// e.g. `alice@laptop (scale since 2025-01-01 12:00:00 UTC)`
+    fn describe(&self) -> String {
+        format!(
+            "{} ({} since {})",
+            self.holder,
+            self.command,
+            self.since.format("%Y-%m-%d %H:%M:%S UTC")
+        )
+    }
+}
+
+/// The state of one run, pulled from the bucket
+pub struct RemoteState {
+    storage: ObjectStorage,
+    output_dir: PathBuf,
+    path: PathBuf,
+    /// ETag and content of the remote state as pulled, `None` if there was none
+    pulled: Option<(String, Vec<u8>)>,
+    /// Files kept next to the state as pulled, by object name
+    pulled_files: BTreeMap<String, Vec<u8>>,
+    locked: bool,
+}
+
+impl RemoteState {
+    /// Replace the local state with the remote one, `None` unless
+    /// `storage.remote_state` is set
+    ///
+    /// With `lock`, the state lock is taken first and fails if another run
+    /// holds it.
+    pub async fn pull(
+        config: &ClusterConfig,
+        output_dir: &Path,
+        command: &str,
+        lock: bool,
+    ) -> Result<Option<Self>> {
+        if !config.storage.as_ref().is_some_and(|s| s.remote_state) {
+            return Ok(None);
+        }
+        let storage = ObjectStorage::from_config(config)?;
+
+        if lock {
+            let info = LockInfo::new(command);
+            if !storage
+                .put_if(
+                    LOCK_OBJECT,
+                    serde_json::to_vec_pretty(&info)?,
+                    Precondition::Absent,
+                )
+                .await?
+            {
+                let holder = match storage.get(LOCK_OBJECT).await? {
+                    Some(body) => serde_json::from_slice::<LockInfo>(&body)
+                        .map(|held| held.describe())
+                        .unwrap_or_else(|_| "an unknown run".to_string()),
+                    None => "a run that just finished".to_string(),
+                };
+                bail!(
+                    "The cluster state at {} is locked by {}; if that run is gone, \
+                     release the lock with `oxide state unlock`",
+                    storage.location(STATE_FILE),
+                    holder
+                );
+            }
+            debug!("Locked {}", storage.location(STATE_FILE));
+        }
+
+        let mut remote = Self {
+            storage,
+            output_dir: output_dir.to_path_buf(),
+            path: output_dir.join(STATE_FILE),
+            pulled: None,
+            pulled_files: BTreeMap::new(),
+            locked: lock,
+        };
+        if let Err(e) = remote.download(output_dir).await {
+            remote.release().await;
+            return Err(e);
+        }
+        Ok(Some(remote))
+    }
+
+    async fn download(&mut self, output_dir: &Path) -> Result<()> {
+        match self.storage.get_versioned(STATE_FILE).await? {
+            Some((body, etag)) => {
+                std::fs::create_dir_all(output_dir)
+                    .context(format!("Failed to create {}", output_dir.display()))?;
+                std::fs::write(&self.path, &body)
+                    .context(format!("Failed to write {}", self.path.display()))?;
+                info!(
+                    "Using cluster state from {}",
+                    self.storage.location(STATE_FILE)
+                );
+                self.pulled = Some((etag, body));
+            }
+            None => {
+                // The bucket is the source of truth: a local state it does not
+                // have is stale, e.g. of a cluster another run destroyed
+                if self.path.exists() {
+                    let aside = self.path.with_extension("json.local");
+                    std::fs::rename(&self.path, &aside)
+                        .context(format!("Failed to move {} aside", self.path.display()))?;
+                    warn!(
+                        "No cluster state at {}; moved the local one to {}",
+                        self.storage.location(STATE_FILE),
+                        aside.display()
+                    );
+                }
+            }
+        }
+
+        let manifests = format!("{}/", MANIFESTS_DIR);
+        for name in self.storage.list().await? {
+            // Plain file names only, so a pull cannot write outside the dir
+            let manifest = name.strip_prefix(&manifests).is_some_and(|file| {
+                !file.is_empty() && !file.contains(['/', '\\']) && file != ".."
+            });
+            if name != hibernate::STATE_FILE && !manifest {
+                continue;
+            }
+            let Some(body) = self.storage.get(&name).await? else {
+                continue;
+            };
+            let path = self.output_dir.join(&name);
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)
+                    .context(format!("Failed to create {}", dir.display()))?;
+            }
+            std::fs::write(&path, &body).context(format!("Failed to write {}", path.display()))?;
+            self.pulled_files.insert(name, body);
+        }
+        // Files another run removed must not come back with the next push
+        for name in local_files(output_dir)?.into_keys() {
+            if !self.pulled_files.contains_key(&name) {
+                let path = self.output_dir.join(&name);
+                std::fs::remove_file(&path)
+                    .context(format!("Failed to remove {}", path.display()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Upload the local state if the run changed it and release the lock
+    ///
+    /// Only runs holding the lock write back; read-only commands leave the
+    /// bucket as they found it.
+    pub async fn push(self) -> Result<()> {
+        if !self.locked {
+            return Ok(());
+        }
+        let result = match self.upload().await {
+            Ok(()) => self.upload_files().await,
+            Err(e) => Err(e),
+        };
+        self.release().await;
+        result
+    }
+
+    async fn upload(&self) -> Result<()> {
+        let local = match std::fs::read(&self.path) {
+            Ok(body) => Some(body),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e).context(format!("Failed to read {}", self.path.display())),
+        };
+        let location = self.storage.location(STATE_FILE);
+        match (local, &self.pulled) {
+            (Some(body), Some((_, pulled))) if body == *pulled => Ok(()),
+            (Some(body), pulled) => {
+                let precondition = match pulled {
+                    Some((etag, _)) => Precondition::Matches(etag),
+                    None => Precondition::Absent,
+                };
+                if !self.storage.put_if(STATE_FILE, body, precondition).await? {
+                    bail!(
+                        "The cluster state at {} changed during this run; the local copy in {} \
+                         has this run's changes, merge them by hand before the next command",
+                        location,
+                        self.path.display()
+                    );
+                }
+                info!("Saved cluster state to {}", location);
+                Ok(())
+            }
+            (None, Some(_)) => {
+                self.storage.delete(STATE_FILE).await?;
+                info!("Removed cluster state from {}", location);
+                Ok(())
+            }
+            (None, None) => Ok(()),
+        }
+    }
+
+    /// Upload the files kept next to the state that the run changed, and
+    /// delete the ones it removed
+    async fn upload_files(&self) -> Result<()> {
+        let local = local_files(&self.output_dir)?;
+        for (name, body) in &local {
+            if self.pulled_files.get(name) != Some(body) {
+                self.storage.put(name, body.clone()).await?;
+                debug!("Saved {}", self.storage.location(name));
+            }
+        }
+        for name in self.pulled_files.keys() {
+            if !local.contains_key(name) {
+                self.storage.delete(name).await?;
+                debug!("Removed {}", self.storage.location(name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Release the lock, if this run holds it
+    ///
+    /// A lock left behind blocks every later run, but failing here would
+    /// hide the outcome of the command, so errors are only logged.
+    async fn release(&self) {
+        if self.locked {
+            if let Err(e) = self.storage.delete(LOCK_OBJECT).await {
+                warn!(
+                    "Failed to release the cluster state lock, run `oxide state unlock`: {:#}",
+                    e
+                );
+            }
+        }
+    }
+}
+
+/// The hibernation record and saved manifests in the output directory,
+/// by object name
+fn local_files(output_dir: &Path) -> Result<BTreeMap<String, Vec<u8>>> {
+    let mut files = BTreeMap::new();
+    let hibernation = output_dir.join(hibernate::STATE_FILE);
+    if hibernation.exists() {
+        files.insert(
+            hibernate::STATE_FILE.to_string(),
+            std::fs::read(&hibernation)
+                .context(format!("Failed to read {}", hibernation.display()))?,
+        );
+    }
+    let manifests = output_dir.join(MANIFESTS_DIR);
+    if manifests.is_dir() {
+        for entry in std::fs::read_dir(&manifests)
+            .context(format!("Failed to read {}", manifests.display()))?
+        {
+            let path = entry?.path();
+            let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            if path.is_file() {
+                files.insert(
+                    format!("{}/{}", MANIFESTS_DIR, file_name),
+                    std::fs::read(&path).context(format!("Failed to read {}", path.display()))?,
+                );
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Bucket of another cluster of the same config, e.g. a preview cluster
+/// named by `oxide ci`, whose objects live below its own name
+fn cluster_storage(config: &ClusterConfig, cluster_name: &str) -> Result<ObjectStorage> {
+    ObjectStorage::from_config(&ClusterConfig {
+        cluster_name: cluster_name.to_string(),
+        ..config.clone()
+    })
+}
+
+/// Remote state of `cluster_name` with its location, `None` if the bucket
+/// has none
+pub async fn load_cluster(
+    config: &ClusterConfig,
+    cluster_name: &str,
+) -> Result<Option<(ClusterState, String)>> {
+    let storage = cluster_storage(config, cluster_name)?;
+    let location = storage.location(STATE_FILE);
+    let Some(body) = storage.get(STATE_FILE).await? else {
+        return Ok(None);
+    };
+    let state =
+        serde_json::from_slice(&body).context(format!("Invalid cluster state in {}", location))?;
+    Ok(Some((state, location)))
+}
+
+/// Delete the remote state of a destroyed cluster and the files kept next
+/// to it
+pub async fn forget_cluster(config: &ClusterConfig, cluster_name: &str) -> Result<()> {
+    let storage = cluster_storage(config, cluster_name)?;
+    let manifests = format!("{}/", MANIFESTS_DIR);
+    for name in storage.list().await? {
+        if name == hibernate::STATE_FILE || name.starts_with(&manifests) {
+            storage.delete(&name).await?;
+        }
+    }
+    storage.delete(STATE_FILE).await?;
+    info!(
+        "Removed cluster state from {}",
+        storage.location(STATE_FILE)
+    );
+    Ok(())
+}
+
+/// Force-release the state lock of a run that did not finish
+pub async fn unlock(config: &ClusterConfig) -> Result<()> {
+    let storage = ObjectStorage::from_config(config)?;
+    match storage.get(LOCK_OBJECT).await? {
+        Some(body) => {
+            if let Ok(held) = serde_json::from_slice::<LockInfo>(&body) {
+                info!("Releasing the lock held by {}", held.describe());
+            }
+            storage.delete(LOCK_OBJECT).await?;
+            info!("✓ Unlocked {}", storage.location(STATE_FILE));
+        }
+        None => info!("The cluster state is not locked"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::mock::MockS3;
+
+    /// Example config sharing its state through `mock`, below `prod/`
+    fn remote_config(mock: &MockS3) -> ClusterConfig {
+        let mut storage = mock.config("oxide");
+        storage.prefix = Some("prod".to_string());
+        ClusterConfig {
+            storage: Some(storage),
+            ..ClusterConfig::example()
+        }
+    }
+
+    #[test]
+    fn test_lock_info_round_trip() {
+        let info = LockInfo::new("scale");
+        assert_eq!(info.command, "scale");
+        assert!(info.holder.contains('@'));
+
+        let body = serde_json::to_vec_pretty(&info).unwrap();
+        let parsed: LockInfo = serde_json::from_slice(&body).unwrap();
+        assert_eq!(parsed, info);
+    }
+
+    #[test]
+    fn test_local_files() {
+        let dir = std::env::temp_dir().join(format!("oxide-remote-files-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join(MANIFESTS_DIR)).unwrap();
+        assert!(local_files(&dir).unwrap().is_empty());
+
+        std::fs::write(dir.join(STATE_FILE), "{}").unwrap();
+        std::fs::write(dir.join(hibernate::STATE_FILE), "{}").unwrap();
+        std::fs::write(dir.join(MANIFESTS_DIR).join("cilium.yaml"), "kind: List").unwrap();
+        std::fs::write(dir.join("kubeconfig"), "").unwrap();
+
+        let files = local_files(&dir).unwrap();
+        assert_eq!(
+            files.keys().collect::<Vec<_>>(),
+            ["hibernation.json", "manifests/cilium.yaml"]
+        );
+        assert_eq!(files["manifests/cilium.yaml"], b"kind: List");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pull_removes_files_missing_remotely() {
+        let mock = MockS3::start().await;
+        mock.state().insert("prod/cluster-state.json", b"{}");
+        mock.state()
+            .insert("prod/manifests/cilium.yaml", b"kind: List");
+        let dir = std::env::temp_dir().join(format!("oxide-remote-pull-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join(MANIFESTS_DIR)).unwrap();
+        std::fs::write(dir.join(hibernate::STATE_FILE), "{}").unwrap();
+        std::fs::write(dir.join(MANIFESTS_DIR).join("spegel.yaml"), "kind: List").unwrap();
+
+        let remote = RemoteState::pull(&remote_config(&mock), &dir, "scale", true)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            local_files(&dir).unwrap().keys().collect::<Vec<_>>(),
+            ["manifests/cilium.yaml"]
+        );
+        remote.push().await.unwrap();
+        let state = mock.state();
+        assert_eq!(
+            state.objects.keys().collect::<Vec<_>>(),
+            ["prod/cluster-state.json", "prod/manifests/cilium.yaml"]
+        );
+        drop(state);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_missing_remote_state_is_empty() {
+        let mock = MockS3::start().await;
+        let dir = std::env::temp_dir().join(format!("oxide-remote-empty-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(STATE_FILE), "{}").unwrap();
+
+        let remote = RemoteState::pull(&remote_config(&mock), &dir, "scale", true)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!dir.join(STATE_FILE).exists());
+        assert!(dir.join("cluster-state.json.local").exists());
+        remote.push().await.unwrap();
+        assert!(mock.state().objects.is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_read_only_command_does_not_push() {
+        let mock = MockS3::start().await;
+        mock.state().insert("prod/cluster-state.json", b"{}");
+        let dir = std::env::temp_dir().join(format!("oxide-remote-read-{}", std::process::id()));
+
+        let remote = RemoteState::pull(&remote_config(&mock), &dir, "status", false)
+            .await
+            .unwrap()
+            .unwrap();
+        std::fs::write(dir.join(STATE_FILE), r#"{"servers": {}}"#).unwrap();
+        std::fs::write(dir.join(hibernate::STATE_FILE), "{}").unwrap();
+        remote.push().await.unwrap();
+
+        let state = mock.state();
+        assert!(
+            state
+                .requests
+                .iter()
+                .all(|request| !request.starts_with("PUT") && !request.starts_with("DELETE")),
+            "{:?}",
+            state.requests
+        );
+        assert_eq!(state.objects["prod/cluster-state.json"].0, b"{}");
+        drop(state);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_load_and_forget_cluster() {
+        let mock = MockS3::start().await;
+        let mut config = ClusterConfig::example();
+        config.storage = Some(mock.config("oxide"));
+        mock.state().insert(
+            "prod-pr-7/cluster-state.json",
+            br#"{"cluster_name": "prod-pr-7", "servers": {"42": "prod-pr-7-worker-1"}}"#,
+        );
+        mock.state()
+            .insert("prod-pr-7/manifests/cilium.yaml", b"kind: List");
+        mock.state().insert("prod-pr-8/cluster-state.json", b"{}");
+
+        let (state, location) = load_cluster(&config, "prod-pr-7").await.unwrap().unwrap();
+        assert_eq!(state.cluster_name, "prod-pr-7");
+        assert_eq!(state.servers[&42], "prod-pr-7-worker-1");
+        assert_eq!(location, "s3://oxide/prod-pr-7/cluster-state.json");
+        assert!(load_cluster(&config, "prod-pr-9").await.unwrap().is_none());
+
+        forget_cluster(&config, "prod-pr-7").await.unwrap();
+        assert_eq!(
+            mock.state().objects.keys().collect::<Vec<_>>(),
+            ["prod-pr-8/cluster-state.json"]
+        );
+    }
+}
@@ -0,0 +1,208 @@
+/// In-process mock of an S3 bucket for tests
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::config::StorageConfig;
+
+/// Environment variables holding the mock credentials
+const ACCESS_KEY_ENV: &str = "OXIDE_MOCK_S3_ACCESS_KEY";
+const SECRET_KEY_ENV: &str = "OXIDE_MOCK_S3_SECRET_KEY";
+
+/// Running mock object storage
+pub struct MockS3 {
+    endpoint: String,
+    state: Arc<Mutex<State>>,
+}
+
+/// Objects known to the mock
+#[derive(Default)]
+pub struct State {
+    next_etag: u64,
+    /// Body and ETag by full key
+    pub objects: BTreeMap<String, (Vec<u8>, String)>,
+    /// Every request received, as `METHOD path`
+    pub requests: Vec<String>,
+}
+
+impl MockS3 {
+    /// Start the server on a random local port
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let server_state = state.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, server_state.clone()));
+            }
+        });
+
+        Self { endpoint, state }
+    }
+
+    /// `storage` section for the bucket `bucket` of this server, with
+    /// `remote_state` on and credentials in the environment
+    pub fn config(&self, bucket: &str) -> StorageConfig {
+        std::env::set_var(ACCESS_KEY_ENV, "mock-access-key");
+        std::env::set_var(SECRET_KEY_ENV, "mock-secret-key");
+        StorageConfig {
+            bucket: bucket.to_string(),
+            location: "fsn1".to_string(),
+            endpoint: Some(self.endpoint.clone()),
+            region: None,
+            prefix: None,
+            access_key_env: ACCESS_KEY_ENV.to_string(),
+            secret_key_env: SECRET_KEY_ENV.to_string(),
+            remote_state: true,
+        }
+    }
+
+    /// Inspect the current objects
+    pub fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+}
+
+impl State {
+    /// Store an object as if uploaded, returning its ETag
+    pub fn insert(&mut self, key: &str, body: &[u8]) -> String {
+        self.next_etag += 1;
+        let etag = format!("\"{}\"", self.next_etag);
+        self.objects
+            .insert(key.to_string(), (body.to_vec(), etag.clone()));
+        etag
+    }
+
+    /// Route a request, returning the status line, headers and body
+    fn handle(
+        &mut self,
+        method: &str,
+        path: &str,
+        query: &str,
+        headers: &BTreeMap<String, String>,
+        body: Vec<u8>,
+    ) -> (&'static str, Vec<(&'static str, String)>, Vec<u8>) {
+        // Path-style: /<bucket>/<key>
+        let key = path
+            .trim_start_matches('/')
+            .split_once('/')
+            .map_or("", |(_, key)| key);
+
+        match (method, key) {
+            ("GET", "") => {
+                let prefix = url::form_urlencoded::parse(query.as_bytes())
+                    .find(|(name, _)| name == "prefix")
+                    .map(|(_, value)| value.into_owned())
+                    .unwrap_or_default();
+                let contents: String = self
+                    .objects
+                    .keys()
+                    .filter(|key| key.starts_with(&prefix))
+                    .map(|key| format!("<Contents><Key>{}</Key></Contents>", key))
+                    .collect();
+                let listing = format!(
+                    "<ListBucketResult><IsTruncated>false</IsTruncated>{}</ListBucketResult>",
+                    contents
+                );
+                ("200 OK", Vec::new(), listing.into_bytes())
+            }
+            ("GET", key) => match self.objects.get(key) {
+                Some((body, etag)) => ("200 OK", vec![("ETag", etag.clone())], body.clone()),
+                None => not_found(),
+            },
+            ("PUT", key) => {
+                let current = self.objects.get(key).map(|(_, etag)| etag);
+                let conflict = match (headers.get("if-none-match"), headers.get("if-match")) {
+                    (Some(_), _) => current.is_some(),
+                    (None, Some(expected)) => current != Some(expected),
+                    (None, None) => false,
+                };
+                if conflict {
+                    return (
+                        "412 Precondition Failed",
+                        Vec::new(),
+                        b"<Error><Code>PreconditionFailed</Code></Error>".to_vec(),
+                    );
+                }
+                let etag = self.insert(key, &body);
+                ("200 OK", vec![("ETag", etag)], Vec::new())
+            }
+            ("DELETE", key) => {
+                self.objects.remove(key);
+                ("204 No Content", Vec::new(), Vec::new())
+            }
+            _ => not_found(),
+        }
+    }
+}
+
+fn not_found() -> (&'static str, Vec<(&'static str, String)>, Vec<u8>) {
+    (
+        "404 Not Found",
+        Vec::new(),
+        b"<Error><Code>NoSuchKey</Code></Error>".to_vec(),
+    )
+}
+
+/// Handle a single request and close the connection
+async fn serve(mut stream: TcpStream, state: Arc<Mutex<State>>) {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 8192];
+
+    let header_end = loop {
+        if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let headers: BTreeMap<String, String> = head
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+    let content_length = headers
+        .get("content-length")
+        .and_then(|value| value.parse::<usize>().ok())
+        .unwrap_or(0);
+    while buffer.len() < header_end + content_length {
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+        }
+    }
+
+    let mut request_line = head.lines().next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let target = request_line.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let body = buffer[header_end..header_end + content_length].to_vec();
+
+    let (status, response_headers, payload) = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(format!("{} {}", method, path));
+        state.handle(&method, path, query, &headers, body)
+    };
+
+    let mut reply = format!(
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        status,
+        payload.len()
+    );
+    for (name, value) in response_headers {
+        reply.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    reply.push_str("\r\n");
+    let mut reply = reply.into_bytes();
+    reply.extend_from_slice(&payload);
+    let _ = stream.write_all(&reply).await;
+    let _ = stream.shutdown().await;
+}
@@ -3,11 +3,65 @@ use anyhow::{Context, Result};
 use std::path::Path;
 use std::process::Stdio;
 use tokio::process::Command;
-use tracing::info;
+use tracing::{info, Instrument};
 
+use super::diff;
+use super::extensions::NodeExtensions;
+use crate::audit;
+use crate::exit::Failure;
 use crate::hcloud::server::ServerInfo;
 use crate::utils::command::CommandBuilder;
 use crate::utils::polling::PollingConfig;
+use crate::utils::progress;
+
+/// How `talosctl apply-config` brings a changed config into effect
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ApplyMode {
+    /// Without a reboot where Talos can, with one where it must
+    Auto,
+    /// Always reboot the node
+    Reboot,
+    /// Fail instead of rebooting if a change needs a reboot
+    NoReboot,
+}
+
+impl std::fmt::Display for ApplyMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            ApplyMode::Auto => "auto",
+            ApplyMode::Reboot => "reboot",
+            ApplyMode::NoReboot => "no-reboot",
+        })
+    }
+}
+
+/// How long a single API server readiness probe may take
+const API_PROBE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Check once whether the Kubernetes API server at `url` answers
+///
+/// 200, and 401 or 403 for the anonymous request, mean it is up. Otherwise
+/// returns what went wrong.
+async fn probe_api_server(client: &reqwest::Client, url: &str) -> Result<(), String> {
+    match client.get(url).send().await {
+        Ok(response) => match response.status().as_u16() {
+            200 | 401 | 403 => Ok(()),
+            _ => Err(format!("HTTP {}", response.status())),
+        },
+        Err(e) if e.is_timeout() => Err(format!(
+            "no answer within {} seconds",
+            API_PROBE_TIMEOUT.as_secs()
+        )),
+        Err(e) => {
+            // The innermost cause says what happened, e.g. "Connection refused"
+            let mut cause: &dyn std::error::Error = &e;
+            while let Some(source) = cause.source() {
+                cause = source;
+            }
+            Err(cause.to_string())
+        }
+    }
+}
 
 /// Talos client for cluster operations
 pub struct TalosClient {
@@ -27,24 +81,36 @@ impl TalosClient {
 
         info!("Bootstrapping Kubernetes cluster on {}", server_ip);
 
-        let output = Command::new("talosctl")
-            .args([
-                "bootstrap",
-                "--nodes",
-                &server_ip,
-                "--talosconfig",
-                self.talosconfig_path.to_str().unwrap(),
-            ])
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()
-            .await
-            .context("Failed to execute talosctl bootstrap")?;
+        let result: Result<()> = async {
+            let output = Command::new("talosctl")
+                .args([
+                    "bootstrap",
+                    "--nodes",
+                    &server_ip,
+                    "--talosconfig",
+                    self.talosconfig_path.to_str().unwrap(),
+                ])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .output()
+                .await
+                .context("Failed to execute talosctl bootstrap")?;
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            anyhow::bail!("Bootstrap failed: {}", stderr);
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                anyhow::bail!("Bootstrap failed: {}", stderr);
+            }
+            Ok(())
         }
+        .await;
+
+        audit::record(
+            "talosctl.bootstrap",
+            &control_plane.server.name,
+            &[("ip", &server_ip)],
+            &result,
+        );
+        result?;
 
         info!("Kubernetes cluster bootstrapped successfully");
 
@@ -52,12 +118,27 @@ impl TalosClient {
     }
 
     /// Wait for Kubernetes API server to be ready
+    ///
+    /// On timeout the error says what the last probe saw, e.g. a refused
+    /// connection or an HTTP 503.
     pub async fn wait_for_api_server(
         &self,
         control_plane_ip: &str,
         timeout_secs: u64,
     ) -> Result<()> {
-        let api_url = format!("https://{}:6443/version", control_plane_ip);
+        let host = if control_plane_ip.contains(':') {
+            format!("[{}]", control_plane_ip)
+        } else {
+            control_plane_ip.to_string()
+        };
+        let api_url = format!("https://{}:6443/version", host);
+        // The API server certificate is signed by the cluster CA, which the
+        // probe does not need to trust to see that the server answers
+        let client = reqwest::Client::builder()
+            .danger_accept_invalid_certs(true)
+            .timeout(API_PROBE_TIMEOUT)
+            .build()?;
+        let last_failure = std::sync::Mutex::new(String::from("no response yet"));
 
         let config = PollingConfig::new(
             timeout_secs,
@@ -65,35 +146,26 @@ impl TalosClient {
             "Waiting for Kubernetes API server to be ready",
         );
 
-        config
-            .poll_until(|| {
-                let api_url = api_url.clone();
-                async move {
-                    // Try to reach the API server endpoint directly
-                    let output = CommandBuilder::new("curl")
-                        .args([
-                            "-k",
-                            "-s",
-                            "-o",
-                            "/dev/null",
-                            "-w",
-                            "%{http_code}",
-                            &api_url,
-                        ])
-                        .output()
-                        .await;
-
-                    if let Ok(output) = output {
-                        // 401 Unauthorized or 403 Forbidden means API server is up, just needs auth
-                        let status_code = output.stdout.trim();
-                        if status_code == "401" || status_code == "403" || status_code == "200" {
-                            return Ok(true);
-                        }
+        let result = config
+            .poll_until(|| async {
+                match probe_api_server(&client, &api_url).await {
+                    Ok(()) => Ok(true),
+                    Err(failure) => {
+                        tracing::debug!("{}: {}", api_url, failure);
+                        *last_failure.lock().unwrap() = failure;
+                        Ok(false)
                     }
-                    Ok(false)
                 }
             })
-            .await
+            .await;
+
+        result.with_context(|| {
+            format!(
+                "Kubernetes API server at {} is not ready (last attempt: {})",
+                api_url,
+                last_failure.lock().unwrap()
+            )
+        })
     }
 
     /// Generate kubeconfig file
@@ -155,14 +227,84 @@ impl TalosClient {
         Ok(String::from_utf8_lossy(&output.stdout).to_string())
     }
 
-    /// Patch control plane nodes with the actual cluster endpoint
+    /// Wait until Talos has booted with its machine config on every node
+    ///
+    /// Hetzner reports a server as running as soon as the VM starts, long before
+    /// Talos has applied the user_data config. This waits per node for the
+    /// Talos API port to accept connections and for an authenticated
+    /// `talosctl version` to succeed, running all nodes in parallel.
+    pub async fn wait_for_nodes_booted(
+        &self,
+        nodes: &[ServerInfo],
+        timeout_secs: u64,
+    ) -> Result<()> {
+        let total = nodes.len();
+        info!("Waiting for Talos to boot on {} node(s)...", total);
+
+        let ready = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = progress::counter("Talos ready", total as u64);
+        let mut tasks = Vec::new();
+
+        for node in nodes {
+            let server_ip = crate::hcloud::server::ServerManager::talos_ip(&node.server)
+                .context(format!("Node {} does not have an IP", node.server.name))?;
+            let direct = node.server.public_net.ipv4.is_some();
+            let server_name = node.server.name.clone();
+            let talosconfig_path = self.talosconfig_path.clone();
+            let ready = ready.clone();
+            let counter = counter.clone();
+
+            tasks.push(tokio::spawn(
+                async move {
+                    wait_for_node_booted(
+                        &talosconfig_path,
+                        &server_name,
+                        &server_ip,
+                        direct,
+                        timeout_secs,
+                    )
+                    .await?;
+                    counter.inc(1);
+                    let done = ready.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+                    info!(
+                        "✓ Talos ready on {} ({}) [{}/{}]",
+                        server_name, server_ip, done, total
+                    );
+                    Ok::<(), anyhow::Error>(())
+                }
+                .in_current_span(),
+            ));
+        }
+
+        for result in futures::future::join_all(tasks).await {
+            result??;
+        }
+
+        info!("✓ Talos booted on all {} node(s)", total);
+        Ok(())
+    }
+
+    /// Wait for Talos to boot on a node outside Hetzner Cloud, by its IP
+    pub async fn wait_for_ip_booted(
+        &self,
+        node_name: &str,
+        ip: &str,
+        timeout_secs: u64,
+    ) -> Result<()> {
+        wait_for_node_booted(&self.talosconfig_path, node_name, ip, true, timeout_secs).await?;
+        info!("✓ Talos ready on {} ({})", node_name, ip);
+        Ok(())
+    }
+
+    /// Patch nodes (normally the control planes) with the actual cluster endpoint
     pub async fn patch_cluster_endpoint(
         &self,
-        control_planes: &[ServerInfo],
+        nodes: &[ServerInfo],
         actual_endpoint: &str,
     ) -> Result<()> {
         info!(
-            "Patching control plane nodes with actual cluster endpoint: {}",
+            "Patching {} node(s) with actual cluster endpoint: {}",
+            nodes.len(),
             actual_endpoint
         );
 
@@ -172,92 +314,78 @@ impl TalosClient {
             actual_endpoint
         );
 
-        // Only patch control planes - workers use private network and don't need endpoint patching
-        let all_nodes: Vec<&ServerInfo> = control_planes.iter().collect();
-
-        // Patch all control plane nodes in parallel
+        // Patch all nodes in parallel
         let mut patch_tasks = Vec::new();
 
-        for node in all_nodes {
+        for node in nodes {
             let server_ip = match crate::hcloud::server::ServerManager::get_server_ip(&node.server)
             {
                 Some(ip) => ip,
                 None => continue,
             };
             let server_name = node.server.name.clone();
+            let server_name_span = server_name.clone();
             let patch_clone = patch.clone();
             let talosconfig_path = self.talosconfig_path.clone();
 
-            let task = tokio::spawn(async move {
-                // Wait for Talos API to be ready
-                let start = std::time::Instant::now();
-                let timeout = std::time::Duration::from_secs(300);
-
-                info!(
-                    "Waiting for Talos API on {} ({})...",
-                    server_name, server_ip
-                );
-
-                loop {
-                    let output = Command::new("talosctl")
-                        .args([
-                            "version",
-                            "--nodes",
-                            &server_ip,
-                            "--talosconfig",
-                            talosconfig_path.to_str().unwrap(),
-                        ])
-                        .stdout(Stdio::piped())
-                        .stderr(Stdio::piped())
-                        .output()
-                        .await;
-
-                    if let Ok(output) = output {
-                        if output.status.success() {
-                            info!("Talos API ready on {} ({})", server_name, server_ip);
-                            break;
+            let task = tokio::spawn(
+                async move {
+                    // Show the change set first; failing to fetch it does not
+                    // stop the patch
+                    match endpoint_change(&talosconfig_path, &server_ip, &patch_clone).await {
+                        Ok(Some(diff)) => {
+                            info!("Machine config changes for {}:\n{}", server_name, diff)
                         }
-                    }
-
-                    if start.elapsed() > timeout {
-                        anyhow::bail!(
-                            "Timeout waiting for Talos API on {} ({})",
+                        Ok(None) => {}
+                        Err(e) => tracing::warn!(
+                            "⚠️  Could not diff the machine config of {}: {:#}",
                             server_name,
-                            server_ip
-                        );
+                            e
+                        ),
                     }
 
-                    tokio::time::sleep(std::time::Duration::from_secs(5)).await;
-                }
+                    // Apply patch
+                    info!("Patching node: {} ({})", server_name, server_ip);
 
-                // Apply patch
-                info!("Patching node: {} ({})", server_name, server_ip);
+                    let result: Result<()> = async {
+                        let output = Command::new("talosctl")
+                            .args([
+                                "patch",
+                                "mc",
+                                "--nodes",
+                                &server_ip,
+                                "--talosconfig",
+                                talosconfig_path.to_str().unwrap(),
+                                "--patch",
+                                &patch_clone,
+                            ])
+                            .stdout(Stdio::piped())
+                            .stderr(Stdio::piped())
+                            .output()
+                            .await
+                            .context("Failed to patch node endpoint")?;
 
-                let output = Command::new("talosctl")
-                    .args([
-                        "patch",
-                        "mc",
-                        "--nodes",
-                        &server_ip,
-                        "--talosconfig",
-                        talosconfig_path.to_str().unwrap(),
-                        "--patch",
-                        &patch_clone,
-                    ])
-                    .stdout(Stdio::piped())
-                    .stderr(Stdio::piped())
-                    .output()
-                    .await
-                    .context("Failed to patch node endpoint")?;
+                        if !output.status.success() {
+                            let stderr = String::from_utf8_lossy(&output.stderr);
+                            anyhow::bail!("Failed to patch node {}: {}", server_ip, stderr);
+                        }
+                        Ok(())
+                    }
+                    .await;
 
-                if !output.status.success() {
-                    let stderr = String::from_utf8_lossy(&output.stderr);
-                    anyhow::bail!("Failed to patch node {}: {}", server_ip, stderr);
-                }
+                    audit::record(
+                        "talosctl.patch",
+                        &server_name,
+                        &[("ip", &server_ip), ("patch", &patch_clone)],
+                        &result,
+                    );
+                    result?;
 
-                info!("Successfully patched {} ({})", server_name, server_ip);
-                Ok::<(), anyhow::Error>(())
-            });
+                    info!("Successfully patched {} ({})", server_name, server_ip);
+                    Ok::<(), anyhow::Error>(())
+                }
+                .instrument(tracing::info_span!("node", node = %server_name_span)),
+            );
 
             patch_tasks.push(task);
         }
@@ -363,85 +491,302 @@ impl TalosClient {
             info!("Timeout: {} seconds", timeout_secs);
         }
 
-        let mut attempts = 0;
-        let max_attempts = max_retries + 1;
+        let result: Result<()> = async {
+            let mut attempts = 0;
+            let max_attempts = max_retries + 1;
+
+            loop {
+                attempts += 1;
+                if attempts > 1 {
+                    info!(
+                        "Retry attempt {}/{} for node {}",
+                        attempts - 1,
+                        max_retries,
+                        node_name
+                    );
+                }
+
+                let mut args = vec![
+                    "--talosconfig".to_string(),
+                    self.talosconfig_path.to_str().unwrap().to_string(),
+                    "reset".to_string(),
+                    "--nodes".to_string(),
+                    node_ip.to_string(),
+                ];
+
+                if force {
+                    args.push("--graceful=false".to_string());
+                }
+
+                args.push("--wait".to_string());
+                args.push("--timeout".to_string());
+                args.push(format!("{}s", timeout_secs));
+
+                let output = Command::new("talosctl")
+                    .args(&args)
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .output()
+                    .await
+                    .context("Failed to execute talosctl reset")?;
+
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                let stderr = String::from_utf8_lossy(&output.stderr);
 
-        loop {
-            attempts += 1;
-            if attempts > 1 {
+                // Debug: always log what we got from talosctl
+                if !stdout.is_empty() {
+                    info!("talosctl stdout for {}: {}", node_name, stdout);
+                }
+                if !stderr.is_empty() {
+                    info!("talosctl stderr for {}: {}", node_name, stderr);
+                }
                 info!(
-                    "Retry attempt {}/{} for node {}",
-                    attempts - 1,
-                    max_retries,
-                    node_name
+                    "talosctl exit code for {}: {}",
+                    node_name,
+                    output.status.code().unwrap_or(-1)
                 );
-            }
 
-            let mut args = vec![
-                "--talosconfig".to_string(),
-                self.talosconfig_path.to_str().unwrap().to_string(),
-                "reset".to_string(),
-                "--nodes".to_string(),
-                node_ip.to_string(),
-            ];
+                if output.status.success() {
+                    info!("Node {} reset successfully", node_name);
+                    return Ok(());
+                }
 
-            if force {
-                args.push("--graceful=false".to_string());
-            }
+                // Check if this is a retriable error (but not normal progress messages)
+                let is_retriable = (stderr.contains("i/o timeout")
+                    || stderr.contains("connection refused")
+                    || stderr.contains("no route to host"))
+                    && !stderr.contains("events check condition met");
 
-            args.push("--wait".to_string());
-            args.push("--timeout".to_string());
-            args.push(format!("{}s", timeout_secs));
+                if attempts < max_attempts && is_retriable {
+                    info!("Reset failed with retriable error, retrying in 10 seconds...");
+                    tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+                    continue;
+                }
 
-            let output = Command::new("talosctl")
-                .args(&args)
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .output()
-                .await
-                .context("Failed to execute talosctl reset")?;
+                // Non-retriable error or max retries reached
+                anyhow::bail!(
+                    "Failed to reset node {} after {} attempts: {}",
+                    node_name,
+                    attempts,
+                    stderr
+                );
+            }
+        }
+        .await;
 
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let stderr = String::from_utf8_lossy(&output.stderr);
+        let graceful = if force { "false" } else { "true" };
+        audit::record(
+            "talosctl.reset",
+            node_name,
+            &[("ip", node_ip), ("graceful", graceful)],
+            &result,
+        );
+        result
+    }
 
-            // Debug: always log what we got from talosctl
-            if !stdout.is_empty() {
-                info!("talosctl stdout for {}: {}", node_name, stdout);
-            }
-            if !stderr.is_empty() {
-                info!("talosctl stderr for {}: {}", node_name, stderr);
-            }
-            info!(
-                "talosctl exit code for {}: {}",
-                node_name,
-                output.status.code().unwrap_or(-1)
-            );
+    /// Shut a node down cleanly without waiting for it to go offline
+    ///
+    /// Talos stops the Kubernetes services and unmounts its disks; etcd
+    /// membership is kept, unlike with a reset.
+    pub async fn shutdown(&self, node_ip: &str, node_name: &str) -> Result<()> {
+        info!("Shutting down {} ({})", node_name, node_ip);
+        let result = CommandBuilder::new("talosctl")
+            .args([
+                "shutdown",
+                "--nodes",
+                node_ip,
+                "--wait=false",
+                "--talosconfig",
+                self.talosconfig_path.to_str().unwrap(),
+            ])
+            .context(format!("Failed to shut down {}", node_name))
+            .run()
+            .await
+            .map(|_| ());
 
-            if output.status.success() {
-                info!("Node {} reset successfully", node_name);
-                return Ok(());
-            }
+        audit::record("talosctl.shutdown", node_name, &[("ip", node_ip)], &result);
+        result
+    }
 
-            // Check if this is a retriable error (but not normal progress messages)
-            let is_retriable = (stderr.contains("i/o timeout")
-                || stderr.contains("connection refused")
-                || stderr.contains("no route to host"))
-                && !stderr.contains("events check condition met");
+    /// Machine config a node runs
+    pub async fn machine_config(&self, node_ip: &str) -> Result<String> {
+        machine_config(&self.talosconfig_path, node_ip).await
+    }
 
-            if attempts < max_attempts && is_retriable {
-                info!("Reset failed with retriable error, retrying in 10 seconds...");
-                tokio::time::sleep(std::time::Duration::from_secs(10)).await;
-                continue;
-            }
+    /// Apply a machine config file to a node
+    ///
+    /// With `dry_run` nothing changes and the output describes what Talos
+    /// would do, including whether the node would reboot.
+    pub async fn apply_config(
+        &self,
+        node_ip: &str,
+        node_name: &str,
+        config_path: &Path,
+        mode: ApplyMode,
+        dry_run: bool,
+    ) -> Result<String> {
+        let mode = format!("--mode={}", mode);
+        let mut args = vec![
+            "apply-config",
+            "--nodes",
+            node_ip,
+            "--talosconfig",
+            self.talosconfig_path.to_str().unwrap(),
+            "--file",
+            config_path.to_str().unwrap(),
+            &mode,
+        ];
+        if dry_run {
+            args.push("--dry-run");
+        }
+        let result = CommandBuilder::new("talosctl")
+            .args(args)
+            .context(format!(
+                "Failed to apply the machine config to {}",
+                node_name
+            ))
+            .run()
+            .await;
 
-            // Non-retriable error or max retries reached
-            anyhow::bail!(
-                "Failed to reset node {} after {} attempts: {}",
+        if !dry_run {
+            audit::record(
+                "talosctl.apply-config",
                 node_name,
-                attempts,
-                stderr
+                &[
+                    ("ip", node_ip),
+                    ("file", &config_path.display().to_string()),
+                    ("mode", &mode),
+                ],
+                &result,
             );
         }
+        result
+    }
+
+    /// Talos version running on a node, e.g. `v1.11.2`
+    pub async fn node_version(&self, node_ip: &str) -> Result<String> {
+        let output = CommandBuilder::new("talosctl")
+            .args([
+                "version",
+                "--nodes",
+                node_ip,
+                "--talosconfig",
+                self.talosconfig_path.to_str().unwrap(),
+            ])
+            .context(format!("Failed to get the Talos version of {}", node_ip))
+            .run()
+            .await?;
+
+        parse_server_tag(&output).context(format!(
+            "Could not determine the Talos version of {}",
+            node_ip
+        ))
+    }
+
+    /// System extensions a node runs and the schematic of its image
+    pub async fn extensions(&self, node_ip: &str) -> Result<NodeExtensions> {
+        let output = CommandBuilder::new("talosctl")
+            .args([
+                "get",
+                "extensions",
+                "--nodes",
+                node_ip,
+                "--talosconfig",
+                self.talosconfig_path.to_str().unwrap(),
+                "-o",
+                "json",
+            ])
+            .context(format!("Failed to list the extensions of {}", node_ip))
+            .run()
+            .await?;
+        NodeExtensions::parse(&output)
+    }
+
+    /// Install `image` on a node and reboot into it
+    ///
+    /// talosctl cordons and drains the node first and waits until it is back.
+    pub async fn upgrade_node(
+        &self,
+        node_ip: &str,
+        node_name: &str,
+        image: &str,
+        timeout_secs: u64,
+    ) -> Result<()> {
+        info!("Upgrading {} ({}) to {}", node_name, node_ip, image);
+
+        let result = CommandBuilder::new("talosctl")
+            .args([
+                "upgrade",
+                "--nodes",
+                node_ip,
+                "--image",
+                image,
+                "--wait",
+                "--timeout",
+                &format!("{}s", timeout_secs),
+                "--talosconfig",
+                self.talosconfig_path.to_str().unwrap(),
+            ])
+            .context(format!("Failed to upgrade {}", node_name))
+            .run_streaming()
+            .await
+            .map(|_| ());
+
+        audit::record(
+            "talosctl.upgrade",
+            node_name,
+            &[("ip", node_ip), ("image", image)],
+            &result,
+        );
+        result
+    }
+
+    /// Upgrade the Kubernetes control plane and kubelets to `version`
+    pub async fn upgrade_kubernetes(&self, control_plane_ip: &str, version: &str) -> Result<()> {
+        info!("Upgrading Kubernetes to {}", version);
+
+        let result = CommandBuilder::new("talosctl")
+            .args([
+                "upgrade-k8s",
+                "--nodes",
+                control_plane_ip,
+                "--to",
+                version.trim_start_matches('v'),
+                "--talosconfig",
+                self.talosconfig_path.to_str().unwrap(),
+            ])
+            .context("Failed to upgrade Kubernetes")
+            .run_streaming()
+            .await
+            .map(|_| ());
+
+        audit::record(
+            "talosctl.upgrade_k8s",
+            control_plane_ip,
+            &[("version", version)],
+            &result,
+        );
+        result
+    }
+
+    /// Whether every listed control plane node reports a healthy etcd member
+    pub async fn etcd_healthy(&self, control_plane_ips: &[String]) -> bool {
+        let output = CommandBuilder::new("talosctl")
+            .args([
+                "etcd",
+                "status",
+                "--nodes",
+                &control_plane_ips.join(","),
+                "--talosconfig",
+                self.talosconfig_path.to_str().unwrap(),
+            ])
+            .output()
+            .await;
+
+        // One row per node after the header; failing members make talosctl
+        // exit non-zero
+        matches!(output, Ok(ref output)
+            if output.success && output.stdout.lines().skip(1).count() >= control_plane_ips.len())
     }
 
     /// Check if talosctl is installed
@@ -455,10 +800,226 @@ impl TalosClient {
     }
 }
 
+/// Observed boot state of a Talos node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NodeBootState {
+    /// Talos API port not accepting connections yet
+    Unreachable,
+    /// apid is listening but does not accept our credentials yet
+    Booting,
+    /// Talos is in maintenance mode, i.e. it did not receive a machine config
+    Maintenance,
+    /// Talos is running with the cluster machine config
+    Ready,
+}
+
+impl NodeBootState {
+    fn describe(self) -> &'static str {
+        match self {
+            Self::Unreachable => "Talos API not reachable",
+            Self::Booting => "Talos API up, machine config not active yet",
+            Self::Maintenance => "maintenance mode (machine config from user_data was not applied)",
+            Self::Ready => "ready",
+        }
+    }
+}
+
+/// Poll a single node until Talos reports ready, logging state transitions
+#[tracing::instrument(name = "node", skip_all, fields(node = %server_name, ip = %server_ip))]
+async fn wait_for_node_booted(
+    talosconfig_path: &Path,
+    server_name: &str,
+    server_ip: &str,
+    direct: bool,
+    timeout_secs: u64,
+) -> Result<()> {
+    let start = std::time::Instant::now();
+    let timeout = std::time::Duration::from_secs(timeout_secs);
+    let mut last_state = None;
+    let status = progress::item(server_name);
+
+    loop {
+        let state = if direct {
+            probe_node(talosconfig_path, server_ip).await
+        } else {
+            probe_private_node(talosconfig_path, server_ip).await
+        };
+
+        if last_state != Some(state) {
+            info!("{} ({}): {}", server_name, server_ip, state.describe());
+            status.set_message(state.describe());
+            last_state = Some(state);
+        }
+
+        if state == NodeBootState::Ready {
+            progress::finish_item(&status, state.describe());
+            return Ok(());
+        }
+
+        if start.elapsed() > timeout {
+            anyhow::bail!(Failure::timeout(format!(
+                "Timeout after {} seconds waiting for Talos on {} ({}): {}",
+                timeout_secs,
+                server_name,
+                server_ip,
+                state.describe()
+            )));
+        }
+
+        tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+    }
+}
+
+/// Machine config a node runs, as YAML
+async fn machine_config(talosconfig_path: &Path, node_ip: &str) -> Result<String> {
+    CommandBuilder::new("talosctl")
+        .args([
+            "get",
+            "machineconfig",
+            "--nodes",
+            node_ip,
+            "--talosconfig",
+            talosconfig_path.to_str().unwrap(),
+            "-o",
+            "jsonpath={.spec}",
+        ])
+        .context(format!("Failed to get the machine config of {}", node_ip))
+        .run()
+        .await
+}
+
+/// Diff of the machine config a node runs against it with `patch` applied
+async fn endpoint_change(
+    talosconfig_path: &Path,
+    node_ip: &str,
+    patch: &str,
+) -> Result<Option<String>> {
+    let live = machine_config(talosconfig_path, node_ip).await?;
+    let patched = diff::patch(&live, &[patch.to_string()]).await?;
+    Ok(diff::unified_diff(&live, &patched, node_ip, "patched"))
+}
+
+/// Determine the boot state of a node via the Talos API
+async fn probe_node(talosconfig_path: &Path, server_ip: &str) -> NodeBootState {
+    let addr = format!("{}:50000", server_ip);
+    let connect = tokio::net::TcpStream::connect(&addr);
+    if !matches!(
+        tokio::time::timeout(std::time::Duration::from_secs(3), connect).await,
+        Ok(Ok(_))
+    ) {
+        return NodeBootState::Unreachable;
+    }
+
+    // Talk to the node directly rather than through the configured endpoints,
+    // which may not be up yet
+    let authenticated = CommandBuilder::new("talosctl")
+        .args([
+            "version",
+            "--nodes",
+            server_ip,
+            "--endpoints",
+            server_ip,
+            "--talosconfig",
+            talosconfig_path.to_str().unwrap(),
+        ])
+        .output()
+        .await;
+    if matches!(authenticated, Ok(ref output) if output.success) {
+        return NodeBootState::Ready;
+    }
+
+    // In maintenance mode only insecure connections are accepted
+    let insecure = CommandBuilder::new("talosctl")
+        .args(["version", "--insecure", "--nodes", server_ip])
+        .output()
+        .await;
+    if matches!(insecure, Ok(ref output) if output.success) {
+        return NodeBootState::Maintenance;
+    }
+
+    NodeBootState::Booting
+}
+
+/// Determine the boot state of a node without a public IP
+///
+/// Such nodes are only reachable through the control plane endpoints, which
+/// cannot proxy to maintenance mode, so a node is either booting or ready.
+async fn probe_private_node(talosconfig_path: &Path, server_ip: &str) -> NodeBootState {
+    let authenticated = CommandBuilder::new("talosctl")
+        .args([
+            "version",
+            "--nodes",
+            server_ip,
+            "--talosconfig",
+            talosconfig_path.to_str().unwrap(),
+        ])
+        .output()
+        .await;
+    if matches!(authenticated, Ok(ref output) if output.success) {
+        NodeBootState::Ready
+    } else {
+        NodeBootState::Booting
+    }
+}
+
+/// Server tag from `talosctl version`, which lists the client first
+fn parse_server_tag(output: &str) -> Option<String> {
+    output
+        .lines()
+        .skip_while(|line| line.trim() != "Server:")
+        .find_map(|line| line.trim().strip_prefix("Tag:"))
+        .map(|tag| tag.trim().to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_server_tag() {
+        let output = "Client:\n\tTag:         v1.11.2\n\tSHA:         abc\n\
+                      Server:\n\tNODE:        10.0.1.2\n\tTag:         v1.10.6\n";
+        assert_eq!(parse_server_tag(output).as_deref(), Some("v1.10.6"));
+        assert_eq!(parse_server_tag("Client:\n\tTag: v1.11.2\n"), None);
+    }
+
+    /// Answer one request with `status` and return the URL
+    async fn serve_once(status: &'static str) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/version", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request).await;
+            let response = format!("HTTP/1.1 {}\r\ncontent-length: 0\r\n\r\n", status);
+            stream.write_all(response.as_bytes()).await.unwrap();
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_probe_api_server() {
+        let client = reqwest::Client::new();
+
+        let url = serve_once("401 Unauthorized").await;
+        assert_eq!(probe_api_server(&client, &url).await, Ok(()));
+
+        let url = serve_once("503 Service Unavailable").await;
+        assert_eq!(
+            probe_api_server(&client, &url).await,
+            Err("HTTP 503 Service Unavailable".to_string())
+        );
+
+        // Nothing listens on the port once the listener is dropped
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/version", listener.local_addr().unwrap());
+        drop(listener);
+        let failure = probe_api_server(&client, &url).await.unwrap_err();
+        assert!(failure.to_lowercase().contains("refused"), "{}", failure);
+    }
+
     #[tokio::test]
     async fn test_check_talosctl() {
         // This test will pass if talosctl is installed, fail otherwise
@@ -468,4 +1029,11 @@ mod tests {
             println!("talosctl not installed (expected in test environment)");
         }
     }
+
+    #[tokio::test]
+    async fn test_probe_unreachable_node() {
+        // Nothing listens on the Talos API port locally
+        let state = probe_node(Path::new("talosconfig"), "127.0.0.1").await;
+        assert_eq!(state, NodeBootState::Unreachable);
+    }
 }
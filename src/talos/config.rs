@@ -5,12 +5,25 @@ use std::process::Stdio;
 use tokio::process::Command;
 use tracing::info;
 
-use crate::config::TalosConfig;
+use super::extensions::ImageFactory;
+use super::secrets::SecretsStore;
+use crate::config::{OidcConfig, RegistryMirrorConfig, RobotConfig, RobotServer, TalosConfig};
+use crate::utils::command::CommandBuilder;
+
+/// Role-specific patch applied to control plane configs
+const CONTROL_PLANE_PATCH: &str = "@patches/control-plane.yaml";
+
+/// Role-specific patch applied to worker configs
+const WORKER_PATCH: &str = "@patches/worker.yaml";
 
 /// Talos configuration generator
 pub struct TalosConfigGenerator {
     cluster_name: String,
     talos_config: TalosConfig,
+    extra_patches: Vec<String>,
+    control_plane_patches: Vec<String>,
+    extensions: Vec<String>,
+    existing_secrets: Option<std::path::PathBuf>,
 }
 
 impl TalosConfigGenerator {
@@ -19,9 +32,38 @@ impl TalosConfigGenerator {
         Self {
             cluster_name,
             talos_config,
+            extra_patches: Vec::new(),
+            control_plane_patches: Vec::new(),
+            extensions: Vec::new(),
+            existing_secrets: None,
         }
     }
 
+    /// Add machine config patches applied to every node (e.g. addon prerequisites)
+    pub fn with_patches(mut self, patches: Vec<String>) -> Self {
+        self.extra_patches.extend(patches);
+        self
+    }
+
+    /// Add machine config patches applied to control plane nodes only
+    pub fn with_control_plane_patches(mut self, patches: Vec<String>) -> Self {
+        self.control_plane_patches.extend(patches);
+        self
+    }
+
+    /// Require Talos system extensions to be part of the installer image
+    pub fn with_extensions(mut self, extensions: Vec<String>) -> Self {
+        self.extensions.extend(extensions);
+        self
+    }
+
+    /// Regenerate the configs of an existing cluster with the secrets stored
+    /// for the output directory `dir`; never generates new secrets
+    pub fn with_existing_secrets(mut self, dir: &Path) -> Self {
+        self.existing_secrets = Some(dir.to_path_buf());
+        self
+    }
+
     /// Generate Talos configuration files using talosctl
     pub async fn generate_configs(
         &self,
@@ -35,9 +77,20 @@ impl TalosConfigGenerator {
             .await
             .context("Failed to create output directory")?;
 
-        // Check if secrets file already exists
-        let secrets_path = output_dir.join("secrets.yaml");
-        let secrets_exists = secrets_path.exists();
+        // Fetch (or generate) the cluster secrets from the configured backend;
+        // a temporary plaintext copy is removed again when `secrets` drops
+        let secrets = match &self.existing_secrets {
+            Some(dir) => {
+                SecretsStore::new(self.talos_config.secrets.clone(), dir)
+                    .load()
+                    .await?
+            }
+            None => {
+                SecretsStore::new(self.talos_config.secrets.clone(), output_dir)
+                    .materialize()
+                    .await?
+            }
+        };
 
         // Generate base configuration using talosctl with patches
         let mut args = vec![
@@ -54,17 +107,30 @@ impl TalosConfigGenerator {
             "--with-examples=false", // Exclude examples to stay under 32KB user_data limit
             // Control plane patches
             "--config-patch-control-plane",
-            "@patches/control-plane.yaml",
+            CONTROL_PLANE_PATCH,
             // Worker patches
             "--config-patch-worker",
-            "@patches/worker.yaml",
+            WORKER_PATCH,
+            "--with-secrets",
+            secrets.path().to_str().unwrap(),
         ];
 
-        // Only use existing secrets if the file exists
-        if secrets_exists {
-            info!("Using existing secrets file");
-            args.push("--with-secrets");
-            args.push(secrets_path.to_str().unwrap());
+        // User-supplied patches first, then generated ones so that addon
+        // prerequisites cannot be accidentally overridden
+        let mut patches = self.talos_config.config_patches.clone();
+        patches.extend(self.extra_patches.iter().cloned());
+
+        if let Some(patch) = self.extension_patch(&self.extensions).await? {
+            patches.push(patch);
+        }
+
+        for patch in &patches {
+            args.push("--config-patch");
+            args.push(patch);
+        }
+        for patch in &self.control_plane_patches {
+            args.push("--config-patch-control-plane");
+            args.push(patch);
         }
 
         let output = Command::new("talosctl")
@@ -86,9 +152,260 @@ impl TalosConfigGenerator {
             controlplane: output_dir.join("controlplane.yaml"),
             worker: output_dir.join("worker.yaml"),
             talosconfig: output_dir.join("talosconfig"),
-            secrets: output_dir.join("secrets.yaml"),
         })
     }
+
+    /// Patches that went into the generated config for a role
+    ///
+    /// Excludes the extension install-image patch, which is a single line.
+    pub fn applied_patches(&self, control_plane: bool) -> Vec<String> {
+        let mut patches = vec![if control_plane {
+            CONTROL_PLANE_PATCH.to_string()
+        } else {
+            WORKER_PATCH.to_string()
+        }];
+        patches.extend(self.talos_config.config_patches.iter().cloned());
+        patches.extend(self.extra_patches.iter().cloned());
+        if control_plane {
+            patches.extend(self.control_plane_patches.iter().cloned());
+        }
+        patches
+    }
+
+    /// Derive a pool-specific worker config from the base worker config
+    ///
+    /// Pools with hardware requirements (e.g. GPUs) need extra kernel modules,
+    /// labels and a different extension set, while still sharing the cluster
+    /// secrets baked into the base config.
+    pub async fn generate_pool_config(
+        &self,
+        pool_name: &str,
+        base_config: &Path,
+        output_dir: &Path,
+        patches: &[String],
+        extensions: &[String],
+    ) -> Result<std::path::PathBuf> {
+        let output_path = pool_config_path(output_dir, pool_name);
+        info!(
+            "Generating machine config for pool {}: {}",
+            pool_name,
+            output_path.display()
+        );
+
+        let mut patches = patches.to_vec();
+        if let Some(patch) = self.extension_patch(extensions).await? {
+            patches.push(patch);
+        }
+
+        let mut args = vec![
+            "machineconfig".to_string(),
+            "patch".to_string(),
+            base_config.to_str().unwrap().to_string(),
+            "--output".to_string(),
+            output_path.to_str().unwrap().to_string(),
+        ];
+        for patch in patches {
+            args.push("--patch".to_string());
+            args.push(patch);
+        }
+
+        CommandBuilder::new("talosctl")
+            .args(&args)
+            .context(format!("Failed to generate config for pool {}", pool_name))
+            .run_silent()
+            .await?;
+
+        Ok(output_path)
+    }
+
+    /// Resolve an install image patch for the given extensions, plus the
+    /// ones the Talos settings require, if any
+    async fn extension_patch(&self, extensions: &[String]) -> Result<Option<String>> {
+        let mut extensions_with_talos = self.talos_config.extensions();
+        extensions_with_talos.extend(extensions.iter().cloned());
+        if extensions_with_talos.is_empty() {
+            return Ok(None);
+        }
+
+        let images = ImageFactory::default()
+            .resolve(&extensions_with_talos, &self.talos_config.version)
+            .await?;
+        info!(
+            "Talos extensions require Image Factory schematic {}",
+            images.schematic_id
+        );
+        info!(
+            "Ensure the snapshot for these nodes was built from: {}",
+            images.hcloud_disk_image
+        );
+
+        Ok(Some(images.install_image_patch()))
+    }
+}
+
+/// Machine config patch setting the pod and service CIDRs
+///
+/// In dual-stack mode the IPv6 ranges are added; IPv4 stays first so that
+/// existing single-stack assumptions (ClusterIP of kube-dns, Cilium's
+/// primary family) are unchanged.
+pub fn cluster_network_patch(talos_config: &TalosConfig, dual_stack: bool) -> String {
+    let mut pod_subnets = vec![talos_config.pod_cidr.as_str()];
+    let mut service_subnets = vec![talos_config.service_cidr.as_str()];
+    if dual_stack {
+        pod_subnets.push(&talos_config.ipv6_pod_cidr);
+        service_subnets.push(&talos_config.ipv6_service_cidr);
+    }
+    let list = |subnets: &[&str]| -> String {
+        subnets
+            .iter()
+            .map(|subnet| format!("      - {}\n", subnet))
+            .collect()
+    };
+    format!(
+        "cluster:\n  network:\n    podSubnets:\n{}    serviceSubnets:\n{}",
+        list(&pod_subnets),
+        list(&service_subnets)
+    )
+}
+
+/// Machine config patch enabling KubeSpan for clusters spanning network zones
+///
+/// Nodes find each other through the discovery service and route all
+/// node-to-node traffic, including private addresses of other zones, over
+/// WireGuard.
+pub const KUBESPAN_PATCH: &str = r#"machine:
+  network:
+    kubespan:
+      enabled: true
+cluster:
+  discovery:
+    enabled: true
+"#;
+
+/// Machine config patch letting regular workloads run on control planes
+///
+/// Talos then leaves out the control plane taint.
+pub const ALLOW_SCHEDULING_PATCH: &str = r#"cluster:
+  allowSchedulingOnControlPlanes: true
+"#;
+
+/// Hetzner's recursive resolvers, reached through the NAT gateway
+const HETZNER_NAMESERVERS: [&str; 2] = ["185.12.64.1", "185.12.64.2"];
+
+/// Machine config patch for nodes without public IPs
+///
+/// Their only interface is the private one, so the default route goes to the
+/// network gateway, which forwards it to the NAT gateway server via the
+/// network's `0.0.0.0/0` route.
+pub fn private_egress_patch(network_gateway: &str) -> String {
+    let patch = serde_json::json!({
+        "machine": {
+            "network": {
+                "interfaces": [{
+                    "deviceSelector": { "physical": true },
+                    "dhcp": true,
+                    "routes": [{ "network": "0.0.0.0/0", "gateway": network_gateway }],
+                }],
+                "nameservers": HETZNER_NAMESERVERS,
+            }
+        }
+    });
+    serde_yaml::to_string(&patch).expect("egress patch serializes")
+}
+
+/// MTU of vSwitch VLANs
+const VSWITCH_MTU: u32 = 1400;
+
+/// Machine config patch turning a worker config into one for a Robot server
+///
+/// The public interface keeps using DHCP; the vSwitch VLAN gets the static
+/// private address and a route to the cloud network via the subnet gateway.
+pub fn robot_patch(
+    node_name: &str,
+    server: &RobotServer,
+    robot: &RobotConfig,
+    network_cidr: &str,
+    vswitch_gateway: &str,
+) -> String {
+    let prefix = robot.subnet_cidr.split_once('/').map_or("24", |(_, p)| p);
+    let mut interface = serde_json::json!({
+        "dhcp": true,
+        "vlans": [{
+            "vlanId": robot.vlan,
+            "mtu": VSWITCH_MTU,
+            "addresses": [format!("{}/{}", server.private_ip, prefix)],
+            "routes": [{ "network": network_cidr, "gateway": vswitch_gateway }],
+        }],
+    });
+    match &server.interface {
+        Some(name) => interface["interface"] = name.as_str().into(),
+        None => interface["deviceSelector"] = serde_json::json!({ "physical": true }),
+    }
+
+    let patch = serde_json::json!({
+        "machine": {
+            "install": { "disk": server.install_disk },
+            "network": { "hostname": node_name, "interfaces": [interface] },
+            "nodeLabels": server.labels,
+            "kubelet": { "nodeIP": { "validSubnets": [network_cidr] } },
+        }
+    });
+    serde_yaml::to_string(&patch).expect("Robot patch serializes")
+}
+
+/// Control plane patch enabling OIDC authentication on the API server
+pub fn oidc_patch(oidc: &OidcConfig) -> String {
+    let extra_args = serde_json::json!({
+        "oidc-issuer-url": oidc.issuer_url,
+        "oidc-client-id": oidc.client_id,
+        "oidc-username-claim": oidc.username_claim,
+        "oidc-username-prefix": oidc.prefix,
+        "oidc-groups-claim": oidc.groups_claim,
+        "oidc-groups-prefix": oidc.prefix,
+    });
+    let patch = serde_json::json!({ "cluster": { "apiServer": { "extraArgs": extra_args } } });
+    serde_yaml::to_string(&patch).expect("OIDC patch serializes")
+}
+
+/// Machine config patch adding `sans` to the API server and Talos API
+/// certificates
+pub fn cert_sans_patch(sans: &[String]) -> String {
+    let patch = serde_json::json!({
+        "machine": { "certSANs": sans },
+        "cluster": { "apiServer": { "certSANs": sans } },
+    });
+    serde_yaml::to_string(&patch).expect("cert SANs patch serializes")
+}
+
+/// Machine config patch pulling images of the mirrored registries from the
+/// registry mirror
+///
+/// containerd requests `<endpoint>/v2/<repository>`, so the mirror serves each
+/// image under its repository path without the registry host.
+pub fn registry_mirror_patch(mirror: &RegistryMirrorConfig) -> String {
+    let mirrors: serde_json::Map<String, serde_json::Value> = mirror
+        .registries
+        .iter()
+        .map(|registry| {
+            (
+                registry.clone(),
+                serde_json::json!({ "endpoints": [mirror.endpoint] }),
+            )
+        })
+        .collect();
+    let mut registries = serde_json::json!({ "mirrors": mirrors });
+    if mirror.insecure_skip_verify {
+        registries["config"] = serde_json::json!({
+            mirror.host(): { "tls": { "insecureSkipVerify": true } }
+        });
+    }
+    let patch = serde_json::json!({ "machine": { "registries": registries } });
+    serde_yaml::to_string(&patch).expect("registry mirror patch serializes")
+}
+
+/// Path of the machine config generated for a dedicated node pool
+pub fn pool_config_path(output_dir: &Path, pool_name: &str) -> std::path::PathBuf {
+    output_dir.join(format!("worker-{}.yaml", pool_name))
 }
 
 /// Generated Talos configuration files
@@ -97,8 +414,6 @@ pub struct GeneratedConfigs {
     pub controlplane: std::path::PathBuf,
     pub worker: std::path::PathBuf,
     pub talosconfig: std::path::PathBuf,
-    #[allow(dead_code)]
-    pub secrets: std::path::PathBuf,
 }
 
 #[cfg(test)]
@@ -112,10 +427,107 @@ mod tests {
             kubernetes_version: "1.30.0".to_string(),
             cluster_endpoint: None,
             hcloud_snapshot_id: None,
+            cert_sans: vec![],
             config_patches: vec![],
+            pod_cidr: "10.244.0.0/16".to_string(),
+            service_cidr: "10.96.0.0/12".to_string(),
+            ipv6_pod_cidr: "fd00:10:244::/56".to_string(),
+            ipv6_service_cidr: "fd00:10:96::/112".to_string(),
+            secrets: Default::default(),
+            registry_mirror: None,
+            qemu_guest_agent: false,
         };
 
         let generator = TalosConfigGenerator::new("test-cluster".to_string(), talos_config);
         assert_eq!(generator.cluster_name, "test-cluster");
     }
+
+    #[test]
+    fn test_cluster_network_patch() {
+        let talos_config = crate::config::ClusterConfig::example().talos;
+        let single_stack = cluster_network_patch(&talos_config, false);
+        let patch = cluster_network_patch(&talos_config, true);
+
+        let patch: serde_yaml::Value = serde_yaml::from_str(&patch).unwrap();
+        let pod_subnets = &patch["cluster"]["network"]["podSubnets"];
+        assert_eq!(pod_subnets[0].as_str(), Some("10.244.0.0/16"));
+        assert_eq!(pod_subnets[1].as_str(), Some("fd00:10:244::/56"));
+        assert_eq!(
+            single_stack,
+            "cluster:\n  network:\n    podSubnets:\n      - 10.244.0.0/16\n    serviceSubnets:\n      - 10.96.0.0/12\n"
+        );
+    }
+
+    #[test]
+    fn test_oidc_patch() {
+        let oidc: OidcConfig =
+            serde_yaml::from_str("issuer_url: https://auth.example.com\nclient_id: kubernetes\n")
+                .unwrap();
+        let patch: serde_yaml::Value = serde_yaml::from_str(&oidc_patch(&oidc)).unwrap();
+        let args = &patch["cluster"]["apiServer"]["extraArgs"];
+        assert_eq!(args["oidc-issuer-url"], "https://auth.example.com");
+        assert_eq!(args["oidc-groups-prefix"], "oidc:");
+        assert_eq!(args["oidc-username-claim"], "email");
+    }
+
+    #[test]
+    fn test_cert_sans_patch() {
+        let sans = vec!["k8s.example.com".to_string(), "203.0.113.10".to_string()];
+        let patch: serde_yaml::Value = serde_yaml::from_str(&cert_sans_patch(&sans)).unwrap();
+        assert_eq!(patch["machine"]["certSANs"][0], "k8s.example.com");
+        assert_eq!(patch["cluster"]["apiServer"]["certSANs"][1], "203.0.113.10");
+    }
+
+    #[test]
+    fn test_robot_patch() {
+        let robot: RobotConfig = serde_yaml::from_str(
+            "vswitch_id: 1\nvlan: 4000\nsubnet_cidr: 10.0.2.0/24\nservers:\n  - name: dedi-1\n    public_ip: 198.51.100.20\n    private_ip: 10.0.2.10\n    interface: enp5s0\n",
+        )
+        .unwrap();
+        let patch = robot_patch(
+            "prod-dedi-1",
+            &robot.servers[0],
+            &robot,
+            "10.0.0.0/16",
+            "10.0.2.1",
+        );
+        let patch: serde_yaml::Value = serde_yaml::from_str(&patch).unwrap();
+        let machine = &patch["machine"];
+        assert_eq!(machine["network"]["hostname"], "prod-dedi-1");
+        let interface = &machine["network"]["interfaces"][0];
+        assert_eq!(interface["interface"], "enp5s0");
+        assert_eq!(interface["vlans"][0]["vlanId"], 4000);
+        assert_eq!(interface["vlans"][0]["addresses"][0], "10.0.2.10/24");
+        assert_eq!(interface["vlans"][0]["routes"][0]["gateway"], "10.0.2.1");
+        assert_eq!(machine["install"]["disk"], "/dev/nvme0n1");
+    }
+
+    #[test]
+    fn test_private_egress_patch() {
+        let patch: serde_yaml::Value =
+            serde_yaml::from_str(&private_egress_patch("10.0.0.1")).unwrap();
+        let interface = &patch["machine"]["network"]["interfaces"][0];
+        assert_eq!(interface["routes"][0]["network"], "0.0.0.0/0");
+        assert_eq!(interface["routes"][0]["gateway"], "10.0.0.1");
+        assert_eq!(patch["machine"]["network"]["nameservers"][1], "185.12.64.2");
+    }
+
+    #[test]
+    fn test_registry_mirror_patch() {
+        let mirror: RegistryMirrorConfig = serde_yaml::from_str(
+            "endpoint: https://registry.internal:5000\nregistries: [docker.io, quay.io]\ninsecure_skip_verify: true\n",
+        )
+        .unwrap();
+        let patch: serde_yaml::Value =
+            serde_yaml::from_str(&registry_mirror_patch(&mirror)).unwrap();
+        let registries = &patch["machine"]["registries"];
+        assert_eq!(
+            registries["mirrors"]["quay.io"]["endpoints"][0],
+            "https://registry.internal:5000"
+        );
+        assert_eq!(
+            registries["config"]["registry.internal:5000"]["tls"]["insecureSkipVerify"],
+            true
+        );
+    }
 }
@@ -0,0 +1,237 @@
+/// Machine config diffs
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::utils::command::CommandBuilder;
+
+/// Endpoint configs are generated with when `talos.cluster_endpoint` is not
+/// set; nodes are patched with the real one once the servers exist
+pub const PLACEHOLDER_ENDPOINT: &str = "https://127.0.0.1:6443";
+
+/// Unchanged lines shown around each change
+const CONTEXT_LINES: usize = 3;
+
+/// Machine config written to a temporary file for talosctl, removed on drop
+pub struct ScratchConfig {
+    path: PathBuf,
+}
+
+impl ScratchConfig {
+    pub async fn write(config: &str) -> Result<Self> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "oxide-mc-{}-{}.yaml",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        tokio::fs::write(&path, config)
+            .await
+            .context(format!("Failed to write {}", path.display()))?;
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ScratchConfig {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Apply patches to a machine config without touching any node
+pub async fn patch(config: &str, patches: &[String]) -> Result<String> {
+    let scratch = ScratchConfig::write(config).await?;
+    let mut args = vec![
+        "machineconfig".to_string(),
+        "patch".to_string(),
+        scratch.path().to_str().unwrap().to_string(),
+    ];
+    for patch in patches {
+        args.push("--patch".to_string());
+        args.push(patch.clone());
+    }
+    CommandBuilder::new("talosctl")
+        .args(&args)
+        .context("Failed to patch machine config")
+        .run()
+        .await
+}
+
+/// `cluster.controlPlane.endpoint` of a machine config
+pub fn cluster_endpoint(config: &str) -> Option<String> {
+    serde_yaml::Deserializer::from_str(config)
+        .filter_map(|document| serde::Deserialize::deserialize(document).ok())
+        .find_map(|document: serde_yaml::Value| {
+            document["cluster"]["controlPlane"]["endpoint"]
+                .as_str()
+                .map(str::to_string)
+        })
+}
+
+/// A generated config as the node would get it: the placeholder endpoint is
+/// replaced with the endpoint the node was patched with
+pub fn with_live_endpoint(generated: &str, live: &str) -> String {
+    match cluster_endpoint(live) {
+        Some(endpoint) if cluster_endpoint(generated).as_deref() == Some(PLACEHOLDER_ENDPOINT) => {
+            generated.replace(PLACEHOLDER_ENDPOINT, &endpoint)
+        }
+        _ => generated.to_string(),
+    }
+}
+
+enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Shortest edit script between two line sequences
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    // common[i][j]: longest common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(Line::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(Line::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(Line::Added(new[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+/// Unified diff from `old` to `new`, `None` if they are the same
+pub fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> Option<String> {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let lines = diff_lines(&old_lines, &new_lines);
+
+    let changes: Vec<usize> = (0..lines.len())
+        .filter(|&k| !matches!(lines[k], Line::Same(_)))
+        .collect();
+    if changes.is_empty() {
+        return None;
+    }
+
+    // Old and new line numbers before each entry
+    let mut before = Vec::with_capacity(lines.len());
+    let (mut old_no, mut new_no) = (0, 0);
+    for line in &lines {
+        before.push((old_no, new_no));
+        match line {
+            Line::Same(_) => {
+                old_no += 1;
+                new_no += 1;
+            }
+            Line::Removed(_) => old_no += 1,
+            Line::Added(_) => new_no += 1,
+        }
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", old_name, new_name);
+    let mut k = 0;
+    while k < changes.len() {
+        let start = changes[k].saturating_sub(CONTEXT_LINES);
+        let mut end = (changes[k] + CONTEXT_LINES + 1).min(lines.len());
+        k += 1;
+        while k < changes.len() && changes[k].saturating_sub(CONTEXT_LINES) <= end {
+            end = (changes[k] + CONTEXT_LINES + 1).min(lines.len());
+            k += 1;
+        }
+
+        let hunk = &lines[start..end];
+        let old_count = hunk
+            .iter()
+            .filter(|line| !matches!(line, Line::Added(_)))
+            .count();
+        let new_count = hunk
+            .iter()
+            .filter(|line| !matches!(line, Line::Removed(_)))
+            .count();
+        // An empty side starts at the line before the hunk
+        let first = |before: usize, count: usize| if count == 0 { before } else { before + 1 };
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            first(before[start].0, old_count),
+            old_count,
+            first(before[start].1, new_count),
+            new_count
+        ));
+        for line in hunk {
+            let (marker, text) = match line {
+                Line::Same(text) => (' ', text),
+                Line::Removed(text) => ('-', text),
+                Line::Added(text) => ('+', text),
+            };
+            out.push_str(&format!("{}{}\n", marker, text));
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n";
+        let new = "a\nb\nC\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\n";
+
+        assert_eq!(unified_diff(old, old, "live", "new"), None);
+        assert_eq!(
+            unified_diff(old, new, "live", "new").unwrap(),
+            "--- live\n+++ new\n\
+             @@ -1,6 +1,6 @@\n a\n b\n-c\n+C\n d\n e\n f\n\
+             @@ -10,3 +10,4 @@\n j\n k\n l\n+m\n"
+        );
+        assert_eq!(
+            unified_diff("", "a\n", "live", "new").unwrap(),
+            "--- live\n+++ new\n@@ -0,0 +1,1 @@\n+a\n"
+        );
+    }
+
+    #[test]
+    fn test_with_live_endpoint() {
+        let config = |endpoint: &str| {
+            format!(
+                "version: v1alpha1\ncluster:\n  controlPlane:\n    endpoint: {}\n---\napiVersion: v1alpha1\nkind: HostnameConfig\n",
+                endpoint
+            )
+        };
+        let live = config("https://203.0.113.10:6443");
+
+        assert_eq!(
+            cluster_endpoint(&live).as_deref(),
+            Some("https://203.0.113.10:6443")
+        );
+        assert_eq!(
+            with_live_endpoint(&config(PLACEHOLDER_ENDPOINT), &live),
+            live
+        );
+        // A configured endpoint is what the node should run, whatever it runs
+        let configured = config("https://k8s.example.com:6443");
+        assert_eq!(with_live_endpoint(&configured, &live), configured);
+    }
+}
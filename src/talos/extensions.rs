@@ -0,0 +1,363 @@
+/// Talos system extension resolution via the Sidero Labs Image Factory
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use tracing::info;
+
+const IMAGE_FACTORY_BASE: &str = "https://factory.talos.dev";
+
+/// Extension Image Factory images carry, with the schematic ID as version
+const SCHEMATIC_EXTENSION: &str = "schematic";
+
+/// Client for the Talos Image Factory
+///
+/// System extensions cannot be added to a running Talos node through machine
+/// config alone; they have to be baked into the installer image. The factory
+/// turns a list of extensions into a content-addressed schematic ID that can be
+/// used to reference both the installer image and the Hetzner disk image.
+#[derive(Default)]
+pub struct ImageFactory {
+    client: reqwest::Client,
+}
+
+/// Images produced by the Image Factory for a specific schematic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FactoryImages {
+    pub schematic_id: String,
+    pub installer_image: String,
+    pub hcloud_disk_image: String,
+}
+
+impl ImageFactory {
+    /// Resolve the installer and disk images containing the given extensions
+    pub async fn resolve(
+        &self,
+        extensions: &[String],
+        talos_version: &str,
+    ) -> Result<FactoryImages> {
+        let schematic = schematic_yaml(extensions);
+
+        info!(
+            "Resolving Talos Image Factory schematic for extensions: {}",
+            extensions.join(", ")
+        );
+
+        #[derive(serde::Deserialize)]
+        struct SchematicResponse {
+            id: String,
+        }
+
+        let response = self
+            .client
+            .post(format!("{}/schematics", IMAGE_FACTORY_BASE))
+            .body(schematic)
+            .send()
+            .await
+            .context("Failed to reach Talos Image Factory")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            anyhow::bail!(
+                "Image Factory rejected schematic with status {}: {}",
+                status,
+                error_text
+            );
+        }
+
+        let schematic: SchematicResponse = response
+            .json()
+            .await
+            .context("Failed to parse Image Factory response")?;
+
+        Ok(FactoryImages::new(schematic.id, talos_version))
+    }
+
+    /// Official extensions available for a Talos version
+    pub async fn official_extensions(&self, talos_version: &str) -> Result<Vec<OfficialExtension>> {
+        let response = self
+            .client
+            .get(format!(
+                "{}/version/{}/extensions/official",
+                IMAGE_FACTORY_BASE, talos_version
+            ))
+            .send()
+            .await
+            .context("Failed to reach Talos Image Factory")?;
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "Image Factory has no extensions for Talos {} (status {})",
+                talos_version,
+                response.status()
+            );
+        }
+        response
+            .json()
+            .await
+            .context("Failed to parse Image Factory response")
+    }
+}
+
+/// Extension offered by the Image Factory for one Talos version
+#[derive(Debug, Clone, Deserialize)]
+pub struct OfficialExtension {
+    /// e.g. `siderolabs/iscsi-tools`
+    pub name: String,
+    /// Image reference, tagged with the extension version
+    #[serde(rename = "ref")]
+    pub reference: String,
+}
+
+impl OfficialExtension {
+    fn version(&self) -> &str {
+        self.reference.rsplit_once(':').map_or("", |(_, tag)| tag)
+    }
+}
+
+/// Extension running on a node, as `talosctl get extensions` reports it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstalledExtension {
+    /// Name without author, e.g. `iscsi-tools`
+    pub name: String,
+    pub version: String,
+}
+
+/// Extensions of a node and the schematic its image was built from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NodeExtensions {
+    /// Image Factory schematic, if the node runs a factory image
+    pub schematic_id: Option<String>,
+    pub extensions: Vec<InstalledExtension>,
+}
+
+impl NodeExtensions {
+    /// Parse the JSON stream of `talosctl get extensions -o json`
+    pub fn parse(output: &str) -> Result<Self> {
+        #[derive(Deserialize)]
+        struct Resource {
+            spec: Spec,
+        }
+        #[derive(Deserialize)]
+        struct Spec {
+            metadata: Metadata,
+        }
+        #[derive(Deserialize)]
+        struct Metadata {
+            name: String,
+            #[serde(default)]
+            version: String,
+        }
+
+        let mut node = Self::default();
+        for resource in serde_json::Deserializer::from_str(output).into_iter::<Resource>() {
+            let metadata = resource
+                .context("Invalid talosctl extensions output")?
+                .spec
+                .metadata;
+            if metadata.name == SCHEMATIC_EXTENSION {
+                node.schematic_id = Some(metadata.version);
+            } else {
+                node.extensions.push(InstalledExtension {
+                    name: metadata.name,
+                    version: metadata.version,
+                });
+            }
+        }
+        Ok(node)
+    }
+
+    /// Whether the node runs anything but the vanilla Talos image
+    pub fn is_customized(&self) -> bool {
+        self.schematic_id.is_some() || !self.extensions.is_empty()
+    }
+
+    /// Official names of the installed extensions, for a new schematic
+    ///
+    /// Fails if an extension is not in the official list of the target
+    /// version, since the factory would build an image without it.
+    pub fn official_names(&self, official: &[OfficialExtension]) -> Result<Vec<String>> {
+        self.extensions
+            .iter()
+            .map(|installed| match find_official(official, &installed.name) {
+                Some(extension) => Ok(extension.name.clone()),
+                None => anyhow::bail!(
+                    "Extension {} is not an official extension of the target Talos version",
+                    installed.name
+                ),
+            })
+            .collect()
+    }
+
+    /// Extensions the target version drops or ships in another version
+    pub fn changes(&self, official: &[OfficialExtension]) -> Vec<String> {
+        self.extensions
+            .iter()
+            .filter_map(|installed| match find_official(official, &installed.name) {
+                None => Some(format!(
+                    "extension {} {} is not available for the new version",
+                    installed.name, installed.version
+                )),
+                Some(extension) if extension.version() != installed.version => Some(format!(
+                    "extension {} changes from {} to {}",
+                    installed.name,
+                    installed.version,
+                    extension.version()
+                )),
+                Some(_) => None,
+            })
+            .collect()
+    }
+}
+
+/// Official extension with the given name without author
+fn find_official<'a>(
+    official: &'a [OfficialExtension],
+    name: &str,
+) -> Option<&'a OfficialExtension> {
+    official
+        .iter()
+        .find(|extension| extension.name.rsplit('/').next() == Some(name))
+}
+
+impl FactoryImages {
+    /// Build image references for a schematic ID and Talos version
+    pub fn new(schematic_id: String, talos_version: &str) -> Self {
+        Self {
+            installer_image: format!(
+                "factory.talos.dev/installer/{}:{}",
+                schematic_id, talos_version
+            ),
+            hcloud_disk_image: format!(
+                "{}/image/{}/{}/hcloud-amd64.raw.xz",
+                IMAGE_FACTORY_BASE, schematic_id, talos_version
+            ),
+            schematic_id,
+        }
+    }
+
+    /// Machine config patch pinning the installer image to this schematic
+    ///
+    /// Without this, the next `talosctl upgrade` would install the vanilla
+    /// image and silently drop the extensions.
+    pub fn install_image_patch(&self) -> String {
+        format!(
+            "machine:\n  install:\n    image: {}\n",
+            self.installer_image
+        )
+    }
+}
+
+/// Render the schematic document for a set of official extensions
+///
+/// Extensions are sorted and deduplicated so the same set always hashes to the
+/// same schematic ID regardless of which addons requested them.
+fn schematic_yaml(extensions: &[String]) -> String {
+    let mut sorted: Vec<&str> = extensions.iter().map(String::as_str).collect();
+    sorted.sort_unstable();
+    sorted.dedup();
+
+    let mut yaml = String::from("customization:\n  systemExtensions:\n    officialExtensions:\n");
+    for extension in sorted {
+        yaml.push_str(&format!("      - {}\n", extension));
+    }
+    yaml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schematic_is_sorted_and_deduplicated() {
+        let extensions = vec![
+            "siderolabs/util-linux-tools".to_string(),
+            "siderolabs/iscsi-tools".to_string(),
+            "siderolabs/iscsi-tools".to_string(),
+        ];
+
+        assert_eq!(
+            schematic_yaml(&extensions),
+            "customization:\n  systemExtensions:\n    officialExtensions:\n      - siderolabs/iscsi-tools\n      - siderolabs/util-linux-tools\n"
+        );
+    }
+
+    #[test]
+    fn test_factory_image_references() {
+        let images = FactoryImages::new("abc123".to_string(), "v1.11.2");
+        assert_eq!(
+            images.installer_image,
+            "factory.talos.dev/installer/abc123:v1.11.2"
+        );
+        assert_eq!(
+            images.hcloud_disk_image,
+            "https://factory.talos.dev/image/abc123/v1.11.2/hcloud-amd64.raw.xz"
+        );
+        assert!(images
+            .install_image_patch()
+            .contains("image: factory.talos.dev/installer/abc123:v1.11.2"));
+    }
+
+    #[test]
+    fn test_node_extensions() {
+        let output = r#"{
+    "node": "10.0.1.2",
+    "metadata": { "namespace": "runtime", "type": "ExtensionStatuses.runtime.talos.dev", "id": "0" },
+    "spec": { "image": "000-iscsi-tools.sqsh", "metadata": { "name": "iscsi-tools", "version": "v0.1.6" } }
+}
+{
+    "node": "10.0.1.2",
+    "metadata": { "namespace": "runtime", "type": "ExtensionStatuses.runtime.talos.dev", "id": "1" },
+    "spec": { "image": "001-util-linux-tools.sqsh", "metadata": { "name": "util-linux-tools", "version": "2.40.4" } }
+}
+{
+    "node": "10.0.1.2",
+    "metadata": { "namespace": "runtime", "type": "ExtensionStatuses.runtime.talos.dev", "id": "2" },
+    "spec": { "image": "", "metadata": { "name": "schematic", "version": "abc123" } }
+}
+"#;
+        let node = NodeExtensions::parse(output).unwrap();
+        assert_eq!(node.schematic_id.as_deref(), Some("abc123"));
+        assert_eq!(node.extensions.len(), 2);
+        assert!(node.is_customized());
+
+        let official = vec![OfficialExtension {
+            name: "siderolabs/iscsi-tools".to_string(),
+            reference: "ghcr.io/siderolabs/iscsi-tools:v0.2.0".to_string(),
+        }];
+        let err = node.official_names(&official).unwrap_err();
+        assert!(err.to_string().contains("util-linux-tools"));
+        assert_eq!(
+            node.changes(&official),
+            [
+                "extension iscsi-tools changes from v0.1.6 to v0.2.0",
+                "extension util-linux-tools 2.40.4 is not available for the new version",
+            ]
+        );
+
+        assert!(!NodeExtensions::parse("").unwrap().is_customized());
+    }
+
+    #[test]
+    fn test_official_names() {
+        let node = NodeExtensions {
+            schematic_id: None,
+            extensions: vec![InstalledExtension {
+                name: "gvisor".to_string(),
+                version: "20240826.0".to_string(),
+            }],
+        };
+        let official = vec![
+            OfficialExtension {
+                name: "siderolabs/iscsi-tools".to_string(),
+                reference: "ghcr.io/siderolabs/iscsi-tools:v0.2.0".to_string(),
+            },
+            OfficialExtension {
+                name: "siderolabs/gvisor".to_string(),
+                reference: "ghcr.io/siderolabs/gvisor:20250505.0".to_string(),
+            },
+        ];
+        assert_eq!(
+            node.official_names(&official).unwrap(),
+            ["siderolabs/gvisor"]
+        );
+    }
+}
@@ -1,6 +1,9 @@
 /// Talos Linux cluster management
 pub mod client;
 pub mod config;
+pub mod diff;
+pub mod extensions;
+pub mod secrets;
 
-pub use client::TalosClient;
+pub use client::{ApplyMode, TalosClient};
 pub use config::TalosConfigGenerator;
@@ -0,0 +1,345 @@
+/// Storage of the Talos secrets bundle (`secrets.yaml`)
+use anyhow::{Context, Result};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use crate::audit;
+use crate::config::SecretsBackend;
+use crate::exit::Failure;
+use crate::utils::command::CommandBuilder;
+use crate::utils::platform::{restrict_to_owner, write_private};
+use crate::utils::prompt;
+
+/// Plaintext secrets bundle used by the file backend
+const SECRETS_FILE: &str = "secrets.yaml";
+
+/// Encrypted bundle written by the age backend
+const AGE_SECRETS_FILE: &str = "secrets.yaml.age";
+
+/// Working copy for backends that must not leave plaintext behind
+const TEMP_SECRETS_FILE: &str = ".secrets.yaml.tmp";
+
+/// Passphrase for the age backend
+const PASSPHRASE_ENV: &str = "OXIDE_SECRETS_PASSPHRASE";
+
+/// Reads and writes the secrets bundle through the configured backend
+pub struct SecretsStore {
+    backend: SecretsBackend,
+    output_dir: PathBuf,
+}
+
+/// Plaintext secrets bundle for talosctl, removed on drop if temporary
+pub struct SecretsFile {
+    path: PathBuf,
+    temporary: bool,
+}
+
+impl SecretsFile {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for SecretsFile {
+    fn drop(&mut self) {
+        if self.temporary {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+impl SecretsStore {
+    /// Create a store for the given backend
+    pub fn new(backend: SecretsBackend, output_dir: &Path) -> Self {
+        Self {
+            backend,
+            output_dir: output_dir.to_path_buf(),
+        }
+    }
+
+    /// Make the secrets available as a plaintext file, generating and storing
+    /// a new bundle if the backend does not have one yet
+    pub async fn materialize(&self) -> Result<SecretsFile> {
+        let file = self.working_file();
+        if self.fetch(file.path()).await? {
+            info!("Using existing cluster secrets from {}", self.describe());
+            return Ok(file);
+        }
+
+        info!("Generating new cluster secrets");
+        CommandBuilder::new("talosctl")
+            .args(["gen", "secrets", "--force", "--output-file"])
+            .arg(file.path())
+            .context("Failed to generate Talos secrets")
+            .run_silent()
+            .await?;
+        restrict_to_owner(file.path())?;
+
+        let result = self.store(file.path()).await;
+        audit::record("secrets.store", &self.describe(), &[], &result);
+        result?;
+
+        info!("✓ Cluster secrets stored in {}", self.describe());
+        Ok(file)
+    }
+
+    /// Make the stored secrets available as a plaintext file, failing if the
+    /// backend has none
+    ///
+    /// For existing clusters, whose nodes only accept configs signed with
+    /// their own PKI.
+    pub async fn load(&self) -> Result<SecretsFile> {
+        let file = self.working_file();
+        if !self.fetch(file.path()).await? {
+            anyhow::bail!(
+                "No cluster secrets found in {}; the configs of an existing cluster cannot be generated without them",
+                self.describe()
+            );
+        }
+        info!("Using existing cluster secrets from {}", self.describe());
+        Ok(file)
+    }
+
+    /// Plaintext file talosctl reads the secrets from
+    fn working_file(&self) -> SecretsFile {
+        match self.backend {
+            SecretsBackend::File => SecretsFile {
+                path: self.output_dir.join(SECRETS_FILE),
+                temporary: false,
+            },
+            _ => SecretsFile {
+                path: self.output_dir.join(TEMP_SECRETS_FILE),
+                temporary: true,
+            },
+        }
+    }
+
+    /// Human-readable location of the stored secrets
+    fn describe(&self) -> String {
+        match &self.backend {
+            SecretsBackend::File => self.output_dir.join(SECRETS_FILE).display().to_string(),
+            SecretsBackend::Age => self.output_dir.join(AGE_SECRETS_FILE).display().to_string(),
+            SecretsBackend::Vault { path } => format!("vault:{}", path),
+            SecretsBackend::S3 { uri, .. } => uri.clone(),
+            SecretsBackend::OnePassword { vault, item } => format!("1password:{}/{}", vault, item),
+        }
+    }
+
+    /// Copy the stored secrets to `path`; `Ok(false)` if none are stored yet
+    ///
+    /// Anything other than a clear "not found" is an error, so that an expired
+    /// login never leads to a fresh PKI being generated for an existing cluster.
+    async fn fetch(&self, path: &Path) -> Result<bool> {
+        match &self.backend {
+            SecretsBackend::File => Ok(path.exists()),
+            SecretsBackend::Age => {
+                let encrypted_path = self.output_dir.join(AGE_SECRETS_FILE);
+                if !encrypted_path.exists() {
+                    return Ok(false);
+                }
+                let encrypted = std::fs::read(&encrypted_path)?;
+                let plaintext = decrypt(&encrypted, &passphrase(false)?)
+                    .context(format!("Failed to decrypt {}", encrypted_path.display()))?;
+                write_private(path, &plaintext)?;
+                Ok(true)
+            }
+            SecretsBackend::Vault { path: secret_path } => {
+                let output = CommandBuilder::new("vault")
+                    .args(["kv", "get", "-field=secrets.yaml", secret_path.as_str()])
+                    .context("Failed to run vault")
+                    .output()
+                    .await?;
+                if output.success {
+                    write_private(path, output.stdout.as_bytes())?;
+                    Ok(true)
+                } else if output.stderr.contains("No value found") {
+                    Ok(false)
+                } else {
+                    anyhow::bail!("Failed to read secrets from Vault: {}", output.stderr)
+                }
+            }
+            SecretsBackend::S3 { uri, .. } => {
+                let output = CommandBuilder::new("aws")
+                    .args(["s3", "cp", "--only-show-errors", uri.as_str()])
+                    .arg(path)
+                    .context("Failed to run aws")
+                    .output()
+                    .await?;
+                if output.success {
+                    restrict_to_owner(path)?;
+                    Ok(true)
+                } else if output.stderr.contains("(404)") {
+                    Ok(false)
+                } else {
+                    anyhow::bail!("Failed to download secrets from {}: {}", uri, output.stderr)
+                }
+            }
+            SecretsBackend::OnePassword { vault, item } => {
+                let output = CommandBuilder::new("op")
+                    .args([
+                        "document",
+                        "get",
+                        item.as_str(),
+                        "--vault",
+                        vault.as_str(),
+                        "--force",
+                        "--out-file",
+                    ])
+                    .arg(path)
+                    .context("Failed to run op")
+                    .output()
+                    .await?;
+                if output.success {
+                    restrict_to_owner(path)?;
+                    Ok(true)
+                } else if output.stderr.contains("isn't an item") {
+                    Ok(false)
+                } else {
+                    anyhow::bail!("Failed to read secrets from 1Password: {}", output.stderr)
+                }
+            }
+        }
+    }
+
+    /// Save a newly generated bundle to the backend
+    async fn store(&self, path: &Path) -> Result<()> {
+        match &self.backend {
+            SecretsBackend::File => Ok(()),
+            SecretsBackend::Age => {
+                let plaintext = std::fs::read(path)?;
+                let encrypted = encrypt(&plaintext, &passphrase(true)?)?;
+                std::fs::write(self.output_dir.join(AGE_SECRETS_FILE), encrypted)?;
+                Ok(())
+            }
+            SecretsBackend::Vault { path: secret_path } => {
+                CommandBuilder::new("vault")
+                    .args(["kv", "put", secret_path.as_str()])
+                    .arg(format!("secrets.yaml=@{}", path.display()))
+                    .context("Failed to write secrets to Vault")
+                    .run_silent()
+                    .await
+            }
+            SecretsBackend::S3 { uri, kms_key_id } => {
+                let mut args = vec![
+                    "s3".to_string(),
+                    "cp".to_string(),
+                    "--only-show-errors".to_string(),
+                    path.display().to_string(),
+                    uri.clone(),
+                    "--sse".to_string(),
+                    "aws:kms".to_string(),
+                ];
+                if let Some(key) = kms_key_id {
+                    args.push("--sse-kms-key-id".to_string());
+                    args.push(key.clone());
+                }
+                CommandBuilder::new("aws")
+                    .args(&args)
+                    .context(format!("Failed to upload secrets to {}", uri))
+                    .run_silent()
+                    .await
+            }
+            SecretsBackend::OnePassword { vault, item } => {
+                CommandBuilder::new("op")
+                    .args(["document", "create"])
+                    .arg(path)
+                    .args(["--title", item.as_str(), "--vault", vault.as_str()])
+                    .context("Failed to write secrets to 1Password")
+                    .run_silent()
+                    .await
+            }
+        }
+    }
+}
+
+/// Passphrase for the age backend, from the environment or a prompt
+fn passphrase(confirm: bool) -> Result<String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+
+    if !prompt::is_available() {
+        anyhow::bail!(Failure::config(format!(
+            "{} must be set to use the age secrets backend non-interactively",
+            PASSPHRASE_ENV
+        )));
+    }
+
+    let mut prompt = dialoguer::Password::new().with_prompt("Cluster secrets passphrase");
+    if confirm {
+        prompt = prompt.with_confirmation("Repeat passphrase", "Passphrases do not match");
+    }
+    Ok(prompt.interact()?)
+}
+
+fn encrypt(plaintext: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let encryptor =
+        age::Encryptor::with_user_passphrase(age::secrecy::Secret::new(passphrase.to_string()));
+
+    let mut encrypted = Vec::new();
+    let mut writer = encryptor.wrap_output(&mut encrypted)?;
+    writer.write_all(plaintext)?;
+    writer.finish()?;
+    Ok(encrypted)
+}
+
+fn decrypt(encrypted: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let age::Decryptor::Passphrase(decryptor) = age::Decryptor::new(encrypted)? else {
+        anyhow::bail!("not a passphrase-encrypted age file");
+    };
+
+    let mut plaintext = Vec::new();
+    decryptor
+        .decrypt(&age::secrecy::Secret::new(passphrase.to_string()), None)?
+        .read_to_end(&mut plaintext)?;
+    Ok(plaintext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_age_round_trip() {
+        let encrypted = encrypt(b"cluster: secrets", "correct horse").unwrap();
+        assert_ne!(encrypted, b"cluster: secrets");
+
+        assert_eq!(
+            decrypt(&encrypted, "correct horse").unwrap(),
+            b"cluster: secrets"
+        );
+        assert!(decrypt(&encrypted, "wrong").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_file_backend_reuses_existing_secrets() {
+        let dir = std::env::temp_dir().join(format!("oxide-secrets-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(SECRETS_FILE), "existing").unwrap();
+
+        let store = SecretsStore::new(SecretsBackend::File, &dir);
+        let file = store.materialize().await.unwrap();
+        assert_eq!(std::fs::read_to_string(file.path()).unwrap(), "existing");
+
+        drop(file);
+        assert!(dir.join(SECRETS_FILE).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_load_never_generates_secrets() {
+        let dir = std::env::temp_dir().join(format!("oxide-secrets-load-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(SECRETS_FILE), "existing").unwrap();
+
+        let store = SecretsStore::new(SecretsBackend::File, &dir);
+        drop(store.load().await.unwrap());
+
+        // Existing clusters never get a new bundle
+        std::fs::remove_file(dir.join(SECRETS_FILE)).unwrap();
+        assert!(store.load().await.is_err());
+        assert!(!dir.join(SECRETS_FILE).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
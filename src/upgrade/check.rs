@@ -0,0 +1,401 @@
+/// `oxide upgrade check`: available Talos, Kubernetes and Cilium upgrades
+use anyhow::Result;
+use std::path::Path;
+use tracing::{info, warn};
+
+use crate::config::ClusterConfig;
+use crate::k8s::KubernetesClient;
+use crate::utils::command::CommandBuilder;
+use crate::utils::releases;
+
+/// `(major, minor, patch)` of a stable release
+type Version = (u64, u64, u64);
+
+/// Newest Kubernetes minor version each Cilium minor version is tested with
+const CILIUM_MAX_KUBERNETES: &[(u64, u64)] = &[(14, 27), (15, 29), (16, 30), (17, 32), (18, 33)];
+
+/// Talos minor versions whose Kubernetes support range is known
+const KNOWN_TALOS_MINORS: std::ops::RangeInclusive<u64> = 6..=11;
+
+/// Versions of one component
+#[derive(Debug)]
+pub struct Component {
+    pub name: &'static str,
+    pub configured: String,
+    pub running: Option<String>,
+    pub latest: Option<String>,
+    pub recommended: Option<String>,
+}
+
+/// Upgrade matrix for a cluster
+#[derive(Debug)]
+pub struct Report {
+    pub talos: Component,
+    pub kubernetes: Component,
+    pub cilium: Component,
+    pub notes: Vec<String>,
+}
+
+/// Build the upgrade matrix for `config`
+///
+/// Running versions are only looked up if `kubeconfig_path` exists.
+pub async fn run(config: &ClusterConfig, kubeconfig_path: &Path) -> Result<Report> {
+    let talos_releases = releases::list_releases(releases::TALOS_REPO).await?;
+    let kubernetes_releases = releases::list_releases(releases::KUBERNETES_REPO).await?;
+    let cilium_releases = releases::list_releases(releases::CILIUM_REPO).await?;
+
+    let running = if kubeconfig_path.exists() {
+        running_versions(kubeconfig_path).await
+    } else {
+        RunningVersions::default()
+    };
+
+    Ok(plan(
+        config,
+        running,
+        &talos_releases,
+        &kubernetes_releases,
+        &cilium_releases,
+    ))
+}
+
+impl Report {
+    /// Log the matrix and notes
+    pub fn print(&self) {
+        info!(
+            "{:<12} {:<12} {:<12} {:<12} {:<12}",
+            "COMPONENT", "CONFIGURED", "RUNNING", "LATEST", "RECOMMENDED"
+        );
+        for component in [&self.talos, &self.kubernetes, &self.cilium] {
+            info!(
+                "{:<12} {:<12} {:<12} {:<12} {:<12}",
+                component.name,
+                component.configured,
+                component.running.as_deref().unwrap_or("-"),
+                component.latest.as_deref().unwrap_or("-"),
+                component.recommended.as_deref().unwrap_or("up to date"),
+            );
+        }
+        for note in &self.notes {
+            warn!("⚠️  {}", note);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct RunningVersions {
+    talos: Option<String>,
+    kubernetes: Option<String>,
+    cilium: Option<String>,
+}
+
+/// Versions reported by the cluster; anything that cannot be read is left out
+async fn running_versions(kubeconfig_path: &Path) -> RunningVersions {
+    let kubectl = |args: &[&str]| {
+        CommandBuilder::new("kubectl")
+            .args(args)
+            .kubeconfig(kubeconfig_path)
+            .run()
+    };
+
+    let kubernetes = KubernetesClient::server_version(kubeconfig_path).await.ok();
+
+    // Mixed versions mean an upgrade is in progress; the oldest node is the
+    // one that limits the next step
+    let talos = kubectl(&[
+        "get",
+        "nodes",
+        "-o",
+        "jsonpath={.items[*].status.nodeInfo.osImage}",
+    ])
+    .await
+    .ok()
+    .and_then(|output| {
+        talos_os_versions(&output)
+            .into_iter()
+            .min_by_key(|v| parse(v))
+    });
+
+    RunningVersions {
+        talos,
+        kubernetes,
+        cilium: running_cilium_version(kubeconfig_path).await,
+    }
+}
+
+/// Cilium version of the agent DaemonSet image, if it can be read
+pub async fn running_cilium_version(kubeconfig_path: &Path) -> Option<String> {
+    CommandBuilder::new("kubectl")
+        .args([
+            "-n",
+            "kube-system",
+            "get",
+            "daemonset",
+            "cilium",
+            "-o",
+            "jsonpath={.spec.template.spec.containers[0].image}",
+        ])
+        .kubeconfig(kubeconfig_path)
+        .run()
+        .await
+        .ok()
+        .and_then(|image| image_tag(&image))
+}
+
+fn plan(
+    config: &ClusterConfig,
+    running: RunningVersions,
+    talos_releases: &[String],
+    kubernetes_releases: &[String],
+    cilium_releases: &[String],
+) -> Report {
+    let mut notes = Vec::new();
+    let current_talos = parse(&config.talos.version);
+    let current_kubernetes = parse(&config.talos.kubernetes_version);
+    let current_cilium = parse(&config.cilium.version);
+
+    // Talos first: a new Talos minor must still run the current Kubernetes
+    let talos_target = current_talos.and_then(|current| {
+        let kubernetes = current_kubernetes?;
+        recommend(current, talos_releases, |talos| {
+            talos_supports(talos, kubernetes).unwrap_or(false)
+        })
+    });
+
+    // Then Kubernetes, within what the (possibly upgraded) Talos supports
+    let talos_after = talos_target.or(current_talos);
+    let kubernetes_target = current_kubernetes.and_then(|current| {
+        let talos = talos_after?;
+        recommend(current, kubernetes_releases, |kubernetes| {
+            talos_supports(talos, kubernetes).unwrap_or(false)
+        })
+    });
+
+    let cilium_target =
+        current_cilium.and_then(|current| recommend(current, cilium_releases, |_| true));
+
+    if let Some(talos) = talos_after {
+        if !KNOWN_TALOS_MINORS.contains(&talos.1) {
+            notes.push(format!(
+                "Kubernetes support of Talos v{}.{} is unknown, check the Talos support matrix",
+                talos.0, talos.1
+            ));
+        }
+    }
+
+    let kubernetes_after = kubernetes_target.or(current_kubernetes);
+    let cilium_after = cilium_target.or(current_cilium);
+    if let (Some(kubernetes), Some(cilium)) = (kubernetes_after, cilium_after) {
+        match cilium_max_kubernetes(cilium) {
+            Some(max) if kubernetes.1 > max => notes.push(format!(
+                "Cilium {}.{} is only tested up to Kubernetes 1.{}",
+                cilium.0, cilium.1, max
+            )),
+            Some(_) => {}
+            None => notes.push(format!(
+                "Kubernetes support of Cilium {}.{} is unknown, check the Cilium docs",
+                cilium.0, cilium.1
+            )),
+        }
+    }
+
+    if let (Some(current), Some(latest)) = (current_kubernetes, newest(kubernetes_releases)) {
+        if latest.1 > current.1 + 1 {
+            notes.push(format!(
+                "Kubernetes {}.{}.{} is {} minor versions ahead; upgrade one minor version at a time",
+                latest.0,
+                latest.1,
+                latest.2,
+                latest.1 - current.1
+            ));
+        }
+    }
+
+    for (name, configured, running) in [
+        ("Talos", &config.talos.version, &running.talos),
+        (
+            "Kubernetes",
+            &config.talos.kubernetes_version,
+            &running.kubernetes,
+        ),
+        ("Cilium", &config.cilium.version, &running.cilium),
+    ] {
+        if let Some(running) = running {
+            if parse(running) != parse(configured) {
+                notes.push(format!(
+                    "{} runs {} but the config says {}",
+                    name, running, configured
+                ));
+            }
+        }
+    }
+
+    Report {
+        talos: Component {
+            name: "Talos",
+            configured: config.talos.version.clone(),
+            running: running.talos,
+            latest: newest(talos_releases).map(|v| format_version(v, "v")),
+            recommended: talos_target.map(|v| format_version(v, "v")),
+        },
+        kubernetes: Component {
+            name: "Kubernetes",
+            configured: config.talos.kubernetes_version.clone(),
+            running: running.kubernetes,
+            latest: newest(kubernetes_releases).map(|v| format_version(v, "v")),
+            recommended: kubernetes_target.map(|v| format_version(v, "v")),
+        },
+        cilium: Component {
+            name: "Cilium",
+            configured: config.cilium.version.clone(),
+            running: running.cilium,
+            latest: newest(cilium_releases).map(|v| format_version(v, "")),
+            recommended: cilium_target.map(|v| format_version(v, "")),
+        },
+        notes,
+    }
+}
+
+/// Next upgrade step from `current` among `releases` accepted by `allowed`
+///
+/// The latest patch of the next minor version if there is one, otherwise the
+/// latest patch of the current minor version. `None` if already up to date.
+fn recommend(
+    current: Version,
+    releases: &[String],
+    allowed: impl Fn(Version) -> bool,
+) -> Option<Version> {
+    let candidates: Vec<Version> = releases
+        .iter()
+        .filter_map(|r| parse(r))
+        .filter(|&v| v.0 == current.0 && v > current && allowed(v))
+        .collect();
+
+    let latest_of = |minor: u64| candidates.iter().filter(|v| v.1 == minor).max().copied();
+    latest_of(current.1 + 1).or_else(|| latest_of(current.1))
+}
+
+/// Whether a Talos release supports a Kubernetes release, if known
+///
+/// Talos 1.N supports Kubernetes 1.(N+18) through 1.(N+23).
+fn talos_supports(talos: Version, kubernetes: Version) -> Option<bool> {
+    if talos.0 != 1 || !KNOWN_TALOS_MINORS.contains(&talos.1) {
+        return None;
+    }
+    Some(kubernetes.0 == 1 && (talos.1 + 18..=talos.1 + 23).contains(&kubernetes.1))
+}
+
+fn cilium_max_kubernetes(cilium: Version) -> Option<u64> {
+    CILIUM_MAX_KUBERNETES
+        .iter()
+        .find(|(minor, _)| cilium.0 == 1 && *minor == cilium.1)
+        .map(|(_, max)| *max)
+}
+
+fn newest(releases: &[String]) -> Option<Version> {
+    releases.iter().filter_map(|r| parse(r)).max()
+}
+
+/// Parse `v1.11.2` or `1.11.2`; pre-releases are rejected
+pub fn parse(version: &str) -> Option<Version> {
+    let mut parts = version.trim().trim_start_matches('v').split('.');
+    let version = (
+        parts.next()?.parse().ok()?,
+        parts.next()?.parse().ok()?,
+        parts.next()?.parse().ok()?,
+    );
+    parts.next().is_none().then_some(version)
+}
+
+fn format_version(version: Version, prefix: &str) -> String {
+    format!("{}{}.{}.{}", prefix, version.0, version.1, version.2)
+}
+
+/// Versions from node OS images like `Talos (v1.11.2)`
+fn talos_os_versions(os_images: &str) -> Vec<String> {
+    os_images
+        .split("Talos (")
+        .skip(1)
+        .filter_map(|rest| rest.split(')').next())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Tag of an image reference like `quay.io/cilium/cilium:v1.17.8@sha256:...`
+fn image_tag(image: &str) -> Option<String> {
+    let name = image.split('@').next()?;
+    let (repository, tag) = name.rsplit_once(':')?;
+    // A colon before the last slash belongs to a registry port
+    (!tag.contains('/') && !repository.is_empty()).then(|| tag.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn releases(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn test_recommend_one_minor_at_a_time() {
+        let available = releases(&["v1.33.5", "v1.32.9", "v1.32.8", "v1.31.2", "v1.30.1"]);
+
+        assert_eq!(
+            recommend((1, 31, 0), &available, |_| true),
+            Some((1, 32, 9))
+        );
+        assert_eq!(
+            recommend((1, 31, 0), &available, |v| v.1 <= 31),
+            Some((1, 31, 2))
+        );
+        assert_eq!(recommend((1, 33, 5), &available, |_| true), None);
+    }
+
+    #[test]
+    fn test_talos_kubernetes_support() {
+        assert_eq!(talos_supports((1, 11, 2), (1, 34, 1)), Some(true));
+        assert_eq!(talos_supports((1, 11, 2), (1, 29, 0)), Some(true));
+        assert_eq!(talos_supports((1, 11, 2), (1, 28, 0)), Some(false));
+        assert_eq!(talos_supports((1, 12, 0), (1, 34, 0)), None);
+    }
+
+    #[test]
+    fn test_plan_upgrades_talos_before_kubernetes() {
+        let mut config = ClusterConfig::example();
+        config.talos.version = "v1.10.6".to_string();
+        config.talos.kubernetes_version = "1.33.4".to_string();
+        config.cilium.version = "1.17.8".to_string();
+
+        let report = plan(
+            &config,
+            RunningVersions::default(),
+            &releases(&["v1.11.2", "v1.10.7", "v1.10.6"]),
+            &releases(&["v1.34.1", "v1.33.5", "v1.33.4"]),
+            &releases(&["1.18.2", "1.17.8"]),
+        );
+
+        assert_eq!(report.talos.recommended.as_deref(), Some("v1.11.2"));
+        assert_eq!(report.kubernetes.recommended.as_deref(), Some("v1.34.1"));
+        assert_eq!(report.cilium.recommended.as_deref(), Some("1.18.2"));
+        assert!(report.notes.iter().any(|n| n.contains("tested up to")));
+    }
+
+    #[test]
+    fn test_parse_versions() {
+        assert_eq!(parse("v1.11.2"), Some((1, 11, 2)));
+        assert_eq!(parse("1.34.1"), Some((1, 34, 1)));
+        assert_eq!(parse("v1.12.0-beta.0"), None);
+        assert_eq!(parse("1.34"), None);
+
+        assert_eq!(
+            talos_os_versions("Talos (v1.11.2) Talos (v1.10.6)"),
+            vec!["v1.11.2", "v1.10.6"]
+        );
+        assert_eq!(
+            image_tag("quay.io/cilium/cilium:v1.17.8@sha256:abc").as_deref(),
+            Some("v1.17.8")
+        );
+        assert_eq!(image_tag("registry:5000/cilium/cilium"), None);
+    }
+}
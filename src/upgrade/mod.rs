@@ -0,0 +1,4 @@
+/// Cluster version upgrades
+pub mod check;
+pub mod rolling;
+pub mod schedule;
@@ -0,0 +1,467 @@
+/// Staged Talos and Kubernetes upgrades
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use super::check;
+use crate::config::{ClusterConfig, HealthGate};
+use crate::hcloud::server::{NodeRole, ServerInfo, ServerManager};
+use crate::k8s::{KubernetesClient, NodeManager};
+use crate::talos::extensions::{FactoryImages, ImageFactory};
+use crate::talos::{ApplyMode, TalosClient};
+use crate::utils::polling::PollingConfig;
+use crate::utils::progress;
+
+/// Time a node gets to go down after a config apply that reboots it, so the
+/// health gates do not pass on the state from before the reboot
+const REBOOT_GRACE: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// File in the output directory holding an unfinished upgrade
+const STATE_FILE: &str = "upgrade-state.json";
+
+/// Where a staged upgrade stops for manual verification
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum PausePoint {
+    /// After all control plane nodes run the new Talos version
+    ControlPlane,
+}
+
+/// Target of an upgrade, saved until it completes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpgradeState {
+    pub talos_version: String,
+    pub kubernetes_version: String,
+    #[serde(default)]
+    pub pause_after: Option<PausePoint>,
+    /// Only upgrade the nodes of this pool
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool: Option<String>,
+}
+
+impl UpgradeState {
+    /// Unfinished upgrade in `output_dir`, if any
+    pub fn load(output_dir: &Path) -> Result<Option<Self>> {
+        let path = output_dir.join(STATE_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content =
+            std::fs::read_to_string(&path).context(format!("Failed to read {}", path.display()))?;
+        let state = serde_json::from_str(&content)
+            .context(format!("Invalid upgrade state in {}", path.display()))?;
+        Ok(Some(state))
+    }
+
+    fn save(&self, output_dir: &Path) -> Result<()> {
+        let path = output_dir.join(STATE_FILE);
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)
+            .context(format!("Failed to write {}", path.display()))
+    }
+
+    fn clear(output_dir: &Path) -> Result<()> {
+        let path = output_dir.join(STATE_FILE);
+        if path.exists() {
+            std::fs::remove_file(&path).context(format!("Failed to remove {}", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+/// How a run of the upgrade ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Every node and Kubernetes run the target versions
+    Completed,
+    /// Stopped at the requested pause point
+    Paused,
+    /// Stopped before the next node because the maintenance window closed
+    WindowClosed,
+}
+
+/// Node-by-node upgrade runner
+pub struct RollingUpgrade {
+    talos: TalosClient,
+    output_dir: PathBuf,
+    kubeconfig_path: PathBuf,
+    health_gates: Vec<HealthGate>,
+    node_upgrade_timeout: u64,
+    node_ready_timeout: u64,
+    health_gates_timeout: u64,
+    deadline: Option<DateTime<Utc>>,
+}
+
+impl RollingUpgrade {
+    /// Create a runner using the talosconfig and kubeconfig in `output_dir`
+    pub fn new(output_dir: &Path, config: &ClusterConfig) -> Self {
+        Self {
+            talos: TalosClient::new(output_dir.join("talosconfig")),
+            output_dir: output_dir.to_path_buf(),
+            kubeconfig_path: output_dir.join("kubeconfig"),
+            health_gates: config.upgrade.health_gates.clone(),
+            node_upgrade_timeout: config.timeouts.node_upgrade,
+            node_ready_timeout: config.timeouts.node_ready,
+            health_gates_timeout: config.timeouts.health_gates,
+            deadline: None,
+        }
+    }
+
+    /// Start no further node upgrade after `deadline`
+    ///
+    /// The state stays saved, so a later run continues where this one stopped.
+    pub fn with_deadline(mut self, deadline: Option<DateTime<Utc>>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Upgrade `servers` (or the pool in `state`) and then Kubernetes to the
+    /// versions in `state`
+    pub async fn run(&self, state: &UpgradeState, servers: &[ServerInfo]) -> Result<Outcome> {
+        // Saved up front so that a failed gate leaves something to resume
+        state.save(&self.output_dir)?;
+
+        let mut nodes = Vec::new();
+        for server_info in servers {
+            let ip = ServerManager::talos_ip(&server_info.server).context(format!(
+                "Node {} does not have an IP",
+                server_info.server.name
+            ))?;
+            nodes.push((server_info, ip));
+        }
+        nodes.sort_by_key(|(s, _)| (s.role != NodeRole::ControlPlane, s.server.name.clone()));
+
+        let control_plane_ips: Vec<String> = nodes
+            .iter()
+            .filter(|(s, _)| s.role == NodeRole::ControlPlane)
+            .map(|(_, ip)| ip.clone())
+            .collect();
+        let first_control_plane = control_plane_ips
+            .first()
+            .context("No control plane nodes found")?
+            .clone();
+
+        let selected: Vec<String> = match &state.pool {
+            Some(pool) => [NodeRole::ControlPlane, NodeRole::Worker]
+                .into_iter()
+                .flat_map(|role| ServerManager::filter_by_role_and_pool(servers, role, Some(pool)))
+                .map(|s| s.server.name)
+                .collect(),
+            None => servers.iter().map(|s| s.server.name.clone()).collect(),
+        };
+        if selected.is_empty() {
+            anyhow::bail!(
+                "No servers found in pool {}",
+                state.pool.as_deref().unwrap_or_default()
+            );
+        }
+
+        for (server_info, ip) in &nodes {
+            if !selected.contains(&server_info.server.name) {
+                continue;
+            }
+            if server_info.role == NodeRole::Worker
+                && state.pause_after == Some(PausePoint::ControlPlane)
+            {
+                return self.pause(state);
+            }
+            if self.deadline.is_some_and(|deadline| Utc::now() >= deadline) {
+                warn!(
+                    "⚠️  Maintenance window closed before {}; the upgrade continues in the next one",
+                    server_info.server.name
+                );
+                return Ok(Outcome::WindowClosed);
+            }
+            self.upgrade_node(server_info, ip, &state.talos_version, &control_plane_ips)
+                .await?;
+        }
+
+        let running = KubernetesClient::server_version(&self.kubeconfig_path).await?;
+        if let Some(pool) = &state.pool {
+            if !same_version(&running, &state.kubernetes_version) {
+                warn!(
+                    "⚠️  Kubernetes stays at {}: it can only be upgraded cluster-wide, not for pool {}",
+                    running, pool
+                );
+            }
+        } else if same_version(&running, &state.kubernetes_version) {
+            info!("✓ Kubernetes already at {}", running);
+        } else {
+            let phase = progress::phase("Kubernetes");
+            self.talos
+                .upgrade_kubernetes(&first_control_plane, &state.kubernetes_version)
+                .await?;
+            NodeManager::wait_for_all_nodes_ready(&self.kubeconfig_path, self.node_ready_timeout)
+                .await?;
+            if self.health_gates.contains(&HealthGate::Etcd) {
+                self.wait_for_etcd(&control_plane_ips).await?;
+            }
+            phase.finish();
+        }
+
+        UpgradeState::clear(&self.output_dir)?;
+        Ok(Outcome::Completed)
+    }
+
+    /// Save the state without the pause point, so resuming continues past it
+    fn pause(&self, state: &UpgradeState) -> Result<Outcome> {
+        UpgradeState {
+            pause_after: None,
+            ..state.clone()
+        }
+        .save(&self.output_dir)?;
+        Ok(Outcome::Paused)
+    }
+
+    /// Upgrade Talos on one node unless it already runs the target version
+    async fn upgrade_node(
+        &self,
+        server_info: &ServerInfo,
+        ip: &str,
+        target: &str,
+        control_plane_ips: &[String],
+    ) -> Result<()> {
+        let name = &server_info.server.name;
+
+        let current = self.talos.node_version(ip).await?;
+        if same_version(&current, target) {
+            info!("✓ {} already runs Talos {}", name, current);
+            return Ok(());
+        }
+        // e.g. a canary pool ahead of the config after a plain `oxide upgrade`
+        if let (Some(running), Some(wanted)) = (check::parse(&current), check::parse(target)) {
+            if running > wanted {
+                anyhow::bail!(
+                    "{} runs Talos {}, which is newer than {}; refusing to downgrade",
+                    name,
+                    current,
+                    target
+                );
+            }
+        }
+
+        let image = self.installer_for(ip, name, target).await?;
+        let phase = progress::phase(&format!("Upgrade {}", name));
+        self.talos
+            .upgrade_node(ip, name, &image, self.node_upgrade_timeout)
+            .await?;
+
+        let upgraded = self.talos.node_version(ip).await?;
+        if !same_version(&upgraded, target) {
+            anyhow::bail!(
+                "{} still runs Talos {} after the upgrade to {}; the upgrade may have been rolled back",
+                name,
+                upgraded,
+                target
+            );
+        }
+
+        self.check_gates(name, server_info.role, control_plane_ips)
+            .await
+            .context(format!(
+                "Health gates failed after upgrading {}; fix the problem, then run `oxide upgrade resume`",
+                name
+            ))?;
+        phase.finish();
+
+        info!("✓ {} upgraded from Talos {} to {}", name, current, upgraded);
+        Ok(())
+    }
+
+    /// Apply a machine config to one node and wait for the health gates
+    pub async fn apply_config(
+        &self,
+        server_info: &ServerInfo,
+        ip: &str,
+        config_path: &Path,
+        mode: ApplyMode,
+        control_plane_ips: &[String],
+    ) -> Result<()> {
+        let name = &server_info.server.name;
+        let phase = progress::phase(&format!("Apply config {}", name));
+
+        let outcome = self
+            .talos
+            .apply_config(ip, name, config_path, mode, false)
+            .await?;
+        if mode == ApplyMode::Reboot || outcome.contains("with a reboot") {
+            info!("{} is rebooting into the new config", name);
+            tokio::time::sleep(REBOOT_GRACE).await;
+            self.talos
+                .wait_for_nodes_booted(std::slice::from_ref(server_info), self.node_upgrade_timeout)
+                .await?;
+        }
+
+        self.check_gates(name, server_info.role, control_plane_ips)
+            .await
+            .context(format!(
+                "Health gates failed after applying the config to {}; fix the problem, then rerun `oxide config apply`",
+                name
+            ))?;
+        phase.finish();
+
+        info!("✓ Applied the new machine config to {}", name);
+        Ok(())
+    }
+
+    /// Wait for the node and every configured health gate
+    async fn check_gates(
+        &self,
+        node_name: &str,
+        role: NodeRole,
+        control_plane_ips: &[String],
+    ) -> Result<()> {
+        NodeManager::wait_for_node_ready(&self.kubeconfig_path, node_name, self.node_ready_timeout)
+            .await?;
+
+        for gate in &self.health_gates {
+            match gate {
+                HealthGate::PodsReady => {
+                    self.wait_for_pods(
+                        node_name,
+                        None,
+                        format!("Waiting for all pods on {} to be Ready", node_name),
+                    )
+                    .await?
+                }
+                HealthGate::Etcd if role == NodeRole::ControlPlane => {
+                    self.wait_for_etcd(control_plane_ips).await?
+                }
+                HealthGate::Etcd => {}
+                HealthGate::Cilium => {
+                    self.wait_for_pods(
+                        node_name,
+                        Some("k8s-app=cilium"),
+                        format!("Waiting for the Cilium agent on {} to be Ready", node_name),
+                    )
+                    .await?
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Wait until there are pods on the node and all of them are Ready
+    async fn wait_for_pods(
+        &self,
+        node_name: &str,
+        selector: Option<&str>,
+        description: String,
+    ) -> Result<()> {
+        PollingConfig::new(self.health_gates_timeout, 5, description)
+            .poll_until(|| async {
+                // The API may briefly be unavailable while a control plane reboots
+                let pods = NodeManager::pod_readiness(&self.kubeconfig_path, node_name, selector)
+                    .await
+                    .unwrap_or_default();
+                Ok(!pods.is_empty() && pods.iter().all(|(_, ready)| *ready))
+            })
+            .await
+    }
+
+    async fn wait_for_etcd(&self, control_plane_ips: &[String]) -> Result<()> {
+        PollingConfig::new(
+            self.health_gates_timeout,
+            5,
+            "Waiting for all etcd members to be healthy",
+        )
+        .poll_until(|| async { Ok(self.talos.etcd_healthy(control_plane_ips).await) })
+        .await
+    }
+}
+
+impl RollingUpgrade {
+    /// Installer image for `target` with the extensions the node runs
+    ///
+    /// Nodes booted from an Image Factory image keep their schematic, so the
+    /// new version gets the same extension set. For other nodes with
+    /// extensions a schematic is resolved from the installed ones. Extensions
+    /// the target version drops or ships in another version are warned about.
+    async fn installer_for(&self, ip: &str, name: &str, target: &str) -> Result<String> {
+        let installed = self.talos.extensions(ip).await?;
+        if !installed.is_customized() {
+            return Ok(installer_image(target));
+        }
+
+        let factory = ImageFactory::default();
+        let version = format!("v{}", target.trim_start_matches('v'));
+        let official = factory.official_extensions(&version).await;
+        match official {
+            Ok(ref official) => {
+                for change in installed.changes(official) {
+                    warn!("{}: {} (Talos {})", name, change, version);
+                }
+            }
+            Err(ref e) => warn!(
+                "Could not check the extensions of {} against Talos {}: {:#}",
+                name, version, e
+            ),
+        }
+
+        let images = match installed.schematic_id {
+            Some(ref schematic_id) => FactoryImages::new(schematic_id.clone(), &version),
+            None => {
+                let names = installed
+                    .official_names(&official?)
+                    .with_context(|| format!("Cannot keep the extensions of {}", name))?;
+                factory.resolve(&names, &version).await?
+            }
+        };
+        info!(
+            "{} keeps its extensions with schematic {}",
+            name, images.schematic_id
+        );
+        Ok(images.installer_image)
+    }
+}
+
+/// Official installer image for a Talos version
+fn installer_image(talos_version: &str) -> String {
+    format!(
+        "ghcr.io/siderolabs/installer:v{}",
+        talos_version.trim_start_matches('v')
+    )
+}
+
+/// Whether two versions are equal, ignoring a `v` prefix
+pub fn same_version(a: &str, b: &str) -> bool {
+    a.trim().trim_start_matches('v') == b.trim().trim_start_matches('v')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("oxide-upgrade-state-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        assert_eq!(UpgradeState::load(&dir).unwrap(), None);
+
+        let state = UpgradeState {
+            talos_version: "v1.11.2".to_string(),
+            kubernetes_version: "1.34.1".to_string(),
+            pause_after: Some(PausePoint::ControlPlane),
+            pool: None,
+        };
+        state.save(&dir).unwrap();
+        assert_eq!(UpgradeState::load(&dir).unwrap(), Some(state));
+
+        UpgradeState::clear(&dir).unwrap();
+        assert_eq!(UpgradeState::load(&dir).unwrap(), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_versions() {
+        assert_eq!(
+            installer_image("1.11.2"),
+            "ghcr.io/siderolabs/installer:v1.11.2"
+        );
+        assert!(same_version("v1.34.1", "1.34.1"));
+        assert!(!same_version("v1.34.1", "1.34.0"));
+    }
+}
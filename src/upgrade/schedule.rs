@@ -0,0 +1,185 @@
+/// Scheduled Talos patch upgrades
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Duration, DurationRound, Timelike, Utc};
+
+use super::check;
+
+/// Minutes searched for the next match, a bit over four years so that
+/// expressions like `0 0 29 2 *` still find one
+const SEARCH_LIMIT_MINUTES: i64 = 4 * 366 * 24 * 60 + 24 * 60;
+
+/// Five-field cron expression (minute, hour, day of month, month, day of
+/// week), evaluated in UTC
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    minutes: Vec<u32>,
+    hours: Vec<u32>,
+    days_of_month: Vec<u32>,
+    months: Vec<u32>,
+    days_of_week: Vec<u32>,
+    /// Day of month and day of week match either way when both are
+    /// restricted, as in cron
+    any_day: bool,
+}
+
+impl Cron {
+    /// Parse an expression like `0 3 * * 6` or `30 2 1-7 * 0`
+    pub fn parse(expression: &str) -> Result<Self> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+            anyhow::bail!(
+                "Invalid cron expression '{}': expected 5 fields (minute hour day-of-month month day-of-week)",
+                expression
+            );
+        };
+        let context = || format!("Invalid cron expression '{}'", expression);
+
+        // Sunday is 0 or 7
+        let mut days_of_week = parse_field(day_of_week, 0, 7).with_context(context)?;
+        if days_of_week.contains(&7) {
+            days_of_week.retain(|&day| day != 7);
+            if !days_of_week.contains(&0) {
+                days_of_week.insert(0, 0);
+            }
+        }
+
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59).with_context(context)?,
+            hours: parse_field(hour, 0, 23).with_context(context)?,
+            days_of_month: parse_field(day_of_month, 1, 31).with_context(context)?,
+            months: parse_field(month, 1, 12).with_context(context)?,
+            days_of_week,
+            any_day: day_of_month != "*" && day_of_week != "*",
+        })
+    }
+
+    /// Whether the expression matches the minute of `time`
+    pub fn matches(&self, time: DateTime<Utc>) -> bool {
+        let day_of_month = self.days_of_month.contains(&time.day());
+        let day_of_week = self
+            .days_of_week
+            .contains(&time.weekday().num_days_from_sunday());
+        let day = if self.any_day {
+            day_of_month || day_of_week
+        } else {
+            day_of_month && day_of_week
+        };
+        day && self.minutes.contains(&time.minute())
+            && self.hours.contains(&time.hour())
+            && self.months.contains(&time.month())
+    }
+
+    /// First matching minute after `time`
+    pub fn next_after(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = time.duration_trunc(Duration::minutes(1)).ok()? + Duration::minutes(1);
+        (0..SEARCH_LIMIT_MINUTES)
+            .map(|minute| start + Duration::minutes(minute))
+            .find(|&candidate| self.matches(candidate))
+    }
+}
+
+/// Values of one field: `*`, `5`, `1-5`, `*/15`, `0-30/10` or a list of them
+fn parse_field(field: &str, min: u32, max: u32) -> Result<Vec<u32>> {
+    let mut values = Vec::new();
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|&step| step > 0)
+                    .context(format!("invalid step in '{}'", part))?,
+            ),
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (parse_value(start, part)?, parse_value(end, part)?)
+        } else {
+            let value = parse_value(range, part)?;
+            // `5/10` means from 5 to the end in steps of 10
+            (value, if step > 1 { max } else { value })
+        };
+        if start < min || end > max || start > end {
+            anyhow::bail!("'{}' is outside {}-{}", part, min, max);
+        }
+        values.extend((start..=end).step_by(step as usize));
+    }
+    values.sort_unstable();
+    values.dedup();
+    Ok(values)
+}
+
+fn parse_value(value: &str, part: &str) -> Result<u32> {
+    value
+        .parse()
+        .context(format!("invalid value '{}' in '{}'", value, part))
+}
+
+/// Newest patch release of the minor version of `current`, if newer
+pub fn patch_target(current: &str, releases: &[String]) -> Option<String> {
+    let current = check::parse(current)?;
+    releases
+        .iter()
+        .filter_map(|release| check::parse(release))
+        .filter(|version| version.0 == current.0 && version.1 == current.1 && *version > current)
+        .max()
+        .map(|version| format!("v{}.{}.{}", version.0, version.1, version.2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_cron() {
+        // Saturdays at 03:00
+        let cron = Cron::parse("0 3 * * 6").unwrap();
+        assert_eq!(
+            cron.next_after(at("2025-03-05T12:00:00Z")),
+            Some(at("2025-03-08T03:00:00Z"))
+        );
+        assert_eq!(
+            cron.next_after(at("2025-03-08T03:00:00Z")),
+            Some(at("2025-03-15T03:00:00Z"))
+        );
+
+        let cron = Cron::parse("*/20 1-2 * * *").unwrap();
+        assert_eq!(
+            cron.next_after(at("2025-03-05T01:45:10Z")),
+            Some(at("2025-03-05T02:00:00Z"))
+        );
+
+        // Day of month and day of week match either way, Sunday also as 7
+        let cron = Cron::parse("0 0 1 * 7").unwrap();
+        assert!(cron.matches(at("2025-04-01T00:00:00Z")));
+        assert!(cron.matches(at("2025-03-09T00:00:00Z")));
+        assert!(!cron.matches(at("2025-03-10T00:00:00Z")));
+
+        assert!(Cron::parse("0 3 * *").is_err());
+        assert!(Cron::parse("60 3 * * *").is_err());
+        assert!(Cron::parse("0 3 * * */0").is_err());
+        assert!(Cron::parse("0 5-3 * * *").is_err());
+    }
+
+    #[test]
+    fn test_patch_target() {
+        let releases: Vec<String> = ["v1.11.3", "v1.11.5", "v1.12.0", "v1.10.9"]
+            .iter()
+            .map(|release| release.to_string())
+            .collect();
+        assert_eq!(
+            patch_target("v1.11.2", &releases),
+            Some("v1.11.5".to_string())
+        );
+        assert_eq!(patch_target("v1.11.5", &releases), None);
+        assert_eq!(patch_target("v1.12.0", &releases), None);
+    }
+}
@@ -3,7 +3,9 @@ use anyhow::{Context, Result};
 use std::ffi::OsStr;
 use std::path::Path;
 use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::Command;
+use tracing::info;
 
 /// Result from command execution with captured output
 pub struct CommandOutput {
@@ -35,22 +37,29 @@ impl CommandOutput {
 /// Builder for executing external commands with common patterns
 pub struct CommandBuilder {
     command: Command,
+    program: String,
     context_msg: Option<String>,
+    stdin_data: Option<String>,
 }
 
 impl CommandBuilder {
     /// Create a new command builder
     pub fn new<S: AsRef<OsStr>>(program: S) -> Self {
-        let mut command = Command::new(program);
+        let mut command = Command::new(&program);
         command.stdout(Stdio::piped()).stderr(Stdio::piped());
         Self {
             command,
+            program: Path::new(program.as_ref())
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
             context_msg: None,
+            stdin_data: None,
         }
     }
 
     /// Add a single argument
-    #[allow(dead_code)]
     pub fn arg<S: AsRef<OsStr>>(mut self, arg: S) -> Self {
         self.command.arg(arg);
         self
@@ -81,6 +90,12 @@ impl CommandBuilder {
         self.env("KUBECONFIG", path)
     }
 
+    /// Pipe the given data to the command's stdin
+    pub fn stdin<S: Into<String>>(mut self, data: S) -> Self {
+        self.stdin_data = Some(data.into());
+        self
+    }
+
     /// Set context message for error reporting
     pub fn context<S: Into<String>>(mut self, msg: S) -> Self {
         self.context_msg = Some(msg.into());
@@ -89,25 +104,107 @@ impl CommandBuilder {
 
     /// Execute and return raw output
     pub async fn output(mut self) -> Result<CommandOutput> {
+        let result = match self.stdin_data.take() {
+            Some(data) => Self::output_with_stdin(&mut self.command, data).await,
+            None => self.command.output().await,
+        };
+
         let output = if let Some(ctx) = &self.context_msg {
-            self.command.output().await.context(ctx.clone())?
+            result.context(ctx.clone())?
         } else {
-            self.command.output().await?
+            result?
         };
         Ok(CommandOutput::from_output(output))
     }
 
+    /// Spawn the command, feed stdin, and collect its output
+    async fn output_with_stdin(
+        command: &mut Command,
+        data: String,
+    ) -> std::io::Result<std::process::Output> {
+        use tokio::io::AsyncWriteExt;
+
+        let mut child = command.stdin(Stdio::piped()).spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(data.as_bytes()).await?;
+            // Dropping stdin closes the pipe so the child sees EOF
+        }
+        child.wait_with_output().await
+    }
+
     /// Execute and return stdout on success, error on failure
     pub async fn run(self) -> Result<String> {
         self.output().await?.into_result()
     }
 
+    /// Execute while forwarding each stdout/stderr line to the log as it arrives
+    ///
+    /// Meant for long-running tools (helm, talosctl upgrade, rollout waits) that
+    /// would otherwise look hung. Lines are prefixed with the tool name, and the
+    /// captured stdout is returned on success or the stderr used as the error.
+    pub async fn run_streaming(mut self) -> Result<String> {
+        let stdin_data = self.stdin_data.take();
+        if stdin_data.is_some() {
+            self.command.stdin(Stdio::piped());
+        }
+
+        let spawned = self.command.spawn();
+        let mut child = match &self.context_msg {
+            Some(ctx) => spawned.context(ctx.clone())?,
+            None => spawned?,
+        };
+
+        if let (Some(data), Some(mut stdin)) = (stdin_data, child.stdin.take()) {
+            use tokio::io::AsyncWriteExt;
+            stdin.write_all(data.as_bytes()).await?;
+        }
+
+        let stdout = child.stdout.take().context("Failed to capture stdout")?;
+        let stderr = child.stderr.take().context("Failed to capture stderr")?;
+
+        let (stdout, stderr, status) = tokio::join!(
+            forward_lines(stdout, &self.program),
+            forward_lines(stderr, &self.program),
+            child.wait()
+        );
+
+        let output = CommandOutput {
+            stdout: stdout?,
+            stderr: stderr?,
+            success: status?.success(),
+        };
+
+        match &self.context_msg {
+            Some(ctx) => output.into_result().context(ctx.clone()),
+            None => output.into_result(),
+        }
+    }
+
     /// Execute and ignore output (just check success)
     pub async fn run_silent(self) -> Result<()> {
         self.output().await?.into_result().map(|_| ())
     }
 }
 
+/// Log each line of a child stream and return everything that was read
+async fn forward_lines<R>(stream: R, prefix: &str) -> Result<String>
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    let mut lines = BufReader::new(stream).lines();
+    let mut captured = String::new();
+
+    while let Some(line) = lines.next_line().await? {
+        if !line.trim().is_empty() {
+            info!("[{}] {}", prefix, line);
+        }
+        captured.push_str(&line);
+        captured.push('\n');
+    }
+
+    Ok(captured)
+}
+
 /// Check if a command-line tool is installed
 pub async fn check_tool_installed(
     tool_name: &str,
@@ -129,13 +226,13 @@ pub async fn check_tool_installed(
     }
 }
 
-#[cfg(test)]
+// The tests spawn unix programs (`echo`, `cat`, `sh`)
+#[cfg(all(test, unix))]
 mod tests {
     use super::*;
 
     #[tokio::test]
     async fn test_command_builder_basic() {
-        // Test with a simple command that should exist on all systems
         let result = CommandBuilder::new("echo")
             .arg("test")
             .context("Testing echo command")
@@ -148,6 +245,34 @@ mod tests {
         assert!(output.stdout.contains("test"));
     }
 
+    #[tokio::test]
+    async fn test_command_builder_stdin() {
+        let output = CommandBuilder::new("cat")
+            .stdin("piped input")
+            .run()
+            .await
+            .unwrap();
+
+        assert_eq!(output, "piped input");
+    }
+
+    #[tokio::test]
+    async fn test_command_builder_streaming() {
+        let output = CommandBuilder::new("sh")
+            .args(["-c", "echo first; echo second"])
+            .run_streaming()
+            .await
+            .unwrap();
+        assert_eq!(output, "first\nsecond\n");
+
+        let err = CommandBuilder::new("sh")
+            .args(["-c", "echo progress; echo boom >&2; exit 1"])
+            .run_streaming()
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("boom"));
+    }
+
     #[tokio::test]
     async fn test_command_builder_env() {
         let result = CommandBuilder::new("sh")
@@ -0,0 +1,82 @@
+/// Dynamic shell completion helpers
+use clap_complete::engine::CompletionCandidate;
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+
+use crate::config::ClusterConfig;
+
+/// Config file used when the command line does not name one
+const DEFAULT_CONFIG: &str = "cluster.yaml";
+
+/// Complete node pool names from the cluster config
+pub fn pool_names(current: &OsStr) -> Vec<CompletionCandidate> {
+    let Some(current) = current.to_str() else {
+        return Vec::new();
+    };
+
+    let paths = config_paths(std::env::args_os());
+    let Ok(config) = ClusterConfig::from_files(&paths) else {
+        return Vec::new();
+    };
+
+    config
+        .control_planes
+        .iter()
+        .chain(config.workers.iter())
+        .filter(|pool| pool.name.starts_with(current))
+        .map(|pool| {
+            CompletionCandidate::new(&pool.name).help(Some(pool.server_type.clone().into()))
+        })
+        .collect()
+}
+
+/// Find the `-c/--config` values in the partial command line being completed
+fn config_paths<I: IntoIterator<Item = OsString>>(args: I) -> Vec<PathBuf> {
+    let args: Vec<String> = args
+        .into_iter()
+        .map(|arg| arg.to_string_lossy().to_string())
+        .collect();
+
+    let mut paths = Vec::new();
+    for (i, arg) in args.iter().enumerate() {
+        if let Some(value) = arg.strip_prefix("--config=") {
+            paths.push(PathBuf::from(value));
+        } else if arg == "-c" || arg == "--config" {
+            if let Some(value) = args.get(i + 1) {
+                paths.push(PathBuf::from(value));
+            }
+        }
+    }
+
+    if paths.is_empty() {
+        paths.push(PathBuf::from(DEFAULT_CONFIG));
+    }
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<OsString> {
+        line.split_whitespace().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn test_config_paths_from_args() {
+        assert_eq!(
+            config_paths(args("oxide -- oxide scale worker --pool")),
+            vec![PathBuf::from("cluster.yaml")]
+        );
+        assert_eq!(
+            config_paths(args(
+                "oxide -- oxide -c base.yaml -c prod.yaml scale --pool"
+            )),
+            vec![PathBuf::from("base.yaml"), PathBuf::from("prod.yaml")]
+        );
+        assert_eq!(
+            config_paths(args("oxide -- oxide --config=dev.yaml scale")),
+            vec![PathBuf::from("dev.yaml")]
+        );
+    }
+}
@@ -0,0 +1,651 @@
+/// Helm helpers shared by Cilium and the addon installers
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use super::command::CommandBuilder;
+use crate::audit;
+use crate::config::HelmMode;
+use crate::k8s::resources::manifest_objects;
+use crate::k8s::ResourceManager;
+use crate::state::{ClusterState, ReleaseRecord};
+
+/// Add a Helm chart repository and refresh the local index
+///
+/// Re-adding an existing repository is not treated as an error so that
+/// repeated runs (e.g. re-running `oxide create`) stay idempotent.
+pub async fn add_repo(kubeconfig_path: &Path, name: &str, url: &str) -> Result<()> {
+    let output = CommandBuilder::new("helm")
+        .args(["repo", "add", name, url])
+        .kubeconfig(kubeconfig_path)
+        .context(format!("Failed to add {} Helm repo", name))
+        .output()
+        .await?;
+
+    if !output.success && !output.stderr.contains("already exists") {
+        anyhow::bail!("Failed to add Helm repo {}: {}", name, output.stderr);
+    }
+
+    CommandBuilder::new("helm")
+        .args(["repo", "update", name])
+        .kubeconfig(kubeconfig_path)
+        .context("Failed to update Helm repos")
+        .run_silent()
+        .await
+}
+
+/// A chart oxide installs, with the values computed from the config
+#[derive(Debug, Clone)]
+pub struct Chart {
+    /// Release name, e.g. `cilium`
+    pub release: String,
+    /// Name of the chart repository, e.g. `cilium`
+    pub repo_name: String,
+    pub repo_url: String,
+    /// Chart name within the repository
+    pub name: String,
+    pub version: String,
+    pub namespace: String,
+    /// `--set` values; commas and dots in values are escaped the way the Helm
+    /// CLI expects
+    pub values: Vec<String>,
+    /// Whether the namespace is exempt from Pod Security admission, for
+    /// workloads that need host access
+    pub privileged: bool,
+    /// Local chart archive installed instead of the repository chart, from
+    /// an offline bundle
+    pub archive: Option<PathBuf>,
+}
+
+impl Chart {
+    /// `repo/chart`, or the full `oci://` URL of a chart in an OCI registry
+    pub fn reference(&self) -> String {
+        if self.is_oci() {
+            return format!("{}/{}", self.repo_url.trim_end_matches('/'), self.name);
+        }
+        format!("{}/{}", self.repo_name, self.name)
+    }
+
+    /// Whether the chart is pulled from an OCI registry, which needs no
+    /// `helm repo add`
+    fn is_oci(&self) -> bool {
+        self.repo_url.starts_with("oci://")
+    }
+
+    /// What `helm install` and `helm template` are given: the archive if
+    /// there is one, else the repository chart
+    fn source(&self) -> String {
+        match &self.archive {
+            Some(archive) => archive.display().to_string(),
+            None => self.reference(),
+        }
+    }
+}
+
+/// Add the repository of a chart, unless it is installed from an archive or
+/// an OCI registry
+pub async fn add_chart_repo(kubeconfig_path: &Path, chart: &Chart) -> Result<()> {
+    if chart.archive.is_some() || chart.is_oci() {
+        return Ok(());
+    }
+    add_repo(kubeconfig_path, &chart.repo_name, &chart.repo_url).await
+}
+
+/// Download the archive of a repository chart into the empty directory
+/// `dir` and return its path
+pub async fn pull(kubeconfig_path: &Path, chart: &Chart, dir: &Path) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+    CommandBuilder::new("helm")
+        .args(["pull", &chart.reference(), "--version", &chart.version])
+        .arg("--destination")
+        .arg(dir)
+        .kubeconfig(kubeconfig_path)
+        .context(format!(
+            "Failed to download Helm chart {}",
+            chart.reference()
+        ))
+        .run_silent()
+        .await?;
+
+    std::fs::read_dir(dir)
+        .context(format!("Failed to read {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .find(|path| path.extension().is_some_and(|ext| ext == "tgz"))
+        .context(format!("helm pull wrote no archive to {}", dir.display()))
+}
+
+/// Install or upgrade a Helm release
+///
+/// In template mode the chart is rendered locally and server-side applied
+/// instead, so no release state is kept in the cluster. The rendered manifest
+/// is saved to `manifests/<release>.yaml` next to the kubeconfig and compared
+/// with the previous render to report what changed; objects no longer
+/// rendered are pruned.
+pub async fn upgrade_install(kubeconfig_path: &Path, mode: HelmMode, chart: &Chart) -> Result<()> {
+    let (action, result) = match mode {
+        HelmMode::Release => (
+            "helm.upgrade_install",
+            install_release(kubeconfig_path, chart).await,
+        ),
+        HelmMode::Template => (
+            "helm.template_apply",
+            apply_rendered(kubeconfig_path, chart).await,
+        ),
+    };
+
+    let reference = chart.reference();
+    audit::record(
+        action,
+        &chart.release,
+        &[
+            ("chart", &reference),
+            ("version", &chart.version),
+            ("namespace", &chart.namespace),
+        ],
+        &result,
+    );
+    result?;
+
+    let record = ReleaseRecord {
+        chart: reference.clone(),
+        version: chart.version.clone(),
+        app_version: chart_app_version(kubeconfig_path, &chart.source(), &chart.version).await,
+        namespace: chart.namespace.clone(),
+        mode,
+        deployed: chrono::Utc::now(),
+    };
+    let output_dir = kubeconfig_path.parent().unwrap_or(Path::new("."));
+    ClusterState::record_release(output_dir, &chart.release, record)
+}
+
+/// Uninstall a chart installed by [`upgrade_install`]
+///
+/// In template mode the objects of the saved render are deleted, except
+/// Namespaces and CustomResourceDefinitions: deleting a CRD deletes every
+/// custom resource of it. Helm likewise keeps the CRDs of a chart's `crds`
+/// directory. The Gateway API CRDs are not part of any chart and always stay.
+pub async fn uninstall(
+    kubeconfig_path: &Path,
+    mode: HelmMode,
+    release: &str,
+    namespace: &str,
+) -> Result<()> {
+    info!("Uninstalling {}...", release);
+
+    let result = match mode {
+        HelmMode::Release => uninstall_release(kubeconfig_path, release, namespace).await,
+        HelmMode::Template => delete_rendered(kubeconfig_path, release, namespace).await,
+    };
+    audit::record(
+        "helm.uninstall",
+        release,
+        &[("namespace", namespace)],
+        &result,
+    );
+    result?;
+
+    let output_dir = kubeconfig_path.parent().unwrap_or(Path::new("."));
+    ClusterState::forget_release(output_dir, release)?;
+    info!("✓ Uninstalled {}", release);
+    Ok(())
+}
+
+async fn uninstall_release(kubeconfig_path: &Path, release: &str, namespace: &str) -> Result<()> {
+    let output = CommandBuilder::new("helm")
+        .args(["uninstall", release, "--namespace", namespace, "--wait"])
+        .kubeconfig(kubeconfig_path)
+        .context(format!("Failed to uninstall Helm release {}", release))
+        .output()
+        .await?;
+
+    if !output.success && !output.stderr.contains("not found") {
+        anyhow::bail!(
+            "Failed to uninstall Helm release {}: {}",
+            release,
+            output.stderr.trim()
+        );
+    }
+    Ok(())
+}
+
+async fn delete_rendered(kubeconfig_path: &Path, release: &str, namespace: &str) -> Result<()> {
+    let path = ResourceManager::saved_manifest_path(kubeconfig_path, release);
+    let manifest = match std::fs::read_to_string(&path) {
+        Ok(manifest) => manifest,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            info!("No saved render of {}, nothing to delete", release);
+            return Ok(());
+        }
+        Err(e) => return Err(e).context(format!("Failed to read {}", path.display())),
+    };
+
+    CommandBuilder::new("kubectl")
+        .args([
+            "delete",
+            "-n",
+            namespace,
+            "-f",
+            "-",
+            "--ignore-not-found",
+            "--wait",
+        ])
+        .kubeconfig(kubeconfig_path)
+        .stdin(deletable_objects(&manifest))
+        .context(format!("Failed to delete the objects of {}", release))
+        .run_silent()
+        .await?;
+
+    std::fs::remove_file(&path).context(format!("Failed to remove {}", path.display()))
+}
+
+/// Objects of a rendered chart that uninstalling deletes: everything but
+/// Namespaces and CustomResourceDefinitions
+fn deletable_objects(manifest: &str) -> String {
+    manifest_objects(manifest)
+        .into_values()
+        .filter(|doc| {
+            !matches!(
+                doc["kind"].as_str(),
+                Some("Namespace" | "CustomResourceDefinition")
+            )
+        })
+        .filter_map(|doc| serde_yaml::to_string(&doc).ok())
+        .collect::<Vec<_>>()
+        .join("---\n")
+}
+
+/// `appVersion` of a chart from the local repository index or archive,
+/// `None` if the chart does not declare one or cannot be read
+async fn chart_app_version(kubeconfig_path: &Path, chart: &str, version: &str) -> Option<String> {
+    let metadata = CommandBuilder::new("helm")
+        .args(["show", "chart", chart, "--version", version])
+        .kubeconfig(kubeconfig_path)
+        .run()
+        .await
+        .ok()?;
+    let metadata: serde_yaml::Value = serde_yaml::from_str(&metadata).ok()?;
+    match &metadata["appVersion"] {
+        serde_yaml::Value::String(app_version) => Some(app_version.clone()),
+        serde_yaml::Value::Number(app_version) => Some(app_version.to_string()),
+        _ => None,
+    }
+}
+
+/// A release as listed by `helm list -o json`
+#[derive(Debug, Clone, Deserialize)]
+pub struct ListedRelease {
+    pub name: String,
+    pub namespace: String,
+    /// `deployed`, `failed`, `pending-upgrade`, ...
+    pub status: String,
+    pub app_version: String,
+}
+
+/// Helm releases in all namespaces
+pub async fn list_releases(kubeconfig_path: &Path) -> Result<Vec<ListedRelease>> {
+    let output = CommandBuilder::new("helm")
+        .args(["list", "--all-namespaces", "--all", "-o", "json"])
+        .kubeconfig(kubeconfig_path)
+        .context("Failed to list Helm releases")
+        .run()
+        .await?;
+    serde_json::from_str(&output).context("Invalid output of helm list")
+}
+
+/// Values a release was installed with (`helm get values`), or `None` if
+/// there is no such release, e.g. in template mode
+pub async fn release_values(
+    kubeconfig_path: &Path,
+    release: &str,
+    namespace: &str,
+) -> Result<Option<serde_json::Value>> {
+    let output = CommandBuilder::new("helm")
+        .args([
+            "get",
+            "values",
+            release,
+            "--namespace",
+            namespace,
+            "-o",
+            "json",
+        ])
+        .kubeconfig(kubeconfig_path)
+        .context(format!("Failed to get values of Helm release {}", release))
+        .output()
+        .await?;
+
+    if !output.success {
+        if output.stderr.contains("not found") {
+            return Ok(None);
+        }
+        anyhow::bail!(
+            "Failed to get values of Helm release {}: {}",
+            release,
+            output.stderr
+        );
+    }
+    let values = serde_json::from_str(&output.stdout)
+        .context(format!("Invalid values of Helm release {}", release))?;
+    Ok(Some(values))
+}
+
+async fn install_release(kubeconfig_path: &Path, chart: &Chart) -> Result<()> {
+    let mut args = vec![
+        "upgrade".to_string(),
+        "--install".to_string(),
+        chart.release.clone(),
+        chart.source(),
+        "--version".to_string(),
+        chart.version.clone(),
+        "--namespace".to_string(),
+        chart.namespace.clone(),
+        "--create-namespace".to_string(),
+    ];
+    args.extend(set_flags(&chart.values));
+
+    CommandBuilder::new("helm")
+        .args(&args)
+        .kubeconfig(kubeconfig_path)
+        .context(format!("Failed to install Helm release {}", chart.release))
+        .run_streaming()
+        .await
+        .map(|_| ())
+}
+
+/// Render a chart locally, with its namespace in front since applied
+/// manifests cannot `--create-namespace`
+pub async fn template(kubeconfig_path: &Path, chart: &Chart) -> Result<String> {
+    let mut args = vec![
+        "template".to_string(),
+        chart.release.clone(),
+        chart.source(),
+        "--version".to_string(),
+        chart.version.clone(),
+        "--namespace".to_string(),
+        chart.namespace.clone(),
+        "--include-crds".to_string(),
+    ];
+    args.extend(set_flags(&chart.values));
+
+    let rendered = CommandBuilder::new("helm")
+        .args(&args)
+        .kubeconfig(kubeconfig_path)
+        .context(format!("Failed to render Helm chart {}", chart.reference()))
+        .run()
+        .await?;
+    Ok(format!(
+        "{}---\n{}",
+        namespace_manifest(&chart.namespace, chart.privileged),
+        rendered
+    ))
+}
+
+async fn apply_rendered(kubeconfig_path: &Path, chart: &Chart) -> Result<()> {
+    let manifest = template(kubeconfig_path, chart).await?;
+    let release = &chart.release;
+
+    let path = ResourceManager::saved_manifest_path(kubeconfig_path, release);
+    if let Ok(previous) = std::fs::read_to_string(&path) {
+        let changes = changes(&previous, &manifest);
+        if changes.is_empty() {
+            info!("{}: rendered manifest unchanged", release);
+        }
+        for change in &changes {
+            info!("{}: {}", release, change);
+        }
+    }
+
+    info!(
+        "Applying rendered {} chart ({})",
+        chart.reference(),
+        chart.version
+    );
+    ResourceManager::apply_managed(kubeconfig_path, &chart.namespace, release, &manifest).await
+}
+
+/// Wait for the workloads and Gateways of an installed chart to roll out
+///
+/// The objects are taken from the release manifest, or from the saved
+/// render in template mode.
+pub async fn wait_for_release(
+    kubeconfig_path: &Path,
+    mode: HelmMode,
+    release: &str,
+    namespace: &str,
+    timeout_secs: u64,
+) -> Result<()> {
+    let manifest = release_manifest(kubeconfig_path, mode, release, namespace).await?;
+    ResourceManager::wait_for_manifest(kubeconfig_path, namespace, &manifest, timeout_secs).await
+}
+
+/// Manifest of an installed chart: the release manifest, or the saved render
+/// in template mode
+pub async fn release_manifest(
+    kubeconfig_path: &Path,
+    mode: HelmMode,
+    release: &str,
+    namespace: &str,
+) -> Result<String> {
+    match mode {
+        HelmMode::Release => {
+            CommandBuilder::new("helm")
+                .args(["get", "manifest", release, "--namespace", namespace])
+                .kubeconfig(kubeconfig_path)
+                .context(format!(
+                    "Failed to get the manifest of Helm release {}",
+                    release
+                ))
+                .run()
+                .await
+        }
+        HelmMode::Template => {
+            let path = ResourceManager::saved_manifest_path(kubeconfig_path, release);
+            std::fs::read_to_string(&path).context(format!("Failed to read {}", path.display()))
+        }
+    }
+}
+
+fn set_flags(set_values: &[String]) -> Vec<String> {
+    set_values
+        .iter()
+        .flat_map(|value| ["--set".to_string(), value.clone()])
+        .collect()
+}
+
+/// Namespace object, since applied manifests cannot `--create-namespace`
+fn namespace_manifest(namespace: &str, privileged: bool) -> String {
+    let mut manifest = format!(
+        "apiVersion: v1\nkind: Namespace\nmetadata:\n  name: {}\n",
+        namespace
+    );
+    if privileged {
+        manifest.push_str("  labels:\n    pod-security.kubernetes.io/enforce: privileged\n");
+    }
+    manifest
+}
+
+/// `--set` values as the values file Helm merges them into
+///
+/// Follows Helm's rules: keys split at unescaped dots, `{a,b}` is a list,
+/// a backslash escapes the next character, and `true`, `false`, `null` and
+/// integers are typed.
+pub fn values_document(set_values: &[String]) -> Result<serde_yaml::Value> {
+    let mut root = serde_yaml::Mapping::new();
+    for set_value in set_values {
+        let [key, value] = &split_unescaped(set_value, '=', true)[..] else {
+            anyhow::bail!("Invalid Helm value {}", set_value);
+        };
+        let value = match value.strip_prefix('{').and_then(|v| v.strip_suffix('}')) {
+            Some(items) => serde_yaml::Value::Sequence(
+                split_unescaped(items, ',', false)
+                    .iter()
+                    .map(|item| typed_value(&unescape(item)))
+                    .collect(),
+            ),
+            None => typed_value(&unescape(value)),
+        };
+
+        let path: Vec<String> = split_unescaped(key, '.', false)
+            .iter()
+            .map(|segment| unescape(segment))
+            .collect();
+        let (last, parents) = path.split_last().expect("split yields a segment");
+        let mut current = &mut root;
+        for segment in parents {
+            let entry = current
+                .entry(segment.as_str().into())
+                .or_insert_with(|| serde_yaml::Mapping::new().into());
+            if !entry.is_mapping() {
+                *entry = serde_yaml::Mapping::new().into();
+            }
+            current = entry.as_mapping_mut().expect("just made a mapping");
+        }
+        current.insert(last.as_str().into(), value);
+    }
+    Ok(root.into())
+}
+
+/// Split at unescaped `separator`s, keeping escapes; with `once`, only at
+/// the first one
+fn split_unescaped(value: &str, separator: char, once: bool) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == separator && !(once && parts.len() > 1) {
+            parts.push(String::new());
+            continue;
+        }
+        let part = parts.last_mut().expect("starts with a part");
+        part.push(c);
+        if c == '\\' {
+            part.extend(chars.next());
+        }
+    }
+    parts
+}
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            c => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+fn typed_value(value: &str) -> serde_yaml::Value {
+    match value {
+        "true" => true.into(),
+        "false" => false.into(),
+        "null" => serde_yaml::Value::Null,
+        // Helm keeps numbers with leading zeros as strings
+        _ if value.len() > 1 && value.starts_with('0') => value.into(),
+        _ => match value.parse::<i64>() {
+            Ok(number) => number.into(),
+            Err(_) => value.into(),
+        },
+    }
+}
+
+/// Objects added (`+`), changed (`~`) and removed (`-`) between two renders
+fn changes(previous: &str, current: &str) -> Vec<String> {
+    let previous = manifest_objects(previous);
+    let current = manifest_objects(current);
+
+    let mut changes = Vec::new();
+    for (key, object) in &current {
+        match previous.get(key) {
+            None => changes.push(format!("+ {}", key)),
+            Some(old) if old != object => changes.push(format!("~ {}", key)),
+            Some(_) => {}
+        }
+    }
+    for key in previous.keys().filter(|key| !current.contains_key(*key)) {
+        changes.push(format!("- {}", key));
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_values_document() {
+        let values = values_document(&[
+            "ipam.mode=kubernetes".to_string(),
+            "operator.replicas=2".to_string(),
+            "cgroup.autoMount.enabled=false".to_string(),
+            "MTU=1370".to_string(),
+            "nodeSelector.topology\\.kubernetes\\.io/zone=eu-central".to_string(),
+            "securityContext.capabilities.cleanCiliumState={NET_ADMIN,SYS_ADMIN}".to_string(),
+            "hubble.metrics.enabled={dns,httpV2:labelsContext=source_ip\\,source_namespace}"
+                .to_string(),
+            "image.tag=0123".to_string(),
+        ])
+        .unwrap();
+
+        let expected: serde_yaml::Value = serde_yaml::from_str(
+            r#"
+ipam:
+  mode: kubernetes
+operator:
+  replicas: 2
+cgroup:
+  autoMount:
+    enabled: false
+MTU: 1370
+nodeSelector:
+  topology.kubernetes.io/zone: eu-central
+securityContext:
+  capabilities:
+    cleanCiliumState: [NET_ADMIN, SYS_ADMIN]
+hubble:
+  metrics:
+    enabled: [dns, "httpV2:labelsContext=source_ip,source_namespace"]
+image:
+  tag: "0123"
+"#,
+        )
+        .unwrap();
+        assert_eq!(values, expected);
+        assert!(values_document(&["no-value".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_deletable_objects() {
+        let manifest = "apiVersion: v1\nkind: Namespace\nmetadata:\n  name: kube-system\n---\n\
+                        apiVersion: apiextensions.k8s.io/v1\nkind: CustomResourceDefinition\nmetadata:\n  name: volumes.longhorn.io\n---\n\
+                        apiVersion: apps/v1\nkind: DaemonSet\nmetadata:\n  name: longhorn-manager\n";
+
+        let deletable = manifest_objects(&deletable_objects(manifest));
+        assert_eq!(
+            deletable.keys().collect::<Vec<_>>(),
+            ["daemonset/longhorn-manager"]
+        );
+    }
+
+    #[test]
+    fn test_rendered_changes() {
+        let previous = "kind: ConfigMap\nmetadata:\n  name: config\ndata:\n  a: \"1\"\n---\n\
+                        kind: Service\nmetadata:\n  name: old\n  namespace: kube-system\n---\n\
+                        kind: Deployment\nmetadata:\n  name: same\n";
+        let current = "kind: ConfigMap\nmetadata:\n  name: config\ndata:\n  a: \"2\"\n---\n\
+                       kind: Deployment\nmetadata:\n  name: same\n---\n\
+                       kind: DaemonSet\nmetadata:\n  name: new\n";
+
+        assert_eq!(
+            changes(previous, current),
+            vec![
+                "~ configmap/config",
+                "+ daemonset/new",
+                "- service/kube-system/old"
+            ]
+        );
+        assert!(changes(current, current).is_empty());
+    }
+}
@@ -1,3 +1,11 @@
 /// Shared utilities for command execution and common patterns
 pub mod command;
+pub mod completion;
+pub mod helm;
+pub mod platform;
 pub mod polling;
+pub mod progress;
+pub mod prompt;
+pub mod releases;
+pub mod telemetry;
+pub mod tools;
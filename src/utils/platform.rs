@@ -0,0 +1,133 @@
+/// Differences between Linux, macOS and Windows workstations
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// Device that discards everything written to it
+#[cfg(not(windows))]
+pub const NULL_DEVICE: &str = "/dev/null";
+#[cfg(windows)]
+pub const NULL_DEVICE: &str = "NUL";
+
+/// Home directory of the current user: `HOME`, or `USERPROFILE` on Windows
+pub fn home_dir() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").filter(|home| !home.is_empty());
+    #[cfg(windows)]
+    let home = home.or_else(|| std::env::var_os("USERPROFILE").filter(|home| !home.is_empty()));
+    home.map(PathBuf::from)
+}
+
+/// Per-user cache directory: `XDG_CACHE_HOME` if set, otherwise
+/// `~/Library/Caches` on macOS, `%LOCALAPPDATA%` on Windows and `~/.cache`
+/// elsewhere
+pub fn cache_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    if cfg!(windows) {
+        return std::env::var_os("LOCALAPPDATA")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from);
+    }
+    let home = home_dir()?;
+    Some(if cfg!(target_os = "macos") {
+        home.join("Library").join("Caches")
+    } else {
+        home.join(".cache")
+    })
+}
+
+/// File name of an executable, `kubectl.exe` on Windows
+pub fn executable_name(name: &str) -> String {
+    format!("{}{}", name, std::env::consts::EXE_SUFFIX)
+}
+
+/// Write a file only the current user can read, such as a private key
+pub fn write_private(path: &Path, data: impl AsRef<[u8]>) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+
+        // Created with the final mode, so the contents are never readable by
+        // others, even briefly
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)
+            .context(format!("Failed to write {}", path.display()))?;
+        file.write_all(data.as_ref())
+            .context(format!("Failed to write {}", path.display()))?;
+    }
+    #[cfg(not(unix))]
+    std::fs::write(path, data).context(format!("Failed to write {}", path.display()))?;
+
+    // An existing file keeps its permissions when truncated
+    restrict_to_owner(path)
+}
+
+/// Make an existing file readable and writable by the current user only
+///
+/// Unix gets mode 0600. On Windows the inherited ACL entries are replaced
+/// with full control for the current user via `icacls`, which is also what
+/// OpenSSH requires before it uses a private key.
+pub fn restrict_to_owner(path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).context(format!(
+            "Failed to set the permissions of {}",
+            path.display()
+        ))?;
+    }
+    #[cfg(windows)]
+    {
+        let user = std::env::var("USERNAME").context("USERNAME is not set")?;
+        let output = std::process::Command::new("icacls")
+            .arg(path)
+            .args(["/inheritance:r", "/grant:r"])
+            .arg(format!("{}:F", user))
+            .output()
+            .context("Failed to run icacls")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to restrict access to {}: {}",
+                path.display(),
+                String::from_utf8_lossy(&output.stdout).trim()
+            );
+        }
+    }
+    #[cfg(not(any(unix, windows)))]
+    let _ = path;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_private() {
+        let dir = std::env::temp_dir().join(format!("oxide-platform-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("id_ed25519");
+        std::fs::write(&path, "old").unwrap();
+
+        write_private(&path, "key").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "key");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_executable_name() {
+        let name = executable_name("kubectl");
+        assert!(name == "kubectl" || name == "kubectl.exe");
+    }
+}
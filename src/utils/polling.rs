@@ -4,6 +4,8 @@ use std::future::Future;
 use std::time::{Duration, Instant};
 use tracing::info;
 
+use crate::exit::Failure;
+
 /// Configuration for polling operations
 pub struct PollingConfig {
     pub timeout: Duration,
@@ -53,11 +55,11 @@ impl PollingConfig {
 
             // Check timeout
             if start.elapsed() > self.timeout {
-                anyhow::bail!(
+                anyhow::bail!(Failure::timeout(format!(
                     "Timeout after {} seconds: {}",
                     self.timeout.as_secs(),
                     self.description
-                );
+                )));
             }
 
             // Wait before next attempt
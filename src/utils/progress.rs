@@ -0,0 +1,189 @@
+/// Terminal progress display for long-running cluster operations
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::io::Write;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+static MULTI: OnceLock<MultiProgress> = OnceLock::new();
+
+/// Enable the progress display for the rest of the process
+///
+/// Returns false (and leaves progress disabled) when stderr is not a terminal.
+pub fn init(enabled: bool) -> bool {
+    use std::io::IsTerminal;
+
+    if !enabled || !std::io::stderr().is_terminal() {
+        return false;
+    }
+
+    MULTI.set(MultiProgress::new()).is_ok()
+}
+
+/// Whether the progress display is active
+pub fn is_enabled() -> bool {
+    MULTI.get().is_some()
+}
+
+/// Start a named phase, shown as a spinner until finished
+///
+/// The phase name is also recorded on the current span's `phase` field so
+/// that structured logs can be grouped by phase, and a `phase` span covering
+/// the phase's lifetime is opened for trace export.
+pub fn phase(name: &str) -> Phase {
+    tracing::Span::current().record("phase", name);
+    let span = tracing::info_span!("phase", otel.name = name, phase = name);
+
+    let bar = add(ProgressBar::new_spinner(), spinner_style());
+    bar.set_prefix(name.to_string());
+    bar.enable_steady_tick(Duration::from_millis(120));
+    Phase { bar, _span: span }
+}
+
+/// Start a counted phase (e.g. servers created out of total)
+pub fn counter(name: &str, total: u64) -> ProgressBar {
+    let bar = add(ProgressBar::new(total), counter_style());
+    bar.set_prefix(name.to_string());
+    bar
+}
+
+/// Add a sub-status line for a single resource (e.g. one server)
+pub fn item(name: &str) -> ProgressBar {
+    let bar = add(ProgressBar::new_spinner(), item_style());
+    bar.set_prefix(name.to_string());
+    bar.enable_steady_tick(Duration::from_millis(120));
+    bar
+}
+
+/// Mark a sub-status line as done, keeping it visible with a final message
+pub fn finish_item(bar: &ProgressBar, message: &str) {
+    bar.set_style(item_done_style());
+    bar.finish_with_message(message.to_string());
+}
+
+/// A running phase; abandoned (left in place) if dropped unfinished
+///
+/// The span is never entered (phases span many awaits); it only marks the
+/// phase's start and end, closing when the phase is dropped.
+pub struct Phase {
+    bar: ProgressBar,
+    _span: tracing::Span,
+}
+
+impl Phase {
+    /// Update the phase's status text
+    pub fn set_message(&self, message: impl Into<String>) {
+        self.bar.set_message(message.into());
+    }
+
+    /// Mark the phase as completed
+    pub fn finish(self) {
+        self.bar.set_style(done_style());
+        self.bar.finish_with_message("done");
+    }
+}
+
+impl Drop for Phase {
+    fn drop(&mut self) {
+        if !self.bar.is_finished() {
+            self.bar.abandon_with_message("failed");
+        }
+    }
+}
+
+fn add(bar: ProgressBar, style: ProgressStyle) -> ProgressBar {
+    match MULTI.get() {
+        Some(multi) => multi.add(bar.with_style(style)),
+        None => {
+            bar.set_draw_target(ProgressDrawTarget::hidden());
+            bar
+        }
+    }
+}
+
+fn spinner_style() -> ProgressStyle {
+    ProgressStyle::with_template("{spinner:.cyan} {prefix:.bold} {wide_msg}")
+        .expect("valid template")
+}
+
+fn done_style() -> ProgressStyle {
+    ProgressStyle::with_template("{prefix:.bold.green} ✓").expect("valid template")
+}
+
+fn counter_style() -> ProgressStyle {
+    ProgressStyle::with_template("  {prefix:.bold} [{bar:30.cyan/blue}] {pos}/{len} {wide_msg}")
+        .expect("valid template")
+        .progress_chars("=> ")
+}
+
+fn item_style() -> ProgressStyle {
+    ProgressStyle::with_template("    {spinner:.cyan} {prefix} {wide_msg:.dim}")
+        .expect("valid template")
+}
+
+fn item_done_style() -> ProgressStyle {
+    ProgressStyle::with_template("    ✓ {prefix} {wide_msg:.dim}").expect("valid template")
+}
+
+/// tracing writer (stdout) that prints log lines above the progress display
+#[derive(Clone, Copy, Default)]
+pub struct LogWriter;
+
+impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for LogWriter {
+    type Writer = LineWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        LineWriter { buf: Vec::new() }
+    }
+}
+
+/// Buffers one log event and emits it while the progress bars are suspended
+pub struct LineWriter {
+    buf: Vec<u8>,
+}
+
+impl Write for LineWriter {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for LineWriter {
+    fn drop(&mut self) {
+        if self.buf.is_empty() {
+            return;
+        }
+
+        let write = || {
+            let _ = std::io::stdout().write_all(&self.buf);
+        };
+        match MULTI.get() {
+            Some(multi) => multi.suspend(write),
+            None => write(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_progress_is_hidden() {
+        // Tests never run with progress initialised
+        assert!(!is_enabled());
+
+        let phase = phase("Servers");
+        phase.set_message("creating");
+        phase.finish();
+
+        let bar = counter("Servers", 3);
+        bar.inc(1);
+        assert_eq!(bar.position(), 1);
+        assert!(bar.is_hidden());
+    }
+}
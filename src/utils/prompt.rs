@@ -0,0 +1,20 @@
+/// Whether oxide may ask questions on the terminal
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static NON_INTERACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Forbid prompts for the rest of the process
+pub fn disable() {
+    NON_INTERACTIVE.store(true, Ordering::Relaxed);
+}
+
+/// Whether prompts were forbidden with `--non-interactive`
+pub fn is_disabled() -> bool {
+    NON_INTERACTIVE.load(Ordering::Relaxed)
+}
+
+/// Whether a prompt can be shown
+pub fn is_available() -> bool {
+    !is_disabled() && std::io::stdin().is_terminal()
+}
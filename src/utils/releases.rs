@@ -0,0 +1,83 @@
+/// Latest upstream release lookups via the GitHub API
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::time::Duration;
+
+const GITHUB_API_BASE: &str = "https://api.github.com";
+
+/// Talos Linux releases
+pub const TALOS_REPO: &str = "siderolabs/talos";
+/// Kubernetes releases
+pub const KUBERNETES_REPO: &str = "kubernetes/kubernetes";
+/// Cilium releases
+pub const CILIUM_REPO: &str = "cilium/cilium";
+
+/// Tag of the latest (non-prerelease) release of `repo`, e.g. `v1.11.2`
+pub async fn latest_release(repo: &str) -> Result<String> {
+    #[derive(Deserialize)]
+    struct Release {
+        tag_name: String,
+    }
+
+    let release: Release = github_client()?
+        .get(format!(
+            "{}/repos/{}/releases/latest",
+            GITHUB_API_BASE, repo
+        ))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .context(format!("Failed to fetch the latest {} release", repo))?
+        .json()
+        .await?;
+
+    Ok(release.tag_name)
+}
+
+/// Tags of the most recent stable releases of `repo`, newest first
+pub async fn list_releases(repo: &str) -> Result<Vec<String>> {
+    #[derive(Deserialize)]
+    struct Release {
+        tag_name: String,
+        prerelease: bool,
+        draft: bool,
+    }
+
+    let releases: Vec<Release> = github_client()?
+        .get(format!(
+            "{}/repos/{}/releases?per_page=100",
+            GITHUB_API_BASE, repo
+        ))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .context(format!("Failed to fetch {} releases", repo))?
+        .json()
+        .await?;
+
+    Ok(releases
+        .into_iter()
+        .filter(|r| !r.prerelease && !r.draft)
+        .map(|r| r.tag_name)
+        .collect())
+}
+
+/// GitHub API client, authenticated with `GITHUB_TOKEN` if set to avoid the
+/// low anonymous rate limit
+fn github_client() -> Result<reqwest::Client> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    if let Ok(token) = std::env::var("GITHUB_TOKEN") {
+        headers.insert(
+            reqwest::header::AUTHORIZATION,
+            reqwest::header::HeaderValue::from_str(&format!("Bearer {}", token))
+                .context("Invalid GITHUB_TOKEN")?,
+        );
+    }
+
+    // GitHub rejects API requests without a User-Agent
+    Ok(reqwest::Client::builder()
+        .user_agent(concat!("oxide/", env!("CARGO_PKG_VERSION")))
+        .default_headers(headers)
+        .timeout(Duration::from_secs(10))
+        .build()?)
+}
@@ -0,0 +1,70 @@
+/// Optional OpenTelemetry (OTLP) export of tracing spans
+use anyhow::{Context, Result};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_sdk::trace::{Tracer, TracerProvider};
+use opentelemetry_sdk::{runtime, Resource};
+
+const ENDPOINT_VARS: &[&str] = &[
+    "OTEL_EXPORTER_OTLP_ENDPOINT",
+    "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+];
+
+/// Handle to the OTLP pipeline; call `shutdown` before exiting to flush spans
+pub struct Telemetry {
+    provider: TracerProvider,
+}
+
+impl Telemetry {
+    /// Set up the OTLP exporter if an endpoint is configured
+    pub fn from_env() -> Result<Option<(Self, Tracer)>> {
+        if !is_configured(|var| std::env::var(var).ok()) {
+            return Ok(None);
+        }
+
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .build()
+            .context("Failed to create OTLP span exporter")?;
+
+        let provider = TracerProvider::builder()
+            .with_batch_exporter(exporter, runtime::Tokio)
+            .with_resource(Resource::new(vec![
+                KeyValue::new("service.name", "oxide"),
+                KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
+            ]))
+            .build();
+        let tracer = provider.tracer("oxide");
+
+        Ok(Some((Self { provider }, tracer)))
+    }
+
+    /// Flush pending spans and stop the exporter
+    pub fn shutdown(self) {
+        if let Err(e) = self.provider.shutdown() {
+            eprintln!("Failed to flush OpenTelemetry spans: {}", e);
+        }
+    }
+}
+
+/// Whether any OTLP endpoint variable is set to a non-empty value
+fn is_configured(lookup: impl Fn(&str) -> Option<String>) -> bool {
+    ENDPOINT_VARS
+        .iter()
+        .any(|var| lookup(var).is_some_and(|v| !v.trim().is_empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_is_opt_in() {
+        assert!(!is_configured(|_| None));
+        assert!(!is_configured(|_| Some(" ".to_string())));
+        assert!(is_configured(|var| {
+            (var == "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT")
+                .then(|| "http://localhost:4318/v1/traces".to_string())
+        }));
+    }
+}
@@ -0,0 +1,449 @@
+/// Version-matched talosctl, kubectl and helm binaries
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::config::ClusterConfig;
+use crate::utils::command::CommandBuilder;
+use crate::utils::platform;
+
+/// Helm release installed alongside the cluster-specific tools
+pub const HELM_VERSION: &str = "v3.16.3";
+
+/// Overrides the tools cache directory
+const TOOLS_DIR_ENV: &str = "OXIDE_TOOLS_DIR";
+
+/// A CLI tool oxide shells out to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Talosctl,
+    Kubectl,
+    Helm,
+}
+
+impl Tool {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tool::Talosctl => "talosctl",
+            Tool::Kubectl => "kubectl",
+            Tool::Helm => "helm",
+        }
+    }
+
+    /// Release artifact to download and the checksum file published for it
+    fn artifact(&self, version: &str, os: &str, arch: &str) -> (String, String) {
+        let exe = if os == "windows" { ".exe" } else { "" };
+        match self {
+            Tool::Talosctl => {
+                let base = format!(
+                    "https://github.com/siderolabs/talos/releases/download/{}",
+                    version
+                );
+                (
+                    format!("{}/talosctl-{}-{}{}", base, os, arch, exe),
+                    format!("{}/sha256sum.txt", base),
+                )
+            }
+            Tool::Kubectl => {
+                let url = format!(
+                    "https://dl.k8s.io/release/{}/bin/{}/{}/kubectl{}",
+                    version, os, arch, exe
+                );
+                (url.clone(), format!("{}.sha256", url))
+            }
+            Tool::Helm => {
+                let url = format!(
+                    "https://get.helm.sh/helm-{}-{}-{}.tar.gz",
+                    version, os, arch
+                );
+                (url.clone(), format!("{}.sha256sum", url))
+            }
+        }
+    }
+}
+
+/// Tool versions matching a cluster config
+pub fn wanted(config: &ClusterConfig) -> [(Tool, String); 3] {
+    [
+        (Tool::Talosctl, with_v_prefix(&config.talos.version)),
+        (
+            Tool::Kubectl,
+            with_v_prefix(&config.talos.kubernetes_version),
+        ),
+        (Tool::Helm, HELM_VERSION.to_string()),
+    ]
+}
+
+/// Download any missing tools for `config` into the cache
+pub async fn install(config: &ClusterConfig) -> Result<()> {
+    let (os, arch) = platform()?;
+    let client = reqwest::Client::builder()
+        .user_agent(concat!("oxide/", env!("CARGO_PKG_VERSION")))
+        .timeout(Duration::from_secs(600))
+        .build()?;
+
+    for (tool, version) in wanted(config) {
+        let path = binary_path(tool, &version)?;
+        if path.exists() {
+            info!("✓ {} {} already installed", tool.name(), version);
+            continue;
+        }
+
+        // Windows releases of helm are zip archives
+        if tool == Tool::Helm && os == "windows" {
+            warn!(
+                "⚠️  Skipping helm, install helm {} yourself (e.g. `winget install Helm.Helm`)",
+                version
+            );
+            continue;
+        }
+
+        info!("Downloading {} {}", tool.name(), version);
+        let (url, checksum_url) = tool.artifact(&version, os, arch);
+        let data = download(&client, &url).await?;
+
+        let file_name = url.rsplit('/').next().unwrap_or_default();
+        let checksums = String::from_utf8(download(&client, &checksum_url).await?)
+            .context(format!("Invalid checksum file at {}", checksum_url))?;
+        let expected = parse_checksum(&checksums, file_name)
+            .context(format!("No checksum for {} in {}", file_name, checksum_url))?;
+        let actual = hex(&Sha256::digest(&data));
+        if !actual.eq_ignore_ascii_case(&expected) {
+            anyhow::bail!(
+                "Checksum mismatch for {}: expected {}, got {}",
+                url,
+                expected,
+                actual
+            );
+        }
+
+        let binary = match tool {
+            Tool::Helm => extract_helm(&data)?,
+            _ => data,
+        };
+        write_executable(&path, &binary)?;
+        info!(
+            "✓ Installed {} {} to {}",
+            tool.name(),
+            version,
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Put installed tools for `config` in front of `PATH`
+///
+/// Tools that are not installed are left to the system `PATH`.
+pub fn activate(config: &ClusterConfig) {
+    let current = std::env::var_os("PATH").unwrap_or_default();
+    let mut paths: Vec<PathBuf> = std::env::split_paths(&current).collect();
+
+    for (tool, version) in wanted(config).into_iter().rev() {
+        let Ok(path) = binary_path(tool, &version) else {
+            continue;
+        };
+        let Some(dir) = path.parent().filter(|_| path.exists()) else {
+            continue;
+        };
+        if !paths.iter().any(|p| p == dir) {
+            tracing::debug!("Using {} from {}", tool.name(), dir.display());
+            paths.insert(0, dir.to_path_buf());
+        }
+    }
+
+    if let Ok(joined) = std::env::join_paths(paths) {
+        std::env::set_var("PATH", joined);
+    }
+}
+
+/// Check that the local talosctl and kubectl can manage the given versions
+///
+/// talosctl must be the cluster's Talos minor version or one newer (older
+/// clients generate machine configs the cluster does not understand); kubectl
+/// follows the Kubernetes skew policy of one minor version either way.
+pub async fn check_versions(talos_version: &str, kubernetes_version: &str) -> Result<()> {
+    let talosctl = CommandBuilder::new("talosctl")
+        .args(["version", "--client"])
+        .context("Failed to run talosctl")
+        .run()
+        .await?;
+    let talosctl =
+        parse_talosctl_version(&talosctl).context("Could not determine the talosctl version")?;
+
+    let kubectl = CommandBuilder::new("kubectl")
+        .args(["version", "--client", "-o", "json"])
+        .context("Failed to run kubectl")
+        .run()
+        .await?;
+    let kubectl =
+        parse_kubectl_version(&kubectl).context("Could not determine the kubectl version")?;
+
+    let problems: Vec<String> = [
+        skew_problem(Tool::Talosctl, &talosctl, "Talos", talos_version, 0),
+        skew_problem(Tool::Kubectl, &kubectl, "Kubernetes", kubernetes_version, 1),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    if !problems.is_empty() {
+        anyhow::bail!(
+            "{}\nRun `oxide tools install` to download matching versions",
+            problems.join("\n")
+        );
+    }
+
+    info!(
+        "✓ talosctl {} and kubectl {} match the cluster versions",
+        talosctl, kubectl
+    );
+    Ok(())
+}
+
+/// Describe a client/cluster version mismatch, if any
+///
+/// The client may be up to one minor version newer, and `behind` versions older.
+fn skew_problem(
+    tool: Tool,
+    client: &str,
+    product: &str,
+    cluster: &str,
+    behind: u64,
+) -> Option<String> {
+    let (Some((client_major, client_minor)), Some((major, minor))) =
+        (minor_version(client), minor_version(cluster))
+    else {
+        return Some(format!(
+            "Cannot compare {} {} with {} {}",
+            tool.name(),
+            client,
+            product,
+            cluster
+        ));
+    };
+
+    let oldest = minor.saturating_sub(behind);
+    if client_major == major && (oldest..=minor + 1).contains(&client_minor) {
+        return None;
+    }
+
+    Some(format!(
+        "{} {} is not compatible with {} {} (supported: v{}.{}.x to v{}.{}.x)",
+        tool.name(),
+        client,
+        product,
+        cluster,
+        major,
+        oldest,
+        major,
+        minor + 1
+    ))
+}
+
+/// Major and minor version of `v1.30.2`, `1.30.2` or `v1.11.0-beta.0`
+fn minor_version(version: &str) -> Option<(u64, u64)> {
+    let mut parts = version.trim().trim_start_matches('v').split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+/// Client tag from `talosctl version --client` (`Tag: v1.11.2`)
+fn parse_talosctl_version(output: &str) -> Option<String> {
+    output
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Tag:"))
+        .map(|tag| tag.trim().to_string())
+}
+
+/// Client version from `kubectl version --client -o json`
+fn parse_kubectl_version(output: &str) -> Option<String> {
+    let json: serde_json::Value = serde_json::from_str(output).ok()?;
+    json["clientVersion"]["gitVersion"]
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Location of an installed tool: `<cache>/<tool>/<version>/<tool>`
+pub fn binary_path(tool: Tool, version: &str) -> Result<PathBuf> {
+    Ok(cache_dir()?
+        .join(tool.name())
+        .join(version)
+        .join(platform::executable_name(tool.name())))
+}
+
+fn cache_dir() -> Result<PathBuf> {
+    if let Some(dir) = std::env::var_os(TOOLS_DIR_ENV) {
+        return Ok(PathBuf::from(dir));
+    }
+    let base = platform::cache_dir().context(format!(
+        "Cannot determine the tools cache directory, set {}",
+        TOOLS_DIR_ENV
+    ))?;
+    Ok(base.join("oxide").join("tools"))
+}
+
+/// OS and architecture as used in release artifact names
+fn platform() -> Result<(&'static str, &'static str)> {
+    let os = match std::env::consts::OS {
+        "linux" => "linux",
+        "macos" => "darwin",
+        "windows" => "windows",
+        other => anyhow::bail!("Tool installation is not supported on {}", other),
+    };
+    let arch = match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        other => anyhow::bail!("Tool installation is not supported on {}", other),
+    };
+    Ok((os, arch))
+}
+
+async fn download(client: &reqwest::Client, url: &str) -> Result<Vec<u8>> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .context(format!("Failed to download {}", url))?;
+    Ok(response.bytes().await?.to_vec())
+}
+
+/// Find the checksum for `file_name` in a `sha256sum`-style listing
+///
+/// Files containing a bare checksum (as published for kubectl) match any name.
+fn parse_checksum(content: &str, file_name: &str) -> Option<String> {
+    content.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        let checksum = fields.next()?;
+        match fields.next() {
+            None => Some(checksum.to_string()),
+            Some(name) if name.trim_start_matches('*') == file_name => Some(checksum.to_string()),
+            Some(_) => None,
+        }
+    })
+}
+
+/// Pull the `helm` binary out of a release tarball
+fn extract_helm(tarball: &[u8]) -> Result<Vec<u8>> {
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(tarball));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()?.file_name() == Some("helm".as_ref()) {
+            let mut binary = Vec::new();
+            entry.read_to_end(&mut binary)?;
+            return Ok(binary);
+        }
+    }
+    anyhow::bail!("helm binary not found in release archive")
+}
+
+fn write_executable(path: &Path, data: &[u8]) -> Result<()> {
+    let dir = path.parent().context("Invalid tool path")?;
+    std::fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+
+    // Write next to the target and rename, so an interrupted download never
+    // leaves a truncated binary that looks installed
+    let partial = path.with_extension("partial");
+    std::fs::write(&partial, data)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&partial, std::fs::Permissions::from_mode(0o755))?;
+    }
+    std::fs::rename(&partial, path)?;
+    Ok(())
+}
+
+fn with_v_prefix(version: &str) -> String {
+    format!("v{}", version.trim_start_matches('v'))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_checksum() {
+        let listing = "aaa  talosctl-darwin-arm64\nbbb  talosctl-linux-amd64\n";
+        assert_eq!(
+            parse_checksum(listing, "talosctl-linux-amd64").as_deref(),
+            Some("bbb")
+        );
+        assert_eq!(parse_checksum(listing, "talosctl-windows-amd64.exe"), None);
+
+        assert_eq!(parse_checksum("ccc\n", "kubectl").as_deref(), Some("ccc"));
+        assert_eq!(
+            parse_checksum(
+                "ddd *helm-v3.16.3-linux-amd64.tar.gz",
+                "helm-v3.16.3-linux-amd64.tar.gz"
+            )
+            .as_deref(),
+            Some("ddd")
+        );
+    }
+
+    #[test]
+    fn test_version_skew() {
+        assert_eq!(
+            skew_problem(Tool::Talosctl, "v1.11.0", "Talos", "v1.11.2", 0),
+            None
+        );
+        assert_eq!(
+            skew_problem(Tool::Talosctl, "v1.12.1", "Talos", "v1.11.2", 0),
+            None
+        );
+        assert!(
+            skew_problem(Tool::Talosctl, "v1.10.5", "Talos", "v1.11.2", 0)
+                .unwrap()
+                .contains("supported: v1.11.x to v1.12.x")
+        );
+
+        assert_eq!(
+            skew_problem(Tool::Kubectl, "v1.29.3", "Kubernetes", "1.30.0", 1),
+            None
+        );
+        assert!(skew_problem(Tool::Kubectl, "v1.32.0", "Kubernetes", "1.30.0", 1).is_some());
+        assert!(skew_problem(Tool::Kubectl, "v2.30.0", "Kubernetes", "1.30.0", 1).is_some());
+    }
+
+    #[test]
+    fn test_parse_client_versions() {
+        let talosctl = "Client:\n\tTag:         v1.11.2\n\tSHA:         abc123\n";
+        assert_eq!(parse_talosctl_version(talosctl).as_deref(), Some("v1.11.2"));
+
+        let kubectl =
+            r#"{"clientVersion": {"major": "1", "minor": "30", "gitVersion": "v1.30.4"}}"#;
+        assert_eq!(parse_kubectl_version(kubectl).as_deref(), Some("v1.30.4"));
+    }
+
+    #[test]
+    fn test_windows_artifacts() {
+        let (url, _) = Tool::Talosctl.artifact("v1.11.2", "windows", "amd64");
+        assert!(url.ends_with("/v1.11.2/talosctl-windows-amd64.exe"));
+        let (url, checksum) = Tool::Kubectl.artifact("v1.34.1", "windows", "amd64");
+        assert!(url.ends_with("/bin/windows/amd64/kubectl.exe"));
+        assert!(checksum.ends_with("kubectl.exe.sha256"));
+        let (url, _) = Tool::Kubectl.artifact("v1.34.1", "linux", "amd64");
+        assert!(url.ends_with("/bin/linux/amd64/kubectl"));
+    }
+
+    #[test]
+    fn test_wanted_versions() {
+        let mut config = ClusterConfig::example();
+        config.talos.kubernetes_version = "1.30.0".to_string();
+
+        let wanted = wanted(&config);
+        assert_eq!(wanted[1], (Tool::Kubectl, "v1.30.0".to_string()));
+        assert!(wanted[0].1.starts_with('v'));
+    }
+}